@@ -0,0 +1,114 @@
+//! Ring buffer and per-probe aggregation costs at fleet scale
+//!
+//! Compares the allocation-free paths against the previous approach of
+//! collecting the window into a `Vec` and recounting it on every probe.
+
+use std::hint::black_box;
+
+use cloud_ping::models::utils::percentile;
+use cloud_ping::models::{AggregatorState, ProbeRecord, RingBuffer};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const ENDPOINTS: usize = 10_000;
+const SHORT_WINDOW: usize = 60;
+const LONG_WINDOW: usize = 720;
+
+fn record(endpoint: &str, i: usize) -> ProbeRecord {
+    if i % 17 == 0 {
+        ProbeRecord::failure(endpoint.to_string(), None)
+    } else {
+        ProbeRecord::success(endpoint.to_string(), 20.0 + (i % 13) as f64)
+    }
+}
+
+/// Full windows for every endpoint, as after an hour of monitoring
+fn warm_states() -> Vec<AggregatorState> {
+    (0..ENDPOINTS)
+        .map(|n| {
+            let id = format!("endpoint-{n}");
+            let mut state = AggregatorState::new(id.clone(), SHORT_WINDOW, LONG_WINDOW);
+            for i in 0..LONG_WINDOW {
+                state.add_record(record(&id, i), 0.0625);
+            }
+            state
+        })
+        .collect()
+}
+
+/// Per-probe work as done before: recount both windows, collect and sort RTTs per percentile
+fn legacy_update(short: &RingBuffer<ProbeRecord>, long: &RingBuffer<ProbeRecord>) -> f64 {
+    let recv_short = short.iter().filter(|r| r.success).count();
+    let recv_long = long.iter().filter(|r| r.success).count();
+    let rtts: Vec<f64> = short.iter().filter_map(|r| r.rtt_ms).collect();
+    let p50 = percentile(&rtts, 50.0);
+    let p90 = percentile(&rtts, 90.0);
+    let p99 = percentile(&rtts, 99.0);
+    p50 + p90 + p99 + (recv_short + recv_long) as f64
+}
+
+fn bench_add_record(c: &mut Criterion) {
+    let mut group = c.benchmark_group("probe_at_10k_endpoints");
+    group.sample_size(10);
+
+    // Windows stay full, so every iteration does the same steady-state work
+    let mut states = warm_states();
+    group.bench_function("add_record", |b| {
+        b.iter(|| {
+            for (i, state) in states.iter_mut().enumerate() {
+                let id = state.endpoint_id.clone();
+                state.add_record(record(&id, i), 0.0625);
+            }
+        });
+    });
+
+    group.bench_function("legacy_recount", |b| {
+        b.iter(|| {
+            for (i, state) in states.iter_mut().enumerate() {
+                let probe = record(&state.endpoint_id, i);
+                state.circular_buffer_short.push(probe.clone());
+                state.circular_buffer_long.push(probe);
+                black_box(legacy_update(
+                    &state.circular_buffer_short,
+                    &state.circular_buffer_long,
+                ));
+            }
+        });
+    });
+
+    group.finish();
+}
+
+fn bench_recent_items(c: &mut Criterion) {
+    let mut buffer = RingBuffer::new(LONG_WINDOW);
+    for i in 0..LONG_WINDOW * 2 {
+        buffer.push(record("endpoint", i));
+    }
+    let mut group = c.benchmark_group("recent_failures");
+
+    group.bench_function("iter_recent", |b| {
+        b.iter(|| {
+            buffer
+                .iter_recent()
+                .take(SHORT_WINDOW)
+                .filter(|r| !r.success)
+                .count()
+        });
+    });
+
+    #[allow(deprecated)]
+    group.bench_function("as_slice", |b| {
+        b.iter(|| {
+            buffer
+                .as_slice()
+                .iter()
+                .take(SHORT_WINDOW)
+                .filter(|r| !r.success)
+                .count()
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_add_record, bench_recent_items);
+criterion_main!(benches);
@@ -0,0 +1,341 @@
+//! Scoring and aggregation hot paths
+//!
+//! Every probe goes through `AggregatorState::add_record` and
+//! `scoring::compute_score`, and every benchmark run sorts its regions with
+//! `ScoringAdapter::get_sorted_results`. The aggregator's throughput is
+//! measured with records processed one at a time and in batches, as its loop
+//! drains them from the probe channel, and with the endpoints spread over
+//! several aggregator shards running in parallel. When run with
+//! `--baseline <name>`, `cargo bench` then compares the mean time of each
+//! benchmark with the saved baseline and fails when one is slower by more
+//! than its tolerance in `benches/thresholds.toml`.
+
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use cloud_ping::aggregator::{shard_index, AggregatorConfig, StreamingAggregator};
+use cloud_ping::models::utils::percentile;
+use cloud_ping::models::{
+    scoring, AggregatorState, AlgorithmWeights, PingStats, ProbeRecord, RingBuffer,
+};
+use criterion::{criterion_group, BenchmarkId, Criterion, Throughput};
+use serde::Deserialize;
+
+/// Default short and long window sizes, in probes
+const WINDOWS: [usize; 2] = [60, 720];
+
+/// Regions in a typical run and in a large data file
+const REGION_COUNTS: [usize; 2] = [100, 1000];
+
+/// Records per aggregator iteration, one round of probes of 1000 endpoints
+const ROUND: usize = 1000;
+
+/// Records processed one at a time and in the aggregator's default batches
+const BATCH_SIZES: [usize; 2] = [1, 256];
+
+/// Aggregator shards, each processing its endpoints' records on its own task
+const SHARD_COUNTS: [usize; 3] = [1, 2, 4];
+
+fn latency(i: usize) -> f64 {
+    20.0 + ((i * 7919) % 97) as f64 / 4.0
+}
+
+fn record(i: usize) -> ProbeRecord {
+    if i % 23 == 0 {
+        ProbeRecord::failure("endpoint".to_string(), None)
+    } else {
+        ProbeRecord::success("endpoint".to_string(), latency(i))
+    }
+}
+
+/// State with both windows full, as after an hour of monitoring
+fn warm_state(window: usize) -> AggregatorState {
+    let mut state = AggregatorState::new("endpoint".to_string(), window.min(60), window);
+    for i in 0..window {
+        state.add_record(record(i), 0.0625);
+    }
+    state
+}
+
+fn ping_stats(i: usize) -> PingStats {
+    let mut stats = PingStats::new(10);
+    stats.latencies = (0..10).map(|n| latency(i + n)).collect();
+    stats.successful_pings = 10 - i % 3;
+    stats.avg = stats.latencies.iter().sum::<f64>() / 10.0;
+    stats.min = stats.latencies.iter().copied().fold(f64::MAX, f64::min);
+    stats.max = stats.latencies.iter().copied().fold(0.0, f64::max);
+    stats.jitter = (i % 11) as f64;
+    stats
+}
+
+fn bench_percentile(c: &mut Criterion) {
+    let mut group = c.benchmark_group("percentile");
+    for window in WINDOWS {
+        let values: Vec<f64> = (0..window).map(latency).collect();
+        let mut buffer = RingBuffer::new(window);
+        for &value in &values {
+            buffer.push(value);
+        }
+        group.bench_with_input(BenchmarkId::new("slice", window), &values, |b, values| {
+            b.iter(|| percentile(black_box(values), 90.0));
+        });
+        group.bench_with_input(
+            BenchmarkId::new("ring_buffer", window),
+            &buffer,
+            |b, buffer| b.iter(|| black_box(buffer).percentiles([50.0, 90.0, 99.0])),
+        );
+    }
+    group.finish();
+}
+
+fn bench_add_record(c: &mut Criterion) {
+    let mut group = c.benchmark_group("add_record");
+    for window in WINDOWS {
+        let mut state = warm_state(window);
+        let mut i = window;
+        group.bench_function(BenchmarkId::from_parameter(window), |b| {
+            b.iter(|| {
+                i += 1;
+                state.add_record(record(i), 0.0625);
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_compute_score(c: &mut Criterion) {
+    let weights = AlgorithmWeights::default();
+    let mut group = c.benchmark_group("compute_score");
+    for window in WINDOWS {
+        let state = warm_state(window);
+        group.bench_with_input(BenchmarkId::from_parameter(window), &state, |b, state| {
+            b.iter(|| scoring::compute_score(black_box(state), &weights));
+        });
+    }
+    group.finish();
+}
+
+/// One round of probes of every endpoint, continuing the probe sequence at `i`
+fn round(i: &mut usize) -> Vec<ProbeRecord> {
+    (0..ROUND)
+        .map(|endpoint| {
+            *i += 1;
+            ProbeRecord {
+                endpoint_id: format!("endpoint-{endpoint}"),
+                ..record(*i)
+            }
+        })
+        .collect()
+}
+
+async fn process_in_batches(
+    aggregator: &mut StreamingAggregator,
+    mut records: Vec<ProbeRecord>,
+    batch_size: usize,
+) {
+    while !records.is_empty() {
+        let rest = records.split_off(records.len().min(batch_size));
+        aggregator
+            .process_probe_batch(std::mem::replace(&mut records, rest))
+            .await;
+    }
+}
+
+fn bench_aggregator_throughput(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("benchmark runtime");
+    let mut group = c.benchmark_group("aggregator_throughput");
+    group.throughput(Throughput::Elements(ROUND as u64));
+    for batch_size in BATCH_SIZES {
+        // Monitoring always publishes scores, whose lock batches take once
+        let (aggregator, _alerts) = StreamingAggregator::new(AggregatorConfig::default());
+        let mut aggregator = aggregator.with_score_snapshot(Arc::default());
+        let mut i = 0;
+        group.bench_function(BenchmarkId::from_parameter(batch_size), |b| {
+            b.iter(|| {
+                let records = round(&mut i);
+                runtime.block_on(process_in_batches(&mut aggregator, records, batch_size));
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_aggregator_shards(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(SHARD_COUNTS[SHARD_COUNTS.len() - 1])
+        .build()
+        .expect("benchmark runtime");
+    let mut group = c.benchmark_group("aggregator_shards");
+    group.throughput(Throughput::Elements(ROUND as u64));
+    for shards in SHARD_COUNTS {
+        let (aggregator, _alerts) = StreamingAggregator::new(AggregatorConfig::default());
+        let mut aggregators = aggregator.with_score_snapshot(Arc::default()).split(shards);
+        let mut i = 0;
+        group.bench_function(BenchmarkId::from_parameter(shards), |b| {
+            b.iter(|| {
+                // Routing is part of the work, as in the router task
+                let mut routed: Vec<Vec<ProbeRecord>> = vec![Vec::new(); shards];
+                for record in round(&mut i) {
+                    routed[shard_index(&record.endpoint_id, shards)].push(record);
+                }
+                let running = std::mem::take(&mut aggregators);
+                aggregators = runtime.block_on(async move {
+                    let tasks: Vec<_> = running
+                        .into_iter()
+                        .zip(routed)
+                        .map(|(mut aggregator, records)| {
+                            tokio::spawn(async move {
+                                process_in_batches(&mut aggregator, records, 256).await;
+                                aggregator
+                            })
+                        })
+                        .collect();
+                    let mut done = Vec::with_capacity(tasks.len());
+                    for task in tasks {
+                        done.push(task.await.expect("aggregator shard"));
+                    }
+                    done
+                });
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_sorted_results(c: &mut Criterion) {
+    let weights = AlgorithmWeights::default();
+    let mut group = c.benchmark_group("get_sorted_results");
+    for regions in REGION_COUNTS {
+        let results: Vec<(String, PingStats)> = (0..regions)
+            .map(|i| (format!("region-{i}"), ping_stats(i)))
+            .collect();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(regions),
+            &results,
+            |b, results| {
+                b.iter(|| {
+                    cloud_ping::models::ScoringAdapter::get_sorted_results(
+                        black_box(results),
+                        &weights,
+                    )
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Largest slowdown against the baseline, as a fraction of its mean, by benchmark ID
+#[derive(Deserialize)]
+struct Thresholds {
+    max_regression: BTreeMap<String, f64>,
+}
+
+#[derive(Deserialize)]
+struct Estimates {
+    mean: Estimate,
+}
+
+#[derive(Deserialize)]
+struct Estimate {
+    point_estimate: f64,
+}
+
+/// Baseline named by `--baseline` or `--baseline-lenient`, which Criterion
+/// compares against without overwriting it
+fn baseline_arg() -> Option<String> {
+    let mut args = std::env::args();
+    while let Some(arg) = args.next() {
+        if arg == "--baseline" || arg == "--baseline-lenient" {
+            return args.next();
+        }
+        if let Some(name) = arg
+            .strip_prefix("--baseline=")
+            .or_else(|| arg.strip_prefix("--baseline-lenient="))
+        {
+            return Some(name.to_string());
+        }
+    }
+    None
+}
+
+/// Mean time of the estimates in `path`
+fn mean_ns(path: &Path) -> Result<f64, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {e}", path.display()))?;
+    let estimates: Estimates =
+        serde_json::from_str(&json).map_err(|e| format!("Cannot read {}: {e}", path.display()))?;
+    Ok(estimates.mean.point_estimate)
+}
+
+/// Compare the estimates of this run, which started at `started`, with
+/// those of `baseline`; benchmarks not run or not in the baseline are skipped
+fn check_thresholds(baseline: &str, started: SystemTime) -> Result<(), String> {
+    let thresholds: Thresholds = toml::from_str(include_str!("thresholds.toml"))
+        .map_err(|e| format!("Invalid benches/thresholds.toml: {e}"))?;
+    let target = std::env::var_os("CARGO_TARGET_DIR").map_or_else(
+        || PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target"),
+        PathBuf::from,
+    );
+
+    let mut exceeded = Vec::new();
+    for (id, max_regression) in &thresholds.max_regression {
+        let dir = target.join("criterion").join(id);
+        let new = dir.join("new/estimates.json");
+        let base = dir.join(baseline).join("estimates.json");
+        // Estimates left by an earlier run of a benchmark filtered out of this one
+        let ran = std::fs::metadata(&new)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified >= started);
+        if !ran || !base.exists() {
+            continue;
+        }
+        let (mean, base_mean) = (mean_ns(&new)?, mean_ns(&base)?);
+        let regression = mean / base_mean - 1.0;
+        if regression > *max_regression {
+            exceeded.push(format!(
+                "{id}: mean {mean:.0} ns is {:.0}% slower than {base_mean:.0} ns in `{baseline}`, over the {:.0}% allowed",
+                regression * 100.0,
+                max_regression * 100.0
+            ));
+        }
+    }
+
+    if exceeded.is_empty() {
+        Ok(())
+    } else {
+        Err(exceeded.join("\n"))
+    }
+}
+
+criterion_group!(
+    benches,
+    bench_percentile,
+    bench_add_record,
+    bench_compute_score,
+    bench_aggregator_throughput,
+    bench_aggregator_shards,
+    bench_sorted_results
+);
+
+fn main() {
+    let started = SystemTime::now();
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+
+    // `cargo test --benches` runs each benchmark once without measuring
+    if std::env::args().any(|arg| arg == "--bench") {
+        let Some(baseline) = baseline_arg() else {
+            eprintln!("Not checking performance thresholds, as no --baseline was given");
+            return;
+        };
+        if let Err(message) = check_thresholds(&baseline, started) {
+            eprintln!("Performance thresholds exceeded:\n{message}");
+            std::process::exit(1);
+        }
+    }
+}
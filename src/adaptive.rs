@@ -0,0 +1,315 @@
+//! Adaptive concurrency based on local saturation
+//!
+//! Testing many regions at once can saturate the local uplink, which inflates
+//! every latency measured and says more about the client than the regions.
+//! The [`ConcurrencyController`] pings a known-fast reference endpoint before
+//! and during a run. When the reference latency degrades well beyond its idle
+//! baseline, it halves the number of regions tested at once and records the
+//! change.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::models::ConcurrencyAdjustment;
+use crate::network::NetworkTester;
+use crate::time_utils::TimeUtils;
+
+/// Reference pings whose median is the idle baseline
+const BASELINE_SAMPLES: usize = 3;
+
+/// Consecutive degraded reference pings needed before dialing down
+const DEGRADED_SAMPLES: usize = 2;
+
+/// Settings of the adaptive concurrency controller
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AdaptiveConcurrencyConfig {
+    /// Known-fast endpoint watched during runs; the controller is off when unset
+    #[serde(default)]
+    pub reference_url: Option<String>,
+    /// Time between reference pings during a run
+    #[serde(with = "humantime_serde", default = "default_interval")]
+    pub interval: Duration,
+    /// Dial down when reference latency exceeds its baseline by this factor
+    #[serde(default = "default_degradation_ratio")]
+    pub degradation_ratio: f64,
+    /// and by at least this many milliseconds, so jitter on very fast references is ignored
+    #[serde(default = "default_min_increase_ms")]
+    pub min_increase_ms: f64,
+    /// Never test fewer regions at once than this
+    #[serde(default = "default_min_threads")]
+    pub min_threads: usize,
+}
+
+const fn default_interval() -> Duration {
+    Duration::from_secs(2)
+}
+
+const fn default_degradation_ratio() -> f64 {
+    2.0
+}
+
+const fn default_min_increase_ms() -> f64 {
+    10.0
+}
+
+const fn default_min_threads() -> usize {
+    1
+}
+
+impl Default for AdaptiveConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            reference_url: None,
+            interval: default_interval(),
+            degradation_ratio: default_degradation_ratio(),
+            min_increase_ms: default_min_increase_ms(),
+            min_threads: default_min_threads(),
+        }
+    }
+}
+
+/// Watches a reference endpoint during a run and lowers concurrency when it degrades
+pub struct ConcurrencyController {
+    config: AdaptiveConcurrencyConfig,
+    reference_url: String,
+    tester: NetworkTester,
+    semaphore: Arc<Semaphore>,
+    threads: usize,
+}
+
+/// A running controller; stop it to collect its adjustments
+pub struct ControllerHandle {
+    stop: CancellationToken,
+    task: JoinHandle<Vec<ConcurrencyAdjustment>>,
+}
+
+impl ControllerHandle {
+    /// Stop watching and return the adjustments made
+    pub async fn stop(self) -> Vec<ConcurrencyAdjustment> {
+        self.stop.cancel();
+        self.task.await.unwrap_or_else(|e| {
+            warn!("Concurrency controller failed: {}", e);
+            Vec::new()
+        })
+    }
+}
+
+impl ConcurrencyController {
+    /// Control `semaphore`, which currently allows `threads` regions at once
+    ///
+    /// Returns `None` when no reference endpoint is configured.
+    #[must_use]
+    pub fn new(
+        config: AdaptiveConcurrencyConfig,
+        tester: NetworkTester,
+        semaphore: Arc<Semaphore>,
+        threads: usize,
+    ) -> Option<Self> {
+        let reference_url = config.reference_url.clone()?;
+        Some(Self {
+            config,
+            reference_url,
+            tester,
+            semaphore,
+            threads,
+        })
+    }
+
+    /// Measure the idle baseline, then watch the reference in the background
+    ///
+    /// Call this before the run starts so the baseline is taken without load.
+    /// Returns `None` when the reference cannot be reached, leaving
+    /// concurrency unchanged.
+    pub async fn start(self) -> Option<ControllerHandle> {
+        let mut samples = Vec::with_capacity(BASELINE_SAMPLES);
+        for _ in 0..BASELINE_SAMPLES {
+            if let Some(ms) = self.reference_ms().await {
+                samples.push(ms);
+            }
+        }
+        if samples.is_empty() {
+            warn!(
+                "Reference endpoint {} unreachable, adaptive concurrency disabled",
+                self.reference_url
+            );
+            return None;
+        }
+        samples.sort_by(f64::total_cmp);
+        let baseline_ms = samples[samples.len() / 2];
+        info!(
+            "Reference latency baseline {:.1}ms from {}",
+            baseline_ms, self.reference_url
+        );
+
+        let stop = CancellationToken::new();
+        let task = tokio::spawn(self.watch(baseline_ms, stop.clone()));
+        Some(ControllerHandle { stop, task })
+    }
+
+    async fn watch(
+        mut self,
+        baseline_ms: f64,
+        stop: CancellationToken,
+    ) -> Vec<ConcurrencyAdjustment> {
+        let mut adjustments = Vec::new();
+        let mut degraded = 0;
+
+        loop {
+            let sample = tokio::select! {
+                () = stop.cancelled() => break,
+                () = tokio::time::sleep(self.config.interval) => self.reference_ms().await,
+            };
+            let Some(reference_ms) = sample else {
+                debug!("Reference ping to {} failed", self.reference_url);
+                continue;
+            };
+
+            if self.is_degraded(reference_ms, baseline_ms) {
+                degraded += 1;
+            } else {
+                degraded = 0;
+            }
+            if degraded < DEGRADED_SAMPLES || self.threads <= self.config.min_threads {
+                continue;
+            }
+
+            let to_threads = (self.threads / 2).max(self.config.min_threads);
+            warn!(
+                "Reference latency {:.1}ms vs {:.1}ms baseline suggests local saturation, \
+                 testing {} regions at once instead of {}",
+                reference_ms, baseline_ms, to_threads, self.threads
+            );
+            adjustments.push(ConcurrencyAdjustment {
+                at: TimeUtils::now(),
+                from_threads: self.threads,
+                to_threads,
+                reference_ms,
+                baseline_ms,
+            });
+
+            // Withdraw the permits as regions finish; give up if the run ends first
+            let withdrawn = u32::try_from(self.threads - to_threads).unwrap_or(u32::MAX);
+            tokio::select! {
+                permits = self.semaphore.acquire_many(withdrawn) => {
+                    if let Ok(permits) = permits {
+                        permits.forget();
+                    }
+                }
+                () = stop.cancelled() => break,
+            }
+            self.threads = to_threads;
+            degraded = 0;
+        }
+
+        adjustments
+    }
+
+    fn is_degraded(&self, reference_ms: f64, baseline_ms: f64) -> bool {
+        reference_ms > baseline_ms * self.config.degradation_ratio
+            && reference_ms - baseline_ms >= self.config.min_increase_ms
+    }
+
+    /// Latency of one reference ping in milliseconds, `None` if it failed
+    async fn reference_ms(&self) -> Option<f64> {
+        let timing = self
+            .tester
+            .ping_url_with_retry(&self.reference_url, 0)
+            .await;
+        timing
+            .success
+            .then_some(timing.total_time.as_secs_f64() * 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::network::RequestTiming;
+    use crate::test_util::MockTransport;
+
+    const REFERENCE: &str = "https://reference.example.com";
+
+    fn controller(
+        timings: Vec<RequestTiming>,
+        semaphore: Arc<Semaphore>,
+    ) -> Option<ConcurrencyController> {
+        let transport = Arc::new(MockTransport::new().respond(REFERENCE, timings));
+        let config = AppConfig {
+            max_requests_per_host_per_second: 0.0,
+            ..AppConfig::default()
+        };
+        let tester = NetworkTester::with_transport(config, transport).unwrap();
+        let adaptive = AdaptiveConcurrencyConfig {
+            reference_url: Some(REFERENCE.to_string()),
+            interval: Duration::from_millis(5),
+            min_threads: 2,
+            ..Default::default()
+        };
+        ConcurrencyController::new(adaptive, tester, semaphore, 8)
+    }
+
+    fn reference(ms: u64) -> RequestTiming {
+        RequestTiming::succeeded(Duration::from_millis(ms), 200)
+    }
+
+    #[tokio::test]
+    async fn test_saturation_dials_concurrency_down() {
+        let semaphore = Arc::new(Semaphore::new(8));
+        // Idle baseline of 10ms, then the reference slows to 60ms for good
+        let timings = vec![reference(10), reference(10), reference(10), reference(60)];
+        let handle = controller(timings, Arc::clone(&semaphore))
+            .unwrap()
+            .start()
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let adjustments = handle.stop().await;
+
+        // Halved twice, then held at `min_threads`
+        assert_eq!(adjustments.len(), 2);
+        assert_eq!(
+            (adjustments[0].from_threads, adjustments[0].to_threads),
+            (8, 4)
+        );
+        assert_eq!(
+            (adjustments[1].from_threads, adjustments[1].to_threads),
+            (4, 2)
+        );
+        assert!((adjustments[0].baseline_ms - 10.0).abs() < f64::EPSILON);
+        assert_eq!(semaphore.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_steady_reference_keeps_concurrency() {
+        let semaphore = Arc::new(Semaphore::new(8));
+        // 14ms is more than twice the baseline but short of the minimum increase
+        let timings = vec![reference(5), reference(5), reference(5), reference(14)];
+        let handle = controller(timings, Arc::clone(&semaphore))
+            .unwrap()
+            .start()
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(handle.stop().await.is_empty());
+        assert_eq!(semaphore.available_permits(), 8);
+
+        // Without a reference URL there is nothing to control
+        let tester = NetworkTester::new(AppConfig::default()).unwrap();
+        assert!(ConcurrencyController::new(
+            AdaptiveConcurrencyConfig::default(),
+            tester,
+            semaphore,
+            8
+        )
+        .is_none());
+    }
+}
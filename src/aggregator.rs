@@ -3,16 +3,30 @@
 //! Processes probe records in real-time, maintaining short and long-term
 //! performance metrics with configurable scoring algorithms.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, PoisonError, RwLock as SyncRwLock};
+use std::time::Duration;
 
-use tokio::sync::mpsc;
+use chrono::{DateTime, Utc};
+use futures::FutureExt;
+use tokio::sync::{mpsc, watch, RwLock};
 use tokio::time::{interval, Instant};
 use crate::time_utils::TimeUtils;
 use crate::collection_utils::CollectionUtils;
-use tracing::{debug, info};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
+
+use crate::error::CloudPingError;
+use crate::events::{EventBus, EventKind, MonitoringEvent};
+use crate::scripting::{ConditionResult, ScriptInputs, ScriptSet, ScriptSettings};
+use crate::self_metrics::{Queue, SelfMetrics};
+use crate::supervisor::panic_message;
 
 use crate::models::{
-    AggregatorState, Alert, AlgorithmWeights, ComprehensiveScoreResult, ProbeRecord,
+    vantage_key, AggregatorState, Alert, AlertType, AlgorithmWeights, ComprehensiveScoreResult,
+    ExponentialHistogram, FailureKind, JitterAlgorithm, MetricsSnapshot, ProbeRecord, RootCauseHint,
+    ScorePoint, SuccessCriteria, DEFAULT_SCORE_HISTORY,
 };
 use crate::models::scoring;
 
@@ -27,6 +41,24 @@ pub struct AggregatorConfig {
     pub alert_score_drop_threshold: f64,
     pub alert_sustained_loss_threshold: f64,
     pub alert_availability_threshold: f64,
+    /// Scores kept per endpoint; score drops are measured against their median
+    pub score_history_len: usize,
+    /// Algorithm for the streaming jitter estimate
+    pub jitter_algorithm: JitterAlgorithm,
+    /// Clock offset from an NTP server, either way, above which an alert fires
+    pub alert_clock_offset_threshold_ms: f64,
+    /// Windows, smoothing and thresholds overriding these for the endpoints
+    /// of a namespace, by namespace name
+    pub namespaces: HashMap<String, AggregatorOverrides>,
+    /// Bytes the ring buffers of all endpoints together may take; windows
+    /// are cut short as endpoints are added to stay within it
+    pub memory_budget_bytes: Option<usize>,
+    /// Probe records taken from the channel at once before rescoring their endpoints
+    pub batch_size: usize,
+    /// Aggregator tasks the endpoints are spread over
+    pub shards: usize,
+    /// Scripted alert conditions and derived metrics, evaluated after every rescore
+    pub scripts: Vec<ScriptSettings>,
 }
 
 impl Default for AggregatorConfig {
@@ -40,17 +72,459 @@ impl Default for AggregatorConfig {
             alert_score_drop_threshold: 20.0,
             alert_sustained_loss_threshold: 3.0,
             alert_availability_threshold: 95.0,
+            score_history_len: DEFAULT_SCORE_HISTORY,
+            jitter_algorithm: JitterAlgorithm::default(),
+            alert_clock_offset_threshold_ms: 500.0,
+            namespaces: HashMap::new(),
+            memory_budget_bytes: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            shards: 1,
+            scripts: Vec::new(),
+        }
+    }
+}
+
+impl AggregatorConfig {
+    /// Configuration for the endpoints of `namespace`
+    #[must_use]
+    pub fn for_namespace(&self, namespace: &str) -> Self {
+        let mut config = Self {
+            namespaces: HashMap::new(),
+            ..self.clone()
+        };
+        if let Some(overrides) = self.namespaces.get(namespace) {
+            overrides.apply(&mut config);
+        }
+        config
+    }
+
+    /// Configuration of every namespace with its own settings
+    fn namespace_configs(&self) -> HashMap<String, Self> {
+        self.namespaces
+            .keys()
+            .map(|namespace| (namespace.clone(), self.for_namespace(namespace)))
+            .collect()
+    }
+}
+
+/// Aggregator settings read from `[monitoring.aggregator]` in the application config
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AggregatorSettings {
+    /// Probes kept in the short window
+    #[serde(default = "default_short_window")]
+    pub short_window: usize,
+    /// Probes kept in the long window
+    #[serde(default = "default_long_window")]
+    pub long_window: usize,
+    /// Smoothing factor of the latency EWMA, in (0, 1]
+    #[serde(default = "default_ewma_alpha")]
+    pub ewma_alpha: f64,
+    /// Time between recomputations of long-window metrics
+    #[serde(with = "humantime_serde", default = "default_long_recompute_interval")]
+    pub long_recompute_interval: Duration,
+    /// Alert when the score falls this many points below its recent median
+    #[serde(default = "default_score_drop_threshold")]
+    pub score_drop_threshold: f64,
+    /// Scores kept per endpoint as the baseline for score drops
+    #[serde(default = "default_score_history")]
+    pub score_history: usize,
+    /// Alert when short-window packet loss exceeds this percentage
+    #[serde(default = "default_sustained_loss_threshold")]
+    pub sustained_loss_threshold: f64,
+    /// Alert when short-window availability falls below this percentage
+    #[serde(default = "default_availability_threshold")]
+    pub availability_threshold: f64,
+    /// Alert when an NTP probe finds the local clock off by more than this many milliseconds
+    #[serde(default = "default_clock_offset_threshold_ms")]
+    pub clock_offset_threshold_ms: f64,
+    /// Bytes the probe windows of all endpoints together may take; ignored
+    /// in the settings of a namespace
+    #[serde(default)]
+    pub memory_budget_bytes: Option<u64>,
+    /// Probe records already waiting that are processed together, rescoring
+    /// each of their endpoints once; ignored in the settings of a namespace
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Aggregator tasks the endpoints are spread over, each on its own core
+    /// when there are enough; fixed at start and ignored in the settings of
+    /// a namespace
+    #[serde(default = "default_shards")]
+    pub shards: usize,
+}
+
+const fn default_short_window() -> usize {
+    60
+}
+
+const fn default_long_window() -> usize {
+    720
+}
+
+const fn default_ewma_alpha() -> f64 {
+    1.0 / 16.0
+}
+
+const fn default_long_recompute_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
+const fn default_score_drop_threshold() -> f64 {
+    20.0
+}
+
+const fn default_score_history() -> usize {
+    DEFAULT_SCORE_HISTORY
+}
+
+const fn default_sustained_loss_threshold() -> f64 {
+    3.0
+}
+
+const fn default_availability_threshold() -> f64 {
+    95.0
+}
+
+const fn default_clock_offset_threshold_ms() -> f64 {
+    500.0
+}
+
+/// Probe records processed together by default
+const DEFAULT_BATCH_SIZE: usize = 256;
+
+const fn default_batch_size() -> usize {
+    DEFAULT_BATCH_SIZE
+}
+
+const fn default_shards() -> usize {
+    1
+}
+
+impl Default for AggregatorSettings {
+    fn default() -> Self {
+        Self {
+            short_window: default_short_window(),
+            long_window: default_long_window(),
+            ewma_alpha: default_ewma_alpha(),
+            long_recompute_interval: default_long_recompute_interval(),
+            score_drop_threshold: default_score_drop_threshold(),
+            score_history: default_score_history(),
+            sustained_loss_threshold: default_sustained_loss_threshold(),
+            availability_threshold: default_availability_threshold(),
+            clock_offset_threshold_ms: default_clock_offset_threshold_ms(),
+            memory_budget_bytes: None,
+            batch_size: default_batch_size(),
+            shards: default_shards(),
+        }
+    }
+}
+
+impl AggregatorSettings {
+    /// Copy these settings into an aggregator configuration
+    pub fn apply(&self, config: &mut AggregatorConfig) {
+        config.w_short = self.short_window;
+        config.w_long = self.long_window;
+        config.ewma_alpha = self.ewma_alpha;
+        config.long_recompute_interval_ms =
+            u64::try_from(self.long_recompute_interval.as_millis()).unwrap_or(u64::MAX);
+        config.alert_score_drop_threshold = self.score_drop_threshold;
+        config.score_history_len = self.score_history;
+        config.alert_sustained_loss_threshold = self.sustained_loss_threshold;
+        config.alert_availability_threshold = self.availability_threshold;
+        config.alert_clock_offset_threshold_ms = self.clock_offset_threshold_ms;
+        config.memory_budget_bytes = self
+            .memory_budget_bytes
+            .map(|bytes| usize::try_from(bytes).unwrap_or(usize::MAX));
+        config.batch_size = self.batch_size;
+        config.shards = self.shards;
+    }
+
+    /// # Errors
+    /// Returns a validation error naming the first invalid setting
+    pub fn validate(&self) -> crate::error::Result<()> {
+        if self.short_window == 0 || self.short_window > self.long_window {
+            return Err(CloudPingError::validation(
+                "monitoring.aggregator.short_window",
+                "must be greater than 0 and at most long_window",
+            ));
+        }
+        if !(self.ewma_alpha > 0.0 && self.ewma_alpha <= 1.0) {
+            return Err(CloudPingError::validation(
+                "monitoring.aggregator.ewma_alpha",
+                "must be in (0, 1]",
+            ));
+        }
+        if self.long_recompute_interval.is_zero() {
+            return Err(CloudPingError::validation(
+                "monitoring.aggregator.long_recompute_interval",
+                "must be greater than 0",
+            ));
+        }
+        for (field, value) in [
+            ("monitoring.aggregator.sustained_loss_threshold", self.sustained_loss_threshold),
+            ("monitoring.aggregator.availability_threshold", self.availability_threshold),
+        ] {
+            if !(0.0..=100.0).contains(&value) {
+                return Err(CloudPingError::validation(field, "must be between 0 and 100"));
+            }
+        }
+        if self.score_drop_threshold <= 0.0 {
+            return Err(CloudPingError::validation(
+                "monitoring.aggregator.score_drop_threshold",
+                "must be greater than 0",
+            ));
+        }
+        if self.clock_offset_threshold_ms <= 0.0 {
+            return Err(CloudPingError::validation(
+                "monitoring.aggregator.clock_offset_threshold_ms",
+                "must be greater than 0",
+            ));
+        }
+        if self.score_history == 0 {
+            return Err(CloudPingError::validation(
+                "monitoring.aggregator.score_history",
+                "must be greater than 0",
+            ));
+        }
+        for (field, value) in [
+            ("monitoring.aggregator.batch_size", self.batch_size),
+            ("monitoring.aggregator.shards", self.shards),
+        ] {
+            if value == 0 {
+                return Err(CloudPingError::validation(field, "must be greater than 0"));
+            }
+        }
+        if self.memory_budget_bytes == Some(0) {
+            return Err(CloudPingError::validation(
+                "monitoring.aggregator.memory_budget_bytes",
+                "must be greater than 0",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Aggregator settings of a namespace, read from its `aggregator` table
+///
+/// Only the settings given override `[monitoring.aggregator]`; the rest
+/// keep their global values.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AggregatorOverrides {
+    /// Probes kept in the short window
+    #[serde(default)]
+    pub short_window: Option<usize>,
+    /// Probes kept in the long window
+    #[serde(default)]
+    pub long_window: Option<usize>,
+    /// Smoothing factor of the latency EWMA, in (0, 1]
+    #[serde(default)]
+    pub ewma_alpha: Option<f64>,
+    /// Time between recomputations of long-window metrics
+    #[serde(with = "humantime_serde", default)]
+    pub long_recompute_interval: Option<Duration>,
+    /// Alert when the score falls this many points below its recent median
+    #[serde(default)]
+    pub score_drop_threshold: Option<f64>,
+    /// Scores kept per endpoint as the baseline for score drops
+    #[serde(default)]
+    pub score_history: Option<usize>,
+    /// Alert when short-window packet loss exceeds this percentage
+    #[serde(default)]
+    pub sustained_loss_threshold: Option<f64>,
+    /// Alert when short-window availability falls below this percentage
+    #[serde(default)]
+    pub availability_threshold: Option<f64>,
+    /// Alert when an NTP probe finds the local clock off by more than this many milliseconds
+    #[serde(default)]
+    pub clock_offset_threshold_ms: Option<f64>,
+}
+
+impl AggregatorOverrides {
+    /// `base` with these overrides applied
+    #[must_use]
+    pub fn over(&self, base: &AggregatorSettings) -> AggregatorSettings {
+        AggregatorSettings {
+            short_window: self.short_window.unwrap_or(base.short_window),
+            long_window: self.long_window.unwrap_or(base.long_window),
+            ewma_alpha: self.ewma_alpha.unwrap_or(base.ewma_alpha),
+            long_recompute_interval: self.long_recompute_interval.unwrap_or(base.long_recompute_interval),
+            score_drop_threshold: self.score_drop_threshold.unwrap_or(base.score_drop_threshold),
+            score_history: self.score_history.unwrap_or(base.score_history),
+            sustained_loss_threshold: self.sustained_loss_threshold.unwrap_or(base.sustained_loss_threshold),
+            availability_threshold: self.availability_threshold.unwrap_or(base.availability_threshold),
+            clock_offset_threshold_ms: self.clock_offset_threshold_ms.unwrap_or(base.clock_offset_threshold_ms),
+            ..base.clone()
+        }
+    }
+
+    /// Copy the settings given into an aggregator configuration
+    pub fn apply(&self, config: &mut AggregatorConfig) {
+        if let Some(window) = self.short_window {
+            config.w_short = window;
+        }
+        if let Some(window) = self.long_window {
+            config.w_long = window;
+        }
+        if let Some(alpha) = self.ewma_alpha {
+            config.ewma_alpha = alpha;
+        }
+        if let Some(interval) = self.long_recompute_interval {
+            config.long_recompute_interval_ms = u64::try_from(interval.as_millis()).unwrap_or(u64::MAX);
+        }
+        if let Some(threshold) = self.score_drop_threshold {
+            config.alert_score_drop_threshold = threshold;
+        }
+        if let Some(history) = self.score_history {
+            config.score_history_len = history;
+        }
+        if let Some(threshold) = self.sustained_loss_threshold {
+            config.alert_sustained_loss_threshold = threshold;
+        }
+        if let Some(threshold) = self.availability_threshold {
+            config.alert_availability_threshold = threshold;
+        }
+        if let Some(threshold) = self.clock_offset_threshold_ms {
+            config.alert_clock_offset_threshold_ms = threshold;
+        }
+    }
+}
+
+/// Compile `scripts`, logging why they are left out when they do not compile
+fn compile_scripts(scripts: &[ScriptSettings]) -> Option<Arc<ScriptSet>> {
+    match ScriptSet::compile(scripts) {
+        Ok(compiled) => compiled.map(Arc::new),
+        Err(e) => {
+            error!("Scripts disabled: {}", e);
+            None
         }
     }
 }
 
+/// Fewest probes a memory budget cuts the short and long windows down to
+const MIN_BUDGET_WINDOW: usize = 8;
+
+/// Short-window samples required before loss and availability alerts are evaluated
+const MIN_ALERT_SAMPLES: usize = 10;
+
+/// How long after a score drop an endpoint still counts as degraded for hints
+const SCORE_DROP_HOLD: chrono::Duration = chrono::Duration::minutes(5);
+
+/// Alert conditions currently raised for an endpoint, so each fires once per episode
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)] // one flag per alert condition
+struct ActiveAlerts {
+    sustained_loss: bool,
+    availability_low: bool,
+    score_drop: bool,
+    clock_drift: bool,
+    /// Time of the latest score drop alert
+    score_drop_at: Option<DateTime<Utc>>,
+    /// Scripted conditions that hold, by script name
+    scripted: BTreeSet<String>,
+}
+
+impl ActiveAlerts {
+    /// Whether any alert condition holds at `now`
+    fn degraded(&self, now: DateTime<Utc>) -> bool {
+        self.sustained_loss
+            || self.availability_low
+            || self.score_drop
+            || !self.scripted.is_empty()
+            || self.score_drop_at.is_some_and(|at| now - at <= SCORE_DROP_HOLD)
+    }
+
+    /// Alerts resolved and health changed going from `self` to `current` at `at`
+    fn changes(&self, current: &Self, endpoint_id: &str, at: DateTime<Utc>) -> Vec<MonitoringEvent> {
+        let conditions = [
+            (self.sustained_loss, current.sustained_loss, "sustained_loss"),
+            (self.availability_low, current.availability_low, "availability_low"),
+            (self.score_drop, current.score_drop, "score_drop"),
+            (self.clock_drift, current.clock_drift, "clock_drift"),
+        ];
+        let scripted = self
+            .scripted
+            .difference(&current.scripted)
+            .map(|_| "scripted");
+        let mut events: Vec<MonitoringEvent> = conditions
+            .into_iter()
+            .filter(|(held, holds, _)| *held && !*holds)
+            .map(|(_, _, kind)| kind)
+            .chain(scripted)
+            .map(|kind| MonitoringEvent::AlertResolved {
+                endpoint_id: endpoint_id.to_string(),
+                kind,
+                at,
+            })
+            .collect();
+        let degraded = current.degraded(at);
+        if self.degraded(at) != degraded {
+            events.push(MonitoringEvent::HealthChanged {
+                endpoint_id: endpoint_id.to_string(),
+                degraded,
+                at,
+            });
+        }
+        events
+    }
+}
+
+/// Which provider each endpoint belongs to and which endpoint is the control
+///
+/// Used to hint at the cause of alerts: a degraded control endpoint points to
+/// the local network, all endpoints of one provider degrading to that provider.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointTopology {
+    /// Provider name by endpoint ID
+    pub providers: HashMap<String, String>,
+    /// Endpoint measuring the local network, if monitored
+    pub control: Option<String>,
+    /// Namespace by endpoint ID, for endpoints monitored in one
+    pub namespaces: HashMap<String, String>,
+    /// What counts as a successful probe, by endpoint ID, for endpoints
+    /// whose metadata tightens transport success
+    pub success_criteria: HashMap<String, SuccessCriteria>,
+}
+
+/// Alert conditions of the endpoints of every shard, by endpoint ID
+///
+/// Alert hints compare an endpoint with the control endpoint and the rest of
+/// its provider, which may be aggregated by other shards.
+type SharedAlertStates = Arc<SyncRwLock<HashMap<String, ActiveAlerts>>>;
+
+/// Score history of every endpoint, shared with readers such as the HTTP API
+pub type ScoreHistorySnapshot = Arc<RwLock<HashMap<String, Vec<ScorePoint>>>>;
+
+/// Round-trip histogram of every endpoint, shared with metrics exporters
+pub type RttHistogramSnapshot = Arc<RwLock<HashMap<String, ExponentialHistogram>>>;
+
 /// Real-time aggregator for probe data with sliding window metrics
 pub struct StreamingAggregator {
     config: AggregatorConfig,
+    namespace_configs: HashMap<String, AggregatorConfig>,
     state_map: HashMap<String, AggregatorState>,
-    #[allow(dead_code)]
+    active_alerts: HashMap<String, ActiveAlerts>,
     alert_sender: mpsc::UnboundedSender<Alert>,
     last_long_recompute: Instant,
+    score_snapshot: Option<Arc<RwLock<HashMap<String, ComprehensiveScoreResult>>>>,
+    history_snapshot: Option<ScoreHistorySnapshot>,
+    histogram_snapshot: Option<RttHistogramSnapshot>,
+    config_updates: Option<watch::Receiver<AggregatorConfig>>,
+    topology: EndpointTopology,
+    topology_updates: Option<watch::Receiver<EndpointTopology>>,
+    self_metrics: Arc<SelfMetrics>,
+    /// Short and long window every endpoint is cut down to for the memory budget
+    window_limit: Option<(usize, usize)>,
+    /// Aggregators sharing the memory budget, this one included
+    shard_count: usize,
+    /// Alert conditions of the endpoints of the other shards, when sharded
+    shared_alerts: Option<SharedAlertStates>,
+    /// Ring buffer bytes last added to the self-metrics
+    reported_buffer_bytes: usize,
+    /// Bus alert resolutions and health changes are published on
+    events: Option<Arc<EventBus>>,
+    /// Compiled `config.scripts`; `None` without any
+    scripts: Option<Arc<ScriptSet>>,
+    /// Sequence number of the next record added to a window
+    next_sequence: u64,
 }
 
 impl StreamingAggregator {
@@ -58,15 +532,236 @@ impl StreamingAggregator {
         let (alert_sender, alert_receiver) = mpsc::unbounded_channel();
 
         let aggregator = Self {
+            namespace_configs: config.namespace_configs(),
+            scripts: compile_scripts(&config.scripts),
             config,
             state_map: CollectionUtils::new_hashmap(),
+            active_alerts: CollectionUtils::new_hashmap(),
             alert_sender,
             last_long_recompute: Instant::now(),
+            score_snapshot: None,
+            history_snapshot: None,
+            histogram_snapshot: None,
+            config_updates: None,
+            topology: EndpointTopology::default(),
+            topology_updates: None,
+            self_metrics: Arc::default(),
+            window_limit: None,
+            shard_count: 1,
+            shared_alerts: None,
+            reported_buffer_bytes: 0,
+            events: None,
+            next_sequence: 1,
         };
 
         (aggregator, alert_receiver)
     }
 
+    /// Publish the latest score of every endpoint into `snapshot` as records arrive
+    #[must_use]
+    pub fn with_score_snapshot(
+        mut self,
+        snapshot: Arc<RwLock<HashMap<String, ComprehensiveScoreResult>>>,
+    ) -> Self {
+        self.score_snapshot = Some(snapshot);
+        self
+    }
+
+    /// Publish every endpoint's score history into `snapshot` as records arrive
+    #[must_use]
+    pub fn with_history_snapshot(mut self, snapshot: ScoreHistorySnapshot) -> Self {
+        self.history_snapshot = Some(snapshot);
+        self
+    }
+
+    /// Publish every endpoint's round-trip histogram into `snapshot` as records arrive
+    #[must_use]
+    pub fn with_histogram_snapshot(mut self, snapshot: RttHistogramSnapshot) -> Self {
+        self.histogram_snapshot = Some(snapshot);
+        self
+    }
+
+    /// Apply configurations published on `updates` while running
+    #[must_use]
+    pub fn with_config_updates(mut self, updates: watch::Receiver<AggregatorConfig>) -> Self {
+        self.config_updates = Some(updates);
+        self
+    }
+
+    /// Report processing latency, queue depths and buffer memory into `metrics`
+    #[must_use]
+    pub fn with_self_metrics(mut self, metrics: Arc<SelfMetrics>) -> Self {
+        self.self_metrics = metrics;
+        self
+    }
+
+    /// Providers and control endpoint used to hint at the cause of alerts
+    #[must_use]
+    pub fn with_topology(mut self, topology: EndpointTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Replace the topology with those published on `updates` while running,
+    /// as endpoints are added, changed or removed
+    #[must_use]
+    pub fn with_topology_updates(mut self, updates: watch::Receiver<EndpointTopology>) -> Self {
+        self.topology_updates = Some(updates);
+        self
+    }
+
+    /// Publish alerts resolving and endpoints turning degraded or healthy on `events`
+    #[must_use]
+    pub fn with_events(mut self, events: Arc<EventBus>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Split into `shards` aggregators, each to be given the endpoints for
+    /// which [`shard_index`] picks it
+    ///
+    /// The shards share the score snapshots, alert channel, event bus,
+    /// self-metrics, configuration and topology updates, and divide the memory budget evenly. Endpoints
+    /// already aggregated move to their shard.
+    #[must_use]
+    pub fn split(mut self, shards: usize) -> Vec<Self> {
+        let shard_count = shards.max(1);
+        let shared_alerts: SharedAlertStates = Arc::new(SyncRwLock::new(self.active_alerts.clone()));
+        let mut split: Vec<Self> = (0..shard_count)
+            .map(|_| Self {
+                config: self.config.clone(),
+                namespace_configs: self.namespace_configs.clone(),
+                state_map: CollectionUtils::new_hashmap(),
+                active_alerts: CollectionUtils::new_hashmap(),
+                alert_sender: self.alert_sender.clone(),
+                last_long_recompute: Instant::now(),
+                score_snapshot: self.score_snapshot.clone(),
+                history_snapshot: self.history_snapshot.clone(),
+                histogram_snapshot: self.histogram_snapshot.clone(),
+                config_updates: self.config_updates.clone(),
+                topology: self.topology.clone(),
+                topology_updates: self.topology_updates.clone(),
+                self_metrics: Arc::clone(&self.self_metrics),
+                window_limit: None,
+                shard_count,
+                shared_alerts: Some(Arc::clone(&shared_alerts)),
+                reported_buffer_bytes: 0,
+                events: self.events.clone(),
+                scripts: self.scripts.clone(),
+                // Windows moving to a shard keep their numbers, so count on from them
+                next_sequence: self.next_sequence,
+            })
+            .collect();
+        for (endpoint_id, state) in self.state_map.drain() {
+            let shard = &mut split[shard_index(&endpoint_id, shard_count)];
+            if let Some(active) = self.active_alerts.remove(&endpoint_id) {
+                shard.active_alerts.insert(endpoint_id.clone(), active);
+            }
+            shard.state_map.insert(endpoint_id, state);
+        }
+        for shard in &mut split {
+            shard.enforce_memory_budget(shard.state_map.len());
+        }
+        split
+    }
+
+    /// Sender for alerts raised outside the aggregator, delivered with its own
+    #[must_use]
+    pub fn alert_sender(&self) -> mpsc::UnboundedSender<Alert> {
+        self.alert_sender.clone()
+    }
+
+    /// Replace the configuration, keeping every endpoint's windows
+    ///
+    /// Thresholds, weights and smoothing apply from the next record on. Window
+    /// sizes and the jitter algorithm are fixed per endpoint, so they only
+    /// apply to endpoints first seen after the change, unless a changed memory
+    /// budget resizes every endpoint's windows.
+    pub fn update_config(&mut self, config: AggregatorConfig) {
+        if config.w_short != self.config.w_short
+            || config.w_long != self.config.w_long
+            || config.score_history_len != self.config.score_history_len
+            || config.jitter_algorithm != self.config.jitter_algorithm
+        {
+            info!("New window settings apply to endpoints first seen from now on");
+        }
+        if config.scripts != self.config.scripts {
+            self.scripts = compile_scripts(&config.scripts);
+        }
+        self.namespace_configs = config.namespace_configs();
+        self.config = config;
+        self.enforce_memory_budget(self.state_map.len());
+    }
+
+    /// Windows to cut every endpoint down to so `endpoints` of them fit the
+    /// memory budget, and whether they do fit; `None` when no cut is needed
+    fn window_limit_for(&self, endpoints: usize) -> Option<((usize, usize), bool)> {
+        let budget = self.config.memory_budget_bytes? / self.shard_count;
+        let (mut short, mut long, history) = std::iter::once(&self.config)
+            .chain(self.namespace_configs.values())
+            .fold((0, 0, 0), |(short, long, history), config| {
+                (
+                    short.max(config.w_short),
+                    long.max(config.w_long),
+                    history.max(config.score_history_len),
+                )
+            });
+        let fits = |short, long| {
+            AggregatorState::full_buffer_bytes(short, long, history).saturating_mul(endpoints) <= budget
+        };
+        if fits(short, long) {
+            return None;
+        }
+        // Halve the long window down to the short one first, then both together
+        while !fits(short, long) {
+            if long > short {
+                long = (long / 2).max(short);
+            } else if short > MIN_BUDGET_WINDOW {
+                short = (short / 2).max(MIN_BUDGET_WINDOW);
+                long = short;
+            } else {
+                break;
+            }
+        }
+        Some(((short, long), fits(short, long)))
+    }
+
+    /// Resize every endpoint's windows when `endpoints` endpoints call for another cut
+    fn enforce_memory_budget(&mut self, endpoints: usize) {
+        let limit = self.window_limit_for(endpoints);
+        if limit.map(|(windows, _)| windows) == self.window_limit {
+            return;
+        }
+        match limit {
+            Some(((short, long), true)) => info!(
+                "Cutting windows to {} short and {} long probes to keep {} endpoints within the memory budget",
+                short, long, endpoints
+            ),
+            Some(((short, long), false)) => warn!(
+                "Memory budget is too small for {} endpoints even with windows of {} short and {} long probes",
+                endpoints, short, long
+            ),
+            None => info!("Windows back to their configured sizes within the memory budget"),
+        }
+        self.window_limit = limit.map(|(windows, _)| windows);
+        for (endpoint_id, state) in &mut self.state_map {
+            let config = self
+                .topology
+                .namespaces
+                .get(endpoint_id)
+                .and_then(|namespace| self.namespace_configs.get(namespace))
+                .unwrap_or(&self.config);
+            let (short, long) = limit_windows(config, self.window_limit);
+            state.resize_windows(short, long);
+        }
+    }
+
+    /// Current configuration
+    #[must_use]
+    pub const fn config(&self) -> &AggregatorConfig {
+        &self.config
+    }
+
     /// Main processing loop for probe records and periodic tasks
     pub async fn start(
         mut self,
@@ -76,17 +771,54 @@ impl StreamingAggregator {
 
         // Set up periodic long window recomputation
         let mut recompute_timer = interval(TimeUtils::duration_from_millis(self.config.long_recompute_interval_ms));
+        let mut updates = self.config_updates.take();
+        let mut topology_updates = self.topology_updates.take();
 
         loop {
             tokio::select! {
                 // Process incoming probe records
                 Some(record) = probe_receiver.recv() => {
-                    self.process_probe_record(record).await;
+                    // Take whatever else is already waiting, up to a batch
+                    let mut batch = vec![record];
+                    while batch.len() < self.config.batch_size {
+                        let Ok(record) = probe_receiver.try_recv() else {
+                            break;
+                        };
+                        batch.push(record);
+                    }
+                    for _ in &batch {
+                        self.self_metrics.dequeued(Queue::Aggregator);
+                    }
+                    let records = batch.len();
+                    let started = Instant::now();
+                    self.process_probe_batch_guarded(batch).await;
+                    self.self_metrics.batch_aggregated(records, started.elapsed());
                 }
                 
                 // Periodic long window recomputation
                 _ = recompute_timer.tick() => {
                     self.recompute_long_windows().await;
+                    let buffer_bytes = self.state_map.values().map(AggregatorState::buffer_bytes).sum();
+                    self.self_metrics.ring_buffer_bytes_changed(self.reported_buffer_bytes, buffer_bytes);
+                    self.reported_buffer_bytes = buffer_bytes;
+                }
+
+                // Reloaded configuration
+                config = Self::next_update(&mut updates) => {
+                    let recompute_changed =
+                        config.long_recompute_interval_ms != self.config.long_recompute_interval_ms;
+                    self.update_config(config);
+                    if recompute_changed {
+                        recompute_timer = interval(TimeUtils::duration_from_millis(
+                            self.config.long_recompute_interval_ms,
+                        ));
+                    }
+                    info!("Aggregator configuration reloaded");
+                }
+
+                // Endpoints added, changed or removed
+                topology = Self::next_update(&mut topology_updates) => {
+                    self.topology = topology;
                 }
                 
                 // Handle shutdown gracefully
@@ -100,43 +832,398 @@ impl StreamingAggregator {
         Ok(())
     }
 
-    async fn process_probe_record(&mut self, record: ProbeRecord) {
-        debug!("Processing probe record for endpoint: {}", record.endpoint_id);
-
-        // Get or create aggregator state for this endpoint
-        let endpoint_id = record.endpoint_id.clone();
-        
-        // Use entry API to avoid double lookup and borrowing issues
-        let state = self.state_map
-            .entry(endpoint_id)
-            .or_insert_with(|| {
-                AggregatorState::new(
-                    record.endpoint_id.clone(),
-                    self.config.w_short,
-                    self.config.w_long,
-                )
-            });
+    /// Wait for the next published value; never resolves without an open update channel
+    async fn next_update<T: Clone + Send + Sync>(updates: &mut Option<watch::Receiver<T>>) -> T {
+        if let Some(receiver) = updates {
+            if receiver.changed().await.is_ok() {
+                return receiver.borrow_and_update().clone();
+            }
+        }
+        *updates = None;
+        std::future::pending().await
+    }
 
-        // Add record and update metrics
-        state.add_record(record, self.config.ewma_alpha);
-
-        // Compute current score
-        let score_result = scoring::compute_score(state, &self.config.weights);
-        
-        // Update last score for future comparisons
-        state.last_score = Some(score_result.score as f64);
-
-        debug!(
-            "Updated metrics for {}: score={}, grade={}, loss={:.1}%, avail={:.1}%",
-            state.endpoint_id,
-            score_result.score,
-            score_result.grade,
-            state.cached_loss_short,
-            state.cached_avail_short
-        );
+    /// Process a batch of probe records, recovering from a panic while doing so
+    ///
+    /// A batch that makes the aggregator panic could leave its endpoints'
+    /// windows half updated, so they start over rather than stopping the
+    /// aggregation of every endpoint.
+    async fn process_probe_batch_guarded(&mut self, records: Vec<ProbeRecord>) {
+        let endpoint_ids: Vec<String> = records.iter().map(|record| record.endpoint_id.clone()).collect();
+        let processed = AssertUnwindSafe(self.process_probe_batch(records))
+            .catch_unwind()
+            .await;
+        if let Err(panic) = processed {
+            self.self_metrics.task_panicked();
+            error!(
+                endpoints = endpoint_ids.len(),
+                "Aggregator panicked on a batch of records, resetting the windows of {}: {}",
+                endpoint_ids.join(", "),
+                panic_message(panic.as_ref())
+            );
+            for endpoint_id in &endpoint_ids {
+                self.state_map.remove(endpoint_id);
+                self.active_alerts.remove(endpoint_id);
+            }
+            if let Some(shared) = &self.shared_alerts {
+                let mut shared = shared.write().unwrap_or_else(PoisonError::into_inner);
+                for endpoint_id in &endpoint_ids {
+                    shared.remove(endpoint_id);
+                }
+            }
+        }
+    }
+
+    /// Settings for the endpoint `endpoint_id`: its namespace's, or the global ones
+    fn config_of<'a>(
+        config: &'a AggregatorConfig,
+        namespace_configs: &'a HashMap<String, AggregatorConfig>,
+        topology: &EndpointTopology,
+        endpoint_id: &str,
+    ) -> &'a AggregatorConfig {
+        topology
+            .namespaces
+            .get(endpoint_id)
+            .and_then(|namespace| namespace_configs.get(namespace))
+            .unwrap_or(config)
+    }
+
+    /// Add a probe record to its endpoint's windows, rescore it and raise any alerts
+    pub async fn process_probe_record(&mut self, record: ProbeRecord) {
+        self.process_probe_batch(vec![record]).await;
+    }
+
+    /// Add probe records to their endpoints' windows, then rescore every
+    /// endpoint they belong to and raise any alerts
+    ///
+    /// Short-window aggregates, scores and alerts are computed once per
+    /// endpoint, after its last record in the batch, and shared score
+    /// snapshots are locked once per batch.
+    pub async fn process_probe_batch(&mut self, records: Vec<ProbeRecord>) {
+        let updated = self.add_records(records);
+
+        let mut scores = Vec::with_capacity(updated.len());
+        let mut raised = Vec::new();
+        let mut changed_alerts = Vec::new();
+        let mut events = Vec::new();
+        let publish_changes = self.events.as_ref().is_some_and(|bus| {
+            bus.wants(EventKind::AlertResolved) || bus.wants(EventKind::HealthChanged)
+        });
+        for (endpoint_id, timestamp, clock_offset_ms) in updated {
+            let config = Self::config_of(&self.config, &self.namespace_configs, &self.topology, &endpoint_id);
+            let Some(state) = self.state_map.get_mut(&endpoint_id) else {
+                continue;
+            };
+            state.recompute_short_aggregates();
+
+            // Compute current score
+            let mut score_result = scoring::compute_score(state, &config.weights);
+
+            // Measure against the scores before this one, then record it
+            let baseline = state.score_baseline();
+            state.record_score(timestamp, score_result.score as f64);
+
+            let active = self.active_alerts.entry(endpoint_id.clone()).or_default();
+            let previous = active.clone();
+            let mut alerts = Self::evaluate_alerts(config, state, baseline, active, timestamp);
+            alerts.extend(Self::evaluate_clock_offset(config, state, clock_offset_ms, active));
+            if let Some(scripts) = &self.scripts {
+                let namespace = self.topology.namespaces.get(&endpoint_id).map_or("", String::as_str);
+                alerts.extend(Self::evaluate_scripts(scripts, namespace, state, &mut score_result, active));
+            } else {
+                active.scripted.clear();
+            }
+            if self.shared_alerts.is_some() && *active != previous {
+                changed_alerts.push((endpoint_id.clone(), active.clone()));
+            }
+            if publish_changes {
+                events.extend(previous.changes(active, &endpoint_id, timestamp));
+            }
+
+            debug!(
+                "Updated metrics for {}: score={}, grade={}, loss={:.1}%, avail={:.1}%",
+                state.endpoint_id,
+                score_result.score,
+                score_result.grade,
+                state.cached_loss_short,
+                state.cached_avail_short
+            );
+
+            let history = self.history_snapshot.as_ref().map(|_| state.score_history());
+            let histogram = self.histogram_snapshot.as_ref().map(|_| state.rtt_histogram.clone());
+            if !alerts.is_empty() {
+                raised.push((endpoint_id.clone(), timestamp, MetricsSnapshot::from_state(state), alerts));
+            }
+            scores.push((endpoint_id, score_result, history, histogram));
+        }
+
+        if let Some(shared) = self.shared_alerts.as_ref().filter(|_| !changed_alerts.is_empty()) {
+            shared
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .extend(changed_alerts);
+        }
+        if let Some(snapshot) = &self.score_snapshot {
+            let mut snapshot = snapshot.write().await;
+            for (endpoint_id, score_result, _, _) in &scores {
+                snapshot.insert(endpoint_id.clone(), score_result.clone());
+            }
+        }
+        if let Some(snapshot) = &self.history_snapshot {
+            let mut snapshot = snapshot.write().await;
+            for (endpoint_id, _, history, _) in &mut scores {
+                if let Some(history) = history.take() {
+                    snapshot.insert(endpoint_id.clone(), history);
+                }
+            }
+        }
+        if let Some(snapshot) = &self.histogram_snapshot {
+            let mut snapshot = snapshot.write().await;
+            for (endpoint_id, _, _, histogram) in scores {
+                if let Some(histogram) = histogram {
+                    snapshot.insert(endpoint_id, histogram);
+                }
+            }
+        }
+
+        for (endpoint_id, timestamp, metrics, alerts) in raised {
+            let namespace = self.topology.namespaces.get(&endpoint_id).cloned();
+            let hint = self.root_cause_hint(&endpoint_id, timestamp);
+            for mut alert in alerts {
+                // Stamp alerts with the probe time so replayed records keep their timeline
+                alert.timestamp = timestamp;
+                alert.metrics = Some(metrics.clone());
+                alert.hint.clone_from(&hint);
+                alert.namespace.clone_from(&namespace);
+                self.self_metrics.enqueued(Queue::Alert);
+                if self.alert_sender.send(alert).is_err() {
+                    self.self_metrics.dequeued(Queue::Alert);
+                    debug!("Alert receiver closed, dropping alert");
+                }
+            }
+        }
+        if let Some(bus) = &self.events {
+            for event in &events {
+                bus.publish(event);
+            }
+        }
+    }
+
+    /// Add probe records to their endpoints' windows without rescoring them
+    ///
+    /// Records are added by wall-clock time, then by sequence, then in the
+    /// order they arrived, and numbered by this aggregator as they are added.
+    /// Returns the endpoints in the order they first appear, with their latest
+    /// probe time and clock offset.
+    fn add_records(&mut self, mut records: Vec<ProbeRecord>) -> Vec<(String, DateTime<Utc>, Option<f64>)> {
+        let mut updated: Vec<(String, DateTime<Utc>, Option<f64>)> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+
+        records.sort_by_key(|record| (record.timestamp, record.sequence));
+        for mut record in records {
+            record.sequence = self.next_sequence;
+            self.next_sequence += 1;
+            debug!("Processing probe record for endpoint: {}", record.endpoint_id);
+
+            // A new endpoint may need every window cut to fit the memory budget
+            if !self.state_map.contains_key(&record.endpoint_id) {
+                self.enforce_memory_budget(self.state_map.len() + 1);
+            }
+
+            // Endpoints of a namespace are measured against its own settings
+            let config = Self::config_of(&self.config, &self.namespace_configs, &self.topology, &record.endpoint_id);
+            let (w_short, w_long) = limit_windows(config, self.window_limit);
+            let state = self.state_map
+                .entry(record.endpoint_id.clone())
+                .or_insert_with(|| {
+                    AggregatorState::builder(record.endpoint_id.clone())
+                        .short_window(w_short)
+                        .long_window(w_long)
+                        .score_history(config.score_history_len)
+                        .jitter_algorithm(config.jitter_algorithm)
+                        .build()
+                });
+
+            // Records failing the endpoint's criteria count as lost
+            let mut record = record;
+            if let Some(criteria) = self.topology.success_criteria.get(&record.endpoint_id) {
+                criteria.apply(&mut record);
+            }
+            let (endpoint_id, clock_offset) = (record.endpoint_id.clone(), record.clock_offset_ms);
+            let probed_at = record.timestamp;
+            state.push_record(record, config.ewma_alpha);
+            // The time the record was filed under, after any wall-clock step
+            let probed_at = state.circular_buffer_long.latest().map_or(probed_at, |newest| newest.timestamp);
+            if let Some(&position) = positions.get(&endpoint_id) {
+                let (_, timestamp, clock_offset_ms) = &mut updated[position];
+                *timestamp = probed_at;
+                if clock_offset.is_some() {
+                    *clock_offset_ms = clock_offset;
+                }
+            } else {
+                positions.insert(endpoint_id.clone(), updated.len());
+                updated.push((endpoint_id, probed_at, clock_offset));
+            }
+        }
+        updated
+    }
+
+    /// Whether an alert condition of `endpoint_id` holds at `now`, in whichever shard
+    fn degraded(&self, endpoint_id: &str, now: DateTime<Utc>) -> bool {
+        let degraded = |active: Option<&ActiveAlerts>| active.is_some_and(|active| active.degraded(now));
+        self.shared_alerts.as_ref().map_or_else(
+            || degraded(self.active_alerts.get(endpoint_id)),
+            |shared| degraded(shared.read().unwrap_or_else(PoisonError::into_inner).get(endpoint_id)),
+        )
+    }
+
+    /// Likely cause of an alert on `endpoint_id`, from its failures and the other endpoints
+    ///
+    /// A degraded control endpoint points to the local network first. Failures
+    /// that are mostly DNS lookups point to the resolver. When every endpoint
+    /// of the provider is degraded, the provider is blamed, unless every other
+    /// provider is degraded as well.
+    fn root_cause_hint(&self, endpoint_id: &str, now: DateTime<Utc>) -> Option<RootCauseHint> {
+        let degraded = |id: &str| self.degraded(id, now);
+
+        if let Some(control) = self.topology.control.as_deref() {
+            if control != endpoint_id && degraded(control) {
+                return Some(RootCauseHint::LocalNetwork);
+            }
+        }
+
+        if self.state_map.get(endpoint_id).is_some_and(dns_failures_dominate) {
+            return Some(RootCauseHint::ResolverIssue);
+        }
+
+        // Only endpoints of the same namespace are compared
+        let provider = self.topology.providers.get(endpoint_id)?;
+        let namespace = self.topology.namespaces.get(endpoint_id);
+        let (peers, others): (Vec<_>, Vec<_>) = self
+            .topology
+            .providers
+            .iter()
+            .filter(|(id, _)| self.topology.control.as_deref() != Some(id.as_str()))
+            .filter(|(id, _)| self.topology.namespaces.get(*id) == namespace)
+            .partition(|(_, p)| *p == provider);
+        if peers.len() < 2 || !peers.iter().all(|(id, _)| degraded(id)) {
+            return None;
+        }
+        if !others.is_empty() && others.iter().all(|(id, _)| degraded(id)) {
+            return Some(RootCauseHint::LocalNetwork);
+        }
+        Some(RootCauseHint::ProviderSide {
+            provider: provider.clone(),
+        })
     }
 
+    /// Compare an endpoint's fresh metrics with the alert thresholds
+    ///
+    /// Loss and availability alerts fire when a threshold is first crossed and
+    /// re-arm once the endpoint recovers. Score drops compare the new score with
+    /// the median of the recent scores, so one noisy sample does not mask or
+    /// fake a drop, and also fire once until the score is back near the baseline.
+    fn evaluate_alerts(
+        config: &AggregatorConfig,
+        state: &AggregatorState,
+        baseline: Option<f64>,
+        active: &mut ActiveAlerts,
+        now: DateTime<Utc>,
+    ) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+
+        if let (Some(old_score), Some(new_score)) = (baseline, state.last_score) {
+            let dropped = old_score - new_score >= config.alert_score_drop_threshold;
+            if dropped && !active.score_drop {
+                active.score_drop_at = Some(now);
+                alerts.push(Alert::new(
+                    state.endpoint_id.clone(),
+                    AlertType::ScoreDrop { old_score, new_score },
+                ));
+            }
+            active.score_drop = dropped;
+        }
+
+        if state.circular_buffer_short.len() < MIN_ALERT_SAMPLES {
+            return alerts;
+        }
+
+        let lossy = state.cached_loss_short >= config.alert_sustained_loss_threshold;
+        if lossy && !active.sustained_loss {
+            alerts.push(Alert::new(
+                state.endpoint_id.clone(),
+                AlertType::SustainedLoss { loss_percent: state.cached_loss_short },
+            ));
+        }
+        active.sustained_loss = lossy;
 
+        let unavailable = state.cached_avail_short < config.alert_availability_threshold;
+        if unavailable && !active.availability_low {
+            alerts.push(Alert::new(
+                state.endpoint_id.clone(),
+                AlertType::AvailabilityLow { availability: state.cached_avail_short },
+            ));
+        }
+        active.availability_low = unavailable;
+
+        alerts
+    }
+
+    /// Compare the clock offset an NTP probe measured with its threshold
+    ///
+    /// Fires once when the offset first exceeds the threshold either way and
+    /// re-arms once a probe finds the clock back within it. Failed probes
+    /// leave the condition as it was.
+    fn evaluate_clock_offset(
+        config: &AggregatorConfig,
+        state: &AggregatorState,
+        offset_ms: Option<f64>,
+        active: &mut ActiveAlerts,
+    ) -> Option<Alert> {
+        let offset_ms = offset_ms?;
+        let drifted = offset_ms.abs() > config.alert_clock_offset_threshold_ms;
+        let fire = drifted && !active.clock_drift;
+        active.clock_drift = drifted;
+        fire.then(|| Alert::new(state.endpoint_id.clone(), AlertType::ClockDrift { offset_ms }))
+    }
+
+    /// Evaluate the scripts against an endpoint's fresh metrics
+    ///
+    /// Derived metrics are kept with `score_result`. A scripted condition
+    /// fires when it first holds and re-arms once it no longer does, or
+    /// once its script is removed.
+    fn evaluate_scripts(
+        scripts: &ScriptSet,
+        namespace: &str,
+        state: &AggregatorState,
+        score_result: &mut ComprehensiveScoreResult,
+        active: &mut ActiveAlerts,
+    ) -> Vec<Alert> {
+        let outcome = scripts.evaluate(&ScriptInputs {
+            endpoint: &state.endpoint_id,
+            namespace,
+            state,
+            score: score_result.score,
+        });
+        score_result.derived = outcome.metrics;
+
+        let held = std::mem::take(&mut active.scripted);
+        let mut alerts = Vec::new();
+        let holding = outcome.conditions.into_iter().filter(|condition| condition.holds);
+        for ConditionResult { name, severity, .. } in holding {
+            active.scripted.insert(name.clone());
+            if !held.contains(&name) {
+                alerts.push(Alert::new(state.endpoint_id.clone(), AlertType::Scripted { name, severity }));
+            }
+        }
+        alerts
+    }
+
+    /// Process a record pushed by a remote agent, keeping per-vantage state separate
+    pub async fn process_agent_record(&mut self, agent_id: &str, mut record: ProbeRecord) {
+        record.endpoint_id = vantage_key(agent_id, &record.endpoint_id);
+        self.process_probe_record(record).await;
+    }
 
     /// # PERF: Periodic recomputation prevents drift in long-term metrics
     async fn recompute_long_windows(&mut self) {
@@ -158,6 +1245,14 @@ impl StreamingAggregator {
         self.state_map.get(endpoint_id)
     }
 
+    /// Recorded scores of an endpoint, oldest first
+    pub fn score_history(&self, endpoint_id: &str) -> Vec<ScorePoint> {
+        self.state_map
+            .get(endpoint_id)
+            .map(AggregatorState::score_history)
+            .unwrap_or_default()
+    }
+
     pub fn get_endpoint_score(&self, endpoint_id: &str) -> Option<ComprehensiveScoreResult> {
         self.state_map.get(endpoint_id)
             .map(|state| scoring::compute_score(state, &self.config.weights))
@@ -188,17 +1283,65 @@ impl StreamingAggregator {
             healthy_endpoints,
             degraded_endpoints,
             failed_endpoints,
+            buffer_bytes: self.state_map.values().map(AggregatorState::buffer_bytes).sum(),
+            memory_budget_bytes: self
+                .config
+                .memory_budget_bytes
+                .map(|budget| budget / self.shard_count),
+            window_limit: self.window_limit,
         }
     }
 }
 
+/// Short and long window of `config`, cut down to `limit`
+fn limit_windows(config: &AggregatorConfig, limit: Option<(usize, usize)>) -> (usize, usize) {
+    limit.map_or((config.w_short, config.w_long), |(short, long)| {
+        (config.w_short.min(short), config.w_long.min(long))
+    })
+}
+
+/// Whether more than half of the failures in the short window of `state` are DNS failures
+fn dns_failures_dominate(state: &AggregatorState) -> bool {
+    let (failures, dns) = state
+        .circular_buffer_short
+        .iter()
+        .filter(|record| !record.is_success())
+        .map(|record| FailureKind::classify(record.error_code.as_deref().unwrap_or_default(), None))
+        .fold((0_usize, 0_usize), |(failures, dns), kind| {
+            (failures + 1, dns + usize::from(kind == FailureKind::Dns))
+        });
+    dns * 2 > failures
+}
+
+/// Aggregator of the endpoint `endpoint_id` among `shards`
+///
+/// Stable for as long as the process runs, so every record of an endpoint
+/// goes to the same shard.
+#[must_use]
+pub fn shard_index(endpoint_id: &str, shards: usize) -> usize {
+    use std::hash::{Hash, Hasher};
+
+    if shards <= 1 {
+        return 0;
+    }
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    endpoint_id.hash(&mut hasher);
+    usize::try_from(hasher.finish() % shards as u64).unwrap_or(0)
+}
+
 /// High-level health summary across all monitored endpoints
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct AggregatorSummary {
     pub total_endpoints: usize,
     pub healthy_endpoints: usize,
     pub degraded_endpoints: usize,
     pub failed_endpoints: usize,
+    /// Bytes allocated for the ring buffers of all endpoints
+    pub buffer_bytes: usize,
+    /// Bytes the ring buffers may take, if limited
+    pub memory_budget_bytes: Option<usize>,
+    /// Short and long window every endpoint is cut down to for the memory budget
+    pub window_limit: Option<(usize, usize)>,
 }
 
 #[cfg(test)]
@@ -219,6 +1362,11 @@ mod tests {
             rtt_ms: Some(50.0),
             success: true,
             error_code: None,
+            setup_ms: None,
+            clock_offset_ms: None,
+            status: None,
+            sequence: 0,
+            monotonic_ns: 0,
         };
 
         let record2 = ProbeRecord {
@@ -227,6 +1375,11 @@ mod tests {
             rtt_ms: Some(75.0),
             success: true,
             error_code: None,
+            setup_ms: None,
+            clock_offset_ms: None,
+            status: None,
+            sequence: 0,
+            monotonic_ns: 0,
         };
 
         // Process records
@@ -253,6 +1406,11 @@ mod tests {
                 rtt_ms: Some(20.0 + i as f64), // 20-29ms latency
                 success: true,
                 error_code: None,
+                setup_ms: None,
+                clock_offset_ms: None,
+                status: None,
+                sequence: 0,
+                monotonic_ns: 0,
             };
             aggregator.process_probe_record(record).await;
         }
@@ -261,4 +1419,489 @@ mod tests {
         assert!(score.score >= 80.0); // Should be a good score
         assert!(matches!(score.grade, 'A' | 'B'));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_reloaded_thresholds_keep_window_state() {
+        let config = AggregatorConfig {
+            alert_sustained_loss_threshold: 100.0,
+            alert_availability_threshold: 0.0,
+            ..AggregatorConfig::default()
+        };
+        let (updates, receiver) = watch::channel(config.clone());
+        let (aggregator, mut alerts) = StreamingAggregator::new(config.clone());
+        let (records, probe_receiver) = mpsc::unbounded_channel();
+        tokio::spawn(aggregator.with_config_updates(receiver).start(probe_receiver));
+
+        let record = |success: bool| ProbeRecord {
+            endpoint_id: "test-endpoint".to_string(),
+            timestamp: TimeUtils::now(),
+            rtt_ms: success.then_some(20.0),
+            success,
+            error_code: None,
+            setup_ms: None,
+            clock_offset_ms: None,
+            status: None,
+            sequence: 0,
+            monotonic_ns: 0,
+        };
+        // 10% loss stays below the initial threshold
+        for i in 0..10 {
+            records.send(record(i != 0)).unwrap();
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(alerts.try_recv().is_err());
+
+        updates.send_replace(AggregatorConfig {
+            alert_sustained_loss_threshold: 5.0,
+            ..config
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        // One more record is enough because the earlier ten are still in the window
+        records.send(record(true)).unwrap();
+        let alert = tokio::time::timeout(std::time::Duration::from_secs(1), alerts.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(alert.alert_type, AlertType::SustainedLoss { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_published_topology_applies_to_later_records() {
+        let config = AggregatorConfig {
+            alert_sustained_loss_threshold: 5.0,
+            alert_availability_threshold: 0.0,
+            alert_score_drop_threshold: 100.0,
+            ..AggregatorConfig::default()
+        };
+        let (updates, receiver) = watch::channel(EndpointTopology::default());
+        let (aggregator, mut alerts) = StreamingAggregator::new(config);
+        let (records, probe_receiver) = mpsc::unbounded_channel();
+        tokio::spawn(aggregator.with_topology_updates(receiver).start(probe_receiver));
+
+        // Slow round trips are fine until the endpoint's criteria change
+        for _ in 0..10 {
+            records.send(ProbeRecord::success("api".to_string(), 250.0)).unwrap();
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(alerts.try_recv().is_err());
+
+        updates.send_replace(EndpointTopology {
+            success_criteria: HashMap::from([(
+                "api".to_string(),
+                SuccessCriteria {
+                    max_rtt_ms: Some(100.0),
+                    ..SuccessCriteria::default()
+                },
+            )]),
+            ..EndpointTopology::default()
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        records.send(ProbeRecord::success("api".to_string(), 250.0)).unwrap();
+        let alert = tokio::time::timeout(std::time::Duration::from_secs(1), alerts.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(alert.alert_type, AlertType::SustainedLoss { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_records_failing_success_criteria_count_as_lost() {
+        let criteria = SuccessCriteria {
+            max_rtt_ms: Some(100.0),
+            ..SuccessCriteria::default()
+        };
+        let topology = EndpointTopology {
+            success_criteria: HashMap::from([("api".to_string(), criteria)]),
+            ..EndpointTopology::default()
+        };
+        let (aggregator, _alerts) = StreamingAggregator::new(AggregatorConfig::default());
+        let mut aggregator = aggregator.with_topology(topology);
+
+        let records = [20.0, 30.0, 250.0, 40.0].map(|rtt| ProbeRecord::success("api".to_string(), rtt));
+        aggregator.process_probe_batch(records.to_vec()).await;
+        aggregator.process_probe_record(ProbeRecord::success("web".to_string(), 250.0)).await;
+
+        let state = aggregator.get_endpoint_state("api").unwrap();
+        assert_eq!((state.total_sent_short, state.total_recv_short), (4, 3));
+        assert!((state.cached_loss_short - 25.0).abs() < 1e-9);
+        // The slow round trip still counts toward latency
+        assert_eq!(state.rtts_short.len(), 4);
+        let web = aggregator.get_endpoint_state("web").unwrap();
+        assert_eq!(web.total_recv_short, 1);
+    }
+
+    #[tokio::test]
+    async fn test_namespaces_use_their_own_thresholds() {
+        // Loss never alerts by default, but does with the thresholds of `acme`
+        let config = AggregatorConfig {
+            alert_sustained_loss_threshold: 100.0,
+            alert_availability_threshold: 0.0,
+            namespaces: HashMap::from([(
+                "acme".to_string(),
+                AggregatorOverrides {
+                    sustained_loss_threshold: Some(3.0),
+                    availability_threshold: Some(95.0),
+                    ..AggregatorOverrides::default()
+                },
+            )]),
+            ..AggregatorConfig::default()
+        };
+        let topology = EndpointTopology {
+            namespaces: HashMap::from([("acme/eu".to_string(), "acme".to_string())]),
+            ..EndpointTopology::default()
+        };
+        let (aggregator, mut alerts) = StreamingAggregator::new(config);
+        let mut aggregator = aggregator.with_topology(topology);
+
+        for endpoint in ["eu", "acme/eu"] {
+            for i in 0..MIN_ALERT_SAMPLES {
+                let record = if i < 2 {
+                    ProbeRecord::with_error(endpoint.to_string(), "timeout".to_string())
+                } else {
+                    ProbeRecord::success(endpoint.to_string(), 20.0)
+                };
+                aggregator.process_probe_record(record).await;
+            }
+        }
+
+        let mut raised = Vec::new();
+        while let Ok(alert) = alerts.try_recv() {
+            raised.push((alert.endpoint_id, alert.namespace));
+        }
+        assert!(!raised.is_empty());
+        assert!(raised
+            .iter()
+            .all(|(id, namespace)| id == "acme/eu" && namespace.as_deref() == Some("acme")));
+    }
+
+    #[tokio::test]
+    async fn test_alert_hints_compare_endpoints() {
+        let topology = EndpointTopology {
+            providers: [("aws-1", "AWS"), ("aws-2", "AWS"), ("gcp-1", "GCP")]
+                .into_iter()
+                .map(|(id, provider)| (id.to_string(), provider.to_string()))
+                .collect(),
+            control: Some("control".to_string()),
+            ..EndpointTopology::default()
+        };
+        let failures = |endpoint: &str, error: &str| {
+            (0..MIN_ALERT_SAMPLES)
+                .map(|_| ProbeRecord::with_error(endpoint.to_string(), error.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let hints = |mut alerts: mpsc::UnboundedReceiver<Alert>| {
+            let mut hints = Vec::new();
+            while let Ok(alert) = alerts.try_recv() {
+                hints.push((alert.endpoint_id.clone(), alert.hint));
+            }
+            hints
+        };
+
+        // Both AWS endpoints failing while GCP is fine blames AWS
+        let (aggregator, alerts) = StreamingAggregator::new(AggregatorConfig::default());
+        let mut aggregator = aggregator.with_topology(topology.clone());
+        for id in ["aws-1", "aws-2"] {
+            for record in failures(id, "timeout") {
+                aggregator.process_probe_record(record).await;
+            }
+        }
+        let provider_side = Some(RootCauseHint::ProviderSide { provider: "AWS".to_string() });
+        assert!(hints(alerts).contains(&("aws-2".to_string(), provider_side)));
+
+        // Mostly DNS failures point to the resolver
+        let (aggregator, alerts) = StreamingAggregator::new(AggregatorConfig::default());
+        let mut aggregator = aggregator.with_topology(topology.clone());
+        for record in failures("gcp-1", "DNS resolution") {
+            aggregator.process_probe_record(record).await;
+        }
+        let resolver = hints(alerts);
+        assert!(!resolver.is_empty());
+        assert!(resolver
+            .iter()
+            .all(|(_, hint)| *hint == Some(RootCauseHint::ResolverIssue)));
+
+        // A degraded control endpoint points to the local network
+        let (aggregator, alerts) = StreamingAggregator::new(AggregatorConfig::default());
+        let mut aggregator = aggregator.with_topology(topology);
+        for record in failures("control", "timeout").into_iter().chain(failures("gcp-1", "timeout")) {
+            aggregator.process_probe_record(record).await;
+        }
+        assert!(hints(alerts).contains(&("gcp-1".to_string(), Some(RootCauseHint::LocalNetwork))));
+    }
+
+    #[tokio::test]
+    async fn test_shards_see_each_others_alerts() {
+        let topology = EndpointTopology {
+            control: Some("control".to_string()),
+            ..EndpointTopology::default()
+        };
+        let shards = (2..64)
+            .find(|&shards| shard_index("control", shards) != shard_index("gcp-1", shards))
+            .unwrap();
+        let (aggregator, mut alerts) = StreamingAggregator::new(AggregatorConfig::default());
+        let mut aggregator = aggregator.with_topology(topology);
+        aggregator.process_probe_record(ProbeRecord::success("gcp-1".to_string(), 20.0)).await;
+
+        // Endpoints aggregated before the split move to their shard
+        let mut split = aggregator.split(shards);
+        let gcp = shard_index("gcp-1", shards);
+        let control = shard_index("control", shards);
+        assert!(split[gcp].get_endpoint_state("gcp-1").is_some());
+        let endpoints: usize = split.iter().map(|shard| shard.get_summary_stats().total_endpoints).sum();
+        assert_eq!(endpoints, 1);
+
+        // The control endpoint's shard tells the other one about its failures
+        for _ in 0..MIN_ALERT_SAMPLES {
+            split[control]
+                .process_probe_record(ProbeRecord::with_error("control".to_string(), "timeout".to_string()))
+                .await;
+        }
+        for _ in 0..MIN_ALERT_SAMPLES {
+            split[gcp]
+                .process_probe_record(ProbeRecord::with_error("gcp-1".to_string(), "timeout".to_string()))
+                .await;
+        }
+        let mut hints = Vec::new();
+        while let Ok(alert) = alerts.try_recv() {
+            hints.push((alert.endpoint_id.clone(), alert.hint));
+        }
+        assert!(hints.contains(&("gcp-1".to_string(), Some(RootCauseHint::LocalNetwork))));
+    }
+
+    #[tokio::test]
+    async fn test_score_drop_uses_rolling_baseline() {
+        let config = AggregatorConfig {
+            alert_sustained_loss_threshold: 100.0,
+            alert_availability_threshold: 0.0,
+            score_history_len: 8,
+            ..AggregatorConfig::default()
+        };
+        let (mut aggregator, mut alerts) = StreamingAggregator::new(config);
+        let record = |success: bool| ProbeRecord {
+            endpoint_id: "test-endpoint".to_string(),
+            timestamp: TimeUtils::now(),
+            rtt_ms: success.then_some(20.0),
+            success,
+            error_code: None,
+            setup_ms: None,
+            clock_offset_ms: None,
+            status: None,
+            sequence: 0,
+            monotonic_ns: 0,
+        };
+
+        for _ in 0..20 {
+            aggregator.process_probe_record(record(true)).await;
+        }
+        for _ in 0..10 {
+            aggregator.process_probe_record(record(false)).await;
+        }
+        // The outage fires once against the healthy median, not on every lower sample
+        let mut drops = Vec::new();
+        while let Ok(alert) = alerts.try_recv() {
+            drops.push(alert.alert_type);
+        }
+        let [AlertType::ScoreDrop { old_score, new_score }] = drops.as_slice() else {
+            panic!("expected one score drop, got {drops:?}");
+        };
+        assert!(*old_score >= 90.0);
+        assert!(old_score - new_score >= 20.0);
+
+        // History is bounded and ordered oldest first
+        let history = aggregator.score_history("test-endpoint");
+        assert_eq!(history.len(), 8);
+        assert!(history.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+        assert!(aggregator.score_history("unknown").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_clock_drift_fires_once_per_episode() {
+        let (mut aggregator, mut alerts) = StreamingAggregator::new(AggregatorConfig::default());
+        let record = |offset_ms: Option<f64>| ProbeRecord {
+            clock_offset_ms: offset_ms,
+            ..ProbeRecord::success("pool.ntp.org".to_string(), 20.0)
+        };
+
+        for offset_ms in [Some(12.0), Some(-800.0), None, Some(900.0), Some(40.0), Some(700.0)] {
+            aggregator.process_probe_record(record(offset_ms)).await;
+        }
+        let mut drifts = Vec::new();
+        while let Ok(alert) = alerts.try_recv() {
+            if let AlertType::ClockDrift { offset_ms } = alert.alert_type {
+                drifts.push(offset_ms);
+            }
+        }
+        // A probe without an offset and a drift that persists do not fire again
+        assert_eq!(drifts, [-800.0, 700.0]);
+    }
+
+    #[cfg(feature = "scripting")]
+    #[tokio::test]
+    async fn test_scripted_conditions_fire_once_per_episode() {
+        let script = |name: &str, condition: Option<&str>, metric: Option<&str>| ScriptSettings {
+            name: name.to_string(),
+            condition: condition.map(str::to_string),
+            metric: metric.map(str::to_string),
+            severity: crate::models::AlertSeverity::Critical,
+        };
+        let scores = Arc::new(RwLock::new(HashMap::new()));
+        let (aggregator, mut alerts) = StreamingAggregator::new(AggregatorConfig {
+            w_short: 3,
+            alert_score_drop_threshold: 1000.0,
+            scripts: vec![
+                script("double_p50", None, Some("p50_ms * 2.0")),
+                script("slow", Some("double_p50 > 200.0"), None),
+            ],
+            ..AggregatorConfig::default()
+        });
+        let mut aggregator = aggregator.with_score_snapshot(Arc::clone(&scores));
+
+        for rtt_ms in [20.0, 20.0, 20.0, 300.0, 300.0, 300.0, 20.0, 20.0, 300.0, 300.0] {
+            aggregator.process_probe_record(ProbeRecord::success("api".to_string(), rtt_ms)).await;
+        }
+        let mut fired = 0;
+        while let Ok(alert) = alerts.try_recv() {
+            assert_eq!(alert.severity(), crate::models::AlertSeverity::Critical);
+            assert!(matches!(alert.alert_type, AlertType::Scripted { ref name, .. } if name == "slow"));
+            fired += 1;
+        }
+        // Fired when the median first turned slow, and again after recovering
+        assert_eq!(fired, 2);
+        let derived = scores.read().await["api"].derived.clone();
+        assert!((derived["double_p50"] - 600.0).abs() < 1e-9);
+
+        // Removing the script resolves its condition
+        aggregator.update_config(AggregatorConfig { w_short: 3, ..AggregatorConfig::default() });
+        aggregator.process_probe_record(ProbeRecord::success("api".to_string(), 300.0)).await;
+        assert!(aggregator.active_alerts["api"].scripted.is_empty());
+        assert!(scores.read().await["api"].derived.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resolutions_and_health_changes_are_published() {
+        let events = Arc::new(EventBus::new(100));
+        let mut changes = events.subscribe(
+            crate::events::EventFilter::all().kinds([EventKind::AlertResolved, EventKind::HealthChanged]),
+        );
+        let (aggregator, _alerts) = StreamingAggregator::new(AggregatorConfig {
+            w_short: 10,
+            alert_score_drop_threshold: 1000.0,
+            alert_sustained_loss_threshold: 100.0,
+            alert_availability_threshold: 95.0,
+            ..AggregatorConfig::default()
+        });
+        let mut aggregator = aggregator.with_events(events);
+        let success = || ProbeRecord::success("api".to_string(), 20.0);
+
+        aggregator.process_probe_batch((0..10).map(|_| success()).collect()).await;
+        assert!(changes.try_recv().is_none());
+
+        aggregator.process_probe_record(ProbeRecord::timeout("api".to_string())).await;
+        assert!(matches!(changes.try_recv(), Some(MonitoringEvent::HealthChanged { degraded: true, .. })));
+
+        // Recovered once the failure leaves the short window
+        for _ in 0..10 {
+            aggregator.process_probe_record(success()).await;
+        }
+        assert!(matches!(
+            changes.try_recv(),
+            Some(MonitoringEvent::AlertResolved { kind: "availability_low", .. })
+        ));
+        assert!(matches!(changes.try_recv(), Some(MonitoringEvent::HealthChanged { degraded: false, .. })));
+        assert!(changes.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_batches_rescore_each_endpoint_once() {
+        let scores = Arc::new(RwLock::new(HashMap::new()));
+        let (aggregator, _alerts) = StreamingAggregator::new(AggregatorConfig::default());
+        let mut aggregator = aggregator.with_score_snapshot(Arc::clone(&scores));
+        let records: Vec<ProbeRecord> = (0..30)
+            .map(|i| ProbeRecord::success(format!("endpoint-{}", i % 3), 20.0 + f64::from(i)))
+            .collect();
+        aggregator.process_probe_batch(records).await;
+
+        for endpoint in ["endpoint-0", "endpoint-1", "endpoint-2"] {
+            let state = aggregator.get_endpoint_state(endpoint).unwrap();
+            assert_eq!(state.total_sent_short, 10);
+            assert_eq!(state.rtts_short.len(), 10);
+            assert!(state.cached_p50_short > 20.0);
+            assert_eq!(aggregator.score_history(endpoint).len(), 1);
+        }
+        assert_eq!(scores.read().await.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_added_by_time_then_sequence() {
+        let (mut aggregator, _alerts) = StreamingAggregator::new(AggregatorConfig::default());
+        let mut records: Vec<ProbeRecord> = (0..4)
+            .map(|i| ProbeRecord::success("test-endpoint".to_string(), 20.0 + f64::from(i)))
+            .collect();
+        let start = records[0].timestamp;
+        for (i, (offset_s, sequence)) in [(0, 9), (1, 0), (1, 3), (2, 0)].into_iter().enumerate() {
+            records[i].timestamp = start + chrono::Duration::seconds(offset_s);
+            records[i].sequence = sequence;
+        }
+        records.swap(0, 3);
+        aggregator.process_probe_batch(records).await;
+
+        let window: Vec<&ProbeRecord> = aggregator
+            .get_endpoint_state("test-endpoint")
+            .unwrap()
+            .circular_buffer_long
+            .iter()
+            .collect();
+        assert_eq!(window.len(), 4);
+        assert_eq!(
+            window.iter().map(|record| record.sequence).collect::<Vec<_>>(),
+            [1, 2, 3, 4]
+        );
+        assert!(window.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+        assert_eq!(
+            window.iter().map(|record| record.rtt_ms.unwrap()).collect::<Vec<_>>(),
+            [20.0, 21.0, 22.0, 23.0]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memory_budget_cuts_windows() {
+        let config = AggregatorConfig::default();
+        let budget = 4 * AggregatorState::full_buffer_bytes(60, 360, config.score_history_len);
+        let (mut aggregator, _alerts) = StreamingAggregator::new(AggregatorConfig {
+            memory_budget_bytes: Some(budget),
+            ..config
+        });
+        let records = |endpoint: usize| {
+            let endpoint = format!("endpoint-{endpoint}");
+            (0..400).map(move |_| ProbeRecord::success(endpoint.clone(), 20.0))
+        };
+        for endpoint in 0..3 {
+            for record in records(endpoint) {
+                aggregator.process_probe_record(record).await;
+            }
+        }
+        // The third endpoint halved every long window, keeping the newest probes
+        assert_eq!(aggregator.get_summary_stats().window_limit, Some((60, 360)));
+        let state = aggregator.get_endpoint_state("endpoint-0").unwrap();
+        assert_eq!(state.circular_buffer_long.capacity(), 360);
+        assert_eq!(state.total_sent_long, 360);
+
+        for endpoint in 3..5 {
+            for record in records(endpoint) {
+                aggregator.process_probe_record(record).await;
+            }
+        }
+        let summary = aggregator.get_summary_stats();
+        assert_eq!(summary.window_limit, Some((60, 180)));
+        assert_eq!(summary.memory_budget_bytes, Some(budget));
+        assert!(summary.buffer_bytes <= budget);
+
+        // Lifting the budget gives the windows their configured sizes back
+        aggregator.update_config(AggregatorConfig::default());
+        assert_eq!(aggregator.get_summary_stats().window_limit, None);
+        let state = aggregator.get_endpoint_state("endpoint-4").unwrap();
+        assert_eq!(state.circular_buffer_long.capacity(), 720);
+    }
+}
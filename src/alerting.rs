@@ -0,0 +1,381 @@
+//! Alert correlation
+//!
+//! A local network or ISP problem makes every endpoint cross its thresholds
+//! at once. [`AlertManager`] holds alerts back for a short window; when
+//! enough distinct endpoints alert within it, they are reported as a single
+//! widespread degradation [`Incident`] instead of one notification each.
+//! Alerts arriving while an incident is open join it silently.
+//!
+//! [`AlertRouter`] keeps one manager per namespace, so alerts of one tenant
+//! never group with another's and each namespace can set its own window.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CloudPingError, Result};
+use crate::models::utils::generate_uuid;
+use crate::models::{Alert, AlertSeverity};
+
+/// Alert grouping read from `[monitoring.correlation]` in the application config
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CorrelationSettings {
+    /// How long alerts are held back, and how close together alerts must be to group
+    #[serde(with = "humantime_serde", default = "default_correlation_window")]
+    pub window: Duration,
+    /// Distinct endpoints alerting within the window that make an incident
+    #[serde(default = "default_min_endpoints")]
+    pub min_endpoints: usize,
+}
+
+const fn default_correlation_window() -> Duration {
+    Duration::from_secs(30)
+}
+
+const fn default_min_endpoints() -> usize {
+    3
+}
+
+impl Default for CorrelationSettings {
+    fn default() -> Self {
+        Self {
+            window: default_correlation_window(),
+            min_endpoints: default_min_endpoints(),
+        }
+    }
+}
+
+impl CorrelationSettings {
+    /// # Errors
+    /// Returns a validation error naming the first invalid setting
+    pub fn validate(&self) -> Result<()> {
+        if self.min_endpoints < 2 {
+            return Err(CloudPingError::validation(
+                "monitoring.correlation.min_endpoints",
+                "must be at least 2",
+            ));
+        }
+        Ok(())
+    }
+
+    fn window(&self) -> chrono::Duration {
+        chrono::Duration::from_std(self.window).unwrap_or(chrono::Duration::MAX)
+    }
+}
+
+/// Alerts from many endpoints grouped into one event
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Incident {
+    /// Unique incident ID
+    pub id: String,
+    /// Time of the first member alert
+    pub started_at: DateTime<Utc>,
+    /// Time of the latest member alert
+    pub updated_at: DateTime<Utc>,
+    /// Highest severity among the member alerts
+    pub severity: AlertSeverity,
+    /// Endpoints with alerts in the incident, in order of their first alert
+    pub members: Vec<String>,
+    /// Every alert grouped into the incident
+    pub alerts: Vec<Alert>,
+    /// Namespace of the member endpoints, if they are monitored in one
+    #[serde(default)]
+    pub namespace: Option<String>,
+}
+
+impl Incident {
+    fn new(alerts: Vec<Alert>) -> Self {
+        let mut incident = Self {
+            id: generate_uuid(),
+            started_at: alerts
+                .first()
+                .map_or_else(Utc::now, |alert| alert.timestamp),
+            updated_at: DateTime::<Utc>::MIN_UTC,
+            severity: AlertSeverity::Info,
+            members: Vec::new(),
+            alerts: Vec::with_capacity(alerts.len()),
+            namespace: alerts.first().and_then(|alert| alert.namespace.clone()),
+        };
+        for alert in alerts {
+            incident.add(alert);
+        }
+        incident
+    }
+
+    fn add(&mut self, alert: Alert) {
+        if !self.members.contains(&alert.endpoint_id) {
+            self.members.push(alert.endpoint_id.clone());
+        }
+        self.severity = self.severity.max(alert.severity());
+        self.updated_at = self.updated_at.max(alert.timestamp);
+        self.alerts.push(alert);
+    }
+
+    /// One-line description for notifications
+    #[must_use]
+    pub fn summary(&self) -> String {
+        format!(
+            "Widespread degradation: {} endpoints alerting",
+            self.members.len()
+        )
+    }
+}
+
+/// What should be passed on to subscribers
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Notification {
+    /// An alert that did not correlate with others
+    Alert(Alert),
+    /// A newly opened incident
+    Incident(Incident),
+}
+
+/// Groups alerts that occur together across many endpoints
+///
+/// Time is taken from alert timestamps and the `now` passed to
+/// [`AlertManager::flush`], so replayed alerts group the same way as live ones.
+#[derive(Debug)]
+pub struct AlertManager {
+    settings: CorrelationSettings,
+    pending: Vec<Alert>,
+    open: Option<Incident>,
+}
+
+impl AlertManager {
+    /// Create a manager grouping alerts by `settings`
+    #[must_use]
+    pub const fn new(settings: CorrelationSettings) -> Self {
+        Self {
+            settings,
+            pending: Vec::new(),
+            open: None,
+        }
+    }
+
+    /// Incident currently collecting alerts, if any
+    #[must_use]
+    pub const fn open_incident(&self) -> Option<&Incident> {
+        self.open.as_ref()
+    }
+
+    /// Take in an alert; returns a new incident once enough endpoints alert together
+    pub fn ingest(&mut self, alert: Alert) -> Vec<Notification> {
+        let window = self.settings.window();
+        if let Some(incident) = &mut self.open {
+            if alert.timestamp - incident.updated_at <= window {
+                incident.add(alert);
+                return Vec::new();
+            }
+            self.open = None;
+        }
+
+        // Alerts too old to group with this one are released first
+        let mut released = self.release_before(earlier_by(alert.timestamp, window));
+        self.pending.push(alert);
+
+        let mut endpoints: Vec<&str> = self
+            .pending
+            .iter()
+            .map(|alert| alert.endpoint_id.as_str())
+            .collect();
+        endpoints.sort_unstable();
+        endpoints.dedup();
+        if endpoints.len() >= self.settings.min_endpoints {
+            let incident = Incident::new(std::mem::take(&mut self.pending));
+            self.open = Some(incident.clone());
+            released.push(Notification::Incident(incident));
+        }
+        released
+    }
+
+    /// Release alerts held back for a full window and close a quiet incident
+    pub fn flush(&mut self, now: DateTime<Utc>) -> Vec<Notification> {
+        let window = self.settings.window();
+        if self
+            .open
+            .as_ref()
+            .is_some_and(|incident| now - incident.updated_at > window)
+        {
+            self.open = None;
+        }
+        self.release_before(earlier_by(now, window))
+    }
+
+    /// Release every alert still held back, as when shutting down
+    pub fn drain(&mut self) -> Vec<Notification> {
+        self.open = None;
+        std::mem::take(&mut self.pending)
+            .into_iter()
+            .map(Notification::Alert)
+            .collect()
+    }
+
+    /// Alerts held back since before `cutoff`, as individual notifications
+    fn release_before(&mut self, cutoff: DateTime<Utc>) -> Vec<Notification> {
+        let (expired, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|alert| alert.timestamp <= cutoff);
+        self.pending = pending;
+        expired.into_iter().map(Notification::Alert).collect()
+    }
+}
+
+/// Alert managers for the default namespace and each configured one
+#[derive(Debug)]
+pub struct AlertRouter {
+    default: CorrelationSettings,
+    namespaces: HashMap<String, CorrelationSettings>,
+    managers: HashMap<Option<String>, AlertManager>,
+}
+
+impl AlertRouter {
+    /// Group alerts by `default`, or by the settings of their namespace in `namespaces`
+    #[must_use]
+    pub fn new(default: CorrelationSettings, namespaces: HashMap<String, CorrelationSettings>) -> Self {
+        Self {
+            default,
+            namespaces,
+            managers: HashMap::new(),
+        }
+    }
+
+    /// Take in an alert through the manager of its namespace
+    pub fn ingest(&mut self, alert: Alert) -> Vec<Notification> {
+        let manager = self.managers.entry(alert.namespace.clone()).or_insert_with(|| {
+            let settings = alert
+                .namespace
+                .as_ref()
+                .and_then(|namespace| self.namespaces.get(namespace))
+                .unwrap_or(&self.default);
+            AlertManager::new(settings.clone())
+        });
+        manager.ingest(alert)
+    }
+
+    /// Flush the manager of every namespace
+    pub fn flush(&mut self, now: DateTime<Utc>) -> Vec<Notification> {
+        self.managers
+            .values_mut()
+            .flat_map(|manager| manager.flush(now))
+            .collect()
+    }
+
+    /// Release every alert still held back by any namespace's manager
+    pub fn drain(&mut self) -> Vec<Notification> {
+        self.managers.values_mut().flat_map(AlertManager::drain).collect()
+    }
+}
+
+/// `time` moved back by `window`, saturating at the earliest representable time
+fn earlier_by(time: DateTime<Utc>, window: chrono::Duration) -> DateTime<Utc> {
+    time.checked_sub_signed(window)
+        .unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AlertType;
+
+    fn alert_at(endpoint: &str, at: DateTime<Utc>) -> Alert {
+        let mut alert = Alert::new(
+            endpoint.to_string(),
+            AlertType::SustainedLoss { loss_percent: 12.0 },
+        );
+        alert.timestamp = at;
+        alert
+    }
+
+    #[test]
+    fn test_simultaneous_alerts_become_one_incident() {
+        let start = Utc::now();
+        let seconds = |s| start + chrono::Duration::seconds(s);
+        let mut manager = AlertManager::new(CorrelationSettings::default());
+
+        assert!(manager.ingest(alert_at("eu", seconds(0))).is_empty());
+        assert!(manager.ingest(alert_at("eu", seconds(1))).is_empty());
+        assert!(manager.ingest(alert_at("us", seconds(2))).is_empty());
+        let notifications = manager.ingest(alert_at("ap", seconds(3)));
+        let [Notification::Incident(incident)] = notifications.as_slice() else {
+            panic!("expected one incident, got {notifications:?}");
+        };
+        assert_eq!(incident.members, ["eu", "us", "ap"]);
+        assert_eq!(incident.alerts.len(), 4);
+        assert_eq!(incident.severity, AlertSeverity::Critical);
+
+        // Later alerts join the open incident without notifying
+        assert!(manager.ingest(alert_at("sa", seconds(20))).is_empty());
+        assert_eq!(manager.open_incident().unwrap().members.len(), 4);
+
+        // The incident closes once quiet for a full window
+        assert!(manager.flush(seconds(60)).is_empty());
+        assert!(manager.open_incident().is_none());
+    }
+
+    #[test]
+    fn test_isolated_alerts_are_released_after_window() {
+        let start = Utc::now();
+        let seconds = |s| start + chrono::Duration::seconds(s);
+        let mut manager = AlertManager::new(CorrelationSettings::default());
+
+        assert!(manager.ingest(alert_at("eu", seconds(0))).is_empty());
+        assert!(manager.flush(seconds(10)).is_empty());
+        let released = manager.flush(seconds(30));
+        assert!(
+            matches!(released.as_slice(), [Notification::Alert(alert)] if alert.endpoint_id == "eu")
+        );
+
+        // An alert outside the window releases earlier ones instead of grouping
+        manager.ingest(alert_at("us", seconds(40)));
+        manager.ingest(alert_at("ap", seconds(45)));
+        let released = manager.ingest(alert_at("sa", seconds(80)));
+        assert_eq!(released.len(), 2);
+        assert!(manager.open_incident().is_none());
+
+        // Shutting down releases what is still held back
+        assert_eq!(manager.drain().len(), 1);
+        assert!(manager.drain().is_empty());
+
+        assert!(CorrelationSettings {
+            min_endpoints: 1,
+            ..CorrelationSettings::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_router_keeps_namespaces_apart() {
+        let start = Utc::now();
+        let seconds = |s| start + chrono::Duration::seconds(s);
+        let in_namespace = |endpoint: &str, namespace: &str, at| Alert {
+            namespace: Some(namespace.to_string()),
+            ..alert_at(endpoint, at)
+        };
+        let strict = CorrelationSettings {
+            min_endpoints: 2,
+            ..CorrelationSettings::default()
+        };
+        let mut router = AlertRouter::new(
+            CorrelationSettings::default(),
+            HashMap::from([("acme".to_string(), strict)]),
+        );
+
+        // Three endpoints alert together, but no namespace has enough of them
+        assert!(router.ingest(in_namespace("eu", "globex", seconds(0))).is_empty());
+        assert!(router.ingest(alert_at("us", seconds(1))).is_empty());
+        assert!(router.ingest(in_namespace("ap", "acme", seconds(2))).is_empty());
+        assert_eq!(router.flush(seconds(40)).len(), 3);
+
+        // Two are enough in a namespace with its own settings
+        router.ingest(in_namespace("eu", "acme", seconds(50)));
+        let notifications = router.ingest(in_namespace("us", "acme", seconds(51)));
+        let [Notification::Incident(incident)] = notifications.as_slice() else {
+            panic!("expected one incident, got {notifications:?}");
+        };
+        assert_eq!(incident.namespace.as_deref(), Some("acme"));
+    }
+}
@@ -0,0 +1,419 @@
+//! ASN enrichment of tested endpoints
+//!
+//! Resolves each endpoint's host and looks its address up in a local MMDB
+//! file (see [`crate::mmdb`]), such as `GeoLite2-ASN.mmdb`, and, for addresses the file does not
+//! cover or without one, through Team Cymru's IP-to-ASN DNS service. Both sources
+//! are optional; enrichment is off unless one is configured, and a failed
+//! lookup leaves the result without ASN information rather than failing the
+//! run.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+use tracing::{debug, warn};
+
+use crate::config::AppConfig;
+use crate::error::{CloudPingError, Result};
+use crate::mmdb::{MmdbReader, MmdbRecord, MmdbValue};
+use crate::models::AsnInfo;
+use crate::network::NetworkTester;
+
+/// DNS server used for Team Cymru lookups when none is set or found in `/etc/resolv.conf`
+pub const FALLBACK_DNS_SERVER: &str = "1.1.1.1:53";
+
+/// Sources of ASN enrichment
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AsnConfig {
+    /// MMDB file mapping addresses to autonomous systems, e.g. `GeoLite2-ASN.mmdb`
+    #[serde(default)]
+    pub mmdb_file: Option<String>,
+    /// Look addresses up through Team Cymru's DNS service
+    #[serde(default)]
+    pub cymru: bool,
+    /// DNS server for Team Cymru lookups; the system resolver's when unset
+    #[serde(default)]
+    pub dns_server: Option<String>,
+}
+
+impl AsnConfig {
+    /// Whether any enrichment source is configured
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        self.mmdb_file.is_some() || self.cymru
+    }
+
+    /// # Errors
+    /// Returns a validation error when `dns_server` is not an address
+    pub fn validate(&self) -> Result<()> {
+        match &self.dns_server {
+            Some(server) if parse_dns_server(server).is_none() => Err(CloudPingError::validation(
+                "asn.dns_server",
+                "must be an IP address, optionally with a port",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parse `1.1.1.1`, `1.1.1.1:53`, `2606:4700::1111` or `[2606:4700::1111]:53`
+fn parse_dns_server(server: &str) -> Option<SocketAddr> {
+    server
+        .parse::<SocketAddr>()
+        .ok()
+        .or_else(|| server.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, 53)))
+}
+
+/// First `nameserver` of `/etc/resolv.conf`
+fn system_dns_server() -> Option<SocketAddr> {
+    std::fs::read_to_string("/etc/resolv.conf")
+        .ok()?
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .find_map(|server| parse_dns_server(server.trim()))
+}
+
+/// Resolves endpoints to the autonomous system announcing them
+pub struct AsnResolver {
+    mmdb: Option<MmdbReader>,
+    cymru: Option<CymruClient>,
+    cache: Mutex<HashMap<IpAddr, Option<AsnInfo>>>,
+}
+
+impl AsnResolver {
+    /// Create a resolver from the `asn` settings, `None` when enrichment is off
+    ///
+    /// # Errors
+    /// Returns an error when the MMDB file cannot be read or parsed
+    pub fn from_config(config: &AppConfig) -> Result<Option<Self>> {
+        let settings = &config.asn;
+        if !settings.is_enabled() {
+            return Ok(None);
+        }
+        let mmdb = settings
+            .mmdb_file
+            .as_ref()
+            .map(|path| MmdbReader::open(Path::new(path)))
+            .transpose()?;
+        let cymru = settings.cymru.then(|| {
+            let server = settings
+                .dns_server
+                .as_deref()
+                .and_then(parse_dns_server)
+                .or_else(system_dns_server)
+                .or_else(|| parse_dns_server(FALLBACK_DNS_SERVER))
+                .unwrap_or_else(|| SocketAddr::from(([1, 1, 1, 1], 53)));
+            CymruClient::new(server, config.get_timeout())
+        });
+        Ok(Some(Self {
+            mmdb,
+            cymru,
+            cache: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// Autonomous system of the host `url` points at, `None` when the host
+    /// does not resolve or no source knows its address
+    pub async fn lookup_url(&self, url: &str) -> Option<AsnInfo> {
+        let ip = match NetworkTester::resolve_host(url).await {
+            Ok(ip) => ip,
+            Err(e) => {
+                warn!("Could not resolve {url} for ASN lookup: {e}");
+                return None;
+            }
+        };
+        self.lookup(ip).await
+    }
+
+    /// Autonomous system of `ip`, from the MMDB first and Team Cymru second
+    pub async fn lookup(&self, ip: IpAddr) -> Option<AsnInfo> {
+        if let Some(cached) = self.cache.lock().unwrap_or_else(PoisonError::into_inner).get(&ip) {
+            return cached.clone();
+        }
+        let mut info = self.mmdb.as_ref().and_then(|mmdb| Self::lookup_mmdb(mmdb, ip));
+        if info.is_none() {
+            if let Some(cymru) = &self.cymru {
+                info = cymru.lookup(ip).await.unwrap_or_else(|e| {
+                    warn!("Team Cymru lookup of {ip} failed: {e}");
+                    None
+                });
+            }
+        }
+        debug!("ASN of {ip}: {info:?}");
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(ip, info.clone());
+        info
+    }
+
+    /// Autonomous system of `ip` in a GeoLite2-ASN (`autonomous_system_number`,
+    /// `autonomous_system_organization`) or ipinfo (`asn`, `name`, `country`) database
+    fn lookup_mmdb(mmdb: &MmdbReader, ip: IpAddr) -> Option<AsnInfo> {
+        let MmdbRecord { value, network } = mmdb.lookup(ip)?;
+        let asn = value
+            .get("autonomous_system_number")
+            .and_then(MmdbValue::as_u32)
+            .or_else(|| {
+                // ipinfo stores the number as "AS15169"
+                let asn = value.get("asn")?;
+                asn.as_u32()
+                    .or_else(|| asn.as_str()?.trim_start_matches("AS").parse().ok())
+            })?;
+        let organization = value
+            .get("autonomous_system_organization")
+            .or_else(|| value.get("name"))
+            .and_then(MmdbValue::as_str)
+            .unwrap_or_default()
+            .to_string();
+        Some(AsnInfo {
+            ip,
+            asn,
+            organization,
+            prefix: Some(network),
+            country: value.get("country").and_then(MmdbValue::as_str).map(str::to_string),
+        })
+    }
+}
+
+/// Looks addresses up through Team Cymru's IP-to-ASN DNS service
+///
+/// `4.3.2.1.origin.asn.cymru.com TXT` answers `"15169 | 1.2.3.0/24 | US | arin | 2000-03-30"`
+/// and `AS15169.asn.cymru.com TXT` answers `"15169 | US | arin | 2000-03-30 | GOOGLE - Google LLC, US"`.
+#[derive(Debug, Clone)]
+pub struct CymruClient {
+    server: SocketAddr,
+    timeout: Duration,
+}
+
+impl CymruClient {
+    /// Create a client querying the DNS server `server`
+    #[must_use]
+    pub const fn new(server: SocketAddr, timeout: Duration) -> Self {
+        Self { server, timeout }
+    }
+
+    /// Autonomous system announcing `ip`, `None` when Team Cymru has no origin for it
+    ///
+    /// # Errors
+    /// Returns an error when the DNS server does not answer or answers with an error
+    pub async fn lookup(&self, ip: IpAddr) -> Result<Option<AsnInfo>> {
+        let Some(origin) = self.query_txt(&Self::origin_name(ip)).await? else {
+            return Ok(None);
+        };
+        let Some((asn, prefix, country)) = Self::parse_origin(&origin) else {
+            return Ok(None);
+        };
+        let organization = self
+            .query_txt(&format!("AS{asn}.asn.cymru.com"))
+            .await?
+            .and_then(|description| {
+                description
+                    .rsplit('|')
+                    .next()
+                    .map(|name| name.trim().to_string())
+            })
+            .unwrap_or_default();
+        Ok(Some(AsnInfo {
+            ip,
+            asn,
+            organization,
+            prefix,
+            country,
+        }))
+    }
+
+    /// Origin query name of `ip`: its reversed octets or nibbles under the Cymru zone
+    #[must_use]
+    pub fn origin_name(ip: IpAddr) -> String {
+        match ip {
+            IpAddr::V4(v4) => {
+                let [a, b, c, d] = v4.octets();
+                format!("{d}.{c}.{b}.{a}.origin.asn.cymru.com")
+            }
+            IpAddr::V6(v6) => {
+                let nibbles: Vec<String> = v6
+                    .octets()
+                    .iter()
+                    .rev()
+                    .flat_map(|byte| [byte & 0x0F, byte >> 4])
+                    .map(|nibble| format!("{nibble:x}"))
+                    .collect();
+                format!("{}.origin6.asn.cymru.com", nibbles.join("."))
+            }
+        }
+    }
+
+    /// ASN, prefix and country of an origin answer; the first ASN when several announce the prefix
+    #[must_use]
+    pub fn parse_origin(txt: &str) -> Option<(u32, Option<String>, Option<String>)> {
+        let mut fields = txt.split('|').map(str::trim);
+        let asn = fields.next()?.split_whitespace().next()?.parse().ok()?;
+        let non_empty = |field: Option<&str>| field.filter(|f| !f.is_empty()).map(str::to_string);
+        let prefix = non_empty(fields.next());
+        let country = non_empty(fields.next());
+        Some((asn, prefix, country))
+    }
+
+    /// First TXT record of `name`, `None` when the name does not exist
+    async fn query_txt(&self, name: &str) -> Result<Option<String>> {
+        let id: u16 = rand::random();
+        let query = Self::encode_query(id, name);
+        let bind: SocketAddr = if self.server.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = UdpSocket::bind(bind).await?;
+        socket.send_to(&query, self.server).await?;
+        let mut buffer = [0u8; 1500];
+        let len = tokio::time::timeout(self.timeout, socket.recv(&mut buffer))
+            .await
+            .map_err(|_| {
+                CloudPingError::timeout(u64::try_from(self.timeout.as_millis()).unwrap_or(u64::MAX))
+            })??;
+        Self::decode_txt(&buffer[..len], id).map_err(|e| {
+            CloudPingError::network(format!("Bad DNS answer for {name} from {}: {e}", self.server))
+        })
+    }
+
+    /// DNS query for the TXT records of `name`, with recursion desired
+    #[must_use]
+    pub fn encode_query(id: u16, name: &str) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(name.len() + 18);
+        packet.extend_from_slice(&id.to_be_bytes());
+        packet.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        for label in name.trim_end_matches('.').split('.') {
+            #[allow(clippy::cast_possible_truncation)] // labels are capped at 63 bytes
+            packet.push(label.len().min(63) as u8);
+            packet.extend_from_slice(&label.as_bytes()[..label.len().min(63)]);
+        }
+        packet.extend_from_slice(&[0, 0, 16, 0, 1]);
+        packet
+    }
+
+    /// First TXT record of a DNS answer to the query with `id`
+    ///
+    /// # Errors
+    /// Returns a message when the answer is malformed, answers another query
+    /// or reports an error other than a missing name
+    pub fn decode_txt(packet: &[u8], id: u16) -> std::result::Result<Option<String>, String> {
+        let u16_at = |at: usize| {
+            packet
+                .get(at..at + 2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .ok_or("answer ends early")
+        };
+        if u16_at(0)? != id {
+            return Err("answer to another query".to_string());
+        }
+        match u16_at(2)? & 0x000F {
+            0 => {}
+            3 => return Ok(None),
+            rcode => return Err(format!("server error {rcode}")),
+        }
+        let questions = u16_at(4)?;
+        let answers = u16_at(6)?;
+        let mut at = 12;
+        for _ in 0..questions {
+            at = Self::skip_name(packet, at)? + 4;
+        }
+        for _ in 0..answers {
+            at = Self::skip_name(packet, at)?;
+            let kind = u16_at(at)?;
+            let len = usize::from(u16_at(at + 8)?);
+            let data = packet.get(at + 10..at + 10 + len).ok_or("answer ends early")?;
+            at += 10 + len;
+            if kind != 16 {
+                continue;
+            }
+            let mut text = String::new();
+            let mut i = 0;
+            while i < data.len() {
+                let part = usize::from(data[i]);
+                let chunk = data.get(i + 1..i + 1 + part).ok_or("TXT record ends early")?;
+                text.push_str(&String::from_utf8_lossy(chunk));
+                i += 1 + part;
+            }
+            return Ok(Some(text));
+        }
+        Ok(None)
+    }
+
+    /// Offset after the (possibly compressed) name at `at`
+    fn skip_name(packet: &[u8], mut at: usize) -> std::result::Result<usize, String> {
+        loop {
+            let len = *packet.get(at).ok_or("name ends early")?;
+            match len {
+                0 => return Ok(at + 1),
+                len if len & 0xC0 == 0xC0 => return Ok(at + 2),
+                len => at += 1 + usize::from(len),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mmdb::testing::{database, map, string, uint32};
+
+    #[test]
+    fn test_mmdb_lookup() {
+        let mmdb = MmdbReader::from_bytes(database(&map(&[
+            ("autonomous_system_number", uint32(64_500)),
+            ("autonomous_system_organization", string("EXAMPLE-NET")),
+        ])))
+        .unwrap();
+        let info = AsnResolver::lookup_mmdb(&mmdb, "10.1.2.3".parse().unwrap()).unwrap();
+        assert_eq!(info.asn, 64_500);
+        assert_eq!(info.organization, "EXAMPLE-NET");
+        assert_eq!(info.prefix.as_deref(), Some("0.0.0.0/1"));
+        assert_eq!(AsnResolver::lookup_mmdb(&mmdb, "192.0.2.1".parse().unwrap()), None);
+
+        let ipinfo = MmdbReader::from_bytes(database(&map(&[
+            ("asn", string("AS64501")),
+            ("name", string("Example Cloud")),
+            ("country", string("DE")),
+        ])))
+        .unwrap();
+        let info = AsnResolver::lookup_mmdb(&ipinfo, "10.1.2.3".parse().unwrap()).unwrap();
+        assert_eq!((info.asn, info.country.as_deref()), (64_501, Some("DE")));
+    }
+
+    #[test]
+    fn test_cymru_dns() {
+        assert_eq!(
+            CymruClient::origin_name("8.8.4.1".parse().unwrap()),
+            "1.4.8.8.origin.asn.cymru.com"
+        );
+        assert!(CymruClient::origin_name("2001:db8::1".parse().unwrap())
+            .starts_with("1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.origin6"));
+        assert_eq!(
+            CymruClient::parse_origin("15169 36040 | 8.8.8.0/24 | US | arin | 2000-03-30"),
+            Some((15169, Some("8.8.8.0/24".to_string()), Some("US".to_string())))
+        );
+        assert_eq!(CymruClient::parse_origin("NA | | | |"), None);
+
+        // Answer the query with a TXT record split in two strings, its name compressed
+        let query = CymruClient::encode_query(7, "1.4.8.8.origin.asn.cymru.com");
+        let mut answer = query.clone();
+        answer[2] = 0x81;
+        answer[3] = 0x80;
+        answer[7] = 1;
+        answer.extend_from_slice(&[0xC0, 12, 0, 16, 0, 1, 0, 0, 0, 60, 0, 12]);
+        answer.extend_from_slice(b"\x0515169\x05 | US");
+        assert_eq!(
+            CymruClient::decode_txt(&answer, 7),
+            Ok(Some("15169 | US".to_string()))
+        );
+        assert!(CymruClient::decode_txt(&answer, 8).is_err());
+        let mut missing = query;
+        missing[3] = 0x83;
+        assert_eq!(CymruClient::decode_txt(&missing, 7), Ok(None));
+    }
+}
@@ -0,0 +1,301 @@
+//! Health badges for wikis and READMEs
+//!
+//! A [`Badge`] is a small flat SVG in the style of shields.io: a grey label
+//! on the left and a score with its grade on the right, colored by grade.
+//! [`write_badges`] writes one badge per region and per provider after a
+//! benchmark, and the API serves the same badges for monitored endpoints.
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::error::Result;
+use crate::models::scoring::score_to_grade;
+use crate::models::RankedResult;
+use crate::report::escape_html;
+
+/// Color of badges without a score
+const PENDING_COLOR: &str = "#9f9f9f";
+
+/// Width in pixels reserved per character; badges are not measured by font
+const CHAR_WIDTH: u32 = 7;
+
+/// Horizontal padding in pixels around each half of a badge
+const PADDING: u32 = 10;
+
+/// Label and value rendered as a flat SVG badge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Badge {
+    label: String,
+    message: String,
+    color: &'static str,
+}
+
+impl Badge {
+    /// Badge showing `score` and `grade`, colored by grade
+    #[must_use]
+    pub fn score(label: &str, score: f64, grade: char) -> Self {
+        Self {
+            label: label.to_string(),
+            message: format!("{score:.1} {grade}"),
+            color: grade_color(grade),
+        }
+    }
+
+    /// Grey badge for a region or endpoint without a score yet
+    #[must_use]
+    pub fn pending(label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            message: "pending".to_string(),
+            color: PENDING_COLOR,
+        }
+    }
+
+    /// Grey badge for a region, endpoint or provider that is not monitored
+    #[must_use]
+    pub fn unknown(label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            message: "unknown".to_string(),
+            color: PENDING_COLOR,
+        }
+    }
+
+    /// Badge text as read by screen readers, e.g. `Frankfurt: 92.4 A`
+    #[must_use]
+    pub fn text(&self) -> String {
+        format!("{}: {}", self.label, self.message)
+    }
+
+    /// Render as a standalone SVG document
+    #[must_use]
+    pub fn render(&self) -> String {
+        let label_width = text_width(&self.label);
+        let message_width = text_width(&self.message);
+        let width = label_width + message_width;
+        let text = escape_html(&self.text());
+        let mut svg = String::new();
+        let _ = write!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"20\" \
+             role=\"img\" aria-label=\"{text}\"><title>{text}</title>\
+             <linearGradient id=\"s\" x2=\"0\" y2=\"100%\">\
+             <stop offset=\"0\" stop-color=\"#bbb\" stop-opacity=\".1\"/>\
+             <stop offset=\"1\" stop-opacity=\".1\"/></linearGradient>\
+             <clipPath id=\"r\"><rect width=\"{width}\" height=\"20\" rx=\"3\" fill=\"#fff\"/></clipPath>\
+             <g clip-path=\"url(#r)\"><rect width=\"{label_width}\" height=\"20\" fill=\"#555\"/>\
+             <rect x=\"{label_width}\" width=\"{message_width}\" height=\"20\" fill=\"{}\"/>\
+             <rect width=\"{width}\" height=\"20\" fill=\"url(#s)\"/></g>\
+             <g fill=\"#fff\" text-anchor=\"middle\" \
+             font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\" font-size=\"11\">\
+             <text x=\"{}\" y=\"14\">{}</text><text x=\"{}\" y=\"14\">{}</text></g></svg>",
+            self.color,
+            label_width / 2,
+            escape_html(&self.label),
+            label_width + message_width / 2,
+            escape_html(&self.message)
+        );
+        svg
+    }
+}
+
+/// Badge color of a grade, from green for A to red for F
+const fn grade_color(grade: char) -> &'static str {
+    match grade {
+        'A' => "#4c1",
+        'B' => "#97ca00",
+        'C' => "#dfb317",
+        'D' => "#fe7d37",
+        _ => "#e05d44",
+    }
+}
+
+fn text_width(text: &str) -> u32 {
+    let chars = u32::try_from(text.chars().count()).unwrap_or(u32::MAX);
+    chars.saturating_mul(CHAR_WIDTH).saturating_add(2 * PADDING)
+}
+
+/// Lowercase file name part of `name`, with runs of other characters as `-`
+///
+/// Letters outside ASCII are kept, so `São Paulo` becomes `são-paulo`.
+#[must_use]
+pub fn badge_slug(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let trimmed = slug.trim_end_matches('-').len();
+    slug.truncate(trimmed);
+    slug
+}
+
+/// File name part of a region, qualified by its provider since providers
+/// share region names such as `Singapore`
+#[must_use]
+pub fn region_badge_slug(provider: &str, name: &str) -> String {
+    if provider.is_empty() {
+        badge_slug(name)
+    } else {
+        badge_slug(&format!("{provider} {name}"))
+    }
+}
+
+/// Badges of a ranking keyed by file name
+///
+/// Each region gets `region-<provider>-<name>.svg`, or `region-<name>.svg`
+/// without a provider, numbered when two regions still share a name; each
+/// provider gets `provider-<name>.svg` showing the average score of its
+/// ranked regions.
+#[must_use]
+pub fn ranking_badges(ranked: &[RankedResult]) -> Vec<(String, Badge)> {
+    let mut providers: Vec<(&str, Vec<f64>)> = Vec::new();
+    let mut taken: HashSet<String> = HashSet::new();
+    let mut badges: Vec<(String, Badge)> = ranked
+        .iter()
+        .map(|(score, name, stats, result)| {
+            if !stats.provider.is_empty() {
+                match providers.iter_mut().find(|(p, _)| *p == stats.provider) {
+                    Some((_, scores)) => scores.push(*score),
+                    None => providers.push((&stats.provider, vec![*score])),
+                }
+            }
+            let slug = region_badge_slug(&stats.provider, name);
+            let file_name = (1..=taken.len() + 1)
+                .map(|n| if n == 1 { format!("region-{slug}.svg") } else { format!("region-{slug}-{n}.svg") })
+                .find(|file_name| !taken.contains(file_name))
+                .unwrap_or_default();
+            taken.insert(file_name.clone());
+            (file_name, Badge::score(name, *score, result.grade))
+        })
+        .collect();
+
+    for (provider, scores) in providers {
+        let average = statistical::mean(&scores);
+        badges.push((
+            format!("provider-{}.svg", badge_slug(provider)),
+            Badge::score(provider, average, score_to_grade(average)),
+        ));
+    }
+    badges
+}
+
+/// Write the badges of a ranking into `dir`, creating it if needed
+///
+/// Returns the number of badges written.
+///
+/// # Errors
+/// Returns an error when the directory or a badge cannot be written
+pub fn write_badges(dir: &Path, ranked: &[RankedResult]) -> Result<usize> {
+    std::fs::create_dir_all(dir)?;
+    let badges = ranking_badges(ranked);
+    for (file_name, badge) in &badges {
+        std::fs::write(dir.join(file_name), badge.render())?;
+    }
+    Ok(badges.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AlgorithmWeights, PingStats, ScoringAdapter};
+
+    #[test]
+    fn test_badge_render() {
+        let badge = Badge::score("AWS <eu>", 92.44, 'A');
+        let svg = badge.render();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.contains("<title>AWS &lt;eu&gt;: 92.4 A</title>"));
+        assert!(svg.contains("fill=\"#4c1\""));
+        assert!(Badge::pending("Paris").render().contains("pending"));
+
+        assert_eq!(badge_slug("Frankfurt (eu-central-1)"), "frankfurt-eu-central-1");
+        assert_eq!(badge_slug("  São Paulo "), "são-paulo");
+        assert_eq!(region_badge_slug("AWS", "Frankfurt"), "aws-frankfurt");
+    }
+
+    #[test]
+    fn test_ranking_badges() {
+        let region = |name: &str, avg: f64, provider: &str| {
+            let mut stats = PingStats::new(10);
+            stats.successful_pings = 10;
+            stats.avg = avg;
+            stats.latencies = vec![avg; 10];
+            stats.provider = provider.to_string();
+            (name.to_string(), stats)
+        };
+        let ranked = ScoringAdapter::get_sorted_results(
+            &[
+                region("Frankfurt", 15.0, "AWS"),
+                region("Sydney", 450.0, "AWS"),
+                region("Local", 5.0, ""),
+            ],
+            &AlgorithmWeights::default(),
+        );
+
+        // Regions without a provider get no provider badge
+        let badges = ranking_badges(&ranked);
+        assert_eq!(badges.len(), 4);
+        assert!(badges.iter().any(|(name, _)| name == "region-local.svg"));
+        assert!(badges.iter().any(|(name, _)| name == "region-aws-frankfurt.svg"));
+        let aws: Vec<f64> = ranked
+            .iter()
+            .filter(|(_, _, stats, _)| stats.provider == "AWS")
+            .map(|(score, _, _, _)| *score)
+            .collect();
+        let average = (aws[0] + aws[1]) / 2.0;
+        assert_eq!(
+            badges[3],
+            (
+                "provider-aws.svg".to_string(),
+                Badge::score("AWS", average, score_to_grade(average))
+            )
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(write_badges(&dir.path().join("badges"), &ranked).unwrap(), 4);
+        assert!(dir.path().join("badges/provider-aws.svg").exists());
+    }
+
+    #[test]
+    fn test_region_badges_do_not_collide() {
+        let region = |name: &str, provider: &str| {
+            let mut stats = PingStats::new(10);
+            stats.successful_pings = 10;
+            stats.avg = 20.0;
+            stats.latencies = vec![20.0; 10];
+            stats.provider = provider.to_string();
+            (name.to_string(), stats)
+        };
+        let ranked = ScoringAdapter::get_sorted_results(
+            &[
+                region("Singapore", "AWS"),
+                region("Singapore", "GCP"),
+                region("Singapore", ""),
+                region("Singapore!", ""),
+            ],
+            &AlgorithmWeights::default(),
+        );
+
+        let badges = ranking_badges(&ranked);
+        let mut names: Vec<&str> = badges
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .filter(|name| name.starts_with("region-"))
+            .collect();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            [
+                "region-aws-singapore.svg",
+                "region-gcp-singapore.svg",
+                "region-singapore-2.svg",
+                "region-singapore.svg"
+            ]
+        );
+    }
+}
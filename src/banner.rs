@@ -0,0 +1,146 @@
+//! Application protocol greetings of infrastructure services
+//!
+//! A TCP connect only shows that the host's kernel accepted the connection;
+//! a mail server or database behind it can still be slow to serve. A banner
+//! probe connects and then waits for the first answer of the service itself:
+//! the SMTP `220` greeting, the IMAP `* OK` greeting, the `+PONG` to a Redis
+//! `PING`, or the Postgres answer to an `SSLRequest`. No credentials are
+//! sent, and the connection is closed right after the answer.
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tracing::debug;
+
+use crate::models::{FailureKind, ProbeType};
+
+/// `SSLRequest` message: length 8, then the request code 80877103
+const POSTGRES_SSL_REQUEST: [u8; 8] = [0, 0, 0, 8, 0x04, 0xD2, 0x16, 0x2F];
+
+/// Longest greeting line read before giving up
+const MAX_LINE_LEN: u64 = 4096;
+
+/// Wait on a connected `stream` for the greeting of the `probe_type` service
+///
+/// # Errors
+/// Returns [`FailureKind::Protocol`] when the service answers with anything
+/// but its greeting, or for a probe type without one, and
+/// [`FailureKind::Connection`] when the connection fails first
+pub async fn greet<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    probe_type: ProbeType,
+) -> Result<(), FailureKind> {
+    let mut stream = BufReader::new(stream);
+    let ready = match probe_type {
+        ProbeType::SMTP => {
+            // Multi-line greetings continue with `220-` up to the last `220 ` line
+            loop {
+                let line = read_line(&mut stream).await?;
+                if !line.starts_with("220-") {
+                    let ready = line.starts_with("220");
+                    if ready {
+                        let _ = stream.write_all(b"QUIT\r\n").await;
+                    }
+                    break ready;
+                }
+            }
+        }
+        ProbeType::IMAP => {
+            let line = read_line(&mut stream).await?;
+            line.starts_with("* OK") || line.starts_with("* PREAUTH")
+        }
+        ProbeType::Redis => {
+            send(&mut stream, b"PING\r\n").await?;
+            let line = read_line(&mut stream).await?;
+            // A server requiring a password still answers, which is all a probe needs
+            line.starts_with("+PONG") || line.starts_with("-NOAUTH")
+        }
+        ProbeType::Postgres => {
+            send(&mut stream, &POSTGRES_SSL_REQUEST).await?;
+            let answer = stream.read_u8().await.map_err(|e| connection_failure(&e))?;
+            // `S` or `N`: the server will or will not use TLS for the session
+            matches!(answer, b'S' | b'N')
+        }
+        ProbeType::TCP
+        | ProbeType::HTTP
+        | ProbeType::ICMP
+        | ProbeType::WebSocket
+        | ProbeType::NTP => false,
+    };
+    if ready {
+        Ok(())
+    } else {
+        debug!("{:?} service answered without its greeting", probe_type);
+        Err(FailureKind::Protocol)
+    }
+}
+
+async fn send<S: AsyncWrite + Unpin>(stream: &mut S, message: &[u8]) -> Result<(), FailureKind> {
+    stream.write_all(message).await.map_err(|e| connection_failure(&e))?;
+    stream.flush().await.map_err(|e| connection_failure(&e))
+}
+
+async fn read_line<S: AsyncRead + Unpin>(stream: &mut BufReader<S>) -> Result<String, FailureKind> {
+    let mut line = String::new();
+    let read = (&mut *stream)
+        .take(MAX_LINE_LEN)
+        .read_line(&mut line)
+        .await
+        .map_err(|e| connection_failure(&e))?;
+    if read == 0 {
+        debug!("Connection closed before the greeting");
+        return Err(FailureKind::Connection);
+    }
+    Ok(line)
+}
+
+fn connection_failure(error: &std::io::Error) -> FailureKind {
+    debug!("Reading the greeting failed: {}", error);
+    FailureKind::Connection
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `greet` against a peer that answers the probe's message with `reply`
+    async fn greet_with(probe_type: ProbeType, reply: &'static [u8]) -> (Result<(), FailureKind>, Vec<u8>) {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let peer = tokio::spawn(async move {
+            let mut received = vec![0u8; 64];
+            let read = if matches!(probe_type, ProbeType::Redis | ProbeType::Postgres) {
+                server.read(&mut received).await.unwrap()
+            } else {
+                0
+            };
+            received.truncate(read);
+            server.write_all(reply).await.unwrap();
+            received
+        });
+        let result = greet(&mut client, probe_type).await;
+        (result, peer.await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_greetings() {
+        let smtp = b"220-mail.example.com ESMTP\r\n220 ready\r\n";
+        assert_eq!(greet_with(ProbeType::SMTP, smtp).await.0, Ok(()));
+        assert_eq!(
+            greet_with(ProbeType::SMTP, b"554 no service\r\n").await.0,
+            Err(FailureKind::Protocol)
+        );
+        assert_eq!(greet_with(ProbeType::IMAP, b"* OK IMAP4rev1 ready\r\n").await.0, Ok(()));
+
+        let (result, sent) = greet_with(ProbeType::Redis, b"+PONG\r\n").await;
+        assert_eq!((result, sent.as_slice()), (Ok(()), b"PING\r\n".as_slice()));
+        assert_eq!(greet_with(ProbeType::Redis, b"-NOAUTH Authentication required.\r\n").await.0, Ok(()));
+
+        let (result, sent) = greet_with(ProbeType::Postgres, b"N").await;
+        assert_eq!((result, sent.as_slice()), (Ok(()), POSTGRES_SSL_REQUEST.as_slice()));
+        assert_eq!(
+            greet_with(ProbeType::Postgres, b"HTTP/1.1 400").await.0,
+            Err(FailureKind::Protocol)
+        );
+
+        // The peer hangs up without a greeting
+        assert_eq!(greet_with(ProbeType::IMAP, b"").await.0, Err(FailureKind::Connection));
+    }
+}
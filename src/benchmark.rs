@@ -6,21 +6,42 @@
 
 use dashmap::DashMap;
 use futures::future::join_all;
+use futures::StreamExt;
 use indicatif::{MultiProgress, ProgressBar};
 use std::sync::Arc;
-use tokio::sync::Semaphore;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, warn};
 
 use crate::{
+    adaptive::ConcurrencyController,
+    asn::AsnResolver,
+    budget::ProbeBudget,
+    carbon::CarbonClient,
     config::AppConfig,
+    control::ControlMonitor,
+    connection_budget::ConnectionBudget,
     data_loader::DataLoader,
     display::DisplayFormatter,
     error::{CloudPingError, Result},
-    models::{CloudProvider, PingStats, Region, TestHistory, AlgorithmWeights, ScoringAdapter},
+    environment::{trace_hops, EnvironmentCapture},
+    models::{BenchmarkPlan, BenchmarkRun, CarbonIntensity, CloudProvider, FailureKind, MeasurementQuality, PingStats, Region, RegionFailure, RegionNotes, RegionPricing, RunReport, RoutePath, SpeedTestEndpoint, TestHistory, AlgorithmWeights, RankedResult, ScoringAdapter},
     network::NetworkTester,
+    pricing::PricingClient,
+    speed_test::SpeedTester,
+    transport::HttpTransport,
     ui_utils::{ProgressBarFactory, DisplayUtils},
 };
 
+/// Hops traced towards a region when `route_tracing` is on
+const ROUTE_TRACE_MAX_TTL: u8 = 30;
+
+/// Time a route trace may take
+const ROUTE_TRACE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(45);
+
+/// Result of one region's test task; `None` when the run was cancelled before any ping
+type RegionOutcome = Result<Option<(String, PingStats)>>;
+
 /// Orchestrates concurrent network testing across multiple regions
 pub struct ConnectionBenchmark {
     config: AppConfig,
@@ -29,6 +50,12 @@ pub struct ConnectionBenchmark {
     test_history: Arc<DashMap<String, TestHistory>>,
     network_tester: NetworkTester,
     progress_factory: ProgressBarFactory,
+    budget: Arc<ProbeBudget>,
+    notes: RegionNotes,
+    pricing: RegionPricing,
+    carbon: CarbonIntensity,
+    asn: Option<Arc<AsnResolver>>,
+    speed_tester: Option<Arc<SpeedTester>>,
 }
 
 impl ConnectionBenchmark {
@@ -37,6 +64,8 @@ impl ConnectionBenchmark {
         let weights = AlgorithmWeights::default();
         let multi_progress = MultiProgress::new();
         let progress_factory = ProgressBarFactory::new(multi_progress);
+        let budget = Arc::new(ProbeBudget::new(config.probe_budget.clone()));
+        let speed_tester = SpeedTester::from_config(&config, &network_tester).map(Arc::new);
 
         Ok(Self {
             config,
@@ -45,6 +74,12 @@ impl ConnectionBenchmark {
             test_history: Arc::new(DashMap::new()),
             network_tester,
             progress_factory,
+            budget,
+            notes: RegionNotes::default(),
+            pricing: RegionPricing::default(),
+            carbon: CarbonIntensity::default(),
+            asn: None,
+            speed_tester,
         })
     }
 
@@ -58,6 +93,8 @@ impl ConnectionBenchmark {
 
         let multi_progress = MultiProgress::new();
         let progress_factory = ProgressBarFactory::new(multi_progress);
+        let budget = Arc::new(ProbeBudget::new(config.probe_budget.clone()));
+        let speed_tester = SpeedTester::from_config(&config, &network_tester).map(Arc::new);
 
         Ok(Self {
             config,
@@ -66,6 +103,12 @@ impl ConnectionBenchmark {
             test_history: Arc::new(DashMap::new()),
             network_tester,
             progress_factory,
+            budget,
+            notes: RegionNotes::default(),
+            pricing: RegionPricing::default(),
+            carbon: CarbonIntensity::default(),
+            asn: None,
+            speed_tester,
         })
     }
 
@@ -121,13 +164,51 @@ impl ConnectionBenchmark {
         regions: &[Region],
         ping_count: usize,
     ) -> Result<Vec<(String, PingStats)>> {
+        let report = self
+            .test_regions_until(regions, ping_count, &CancellationToken::new())
+            .await?;
+        Ok(report.results)
+    }
+
+    /// Execute concurrent tests across multiple regions until `cancel` fires
+    ///
+    /// Regions start in order of descending priority, so high-priority
+    /// regions get the first slots. Regions still waiting for a slot when the
+    /// token fires are skipped; regions being tested return their completed
+    /// pings marked `incomplete`. Skipped regions, regions whose task failed
+    /// and regions where every ping failed are listed in the report's `failures`.
+    pub async fn test_regions_until(
+        &self,
+        regions: &[Region],
+        ping_count: usize,
+        cancel: &CancellationToken,
+    ) -> Result<RunReport> {
         if regions.is_empty() {
-            return Ok(Vec::new());
+            return Ok(RunReport::default());
         }
 
+        let mut regions: Vec<&Region> = regions.iter().collect();
+        regions.sort_by(|a, b| {
+            b.priority
+                .partial_cmp(&a.priority)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
         let semaphore = Arc::new(Semaphore::new(self.config.max_threads));
         let mut tasks = Vec::new();
 
+        // Takes its idle baseline now, before any region loads the uplink
+        let controller = match ConcurrencyController::new(
+            self.config.adaptive_concurrency.clone(),
+            self.network_tester.clone(),
+            Arc::clone(&semaphore),
+            self.config.max_threads,
+        ) {
+            Some(controller) => controller.start().await,
+            None => None,
+        };
+        let control = ControlMonitor::start(&self.config.control, self.network_tester.clone());
+
         info!(
             "Testing {} regions with {} concurrent threads, {} pings each",
             regions.len(),
@@ -139,62 +220,202 @@ impl ConnectionBenchmark {
         let progress_bars: Vec<Option<ProgressBar>> = if self.config.show_progress {
             regions
                 .iter()
-                .map(|region| Some(self.progress_factory.create_test_progress_bar(ping_count, &region.name)))
+                .map(|region| {
+                    let pings = self.pings_for(region, ping_count);
+                    Some(self.progress_factory.create_test_progress_bar(pings, &region.name))
+                })
                 .collect()
         } else {
             vec![None; regions.len()]
         };
 
+        // Hand out slots here rather than inside the tasks so regions start in priority order
         for (i, region) in regions.iter().enumerate() {
-            let task = self.create_region_test_task(
-                semaphore.clone(),
-                region.clone(),
-                ping_count,
-                progress_bars[i].clone(),
-            );
+            let task = tokio::select! {
+                permit = semaphore.clone().acquire_owned() => Some(self.create_region_test_task(
+                    permit.map_err(|e| {
+                        CloudPingError::concurrency(format!("Failed to acquire semaphore: {}", e))
+                    }),
+                    (*region).clone(),
+                    self.pings_for(region, ping_count),
+                    progress_bars[i].clone(),
+                    cancel.clone(),
+                )),
+                () = cancel.cancelled() => {
+                    debug!("Skipping region {}: run cancelled", region.name);
+                    None
+                }
+            };
             tasks.push(task);
         }
 
-        let results = join_all(tasks).await;
+        let results = join_all(tasks.into_iter().map(|task| async move {
+            match task {
+                Some(task) => Some(task.await),
+                None => None,
+            }
+        }))
+        .await;
         
-        // Collect successful results and log failures
-        let mut successful_results = Vec::new();
-        for result in results {
-            match result {
-                Ok(Ok((name, stats))) => {
-                    successful_results.push((name, stats));
-                }
-                Ok(Err(e)) => {
-                    warn!("Region test failed: {}", e);
-                }
-                Err(e) => {
-                    warn!("Task execution failed: {}", e);
+        // Keep every region's outcome, successful or not
+        let mut report = RunReport::default();
+        if let Some(controller) = controller {
+            report.concurrency_adjustments = controller.stop().await;
+        }
+        if let Some(control) = control {
+            report.control = Some(control.stop().await);
+        }
+        for (region, result) in regions.iter().zip(results) {
+            Self::record_outcome(&mut report, region, result);
+        }
+        if self.config.route_tracing {
+            Self::trace_routes(&regions, &mut report, self.config.max_threads, cancel).await;
+        }
+        // Speed tests saturate the link, so they wait until no region is pinging
+        if let Some(tester) = &self.speed_tester {
+            Self::run_speed_tests(tester, &regions, &mut report, cancel).await;
+        }
+        report.incomplete = cancel.is_cancelled();
+
+        info!(
+            "Completed testing {} regions, {} failed",
+            report.results.len(),
+            report.failures.len()
+        );
+        Ok(report)
+    }
+
+    /// Add one region's task outcome to `report`; `None` means the region never started
+    fn record_outcome(
+        report: &mut RunReport,
+        region: &Region,
+        result: Option<std::result::Result<RegionOutcome, tokio::task::JoinError>>,
+    ) {
+        let failure = |kind, message: String| RegionFailure {
+            region: region.name.clone(),
+            url: region.url.clone(),
+            kind,
+            message,
+        };
+        match result {
+            Some(Ok(Ok(Some((name, mut stats))))) => {
+                if let Some(control) = &report.control {
+                    control.normalize(&mut stats);
                 }
+                report.push_result(name, &region.url, stats);
+            }
+            None | Some(Ok(Ok(None))) => report.failures.push(failure(
+                FailureKind::Cancelled,
+                "Run cancelled before the region was tested".to_string(),
+            )),
+            Some(Ok(Err(e))) => {
+                warn!("Region test failed: {}", e);
+                report.failures.push(failure(FailureKind::Internal, e.to_string()));
+            }
+            Some(Err(e)) => {
+                warn!("Task execution failed: {}", e);
+                report.failures.push(failure(FailureKind::Internal, e.to_string()));
+            }
+        }
+    }
+
+    /// Speed-test each region of `report` that answered its pings and names a
+    /// speed-test endpoint, one region at a time
+    async fn run_speed_tests(
+        tester: &SpeedTester,
+        regions: &[&Region],
+        report: &mut RunReport,
+        cancel: &CancellationToken,
+    ) {
+        for (_, stats) in report.results.iter_mut().filter(|(_, stats)| stats.is_successful()) {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let Some(region) = regions
+                .iter()
+                .find(|region| stats.region_id.as_deref() == Some(region.id.as_str()))
+            else {
+                continue;
+            };
+            match SpeedTestEndpoint::for_region(region) {
+                Ok(Some(endpoint)) => stats.speed_test = Some(tester.run(&endpoint).await),
+                Ok(None) => {}
+                Err(e) => warn!("Skipping speed test of {}: {}", region.name, e),
             }
         }
+    }
+
+    /// Pings to send to `region`, scaled by its priority when `priority_scaling` is on
+    fn pings_for(&self, region: &Region, ping_count: usize) -> usize {
+        if self.config.priority_scaling {
+            region.scaled_by_priority(ping_count)
+        } else {
+            ping_count
+        }
+    }
 
-        info!("Completed testing {} regions successfully", successful_results.len());
-        Ok(successful_results)
+    /// Retries per failed ping to `region`, scaled by its priority when `priority_scaling` is on
+    fn retries_for(&self, region: &Region) -> usize {
+        if self.config.priority_scaling {
+            region.scaled_by_priority(self.config.retry_attempts)
+        } else {
+            self.config.retry_attempts
+        }
     }
 
     fn create_region_test_task(
         &self,
-        semaphore: Arc<Semaphore>,
+        permit: Result<OwnedSemaphorePermit>,
         region: Region,
         ping_count: usize,
         progress_bar: Option<ProgressBar>,
-    ) -> tokio::task::JoinHandle<Result<(String, PingStats)>> {
-        let network_tester = self.network_tester.clone();
+        cancel: CancellationToken,
+    ) -> tokio::task::JoinHandle<RegionOutcome> {
+        let network_tester = self
+            .network_tester
+            .clone()
+            .with_retry_budget(Arc::clone(&self.budget), region.provider.clone());
         let region_id = region.id.clone();
+        let retry_attempts = self.retries_for(&region);
+        let client_coordinates = self.config.client_coordinates.clone();
+        let notes = self.notes.for_region(&region);
+        let cost = self.pricing.for_region(&region);
+        let carbon = self.carbon.for_region(&region);
+        let asn = self.asn.clone();
         
         tokio::spawn(async move {
-            let _permit = semaphore.acquire().await
-                .map_err(|e| CloudPingError::concurrency(format!("Failed to acquire semaphore: {}", e)))?;
+            let _permit = permit?;
             
             debug!("Starting test for region: {}", region.name);
             
-            let mut stats = network_tester.perform_ping_test(&region.url, ping_count).await;
+            let mut stats = network_tester
+                .perform_ping_test_with_retries(&region.url, ping_count, retry_attempts, &cancel)
+                .await;
+            if stats.incomplete && stats.total_pings == 0 {
+                return Ok(None);
+            }
             stats.region_id = Some(region_id);
+            stats.priority = region.priority;
+            stats.provider.clone_from(&region.provider);
+            stats.country.clone_from(&region.country);
+            stats.notes = notes;
+            stats.cost = cost;
+            stats.carbon_g_per_kwh = carbon;
+            if let Some(resolver) = &asn {
+                stats.asn = resolver.lookup_url(&region.url).await;
+            }
+            // Routes are traced after the ping phase, which holds no slot for them
+            stats.route = stats.asn.as_ref().map(|asn| RoutePath {
+                final_asn: Some(asn.asn),
+                ..RoutePath::default()
+            });
+
+            if let (Some(client), Some(target)) = (&client_coordinates, &region.coordinates) {
+                MeasurementQuality::flag(&mut stats, client, target);
+                if stats.is_low_quality() {
+                    warn!("Low-quality measurement for {}: {:?}", region.name, stats.quality_flags);
+                }
+            }
             
             if let Some(pb) = progress_bar {
                 pb.finish_with_message(format!(
@@ -207,24 +428,78 @@ impl ConnectionBenchmark {
             
             debug!("Completed test for region: {} - Success: {:.1}%", region.name, stats.success_rate());
             
-            Ok((region.name, stats))
+            Ok(Some((region.name, stats)))
         })
     }
 
+    /// Trace the route to each region of `report` that answered its pings,
+    /// `concurrency` at a time, keeping the traces finished when `cancel` fires
+    async fn trace_routes(
+        regions: &[&Region],
+        report: &mut RunReport,
+        concurrency: usize,
+        cancel: &CancellationToken,
+    ) {
+        let targets: Vec<(usize, String)> = report
+            .results
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, stats))| stats.is_successful())
+            .filter_map(|(i, (_, stats))| {
+                let region = regions
+                    .iter()
+                    .find(|region| stats.region_id.as_deref() == Some(region.id.as_str()))?;
+                Some((i, region.url.clone()))
+            })
+            .collect();
+        let traces: Vec<(usize, Option<Vec<String>>)> = futures::stream::iter(targets)
+            .map(|(i, url)| async move { (i, Self::trace_route(&url).await) })
+            .buffer_unordered(concurrency.max(1))
+            .take_until(cancel.cancelled())
+            .collect()
+            .await;
+
+        for (i, hops) in traces {
+            let Some(hops) = hops else { continue };
+            let stats = &mut report.results[i].1;
+            let final_asn = stats.route.as_ref().and_then(|route| route.final_asn);
+            stats.route = Some(RoutePath::from_hops(&hops, final_asn));
+        }
+    }
+
+    /// Addresses of the hops answering a traceroute to `url`, in order;
+    /// `None` when it cannot be traced
+    async fn trace_route(url: &str) -> Option<Vec<String>> {
+        let address = match NetworkTester::resolve_host(url).await {
+            Ok(address) => address,
+            Err(e) => {
+                debug!("Not tracing the route to {}: {}", url, e);
+                return None;
+            }
+        };
+        let hops = trace_hops(&address.to_string(), ROUTE_TRACE_MAX_TTL, ROUTE_TRACE_TIMEOUT).await?;
+        Some(hops.into_iter().map(|(_, address, _)| address).collect())
+    }
+
     pub fn display_enhanced_results(&self, name: &str, stats: &PingStats) {
         DisplayFormatter::display_enhanced_results(name, stats, &self.weights);
     }
 
     pub fn display_top_results(&self, results: &[(String, PingStats)], count: usize) {
-        let scored_results = ScoringAdapter::get_sorted_results(results, &self.weights);
+        let scored_results = self.config.report_filter.apply(ScoringAdapter::get_sorted_results_by(
+            results,
+            &self.weights,
+            self.config.sort_by,
+        ));
 
         println!("\n{}", console::style("=== TOP PERFORMERS ===").cyan().bold());
         for (i, (score, name, stats, _)) in scored_results.iter().take(count).enumerate() {
             println!(
-                "{}. {} - Score: {}, Latency: {}, Loss: {}",
+                "{}. {} - Score: {}, Health: {}, Latency: {}, Loss: {}",
                 i + 1,
                 console::style(name).green(),
                 DisplayUtils::format_score(*score),
+                DisplayUtils::format_health(stats.health_status()),
                 DisplayUtils::format_latency(stats.avg),
                 DisplayUtils::format_percentage(stats.packet_loss)
             );
@@ -242,6 +517,39 @@ impl ConnectionBenchmark {
     pub async fn load_cloud_providers(&mut self, filename: &str) -> Result<()> {
         info!("Loading cloud providers from: {}", filename);
         self.providers = DataLoader::load_cloud_providers(filename).await?;
+        if let Some(path) = &self.config.geoip_file {
+            #[cfg(feature = "geoip")]
+            crate::geoip::GeoIpLocator::open(std::path::Path::new(path))?
+                .fill_missing_coordinates(&mut self.providers)
+                .await;
+            #[cfg(not(feature = "geoip"))]
+            warn!("Ignoring geoip_file {path}: built without the geoip feature");
+        }
+        if let Some(path) = &self.config.notes_file {
+            self.notes = RegionNotes::load(std::path::Path::new(path))?;
+        }
+        self.pricing = RegionPricing::default();
+        if let Some(url) = &self.config.pricing_url {
+            // A pricing feed that is down only costs the run its fetched prices
+            match PricingClient::new(&self.config, url)?.fetch().await {
+                Ok(pricing) => self.pricing = pricing,
+                Err(e) => warn!("Continuing without prices from {}: {}", url, e),
+            }
+        }
+        if let Some(path) = &self.config.pricing_file {
+            self.pricing.merge(RegionPricing::load(std::path::Path::new(path))?);
+        }
+        self.carbon = CarbonIntensity::default();
+        if let Some(url) = &self.config.carbon_url {
+            match CarbonClient::new(&self.config, url)?.fetch().await {
+                Ok(carbon) => self.carbon = carbon,
+                Err(e) => warn!("Continuing without carbon intensity from {}: {}", url, e),
+            }
+        }
+        if let Some(path) = &self.config.carbon_file {
+            self.carbon.merge(CarbonIntensity::load(std::path::Path::new(path))?);
+        }
+        self.asn = AsnResolver::from_config(&self.config)?.map(Arc::new);
         info!("Loaded {} providers with {} total regions", 
               self.providers.len(),
               self.providers.iter().map(|p| p.regions.len()).sum::<usize>());
@@ -255,6 +563,28 @@ impl ConnectionBenchmark {
         provider_filter: Option<String>,
         region_filter: Option<String>,
     ) -> Result<Vec<(String, PingStats)>> {
+        let report = self
+            .run_filtered_benchmark_until(
+                ping_count,
+                provider_filter,
+                region_filter,
+                &CancellationToken::new(),
+            )
+            .await?;
+        Ok(report.results)
+    }
+
+    /// Execute a filtered benchmark that stops early when `cancel` fires
+    ///
+    /// The configured `run_deadline` cancels the token when it passes; the
+    /// report's `incomplete` flag tells whether the results are partial.
+    pub async fn run_filtered_benchmark_until(
+        &mut self,
+        ping_count: usize,
+        provider_filter: Option<String>,
+        region_filter: Option<String>,
+        cancel: &CancellationToken,
+    ) -> Result<RunReport> {
         if self.providers.is_empty() {
             self.load_cloud_providers(&self.config.data_file.clone()).await?;
         }
@@ -266,14 +596,124 @@ impl ConnectionBenchmark {
         }
 
         info!("Testing {} regions with {} pings each", filtered_regions.len(), ping_count);
-        
-        let results = self.test_regions_concurrently(&filtered_regions, ping_count).await?;
 
-        Ok(results)
+        // Refuse the whole run up front rather than stopping halfway through
+        self.budget.start_run();
+        self.budget.reserve_plan(&self.request_plan(&filtered_regions, ping_count))?;
+
+        let deadline_timer = self.config.run_deadline.map(|deadline| {
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(deadline).await;
+                warn!(
+                    "Benchmark exceeded its run deadline of {}, stopping",
+                    humantime::format_duration(deadline)
+                );
+                cancel.cancel();
+            })
+        });
+
+        let report = self
+            .test_regions_until(&filtered_regions, ping_count, cancel)
+            .await;
+        if let Some(timer) = deadline_timer {
+            timer.abort();
+        }
+        let report = report?;
+
+        if let Some(budget) = self.connection_budget() {
+            let stats = budget.stats();
+            debug!(
+                "Connection budget: peak {} of {} connections, {} of {} requests waited {:?}",
+                stats.peak_in_use, stats.capacity, stats.waited, stats.acquired, stats.total_wait
+            );
+        }
+
+        if report.incomplete {
+            warn!(
+                "Benchmark stopped early: {} of {} regions returned results",
+                report.results.len(),
+                filtered_regions.len()
+            );
+        }
+        Ok(report)
     }
 
+    /// Estimate a filtered benchmark without sending any requests
+    pub fn plan(
+        &self,
+        ping_count: usize,
+        provider_filter: Option<String>,
+        region_filter: Option<String>,
+    ) -> Result<BenchmarkPlan> {
+        let filtered_regions = self.collect_filtered_regions(provider_filter, region_filter);
+        if filtered_regions.is_empty() {
+            return Err(CloudPingError::test_execution("No regions match the specified filters"));
+        }
+
+        Ok(BenchmarkPlan::estimate(&filtered_regions, ping_count, &self.config))
+    }
+
+    /// Capture the local environment, then run a filtered benchmark until `cancel` fires
+    ///
+    /// Every result is stamped with the configured `labels` and added to the
+    /// test history, so saved history can be grouped by label later.
+    pub async fn run_benchmark_with_environment(
+        &mut self,
+        ping_count: usize,
+        provider_filter: Option<String>,
+        region_filter: Option<String>,
+        cancel: &CancellationToken,
+    ) -> Result<BenchmarkRun> {
+        let environment = EnvironmentCapture::capture(&self.config).await;
+        info!("Test environment: {}", environment.summary());
+
+        let mut report = self
+            .run_filtered_benchmark_until(ping_count, provider_filter, region_filter, cancel)
+            .await?;
+
+        for (_, stats) in &mut report.results {
+            stats.labels.clone_from(&self.config.labels);
+            let region = self
+                .providers
+                .iter()
+                .flat_map(|provider| &provider.regions)
+                .find(|region| stats.region_id.as_ref() == Some(&region.id));
+            if let Some(region) = region {
+                self.update_test_history(region.id.clone(), region.url.clone(), stats.clone());
+            }
+        }
+
+        let mut run = BenchmarkRun::new(environment, report.results);
+        run.labels.clone_from(&self.config.labels);
+        run.incomplete = report.incomplete;
+        run.failures = report.failures;
+        run.concurrency_adjustments = report.concurrency_adjustments;
+        run.control = report.control;
+        Ok(run)
+    }
+
+    /// Requests per provider for pinging each region `ping_count` times, scaled by priority if enabled
+    fn request_plan(&self, regions: &[Region], ping_count: usize) -> Vec<(String, u64)> {
+        self.providers
+            .iter()
+            .filter_map(|provider| {
+                let planned: u64 = provider
+                    .regions
+                    .iter()
+                    .filter(|region| regions.iter().any(|planned| planned.id == region.id))
+                    .map(|region| {
+                        u64::try_from(self.pings_for(region, ping_count)).unwrap_or(u64::MAX)
+                    })
+                    .sum();
+                (planned > 0).then(|| (provider.name.clone(), planned))
+            })
+            .collect()
+    }
+
+    /// Enabled regions whose provider and name contain the filters, ignoring case
     #[must_use]
-    fn collect_filtered_regions(
+    pub fn collect_filtered_regions(
         &self,
         provider_filter: Option<String>,
         region_filter: Option<String>,
@@ -305,7 +745,31 @@ impl ConnectionBenchmark {
     }
 
     pub fn generate_ranking_report(&self, results: &[(String, PingStats)]) {
-        DisplayFormatter::generate_ranking_report(results, &self.weights);
+        DisplayFormatter::generate_ranking_report(
+            &self.ranking(results),
+            &self.get_all_test_histories(),
+        );
+    }
+
+    /// Scorable results ordered by `sort_by` and narrowed by `report_filter`
+    #[must_use]
+    pub fn ranking(&self, results: &[(String, PingStats)]) -> Vec<RankedResult> {
+        let ranked = ScoringAdapter::get_sorted_results_by(
+            &self.scorable_results(results),
+            &self.weights,
+            self.config.sort_by,
+        );
+        self.config.report_filter.apply(ranked)
+    }
+
+    /// Results eligible for scoring; low-quality measurements are dropped unless configured otherwise
+    #[must_use]
+    pub fn scorable_results(&self, results: &[(String, PingStats)]) -> Vec<(String, PingStats)> {
+        results
+            .iter()
+            .filter(|(_, stats)| self.config.score_low_quality || !stats.is_low_quality())
+            .cloned()
+            .collect()
     }
 
     #[must_use]
@@ -318,10 +782,36 @@ impl ConnectionBenchmark {
         self.test_history.iter().map(|entry| entry.value().clone()).collect()
     }
 
+    /// Restore saved histories, matching them to loaded regions by URL
+    ///
+    /// Region IDs are generated on every load, so saved histories are re-keyed
+    /// to the current ID of the region with the same URL. Histories for regions
+    /// no longer in the data file are kept under their saved ID.
+    pub fn restore_test_history(&self, histories: Vec<TestHistory>) {
+        for mut history in histories {
+            if let Some(region) = self
+                .providers
+                .iter()
+                .flat_map(|p| &p.regions)
+                .find(|r| r.url == history.region_url)
+            {
+                history.region_id.clone_from(&region.id);
+                history.region_name.clone_from(&region.name);
+            }
+            self.test_history.insert(history.region_id.clone(), history);
+        }
+    }
+
     pub fn clear_test_history(&self) {
         self.test_history.clear();
     }
 
+    /// Loaded providers with their regions
+    #[must_use]
+    pub fn providers(&self) -> &[CloudProvider] {
+        &self.providers
+    }
+
     #[must_use]
     pub const fn config(&self) -> &AppConfig {
         &self.config
@@ -332,6 +822,12 @@ impl ConnectionBenchmark {
         &self.weights
     }
 
+    /// Connection budget the benchmark shares with other components, if any
+    #[must_use]
+    pub const fn connection_budget(&self) -> Option<&ConnectionBudget> {
+        self.network_tester.connection_budget()
+    }
+
     pub fn set_weights(&mut self, mut weights: AlgorithmWeights) -> Result<()> {
         if !weights.is_valid() {
             weights.normalize();
@@ -351,6 +847,8 @@ impl ConnectionBenchmark {
 pub struct ConnectionBenchmarkBuilder {
     config: AppConfig,
     weights: Option<AlgorithmWeights>,
+    transport: Option<Arc<dyn HttpTransport>>,
+    connection_budget: Option<ConnectionBudget>,
 }
 
 impl ConnectionBenchmarkBuilder {
@@ -359,6 +857,8 @@ impl ConnectionBenchmarkBuilder {
         Self {
             config,
             weights: None,
+            transport: None,
+            connection_budget: None,
         }
     }
 
@@ -368,14 +868,42 @@ impl ConnectionBenchmarkBuilder {
         self
     }
 
+    /// Send benchmark requests through `transport` instead of the default reqwest client
+    #[must_use]
+    pub fn transport(mut self, transport: Arc<dyn HttpTransport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Share `budget` with monitoring probes so together they stay under one connection cap
+    #[must_use]
+    pub fn connection_budget(mut self, budget: ConnectionBudget) -> Self {
+        self.connection_budget = Some(budget);
+        self
+    }
+
+    /// Weights set on the builder take precedence over `weights` from the config
+    ///
     /// # Errors
     /// Returns error if network tester creation fails or weights are invalid
     pub fn build(self) -> Result<ConnectionBenchmark> {
-        if let Some(weights) = self.weights {
-            ConnectionBenchmark::with_weights(self.config, weights)
+        let mut benchmark = if let Some(weights) = self.weights.or_else(|| self.config.weights.clone()) {
+            ConnectionBenchmark::with_weights(self.config, weights)?
         } else {
-            ConnectionBenchmark::new(self.config)
+            ConnectionBenchmark::new(self.config)?
+        };
+
+        if self.transport.is_some() || self.connection_budget.is_some() {
+            let mut builder = NetworkTester::builder().config(benchmark.config.clone());
+            if let Some(transport) = self.transport {
+                builder = builder.transport(transport);
+            }
+            if let Some(budget) = self.connection_budget {
+                builder = builder.connection_budget(budget);
+            }
+            benchmark.network_tester = builder.build()?;
         }
+        Ok(benchmark)
     }
 }
 
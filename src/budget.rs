@@ -0,0 +1,170 @@
+//! Probe budgets
+//!
+//! Hard caps on outbound traffic so unattended schedules cannot generate
+//! abusive volumes: a maximum number of requests per run and per provider in
+//! any rolling hour. Benchmarks check their whole plan before sending
+//! anything and charge retries as they happen; monitoring checks each probe
+//! as it goes. Counts live in memory, per budget, so they start from zero in
+//! every process and are not shared between a benchmark and monitoring.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CloudPingError, Result};
+
+/// Length of the rolling window for per-provider limits
+const PROVIDER_WINDOW: Duration = Duration::from_secs(3600);
+
+/// Request limits read from the configuration file; unset limits are not enforced
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProbeBudgetConfig {
+    /// Maximum requests a single benchmark or monitoring session may send
+    #[serde(default)]
+    pub max_requests_per_run: Option<u64>,
+    /// Maximum requests to one provider in any rolling hour
+    #[serde(default)]
+    pub max_requests_per_provider_per_hour: Option<u64>,
+}
+
+/// Tracks requests against a [`ProbeBudgetConfig`]
+#[derive(Debug)]
+pub struct ProbeBudget {
+    config: ProbeBudgetConfig,
+    run_used: AtomicU64,
+    provider_requests: DashMap<String, VecDeque<Instant>>,
+}
+
+impl ProbeBudget {
+    /// Start tracking a fresh run against `config`
+    #[must_use]
+    pub fn new(config: ProbeBudgetConfig) -> Self {
+        Self {
+            config,
+            run_used: AtomicU64::new(0),
+            provider_requests: DashMap::new(),
+        }
+    }
+
+    /// Requests charged to this run so far
+    #[must_use]
+    pub fn used(&self) -> u64 {
+        self.run_used.load(Ordering::Relaxed)
+    }
+
+    /// Start a new run, clearing the per-run count but keeping hourly provider counts
+    pub fn start_run(&self) {
+        self.run_used.store(0, Ordering::Relaxed);
+    }
+
+    /// Whether the per-run limit has been used up
+    #[must_use]
+    pub fn run_exhausted(&self) -> bool {
+        self.config
+            .max_requests_per_run
+            .is_some_and(|max| self.used() >= max)
+    }
+
+    /// Charge a planned batch of `(provider, requests)` up front, failing before
+    /// anything is sent if the plan does not fit the budget
+    pub fn reserve_plan(&self, plan: &[(String, u64)]) -> Result<()> {
+        self.reserve_plan_at(plan, Instant::now())
+    }
+
+    /// Charge a single request to `provider`
+    pub fn consume(&self, provider: &str) -> Result<()> {
+        self.reserve_plan_at(&[(provider.to_string(), 1)], Instant::now())
+    }
+
+    fn reserve_plan_at(&self, plan: &[(String, u64)], now: Instant) -> Result<()> {
+        let total: u64 = plan.iter().map(|(_, requests)| requests).sum();
+        if let Some(max) = self.config.max_requests_per_run {
+            let used = self.used();
+            if used + total > max {
+                return Err(CloudPingError::budget_exceeded(format!(
+                    "{total} more requests would exceed max_requests_per_run = {max} ({used} already sent)"
+                )));
+            }
+        }
+
+        if let Some(max) = self.config.max_requests_per_provider_per_hour {
+            for (provider, requests) in plan {
+                let recent = self.recent_requests(provider, now);
+                if recent + requests > max {
+                    return Err(CloudPingError::budget_exceeded(format!(
+                        "{requests} more requests to {provider} would exceed max_requests_per_provider_per_hour = {max} ({recent} sent in the last hour)"
+                    )));
+                }
+            }
+            for (provider, requests) in plan {
+                let mut timestamps = self.provider_requests.entry(provider.clone()).or_default();
+                timestamps.extend(
+                    std::iter::repeat(now).take(usize::try_from(*requests).unwrap_or(usize::MAX)),
+                );
+            }
+        }
+
+        self.run_used.fetch_add(total, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Requests to `provider` within the rolling window ending at `now`
+    fn recent_requests(&self, provider: &str, now: Instant) -> u64 {
+        let Some(mut timestamps) = self.provider_requests.get_mut(provider) else {
+            return 0;
+        };
+        while timestamps
+            .front()
+            .is_some_and(|&sent| now.saturating_duration_since(sent) >= PROVIDER_WINDOW)
+        {
+            timestamps.pop_front();
+        }
+        u64::try_from(timestamps.len()).unwrap_or(u64::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_budget_rejects_oversized_plan() {
+        let budget = ProbeBudget::new(ProbeBudgetConfig {
+            max_requests_per_run: Some(100),
+            max_requests_per_provider_per_hour: None,
+        });
+
+        budget.reserve_plan(&[("AWS".to_string(), 60)]).unwrap();
+        let err = budget.reserve_plan(&[("GCP".to_string(), 50)]).unwrap_err();
+        assert!(err.to_string().contains("max_requests_per_run = 100"));
+
+        // A rejected plan is not charged
+        assert_eq!(budget.used(), 60);
+        assert!(budget.consume("GCP").is_ok());
+    }
+
+    #[test]
+    fn test_provider_budget_rolls_over_after_an_hour() {
+        let budget = ProbeBudget::new(ProbeBudgetConfig {
+            max_requests_per_run: None,
+            max_requests_per_provider_per_hour: Some(3),
+        });
+        let start = Instant::now();
+
+        budget
+            .reserve_plan_at(&[("AWS".to_string(), 3)], start)
+            .unwrap();
+        assert!(budget
+            .reserve_plan_at(&[("AWS".to_string(), 1)], start + Duration::from_secs(60))
+            .is_err());
+        assert!(budget
+            .reserve_plan_at(&[("Azure".to_string(), 1)], start + Duration::from_secs(60))
+            .is_ok());
+        assert!(budget
+            .reserve_plan_at(&[("AWS".to_string(), 1)], start + PROVIDER_WINDOW)
+            .is_ok());
+    }
+}
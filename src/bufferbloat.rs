@@ -0,0 +1,121 @@
+//! Measuring latency under load
+//!
+//! Each region is measured on its own: a few round trips with the link idle,
+//! then round trips while parallel bulk downloads saturate the link, taken as
+//! speed tests take them. The load comes from `--load-url` when given, else
+//! from the region's speed-test endpoint, else from the Cloudflare speed API.
+
+use tracing::warn;
+
+use crate::config::AppConfig;
+use crate::error::{CloudPingError, Result};
+use crate::format_utils::FormatUtils;
+use crate::models::{BufferbloatResult, Region, SpeedTestEndpoint, SpeedTestKind, CLOUDFLARE_SPEED_URL};
+use crate::network::NetworkTester;
+use crate::speed_test::{under_load, Load};
+
+/// Parallel downloads loading the link; one TCP stream rarely fills it
+const LOAD_STREAMS: usize = 4;
+
+/// Measures latency under load, one region at a time
+#[derive(Debug, Clone)]
+pub struct BufferbloatTest {
+    tester: NetworkTester,
+    pings: usize,
+    bytes: u64,
+    load_url: Option<String>,
+}
+
+impl BufferbloatTest {
+    /// Test taking `pings` round trips idle and as many under load, each of
+    /// the parallel downloads fetching `bytes`; `load_url` overrides the
+    /// source of the load for every region
+    ///
+    /// # Errors
+    /// Returns a validation error when `pings` or `bytes` is zero, or an
+    /// error when the HTTP client cannot be built
+    pub fn new(config: &AppConfig, pings: usize, bytes: u64, load_url: Option<String>) -> Result<Self> {
+        if pings == 0 {
+            return Err(CloudPingError::validation("count", "must be greater than 0"));
+        }
+        if bytes == 0 {
+            return Err(CloudPingError::validation("bytes", "must be greater than 0"));
+        }
+        Ok(Self {
+            tester: NetworkTester::new(config.clone())?,
+            pings,
+            bytes,
+            load_url,
+        })
+    }
+
+    /// Measure every region in turn; measuring them concurrently would load
+    /// the link while idle round trips are taken
+    pub async fn run(&self, regions: &[Region]) -> Vec<BufferbloatResult> {
+        let mut results = Vec::with_capacity(regions.len());
+        for region in regions {
+            results.push(self.measure(region).await);
+        }
+        results
+    }
+
+    async fn measure(&self, region: &Region) -> BufferbloatResult {
+        let load_url = self.load_url_for(region);
+        let load = Load {
+            url: &load_url,
+            bytes: self.bytes,
+            streams: LOAD_STREAMS,
+            idle_pings: self.pings,
+            loaded_pings: self.pings,
+        };
+        let measured = under_load(self.tester.client(), load, || self.rtt_ms(&region.url)).await;
+        if measured.download_mbps.is_none() {
+            warn!("Could not load the link from {} while testing {}", load_url, region.name);
+        }
+
+        BufferbloatResult::from_samples(
+            region.name.clone(),
+            load_url,
+            &measured.idle,
+            &measured.loaded,
+            measured.download_mbps,
+        )
+    }
+
+    /// Where the load of testing `regions` comes from and how much it
+    /// downloads, e.g. `4 downloads of 10.0 MB per region from https://...`
+    #[must_use]
+    pub fn describe_load(&self, regions: &[Region]) -> String {
+        let mut sources: Vec<String> = regions.iter().map(|region| self.load_url_for(region)).collect();
+        sources.sort();
+        sources.dedup();
+        format!(
+            "{LOAD_STREAMS} downloads of {} per region from {}",
+            FormatUtils::format_bytes(self.bytes),
+            sources.join(", ")
+        )
+    }
+
+    /// Download URL loading the link while `region` is measured
+    fn load_url_for(&self, region: &Region) -> String {
+        if let Some(url) = &self.load_url {
+            return url.clone();
+        }
+        let endpoint = SpeedTestEndpoint::for_region(region)
+            .unwrap_or_else(|e| {
+                warn!("Ignoring the speed test of {}: {}", region.name, e);
+                None
+            })
+            .unwrap_or_else(|| SpeedTestEndpoint {
+                kind: SpeedTestKind::Cloudflare,
+                url: CLOUDFLARE_SPEED_URL.to_string(),
+            });
+        endpoint.download_url(self.bytes)
+    }
+
+    /// One round trip to `url` in milliseconds; `None` when it failed
+    async fn rtt_ms(&self, url: &str) -> Option<f64> {
+        let timing = self.tester.ping_url_with_retry(url, 0).await;
+        timing.success.then_some(timing.total_time.as_secs_f64() * 1000.0)
+    }
+}
@@ -0,0 +1,367 @@
+//! Socket-level event logs of an endpoint's failing probes
+//!
+//! A failure kind says a probe timed out or was refused, not why one
+//! endpoint fails one probe in ten from one network. Once the captured
+//! endpoint's probes have failed `failure_threshold` times in a row, each
+//! further failing probe is followed by a traced connection attempt: the
+//! endpoint is resolved, then every address is connected to (or queried, for
+//! NTP) with the local and peer addresses, TTL, OS error and timing of each
+//! step recorded. Entries are appended as JSON lines to a capture file that
+//! stops growing at `max_bytes`. Capturing packets needs raw socket
+//! privileges, so the first line of each file holds a `tcpdump` filter for
+//! the endpoint to run one alongside.
+//!
+//! Traces run on a task of their own, so the probe loop never waits on one.
+//! Failures arriving while [`TRACE_QUEUE`] traces are already waiting are
+//! not traced.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{lookup_host, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::models::{Endpoint, ProbeRecord, ProbeType};
+use crate::probe::CaptureSettings;
+use crate::time_utils::TimeUtils;
+use crate::{banner, ntp};
+
+/// Addresses of the endpoint connected to in one trace
+const MAX_TRACED_ADDRESSES: usize = 4;
+
+/// Traces waiting for the capture task before further failures are skipped
+pub const TRACE_QUEUE: usize = 4;
+
+/// One step of a traced connection attempt
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CaptureEvent {
+    /// Time since the trace started in milliseconds
+    pub elapsed_ms: f64,
+    /// What happened, such as `resolved` or `connect_failed`
+    pub event: &'static str,
+    /// Addresses, socket options or error of the step
+    pub detail: String,
+}
+
+/// Trace following one failing probe
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CaptureEntry {
+    /// When the probe failed
+    pub timestamp: DateTime<Utc>,
+    /// Failures in a row including this one
+    pub consecutive_failures: u32,
+    /// Error of the failing probe
+    pub error: Option<String>,
+    /// Steps of the traced connection attempt
+    pub events: Vec<CaptureEvent>,
+}
+
+/// A failing probe waiting for its trace
+#[derive(Debug)]
+struct TraceJob {
+    endpoint: Endpoint,
+    entry: CaptureEntry,
+    timeout: Duration,
+}
+
+/// Captures the failing probes of the endpoint named in [`CaptureSettings`]
+#[derive(Debug)]
+pub struct FailureCapture {
+    settings: CaptureSettings,
+    consecutive_failures: AtomicU32,
+    /// Set once the capture file is full or cannot be written
+    full: Arc<AtomicBool>,
+    traces: mpsc::Sender<TraceJob>,
+    /// Receiver of `traces`, until the first trace starts the capture task
+    pending: Mutex<Option<mpsc::Receiver<TraceJob>>>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl FailureCapture {
+    /// Capture configured by `settings`
+    #[must_use]
+    pub fn new(settings: CaptureSettings) -> Self {
+        let (traces, pending) = mpsc::channel(TRACE_QUEUE);
+        Self {
+            settings,
+            consecutive_failures: AtomicU32::new(0),
+            full: Arc::default(),
+            traces,
+            pending: Mutex::new(Some(pending)),
+            writer: Mutex::new(None),
+        }
+    }
+
+    /// Count the result of a probe of `endpoint`, queueing a traced
+    /// connection attempt when it is a failure beyond the threshold
+    pub fn observe(&self, endpoint: &Endpoint, record: &ProbeRecord, timeout: Duration) {
+        if endpoint.id != self.settings.endpoint {
+            return;
+        }
+        if record.success {
+            self.consecutive_failures.store(0, Ordering::Relaxed);
+            return;
+        }
+        let consecutive_failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.full.load(Ordering::Relaxed) || consecutive_failures < self.settings.failure_threshold {
+            return;
+        }
+
+        self.start_writer();
+        let job = TraceJob {
+            endpoint: endpoint.clone(),
+            entry: CaptureEntry {
+                timestamp: record.timestamp,
+                consecutive_failures,
+                error: record.error_code.clone(),
+                events: Vec::new(),
+            },
+            timeout,
+        };
+        if self.traces.try_send(job).is_err() {
+            debug!("Capture of {} is behind, not tracing this failure", endpoint.id);
+        }
+    }
+
+    /// Spawn the task tracing and writing queued failures, unless it runs already
+    fn start_writer(&self) {
+        let Some(jobs) = self.pending.lock().unwrap_or_else(PoisonError::into_inner).take() else {
+            return;
+        };
+        let writer = CaptureWriter {
+            settings: self.settings.clone(),
+            file: None,
+            written: 0,
+            full: Arc::clone(&self.full),
+        };
+        let task = tokio::spawn(writer.run(jobs));
+        *self.writer.lock().unwrap_or_else(PoisonError::into_inner) = Some(task);
+    }
+
+    /// Stop capturing once the traces already queued are written
+    pub async fn close(self) {
+        let Self { traces, writer, .. } = self;
+        drop(traces);
+        let task = writer.into_inner().unwrap_or_else(PoisonError::into_inner);
+        if let Some(task) = task {
+            let _ = task.await;
+        }
+    }
+}
+
+/// Traces queued failures and appends them to the capture file
+struct CaptureWriter {
+    settings: CaptureSettings,
+    file: Option<PathBuf>,
+    written: u64,
+    full: Arc<AtomicBool>,
+}
+
+impl CaptureWriter {
+    async fn run(mut self, mut jobs: mpsc::Receiver<TraceJob>) {
+        while let Some(TraceJob { endpoint, mut entry, timeout }) = jobs.recv().await {
+            entry.events = trace(&endpoint, timeout).await;
+            if let Err(e) = self.append(&endpoint, &entry).await {
+                warn!("Stopping capture of {}: {}", endpoint.id, e);
+                self.full.store(true, Ordering::Relaxed);
+            }
+            if self.full.load(Ordering::Relaxed) {
+                break;
+            }
+        }
+    }
+
+    /// Append `entry` to the capture file, starting the file on the first entry
+    async fn append(&mut self, endpoint: &Endpoint, entry: &CaptureEntry) -> std::io::Result<()> {
+        // A new file starts with what was captured and how to capture its packets
+        let mut lines = if self.file.is_none() {
+            let header = serde_json::json!({
+                "endpoint": endpoint.id,
+                "address": endpoint.address(),
+                "probe_type": endpoint.probe_type,
+                "tcpdump": format!("host {} and port {}", endpoint.host, endpoint.port),
+            });
+            format!("{header}\n")
+        } else {
+            String::new()
+        };
+        lines.push_str(&serde_json::to_string(entry)?);
+        lines.push('\n');
+        if self.written + lines.len() as u64 > self.settings.max_bytes {
+            info!(
+                "Capture of {} is full at {} bytes",
+                endpoint.id, self.written
+            );
+            self.full.store(true, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        if self.file.is_none() {
+            tokio::fs::create_dir_all(&self.settings.directory).await?;
+            let path = self.settings.directory.join(file_name(&endpoint.id, TimeUtils::now()));
+            info!("Capturing failing probes of {} to {}", endpoint.id, path.display());
+            self.file = Some(path);
+        }
+        let path = self.file.clone().unwrap_or_default();
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        file.write_all(lines.as_bytes()).await?;
+        self.written += lines.len() as u64;
+        Ok(())
+    }
+}
+
+/// Capture file name for `endpoint_id` started at `started`
+fn file_name(endpoint_id: &str, started: DateTime<Utc>) -> String {
+    let id: String = endpoint_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    format!("{id}-{}.jsonl", started.format("%Y%m%dT%H%M%SZ"))
+}
+
+/// Resolve `endpoint` and connect to each of its addresses, recording every step
+pub async fn trace(endpoint: &Endpoint, timeout: Duration) -> Vec<CaptureEvent> {
+    let start = Instant::now();
+    let mut events = Vec::new();
+    let mut push = |event: &'static str, detail: String| {
+        events.push(CaptureEvent {
+            elapsed_ms: start.elapsed().as_secs_f64() * 1000.0,
+            event,
+            detail,
+        });
+    };
+
+    let target = format!("{}:{}", endpoint.host, endpoint.port);
+    let addresses: Vec<SocketAddr> = match tokio::time::timeout(timeout, lookup_host(&target)).await {
+        Ok(Ok(addresses)) => addresses.collect(),
+        Ok(Err(e)) => {
+            push("resolve_failed", io_detail(&e));
+            return events;
+        }
+        Err(_) => {
+            push("resolve_timeout", target.clone());
+            return events;
+        }
+    };
+    push(
+        "resolved",
+        addresses.iter().map(ToString::to_string).collect::<Vec<_>>().join(" "),
+    );
+
+    for addr in addresses.into_iter().take(MAX_TRACED_ADDRESSES) {
+        if endpoint.probe_type == ProbeType::NTP {
+            match tokio::time::timeout(timeout, ntp::query(addr)).await {
+                Ok(Ok(sample)) => push(
+                    "ntp_answered",
+                    format!("{addr} delay {:.1}ms offset {:.1}ms", sample.delay_ms, sample.offset_ms),
+                ),
+                Ok(Err(kind)) => push("ntp_failed", format!("{addr} {kind}")),
+                Err(_) => push("ntp_timeout", addr.to_string()),
+            }
+            continue;
+        }
+
+        push("connect", addr.to_string());
+        let mut stream = match tokio::time::timeout(timeout, TcpStream::connect(addr)).await {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(e)) => {
+                push("connect_failed", format!("{addr} {}", io_detail(&e)));
+                continue;
+            }
+            Err(_) => {
+                push("connect_timeout", addr.to_string());
+                continue;
+            }
+        };
+        let local = stream
+            .local_addr()
+            .map_or_else(|e| io_detail(&e), |local| local.to_string());
+        let ttl = stream.ttl().map_or_else(|e| io_detail(&e), |ttl| ttl.to_string());
+        push("connected", format!("{local} -> {addr} ttl {ttl}"));
+
+        if endpoint.probe_type.is_banner() {
+            match tokio::time::timeout(timeout, banner::greet(&mut stream, endpoint.probe_type)).await {
+                Ok(Ok(())) => push("greeted", addr.to_string()),
+                Ok(Err(kind)) => push("greeting_failed", format!("{addr} {kind}")),
+                Err(_) => push("greeting_timeout", addr.to_string()),
+            }
+        }
+    }
+    events
+}
+
+/// Kind, message and OS error code of `error`
+fn io_detail(error: &std::io::Error) -> String {
+    let kind = error.kind();
+    error.raw_os_error().map_or_else(
+        || format!("{kind:?}: {error}"),
+        |code| format!("{kind:?}: {error} (os error {code})"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn closed_endpoint() -> Endpoint {
+        // Nothing listens on a port just released, so connecting is refused
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        Endpoint::new("closed".to_string(), "127.0.0.1".to_string(), port, ProbeType::TCP)
+    }
+
+    #[tokio::test]
+    async fn test_trace_records_refused_connect() {
+        let events = trace(&closed_endpoint(), Duration::from_secs(1)).await;
+        let kinds: Vec<&str> = events.iter().map(|event| event.event).collect();
+        assert_eq!(kinds, ["resolved", "connect", "connect_failed"]);
+        assert!(events[2].detail.contains("ConnectionRefused"), "{events:?}");
+    }
+
+    #[tokio::test]
+    async fn test_capture_starts_at_threshold_and_stays_bounded() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = closed_endpoint();
+        let capture = FailureCapture::new(CaptureSettings {
+            endpoint: endpoint.id.clone(),
+            failure_threshold: 2,
+            directory: dir.path().to_path_buf(),
+            max_bytes: 2048,
+        });
+        let failed = ProbeRecord::with_error(endpoint.id.clone(), "connection".to_string());
+        let timeout = Duration::from_secs(1);
+
+        capture.observe(&endpoint, &failed, timeout);
+        assert!(std::fs::read_dir(dir.path()).unwrap().next().is_none());
+        // Failures beyond the queue are skipped rather than waited on
+        let started = Instant::now();
+        for _ in 0..20 {
+            capture.observe(&endpoint, &failed, timeout);
+        }
+        assert!(started.elapsed() < timeout);
+        capture.close().await;
+
+        let file = std::fs::read_dir(dir.path()).unwrap().next().unwrap().unwrap().path();
+        let content = std::fs::read_to_string(file).unwrap();
+        assert!(content.len() <= 2048);
+        let mut lines = content.lines();
+        assert!(lines.next().unwrap().contains("\"tcpdump\":\"host 127.0.0.1 and port"));
+        let first: serde_json::Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+        assert_eq!(first["consecutive_failures"], 2);
+    }
+}
@@ -0,0 +1,32 @@
+//! Carbon intensity feed
+//!
+//! Downloads current carbon intensity per region from `carbon_url`, so the
+//! greenest region recommendation can follow the grid mix instead of a
+//! static dataset. The feed is a JSON object of gCO2/kWh keyed by region name
+//! or URL, in the same shape as a `carbon_file`.
+
+use crate::config::AppConfig;
+use crate::error::Result;
+use crate::feed::FeedClient;
+use crate::models::CarbonIntensity;
+
+/// Fetches carbon intensity from a carbon feed
+pub struct CarbonClient {
+    feed: FeedClient,
+}
+
+impl CarbonClient {
+    /// Create a client for `url` using the app's timeout, user agent and TLS settings
+    pub fn new(config: &AppConfig, url: &str) -> Result<Self> {
+        Ok(Self {
+            feed: FeedClient::new(config, url)?,
+        })
+    }
+
+    /// Fetch the feed with `GET {url}`
+    pub async fn fetch(&self) -> Result<CarbonIntensity> {
+        let intensity: CarbonIntensity = self.feed.fetch("carbon feed").await?;
+        intensity.validate()?;
+        Ok(intensity)
+    }
+}
@@ -0,0 +1,586 @@
+//! Collector mode merging measurements from multiple remote agents
+//!
+//! Agents push benchmark reports and streaming probe batches, as report files
+//! or over HTTP with [`serve`]; the collector keeps the latest report per
+//! agent and feeds probe records into a shared aggregator keyed by vantage
+//! point, so one ranking can show how each region performs as observed from
+//! every agent.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Serialize;
+use statistical::mean;
+use tokio::sync::{mpsc, Mutex};
+use tracing::{debug, info};
+
+use crate::aggregator::{AggregatorConfig, StreamingAggregator};
+use crate::collection_utils::CollectionUtils;
+use crate::error::{CloudPingError, Result};
+use crate::models::{
+    AgentInfo, AgentProbeBatch, AgentReport, Alert, AlgorithmWeights, ComprehensiveScoreResult,
+    HealthStatus, PingStats, ScoringAdapter,
+};
+
+/// A single region measurement as seen from one agent
+#[derive(Debug, Clone)]
+pub struct VantageObservation {
+    /// Agent that took the measurement
+    pub agent: AgentInfo,
+    /// Raw statistics reported by the agent
+    pub stats: PingStats,
+    /// Score computed by the collector with its own weights
+    pub score: ComprehensiveScoreResult,
+}
+
+/// Region ranking entry merged across all reporting agents
+#[derive(Debug, Clone)]
+pub struct MultiVantageResult {
+    /// Region name
+    pub region: String,
+    /// Per-agent observations, ordered by agent ID
+    pub observations: Vec<VantageObservation>,
+    /// Mean score across agents
+    pub mean_score: f64,
+    /// Mean average latency across agents that reached the region
+    pub mean_latency_ms: f64,
+}
+
+impl MultiVantageResult {
+    /// Number of agents that reported this region
+    #[must_use]
+    pub fn agent_count(&self) -> usize {
+        self.observations.len()
+    }
+}
+
+/// Latency and score for one agent/region pair
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MatrixCell {
+    /// Average latency seen by the agent (0 when unreachable)
+    pub latency_ms: f64,
+    /// Score computed by the collector
+    pub score: f64,
+    /// Whether the agent reached the region at all
+    pub reachable: bool,
+    /// Health derived from the agent's statistics
+    pub health: HealthStatus,
+}
+
+/// Agents × regions view of the latest reports
+#[derive(Debug, Clone, Serialize)]
+pub struct VantageMatrix {
+    /// Column headers, ordered by agent ID
+    pub agents: Vec<AgentInfo>,
+    /// Row headers, ordered by region name
+    pub regions: Vec<String>,
+    /// `cells[row][column]`, `None` when the agent did not test the region
+    pub cells: Vec<Vec<Option<MatrixCell>>>,
+}
+
+impl VantageMatrix {
+    /// Cell for a region as seen from an agent
+    #[must_use]
+    pub fn cell(&self, region: &str, agent_id: &str) -> Option<MatrixCell> {
+        let row = self.regions.iter().position(|r| r == region)?;
+        let column = self.agents.iter().position(|a| a.agent_id == agent_id)?;
+        self.cells[row][column]
+    }
+
+    /// Render the matrix as CSV with latency, score and health columns per agent
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("region");
+        for agent in &self.agents {
+            let id = csv_field(&agent.agent_id);
+            let _ = write!(csv, ",{id} latency_ms,{id} score,{id} health");
+        }
+        csv.push('\n');
+
+        for (region, row) in self.regions.iter().zip(&self.cells) {
+            csv.push_str(&csv_field(region));
+            for cell in row {
+                match cell {
+                    Some(cell) => {
+                        let _ = write!(
+                            csv,
+                            ",{:.2},{:.1},{}",
+                            cell.latency_ms, cell.score, cell.health
+                        );
+                    }
+                    None => csv.push_str(",,,"),
+                }
+            }
+            csv.push('\n');
+        }
+
+        csv
+    }
+}
+
+/// Quote a CSV field when it contains separators or quotes
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Region that is the best choice for the most vantage points
+#[derive(Debug, Clone, Serialize)]
+pub struct MajorityRecommendation {
+    /// Recommended region
+    pub region: String,
+    /// Agents whose best-scoring region this is
+    pub supporting_agents: Vec<String>,
+    /// Number of agents that reported any results
+    pub total_agents: usize,
+    /// Mean score of the region across all agents
+    pub mean_score: f64,
+}
+
+impl MajorityRecommendation {
+    /// Whether more than half of the agents picked this region
+    #[must_use]
+    pub fn is_absolute_majority(&self) -> bool {
+        self.supporting_agents.len() * 2 > self.total_agents
+    }
+}
+
+/// Everything the collector merged, as served at `GET /ranking`
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectorSummary {
+    /// Agents × regions view of the latest reports
+    pub matrix: VantageMatrix,
+    /// Region that is best for the most agents
+    pub recommendation: Option<MajorityRecommendation>,
+    /// Score of every endpoint from the streamed probe batches, by `endpoint@agent`
+    pub probe_scores: BTreeMap<String, f64>,
+}
+
+/// Ingests and merges results pushed from multiple agents
+pub struct Collector {
+    weights: AlgorithmWeights,
+    agents: HashMap<String, AgentInfo>,
+    reports: HashMap<String, AgentReport>,
+    aggregator: StreamingAggregator,
+}
+
+impl Collector {
+    /// Create a collector whose aggregator uses the given configuration
+    #[must_use]
+    pub fn new(config: AggregatorConfig) -> (Self, mpsc::UnboundedReceiver<Alert>) {
+        let weights = config.weights.clone();
+        let (aggregator, alert_receiver) = StreamingAggregator::new(config);
+
+        let collector = Self {
+            weights,
+            agents: CollectionUtils::new_hashmap(),
+            reports: CollectionUtils::new_hashmap(),
+            aggregator,
+        };
+
+        (collector, alert_receiver)
+    }
+
+    /// Store a benchmark report, replacing any earlier report from the same agent
+    pub fn ingest_report(&mut self, report: AgentReport) {
+        info!(
+            "Ingesting report from agent {} with {} regions",
+            report.agent.label(),
+            report.results.len()
+        );
+
+        self.register_agent(&report.agent);
+        self.reports.insert(report.agent.agent_id.clone(), report);
+    }
+
+    /// Feed a batch of streaming probe records into the shared aggregator
+    pub async fn ingest_probe_batch(&mut self, batch: AgentProbeBatch) {
+        debug!(
+            "Ingesting {} probe records from agent {}",
+            batch.records.len(),
+            batch.agent.agent_id
+        );
+
+        self.register_agent(&batch.agent);
+        for record in batch.records {
+            self.aggregator
+                .process_agent_record(&batch.agent.agent_id, record)
+                .await;
+        }
+    }
+
+    fn register_agent(&mut self, agent: &AgentInfo) {
+        self.agents.insert(agent.agent_id.clone(), agent.clone());
+    }
+
+    /// All agents that have pushed data
+    #[must_use]
+    pub fn agents(&self) -> Vec<&AgentInfo> {
+        let mut agents: Vec<&AgentInfo> = self.agents.values().collect();
+        agents.sort_by(|a, b| a.agent_id.cmp(&b.agent_id));
+        agents
+    }
+
+    /// Latest report for each agent
+    pub fn reports(&self) -> impl Iterator<Item = &AgentReport> {
+        self.reports.values()
+    }
+
+    /// Aggregator holding per-vantage streaming state
+    #[must_use]
+    pub const fn aggregator(&self) -> &StreamingAggregator {
+        &self.aggregator
+    }
+
+    /// Merge all agent reports into one ranking ordered by mean score
+    #[must_use]
+    pub fn multi_vantage_ranking(&self) -> Vec<MultiVantageResult> {
+        let mut by_region: BTreeMap<&str, Vec<VantageObservation>> = BTreeMap::new();
+
+        for report in self.reports.values() {
+            for (region, stats) in &report.results {
+                let score = ScoringAdapter::score_ping_stats(stats, &self.weights, region);
+                by_region
+                    .entry(region)
+                    .or_default()
+                    .push(VantageObservation {
+                        agent: report.agent.clone(),
+                        stats: stats.clone(),
+                        score,
+                    });
+            }
+        }
+
+        let mut ranking: Vec<MultiVantageResult> = by_region
+            .into_iter()
+            .map(|(region, mut observations)| {
+                observations.sort_by(|a, b| a.agent.agent_id.cmp(&b.agent.agent_id));
+
+                let scores: Vec<f64> = observations.iter().map(|o| o.score.score).collect();
+                let reachable: Vec<f64> = observations
+                    .iter()
+                    .filter(|o| o.stats.is_successful())
+                    .map(|o| o.stats.avg)
+                    .collect();
+                let mean_score = mean(&scores);
+                let mean_latency_ms = if reachable.is_empty() {
+                    0.0
+                } else {
+                    mean(&reachable)
+                };
+
+                MultiVantageResult {
+                    region: region.to_string(),
+                    observations,
+                    mean_score,
+                    mean_latency_ms,
+                }
+            })
+            .collect();
+
+        ranking.sort_by(|a, b| {
+            b.mean_score
+                .partial_cmp(&a.mean_score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        ranking
+    }
+
+    /// Build the agents × regions matrix from the latest reports
+    #[must_use]
+    pub fn vantage_matrix(&self) -> VantageMatrix {
+        let ranking = self.multi_vantage_ranking();
+        let agents: Vec<AgentInfo> = self
+            .reports
+            .values()
+            .map(|report| (report.agent.agent_id.as_str(), report.agent.clone()))
+            .collect::<BTreeMap<_, _>>()
+            .into_values()
+            .collect();
+
+        let mut rows: Vec<(String, Vec<Option<MatrixCell>>)> = ranking
+            .into_iter()
+            .map(|result| {
+                let row = agents
+                    .iter()
+                    .map(|agent| {
+                        result
+                            .observations
+                            .iter()
+                            .find(|o| o.agent.agent_id == agent.agent_id)
+                            .map(|o| MatrixCell {
+                                latency_ms: o.stats.avg,
+                                score: o.score.score,
+                                reachable: o.stats.is_successful(),
+                                health: o.stats.health_status(),
+                            })
+                    })
+                    .collect();
+                (result.region, row)
+            })
+            .collect();
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let (regions, cells) = rows.into_iter().unzip();
+        VantageMatrix {
+            agents,
+            regions,
+            cells,
+        }
+    }
+
+    /// Matrix, recommendation and streamed probe scores merged so far
+    #[must_use]
+    pub fn summary(&self) -> CollectorSummary {
+        let probe_scores = self
+            .aggregator
+            .get_all_states()
+            .keys()
+            .filter_map(|key| Some((key.clone(), self.aggregator.get_endpoint_score(key)?.score)))
+            .collect();
+        CollectorSummary {
+            matrix: self.vantage_matrix(),
+            recommendation: self.majority_best_region(),
+            probe_scores,
+        }
+    }
+
+    /// Recommend the region that scores best for the most vantage points
+    ///
+    /// Each agent votes for its highest-scoring reachable region; ties between
+    /// regions with the same number of votes go to the higher mean score.
+    #[must_use]
+    pub fn majority_best_region(&self) -> Option<MajorityRecommendation> {
+        let ranking = self.multi_vantage_ranking();
+        let mut votes: HashMap<&str, Vec<String>> = CollectionUtils::new_hashmap();
+
+        for report in self.reports.values() {
+            let best = report
+                .results
+                .iter()
+                .filter(|(_, stats)| stats.is_successful())
+                .map(|(region, stats)| {
+                    let score = ScoringAdapter::score_ping_stats(stats, &self.weights, region);
+                    (region, score.score)
+                })
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+
+            if let Some((region, _)) = best {
+                votes
+                    .entry(region)
+                    .or_default()
+                    .push(report.agent.agent_id.clone());
+            }
+        }
+
+        // Ranking is already ordered by mean score, so the first maximum wins ties
+        let mut best: Option<MajorityRecommendation> = None;
+        for result in &ranking {
+            let Some(supporters) = votes.get(result.region.as_str()) else {
+                continue;
+            };
+            if best
+                .as_ref()
+                .is_some_and(|b| b.supporting_agents.len() >= supporters.len())
+            {
+                continue;
+            }
+
+            let mut supporting_agents = supporters.clone();
+            supporting_agents.sort();
+            best = Some(MajorityRecommendation {
+                region: result.region.clone(),
+                supporting_agents,
+                total_agents: self.reports.len(),
+                mean_score: result.mean_score,
+            });
+        }
+
+        best
+    }
+}
+
+/// Routes agents push to: `POST /reports` takes an [`AgentReport`],
+/// `POST /probes` the [`AgentProbeBatch`] of a probe sink, and `GET /ranking`
+/// returns the [`CollectorSummary`]
+pub fn router(collector: Arc<Mutex<Collector>>) -> Router {
+    Router::new()
+        .route("/reports", post(push_report))
+        .route("/probes", post(push_probes))
+        .route("/ranking", get(ranking))
+        .with_state(collector)
+}
+
+/// Take pushes from agents on `addr` until the process exits
+///
+/// # Errors
+/// Returns an error when `addr` cannot be listened on or the server fails
+pub async fn serve(collector: Collector, addr: SocketAddr) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|e| CloudPingError::network(format!("Cannot listen on {addr}: {e}")))?;
+    info!("Collecting agent pushes on http://{}", addr);
+    axum::serve(listener, router(Arc::new(Mutex::new(collector))))
+        .await
+        .map_err(|e| CloudPingError::system(format!("HTTP server failed: {e}")))
+}
+
+async fn push_report(State(collector): State<Arc<Mutex<Collector>>>, Json(report): Json<AgentReport>) -> StatusCode {
+    collector.lock().await.ingest_report(report);
+    StatusCode::NO_CONTENT
+}
+
+async fn push_probes(State(collector): State<Arc<Mutex<Collector>>>, Json(batch): Json<AgentProbeBatch>) -> StatusCode {
+    collector.lock().await.ingest_probe_batch(batch).await;
+    StatusCode::NO_CONTENT
+}
+
+async fn ranking(State(collector): State<Arc<Mutex<Collector>>>) -> Json<CollectorSummary> {
+    Json(collector.lock().await.summary())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{vantage_key, ProbeRecord};
+
+    fn stats_with_latency(avg: f64) -> PingStats {
+        let mut stats = PingStats::new(10);
+        stats.successful_pings = 10;
+        stats.avg = avg;
+        stats.min = avg;
+        stats.max = avg;
+        stats
+    }
+
+    fn report(agent_id: &str, results: Vec<(&str, f64)>) -> AgentReport {
+        AgentReport::new(
+            AgentInfo::new(agent_id.to_string(), String::new()),
+            results
+                .into_iter()
+                .map(|(name, avg)| (name.to_string(), stats_with_latency(avg)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_multi_vantage_ranking() {
+        let (mut collector, _alerts) = Collector::new(AggregatorConfig::default());
+
+        collector.ingest_report(report("eu", vec![("Frankfurt", 10.0), ("Virginia", 90.0)]));
+        collector.ingest_report(report("us", vec![("Frankfurt", 95.0), ("Virginia", 8.0)]));
+        collector.ingest_report(report(
+            "ap",
+            vec![("Frankfurt", 150.0), ("Virginia", 180.0)],
+        ));
+
+        let ranking = collector.multi_vantage_ranking();
+        assert_eq!(ranking.len(), 2);
+        assert!(ranking.iter().all(|r| r.agent_count() == 3));
+        assert!(ranking[0].mean_score >= ranking[1].mean_score);
+        assert_eq!(collector.agents().len(), 3);
+    }
+
+    #[test]
+    fn test_report_replaces_previous() {
+        let (mut collector, _alerts) = Collector::new(AggregatorConfig::default());
+
+        collector.ingest_report(report("eu", vec![("Frankfurt", 10.0)]));
+        collector.ingest_report(report("eu", vec![("Frankfurt", 20.0), ("Paris", 15.0)]));
+
+        assert_eq!(collector.reports().count(), 1);
+        assert_eq!(collector.multi_vantage_ranking().len(), 2);
+    }
+
+    #[test]
+    fn test_vantage_matrix_and_majority() {
+        let (mut collector, _alerts) = Collector::new(AggregatorConfig::default());
+
+        collector.ingest_report(report("eu", vec![("Frankfurt", 10.0), ("Virginia", 90.0)]));
+        collector.ingest_report(report("us", vec![("Frankfurt", 95.0), ("Virginia", 8.0)]));
+        collector.ingest_report(report("ca", vec![("Virginia", 15.0)]));
+
+        let matrix = collector.vantage_matrix();
+        assert_eq!(matrix.regions, vec!["Frankfurt", "Virginia"]);
+        assert_eq!(matrix.agents.len(), 3);
+        assert!(matrix.cell("Frankfurt", "ca").is_none());
+        assert!((matrix.cell("Virginia", "us").unwrap().latency_ms - 8.0).abs() < f64::EPSILON);
+        let csv = matrix.to_csv();
+        assert_eq!(csv.lines().count(), 3);
+        assert!(csv.starts_with("region,ca latency_ms,ca score,ca health,"));
+        assert_eq!(
+            matrix.cell("Virginia", "us").unwrap().health,
+            HealthStatus::Excellent
+        );
+
+        let recommendation = collector.majority_best_region().unwrap();
+        assert_eq!(recommendation.region, "Virginia");
+        assert_eq!(recommendation.supporting_agents, vec!["ca", "us"]);
+        assert!(recommendation.is_absolute_majority());
+    }
+
+    #[tokio::test]
+    async fn test_probe_batches_keyed_by_vantage() {
+        let (mut collector, _alerts) = Collector::new(AggregatorConfig::default());
+
+        for agent_id in ["eu", "us"] {
+            collector
+                .ingest_probe_batch(AgentProbeBatch {
+                    agent: AgentInfo::new(agent_id.to_string(), String::new()),
+                    records: vec![ProbeRecord::success("aws-eu-west-1".to_string(), 20.0)],
+                })
+                .await;
+        }
+
+        let states = collector.aggregator().get_all_states();
+        assert_eq!(states.len(), 2);
+        assert!(states.contains_key(&vantage_key("eu", "aws-eu-west-1")));
+        assert!(states.contains_key(&vantage_key("us", "aws-eu-west-1")));
+    }
+
+    #[tokio::test]
+    async fn test_agents_push_over_http() {
+        let (collector, _alerts) = Collector::new(AggregatorConfig::default());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(Arc::new(Mutex::new(collector)));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("http://{addr}/reports"))
+            .json(&report("eu", vec![("Frankfurt", 10.0)]))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+        let response = client
+            .post(format!("http://{addr}/probes"))
+            .json(&AgentProbeBatch {
+                agent: AgentInfo::new("us".to_string(), String::new()),
+                records: vec![ProbeRecord::success("aws-eu-west-1".to_string(), 20.0)],
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+        let summary: serde_json::Value = reqwest::get(format!("http://{addr}/ranking"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(summary["recommendation"]["region"], "Frankfurt");
+        assert!(summary["probe_scores"][vantage_key("us", "aws-eu-west-1")].is_number());
+    }
+}
@@ -0,0 +1,326 @@
+//! Community results sharing
+//!
+//! Opt-in upload of anonymised benchmark summaries to a community dataset,
+//! and comparison of local results against the latency other users see from
+//! the same country. Submissions carry only the region, score, latency, the
+//! client's country and AS number, and the hour they were measured in; IP
+//! addresses, host details and exact times never leave the machine.
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use reqwest::{Client, ClientBuilder};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::config::AppConfig;
+use crate::error::Result;
+use crate::models::{AlgorithmWeights, CloudProvider, PingStats, ScoringAdapter, TestEnvironment};
+
+/// Precision of the measurement times uploaded, so a submission cannot be
+/// matched to a user by the second it was made
+const MEASURED_AT_PRECISION: Duration = Duration::hours(1);
+
+/// Anonymised summary of one region's benchmark result
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CommunitySubmission {
+    /// Provider name as it appears in the data file
+    pub provider: String,
+    /// Region name
+    pub region: String,
+    /// Overall score from 0 to 100
+    pub score: f64,
+    /// Average round-trip time in milliseconds
+    pub avg_latency_ms: f64,
+    /// Packet loss percentage
+    pub packet_loss: f64,
+    /// Client country (ISO 3166-1 alpha-2), when the environment lookup is enabled
+    pub country: Option<String>,
+    /// Client autonomous system number (e.g. `AS3320`), without the operator name
+    pub asn: Option<String>,
+    /// Start of the hour the result was measured in
+    pub measured_at: DateTime<Utc>,
+}
+
+impl CommunitySubmission {
+    /// Summaries of the reachable results of a run
+    #[must_use]
+    pub fn from_results(
+        results: &[(String, PingStats)],
+        providers: &[CloudProvider],
+        environment: &TestEnvironment,
+        weights: &AlgorithmWeights,
+    ) -> Vec<Self> {
+        let asn = environment
+            .asn
+            .as_deref()
+            .and_then(|asn| asn.split_whitespace().next())
+            .map(str::to_string);
+
+        results
+            .iter()
+            .filter(|(_, stats)| stats.is_successful())
+            .filter_map(|(name, stats)| {
+                let provider = providers
+                    .iter()
+                    .find(|provider| provider.regions.iter().any(|region| &region.name == name))?;
+                Some(Self {
+                    provider: provider.name.clone(),
+                    region: name.clone(),
+                    score: ScoringAdapter::score_ping_stats(stats, weights, name).score,
+                    avg_latency_ms: stats.avg,
+                    packet_loss: stats.packet_loss,
+                    country: environment.country.clone(),
+                    asn: asn.clone(),
+                    measured_at: stats
+                        .test_time
+                        .duration_trunc(MEASURED_AT_PRECISION)
+                        .unwrap_or(stats.test_time),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Latency distribution the community reports for a region
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CommunityDistribution {
+    /// Provider name
+    pub provider: String,
+    /// Region name
+    pub region: String,
+    /// Country the distribution is limited to, or `None` for all users
+    pub country: Option<String>,
+    /// Number of submissions behind the distribution
+    pub samples: usize,
+    /// Latency quantiles in milliseconds, ascending and evenly spaced from
+    /// the 0th to the 100th percentile
+    pub latency_quantiles_ms: Vec<f64>,
+}
+
+impl CommunityDistribution {
+    /// Percentage of community results with higher latency than `latency_ms`
+    #[must_use]
+    pub fn better_than_percent(&self, latency_ms: f64) -> Option<f64> {
+        let quantiles = &self.latency_quantiles_ms;
+        let (&first, &last) = (quantiles.first()?, quantiles.last()?);
+        if quantiles.len() < 2 {
+            return None;
+        }
+        if latency_ms <= first {
+            return Some(100.0);
+        }
+        if latency_ms >= last {
+            return Some(0.0);
+        }
+
+        let steps = f64::from(u32::try_from(quantiles.len() - 1).ok()?);
+        let mut position = 0.0;
+        for (low, high) in quantiles.iter().zip(&quantiles[1..]) {
+            if latency_ms <= *high {
+                let fraction = if high > low {
+                    (latency_ms - low) / (high - low)
+                } else {
+                    1.0
+                };
+                position += fraction;
+                break;
+            }
+            position += 1.0;
+        }
+        Some((1.0 - position / steps) * 100.0)
+    }
+}
+
+/// A local result placed within the community distribution
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CommunityComparison {
+    /// Provider name
+    pub provider: String,
+    /// Region name
+    pub region: String,
+    /// Local average latency in milliseconds
+    pub latency_ms: f64,
+    /// Country the comparison is limited to, or `None` for all users
+    pub country: Option<String>,
+    /// Number of community submissions compared against
+    pub samples: usize,
+    /// Percentage of community results with higher latency
+    pub better_than_percent: f64,
+}
+
+impl CommunityComparison {
+    /// Sentence describing the comparison
+    #[must_use]
+    pub fn describe(&self) -> String {
+        let audience = self.country.as_ref().map_or_else(
+            || "all users".to_string(),
+            |country| format!("users in {country}"),
+        );
+        format!(
+            "Your latency to {} {} is better than {:.0}% of {audience}",
+            self.provider, self.region, self.better_than_percent
+        )
+    }
+}
+
+/// Uploads submissions to and fetches distributions from a community endpoint
+pub struct CommunityClient {
+    client: Client,
+    endpoint: String,
+}
+
+impl CommunityClient {
+    /// Create a client for `endpoint` using the app's timeout and user agent
+    pub fn new(config: &AppConfig, endpoint: &str) -> Result<Self> {
+        let client = ClientBuilder::new()
+            .timeout(config.get_timeout())
+            .user_agent(&config.user_agent)
+            .build()?;
+
+        Ok(Self {
+            client,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// Upload submissions with `POST {endpoint}/submissions`
+    pub async fn publish(&self, submissions: &[CommunitySubmission]) -> Result<()> {
+        if submissions.is_empty() {
+            return Ok(());
+        }
+
+        debug!("Uploading {} community submissions", submissions.len());
+        self.client
+            .post(format!("{}/submissions", self.endpoint))
+            .json(submissions)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Fetch a region's distribution with `GET {endpoint}/distribution`
+    pub async fn fetch_distribution(
+        &self,
+        provider: &str,
+        region: &str,
+        country: Option<&str>,
+    ) -> Result<CommunityDistribution> {
+        let mut query = vec![("provider", provider), ("region", region)];
+        if let Some(country) = country {
+            query.push(("country", country));
+        }
+
+        Ok(self
+            .client
+            .get(format!("{}/distribution", self.endpoint))
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// Compare each submission with its region's distribution, skipping regions that fail
+    pub async fn compare(&self, submissions: &[CommunitySubmission]) -> Vec<CommunityComparison> {
+        let fetches = submissions.iter().map(|submission| async move {
+            let distribution = match self
+                .fetch_distribution(
+                    &submission.provider,
+                    &submission.region,
+                    submission.country.as_deref(),
+                )
+                .await
+            {
+                Ok(distribution) => distribution,
+                Err(e) => {
+                    warn!(
+                        "Failed to fetch community results for {}: {}",
+                        submission.region, e
+                    );
+                    return None;
+                }
+            };
+
+            Some(CommunityComparison {
+                provider: submission.provider.clone(),
+                region: submission.region.clone(),
+                latency_ms: submission.avg_latency_ms,
+                country: distribution.country.clone(),
+                samples: distribution.samples,
+                better_than_percent: distribution.better_than_percent(submission.avg_latency_ms)?,
+            })
+        });
+
+        futures::future::join_all(fetches)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_better_than_percent() {
+        let distribution = CommunityDistribution {
+            provider: "AWS".to_string(),
+            region: "eu-west-1".to_string(),
+            country: Some("DE".to_string()),
+            samples: 500,
+            latency_quantiles_ms: vec![10.0, 20.0, 30.0, 40.0, 50.0],
+        };
+
+        assert!((distribution.better_than_percent(5.0).unwrap() - 100.0).abs() < 1e-9);
+        assert!((distribution.better_than_percent(30.0).unwrap() - 50.0).abs() < 1e-9);
+        assert!((distribution.better_than_percent(25.0).unwrap() - 62.5).abs() < 1e-9);
+        assert!(distribution.better_than_percent(80.0).unwrap().abs() < 1e-9);
+
+        let comparison = CommunityComparison {
+            provider: "AWS".to_string(),
+            region: "eu-west-1".to_string(),
+            latency_ms: 25.0,
+            country: Some("DE".to_string()),
+            samples: 500,
+            better_than_percent: 62.5,
+        };
+        assert_eq!(
+            comparison.describe(),
+            "Your latency to AWS eu-west-1 is better than 62% of users in DE"
+        );
+    }
+
+    #[test]
+    fn test_submissions_are_anonymised() {
+        let data = r#"[{"name": "AWS", "regions": [{"name": "eu-west-1", "url": "https://eu.example.com"}]}]"#;
+        let providers: Vec<CloudProvider> = serde_json::from_str(data).unwrap();
+        let environment = TestEnvironment {
+            public_ip: Some("203.0.113.7".to_string()),
+            asn: Some("AS3320 Deutsche Telekom AG".to_string()),
+            country: Some("DE".to_string()),
+            ..Default::default()
+        };
+        let mut stats = PingStats::new(10);
+        stats.successful_pings = 10;
+        stats.avg = 25.0;
+        stats.test_time = "2024-05-01T14:37:12.345Z".parse().unwrap();
+
+        let submissions = CommunitySubmission::from_results(
+            &[("eu-west-1".to_string(), stats)],
+            &providers,
+            &environment,
+            &AlgorithmWeights::default(),
+        );
+
+        assert_eq!(submissions.len(), 1);
+        assert_eq!(submissions[0].provider, "AWS");
+        assert_eq!(submissions[0].asn.as_deref(), Some("AS3320"));
+        assert_eq!(submissions[0].measured_at.to_rfc3339(), "2024-05-01T14:00:00+00:00");
+        assert!(!serde_json::to_string(&submissions)
+            .unwrap()
+            .contains("203.0.113.7"));
+    }
+}
@@ -4,10 +4,34 @@
 
 use config::{Config, ConfigError, Environment, File};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 use std::time::Duration;
+use tracing::warn;
 
+use crate::adaptive::AdaptiveConcurrencyConfig;
+use crate::asn::AsnConfig;
+use crate::transport::TlsConfig;
+use crate::budget::ProbeBudgetConfig;
+use crate::control::ControlConfig;
 use crate::error::{CloudPingError, Result};
+use crate::history_store::HistoryBackend;
+use crate::i18n::Locale;
+use crate::monitoring::MonitoringSettings;
+use crate::network::Deadlines;
+use crate::models::{
+    validate_labels, AlgorithmWeights, Coordinates, FootprintConstraints, GreenThreshold, JitterAlgorithm,
+    Recipe, ReportFilter, RetentionPolicy, RunLabels, SortKey, TickBudget,
+};
+use crate::provider_status::{default_status_feeds, StatusFeed};
+use crate::server::ApiSettings;
+use crate::trace_sampling::TraceSampling;
+
+/// Prefix of environment variables that override the configuration
+pub const ENV_PREFIX: &str = "CLOUD_PING";
+
+/// Separates nested keys in environment variable names
+pub const ENV_NESTING_SEPARATOR: &str = "__";
 
 /// Application configuration with defaults and validation
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,6 +72,205 @@ pub struct AppConfig {
     pub user_agent: String,
     /// Enable TLS certificate validation
     pub validate_certificates: bool,
+    /// TLS version, ALPN, client certificate and SNI settings of the HTTP client
+    #[serde(default)]
+    pub tls: TlsConfig,
+    /// Agent identifier used when pushing results to a collector (defaults to host name)
+    #[serde(default)]
+    pub agent_id: String,
+    /// Human-readable agent location included in pushed results
+    #[serde(default)]
+    pub agent_location: String,
+    /// Location of the client, used to flag physically impossible measurements
+    #[serde(default)]
+    pub client_coordinates: Option<Coordinates>,
+    /// Include measurements flagged as low quality when scoring
+    #[serde(default)]
+    pub score_low_quality: bool,
+    /// ISP first-hop address to time; discovered only with `trace_isp_first_hop`
+    #[serde(default)]
+    pub isp_first_hop: Option<String>,
+    /// Discover the ISP first hop with tracepath/traceroute before each run
+    /// when `isp_first_hop` is unset, which can take several seconds
+    #[serde(default)]
+    pub trace_isp_first_hop: bool,
+    /// JSON file recording region up/down transitions across runs
+    #[serde(default)]
+    pub availability_ledger: Option<String>,
+    /// Look up the public IP and ASN when capturing the test environment
+    #[serde(default)]
+    pub environment_lookup: bool,
+    /// STUN servers (`host:port`) asked for the public UDP mapping and NAT
+    /// type when capturing the test environment; none skips NAT discovery
+    #[serde(default)]
+    pub stun_servers: Vec<String>,
+    /// Provider status feeds polled for outage correlation
+    #[serde(default = "default_status_feeds")]
+    pub status_feeds: Vec<StatusFeed>,
+    /// How jitter is computed for benchmarks, scoring and live monitoring
+    #[serde(default)]
+    pub jitter_algorithm: JitterAlgorithm,
+    /// Where per-region test history is kept across runs: a `SQLite` or JSON
+    /// file, a sled directory or a Postgres URL
+    #[serde(default)]
+    pub history_file: Option<String>,
+    /// Store holding `history_file`, inferred from it when unset
+    #[serde(default)]
+    pub history_backend: Option<HistoryBackend>,
+    /// Write-ahead log holding runs the history store could not save yet
+    #[serde(default = "default_history_wal_file")]
+    pub history_wal_file: String,
+    /// How long saved runs are kept as they are before being folded into
+    /// hourly and daily aggregates; kept as they are forever when unset
+    #[serde(default)]
+    pub history_retention: Option<RetentionPolicy>,
+    /// TOML file of notes per region name or URL, shown in reports and exports
+    #[serde(default)]
+    pub notes_file: Option<String>,
+    /// city geolocation database (MMDB) locating regions without coordinates; needs the `geoip` feature
+    #[serde(default)]
+    pub geoip_file: Option<String>,
+    /// TOML file of prices per region name or URL, overriding `pricing_url` and the data file
+    #[serde(default)]
+    pub pricing_file: Option<String>,
+    /// URL of a JSON pricing document fetched when regions are loaded
+    #[serde(default)]
+    pub pricing_url: Option<String>,
+    /// TOML file of carbon intensity per region name or URL, overriding `carbon_url` and the data file
+    #[serde(default)]
+    pub carbon_file: Option<String>,
+    /// URL of a JSON carbon intensity feed fetched when regions are loaded
+    #[serde(default)]
+    pub carbon_url: Option<String>,
+    /// Bytes downloaded from the speed-test endpoint of each region that has
+    /// one in the data file, half as many uploaded (0 skips speed tests)
+    #[serde(default = "default_speed_test_bytes")]
+    pub speed_test_bytes: u64,
+    /// Requests per second allowed to a single host, shared by all regions on it (0 disables)
+    #[serde(default = "default_max_requests_per_host")]
+    pub max_requests_per_host_per_second: f64,
+    /// Caps on requests per run and per provider per hour
+    #[serde(default)]
+    pub probe_budget: ProbeBudgetConfig,
+    /// Upload anonymised result summaries to `community_endpoint` after each benchmark
+    #[serde(default)]
+    pub community_sharing: bool,
+    /// Community dataset service used for sharing and comparisons
+    #[serde(default)]
+    pub community_endpoint: Option<String>,
+    /// Server tick rate used by the gaming tick-budget score
+    #[serde(default = "default_gaming_tick_rate")]
+    pub gaming_tick_rate_hz: f64,
+    /// Stop a benchmark that is still running after this long, keeping partial results
+    #[serde(with = "humantime_serde", default)]
+    pub run_deadline: Option<Duration>,
+    /// Time limits per request attempt, per ping with its retries and per region
+    #[serde(default)]
+    pub deadlines: Deadlines,
+    /// Scale each region's pings and retries by its `priority`
+    #[serde(default)]
+    pub priority_scaling: bool,
+    /// Connections open at once across the benchmark and monitoring probes
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+    /// Lower concurrency during a run when a reference endpoint shows local saturation
+    #[serde(default)]
+    pub adaptive_concurrency: AdaptiveConcurrencyConfig,
+    /// Control endpoint measured during runs to normalize region latencies
+    #[serde(default)]
+    pub control: ControlConfig,
+    /// Labels attached to every benchmark run (e.g. `location = "office"`)
+    #[serde(default)]
+    pub labels: RunLabels,
+    /// Language and number format of HTML reports and the status page
+    #[serde(default)]
+    pub locale: Locale,
+    /// Replace emoji, box-drawing tables and block progress bars with plain ASCII
+    #[serde(default)]
+    pub ascii_only: bool,
+    /// Table columns dropped first, in order, when a table is wider than the terminal
+    #[serde(default)]
+    pub table_drop_order: Vec<String>,
+    /// Scoring weights; the built-in weights are used when unset
+    #[serde(default)]
+    pub weights: Option<AlgorithmWeights>,
+    /// Order of rankings in the terminal and in reports
+    #[serde(default)]
+    pub sort_by: SortKey,
+    /// Regions shown in rankings, in every output format
+    #[serde(default)]
+    pub report_filter: ReportFilter,
+    /// Size and redundancy of the footprint picked with `benchmark --footprint`
+    #[serde(default)]
+    pub footprint: FootprintConstraints,
+    /// Score and latency the greenest recommended region must reach
+    #[serde(default)]
+    pub green: GreenThreshold,
+    /// Sources resolving endpoints to the autonomous system announcing them
+    #[serde(default)]
+    pub asn: AsnConfig,
+    /// Traceroute every region after testing it and record a hash of the
+    /// path, so route changes between runs are detected
+    #[serde(default)]
+    pub route_tracing: bool,
+    /// Which finished probes and requests log their trace span
+    #[serde(default)]
+    pub trace_sampling: TraceSampling,
+    /// Application recipes checked with `recipe`, by name, next to the
+    /// built-in ones; a recipe replaces the built-in one of its name
+    #[serde(default)]
+    pub recipes: BTreeMap<String, Recipe>,
+    /// Probe, aggregation and alerting settings of the monitor mode
+    #[serde(default)]
+    pub monitoring: MonitoringSettings,
+    /// Tokens and TLS certificate of the HTTP API
+    #[serde(default)]
+    pub api: ApiSettings,
+    /// Named presets selected with [`AppConfig::load_profile`]
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ConfigProfile>,
+}
+
+/// Named preset that overrides part of the configuration
+///
+/// Declared as `[profiles.<name>]` in `config.toml`. Only the settings present
+/// in the profile replace the base configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfigProfile {
+    /// Pings per region for benchmarks
+    #[serde(default)]
+    pub default_ping_count: Option<usize>,
+    /// Pings per region for quick tests
+    #[serde(default)]
+    pub quick_ping_count: Option<usize>,
+    /// Request timeout
+    #[serde(with = "humantime_serde", default)]
+    pub timeout: Option<Duration>,
+    /// Retry attempts for failed requests
+    #[serde(default)]
+    pub retry_attempts: Option<usize>,
+    /// Delay between retries
+    #[serde(with = "humantime_serde", default)]
+    pub retry_delay: Option<Duration>,
+    /// Regions tested at once
+    #[serde(default)]
+    pub max_threads: Option<usize>,
+    /// Scoring weights
+    #[serde(default)]
+    pub weights: Option<AlgorithmWeights>,
+    /// Output format
+    #[serde(default)]
+    pub output_format: Option<OutputFormat>,
+    /// Progress bars
+    #[serde(default)]
+    pub show_progress: Option<bool>,
+    /// Colored terminal output
+    #[serde(default)]
+    pub enable_color_output: Option<bool>,
+}
+
+const fn default_max_connections() -> usize {
+    crate::MAX_CONCURRENT_CONNECTIONS
 }
 
 fn default_timeout() -> Duration {
@@ -58,6 +281,22 @@ fn default_retry_delay() -> Duration {
     Duration::from_millis(100)
 }
 
+const fn default_gaming_tick_rate() -> f64 {
+    64.0
+}
+
+const fn default_max_requests_per_host() -> f64 {
+    10.0
+}
+
+const fn default_speed_test_bytes() -> u64 {
+    10_000_000
+}
+
+fn default_history_wal_file() -> String {
+    "history-wal.log".to_string()
+}
+
 /// Supported output formats for test results
 #[derive(Debug, Clone, Serialize, Deserialize, clap::ValueEnum)]
 #[serde(rename_all = "lowercase")]
@@ -93,6 +332,56 @@ impl Default for AppConfig {
             output_format: OutputFormat::default(),
             user_agent: format!("cloud-ping-rs/{}", env!("CARGO_PKG_VERSION")),
             validate_certificates: false,
+            tls: TlsConfig::default(),
+            agent_id: String::new(),
+            agent_location: String::new(),
+            client_coordinates: None,
+            score_low_quality: false,
+            isp_first_hop: None,
+            trace_isp_first_hop: false,
+            availability_ledger: None,
+            environment_lookup: false,
+            stun_servers: Vec::new(),
+            status_feeds: default_status_feeds(),
+            jitter_algorithm: JitterAlgorithm::default(),
+            history_file: None,
+            history_backend: None,
+            history_wal_file: default_history_wal_file(),
+            history_retention: None,
+            notes_file: None,
+            geoip_file: None,
+            pricing_file: None,
+            pricing_url: None,
+            carbon_file: None,
+            carbon_url: None,
+            speed_test_bytes: default_speed_test_bytes(),
+            max_requests_per_host_per_second: default_max_requests_per_host(),
+            probe_budget: ProbeBudgetConfig::default(),
+            community_sharing: false,
+            community_endpoint: None,
+            gaming_tick_rate_hz: default_gaming_tick_rate(),
+            run_deadline: None,
+            deadlines: Deadlines::default(),
+            priority_scaling: false,
+            max_connections: default_max_connections(),
+            adaptive_concurrency: AdaptiveConcurrencyConfig::default(),
+            control: ControlConfig::default(),
+            labels: RunLabels::new(),
+            locale: Locale::default(),
+            ascii_only: false,
+            table_drop_order: Vec::new(),
+            weights: None,
+            sort_by: SortKey::default(),
+            report_filter: ReportFilter::default(),
+            footprint: FootprintConstraints::default(),
+            green: GreenThreshold::default(),
+            asn: AsnConfig::default(),
+            route_tracing: false,
+            trace_sampling: TraceSampling::default(),
+            recipes: BTreeMap::new(),
+            monitoring: MonitoringSettings::default(),
+            api: ApiSettings::default(),
+            profiles: BTreeMap::new(),
         }
     }
 }
@@ -104,25 +393,124 @@ impl AppConfig {
     /// 1. Environment variables (CLOUD_PING_*)
     /// 2. Config file (~/.config/cloud-ping-rs/config.toml)
     /// 3. Built-in defaults
+    ///
+    /// Environment variable names are the key in upper case after the
+    /// `CLOUD_PING_` prefix, with `__` between nested keys:
+    /// `CLOUD_PING_TIMEOUT_MS` sets `timeout_ms` and
+    /// `CLOUD_PING_MONITORING__PROBE__INTERVAL` sets `monitoring.probe.interval`.
     pub fn load() -> Result<Self> {
-        let mut config = Config::builder()
-            .add_source(Config::try_from(&AppConfig::default())?)
-            .add_source(Environment::with_prefix("CLOUD_PING").separator("_"));
+        let mut config = Config::builder().add_source(Config::try_from(&AppConfig::default())?);
 
         // Try to load from config file
-        if let Some(config_path) = Self::get_config_path() {
+        if let Some(config_path) = Self::config_path() {
             if config_path.exists() {
                 config = config.add_source(File::from(config_path));
             }
         }
 
+        for name in Self::unknown_env_vars(std::env::vars()) {
+            warn!("Ignoring {}: no such configuration key", name);
+        }
+
         config
+            .add_source(Self::environment(None))
             .build()
             .and_then(|c| c.try_deserialize())
             .map_err(|e| CloudPingError::config(format!("Failed to load configuration: {}", e)))
     }
 
-    fn get_config_path() -> Option<PathBuf> {
+    /// Environment source reading `CLOUD_PING_*` variables, or `vars` instead of the process environment
+    pub(crate) fn environment(vars: Option<HashMap<String, String>>) -> Environment {
+        Environment::with_prefix(ENV_PREFIX)
+            .prefix_separator("_")
+            .separator(ENV_NESTING_SEPARATOR)
+            .source(vars)
+    }
+
+    /// Names of `CLOUD_PING_*` variables in `vars` that match no configuration key
+    #[must_use]
+    pub fn unknown_env_vars(vars: impl IntoIterator<Item = (String, String)>) -> Vec<String> {
+        let keys = serde_json::to_value(Self::default())
+            .ok()
+            .and_then(|value| value.as_object().map(|object| object.keys().cloned().collect::<Vec<_>>()))
+            .unwrap_or_default();
+        let prefix = format!("{ENV_PREFIX}_");
+
+        vars.into_iter()
+            .map(|(name, _)| name)
+            .filter(|name| {
+                name.strip_prefix(&prefix).is_some_and(|key| {
+                    let top_level = key.split(ENV_NESTING_SEPARATOR).next().unwrap_or_default();
+                    !keys.iter().any(|known| known.eq_ignore_ascii_case(top_level))
+                })
+            })
+            .collect()
+    }
+
+    /// Load configuration as [`AppConfig::load`] does, then apply profile `name`
+    ///
+    /// # Errors
+    /// Returns an error if loading fails, no such profile is configured or the
+    /// resulting configuration is invalid
+    pub fn load_profile(name: &str) -> Result<Self> {
+        let mut config = Self::load()?;
+        config.apply_profile(name)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Override settings with those of profile `name`
+    ///
+    /// # Errors
+    /// Returns an error if no such profile is configured
+    pub fn apply_profile(&mut self, name: &str) -> Result<()> {
+        let profile = self.profiles.get(name).cloned().ok_or_else(|| {
+            let available: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            CloudPingError::config(format!(
+                "Unknown profile '{}' (available: {})",
+                name,
+                if available.is_empty() { "none".to_string() } else { available.join(", ") }
+            ))
+        })?;
+
+        if let Some(count) = profile.default_ping_count {
+            self.default_ping_count = count;
+        }
+        if let Some(count) = profile.quick_ping_count {
+            self.quick_ping_count = count;
+        }
+        if let Some(timeout) = profile.timeout {
+            self.timeout = timeout;
+            self.timeout_ms = u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX);
+        }
+        if let Some(attempts) = profile.retry_attempts {
+            self.retry_attempts = attempts;
+        }
+        if let Some(delay) = profile.retry_delay {
+            self.retry_delay = delay;
+            self.retry_delay_ms = u64::try_from(delay.as_millis()).unwrap_or(u64::MAX);
+        }
+        if let Some(threads) = profile.max_threads {
+            self.max_threads = threads;
+        }
+        if profile.weights.is_some() {
+            self.weights = profile.weights;
+        }
+        if let Some(format) = profile.output_format {
+            self.output_format = format;
+        }
+        if let Some(show_progress) = profile.show_progress {
+            self.show_progress = show_progress;
+        }
+        if let Some(color) = profile.enable_color_output {
+            self.enable_color_output = color;
+        }
+        Ok(())
+    }
+
+    /// Location of the config file, whether or not it exists
+    #[must_use]
+    pub fn config_path() -> Option<PathBuf> {
         dirs::config_dir().map(|mut path| {
             path.push("cloud-ping-rs");
             path.push("config.toml");
@@ -132,7 +520,7 @@ impl AppConfig {
 
     /// Persist configuration to default location
     pub fn save(&self) -> Result<()> {
-        let config_path = Self::get_config_path()
+        let config_path = Self::config_path()
             .ok_or_else(|| CloudPingError::config("Cannot determine config directory"))?;
 
         if let Some(parent) = config_path.parent() {
@@ -183,6 +571,90 @@ impl AppConfig {
             ));
         }
 
+        TickBudget::new(self.gaming_tick_rate_hz)?;
+
+        if !self.max_requests_per_host_per_second.is_finite()
+            || self.max_requests_per_host_per_second < 0.0
+        {
+            return Err(CloudPingError::validation(
+                "max_requests_per_host_per_second",
+                "must be 0 (unlimited) or a positive number",
+            ));
+        }
+
+        if self.max_connections == 0 {
+            return Err(CloudPingError::validation(
+                "max_connections",
+                "must be greater than 0",
+            ));
+        }
+
+        let adaptive = &self.adaptive_concurrency;
+        if let Some(url) = &adaptive.reference_url {
+            url::Url::parse(url).map_err(|e| {
+                CloudPingError::validation("adaptive_concurrency.reference_url", e.to_string())
+            })?;
+        }
+        if adaptive.interval.is_zero() {
+            return Err(CloudPingError::validation(
+                "adaptive_concurrency.interval",
+                "must be greater than 0",
+            ));
+        }
+        if adaptive.degradation_ratio <= 1.0 {
+            return Err(CloudPingError::validation(
+                "adaptive_concurrency.degradation_ratio",
+                "must be greater than 1",
+            ));
+        }
+        if adaptive.min_threads == 0 {
+            return Err(CloudPingError::validation(
+                "adaptive_concurrency.min_threads",
+                "must be greater than 0",
+            ));
+        }
+
+        if let Some(url) = &self.control.url {
+            url::Url::parse(url)
+                .map_err(|e| CloudPingError::validation("control.url", e.to_string()))?;
+        }
+        if self.control.interval.is_zero() {
+            return Err(CloudPingError::validation(
+                "control.interval",
+                "must be greater than 0",
+            ));
+        }
+
+        if self.run_deadline.is_some_and(|deadline| deadline.is_zero()) {
+            return Err(CloudPingError::validation(
+                "run_deadline",
+                "must be greater than 0",
+            ));
+        }
+
+        self.validate_sections()
+    }
+
+    /// Validate the settings that check themselves
+    fn validate_sections(&self) -> Result<()> {
+        validate_labels(&self.labels)?;
+        self.report_filter.validate()?;
+        self.footprint.validate()?;
+        self.green.validate()?;
+        self.tls.validate()?;
+        self.deadlines.validate()?;
+        self.asn.validate()?;
+        self.trace_sampling.validate()?;
+        for (name, recipe) in &self.recipes {
+            recipe.validate(name)?;
+        }
+        if let Some(location) = &self.history_file {
+            HistoryBackend::for_location(self.history_backend, location).validate()?;
+        }
+        self.history_retention.as_ref().map_or(Ok(()), RetentionPolicy::validate)?;
+        self.monitoring.validate()?;
+        self.api.validate()?;
+
         Ok(())
     }
 
@@ -191,6 +663,14 @@ impl AppConfig {
         self.timeout
     }
 
+    /// Time one request attempt may take: `deadlines.attempt`, or `timeout_ms` when it is unset
+    #[must_use]
+    pub fn attempt_timeout(&self) -> Duration {
+        self.deadlines
+            .attempt
+            .unwrap_or_else(|| Duration::from_millis(self.timeout_ms))
+    }
+
     /// Get retry delay as Duration (preferred over retry_delay_ms)
     pub fn get_retry_delay(&self) -> Duration {
         self.retry_delay
@@ -0,0 +1,163 @@
+//! Process-wide cap on open connections
+//!
+//! The benchmark and the monitoring probes each limit their own concurrency,
+//! but an agent runs both at once, so together they can open far more
+//! connections than `max_connections`. A [`ConnectionBudget`] is one semaphore
+//! shared by every component that sends requests, with counters showing how
+//! much it was used and how often callers had to wait.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, TryAcquireError};
+
+use crate::error::{CloudPingError, Result};
+
+/// Usage counters of a budget
+#[derive(Debug, Default)]
+struct BudgetMetrics {
+    in_use: AtomicUsize,
+    peak_in_use: AtomicUsize,
+    acquired: AtomicU64,
+    waited: AtomicU64,
+    wait_micros: AtomicU64,
+}
+
+/// Snapshot of a budget's usage
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ConnectionBudgetStats {
+    /// Connections allowed at once
+    pub capacity: usize,
+    /// Connections open right now
+    pub in_use: usize,
+    /// Most connections open at the same time
+    pub peak_in_use: usize,
+    /// Permits handed out so far
+    pub acquired: u64,
+    /// Permits that were not immediately available
+    pub waited: u64,
+    /// Total time callers spent waiting for a permit
+    #[serde(with = "humantime_serde")]
+    pub total_wait: Duration,
+}
+
+/// Semaphore limiting the connections open across benchmark and monitoring
+#[derive(Debug, Clone)]
+pub struct ConnectionBudget {
+    semaphore: Arc<Semaphore>,
+    capacity: usize,
+    metrics: Arc<BudgetMetrics>,
+}
+
+impl ConnectionBudget {
+    /// Allow at most `capacity` connections at once
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(capacity)),
+            capacity,
+            metrics: Arc::new(BudgetMetrics::default()),
+        }
+    }
+
+    /// Connections allowed at once
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Wait for a free connection slot, held until the permit is dropped
+    pub async fn acquire(&self) -> Result<ConnectionPermit> {
+        let permit = match Arc::clone(&self.semaphore).try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(TryAcquireError::NoPermits) => {
+                let start = Instant::now();
+                let permit = Arc::clone(&self.semaphore)
+                    .acquire_owned()
+                    .await
+                    .map_err(|e| {
+                        CloudPingError::concurrency(format!("Connection budget closed: {e}"))
+                    })?;
+                let waited = u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX);
+                self.metrics.waited.fetch_add(1, Ordering::Relaxed);
+                self.metrics
+                    .wait_micros
+                    .fetch_add(waited, Ordering::Relaxed);
+                permit
+            }
+            Err(TryAcquireError::Closed) => {
+                return Err(CloudPingError::concurrency("Connection budget closed"));
+            }
+        };
+
+        self.metrics.acquired.fetch_add(1, Ordering::Relaxed);
+        let in_use = self.metrics.in_use.fetch_add(1, Ordering::Relaxed) + 1;
+        self.metrics
+            .peak_in_use
+            .fetch_max(in_use, Ordering::Relaxed);
+
+        Ok(ConnectionPermit {
+            _permit: permit,
+            metrics: Arc::clone(&self.metrics),
+        })
+    }
+
+    /// Current usage of the budget
+    #[must_use]
+    pub fn stats(&self) -> ConnectionBudgetStats {
+        ConnectionBudgetStats {
+            capacity: self.capacity,
+            in_use: self.metrics.in_use.load(Ordering::Relaxed),
+            peak_in_use: self.metrics.peak_in_use.load(Ordering::Relaxed),
+            acquired: self.metrics.acquired.load(Ordering::Relaxed),
+            waited: self.metrics.waited.load(Ordering::Relaxed),
+            total_wait: Duration::from_micros(self.metrics.wait_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// A connection slot, returned to the budget when dropped
+#[derive(Debug)]
+pub struct ConnectionPermit {
+    _permit: OwnedSemaphorePermit,
+    metrics: Arc<BudgetMetrics>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.metrics.in_use.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_budget_caps_and_counts_connections() {
+        let budget = ConnectionBudget::new(2);
+        let first = budget.acquire().await.unwrap();
+        let second = budget.acquire().await.unwrap();
+        assert_eq!(budget.stats().in_use, 2);
+
+        // A third caller waits until a slot frees up
+        let shared = budget.clone();
+        let waiter = tokio::spawn(async move { shared.acquire().await.map(drop) });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        drop(first);
+        waiter.await.unwrap().unwrap();
+        drop(second);
+
+        let stats = budget.stats();
+        assert_eq!(stats.capacity, 2);
+        assert_eq!(stats.in_use, 0);
+        assert_eq!(stats.peak_in_use, 2);
+        assert_eq!(stats.acquired, 3);
+        assert_eq!(stats.waited, 1);
+        assert!(stats.total_wait >= Duration::from_millis(10));
+    }
+}
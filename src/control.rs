@@ -0,0 +1,129 @@
+//! Continuous measurement of a control endpoint during a run
+//!
+//! A [`ControlMonitor`] pings a configured control endpoint in the background
+//! while regions are tested. The resulting [`ControlSeries`] is used to
+//! report every region's latency both raw and normalized against the control.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::models::{ControlSample, ControlSeries};
+use crate::network::NetworkTester;
+use crate::time_utils::TimeUtils;
+
+/// Settings of the control endpoint
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ControlConfig {
+    /// Endpoint pinged throughout each run; normalization is off when unset
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Time between control pings
+    #[serde(with = "humantime_serde", default = "default_interval")]
+    pub interval: Duration,
+}
+
+const fn default_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            interval: default_interval(),
+        }
+    }
+}
+
+/// Pings the control endpoint in the background
+pub struct ControlMonitor {
+    stop: CancellationToken,
+    task: JoinHandle<ControlSeries>,
+}
+
+impl ControlMonitor {
+    /// Start pinging the control endpoint, first right away and then every `interval`
+    ///
+    /// Returns `None` when no control endpoint is configured.
+    #[must_use]
+    pub fn start(config: &ControlConfig, tester: NetworkTester) -> Option<Self> {
+        let url = config.url.clone()?;
+        let interval = config.interval;
+        let stop = CancellationToken::new();
+        let stopped = stop.clone();
+
+        info!("Measuring control endpoint {} every {:?}", url, interval);
+        let task = tokio::spawn(async move {
+            let mut series = ControlSeries {
+                url,
+                samples: Vec::new(),
+            };
+            loop {
+                let at = TimeUtils::now();
+                let timing = tokio::select! {
+                    () = stopped.cancelled() => break,
+                    timing = tester.ping_url_with_retry(&series.url, 0) => timing,
+                };
+                series.samples.push(ControlSample {
+                    at,
+                    latency_ms: timing
+                        .success
+                        .then_some(timing.total_time.as_secs_f64() * 1000.0),
+                });
+
+                tokio::select! {
+                    () = stopped.cancelled() => break,
+                    () = tokio::time::sleep(interval) => {}
+                }
+            }
+            series
+        });
+
+        Some(Self { stop, task })
+    }
+
+    /// Stop pinging and return the samples taken
+    pub async fn stop(self) -> ControlSeries {
+        self.stop.cancel();
+        self.task.await.unwrap_or_else(|e| {
+            warn!("Control endpoint monitor failed: {}", e);
+            ControlSeries::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::test_util::MockTransport;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_monitor_samples_until_stopped() {
+        let transport = Arc::new(
+            MockTransport::new().latency("https://control.example.com", Duration::from_millis(12)),
+        );
+        let config = AppConfig {
+            max_requests_per_host_per_second: 0.0,
+            ..AppConfig::default()
+        };
+        let tester = NetworkTester::with_transport(config, transport).unwrap();
+        let control = ControlConfig {
+            url: Some("https://control.example.com".to_string()),
+            interval: Duration::from_millis(5),
+        };
+
+        let monitor = ControlMonitor::start(&control, tester.clone()).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let series = monitor.stop().await;
+
+        assert!(series.samples.len() > 2);
+        assert_eq!(series.baseline_ms(), Some(12.0));
+        assert!(ControlMonitor::start(&ControlConfig::default(), tester).is_none());
+    }
+}
@@ -8,7 +8,7 @@ use serde_json;
 use serde_path_to_error;
 
 
-use crate::models::{CloudProvider, Region, utils::generate_uuid};
+use crate::models::{country_from_name, CloudProvider, Region, utils::generate_uuid};
 
 /// Utilities for loading cloud provider data from JSON files
 pub struct DataLoader;
@@ -105,7 +105,13 @@ impl DataLoader {
         if let Some(regions_array) = regions_data.as_array() {
             for region_value in regions_array {
                 match serde_path_to_error::deserialize::<_, Region>(region_value.clone()) {
-                    Ok(region) => provider.regions.push(region),
+                    Ok(mut region) => {
+                        if region.provider.is_empty() {
+                            region.provider.clone_from(&provider.name);
+                        }
+                        Self::fill_country(&mut region);
+                        provider.regions.push(region);
+                    }
                     Err(e) => {
                         eprintln!("Warning: Failed to parse region in {}: {}", provider.name, e);
                     }
@@ -115,6 +121,15 @@ impl DataLoader {
         Ok(())
     }
 
+    /// Set the country of a region without one from the place its name mentions
+    fn fill_country(region: &mut Region) {
+        if region.country.is_empty() {
+            if let Some(country) = country_from_name(&region.name) {
+                region.country = country.to_string();
+            }
+        }
+    }
+
     fn parse_gaming_servers(
         provider: &mut CloudProvider,
         provider_data: &serde_json::Value,
@@ -127,6 +142,10 @@ impl DataLoader {
                             match serde_path_to_error::deserialize::<_, Region>(region_value.clone()) {
                                 Ok(mut region) => {
                                     region.name = format!("{} - {}", game_name, region.name);
+                                    if region.provider.is_empty() {
+                                        region.provider.clone_from(&provider.name);
+                                    }
+                                    Self::fill_country(&mut region);
                                     provider.regions.push(region);
                                 }
                                 Err(e) => {
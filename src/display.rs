@@ -3,9 +3,18 @@
 //! Provides structured output formatting for test results with scoring
 //! and ranking information.
 
-use crate::models::{PingStats, AlgorithmWeights, ScoringAdapter};
-use crate::ui_utils::DisplayUtils;
-use tabled::{Table, Tabled, settings::{Style, Alignment, Modify, object::Columns}};
+use crate::community::CommunityComparison;
+use crate::collector::{MajorityRecommendation, MultiVantageResult, VantageMatrix};
+use crate::doctor::{CheckStatus, DoctorReport};
+use crate::models::{AgentInfo, BenchmarkPlan, ConcurrencyAdjustment, ControlSeries, RegionFailure, TestEnvironment, TestHistory, PingStats, AlgorithmWeights, RankedResult, ScoreExplanation, ScoringAdapter, TickBudget, WhatIfComparison, Footprint, continent_of, GreenRecommendation, LatencyMatrix, EstimateSource, EdgePop, AsnBreakdown, IpFamily, RaceResult, SoakResult, TransactionResult, UdpMatrixRow, UdpPort, Recipe, RecipeResult, RecipeVerdict, CheckOutcome, UdpReachability, Rollup, ConnectionReuse, CertValidationSummary, SpeedTestResult, BufferbloatResult};
+use crate::provider_status::IncidentAnnotation;
+use crate::simulation::SimulationReport;
+use crate::time_utils::TimeUtils;
+use crate::format_utils::FormatUtils;
+use crate::ui_utils::{DisplayUtils, ProgressBarFactory};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tabled::{Table, Tabled, builder::Builder, settings::{Alignment, Disable, Modify, location::ByColumnName, object::Columns}};
 
 /// Table row for ranking display
 #[derive(Tabled)]
@@ -18,14 +27,287 @@ struct RankingRow {
     score: String,
     #[tabled(rename = "Grade")]
     grade: char,
+    #[tabled(rename = "Health")]
+    health: String,
     #[tabled(rename = "Latency")]
     latency: String,
+    #[tabled(rename = "Trend")]
+    trend: String,
     #[tabled(rename = "Loss %")]
     loss: String,
     #[tabled(rename = "Gaming")]
     gaming: String,
     #[tabled(rename = "Streaming")]
     streaming: String,
+    #[tabled(rename = "Value")]
+    value: String,
+    #[tabled(rename = "gCO2/kWh")]
+    carbon: String,
+    #[tabled(rename = "Edge")]
+    edge: String,
+    #[tabled(rename = "AS")]
+    asn: String,
+    #[tabled(rename = "Notes")]
+    notes: String,
+}
+
+/// Table row for a deployment footprint
+#[derive(Tabled)]
+struct FootprintRow {
+    #[tabled(rename = "Rank")]
+    rank: usize,
+    #[tabled(rename = "Region")]
+    region: String,
+    #[tabled(rename = "Provider")]
+    provider: String,
+    #[tabled(rename = "Continent")]
+    continent: String,
+    #[tabled(rename = "Score")]
+    score: String,
+    #[tabled(rename = "Grade")]
+    grade: char,
+    #[tabled(rename = "Latency")]
+    latency: String,
+}
+
+/// Table row for the regions served by one CDN edge POP
+#[derive(Tabled)]
+struct EdgePopRow {
+    #[tabled(rename = "CDN")]
+    cdn: String,
+    #[tabled(rename = "POP")]
+    pop: String,
+    #[tabled(rename = "Pings")]
+    pings: usize,
+    #[tabled(rename = "Avg Latency")]
+    latency: String,
+    #[tabled(rename = "Regions")]
+    regions: String,
+}
+
+/// Table row for the regions behind one autonomous system
+#[derive(Tabled)]
+struct AsnRow {
+    #[tabled(rename = "AS")]
+    asn: String,
+    #[tabled(rename = "Organization")]
+    organization: String,
+    #[tabled(rename = "Avg Latency")]
+    latency: String,
+    #[tabled(rename = "Slow")]
+    slow: String,
+    #[tabled(rename = "Regions")]
+    regions: String,
+}
+
+/// Table row for the connection races against one endpoint
+#[derive(Tabled)]
+struct RaceRow {
+    #[tabled(rename = "Region")]
+    region: String,
+    #[tabled(rename = "Winner")]
+    winner: String,
+    #[tabled(rename = "Margin")]
+    margin: String,
+    #[tabled(rename = "Best IPv6")]
+    ipv6: String,
+    #[tabled(rename = "Best IPv4")]
+    ipv4: String,
+    #[tabled(rename = "Addresses")]
+    addresses: usize,
+}
+
+/// Table row for one raced address
+#[derive(Tabled)]
+struct RaceAddressRow {
+    #[tabled(rename = "Region")]
+    region: String,
+    #[tabled(rename = "Address")]
+    address: String,
+    #[tabled(rename = "Family")]
+    family: String,
+    #[tabled(rename = "Wins")]
+    wins: String,
+    #[tabled(rename = "Connect")]
+    connect: String,
+    #[tabled(rename = "Failures")]
+    failures: usize,
+}
+
+/// Table row for the soak test of one region
+#[derive(Tabled)]
+struct SoakRow {
+    #[tabled(rename = "Region")]
+    region: String,
+    #[tabled(rename = "Pings")]
+    pings: usize,
+    #[tabled(rename = "Availability")]
+    availability: String,
+    #[tabled(rename = "P50")]
+    p50: String,
+    #[tabled(rename = "P95")]
+    p95: String,
+    #[tabled(rename = "Worst P95")]
+    worst_p95: String,
+    #[tabled(rename = "P95 Trend")]
+    trend: String,
+    #[tabled(rename = "Availability Timeline")]
+    timeline: String,
+}
+
+/// Table row for multi-vantage ranking display
+#[derive(Tabled)]
+struct VantageRankingRow {
+    #[tabled(rename = "Rank")]
+    rank: usize,
+    #[tabled(rename = "Region")]
+    region: String,
+    #[tabled(rename = "Agents")]
+    agents: usize,
+    #[tabled(rename = "Mean Score")]
+    mean_score: String,
+    #[tabled(rename = "Mean Latency")]
+    mean_latency: String,
+    #[tabled(rename = "Best Vantage")]
+    best_vantage: String,
+}
+
+/// Table row for the local versus beyond-ISP latency split
+#[derive(Tabled)]
+struct BeyondIspRow {
+    #[tabled(rename = "Region")]
+    region: String,
+    #[tabled(rename = "RTT")]
+    rtt: String,
+    #[tabled(rename = "Beyond ISP")]
+    beyond_isp: String,
+    #[tabled(rename = "Local Share")]
+    local_share: String,
+}
+
+/// Table row for per-region data usage
+#[derive(Tabled)]
+struct DataUsageRow {
+    #[tabled(rename = "Region")]
+    region: String,
+    #[tabled(rename = "Sent")]
+    sent: String,
+    #[tabled(rename = "Received")]
+    received: String,
+    #[tabled(rename = "Total")]
+    total: String,
+}
+
+/// Table row for a region that failed during a run
+#[derive(Tabled)]
+struct FailedRegionRow {
+    #[tabled(rename = "Region")]
+    region: String,
+    #[tabled(rename = "Cause")]
+    cause: String,
+    #[tabled(rename = "Error")]
+    error: String,
+}
+
+/// Table row for latency normalized against the control endpoint
+#[derive(Tabled)]
+struct NormalizedLatencyRow {
+    #[tabled(rename = "Region")]
+    region: String,
+    #[tabled(rename = "Raw")]
+    raw: String,
+    #[tabled(rename = "Control")]
+    control: String,
+    #[tabled(rename = "Normalized")]
+    normalized: String,
+}
+
+/// Table row for the community comparison
+#[derive(Tabled)]
+struct CommunityRow {
+    #[tabled(rename = "Region")]
+    region: String,
+    #[tabled(rename = "Your Latency")]
+    latency: String,
+    #[tabled(rename = "Better Than")]
+    better_than: String,
+    #[tabled(rename = "Compared With")]
+    compared_with: String,
+}
+
+/// Table row for the final scores of a simulation
+#[derive(Tabled)]
+struct SimulationScoreRow {
+    #[tabled(rename = "Endpoint")]
+    endpoint: String,
+    #[tabled(rename = "Score")]
+    score: String,
+    #[tabled(rename = "Grade")]
+    grade: char,
+    #[tabled(rename = "Gaming")]
+    gaming: String,
+    #[tabled(rename = "Streaming")]
+    streaming: String,
+}
+
+/// Table row for the time-of-day summary
+#[derive(Tabled)]
+struct TimeOfDayRow {
+    #[tabled(rename = "Region")]
+    region: String,
+    #[tabled(rename = "Hours")]
+    hours: usize,
+    #[tabled(rename = "Best Hour")]
+    best: String,
+    #[tabled(rename = "Worst Hour")]
+    worst: String,
+    #[tabled(rename = "Peak Penalty")]
+    penalty: String,
+}
+
+/// Table row for the gaming tick-budget ranking
+#[derive(Tabled)]
+struct TickBudgetRow {
+    #[tabled(rename = "Rank")]
+    rank: usize,
+    #[tabled(rename = "Region")]
+    region: String,
+    #[tabled(rename = "Avg RTT")]
+    latency: String,
+    #[tabled(rename = "≤ 1 Tick")]
+    one_tick: String,
+    #[tabled(rename = "≤ 2 Ticks")]
+    two_ticks: String,
+    #[tabled(rename = "Tick Score")]
+    score: String,
+}
+
+/// Table row for one component of a score explanation
+#[derive(Tabled)]
+struct ScoreComponentRow {
+    #[tabled(rename = "Component")]
+    component: String,
+    #[tabled(rename = "Raw")]
+    raw: String,
+    #[tabled(rename = "Band")]
+    rule: String,
+    #[tabled(rename = "Score")]
+    normalized: String,
+    #[tabled(rename = "Weight")]
+    weight: String,
+    #[tabled(rename = "Points")]
+    contribution: String,
+}
+
+/// Table row for a doctor check
+#[derive(Tabled)]
+struct DoctorRow {
+    #[tabled(rename = "Check")]
+    check: String,
+    #[tabled(rename = "Result")]
+    status: String,
+    #[tabled(rename = "Detail")]
+    detail: String,
 }
 
 /// Table row for detailed metrics display
@@ -55,6 +337,36 @@ impl DisplayFormatter {
         }
     }
 
+    /// Rows of a speed test's throughput and latency under load
+    fn speed_test_rows(speed_test: &SpeedTestResult) -> Vec<MetricsRow> {
+        let mbps = |value: Option<f64>| value.map_or_else(|| "-".to_string(), |mbps| format!("{mbps:.1} Mbps"));
+        let mut rows = vec![
+            MetricsRow {
+                metric: "Download".to_string(),
+                value: mbps(speed_test.download_mbps),
+                score: "-".to_string(),
+            },
+            MetricsRow {
+                metric: "Upload".to_string(),
+                value: mbps(speed_test.upload_mbps),
+                score: "-".to_string(),
+            },
+        ];
+        if let Some(loaded) = speed_test.loaded_latency_ms {
+            rows.push(MetricsRow {
+                metric: "Latency (loaded)".to_string(),
+                value: speed_test.bufferbloat_ms().map_or_else(
+                    || format!("{loaded:.2} ms"),
+                    |bloat| format!("{loaded:.2} ms (+{bloat:.2} ms)"),
+                ),
+                score: speed_test
+                    .bufferbloat_grade()
+                    .map_or_else(|| "-".to_string(), |grade| grade.to_string()),
+            });
+        }
+        rows
+    }
+
     /// Display comprehensive results with scoring breakdown
     pub fn display_enhanced_results(name: &str, stats: &PingStats, weights: &AlgorithmWeights) {
         let score = ScoringAdapter::score_ping_stats(stats, weights, name);
@@ -68,7 +380,7 @@ impl DisplayFormatter {
         }
 
         // Create metrics table
-        let metrics_data = vec![
+        let mut metrics_data = vec![
             MetricsRow {
                 metric: "Latency (avg)".to_string(),
                 value: format!("{:.2} ms", stats.avg),
@@ -89,6 +401,20 @@ impl DisplayFormatter {
                 value: format!("{:.1}%", stats.packet_loss),
                 score: format!("{:.1}", score.components.packet_loss_score),
             },
+            MetricsRow {
+                metric: "Loss Bursts".to_string(),
+                value: if stats.loss_pattern.lost == 0 {
+                    "none".to_string()
+                } else {
+                    format!(
+                        "max {}, ratio {:.2}{}",
+                        stats.loss_pattern.max_burst,
+                        stats.loss_pattern.burst_ratio,
+                        if stats.loss_pattern.is_bursty() { " (bursty)" } else { "" }
+                    )
+                },
+                score: "-".to_string(),
+            },
             MetricsRow {
                 metric: "Availability".to_string(),
                 value: format!("{}/{} successful", stats.successful_pings, stats.total_pings),
@@ -100,14 +426,19 @@ impl DisplayFormatter {
                 score: score.grade.to_string(),
             },
         ];
+        if let Some(speed_test) = &stats.speed_test {
+            let overall = metrics_data.pop();
+            metrics_data.extend(Self::speed_test_rows(speed_test));
+            metrics_data.extend(overall);
+        }
 
         let mut table = Table::new(metrics_data);
-        table
-            .with(Style::rounded())
+        DisplayUtils::style_table(&mut table)
             .with(Modify::new(Columns::single(0)).with(Alignment::left()))
             .with(Modify::new(Columns::single(1)).with(Alignment::right()))
             .with(Modify::new(Columns::single(2)).with(Alignment::center()));
 
+        DisplayUtils::fit_table(&mut table, &[]);
         println!("{}", table);
 
         // Display suitability scores
@@ -148,38 +479,39 @@ impl DisplayFormatter {
         ];
 
         let mut table = Table::new(suitability_data);
-        table
-            .with(Style::rounded())
+        DisplayUtils::style_table(&mut table)
             .with(Modify::new(Columns::single(0)).with(Alignment::left()))
             .with(Modify::new(Columns::single(1)).with(Alignment::right()))
             .with(Modify::new(Columns::single(2)).with(Alignment::center()));
 
+        DisplayUtils::fit_table(&mut table, &[]);
         println!("{}", table);
     }
 
-    fn get_suitability_grade(score: f64) -> char {
+    fn get_suitability_grade(score: f64) -> &'static str {
         match score {
-            s if s >= 80.0 => '★',
-            s if s >= 60.0 => '◆',
-            s if s >= 40.0 => '▲',
-            _ => '○',
+            s if s >= 80.0 => DisplayUtils::symbol("★", "*"),
+            s if s >= 60.0 => DisplayUtils::symbol("◆", "+"),
+            s if s >= 40.0 => DisplayUtils::symbol("▲", "^"),
+            _ => DisplayUtils::symbol("○", "o"),
         }
     }
 
-    /// Generate ranked performance report with recommendations
-    pub fn generate_ranking_report(results: &[(String, PingStats)], weights: &AlgorithmWeights) {
+    /// Generate a performance report with recommendations from an ordered ranking
+    ///
+    /// Regions with earlier runs in `histories` get a sparkline of their recent latencies.
+    pub fn generate_ranking_report(ranked: &[RankedResult], histories: &[TestHistory]) {
         println!("\n{}", DisplayUtils::create_separator(100));
         println!("COMPREHENSIVE RANKING REPORT");
         println!("{}", DisplayUtils::create_separator(100));
 
-        let ranked = ScoringAdapter::get_sorted_results(results, weights);
-        Self::display_top_performers(&ranked);
-        Self::display_recommendations(&ranked);
+        Self::display_top_performers(ranked, histories);
+        Self::display_recommendations(ranked);
     }
 
 
 
-    fn display_top_performers(ranked: &[(f64, String, PingStats, crate::models::ComprehensiveScoreResult)]) {
+    fn display_top_performers(ranked: &[RankedResult], histories: &[TestHistory]) {
         println!("\nTOP PERFORMERS:");
 
         let display_count = ranked.len().min(10);
@@ -193,30 +525,73 @@ impl DisplayFormatter {
                     region: DisplayUtils::format_region_name(name, 40),
                     score: format!("{:.1}", comp_score.score),
                     grade: comp_score.grade,
+                    health: DisplayUtils::format_health(stats.health_status()),
                     latency: DisplayUtils::format_latency(stats.avg),
+                    trend: TestHistory::find(histories, name, stats).map_or_else(String::new, |history| {
+                        DisplayUtils::sparkline(&history.recent_latencies(TestHistory::SPARKLINE_RUNS))
+                    }),
                     loss: DisplayUtils::format_percentage(stats.packet_loss),
                     gaming: format!("{:.1}", comp_score.suitability.gaming),
                     streaming: format!("{:.1}", comp_score.suitability.streaming),
+                    value: stats
+                        .cost
+                        .price_performance(comp_score.score)
+                        .map_or_else(String::new, |value| format!("{value:.1}")),
+                    carbon: stats
+                        .carbon_g_per_kwh
+                        .map_or_else(String::new, |carbon| format!("{carbon:.0}")),
+                    edge: stats.edge_pop().map_or_else(String::new, ToString::to_string),
+                    asn: stats.asn.as_ref().map_or_else(String::new, |info| format!("AS{}", info.asn)),
+                    notes: stats.notes.join("; "),
                 }
             })
             .collect();
 
+        let has_notes = ranking_data.iter().any(|row| !row.notes.is_empty());
+        let has_value = ranking_data.iter().any(|row| !row.value.is_empty());
+        let has_carbon = ranking_data.iter().any(|row| !row.carbon.is_empty());
+        let has_edge = ranking_data.iter().any(|row| !row.edge.is_empty());
+        let has_asn = ranking_data.iter().any(|row| !row.asn.is_empty());
+        let has_trend = ranking_data.iter().any(|row| !row.trend.is_empty());
         let mut table = Table::new(ranking_data);
-        table
-            .with(Style::rounded())
+        DisplayUtils::style_table(&mut table)
             .with(Modify::new(Columns::single(0)).with(Alignment::center()))
             .with(Modify::new(Columns::single(1)).with(Alignment::left()))
             .with(Modify::new(Columns::single(2)).with(Alignment::right()))
             .with(Modify::new(Columns::single(3)).with(Alignment::center()))
-            .with(Modify::new(Columns::single(4)).with(Alignment::right()))
+            .with(Modify::new(Columns::single(4)).with(Alignment::left()))
             .with(Modify::new(Columns::single(5)).with(Alignment::right()))
-            .with(Modify::new(Columns::single(6)).with(Alignment::right()))
-            .with(Modify::new(Columns::single(7)).with(Alignment::right()));
+            .with(Modify::new(Columns::single(6)).with(Alignment::left()))
+            .with(Modify::new(Columns::single(7)).with(Alignment::right()))
+            .with(Modify::new(Columns::single(8)).with(Alignment::right()))
+            .with(Modify::new(Columns::single(9)).with(Alignment::right()))
+            .with(Modify::new(Columns::single(10)).with(Alignment::right()))
+            .with(Modify::new(Columns::single(11)).with(Alignment::right()))
+            .with(Modify::new(Columns::single(12)).with(Alignment::left()));
+        if !has_trend {
+            table.with(Disable::column(ByColumnName::new("Trend")));
+        }
+        if !has_notes {
+            table.with(Disable::column(ByColumnName::new("Notes")));
+        }
+        if !has_value {
+            table.with(Disable::column(ByColumnName::new("Value")));
+        }
+        if !has_carbon {
+            table.with(Disable::column(ByColumnName::new("gCO2/kWh")));
+        }
+        if !has_edge {
+            table.with(Disable::column(ByColumnName::new("Edge")));
+        }
+        if !has_asn {
+            table.with(Disable::column(ByColumnName::new("AS")));
+        }
 
+        DisplayUtils::fit_table(&mut table, &["Streaming", "Gaming", "Notes", "Trend", "gCO2/kWh", "AS", "Edge", "Value", "Health", "Grade", "Loss %"]);
         println!("{}", table);
     }
 
-    fn display_recommendations(ranked: &[(f64, String, PingStats, crate::models::ComprehensiveScoreResult)]) {
+    fn display_recommendations(ranked: &[RankedResult]) {
         if ranked.is_empty() {
             return;
         }
@@ -246,18 +621,1224 @@ impl DisplayFormatter {
             })
             .unwrap();
 
+        let overall_best = ranked
+            .iter()
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .unwrap();
+
         println!(
-            "⚡ Best Latency:       {} (Score: {})",
+            "{} Best Latency:       {} (Score: {})",
+            DisplayUtils::symbol("⚡", "*"),
             best_latency.1, DisplayUtils::format_score(best_latency.3.components.latency_score)
         );
         println!(
-            "🔒 Best Reliability:   {} (Score: {})",
+            "{} Best Reliability:   {} (Score: {})",
+            DisplayUtils::symbol("🔒", "*"),
             best_reliability.1, DisplayUtils::format_score(best_reliability.3.components.availability_score)
         );
         println!(
-            "🌟 Overall Best:       {} (Overall: {})",
-            ranked[0].1, ranked[0].3.score
+            "{} Overall Best:       {} (Overall: {})",
+            DisplayUtils::symbol("🌟", "*"),
+            overall_best.1, overall_best.3.score
+        );
+    }
+
+    /// Display regions ranked by mean score across all reporting agents
+    pub fn display_vantage_ranking(ranking: &[MultiVantageResult]) {
+        println!("\n{}", DisplayUtils::create_separator(100));
+        println!("MULTI-VANTAGE RANKING");
+        println!("{}", DisplayUtils::create_separator(100));
+
+        let rows: Vec<VantageRankingRow> = ranking
+            .iter()
+            .enumerate()
+            .map(|(i, result)| {
+                let best_vantage = result
+                    .observations
+                    .iter()
+                    .max_by(|a, b| {
+                        a.score
+                            .score
+                            .partial_cmp(&b.score.score)
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    })
+                    .map_or_else(|| "-".to_string(), |o| o.agent.label());
+
+                VantageRankingRow {
+                    rank: i + 1,
+                    region: DisplayUtils::format_region_name(&result.region, 40),
+                    agents: result.agent_count(),
+                    mean_score: DisplayUtils::format_score(result.mean_score),
+                    mean_latency: DisplayUtils::format_latency(result.mean_latency_ms),
+                    best_vantage,
+                }
+            })
+            .collect();
+
+        let mut table = Table::new(rows);
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::single(0)).with(Alignment::center()))
+            .with(Modify::new(Columns::single(1)).with(Alignment::left()))
+            .with(Modify::new(Columns::single(2)).with(Alignment::right()))
+            .with(Modify::new(Columns::single(3)).with(Alignment::right()))
+            .with(Modify::new(Columns::single(4)).with(Alignment::right()))
+            .with(Modify::new(Columns::single(5)).with(Alignment::left()));
+
+        DisplayUtils::fit_table(&mut table, &["Best Vantage", "Agents"]);
+        println!("{table}");
+    }
+
+    /// Display the agents × regions matrix with latency and score per pair
+    pub fn display_vantage_matrix(matrix: &VantageMatrix) {
+        println!("\n=== VANTAGE MATRIX (latency / score) ===");
+
+        let mut builder = Builder::default();
+        builder.push_record(
+            std::iter::once("Region".to_string()).chain(matrix.agents.iter().map(AgentInfo::label)),
+        );
+
+        for (region, row) in matrix.regions.iter().zip(&matrix.cells) {
+            let cells = row.iter().map(|cell| match cell {
+                Some(cell) if cell.reachable => format!(
+                    "{} / {}",
+                    DisplayUtils::format_latency(cell.latency_ms),
+                    DisplayUtils::format_score(cell.score)
+                ),
+                Some(_) => "unreachable".to_string(),
+                None => "-".to_string(),
+            });
+            builder.push_record(
+                std::iter::once(DisplayUtils::format_region_name(region, 40)).chain(cells),
+            );
+        }
+
+        let mut table = builder.build();
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::new(1..)).with(Alignment::right()));
+
+        DisplayUtils::fit_table(&mut table, &[]);
+        println!("{table}");
+    }
+
+    /// Display the region recommended for the majority of vantage points
+    pub fn display_majority_recommendation(recommendation: Option<&MajorityRecommendation>) {
+        println!("\n=== MULTI-VANTAGE RECOMMENDATION ===");
+
+        let Some(recommendation) = recommendation else {
+            println!("No agent reached any region.");
+            return;
+        };
+
+        println!(
+            "Best region for {} of {} vantage points: {} (mean score {})",
+            recommendation.supporting_agents.len(),
+            recommendation.total_agents,
+            recommendation.region,
+            DisplayUtils::format_score(recommendation.mean_score)
+        );
+        println!("Chosen by: {}", recommendation.supporting_agents.join(", "));
+
+        if !recommendation.is_absolute_majority() {
+            println!("Note: no region is best for more than half of the agents.");
+        }
+    }
+
+    /// Display the local environment a run was captured in
+    pub fn display_test_environment(environment: &TestEnvironment) {
+        println!("\n=== TEST ENVIRONMENT ===");
+        println!("OS: {}", environment.os);
+        println!(
+            "Interface: {} ({})",
+            environment.interface_name.as_deref().unwrap_or("unknown"),
+            environment.interface_type
+        );
+        if let Some(gateway) = environment.default_gateway {
+            let rtt = environment
+                .gateway_rtt_ms
+                .map_or_else(|| "no response".to_string(), DisplayUtils::format_latency);
+            println!("Gateway: {gateway} ({rtt})");
+        }
+        if let Some(hop) = &environment.isp_first_hop {
+            let rtt = environment
+                .isp_first_hop_rtt_ms
+                .map_or_else(|| "no response".to_string(), DisplayUtils::format_latency);
+            println!("ISP first hop: {hop} ({rtt})");
+        }
+        if let Some(public_ip) = &environment.public_ip {
+            println!("Public IP: {public_ip}");
+        }
+        if let Some(asn) = &environment.asn {
+            println!("ASN: {asn}");
+        }
+        if let Some(nat_type) = environment.nat_type {
+            match environment.public_mapping {
+                Some(mapping) => println!("NAT: {nat_type} (public UDP mapping {mapping})"),
+                None => println!("NAT: {nat_type}"),
+            }
+            if nat_type.needs_relay() {
+                println!("Note: peer-to-peer game and call traffic will likely need a relay, adding latency.");
+            }
+        }
+    }
+
+    /// Display each region's latency beyond the ISP first hop
+    ///
+    /// Subtracting the local (gateway/first-hop) RTT shows whether slowness comes
+    /// from the local network or from the path to the region.
+    pub fn display_beyond_isp_latency(results: &[(String, PingStats)], environment: &TestEnvironment) {
+        let Some(local_rtt) = environment.local_rtt_ms() else {
+            return;
+        };
+
+        println!("\n=== LATENCY BEYOND ISP (local baseline {}) ===", DisplayUtils::format_latency(local_rtt));
+
+        let mut reachable: Vec<&(String, PingStats)> =
+            results.iter().filter(|(_, stats)| stats.is_successful()).collect();
+        if reachable.is_empty() {
+            println!("No region was reachable.");
+            return;
+        }
+        reachable.sort_by(|a, b| a.1.avg.partial_cmp(&b.1.avg).unwrap_or(std::cmp::Ordering::Equal));
+
+        let rows: Vec<BeyondIspRow> = reachable
+            .iter()
+            .map(|(name, stats)| BeyondIspRow {
+                region: DisplayUtils::format_region_name(name, 40),
+                rtt: DisplayUtils::format_latency(stats.avg),
+                beyond_isp: environment
+                    .beyond_isp_ms(stats.avg)
+                    .map_or_else(|| "-".to_string(), DisplayUtils::format_latency),
+                local_share: environment
+                    .local_share_percent(stats.avg)
+                    .map_or_else(|| "-".to_string(), DisplayUtils::format_percentage),
+            })
+            .collect();
+
+        let mut table = Table::new(rows);
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::new(1..)).with(Alignment::right()));
+        DisplayUtils::fit_table(&mut table, &["Local Share"]);
+        println!("{table}");
+
+        let locally_dominated = reachable
+            .iter()
+            .filter(|(_, stats)| environment.local_share_percent(stats.avg).is_some_and(|share| share >= 50.0))
+            .count();
+        if locally_dominated * 2 > reachable.len() {
+            println!("Most of the latency is spent on the local network or ISP access link.");
+        } else {
+            println!("Latency is dominated by the path beyond your ISP.");
+        }
+    }
+
+    /// Display the best and worst hour of the day per region from saved history
+    pub fn display_time_of_day(histories: &[TestHistory], offset: chrono::FixedOffset) {
+        let mut rows: Vec<(f64, TimeOfDayRow)> = histories
+            .iter()
+            .filter_map(|history| {
+                let best = history.best_hour(offset)?;
+                let worst = history.worst_hour(offset)?;
+                let penalty = worst.avg_latency_ms - best.avg_latency_ms;
+                Some((
+                    penalty,
+                    TimeOfDayRow {
+                        region: DisplayUtils::format_region_name(&history.region_name, 40),
+                        hours: history.hourly_profile(offset).len(),
+                        best: format!("{:02}:00 ({})", best.hour, DisplayUtils::format_latency(best.avg_latency_ms)),
+                        worst: format!("{:02}:00 ({})", worst.hour, DisplayUtils::format_latency(worst.avg_latency_ms)),
+                        penalty: format!("+{}", DisplayUtils::format_latency(penalty)),
+                    },
+                ))
+            })
+            .collect();
+
+        if rows.is_empty() {
+            return;
+        }
+        rows.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        println!("\n=== LATENCY BY TIME OF DAY (UTC{offset}) ===");
+        let mut table = Table::new(rows.into_iter().map(|(_, row)| row));
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::new(1..)).with(Alignment::right()));
+        DisplayUtils::fit_table(&mut table, &["Peak Penalty", "Hours"]);
+        println!("{table}");
+    }
+
+    /// Display the outcome of each doctor check and a summary line
+    pub fn display_doctor_report(report: &DoctorReport) {
+        println!("\n=== DIAGNOSTICS ===");
+        let rows = report.checks.iter().map(|check| DoctorRow {
+            check: check.name.clone(),
+            status: check.status.to_string(),
+            detail: check.detail.clone(),
+        });
+        let mut table = Table::new(rows);
+        DisplayUtils::style_table(&mut table);
+        DisplayUtils::fit_table(&mut table, &[]);
+        println!("{table}");
+        println!(
+            "{} passed, {} warnings, {} failed",
+            report.count(CheckStatus::Pass),
+            report.count(CheckStatus::Warn),
+            report.count(CheckStatus::Fail)
+        );
+    }
+
+    /// Display bytes sent and received per region and for the whole run
+    pub fn display_data_usage(results: &[(String, PingStats)]) {
+        if results.is_empty() {
+            return;
+        }
+
+        println!("\n=== DATA USAGE ===");
+        let mut sorted: Vec<&(String, PingStats)> = results.iter().collect();
+        sorted.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.data_usage_bytes()));
+
+        let rows: Vec<DataUsageRow> = sorted
+            .iter()
+            .map(|(name, stats)| DataUsageRow {
+                region: DisplayUtils::format_region_name(name, 40),
+                sent: FormatUtils::format_bytes(stats.bytes_sent),
+                received: FormatUtils::format_bytes(stats.bytes_received),
+                total: FormatUtils::format_bytes(stats.data_usage_bytes()),
+            })
+            .collect();
+
+        let mut table = Table::new(rows);
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::new(1..)).with(Alignment::right()));
+        DisplayUtils::fit_table(&mut table, &["Sent", "Received"]);
+        println!("{table}");
+
+        Self::display_data_usage_total(results);
+    }
+
+    /// Display the regions that failed, were skipped or could not be tested, with their causes
+    pub fn display_failed_regions(failures: &[RegionFailure]) {
+        if failures.is_empty() {
+            return;
+        }
+
+        println!("\n=== FAILED REGIONS ===");
+        let rows: Vec<FailedRegionRow> = failures
+            .iter()
+            .map(|failure| FailedRegionRow {
+                region: DisplayUtils::format_region_name(&failure.region, 40),
+                cause: failure.kind.to_string(),
+                error: failure.message.clone(),
+            })
+            .collect();
+
+        let mut table = Table::new(rows);
+        DisplayUtils::style_table(&mut table);
+        DisplayUtils::fit_table(&mut table, &[]);
+        println!("{table}");
+    }
+
+    /// Display each region's latency raw and normalized against the control endpoint
+    pub fn display_normalized_latency(results: &[(String, PingStats)], control: &ControlSeries) {
+        println!("\n=== CONTROL-NORMALIZED LATENCY ===");
+        let Some(baseline) = control.baseline_ms() else {
+            println!(
+                "Control endpoint {} never answered, so latencies are not normalized",
+                control.url
+            );
+            return;
+        };
+        println!(
+            "Control endpoint {}: {} median over {} pings",
+            control.url,
+            FormatUtils::format_latency_ms(baseline),
+            control.samples.len()
+        );
+
+        let mut sorted: Vec<&(String, PingStats)> =
+            results.iter().filter(|(_, stats)| stats.normalized_avg.is_some()).collect();
+        sorted.sort_by(|(_, a), (_, b)| {
+            a.normalized_avg
+                .partial_cmp(&b.normalized_avg)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let rows: Vec<NormalizedLatencyRow> = sorted
+            .iter()
+            .map(|(name, stats)| NormalizedLatencyRow {
+                region: DisplayUtils::format_region_name(name, 40),
+                raw: FormatUtils::format_latency_ms(stats.avg),
+                control: stats.control_latency_ms.map_or_else(|| "-".to_string(), FormatUtils::format_latency_ms),
+                normalized: stats.normalized_avg.map_or_else(|| "-".to_string(), FormatUtils::format_latency_ms),
+            })
+            .collect();
+
+        let mut table = Table::new(rows);
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::new(1..)).with(Alignment::right()));
+        DisplayUtils::fit_table(&mut table, &["Control"]);
+        println!("{table}");
+    }
+
+    /// Note the concurrency reductions made because the local uplink looked saturated
+    pub fn display_concurrency_adjustments(adjustments: &[ConcurrencyAdjustment]) {
+        if adjustments.is_empty() {
+            return;
+        }
+
+        println!("\n=== CONCURRENCY ADJUSTMENTS ===");
+        println!("The reference endpoint slowed down during the run, so fewer regions were tested at once");
+        for adjustment in adjustments {
+            println!(
+                "  {}: {}",
+                TimeUtils::format_timestamp(&adjustment.at),
+                adjustment.summary()
+            );
+        }
+    }
+
+    /// Warn that a run stopped early and list the regions with partial results
+    pub fn display_incomplete_run(results: &[(String, PingStats)]) {
+        println!("\n=== INCOMPLETE RUN ===");
+        println!("The run stopped early, so these results are partial");
+        for (name, stats) in results.iter().filter(|(_, stats)| stats.incomplete) {
+            println!("  {name}: only {} pings completed", stats.total_pings);
+        }
+    }
+
+    /// Display the run's total data usage on one line
+    pub fn display_data_usage_total(results: &[(String, PingStats)]) {
+        let sent: u64 = results.iter().map(|(_, stats)| stats.bytes_sent).sum();
+        let received: u64 = results.iter().map(|(_, stats)| stats.bytes_received).sum();
+        println!(
+            "Data used: {} (sent {}, received {})",
+            FormatUtils::format_bytes(sent + received),
+            FormatUtils::format_bytes(sent),
+            FormatUtils::format_bytes(received)
+        );
+    }
+
+    /// Display the estimates of a dry run
+    pub fn display_plan(plan: &BenchmarkPlan) {
+        println!("\n=== DRY RUN ===");
+        println!(
+            "Regions:        {} ({} hosts), {} pings each",
+            plan.region_count, plan.host_count, plan.ping_count
+        );
+        println!(
+            "Requests:       {} ({} if every ping is retried)",
+            FormatUtils::format_count(usize::try_from(plan.total_requests).unwrap_or(usize::MAX)),
+            FormatUtils::format_count(usize::try_from(plan.max_requests).unwrap_or(usize::MAX))
+        );
+        println!(
+            "Duration:       about {} (up to {} if every request times out)",
+            humantime::format_duration(Duration::from_secs(plan.expected_duration.as_secs().max(1))),
+            humantime::format_duration(Duration::from_secs(plan.worst_case_duration.as_secs()))
+        );
+        println!("Data usage:     about {}", FormatUtils::format_bytes(plan.estimated_bytes));
+    }
+
+    /// Display the final scores and alerts of a simulation
+    pub fn display_simulation(report: &SimulationReport) {
+        println!("\n=== SIMULATION ===");
+        println!(
+            "Processed {} probe records",
+            FormatUtils::format_count(report.records_processed)
+        );
+
+        if !report.scores.is_empty() {
+            let rows: Vec<SimulationScoreRow> = report
+                .scores
+                .iter()
+                .map(|(endpoint, score)| SimulationScoreRow {
+                    endpoint: DisplayUtils::format_region_name(endpoint, 40),
+                    score: format!("{:.1}", score.score),
+                    grade: score.grade,
+                    gaming: format!("{:.1}", score.suitability.gaming),
+                    streaming: format!("{:.1}", score.suitability.streaming),
+                })
+                .collect();
+
+            let mut table = Table::new(rows);
+            DisplayUtils::style_table(&mut table)
+                .with(Modify::new(Columns::new(1..)).with(Alignment::right()));
+            DisplayUtils::fit_table(&mut table, &["Streaming", "Gaming"]);
+            println!("{table}");
+        }
+
+        if report.alerts.is_empty() {
+            println!("No alerts raised");
+            return;
+        }
+        println!("\nAlerts raised: {}", report.alerts.len());
+        for alert in &report.alerts {
+            println!(
+                "  {} {} {}: {}",
+                TimeUtils::format_timestamp(&alert.timestamp),
+                DisplayUtils::severity_marker(alert.severity()),
+                alert.endpoint_id,
+                alert.description()
+            );
+        }
+    }
+
+    /// Display how local latency compares with community results
+    pub fn display_community_comparison(comparisons: &[CommunityComparison]) {
+        if comparisons.is_empty() {
+            return;
+        }
+
+        println!("\n=== COMMUNITY COMPARISON ===");
+        let rows: Vec<CommunityRow> = comparisons
+            .iter()
+            .map(|comparison| CommunityRow {
+                region: DisplayUtils::format_region_name(
+                    &format!("{} {}", comparison.provider, comparison.region),
+                    40,
+                ),
+                latency: DisplayUtils::format_latency(comparison.latency_ms),
+                better_than: DisplayUtils::format_percentage(comparison.better_than_percent),
+                compared_with: comparison.country.as_ref().map_or_else(
+                    || format!("{} users", comparison.samples),
+                    |country| format!("{} users in {country}", comparison.samples),
+                ),
+            })
+            .collect();
+
+        let mut table = Table::new(rows);
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::new(1..)).with(Alignment::right()));
+        DisplayUtils::fit_table(&mut table, &["Compared With"]);
+        println!("{table}");
+
+        if let Some(best) = comparisons
+            .iter()
+            .max_by(|a, b| a.better_than_percent.total_cmp(&b.better_than_percent))
+        {
+            println!("{}", best.describe());
+        }
+    }
+
+    /// Display step changes in latency found in saved history
+    pub fn display_change_points(histories: &[TestHistory]) {
+        let mut changes: Vec<(String, crate::models::ChangePoint)> = histories
+            .iter()
+            .flat_map(|history| {
+                history
+                    .detect_change_points()
+                    .into_iter()
+                    .map(|change| (history.region_name.clone(), change))
+            })
+            .collect();
+
+        if changes.is_empty() {
+            return;
+        }
+        changes.sort_by_key(|(_, change)| change.at);
+
+        println!("\n=== LATENCY CHANGES ===");
+        for (region, change) in changes {
+            println!("  {}", change.describe(&region));
+        }
+    }
+
+    /// Display regions whose latest run took another path than the run before
+    pub fn display_route_changes(histories: &[TestHistory]) {
+        let changes: Vec<(&str, crate::models::RouteChange)> = histories
+            .iter()
+            .filter_map(|history| {
+                let latest = history.historical_data.iter().map(|stats| stats.test_time).max()?;
+                let change = history
+                    .detect_route_changes()
+                    .into_iter()
+                    .find(|change| change.at == latest)?;
+                Some((history.region_name.as_str(), change))
+            })
+            .collect();
+
+        if changes.is_empty() {
+            return;
+        }
+
+        println!("\n=== ROUTE CHANGES ===");
+        for (region, change) in changes {
+            println!("  {}", change.describe(region));
+        }
+    }
+
+    /// Display regions ranked by how often a round trip fits the server tick budget
+    pub fn display_tick_budget(results: &[(String, PingStats)], budget: &TickBudget) {
+        println!(
+            "\n=== GAMING TICK BUDGET ({:.0} Hz, {:.1} ms per tick) ===",
+            budget.tick_rate_hz(),
+            budget.budget_ms()
+        );
+
+        let mut assessed: Vec<_> = results
+            .iter()
+            .map(|(name, stats)| (name, stats, budget.assess(stats)))
+            .collect();
+        assessed.sort_by(|a, b| b.2.score.partial_cmp(&a.2.score).unwrap_or(std::cmp::Ordering::Equal));
+
+        let rows: Vec<TickBudgetRow> = assessed
+            .iter()
+            .enumerate()
+            .map(|(i, (name, stats, result))| TickBudgetRow {
+                rank: i + 1,
+                region: DisplayUtils::format_region_name(name, 40),
+                latency: DisplayUtils::format_latency(stats.avg),
+                one_tick: DisplayUtils::format_percentage(result.within_one_tick * 100.0),
+                two_ticks: DisplayUtils::format_percentage(result.within_two_ticks * 100.0),
+                score: DisplayUtils::format_score(result.score),
+            })
+            .collect();
+
+        let mut table = Table::new(rows);
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::new(2..)).with(Alignment::right()));
+        DisplayUtils::fit_table(&mut table, &["Avg RTT"]);
+        println!("{table}");
+
+        if let Some((name, _, result)) = assessed.first().filter(|(_, _, r)| r.within_two_ticks > 0.0) {
+            println!(
+                "Best for gaming: {} ({} of round trips within two ticks)",
+                name,
+                DisplayUtils::format_percentage(result.within_two_ticks * 100.0)
+            );
+        } else {
+            println!("No region fits within two ticks at this tick rate.");
+        }
+    }
+
+    /// Display the lowest-carbon region that meets the green threshold
+    pub fn display_green_recommendation(green: &GreenRecommendation) {
+        println!(
+            "{} Greenest Acceptable: {} ({:.0} gCO2/kWh, Score: {}, {})",
+            DisplayUtils::symbol("🌱", "*"),
+            green.region,
+            green.carbon_g_per_kwh,
+            DisplayUtils::format_score(green.score),
+            DisplayUtils::format_latency(green.latency_ms)
         );
+        if let Some(top) = green.top_carbon_g_per_kwh.filter(|top| *top > green.carbon_g_per_kwh) {
+            println!(
+                "  {:.0} gCO2/kWh less than the best scoring region, {}",
+                top - green.carbon_g_per_kwh,
+                green.top_region
+            );
+        }
+    }
+
+    /// Display the regions of a deployment footprint and the spread they give
+    pub fn display_footprint(footprint: &Footprint) {
+        println!("\n=== DEPLOYMENT FOOTPRINT ===");
+        let rows: Vec<FootprintRow> = footprint
+            .regions
+            .iter()
+            .map(|region| FootprintRow {
+                rank: region.rank,
+                region: DisplayUtils::format_region_name(&region.region, 40),
+                provider: region.provider.clone(),
+                continent: continent_of(&region.country).unwrap_or("-").to_string(),
+                score: format!("{:.1}", region.score),
+                grade: region.grade,
+                latency: region.latency_ms.map_or_else(|| "-".to_string(), DisplayUtils::format_latency),
+            })
+            .collect();
+
+        let mut table = Table::new(rows);
+        DisplayUtils::style_table(&mut table);
+        DisplayUtils::fit_table(&mut table, &["Latency", "Grade", "Continent"]);
+        println!("{table}");
+        println!(
+            "Providers: {} | Continents: {} | Average score {:.1}",
+            footprint.providers.join(", "),
+            footprint.continents.join(", "),
+            footprint.average_score
+        );
+    }
+
+    /// Display the CDN edge POPs that served the tested regions, with the
+    /// regions each one served
+    ///
+    /// A region whose pings were answered by several POPs is listed under each.
+    pub fn display_edge_pops(ranked: &[RankedResult]) {
+        let mut groups: BTreeMap<&EdgePop, (usize, f64, Vec<&str>)> = BTreeMap::new();
+        for (_, name, stats, _) in ranked {
+            for count in &stats.edge_pops {
+                let group = groups.entry(&count.edge).or_default();
+                group.0 += count.pings;
+                #[allow(clippy::cast_precision_loss)] // ping counts are far below 2^52
+                let total_ms = count.avg_ms * count.pings as f64;
+                group.1 += total_ms;
+                group.2.push(name);
+            }
+        }
+        if groups.is_empty() {
+            return;
+        }
+
+        println!("\n=== CDN EDGE POPS ===");
+        let rows: Vec<EdgePopRow> = groups
+            .into_iter()
+            .map(|(edge, (pings, total_ms, regions))| {
+                #[allow(clippy::cast_precision_loss)] // ping counts are far below 2^52
+                let avg_ms = total_ms / pings as f64;
+                EdgePopRow {
+                    cdn: edge.cdn.to_string(),
+                    pop: edge.pop.clone(),
+                    pings,
+                    latency: DisplayUtils::format_latency(avg_ms),
+                    regions: regions.join(", "),
+                }
+            })
+            .collect();
+
+        let mut table = Table::new(rows);
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::new(2..4)).with(Alignment::right()));
+        DisplayUtils::fit_table(&mut table, &["Pings", "Regions"]);
+        println!("{table}");
+    }
+
+    /// Display the ranked regions grouped by the autonomous system behind them,
+    /// noting when every slow region shares one
+    pub fn display_asn_breakdown(ranked: &[RankedResult]) {
+        let breakdown = AsnBreakdown::from_ranking(ranked);
+        if breakdown.groups.is_empty() {
+            return;
+        }
+
+        println!("\n=== NETWORKS (ASN) ===");
+        let rows: Vec<AsnRow> = breakdown
+            .groups
+            .iter()
+            .map(|group| AsnRow {
+                asn: format!("AS{}", group.asn),
+                organization: group.organization.clone(),
+                latency: group
+                    .avg_latency_ms
+                    .map_or_else(|| "-".to_string(), DisplayUtils::format_latency),
+                slow: format!("{}/{}", group.slow.len(), group.regions.len()),
+                regions: group.regions.join(", "),
+            })
+            .collect();
+
+        let mut table = Table::new(rows);
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::new(2..4)).with(Alignment::right()));
+        DisplayUtils::fit_table(&mut table, &["Slow", "Organization", "Regions"]);
+        println!("{table}");
+        if !breakdown.unresolved.is_empty() {
+            println!("Unresolved: {}", breakdown.unresolved.join(", "));
+        }
+        if let Some(group) = breakdown.slow_behind_one() {
+            println!(
+                "{} All {} slow regions are behind AS{} {}",
+                DisplayUtils::symbol("⚠️", "!"),
+                group.slow.len(),
+                group.asn,
+                group.organization
+            );
+        }
+    }
+
+    /// Display which address family won the connection races against each
+    /// endpoint, followed by the timings of every raced address
+    pub fn display_race_results(results: &[RaceResult]) {
+        println!("\n=== CONNECTION RACES ===");
+        if results.is_empty() {
+            println!("No endpoints raced");
+            return;
+        }
+
+        let latency = |ms: Option<f64>| ms.map_or_else(|| "-".to_string(), DisplayUtils::format_latency);
+        let rows: Vec<RaceRow> = results
+            .iter()
+            .map(|result| RaceRow {
+                region: DisplayUtils::format_region_name(&result.region, 24),
+                winner: result
+                    .winning_family()
+                    .map_or_else(|| "-".to_string(), |family| family.to_string()),
+                margin: latency(result.median_margin_ms()),
+                ipv6: latency(result.best_connect_ms(IpFamily::Ipv6)),
+                ipv4: latency(result.best_connect_ms(IpFamily::Ipv4)),
+                addresses: result.addresses().len(),
+            })
+            .collect();
+        let mut table = Table::new(rows);
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::new(2..6)).with(Alignment::right()));
+        DisplayUtils::fit_table(&mut table, &["Addresses", "Margin"]);
+        println!("{table}");
+
+        println!("\n=== ADDRESS TIMINGS ===");
+        let rows: Vec<RaceAddressRow> = results
+            .iter()
+            .flat_map(|result| {
+                result.addresses().into_iter().map(|timing| RaceAddressRow {
+                    region: DisplayUtils::format_region_name(&result.region, 24),
+                    address: timing.address.to_string(),
+                    family: timing.family.to_string(),
+                    wins: format!("{}/{}", timing.wins, timing.attempts),
+                    connect: latency(timing.median_connect_ms),
+                    failures: timing.failures,
+                })
+            })
+            .collect();
+        let mut table = Table::new(rows);
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::new(3..6)).with(Alignment::right()));
+        DisplayUtils::fit_table(&mut table, &["Failures", "Family"]);
+        println!("{table}");
+        println!("Margin: how much sooner the winning connection completed than the next");
+    }
+
+    /// Display each region's soak test with its per-bucket p95 trend and availability timeline
+    pub fn display_soak_results(results: &[SoakResult]) {
+        println!("\n=== SOAK TEST ===");
+        let Some(first) = results.first() else {
+            println!("No regions soak tested");
+            return;
+        };
+
+        let latency = |ms: Option<f64>| ms.map_or_else(|| "-".to_string(), DisplayUtils::format_latency);
+        let (up, partial, down) = (
+            DisplayUtils::symbol("█", "#"),
+            DisplayUtils::symbol("▄", "+"),
+            DisplayUtils::symbol("▁", "_"),
+        );
+        let rows: Vec<SoakRow> = results
+            .iter()
+            .map(|result| {
+                let buckets = result.buckets();
+                let p95s: Vec<f64> = buckets.iter().filter_map(|bucket| bucket.p95_ms).collect();
+                let [p50, p95] = result.p50_p95_ms();
+                SoakRow {
+                    region: DisplayUtils::format_region_name(&result.region, 24),
+                    pings: result.samples.len(),
+                    availability: result
+                        .availability()
+                        .map_or_else(|| "-".to_string(), DisplayUtils::format_percentage),
+                    p50: latency(p50),
+                    p95: latency(p95),
+                    worst_p95: latency(result.worst_p95_ms()),
+                    trend: DisplayUtils::sparkline(&p95s),
+                    timeline: buckets
+                        .iter()
+                        .map(|bucket| match bucket.availability() {
+                            None => " ",
+                            Some(availability) if availability >= 100.0 => up,
+                            Some(availability) if availability > 0.0 => partial,
+                            Some(_) => down,
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        let mut table = Table::new(rows);
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::new(1..6)).with(Alignment::right()));
+        DisplayUtils::fit_table(&mut table, &["P95 Trend", "P50", "Pings"]);
+        println!("{table}");
+        println!(
+            "One timeline mark per {}: {up} all pings answered, {partial} some, {down} none",
+            humantime::format_duration(first.bucket_width)
+        );
+        if results.iter().any(|result| result.incomplete) {
+            println!("Stopped before the test duration was up; results are partial");
+        }
+    }
+
+    /// Display each region's run of a transaction with the time of every step,
+    /// followed by why the failed runs stopped
+    pub fn display_transaction_results(results: &[TransactionResult]) {
+        let Some(first) = results.first() else {
+            println!("\n=== TRANSACTION CHECKS ===");
+            println!("No regions checked");
+            return;
+        };
+        println!("\n=== TRANSACTION CHECK: {} ===", first.transaction);
+
+        let mut builder = Builder::default();
+        let mut header = vec!["Region".to_string(), "Result".to_string(), "Total".to_string()];
+        let step_names: Vec<&str> = results
+            .iter()
+            .max_by_key(|result| result.steps.len())
+            .map(|result| result.steps.iter().map(|step| step.name.as_str()).collect())
+            .unwrap_or_default();
+        header.extend(step_names.iter().map(ToString::to_string));
+        builder.push_record(header);
+        for result in results {
+            let mut record = vec![
+                DisplayUtils::format_region_name(&result.region, 24),
+                match result.failed_step() {
+                    Some(step) => format!("failed at {}", step.name),
+                    None if result.is_successful() => "passed".to_string(),
+                    None => "incomplete".to_string(),
+                },
+                DisplayUtils::format_latency(result.total_ms),
+            ];
+            record.extend((0..step_names.len()).map(|index| {
+                result.steps.get(index).map_or_else(
+                    || "-".to_string(),
+                    |step| {
+                        let failed = if step.error.is_some() { DisplayUtils::symbol(" ✗", " !") } else { "" };
+                        format!("{}{failed}", DisplayUtils::format_latency(step.duration_ms))
+                    },
+                )
+            }));
+            builder.push_record(record);
+        }
+
+        let mut table = builder.build();
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::new(2..)).with(Alignment::right()));
+        DisplayUtils::fit_table(&mut table, &[]);
+        println!("{table}");
+        for result in results {
+            if let Some(step) = result.failed_step() {
+                println!(
+                    "{}: step {} failed: {}",
+                    result.region,
+                    step.name,
+                    step.error.as_deref().unwrap_or_default()
+                );
+            }
+        }
+    }
+
+    /// Display which UDP ports of each region or STUN server answered, one
+    /// column per port, followed by the ports nothing answered
+    pub fn display_udp_matrix(rows: &[UdpMatrixRow]) {
+        println!("\n=== UDP REACHABILITY ===");
+        if rows.is_empty() {
+            println!("No regions checked");
+            return;
+        }
+        let mut ports: Vec<UdpPort> = Vec::new();
+        for check in rows.iter().flat_map(|row| &row.checks) {
+            if !ports.contains(&check.port) {
+                ports.push(check.port);
+            }
+        }
+
+        let mut builder = Builder::default();
+        let mut header = vec!["Region".to_string(), "Host".to_string()];
+        header.extend(ports.iter().map(ToString::to_string));
+        builder.push_record(header);
+        for row in rows {
+            let mut record = vec![
+                DisplayUtils::format_region_name(&row.region, 24),
+                row.host.clone(),
+            ];
+            record.extend(ports.iter().map(|port| {
+                let check = row.checks.iter().find(|check| check.port == *port);
+                match check.map(|check| (check.reachability, check.rtt_ms)) {
+                    Some((UdpReachability::Answered, Some(rtt))) => {
+                        format!("{} {}", DisplayUtils::symbol("✓", "+"), DisplayUtils::format_latency(rtt))
+                    }
+                    Some((reachability, _)) => reachability.to_string(),
+                    None => "-".to_string(),
+                }
+            }));
+            builder.push_record(record);
+        }
+
+        let mut table = builder.build();
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::new(2..)).with(Alignment::right()));
+        DisplayUtils::fit_table(&mut table, &["Host"]);
+        println!("{table}");
+        println!("closed: the host refused the port; no answer: filtered on the way or nothing answering");
+        for port in UdpMatrixRow::unanswered_ports(rows) {
+            println!("Nothing answered on {port}; the hosts may not serve it, or something on the way filters it");
+        }
+    }
+
+    /// Display every recipe with its description and limits
+    pub fn display_recipes(recipes: &[(String, Recipe)]) {
+        println!("\n=== RECIPES ===");
+        let mut builder = Builder::default();
+        builder.push_record(["Recipe", "Description", "Limits"]);
+        for (name, recipe) in recipes {
+            let mut limits: Vec<String> = [
+                ("latency", "<=", recipe.max_latency_ms, "ms"),
+                ("p95", "<=", recipe.max_p95_ms, "ms"),
+                ("jitter", "<=", recipe.max_jitter_ms, "ms"),
+                ("loss", "<=", recipe.max_loss_percent, "%"),
+                ("TLS handshake", "<=", recipe.max_tls_handshake_ms, "ms"),
+                ("throughput", ">=", recipe.min_throughput_mbps, "Mbit/s"),
+            ]
+            .into_iter()
+            .filter_map(|(metric, bound, limit, unit)| limit.map(|limit| format!("{metric} {bound} {limit} {unit}")))
+            .collect();
+            limits.extend(recipe.udp_ports.iter().map(|port| format!("UDP {port} answers")));
+            builder.push_record([name.clone(), recipe.description.clone(), limits.join(", ")]);
+        }
+
+        let mut table = builder.build();
+        DisplayUtils::style_table(&mut table);
+        DisplayUtils::fit_table(&mut table, &["Description"]);
+        println!("{table}");
+    }
+
+    /// Display idle and loaded latency per region with the network's grade
+    pub fn display_bufferbloat(results: &[BufferbloatResult]) {
+        println!("\n=== LATENCY UNDER LOAD ===");
+        if results.is_empty() {
+            println!("No regions tested");
+            return;
+        }
+
+        let ms = |value: Option<f64>| value.map_or_else(|| "-".to_string(), |ms| format!("{ms:.1} ms"));
+        let mut builder = Builder::default();
+        builder.push_record(["Region", "Idle", "Loaded", "Increase", "Grade", "Load"]);
+        for result in results {
+            builder.push_record([
+                DisplayUtils::format_region_name(&result.region, 24),
+                ms(result.idle_ms),
+                ms(result.loaded_ms),
+                result.increase_ms().map_or_else(|| "-".to_string(), |ms| format!("+{ms:.1} ms")),
+                result.grade.map_or_else(|| "-".to_string(), |grade| grade.to_string()),
+                result
+                    .download_mbps
+                    .map_or_else(|| "failed".to_string(), |mbps| format!("{mbps:.1} Mbps")),
+            ]);
+        }
+
+        let mut table = builder.build();
+        DisplayUtils::style_table(&mut table);
+        DisplayUtils::fit_table(&mut table, &[]);
+        println!("{table}");
+        if let Some((grade, increase)) = BufferbloatResult::network_grade(results) {
+            println!("Network bufferbloat grade: {grade} (median +{increase:.1} ms under load)");
+        }
+    }
+
+    /// Display the verdict of every recipe for every region, then why recipes failed
+    pub fn display_recipe_results(results: &[RecipeResult]) {
+        println!("\n=== RECIPE CHECKS ===");
+        if results.is_empty() {
+            println!("No regions checked");
+            return;
+        }
+
+        let mut recipes: Vec<&str> = Vec::new();
+        let mut regions: Vec<&str> = Vec::new();
+        for result in results {
+            if !recipes.contains(&result.recipe.as_str()) {
+                recipes.push(&result.recipe);
+            }
+            if !regions.contains(&result.region.as_str()) {
+                regions.push(&result.region);
+            }
+        }
+        let mut builder = Builder::default();
+        let mut header = vec!["Region".to_string()];
+        header.extend(recipes.iter().map(ToString::to_string));
+        builder.push_record(header);
+        for region in &regions {
+            let mut record = vec![DisplayUtils::format_region_name(region, 24)];
+            record.extend(recipes.iter().map(|recipe| {
+                results
+                    .iter()
+                    .find(|result| result.region == *region && result.recipe == *recipe)
+                    .map_or_else(
+                        || "-".to_string(),
+                        |result| {
+                            let symbol = match result.verdict {
+                                RecipeVerdict::Pass => DisplayUtils::symbol("✓", "+"),
+                                RecipeVerdict::Fail => DisplayUtils::symbol("✗", "x"),
+                                RecipeVerdict::Inconclusive => "?",
+                            };
+                            format!("{symbol} {}", result.verdict)
+                        },
+                    )
+            }));
+            builder.push_record(record);
+        }
+
+        let mut table = builder.build();
+        DisplayUtils::style_table(&mut table);
+        DisplayUtils::fit_table(&mut table, &[]);
+        println!("{table}");
+        for result in results.iter().filter(|result| result.verdict != RecipeVerdict::Pass) {
+            let reasons: Vec<String> = result
+                .checks
+                .iter()
+                .filter(|check| check.outcome != CheckOutcome::Pass)
+                .map(|check| match (check.outcome, check.measured, check.limit) {
+                    (CheckOutcome::Unmeasured, _, _) => format!("{} not measured", check.metric),
+                    (_, Some(measured), Some(limit)) => format!("{} {measured:.1} (limit {limit})", check.metric),
+                    _ => format!("{} did not answer", check.metric),
+                })
+                .collect();
+            println!("{} / {}: {}", result.region, result.recipe, reasons.join(", "));
+        }
+    }
+
+    /// Display each region's saved runs in time buckets, one row per bucket
+    pub fn display_rollups(rollups: &[Rollup]) {
+        println!("\n=== HISTORY ROLLUP ===");
+        if rollups.iter().all(|rollup| rollup.buckets.is_empty()) {
+            println!("No saved runs in this range");
+            return;
+        }
+
+        let mut builder = Builder::default();
+        builder.push_record(["Region", "Start", "Runs", "Avg", "P95", "Loss"]);
+        for rollup in rollups {
+            for bucket in &rollup.buckets {
+                let latency = |value: Option<f64>| value.map_or_else(|| "-".to_string(), DisplayUtils::format_latency);
+                builder.push_record([
+                    DisplayUtils::format_region_name(&rollup.region, 24),
+                    bucket.start.format("%Y-%m-%d %H:%M").to_string(),
+                    bucket.runs.to_string(),
+                    latency(bucket.avg_ms),
+                    latency(bucket.p95_ms),
+                    DisplayUtils::format_percentage(bucket.loss_percent),
+                ]);
+            }
+        }
+
+        let mut table = builder.build();
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::new(2..)).with(Alignment::right()));
+        DisplayUtils::fit_table(&mut table, &["Region"]);
+        println!("{table}");
+    }
+
+    /// Display estimated round trips between every pair of regions
+    pub fn display_latency_matrix(matrix: &LatencyMatrix) {
+        println!("\n=== REGION-TO-REGION LATENCY (ms) ===");
+        if matrix.regions.is_empty() {
+            println!("No regions with coordinates or inter-region dataset entries");
+            return;
+        }
+
+        let measured = DisplayUtils::symbol("•", "*");
+        let mut builder = Builder::default();
+        let mut header = vec![String::new()];
+        header.extend(matrix.regions.iter().map(|region| DisplayUtils::format_region_name(region, 16)));
+        builder.push_record(header);
+        for (region, row) in matrix.regions.iter().zip(&matrix.rows) {
+            let mut record = vec![DisplayUtils::format_region_name(region, 24)];
+            record.extend(row.iter().map(|estimate| match estimate {
+                Some(estimate) if estimate.source == EstimateSource::Dataset => {
+                    format!("{:.0}{measured}", estimate.rtt_ms)
+                }
+                Some(estimate) => format!("{:.0}", estimate.rtt_ms),
+                None => "-".to_string(),
+            }));
+            builder.push_record(record);
+        }
+
+        let mut table = builder.build();
+        DisplayUtils::style_table(&mut table);
+        DisplayUtils::fit_table(&mut table, &[]);
+        println!("{table}");
+        if matrix.calibration_samples > 0 {
+            println!(
+                "Route factor {:.2}, calibrated on {} measured region(s); {measured} measured in the dataset",
+                matrix.route_factor, matrix.calibration_samples
+            );
+        } else {
+            println!(
+                "Route factor {:.2} (default); {measured} measured in the dataset",
+                matrix.route_factor
+            );
+        }
+    }
+
+    /// Display the ranking under every weight profile next to the baseline
+    pub fn display_what_if(comparison: &WhatIfComparison) {
+        println!("\n=== WHAT-IF RANKINGS ===");
+        for profile in &comparison.profiles {
+            println!("  {profile}");
+        }
+
+        let mut builder = Builder::default();
+        let mut header = vec!["Region".to_string()];
+        header.extend(comparison.profiles.iter().map(|profile| profile.name.clone()));
+        builder.push_record(header);
+        for row in &comparison.rows {
+            let mut record = vec![DisplayUtils::format_region_name(&row.region, 40)];
+            record.extend(row.ranks.iter().enumerate().map(|(i, rank)| {
+                let shift = match row.rank_shift(i) {
+                    0 => String::new(),
+                    up if up > 0 => format!(" {}{up}", DisplayUtils::symbol("▲", "+")),
+                    down => format!(" {}{}", DisplayUtils::symbol("▼", "-"), down.unsigned_abs()),
+                };
+                format!("#{} {:.1} {}{shift}", rank.rank, rank.score, rank.grade)
+            }));
+            builder.push_record(record);
+        }
+
+        let mut table = builder.build();
+        DisplayUtils::style_table(&mut table);
+        DisplayUtils::fit_table(&mut table, &[]);
+        println!("{table}");
+
+        let leaders = comparison.leaders();
+        for (i, profile) in comparison.profiles.iter().enumerate().skip(1) {
+            println!(
+                "{}: {} region(s) change rank, best is {}",
+                profile.name,
+                comparison.moved(i),
+                leaders.get(i).copied().unwrap_or("-")
+            );
+        }
+    }
+
+    /// Display how each component and weight made up the score of `name`
+    pub fn display_score_explanation(name: &str, explanation: &ScoreExplanation) {
+        println!("\n=== WHY {} SCORED {:.1} ===", name.to_uppercase(), explanation.score);
+
+        let rows: Vec<ScoreComponentRow> = explanation
+            .components
+            .iter()
+            .map(|component| ScoreComponentRow {
+                component: component.component.replace('_', " "),
+                raw: component.raw_text(),
+                rule: component.rule.clone(),
+                normalized: format!("{:.1}", component.normalized),
+                weight: format!("{:.2}", component.weight),
+                contribution: format!("{:.1}", component.contribution),
+            })
+            .collect();
+
+        let mut table = Table::new(rows);
+        DisplayUtils::style_table(&mut table)
+            .with(Modify::new(Columns::single(1)).with(Alignment::right()))
+            .with(Modify::new(Columns::new(3..)).with(Alignment::right()));
+        DisplayUtils::fit_table(&mut table, &["Band", "Raw"]);
+        println!("{table}");
+
+        print!("Grade {} ({})", explanation.grade, explanation.grade_range);
+        match explanation.points_to_next_grade {
+            Some(points) => println!(", {points:.1} points below the next grade"),
+            None => println!(),
+        }
+    }
+
+    /// Display degraded results that coincide with declared provider incidents
+    pub fn display_incident_annotations(annotations: &[IncidentAnnotation]) {
+        println!("\n=== PROVIDER INCIDENTS ===");
+
+        if annotations.is_empty() {
+            println!("No degraded region matches a declared provider incident.");
+            return;
+        }
+
+        for annotation in annotations {
+            println!(
+                "{} may be affected by a provider incident: {}",
+                annotation.region,
+                annotation.incident.headline()
+            );
+            if !annotation.incident.summary.is_empty() {
+                println!("  {}", ProgressBarFactory::truncate_text(&annotation.incident.summary, 160));
+            }
+        }
     }
 
     /// Show detailed URL test results with optional verbose output
@@ -277,6 +1858,42 @@ impl DisplayFormatter {
                 println!("HTTP Status Codes: {:?}", stats.status_codes);
             }
 
+            if let Some(asn) = &stats.asn {
+                println!("Network: {asn} ({})", asn.ip);
+            }
+
+            for count in &stats.edge_pops {
+                println!(
+                    "Edge POP: {} ({} pings, avg {})",
+                    count.edge,
+                    count.pings,
+                    DisplayUtils::format_latency(count.avg_ms)
+                );
+            }
+
+            if let Some(reuse) = ConnectionReuse::from_metadata(&stats.metadata) {
+                let latency = |ms: Option<f64>| ms.map_or_else(|| "-".to_string(), DisplayUtils::format_latency);
+                println!(
+                    "Connections: {} new (avg {}), {} reused (avg {}), {} reconnects",
+                    reuse.new,
+                    latency(reuse.new_avg_ms),
+                    reuse.reused,
+                    latency(reuse.reused_avg_ms),
+                    reuse.reconnects
+                );
+            }
+
+            if let Some(validation) = CertValidationSummary::from_metadata(&stats.metadata) {
+                println!(
+                    "Certificate Validation: avg {:.2}ms, max {:.2}ms over {} handshakes, chain of {}, OCSP stapled in {}",
+                    validation.avg_ms,
+                    validation.max_ms,
+                    validation.validations,
+                    validation.chain_length,
+                    validation.ocsp_stapled
+                );
+            }
+
             if !stats.error_message.is_empty() {
                 println!("Error: {}", stats.error_message);
             }
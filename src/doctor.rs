@@ -0,0 +1,374 @@
+//! Self-test of the local environment
+//!
+//! When every region fails, the cause is usually local: a broken config, an
+//! unreadable data file, no DNS, a firewall blocking outbound traffic or a
+//! badly skewed clock. [`Doctor`] checks each of these in turn and returns a
+//! [`DoctorReport`] of pass, warning and failure results.
+
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::net::TcpStream;
+use url::Url;
+
+use crate::collector::csv_field;
+use crate::config::AppConfig;
+use crate::data_loader::DataLoader;
+use crate::error::Result;
+use crate::models::Region;
+use crate::transport::ReqwestTransport;
+
+/// Clock skew beyond which a warning is reported
+const CLOCK_SKEW_WARN: Duration = Duration::from_secs(5);
+
+/// Clock skew beyond which TLS validation and history timestamps break
+const CLOCK_SKEW_FAIL: Duration = Duration::from_secs(300);
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    /// Working as expected
+    Pass,
+    /// Working, but likely to cause surprises or skipped
+    Warn,
+    /// Broken; tests will fail until fixed
+    Fail,
+}
+
+impl std::fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Pass => "PASS",
+            Self::Warn => "WARN",
+            Self::Fail => "FAIL",
+        })
+    }
+}
+
+/// Result of one diagnostic check
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    /// What was checked
+    pub name: String,
+    /// Whether the check passed
+    pub status: CheckStatus,
+    /// What was found, or how to fix it
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn new(name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        }
+    }
+
+    fn pass(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Pass, detail)
+    }
+
+    fn warn(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Warn, detail)
+    }
+
+    fn fail(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Fail, detail)
+    }
+}
+
+/// Results of all checks in the order they ran
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DoctorReport {
+    /// One result per check
+    pub checks: Vec<CheckResult>,
+}
+
+impl DoctorReport {
+    /// Whether no check failed; warnings still pass
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Fail)
+    }
+
+    /// Number of checks with `status`
+    #[must_use]
+    pub fn count(&self, status: CheckStatus) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| check.status == status)
+            .count()
+    }
+
+    /// Render the report as CSV with one row per check
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("check,status,detail\n");
+        for check in &self.checks {
+            let _ = writeln!(
+                csv,
+                "{},{},{}",
+                csv_field(&check.name),
+                check.status,
+                csv_field(&check.detail)
+            );
+        }
+        csv
+    }
+}
+
+/// Runs the environment checks
+pub struct Doctor {
+    config: AppConfig,
+    config_check: CheckResult,
+    data_file: String,
+}
+
+impl Doctor {
+    /// Diagnose with the outcome of loading the configuration; a config that
+    /// failed to load is reported and the defaults are used for the other checks
+    #[must_use]
+    pub fn new(loaded: Result<AppConfig>, data_file: Option<String>) -> Self {
+        let (config, config_check) = match loaded.and_then(|config| {
+            config.validate()?;
+            Ok(config)
+        }) {
+            Ok(config) => {
+                let source = AppConfig::config_path()
+                    .filter(|path| path.exists())
+                    .map_or_else(
+                        || "no config file, using defaults".to_string(),
+                        |path| format!("loaded {}", path.display()),
+                    );
+                (config, CheckResult::pass("Configuration", source))
+            }
+            Err(e) => (
+                AppConfig::default(),
+                CheckResult::fail("Configuration", format!("{e}; using defaults")),
+            ),
+        };
+        let data_file = data_file.unwrap_or_else(|| config.data_file.clone());
+        Self {
+            config,
+            config_check,
+            data_file,
+        }
+    }
+
+    /// Run every check; network checks use the first region of the data file
+    pub async fn run(self) -> DoctorReport {
+        let mut checks = vec![self.config_check.clone()];
+
+        let (data_check, sample) = self.check_data_file().await;
+        checks.push(data_check);
+
+        match sample.as_ref().and_then(|region| sample_host(&region.url)) {
+            Some(host) => {
+                checks.push(self.check_dns(&host).await);
+                for port in [80, 443] {
+                    checks.push(check_port(&host, port, self.config.timeout).await);
+                }
+            }
+            None => {
+                for name in ["DNS resolution", "Outbound port 80", "Outbound port 443"] {
+                    checks.push(CheckResult::warn(name, "skipped, no sample region"));
+                }
+            }
+        }
+
+        checks.push(check_icmp());
+        checks.push(match &sample {
+            Some(region) => self.check_clock(&region.url).await,
+            None => CheckResult::warn("Clock", "skipped, no sample region"),
+        });
+
+        DoctorReport { checks }
+    }
+
+    async fn check_data_file(&self) -> (CheckResult, Option<Region>) {
+        const NAME: &str = "Data file";
+        match DataLoader::load_cloud_providers(&self.data_file).await {
+            Ok(providers) => {
+                let regions: usize = providers.iter().map(|p| p.regions.len()).sum();
+                let sample = providers
+                    .into_iter()
+                    .flat_map(|provider| provider.regions)
+                    .next();
+                let check = if sample.is_some() {
+                    CheckResult::pass(NAME, format!("{}: {regions} regions", self.data_file))
+                } else {
+                    CheckResult::fail(NAME, format!("{} lists no regions", self.data_file))
+                };
+                (check, sample)
+            }
+            Err(e) => (
+                CheckResult::fail(NAME, format!("{}: {e:#}", self.data_file)),
+                None,
+            ),
+        }
+    }
+
+    async fn check_dns(&self, host: &str) -> CheckResult {
+        const NAME: &str = "DNS resolution";
+        let lookup = tokio::net::lookup_host((host, 443));
+        match tokio::time::timeout(self.config.timeout, lookup).await {
+            Ok(Ok(addrs)) => {
+                let addrs: Vec<SocketAddr> = addrs.collect();
+                addrs.first().map_or_else(
+                    || CheckResult::fail(NAME, format!("{host} has no addresses")),
+                    |addr| CheckResult::pass(NAME, format!("{host} -> {}", addr.ip())),
+                )
+            }
+            Ok(Err(e)) => CheckResult::fail(NAME, format!("{host}: {e}")),
+            Err(_) => CheckResult::fail(NAME, format!("{host}: timed out")),
+        }
+    }
+
+    async fn check_clock(&self, url: &str) -> CheckResult {
+        const NAME: &str = "Clock";
+        let transport = match ReqwestTransport::new(&self.config) {
+            Ok(transport) => transport,
+            Err(e) => return CheckResult::warn(NAME, format!("skipped: {e}")),
+        };
+        let response = match transport.client().head(url).send().await {
+            Ok(response) => response,
+            Err(e) => return CheckResult::warn(NAME, format!("no server time: {e}")),
+        };
+        let server_date = response
+            .headers()
+            .get(reqwest::header::DATE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        server_date.map_or_else(
+            || CheckResult::warn(NAME, "server sent no Date header"),
+            |date| clock_check(Utc::now(), &date),
+        )
+    }
+}
+
+fn sample_host(url: &str) -> Option<String> {
+    Url::parse(url).ok()?.host_str().map(str::to_string)
+}
+
+async fn check_port(host: &str, port: u16, timeout: Duration) -> CheckResult {
+    let name = format!("Outbound port {port}");
+    match tokio::time::timeout(timeout, TcpStream::connect((host, port))).await {
+        Ok(Ok(_)) => CheckResult::pass(name, format!("connected to {host}:{port}")),
+        Ok(Err(e)) => CheckResult::fail(name, format!("{host}:{port}: {e}")),
+        Err(_) => CheckResult::fail(
+            name,
+            format!("{host}:{port}: timed out (blocked by a firewall?)"),
+        ),
+    }
+}
+
+/// ICMP needs either unprivileged ping sockets or raw socket privileges
+fn check_icmp() -> CheckResult {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    const NAME: &str = "ICMP privileges";
+    if Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4)).is_ok() {
+        return CheckResult::pass(NAME, "unprivileged ICMP sockets available");
+    }
+    match Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4)) {
+        Ok(_) => CheckResult::pass(NAME, "raw sockets available"),
+        Err(e) => CheckResult::warn(
+            NAME,
+            format!("ICMP probes unavailable ({e}); HTTP and TCP tests are unaffected"),
+        ),
+    }
+}
+
+/// Compare the local clock with a server's HTTP `Date` header
+fn clock_check(local: DateTime<Utc>, server_date: &str) -> CheckResult {
+    const NAME: &str = "Clock";
+    let Ok(server) = DateTime::parse_from_rfc2822(server_date) else {
+        return CheckResult::warn(NAME, format!("unreadable server time '{server_date}'"));
+    };
+    let skew = (local - server.with_timezone(&Utc))
+        .abs()
+        .to_std()
+        .unwrap_or_default();
+    let detail = format!("{}s off server time", skew.as_secs());
+    if skew > CLOCK_SKEW_FAIL {
+        CheckResult::fail(
+            NAME,
+            format!("{detail}; TLS and history timestamps will be wrong"),
+        )
+    } else if skew > CLOCK_SKEW_WARN {
+        CheckResult::warn(NAME, detail)
+    } else {
+        CheckResult::pass(NAME, detail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CloudPingError;
+
+    #[tokio::test]
+    async fn test_broken_config_and_missing_data_file_fail() {
+        let doctor = Doctor::new(
+            Err(CloudPingError::config("bad toml")),
+            Some("/nonexistent/data.json".to_string()),
+        );
+        assert_eq!(doctor.config_check.status, CheckStatus::Fail);
+
+        let (check, sample) = doctor.check_data_file().await;
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(sample.is_none());
+
+        let report = doctor.run().await;
+        assert!(!report.passed());
+        // Network checks are skipped without a sample region
+        assert_eq!(report.checks[2].status, CheckStatus::Warn);
+    }
+
+    #[tokio::test]
+    async fn test_data_file_sample_and_port_check() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(
+            &mut file,
+            br#"{"Provider": {"regions": [{"name": "Frankfurt", "url": "https://eu.example.com/ping"}]}}"#,
+        )
+        .unwrap();
+        let doctor = Doctor::new(
+            Ok(AppConfig::default()),
+            Some(file.path().display().to_string()),
+        );
+        let (check, sample) = doctor.check_data_file().await;
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert_eq!(
+            sample_host(&sample.unwrap().url).as_deref(),
+            Some("eu.example.com")
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let check = check_port("127.0.0.1", port, Duration::from_secs(1)).await;
+        assert_eq!(check.status, CheckStatus::Pass);
+    }
+
+    #[test]
+    fn test_clock_skew_thresholds() {
+        let server = "Fri, 16 Oct 2026 12:00:00 GMT";
+        let at = |secs| {
+            DateTime::parse_from_rfc2822(server)
+                .unwrap()
+                .with_timezone(&Utc)
+                + chrono::Duration::seconds(secs)
+        };
+        assert_eq!(clock_check(at(2), server).status, CheckStatus::Pass);
+        assert_eq!(clock_check(at(-30), server).status, CheckStatus::Warn);
+        assert_eq!(clock_check(at(3600), server).status, CheckStatus::Fail);
+        assert_eq!(clock_check(at(0), "yesterday").status, CheckStatus::Warn);
+    }
+}
@@ -0,0 +1,141 @@
+//! Endpoints managed at runtime through the HTTP and gRPC APIs
+//!
+//! Endpoints created, changed or deleted through an API while `monitor` or
+//! `agent` runs are recorded in the file set as `monitoring.endpoints_file`.
+//! On the next start the recorded endpoints replace configured ones of the
+//! same ID and deleted ones are dropped again, so a fleet of agents can be
+//! reconfigured centrally without editing their config files.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::error::Result;
+use crate::models::Endpoint;
+
+/// Endpoint to create, as sent to `POST /api/endpoints` or `CreateEndpoint`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EndpointSpec {
+    /// URL probed by its scheme
+    pub url: String,
+    /// ID of the endpoint; the URL when unset
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Name shown on the status page and in alerts; the URL when unset
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Whether the endpoint is probed
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+}
+
+const fn enabled_by_default() -> bool {
+    true
+}
+
+/// Changes to a monitored endpoint; settings left unset are kept
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EndpointChanges {
+    /// URL to probe instead, keeping the endpoint's ID
+    #[serde(default)]
+    pub url: Option<String>,
+    /// New display name
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Resume or pause probing the endpoint
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+/// Endpoints changed through an API since the configuration was written
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ManagedEndpoints {
+    /// Endpoints created or changed, by ID
+    #[serde(default)]
+    pub endpoints: BTreeMap<String, Endpoint>,
+    /// IDs of endpoints deleted, which configured endpoints must not bring back
+    #[serde(default)]
+    pub deleted: BTreeSet<String>,
+}
+
+/// JSON file holding the [`ManagedEndpoints`]
+#[derive(Debug, Clone)]
+pub struct EndpointStore {
+    path: PathBuf,
+}
+
+impl EndpointStore {
+    /// Store kept in `path`
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// File the endpoints are kept in
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Endpoints saved before; none when the file does not exist yet
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or parsed
+    pub async fn load(&self) -> Result<ManagedEndpoints> {
+        match tokio::fs::read(&self.path).await {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ManagedEndpoints::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replace the saved endpoints, creating the file's directory
+    ///
+    /// The endpoints are written to a temporary file that is then moved over
+    /// the store, so a crash leaves either the old endpoints or the new ones.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be written
+    pub async fn save(&self, managed: &ManagedEndpoints) -> Result<()> {
+        if let Some(parent) = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        let mut file = tokio::fs::File::create(&temporary).await?;
+        file.write_all(&serde_json::to_vec_pretty(managed)?).await?;
+        file.sync_all().await?;
+        drop(file);
+        tokio::fs::rename(&temporary, &self.path).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProbeType;
+
+    #[tokio::test]
+    async fn test_endpoint_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EndpointStore::new(dir.path().join("state").join("endpoints.json"));
+        assert_eq!(store.load().await.unwrap(), ManagedEndpoints::default());
+
+        let mut managed = ManagedEndpoints::default();
+        let mut endpoint =
+            Endpoint::new("api".to_string(), "api.example.com".to_string(), 443, ProbeType::HTTP);
+        endpoint.enabled = false;
+        managed.endpoints.insert(endpoint.id.clone(), endpoint);
+        managed.deleted.insert("eu-central-1".to_string());
+        store.save(&managed).await.unwrap();
+        assert_eq!(store.load().await.unwrap(), managed);
+
+        let spec: EndpointSpec = serde_json::from_str(r#"{"url": "https://api.example.com"}"#).unwrap();
+        assert!(spec.enabled);
+        assert_eq!(spec.id, None);
+    }
+}
@@ -0,0 +1,362 @@
+//! Local environment capture
+//!
+//! Collects the operating system, default route interface, gateway and ISP
+//! first-hop round-trip times and (optionally) the public IP and ASN and the
+//! NAT type before a benchmark run.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use tokio::net::TcpStream;
+use tracing::{debug, warn};
+
+use crate::config::AppConfig;
+use crate::error::Result;
+use crate::models::{InterfaceType, TestEnvironment};
+use crate::stun;
+use crate::time_utils::TimeUtils;
+
+/// Service used for the optional public IP / ASN lookup
+const PUBLIC_IP_LOOKUP_URL: &str = "https://ipinfo.io/json";
+
+/// Ports tried when timing the gateway; a refused connection still yields an RTT
+const GATEWAY_PROBE_PORTS: [u16; 3] = [53, 80, 443];
+
+/// Number of gateway samples; the fastest is reported
+const GATEWAY_PROBE_SAMPLES: usize = 3;
+
+/// Per-connection timeout for gateway probes
+const GATEWAY_PROBE_TIMEOUT_MS: u64 = 500;
+
+/// Well-connected anycast address traced towards to discover the ISP first hop
+const FIRST_HOP_TRACE_TARGET: &str = "1.1.1.1";
+
+/// Hops traced when discovering the ISP first hop
+const FIRST_HOP_MAX_TTL: u8 = 3;
+
+/// Upper bound for the trace subprocess
+const FIRST_HOP_TRACE_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Deserialize)]
+struct PublicIpInfo {
+    ip: Option<String>,
+    org: Option<String>,
+    country: Option<String>,
+}
+
+/// Captures a [`TestEnvironment`] snapshot
+pub struct EnvironmentCapture;
+
+impl EnvironmentCapture {
+    /// Capture the local environment; the public lookup runs only when enabled
+    /// in config and NAT discovery only with STUN servers configured
+    pub async fn capture(config: &AppConfig) -> TestEnvironment {
+        let route = Self::default_route();
+        let interface_name = route.as_ref().map(|(name, _)| name.clone());
+        let default_gateway = route.map(|(_, gateway)| IpAddr::V4(gateway));
+
+        let gateway_rtt_ms = match default_gateway {
+            Some(gateway) => Self::measure_rtt(gateway).await,
+            None => None,
+        };
+
+        let (isp_first_hop, isp_first_hop_rtt_ms) =
+            Self::measure_isp_first_hop(config, default_gateway).await;
+
+        let mut environment = TestEnvironment {
+            captured_at: TimeUtils::now(),
+            os: format!("{}/{}", std::env::consts::OS, std::env::consts::ARCH),
+            interface_type: interface_name
+                .as_deref()
+                .map_or(InterfaceType::Unknown, Self::interface_type),
+            interface_name,
+            default_gateway,
+            gateway_rtt_ms,
+            isp_first_hop,
+            isp_first_hop_rtt_ms,
+            public_ip: None,
+            asn: None,
+            country: None,
+            public_mapping: None,
+            nat_type: None,
+        };
+
+        if config.environment_lookup {
+            match Self::lookup_public_ip(config).await {
+                Ok(info) => {
+                    environment.public_ip = info.ip;
+                    environment.asn = info.org;
+                    environment.country = info.country;
+                }
+                Err(e) => warn!("Public IP lookup failed: {}", e),
+            }
+        }
+
+        if !config.stun_servers.is_empty() {
+            let nat = stun::discover(&config.stun_servers).await;
+            environment.public_mapping = nat.public_mapping;
+            environment.nat_type = Some(nat.nat_type);
+        }
+
+        debug!("Captured test environment: {}", environment.summary());
+        environment
+    }
+
+    /// Best TCP handshake time to `host` over a few samples
+    ///
+    /// A refused connection completes a round trip too, so hosts that do not
+    /// listen on any probe port can still be timed.
+    pub async fn measure_rtt(host: IpAddr) -> Option<f64> {
+        let timeout = TimeUtils::duration_from_millis(GATEWAY_PROBE_TIMEOUT_MS);
+        let mut best: Option<Duration> = None;
+
+        for _ in 0..GATEWAY_PROBE_SAMPLES {
+            for port in GATEWAY_PROBE_PORTS {
+                let start = Instant::now();
+                let attempt =
+                    tokio::time::timeout(timeout, TcpStream::connect(SocketAddr::new(host, port)))
+                        .await;
+
+                let answered = match attempt {
+                    Ok(Ok(_)) => true,
+                    Ok(Err(e)) => e.kind() == std::io::ErrorKind::ConnectionRefused,
+                    Err(_) => false,
+                };
+
+                if answered {
+                    let elapsed = start.elapsed();
+                    best = Some(best.map_or(elapsed, |b| b.min(elapsed)));
+                    break;
+                }
+            }
+        }
+
+        best.map(|rtt| rtt.as_secs_f64() * 1000.0)
+    }
+
+    /// Locate and time the first router beyond the local gateway
+    ///
+    /// Uses `isp_first_hop` from the config when set; otherwise, with
+    /// `trace_isp_first_hop`, runs the system `tracepath` or `traceroute` (no
+    /// privileges needed) for a few hops and takes the first responding hop
+    /// that is not the default gateway.
+    async fn measure_isp_first_hop(
+        config: &AppConfig,
+        gateway: Option<IpAddr>,
+    ) -> (Option<String>, Option<f64>) {
+        if let Some(host) = &config.isp_first_hop {
+            let rtt = if let Ok(address) = host.parse::<IpAddr>() {
+                Self::measure_rtt(address).await
+            } else {
+                warn!("isp_first_hop must be an IP address, got {}", host);
+                None
+            };
+            return (Some(host.clone()), rtt);
+        }
+        if !config.trace_isp_first_hop {
+            return (None, None);
+        }
+
+        let timeout = TimeUtils::duration_from_secs(FIRST_HOP_TRACE_TIMEOUT_SECS);
+        let Some(hops) = trace_hops(FIRST_HOP_TRACE_TARGET, FIRST_HOP_MAX_TTL, timeout).await else {
+            debug!("No tracepath/traceroute available; skipping ISP first hop");
+            return (None, None);
+        };
+
+        let gateway = gateway.map(|g| g.to_string());
+        hops.into_iter()
+            .find(|(_, address, _)| Some(address) != gateway.as_ref())
+            .map_or((None, None), |(_, address, rtt)| (Some(address), Some(rtt)))
+    }
+
+    async fn lookup_public_ip(config: &AppConfig) -> Result<PublicIpInfo> {
+        let client = reqwest::Client::builder()
+            .timeout(config.get_timeout())
+            .user_agent(&config.user_agent)
+            .build()?;
+
+        Ok(client
+            .get(PUBLIC_IP_LOOKUP_URL)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// Interface and gateway of the IPv4 default route
+    #[cfg(target_os = "linux")]
+    fn default_route() -> Option<(String, Ipv4Addr)> {
+        let table = std::fs::read_to_string("/proc/net/route").ok()?;
+        parse_default_route(&table)
+    }
+
+    /// Interface and gateway of the IPv4 default route
+    #[cfg(not(target_os = "linux"))]
+    fn default_route() -> Option<(String, Ipv4Addr)> {
+        None
+    }
+
+    #[cfg(target_os = "linux")]
+    fn interface_type(name: &str) -> InterfaceType {
+        let sysfs = std::path::Path::new("/sys/class/net").join(name);
+        if sysfs.join("wireless").exists() || sysfs.join("phy80211").exists() {
+            InterfaceType::Wifi
+        } else if name.starts_with("wwan") || name.starts_with("rmnet") {
+            InterfaceType::Cellular
+        } else if std::fs::read_to_string(sysfs.join("type")).is_ok_and(|t| t.trim() == "1") {
+            InterfaceType::Ethernet
+        } else {
+            InterfaceType::Unknown
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn interface_type(_name: &str) -> InterfaceType {
+        InterfaceType::Unknown
+    }
+}
+
+/// Find the default route in `/proc/net/route` (gateway is little-endian hex)
+fn parse_default_route(table: &str) -> Option<(String, Ipv4Addr)> {
+    table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 3 || fields[1] != "00000000" {
+            return None;
+        }
+
+        let gateway = u32::from_str_radix(fields[2], 16).ok()?;
+        if gateway == 0 {
+            return None;
+        }
+
+        Some((fields[0].to_string(), Ipv4Addr::from(gateway.to_le_bytes())))
+    })
+}
+
+/// Responding hops as `(ttl, address, rtt_ms)` from `tracepath -n` or `traceroute -n` output
+/// Responding hops towards `target` as TTL, address and round trip in ms,
+/// traced with the system `tracepath` or `traceroute` up to `max_ttl` hops
+///
+/// `None` when neither tool runs within `timeout`.
+pub(crate) async fn trace_hops(target: &str, max_ttl: u8, timeout: Duration) -> Option<Vec<(u8, String, f64)>> {
+    let max_ttl = max_ttl.to_string();
+    let commands: [(&str, Vec<&str>); 2] = [
+        ("tracepath", vec!["-n", "-m", &max_ttl, target]),
+        ("traceroute", vec!["-n", "-q", "1", "-w", "1", "-m", &max_ttl, target]),
+    ];
+
+    for (program, args) in commands {
+        let run = tokio::process::Command::new(program)
+            .args(&args)
+            .kill_on_drop(true)
+            .output();
+
+        if let Ok(Ok(output)) = tokio::time::timeout(timeout, run).await {
+            if !output.stdout.is_empty() {
+                return Some(parse_trace_hops(&String::from_utf8_lossy(&output.stdout)));
+            }
+        }
+    }
+
+    None
+}
+
+fn parse_trace_hops(output: &str) -> Vec<(u8, String, f64)> {
+    let mut hops: Vec<(u8, String, f64)> = Vec::new();
+
+    for line in output.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some(ttl) = tokens
+            .first()
+            .and_then(|t| t.trim_end_matches([':', '?']).parse::<u8>().ok())
+        else {
+            continue;
+        };
+        let Some(address) = tokens.get(1).filter(|t| t.parse::<IpAddr>().is_ok()) else {
+            continue;
+        };
+
+        // tracepath prints "0.987ms", traceroute prints "0.987 ms"
+        let rest = tokens[2..].join(" ").replace(" ms", "ms");
+        let rtt = rest
+            .split_whitespace()
+            .find_map(|token| token.strip_suffix("ms")?.parse::<f64>().ok());
+
+        if let Some(rtt) = rtt {
+            if !hops.iter().any(|(seen, _, _)| *seen == ttl) {
+                hops.push((ttl, (*address).to_string(), rtt));
+            }
+        }
+    }
+
+    hops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_default_route() {
+        let table = "Iface\tDestination\tGateway\tFlags\n\
+                     eth0\t000200C0\t00000000\t0001\n\
+                     wlan0\t00000000\t0101A8C0\t0003\n";
+
+        let (name, gateway) = parse_default_route(table).unwrap();
+        assert_eq!(name, "wlan0");
+        assert_eq!(gateway, Ipv4Addr::new(192, 168, 1, 1));
+        assert!(parse_default_route("Iface\tDestination\tGateway\n").is_none());
+    }
+
+    #[test]
+    fn test_parse_trace_hops() {
+        let tracepath = " 1?: [LOCALHOST]                      pmtu 1500\n \
+                         1:  192.168.1.1                       0.987ms\n \
+                         1:  192.168.1.1                       0.850ms\n \
+                         2:  10.20.30.1                        8.123ms\n \
+                         3:  no reply\n";
+        let hops = parse_trace_hops(tracepath);
+        assert_eq!(hops.len(), 2);
+        assert_eq!(hops[1], (2, "10.20.30.1".to_string(), 8.123));
+
+        let traceroute = "traceroute to 1.1.1.1 (1.1.1.1), 3 hops max\n \
+                          1  192.168.1.1  1.234 ms\n \
+                          2  *\n \
+                          3  100.64.0.1  9.5 ms\n";
+        let hops = parse_trace_hops(traceroute);
+        assert_eq!(
+            hops,
+            vec![
+                (1, "192.168.1.1".to_string(), 1.234),
+                (3, "100.64.0.1".to_string(), 9.5),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_isp_first_hop_is_traced_only_when_enabled() {
+        let config = AppConfig::default();
+        assert_eq!(EnvironmentCapture::measure_isp_first_hop(&config, None).await, (None, None));
+
+        let config = AppConfig {
+            isp_first_hop: Some("isp-router".to_string()),
+            trace_isp_first_hop: true,
+            ..AppConfig::default()
+        };
+        assert_eq!(
+            EnvironmentCapture::measure_isp_first_hop(&config, None).await,
+            (Some("isp-router".to_string()), None)
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "times the default gateway over the network"]
+    async fn test_capture_without_lookup() {
+        let environment = EnvironmentCapture::capture(&AppConfig::default()).await;
+
+        assert!(environment.os.contains(std::env::consts::OS));
+        assert!(environment.public_ip.is_none());
+    }
+}
@@ -40,6 +40,13 @@ pub enum CloudPingError {
 
     #[error("Concurrent execution error: {message}")]
     Concurrency { message: String },
+
+    /// A run or probe would exceed a configured probe budget
+    #[error("Probe budget exceeded: {message}")]
+    BudgetExceeded {
+        /// Which limit was hit and by how much
+        message: String,
+    },
 }
 
 impl CloudPingError {
@@ -89,6 +96,14 @@ impl CloudPingError {
         }
     }
 
+    /// Error for a request plan or probe that does not fit the probe budget
+    #[must_use]
+    pub fn budget_exceeded(message: impl Into<String>) -> Self {
+        Self::BudgetExceeded {
+            message: message.into(),
+        }
+    }
+
     #[must_use]
     pub fn network(message: impl Into<String>) -> Self {
         Self::TestExecution {
@@ -0,0 +1,291 @@
+//! One stream of everything the monitor observes
+//!
+//! Integrations that react to several kinds of events would otherwise
+//! subscribe to a channel per kind and merge them. The [`EventBus`] carries
+//! every [`MonitoringEvent`] instead: completed probes, published scores,
+//! endpoints turning degraded or healthy, alerts raised and resolved, and
+//! endpoints added or removed. Each subscriber names the events it wants
+//! with an [`EventFilter`] and receives them on its own bounded queue, so a
+//! slow subscriber misses events rather than holding up the monitor or the
+//! other subscribers.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use crate::models::{Alert, Endpoint, ProbeRecord};
+use crate::score_snapshot::ScoreSnapshot;
+
+/// Kind of a [`MonitoringEvent`], without its data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    /// [`MonitoringEvent::ProbeCompleted`]
+    ProbeCompleted,
+    /// [`MonitoringEvent::ScoreUpdated`]
+    ScoreUpdated,
+    /// [`MonitoringEvent::HealthChanged`]
+    HealthChanged,
+    /// [`MonitoringEvent::AlertRaised`]
+    AlertRaised,
+    /// [`MonitoringEvent::AlertResolved`]
+    AlertResolved,
+    /// [`MonitoringEvent::EndpointAdded`]
+    EndpointAdded,
+    /// [`MonitoringEvent::EndpointRemoved`]
+    EndpointRemoved,
+}
+
+/// Something the monitor observed
+#[derive(Debug, Clone)]
+pub enum MonitoringEvent {
+    /// A probe finished, successfully or not
+    ProbeCompleted(ProbeRecord),
+    /// Scores exported at the metrics export interval
+    ScoreUpdated(Arc<ScoreSnapshot>),
+    /// An endpoint started or stopped meeting an alert condition
+    HealthChanged {
+        /// Endpoint whose health changed
+        endpoint_id: String,
+        /// Whether any alert condition now holds for the endpoint
+        degraded: bool,
+        /// Time of the probe that changed it
+        at: DateTime<Utc>,
+    },
+    /// An alert was delivered after correlation; alerts grouped into an
+    /// incident are delivered with the incident instead
+    AlertRaised(Alert),
+    /// The condition of an earlier alert no longer holds
+    AlertResolved {
+        /// Endpoint the alert was raised for
+        endpoint_id: String,
+        /// [`AlertType::kind`](crate::models::AlertType::kind) of the alert
+        kind: &'static str,
+        /// Time of the probe that found the condition cleared
+        at: DateTime<Utc>,
+    },
+    /// An endpoint was added or replaced
+    EndpointAdded(Endpoint),
+    /// An endpoint was removed
+    EndpointRemoved {
+        /// ID of the removed endpoint
+        endpoint_id: String,
+    },
+}
+
+impl MonitoringEvent {
+    /// Kind of the event
+    #[must_use]
+    pub const fn kind(&self) -> EventKind {
+        match self {
+            Self::ProbeCompleted(_) => EventKind::ProbeCompleted,
+            Self::ScoreUpdated(_) => EventKind::ScoreUpdated,
+            Self::HealthChanged { .. } => EventKind::HealthChanged,
+            Self::AlertRaised(_) => EventKind::AlertRaised,
+            Self::AlertResolved { .. } => EventKind::AlertResolved,
+            Self::EndpointAdded(_) => EventKind::EndpointAdded,
+            Self::EndpointRemoved { .. } => EventKind::EndpointRemoved,
+        }
+    }
+
+    /// Endpoint the event is about; `None` for scores, which cover every endpoint
+    #[must_use]
+    pub fn endpoint_id(&self) -> Option<&str> {
+        match self {
+            Self::ProbeCompleted(record) => Some(&record.endpoint_id),
+            Self::ScoreUpdated(_) => None,
+            Self::HealthChanged { endpoint_id, .. }
+            | Self::AlertResolved { endpoint_id, .. }
+            | Self::EndpointRemoved { endpoint_id } => Some(endpoint_id),
+            Self::AlertRaised(alert) => Some(&alert.endpoint_id),
+            Self::EndpointAdded(endpoint) => Some(&endpoint.id),
+        }
+    }
+}
+
+/// Events a subscriber receives; every event unless narrowed down
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    kinds: Option<HashSet<EventKind>>,
+    endpoints: Option<HashSet<String>>,
+}
+
+impl EventFilter {
+    /// Every event
+    #[must_use]
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Only events of `kinds`
+    #[must_use]
+    pub fn kinds(mut self, kinds: impl IntoIterator<Item = EventKind>) -> Self {
+        self.kinds = Some(kinds.into_iter().collect());
+        self
+    }
+
+    /// Only events about `endpoints`, and scores, which cover every endpoint
+    #[must_use]
+    pub fn endpoints(mut self, endpoints: impl IntoIterator<Item = String>) -> Self {
+        self.endpoints = Some(endpoints.into_iter().collect());
+        self
+    }
+
+    /// Whether events of `kind` may pass
+    #[must_use]
+    pub fn wants(&self, kind: EventKind) -> bool {
+        self.kinds.as_ref().map_or(true, |kinds| kinds.contains(&kind))
+    }
+
+    /// Whether `event` passes
+    #[must_use]
+    pub fn matches(&self, event: &MonitoringEvent) -> bool {
+        self.wants(event.kind())
+            && match (&self.endpoints, event.endpoint_id()) {
+                (Some(endpoints), Some(id)) => endpoints.contains(id),
+                _ => true,
+            }
+    }
+}
+
+struct Subscriber {
+    filter: EventFilter,
+    sender: mpsc::Sender<MonitoringEvent>,
+    missed: Arc<AtomicU64>,
+}
+
+/// Bus every monitoring event is published on
+pub struct EventBus {
+    subscribers: Mutex<Vec<Subscriber>>,
+    capacity: usize,
+}
+
+impl EventBus {
+    /// Bus queueing up to `capacity` events per subscriber
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            subscribers: Mutex::new(Vec::new()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Receive the events passing `filter` from now on
+    pub fn subscribe(&self, filter: EventFilter) -> EventSubscription {
+        let (sender, receiver) = mpsc::channel(self.capacity);
+        let missed = Arc::new(AtomicU64::new(0));
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Subscriber {
+                filter,
+                sender,
+                missed: Arc::clone(&missed),
+            });
+        EventSubscription { receiver, missed }
+    }
+
+    /// Whether any subscriber wants events of `kind`, so events nobody
+    /// receives need not be made
+    pub fn wants(&self, kind: EventKind) -> bool {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .any(|subscriber| subscriber.filter.wants(kind))
+    }
+
+    /// Hand `event` to every subscriber whose filter it passes
+    ///
+    /// Never waits: a subscriber whose queue is full misses the event, and
+    /// subscribers that were dropped are forgotten.
+    pub fn publish(&self, event: &MonitoringEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(PoisonError::into_inner);
+        subscribers.retain(|subscriber| {
+            if !subscriber.filter.matches(event) {
+                return !subscriber.sender.is_closed();
+            }
+            match subscriber.sender.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    subscriber.missed.fetch_add(1, Ordering::Relaxed);
+                    true
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => false,
+            }
+        });
+    }
+
+    /// Number of subscribers
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+}
+
+/// Events of one subscriber, in the order they were published
+pub struct EventSubscription {
+    receiver: mpsc::Receiver<MonitoringEvent>,
+    missed: Arc<AtomicU64>,
+}
+
+impl EventSubscription {
+    /// Next event; `None` once the bus is gone
+    ///
+    /// Events missed because the queue was full are logged.
+    pub async fn recv(&mut self) -> Option<MonitoringEvent> {
+        let event = self.receiver.recv().await;
+        let missed = self.missed.swap(0, Ordering::Relaxed);
+        if missed > 0 {
+            warn!("Event subscriber fell behind, missed {} events", missed);
+        }
+        event
+    }
+
+    /// Next event if one is waiting
+    pub fn try_recv(&mut self) -> Option<MonitoringEvent> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscribers_receive_the_events_they_filter_for() {
+        let bus = EventBus::new(2);
+        let mut api = bus.subscribe(
+            EventFilter::all()
+                .kinds([EventKind::ProbeCompleted, EventKind::EndpointRemoved])
+                .endpoints(["api".to_string()]),
+        );
+        let mut everything = bus.subscribe(EventFilter::all());
+        assert!(bus.wants(EventKind::ScoreUpdated));
+        assert_eq!(bus.subscriber_count(), 2);
+
+        bus.publish(&MonitoringEvent::ProbeCompleted(ProbeRecord::success("db".to_string(), 5.0)));
+        bus.publish(&MonitoringEvent::ProbeCompleted(ProbeRecord::success("api".to_string(), 5.0)));
+        bus.publish(&MonitoringEvent::EndpointRemoved { endpoint_id: "api".to_string() });
+
+        let event = api.recv().await.unwrap();
+        assert!(matches!(event, MonitoringEvent::ProbeCompleted(record) if record.endpoint_id == "api"));
+        assert_eq!(api.recv().await.unwrap().kind(), EventKind::EndpointRemoved);
+        assert!(api.try_recv().is_none());
+
+        // The full queue kept the first two events and missed the third
+        assert_eq!(everything.try_recv().unwrap().endpoint_id(), Some("db"));
+        assert_eq!(everything.try_recv().unwrap().endpoint_id(), Some("api"));
+        assert!(everything.try_recv().is_none());
+        assert_eq!(everything.missed.load(Ordering::Relaxed), 1);
+
+        // Dropped subscribers are forgotten on the next publish
+        drop(everything);
+        drop(api);
+        bus.publish(&MonitoringEvent::ScoreUpdated(Arc::default()));
+        assert_eq!(bus.subscriber_count(), 0);
+        assert!(!bus.wants(EventKind::ScoreUpdated));
+    }
+}
@@ -0,0 +1,59 @@
+//! JSON feeds of region data
+//!
+//! Pricing and carbon intensity can both be fetched from a URL that serves a
+//! JSON object keyed by region. [`FeedClient`] downloads and parses such a
+//! document with the app's timeout, user agent and TLS settings; what the
+//! document holds is up to the caller.
+
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use tracing::debug;
+
+use crate::config::AppConfig;
+use crate::error::{CloudPingError, Result};
+use crate::tls::CertValidations;
+use crate::transport::ReqwestTransport;
+
+/// Fetches a JSON document from a feed URL
+#[derive(Debug, Clone)]
+pub struct FeedClient {
+    client: Client,
+    url: String,
+}
+
+impl FeedClient {
+    /// Create a client for `url` using the app's timeout, user agent and TLS settings
+    ///
+    /// # Errors
+    /// Returns an error when the client certificate cannot be read or the client cannot be built
+    pub fn new(config: &AppConfig, url: &str) -> Result<Self> {
+        let client = ReqwestTransport::client_builder(config, &CertValidations::default())?
+            .timeout(config.get_timeout())
+            .build()?;
+
+        Ok(Self {
+            client,
+            url: url.to_string(),
+        })
+    }
+
+    /// Fetch the document with `GET {url}` and parse it as `T`; `what` names
+    /// the feed in errors
+    ///
+    /// # Errors
+    /// Returns an error when the request fails, the server answers with an
+    /// error status or the body is not a valid document
+    pub async fn fetch<T: DeserializeOwned>(&self, what: &str) -> Result<T> {
+        debug!("Fetching {} from {}", what, self.url);
+        let body = self
+            .client
+            .get(&self.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        serde_json::from_str(&body)
+            .map_err(|e| CloudPingError::data_loading(format!("Invalid {what} from {}: {e}", self.url)))
+    }
+}
@@ -0,0 +1,170 @@
+//! IP geolocation inference of region coordinates
+//!
+//! Regions without `coordinates` in the data file cannot take part in
+//! distance-based features such as measurement quality checks and the
+//! region-to-region matrix. With a `geoip_file` set, the host of each such
+//! region is resolved and located in a city geolocation database (an MMDB
+//! file such as `GeoLite2-City.mmdb`) when regions are loaded. Inferred
+//! coordinates are marked with the `coordinates_source` metadata key.
+//!
+//! Anycast and CDN addresses locate to wherever the database places the
+//! network, often the operator's headquarters, so inferred coordinates are
+//! only as good as the endpoint is tied to its region.
+
+use std::net::IpAddr;
+use std::path::Path;
+
+use futures::future::join_all;
+use tracing::{debug, info};
+
+use crate::error::Result;
+use crate::mmdb::{MmdbReader, MmdbValue};
+use crate::models::{CloudProvider, Coordinates};
+use crate::network::NetworkTester;
+
+/// Region metadata key set to `geoip` on regions whose coordinates were inferred
+pub const COORDINATES_SOURCE_METADATA_KEY: &str = "coordinates_source";
+
+/// Locates addresses with a city geolocation database
+#[derive(Debug, Clone)]
+pub struct GeoIpLocator {
+    reader: MmdbReader,
+}
+
+impl GeoIpLocator {
+    /// Open a city geolocation database
+    ///
+    /// # Errors
+    /// Returns an error when the file cannot be read or is not an MMDB file
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self::new(MmdbReader::open(path)?))
+    }
+
+    /// Locate addresses with an already opened database
+    #[must_use]
+    pub const fn new(reader: MmdbReader) -> Self {
+        Self { reader }
+    }
+
+    /// Coordinates of `ip` from the `location` of its record, `None` when the
+    /// database has no location for it
+    #[must_use]
+    pub fn locate(&self, ip: IpAddr) -> Option<Coordinates> {
+        let record = self.reader.lookup(ip)?;
+        let coordinate = |key| {
+            record
+                .value
+                .get_path(&["location", key])
+                .and_then(MmdbValue::as_f64)
+        };
+        Coordinates::new(coordinate("latitude")?, coordinate("longitude")?).ok()
+    }
+
+    /// ISO 3166-1 alpha-2 code of the country of `ip`, `None` when the
+    /// database has no country for it
+    #[must_use]
+    pub fn country(&self, ip: IpAddr) -> Option<String> {
+        let record = self.reader.lookup(ip)?;
+        record
+            .value
+            .get_path(&["country", "iso_code"])
+            .and_then(MmdbValue::as_str)
+            .map(str::to_string)
+    }
+
+    /// Set the coordinates of regions that have none to the location of
+    /// their host, and the country of regions without one to the host's,
+    /// returning how many regions were given coordinates
+    ///
+    /// Hosts are resolved concurrently; regions whose host does not resolve
+    /// or is not in the database keep what they have.
+    pub async fn fill_missing_coordinates(&self, providers: &mut [CloudProvider]) -> usize {
+        let missing: Vec<(usize, usize)> = providers
+            .iter()
+            .enumerate()
+            .flat_map(|(p, provider)| {
+                provider
+                    .regions
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, region)| region.coordinates.is_none() || region.country.is_empty())
+                    .map(move |(r, _)| (p, r))
+            })
+            .collect();
+        let addresses = join_all(
+            missing
+                .iter()
+                .map(|&(p, r)| NetworkTester::resolve_host(&providers[p].regions[r].url)),
+        )
+        .await;
+
+        let mut located = 0;
+        for ((p, r), address) in missing.into_iter().zip(addresses) {
+            let region = &mut providers[p].regions[r];
+            let Ok(ip) = address else {
+                debug!("No GeoIP location for {}", region.name);
+                continue;
+            };
+            if region.country.is_empty() {
+                if let Some(country) = self.country(ip) {
+                    region.country = country;
+                }
+            }
+            if region.coordinates.is_some() {
+                continue;
+            }
+            let Some(coordinates) = self.locate(ip) else {
+                debug!("No GeoIP location for {}", region.name);
+                continue;
+            };
+            region.coordinates = Some(coordinates);
+            region
+                .metadata
+                .insert(COORDINATES_SOURCE_METADATA_KEY.to_string(), "geoip".to_string());
+            located += 1;
+        }
+        info!("Inferred coordinates of {located} regions from GeoIP");
+        located
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mmdb::testing::{database, double, map, string};
+    use crate::models::Region;
+
+    #[tokio::test]
+    async fn test_fill_missing_coordinates() {
+        let locator = GeoIpLocator::new(
+            MmdbReader::from_bytes(database(&map(&[(
+                "location",
+                map(&[("latitude", double(50.11)), ("longitude", double(8.68))]),
+            ), ("country", map(&[("iso_code", string("DE"))]))])))
+            .unwrap(),
+        );
+        let region = |name: &str, url: &str| {
+            Region::builder(name.to_string(), url.to_string())
+                .unwrap()
+                .build()
+                .unwrap()
+        };
+        let mut provider = CloudProvider::new("Example".to_string()).unwrap();
+        provider.regions = vec![
+            region("frankfurt", "http://10.0.0.1/ping"),
+            region("elsewhere", "http://192.0.2.1/ping"),
+        ];
+        let mut providers = vec![provider];
+
+        assert_eq!(locator.fill_missing_coordinates(&mut providers).await, 1);
+        let frankfurt = &providers[0].regions[0];
+        assert_eq!(frankfurt.coordinates.as_ref().map(|c| c.latitude), Some(50.11));
+        assert_eq!(
+            frankfurt.metadata.get(COORDINATES_SOURCE_METADATA_KEY).map(String::as_str),
+            Some("geoip")
+        );
+        assert!(providers[0].regions[1].coordinates.is_none());
+        assert_eq!(frankfurt.country, "DE");
+        assert_eq!(providers[0].regions[1].country, "");
+    }
+}
@@ -0,0 +1,897 @@
+//! gRPC control plane for running cloud-ping as a remote agent
+//!
+//! Exposes `StartBenchmark`, `StreamMetrics` and `ListEndpoints` so a central
+//! collector can orchestrate agents, and `CreateEndpoint`, `UpdateEndpoint`
+//! and `DeleteEndpoint` so it can change what they monitor. The wire contract is documented in
+//! `proto/control_plane.proto`; message types are maintained by hand so the
+//! build does not depend on `protoc`.
+//!
+//! With `[[api.tokens]]` configured, every call needs a token in the
+//! `authorization: Bearer <token>` metadata, checked as the HTTP API checks
+//! it. Read-only tokens may stream metrics and list endpoints; running
+//! benchmarks and changing endpoints take an admin token. `[api.tls]` serves
+//! the control plane over TLS with the HTTP API's certificate; without it,
+//! tokens are only accepted on a loopback address.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+use tokio::sync::Mutex;
+use tonic::body::BoxBody;
+use tonic::codegen::{empty_body, http, Body, BoxFuture, Service, StdError};
+use tonic::server::{Grpc, NamedService, ServerStreamingService, UnaryService};
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::{Identity, ServerTlsConfig};
+use tonic::{Request, Response, Status};
+use tracing::{debug, info};
+
+use crate::benchmark::ConnectionBenchmark;
+use crate::endpoint_store::{EndpointChanges, EndpointSpec};
+use crate::error::{CloudPingError, Result};
+use crate::events::{EventFilter, EventKind, MonitoringEvent};
+use crate::models::{ComprehensiveScoreResult, Endpoint, ScoringAdapter};
+use crate::monitoring::NetworkMonitoringSystem;
+use crate::self_metrics::SelfMetricsSnapshot;
+use crate::server::{find_token, ApiScope, ApiSettings, ApiTlsSettings, ApiToken};
+use crate::time_utils::TimeUtils;
+
+/// Request to run a benchmark on the agent
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct StartBenchmarkRequest {
+    /// Number of pings per region (0 uses the agent's configured default)
+    #[prost(uint32, tag = "1")]
+    pub ping_count: u32,
+    /// Optional provider name filter
+    #[prost(string, optional, tag = "2")]
+    pub provider_filter: Option<String>,
+    /// Optional region name filter
+    #[prost(string, optional, tag = "3")]
+    pub region_filter: Option<String>,
+}
+
+/// Scored benchmark result for a single region
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RegionResult {
+    /// Region display name
+    #[prost(string, tag = "1")]
+    pub name: String,
+    /// Region identifier
+    #[prost(string, tag = "2")]
+    pub region_id: String,
+    /// Average latency in milliseconds
+    #[prost(double, tag = "3")]
+    pub avg_ms: f64,
+    /// Jitter in milliseconds
+    #[prost(double, tag = "4")]
+    pub jitter_ms: f64,
+    /// Packet loss percentage
+    #[prost(double, tag = "5")]
+    pub packet_loss: f64,
+    /// Overall score (0-100)
+    #[prost(double, tag = "6")]
+    pub score: f64,
+    /// Letter grade
+    #[prost(string, tag = "7")]
+    pub grade: String,
+}
+
+/// Benchmark results returned by `StartBenchmark`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StartBenchmarkResponse {
+    /// Per-region results
+    #[prost(message, repeated, tag = "1")]
+    pub results: Vec<RegionResult>,
+}
+
+/// Request to subscribe to metrics updates
+#[derive(Clone, Copy, PartialEq, Eq, prost::Message)]
+pub struct StreamMetricsRequest {}
+
+/// Current score for a monitored endpoint
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EndpointScore {
+    /// Endpoint identifier
+    #[prost(string, tag = "1")]
+    pub endpoint_id: String,
+    /// Overall score (0-100)
+    #[prost(double, tag = "2")]
+    pub score: f64,
+    /// Letter grade
+    #[prost(string, tag = "3")]
+    pub grade: String,
+}
+
+/// Health of the agent's own monitoring tasks
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MonitorHealth {
+    /// Probes started
+    #[prost(uint64, tag = "1")]
+    pub probes_started: u64,
+    /// Average time probes started after they were due, in milliseconds
+    #[prost(double, tag = "2")]
+    pub scheduling_lag_avg_ms: f64,
+    /// Recent longest time a probe started after it was due, in milliseconds
+    #[prost(double, tag = "3")]
+    pub scheduling_lag_max_ms: f64,
+    /// Probe records waiting to leave the probe loops
+    #[prost(uint64, tag = "4")]
+    pub probe_queue: u64,
+    /// Probe records waiting for the aggregator
+    #[prost(uint64, tag = "5")]
+    pub aggregator_queue: u64,
+    /// Probe records waiting for the probe sink
+    #[prost(uint64, tag = "6")]
+    pub sink_queue: u64,
+    /// Alerts waiting for correlation
+    #[prost(uint64, tag = "7")]
+    pub alert_queue: u64,
+    /// Probe records lost on the way to the probe sink
+    #[prost(uint64, tag = "8")]
+    pub dropped_records: u64,
+    /// Monitoring tasks that panicked
+    #[prost(uint64, tag = "9")]
+    pub task_panics: u64,
+    /// Probe records the aggregator processed
+    #[prost(uint64, tag = "10")]
+    pub records_aggregated: u64,
+    /// Average aggregator time per record, in milliseconds
+    #[prost(double, tag = "11")]
+    pub aggregator_latency_avg_ms: f64,
+    /// Recent longest aggregator time for one record, in milliseconds
+    #[prost(double, tag = "12")]
+    pub aggregator_latency_max_ms: f64,
+    /// Memory held by the aggregator's ring buffers, in bytes
+    #[prost(uint64, tag = "13")]
+    pub ring_buffer_bytes: u64,
+}
+
+impl From<SelfMetricsSnapshot> for MonitorHealth {
+    fn from(health: SelfMetricsSnapshot) -> Self {
+        Self {
+            probes_started: health.probes_started,
+            scheduling_lag_avg_ms: health.scheduling_lag_avg_ms,
+            scheduling_lag_max_ms: health.scheduling_lag_max_ms,
+            probe_queue: health.probe_queue,
+            aggregator_queue: health.aggregator_queue,
+            sink_queue: health.sink_queue,
+            alert_queue: health.alert_queue,
+            dropped_records: health.dropped_records,
+            task_panics: health.task_panics,
+            records_aggregated: health.records_aggregated,
+            aggregator_latency_avg_ms: health.aggregator_latency_avg_ms,
+            aggregator_latency_max_ms: health.aggregator_latency_max_ms,
+            ring_buffer_bytes: health.ring_buffer_bytes,
+        }
+    }
+}
+
+/// Snapshot of endpoint scores pushed on the metrics stream
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MetricsUpdate {
+    /// Snapshot time in milliseconds since the Unix epoch
+    #[prost(int64, tag = "1")]
+    pub timestamp_unix_ms: i64,
+    /// Scores for all endpoints in the snapshot
+    #[prost(message, repeated, tag = "2")]
+    pub scores: Vec<EndpointScore>,
+    /// Health of the agent's own monitoring tasks
+    #[prost(message, optional, tag = "3")]
+    pub health: Option<MonitorHealth>,
+}
+
+/// Request to list monitored endpoints
+#[derive(Clone, Copy, PartialEq, Eq, prost::Message)]
+pub struct ListEndpointsRequest {}
+
+/// Monitored endpoint description
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct EndpointInfo {
+    /// Endpoint identifier
+    #[prost(string, tag = "1")]
+    pub id: String,
+    /// Target host
+    #[prost(string, tag = "2")]
+    pub host: String,
+    /// Target port
+    #[prost(uint32, tag = "3")]
+    pub port: u32,
+    /// Probe type (TCP, HTTP, ICMP)
+    #[prost(string, tag = "4")]
+    pub probe_type: String,
+    /// Whether the endpoint is probed
+    #[prost(bool, tag = "5")]
+    pub enabled: bool,
+}
+
+impl From<Endpoint> for EndpointInfo {
+    fn from(endpoint: Endpoint) -> Self {
+        Self {
+            id: endpoint.id,
+            host: endpoint.host,
+            port: u32::from(endpoint.port),
+            probe_type: format!("{:?}", endpoint.probe_type),
+            enabled: endpoint.enabled,
+        }
+    }
+}
+
+/// Endpoints returned by `ListEndpoints`
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct ListEndpointsResponse {
+    /// All monitored endpoints
+    #[prost(message, repeated, tag = "1")]
+    pub endpoints: Vec<EndpointInfo>,
+}
+
+/// Request to start monitoring an endpoint
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct CreateEndpointRequest {
+    /// URL probed by its scheme
+    #[prost(string, tag = "1")]
+    pub url: String,
+    /// Endpoint identifier (defaults to the URL)
+    #[prost(string, optional, tag = "2")]
+    pub id: Option<String>,
+    /// Display name (defaults to the URL)
+    #[prost(string, optional, tag = "3")]
+    pub name: Option<String>,
+    /// Whether the endpoint is probed (defaults to true)
+    #[prost(bool, optional, tag = "4")]
+    pub enabled: Option<bool>,
+}
+
+/// Request to change a monitored endpoint; unset fields are kept
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct UpdateEndpointRequest {
+    /// Endpoint identifier
+    #[prost(string, tag = "1")]
+    pub id: String,
+    /// URL to probe instead
+    #[prost(string, optional, tag = "2")]
+    pub url: Option<String>,
+    /// New display name
+    #[prost(string, optional, tag = "3")]
+    pub name: Option<String>,
+    /// Resume or pause probing
+    #[prost(bool, optional, tag = "4")]
+    pub enabled: Option<bool>,
+}
+
+/// Endpoint returned by `CreateEndpoint` and `UpdateEndpoint`
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct EndpointResponse {
+    /// The endpoint as now monitored
+    #[prost(message, optional, tag = "1")]
+    pub endpoint: Option<EndpointInfo>,
+}
+
+/// Request to stop monitoring an endpoint
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct DeleteEndpointRequest {
+    /// Endpoint identifier
+    #[prost(string, tag = "1")]
+    pub id: String,
+}
+
+/// Empty reply to `DeleteEndpoint`
+#[derive(Clone, Copy, PartialEq, Eq, prost::Message)]
+pub struct DeleteEndpointResponse {}
+
+/// Stream type returned by `StreamMetrics`
+pub type MetricsStream =
+    Pin<Box<dyn Stream<Item = std::result::Result<MetricsUpdate, Status>> + Send>>;
+
+/// Control-plane handlers backed by a benchmark and a monitoring system
+pub struct ControlPlaneService {
+    benchmark: Mutex<ConnectionBenchmark>,
+    monitoring: Arc<NetworkMonitoringSystem>,
+    tokens: Arc<[ApiToken]>,
+    tls: Option<ServerTlsConfig>,
+}
+
+impl ControlPlaneService {
+    /// Create a service that runs benchmarks and reports monitoring data
+    #[must_use]
+    pub fn new(benchmark: ConnectionBenchmark, monitoring: Arc<NetworkMonitoringSystem>) -> Self {
+        Self {
+            benchmark: Mutex::new(benchmark),
+            monitoring,
+            tokens: Arc::from([]),
+            tls: None,
+        }
+    }
+
+    /// Require the tokens of `settings` and serve with its certificate
+    ///
+    /// # Errors
+    /// Returns an error if the certificate or key cannot be loaded
+    pub fn access(mut self, settings: &ApiSettings) -> Result<Self> {
+        self.tokens = Arc::from(settings.tokens.as_slice());
+        self.tls = settings.tls.as_ref().map(server_tls_config).transpose()?;
+        Ok(self)
+    }
+
+    /// Handle `StartBenchmark`
+    pub async fn start_benchmark(
+        &self,
+        request: Request<StartBenchmarkRequest>,
+    ) -> std::result::Result<Response<StartBenchmarkResponse>, Status> {
+        require_admin(&request)?;
+        let req = request.into_inner();
+        let (results, weights) = {
+            let mut benchmark = self.benchmark.lock().await;
+            let ping_count = if req.ping_count == 0 {
+                benchmark.config().default_ping_count
+            } else {
+                req.ping_count as usize
+            };
+
+            info!("Remote StartBenchmark with {} pings per region", ping_count);
+
+            let results = benchmark
+                .run_filtered_benchmark(ping_count, req.provider_filter, req.region_filter)
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
+            (results, benchmark.weights().clone())
+        };
+
+        let results = results
+            .iter()
+            .map(|(name, stats)| {
+                let score = ScoringAdapter::score_ping_stats(stats, &weights, name);
+                RegionResult {
+                    name: name.clone(),
+                    region_id: stats.region_id.clone().unwrap_or_default(),
+                    avg_ms: stats.avg,
+                    jitter_ms: stats.jitter,
+                    packet_loss: stats.packet_loss,
+                    score: score.score,
+                    grade: score.grade.to_string(),
+                }
+            })
+            .collect();
+
+        Ok(Response::new(StartBenchmarkResponse { results }))
+    }
+
+    /// Handle `StreamMetrics`
+    pub fn stream_metrics(
+        &self,
+        _request: Request<StreamMetricsRequest>,
+    ) -> Response<MetricsStream> {
+        let receiver = self
+            .monitoring
+            .subscribe(EventFilter::all().kinds([EventKind::ScoreUpdated]));
+        let monitoring = Arc::clone(&self.monitoring);
+
+        let stream = futures::stream::unfold((receiver, monitoring), |(mut receiver, monitoring)| async move {
+            loop {
+                if let MonitoringEvent::ScoreUpdated(snapshot) = receiver.recv().await? {
+                    let update = Self::metrics_update(&snapshot.scores, monitoring.self_metrics());
+                    return Some((Ok(update), (receiver, monitoring)));
+                }
+            }
+        });
+
+        Response::new(Box::pin(stream))
+    }
+
+    /// Handle `ListEndpoints`
+    pub async fn list_endpoints(
+        &self,
+        _request: Request<ListEndpointsRequest>,
+    ) -> std::result::Result<Response<ListEndpointsResponse>, Status> {
+        let endpoints = self
+            .monitoring
+            .get_endpoints()
+            .await
+            .into_iter()
+            .map(EndpointInfo::from)
+            .collect();
+
+        Ok(Response::new(ListEndpointsResponse { endpoints }))
+    }
+
+    /// Handle `CreateEndpoint`
+    pub async fn create_endpoint(
+        &self,
+        request: Request<CreateEndpointRequest>,
+    ) -> std::result::Result<Response<EndpointResponse>, Status> {
+        require_admin(&request)?;
+        let req = request.into_inner();
+        let spec = EndpointSpec {
+            url: req.url,
+            id: req.id,
+            name: req.name,
+            enabled: req.enabled.unwrap_or(true),
+        };
+        match self.monitoring.create_endpoint(&spec).await {
+            Ok(Some(endpoint)) => Ok(Response::new(EndpointResponse {
+                endpoint: Some(endpoint.into()),
+            })),
+            Ok(None) => Err(Status::already_exists("an endpoint of this ID is already monitored")),
+            Err(e) => Err(endpoint_status(&e)),
+        }
+    }
+
+    /// Handle `UpdateEndpoint`
+    pub async fn update_endpoint(
+        &self,
+        request: Request<UpdateEndpointRequest>,
+    ) -> std::result::Result<Response<EndpointResponse>, Status> {
+        require_admin(&request)?;
+        let req = request.into_inner();
+        let changes = EndpointChanges {
+            url: req.url,
+            name: req.name,
+            enabled: req.enabled,
+        };
+        match self.monitoring.update_endpoint(&req.id, &changes).await {
+            Ok(Some(endpoint)) => Ok(Response::new(EndpointResponse {
+                endpoint: Some(endpoint.into()),
+            })),
+            Ok(None) => Err(Status::not_found(format!("no endpoint `{}`", req.id))),
+            Err(e) => Err(endpoint_status(&e)),
+        }
+    }
+
+    /// Handle `DeleteEndpoint`
+    pub async fn delete_endpoint(
+        &self,
+        request: Request<DeleteEndpointRequest>,
+    ) -> std::result::Result<Response<DeleteEndpointResponse>, Status> {
+        require_admin(&request)?;
+        let id = request.into_inner().id;
+        match self.monitoring.delete_endpoint(&id).await {
+            Ok(true) => Ok(Response::new(DeleteEndpointResponse {})),
+            Ok(false) => Err(Status::not_found(format!("no endpoint `{id}`"))),
+            Err(e) => Err(endpoint_status(&e)),
+        }
+    }
+
+    fn metrics_update(
+        metrics: &HashMap<String, ComprehensiveScoreResult>,
+        health: SelfMetricsSnapshot,
+    ) -> MetricsUpdate {
+        MetricsUpdate {
+            timestamp_unix_ms: TimeUtils::now().timestamp_millis(),
+            scores: metrics
+                .iter()
+                .map(|(endpoint_id, result)| EndpointScore {
+                    endpoint_id: endpoint_id.clone(),
+                    score: result.score,
+                    grade: result.grade.to_string(),
+                })
+                .collect(),
+            health: Some(health.into()),
+        }
+    }
+
+    /// Serve the control plane on the given address until the server stops
+    ///
+    /// # Errors
+    /// Returns an error if tokens are required on a plaintext listener that
+    /// is not a loopback address, since they would cross the network readable
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        let mut builder = tonic::transport::Server::builder();
+        if let Some(tls) = self.tls.clone() {
+            info!("Starting gRPC control plane on {} with TLS", addr);
+            builder = builder
+                .tls_config(tls)
+                .map_err(|e| CloudPingError::config(format!("Invalid gRPC TLS configuration: {e}")))?;
+        } else if !self.tokens.is_empty() && !addr.ip().is_loopback() {
+            return Err(CloudPingError::config(format!(
+                "Refusing to accept API tokens in plain text on {addr}; set [api.tls] or listen on a loopback address"
+            )));
+        } else {
+            info!("Starting gRPC control plane on {}", addr);
+        }
+
+        let interceptor = TokenInterceptor {
+            tokens: Arc::clone(&self.tokens),
+        };
+        builder
+            .add_service(InterceptedService::new(ControlPlaneServer::new(self), interceptor))
+            .serve(addr)
+            .await
+            .map_err(|e| CloudPingError::system(format!("gRPC server failed: {e}")))
+    }
+}
+
+/// TLS configuration serving the certificate of `[api.tls]`
+///
+/// The certificate and key are first loaded as the HTTP API loads them, so
+/// both report the same errors.
+fn server_tls_config(tls: &ApiTlsSettings) -> Result<ServerTlsConfig> {
+    crate::tls::server_config(&tls.cert, &tls.key)?;
+    let read = |path: &std::path::Path| {
+        std::fs::read(path).map_err(|e| CloudPingError::config(format!("Cannot read {}: {e}", path.display())))
+    };
+    Ok(ServerTlsConfig::new().identity(Identity::from_pem(read(&tls.cert)?, read(&tls.key)?)))
+}
+
+/// Interceptor checking bearer tokens as the HTTP API does
+///
+/// The interceptor cannot see which method is called, so it stamps the
+/// request with its token's [`ApiScope`] and [`require_admin`] checks it in
+/// the handlers that change the agent. Without configured tokens every
+/// caller is an admin.
+#[derive(Clone)]
+pub struct TokenInterceptor {
+    tokens: Arc<[ApiToken]>,
+}
+
+impl Interceptor for TokenInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> std::result::Result<Request<()>, Status> {
+        let scope = if self.tokens.is_empty() {
+            ApiScope::Admin
+        } else {
+            let token = request
+                .metadata()
+                .get("authorization")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .and_then(|presented| find_token(&self.tokens, presented.trim()))
+                .ok_or_else(|| Status::unauthenticated("missing or unknown API token"))?;
+            debug!("gRPC call with token {}", token.name);
+            token.scope
+        };
+        request.extensions_mut().insert(scope);
+        Ok(request)
+    }
+}
+
+/// Reject calls whose token may not change the agent
+#[allow(clippy::result_large_err)] // handlers return `Status` as tonic wants
+fn require_admin<T>(request: &Request<T>) -> std::result::Result<(), Status> {
+    match request.extensions().get::<ApiScope>() {
+        Some(ApiScope::Admin) => Ok(()),
+        _ => Err(Status::permission_denied("an admin token is required")),
+    }
+}
+
+/// Status of a failed endpoint change
+fn endpoint_status(error: &CloudPingError) -> Status {
+    match error {
+        CloudPingError::InvalidUrl { .. } | CloudPingError::Validation { .. } => {
+            Status::invalid_argument(error.to_string())
+        }
+        _ => Status::internal(error.to_string()),
+    }
+}
+
+/// tonic service wrapper routing gRPC paths to [`ControlPlaneService`]
+#[derive(Clone)]
+pub struct ControlPlaneServer {
+    inner: Arc<ControlPlaneService>,
+}
+
+impl ControlPlaneServer {
+    /// Wrap a control-plane service for registration with a tonic server
+    #[must_use]
+    pub fn new(service: ControlPlaneService) -> Self {
+        Self {
+            inner: Arc::new(service),
+        }
+    }
+}
+
+impl NamedService for ControlPlaneServer {
+    const NAME: &'static str = "cloudping.v1.ControlPlane";
+}
+
+struct StartBenchmarkSvc(Arc<ControlPlaneService>);
+
+impl UnaryService<StartBenchmarkRequest> for StartBenchmarkSvc {
+    type Response = StartBenchmarkResponse;
+    type Future = BoxFuture<Response<Self::Response>, Status>;
+
+    fn call(&mut self, request: Request<StartBenchmarkRequest>) -> Self::Future {
+        let inner = Arc::clone(&self.0);
+        Box::pin(async move { inner.start_benchmark(request).await })
+    }
+}
+
+struct StreamMetricsSvc(Arc<ControlPlaneService>);
+
+impl ServerStreamingService<StreamMetricsRequest> for StreamMetricsSvc {
+    type Response = MetricsUpdate;
+    type ResponseStream = MetricsStream;
+    type Future = BoxFuture<Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: Request<StreamMetricsRequest>) -> Self::Future {
+        let inner = Arc::clone(&self.0);
+        Box::pin(async move { Ok(inner.stream_metrics(request)) })
+    }
+}
+
+struct ListEndpointsSvc(Arc<ControlPlaneService>);
+
+impl UnaryService<ListEndpointsRequest> for ListEndpointsSvc {
+    type Response = ListEndpointsResponse;
+    type Future = BoxFuture<Response<Self::Response>, Status>;
+
+    fn call(&mut self, request: Request<ListEndpointsRequest>) -> Self::Future {
+        let inner = Arc::clone(&self.0);
+        Box::pin(async move { inner.list_endpoints(request).await })
+    }
+}
+
+struct CreateEndpointSvc(Arc<ControlPlaneService>);
+
+impl UnaryService<CreateEndpointRequest> for CreateEndpointSvc {
+    type Response = EndpointResponse;
+    type Future = BoxFuture<Response<Self::Response>, Status>;
+
+    fn call(&mut self, request: Request<CreateEndpointRequest>) -> Self::Future {
+        let inner = Arc::clone(&self.0);
+        Box::pin(async move { inner.create_endpoint(request).await })
+    }
+}
+
+struct UpdateEndpointSvc(Arc<ControlPlaneService>);
+
+impl UnaryService<UpdateEndpointRequest> for UpdateEndpointSvc {
+    type Response = EndpointResponse;
+    type Future = BoxFuture<Response<Self::Response>, Status>;
+
+    fn call(&mut self, request: Request<UpdateEndpointRequest>) -> Self::Future {
+        let inner = Arc::clone(&self.0);
+        Box::pin(async move { inner.update_endpoint(request).await })
+    }
+}
+
+struct DeleteEndpointSvc(Arc<ControlPlaneService>);
+
+impl UnaryService<DeleteEndpointRequest> for DeleteEndpointSvc {
+    type Response = DeleteEndpointResponse;
+    type Future = BoxFuture<Response<Self::Response>, Status>;
+
+    fn call(&mut self, request: Request<DeleteEndpointRequest>) -> Self::Future {
+        let inner = Arc::clone(&self.0);
+        Box::pin(async move { inner.delete_endpoint(request).await })
+    }
+}
+
+impl<B> Service<http::Request<B>> for ControlPlaneServer
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let inner = Arc::clone(&self.inner);
+
+        match req.uri().path() {
+            "/cloudping.v1.ControlPlane/StartBenchmark" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.unary(StartBenchmarkSvc(inner), req).await)
+            }),
+            "/cloudping.v1.ControlPlane/StreamMetrics" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.server_streaming(StreamMetricsSvc(inner), req).await)
+            }),
+            "/cloudping.v1.ControlPlane/ListEndpoints" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.unary(ListEndpointsSvc(inner), req).await)
+            }),
+            "/cloudping.v1.ControlPlane/CreateEndpoint" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.unary(CreateEndpointSvc(inner), req).await)
+            }),
+            "/cloudping.v1.ControlPlane/UpdateEndpoint" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.unary(UpdateEndpointSvc(inner), req).await)
+            }),
+            "/cloudping.v1.ControlPlane/DeleteEndpoint" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.unary(DeleteEndpointSvc(inner), req).await)
+            }),
+            _ => Box::pin(async move {
+                let mut response = http::Response::new(empty_body());
+                let headers = response.headers_mut();
+                headers.insert(
+                    Status::GRPC_STATUS,
+                    http::HeaderValue::from(tonic::Code::Unimplemented as i32),
+                );
+                headers.insert(
+                    http::header::CONTENT_TYPE,
+                    tonic::metadata::GRPC_CONTENT_TYPE,
+                );
+                Ok(response)
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::models::{Endpoint, ProbeType};
+    use crate::monitoring::create_default_monitoring_system;
+
+    /// Request as the token interceptor passes it on for an admin token
+    fn admin<T>(message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        request.extensions_mut().insert(ApiScope::Admin);
+        request
+    }
+
+    fn create_service() -> ControlPlaneService {
+        let benchmark = ConnectionBenchmark::new(AppConfig::default()).unwrap();
+        ControlPlaneService::new(benchmark, Arc::new(create_default_monitoring_system()))
+    }
+
+    #[tokio::test]
+    async fn test_list_endpoints() {
+        let service = create_service();
+        service
+            .monitoring
+            .add_endpoint(Endpoint::new(
+                "test".to_string(),
+                "example.com".to_string(),
+                443,
+                ProbeType::HTTP,
+            ))
+            .await;
+
+        let response = service
+            .list_endpoints(Request::new(ListEndpointsRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(response.endpoints.len(), 1);
+        assert_eq!(response.endpoints[0].id, "test");
+        assert_eq!(response.endpoints[0].port, 443);
+        assert_eq!(response.endpoints[0].probe_type, "HTTP");
+    }
+
+    #[tokio::test]
+    async fn test_manage_endpoints() {
+        let service = create_service();
+        let created = service
+            .create_endpoint(admin(CreateEndpointRequest {
+                url: "https://api.example.com".to_string(),
+                id: Some("api".to_string()),
+                name: None,
+                enabled: None,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(created.endpoint.unwrap().host, "api.example.com");
+
+        let duplicate = CreateEndpointRequest {
+            url: "https://other.example.com".to_string(),
+            id: Some("api".to_string()),
+            name: None,
+            enabled: None,
+        };
+        let status = service.create_endpoint(admin(duplicate)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::AlreadyExists);
+
+        let updated = service
+            .update_endpoint(admin(UpdateEndpointRequest {
+                id: "api".to_string(),
+                url: None,
+                name: None,
+                enabled: Some(false),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!updated.endpoint.unwrap().enabled);
+
+        service
+            .delete_endpoint(admin(DeleteEndpointRequest { id: "api".to_string() }))
+            .await
+            .unwrap();
+        let status = service
+            .delete_endpoint(admin(DeleteEndpointRequest { id: "api".to_string() }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_tokens_guard_changes() {
+        let settings = ApiSettings {
+            tokens: vec![
+                ApiToken {
+                    name: "dashboard".to_string(),
+                    token: "read-only-secret-0123".to_string(),
+                    scope: ApiScope::Read,
+                },
+                ApiToken {
+                    name: "ops".to_string(),
+                    token: "admin-secret-0123456".to_string(),
+                    scope: ApiScope::Admin,
+                },
+            ],
+            tls: None,
+        };
+        let service = create_service().access(&settings).unwrap();
+        let mut interceptor = TokenInterceptor {
+            tokens: Arc::clone(&service.tokens),
+        };
+        let call = |interceptor: &mut TokenInterceptor, token: Option<&str>| {
+            let mut request = Request::new(());
+            if let Some(token) = token {
+                request.metadata_mut().insert("authorization", format!("Bearer {token}").parse().unwrap());
+            }
+            interceptor.call(request)
+        };
+
+        let status = call(&mut interceptor, None).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        let status = call(&mut interceptor, Some("not-a-configured-token")).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        let create = |request: Request<()>| {
+            let (metadata, extensions, ()) = request.into_parts();
+            Request::from_parts(
+                metadata,
+                extensions,
+                CreateEndpointRequest {
+                    url: "https://api.example.com".to_string(),
+                    id: Some("api".to_string()),
+                    name: None,
+                    enabled: None,
+                },
+            )
+        };
+        let read = call(&mut interceptor, Some("read-only-secret-0123")).unwrap();
+        assert_eq!(read.extensions().get::<ApiScope>(), Some(&ApiScope::Read));
+        let status = service.create_endpoint(create(read)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        let admin = call(&mut interceptor, Some("admin-secret-0123456")).unwrap();
+        assert!(service.create_endpoint(create(admin)).await.is_ok());
+
+        // Without configured tokens the control plane stays open
+        let mut open = TokenInterceptor { tokens: Arc::from([]) };
+        assert_eq!(call(&mut open, None).unwrap().extensions().get::<ApiScope>(), Some(&ApiScope::Admin));
+
+        // Tokens never travel in plain text beyond the loopback interface
+        let exposed = service.serve("0.0.0.0:0".parse().unwrap()).await.unwrap_err();
+        assert!(exposed.to_string().contains("[api.tls]"));
+        let missing = ApiSettings {
+            tls: Some(ApiTlsSettings {
+                cert: "missing.pem".into(),
+                key: "missing.pem".into(),
+            }),
+            ..settings
+        };
+        assert!(create_service().access(&missing).is_err());
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        use prost::Message;
+
+        let request = StartBenchmarkRequest {
+            ping_count: 5,
+            provider_filter: Some("aws".to_string()),
+            region_filter: None,
+        };
+
+        let encoded = request.encode_to_vec();
+        let decoded = StartBenchmarkRequest::decode(encoded.as_slice()).unwrap();
+        assert_eq!(decoded, request);
+    }
+}
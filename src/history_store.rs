@@ -0,0 +1,1046 @@
+//! Stores keeping per-region test history across runs
+//!
+//! A single user benchmarking from a laptop is served by one local file,
+//! while a fleet of agents reporting into one place needs a shared database.
+//! [`HistoryStore`] hides which one holds the runs: `SQLite` is the default,
+//! the JSON file of earlier versions still works, and Postgres or sled can be
+//! built in with the `postgres` and `sled` features. Runs are identified by
+//! region URL and test time, so saving the same run twice stores it once.
+//! With a [`RetentionPolicy`], [`compact`] folds old runs into hourly and
+//! daily aggregates, after each benchmark and periodically while monitoring.
+//! [`save_buffered`] keeps runs the store cannot take in a [`Wal`] and saves
+//! them with the next run.
+
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::config::AppConfig;
+use crate::error::{CloudPingError, Result};
+#[cfg(any(feature = "sqlite", feature = "postgres", feature = "sled"))]
+use crate::models::PingStats;
+use crate::models::{HistoryArchive, RetentionPolicy, Rollup, TestHistory};
+use crate::time_utils::TimeUtils;
+use crate::wal::Wal;
+
+/// Size in bytes the history write-ahead log is kept under by dropping its
+/// oldest regions
+const HISTORY_WAL_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Which store keeps the test history, read from `history_backend`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryBackend {
+    /// Pretty-printed JSON file rewritten on every save
+    Json,
+    /// `SQLite` database file; needs the `sqlite` feature, on by default
+    Sqlite,
+    /// Postgres database named by a connection URL; needs the `postgres` feature
+    Postgres,
+    /// sled database directory; needs the `sled` feature
+    Sled,
+}
+
+impl HistoryBackend {
+    /// Backend configured for `history_file`, inferred from it when
+    /// `history_backend` is unset: Postgres URLs use Postgres, `.json` files
+    /// use JSON and anything else `SQLite`
+    #[must_use]
+    pub fn for_location(configured: Option<Self>, location: &str) -> Self {
+        configured.unwrap_or_else(|| {
+            if location.starts_with("postgres://") || location.starts_with("postgresql://") {
+                Self::Postgres
+            } else if Path::new(location)
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+            {
+                Self::Json
+            } else {
+                Self::Sqlite
+            }
+        })
+    }
+
+    /// Cargo feature the backend needs, if it is not always built
+    const fn feature(self) -> Option<&'static str> {
+        match self {
+            Self::Json => None,
+            Self::Sqlite => Some("sqlite"),
+            Self::Postgres => Some("postgres"),
+            Self::Sled => Some("sled"),
+        }
+    }
+
+    /// Whether this build includes the backend
+    #[must_use]
+    pub const fn is_built(self) -> bool {
+        match self {
+            Self::Json => true,
+            Self::Sqlite => cfg!(feature = "sqlite"),
+            Self::Postgres => cfg!(feature = "postgres"),
+            Self::Sled => cfg!(feature = "sled"),
+        }
+    }
+
+    /// # Errors
+    /// Returns a validation error when this build does not include the backend
+    pub fn validate(self) -> Result<()> {
+        match self.feature() {
+            Some(feature) if !self.is_built() => Err(CloudPingError::validation(
+                "history_backend",
+                format!("built without the {feature} feature"),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Where test history is saved and read back
+pub trait HistoryStore: Debug + Send + Sync {
+    /// Save the runs of `histories` not stored yet, returning how many were added
+    fn save_stats<'a>(&'a self, histories: &'a [TestHistory]) -> BoxFuture<'a, Result<usize>>;
+
+    /// The latest [`TestHistory::MAX_RUNS`] runs of every region
+    fn load_history(&self) -> BoxFuture<'_, Result<Vec<TestHistory>>>;
+
+    /// Every run tested from `from` up to but excluding `to`
+    fn query_range(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> BoxFuture<'_, Result<Vec<TestHistory>>>;
+
+    /// Replace every run tested from `from` up to but excluding `to` with
+    /// the runs of `histories` in one step
+    fn replace_range<'a>(
+        &'a self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        histories: &'a [TestHistory],
+    ) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Open the store configured by `history_file` and `history_backend`, or
+/// `None` when no history is kept
+///
+/// # Errors
+/// Returns an error when the backend is not built in or cannot be opened
+#[cfg_attr(not(feature = "postgres"), allow(clippy::unused_async))]
+pub async fn open(config: &AppConfig) -> Result<Option<Box<dyn HistoryStore>>> {
+    let Some(location) = config.history_file.as_deref() else {
+        return Ok(None);
+    };
+    let backend = HistoryBackend::for_location(config.history_backend, location);
+    backend.validate()?;
+    let store: Box<dyn HistoryStore> = match backend {
+        #[cfg(feature = "sqlite")]
+        HistoryBackend::Sqlite => Box::new(SqliteHistoryStore::open(Path::new(location))?),
+        #[cfg(feature = "postgres")]
+        HistoryBackend::Postgres => Box::new(PostgresHistoryStore::connect(location, &config.tls).await?),
+        #[cfg(feature = "sled")]
+        HistoryBackend::Sled => Box::new(SledHistoryStore::open(Path::new(location))?),
+        // Backends not built in were rejected above
+        _ => Box::new(JsonHistoryStore::new(location)),
+    };
+    Ok(Some(store))
+}
+
+/// Save `histories` to `store` with the runs an earlier save left in the
+/// write-ahead log at `wal_path`, buffering them in the log when the store
+/// is unavailable
+///
+/// Returns how many runs were added to the store, none when it was unavailable.
+///
+/// # Errors
+/// Returns an error when the log cannot be read or written
+pub async fn save_buffered(store: &dyn HistoryStore, wal_path: &Path, histories: &[TestHistory]) -> Result<usize> {
+    let mut wal = Wal::open(wal_path, HISTORY_WAL_MAX_BYTES).await?;
+    let buffered = wal.read().await?;
+    if buffered.skipped > 0 {
+        warn!("Skipped {} damaged lines of the history write-ahead log", buffered.skipped);
+    }
+    let replayed = buffered.records.len();
+    // Runs are stored once per region and test time, so saving a buffered one again is harmless
+    let mut pending = buffered.records;
+    pending.extend_from_slice(histories);
+
+    match store.save_stats(&pending).await {
+        Ok(added) => {
+            if replayed > 0 {
+                info!("Saved {} regions buffered in {}", replayed, wal_path.display());
+            }
+            wal.clear().await?;
+            Ok(added)
+        }
+        Err(e) => {
+            warn!(
+                "History store unavailable, buffering {} regions in {}: {}",
+                histories.len(),
+                wal_path.display(),
+                e
+            );
+            let dropped = wal.append(histories).await?;
+            if dropped > 0 {
+                warn!("History write-ahead log is full, dropped its {} oldest regions", dropped);
+            }
+            Ok(0)
+        }
+    }
+}
+
+/// Fold and drop runs of `store` older than `policy` keeps as they are,
+/// returning how many runs fewer it holds
+///
+/// # Errors
+/// Returns an error when the store cannot be read or written
+pub async fn compact(store: &dyn HistoryStore, policy: &RetentionPolicy, now: DateTime<Utc>) -> Result<usize> {
+    let cutoff = policy.raw_cutoff(now);
+    let mut histories = store.query_range(DateTime::<Utc>::MIN_UTC, cutoff).await?;
+    let before: usize = histories.iter().map(|history| history.historical_data.len()).sum();
+    let mut changed = false;
+    for history in &mut histories {
+        if let Some(runs) = policy.compact(&history.historical_data, now) {
+            history.historical_data = runs;
+            changed = true;
+        }
+    }
+    if !changed {
+        return Ok(0);
+    }
+    histories.retain(|history| !history.historical_data.is_empty());
+    let after: usize = histories.iter().map(|history| history.historical_data.len()).sum();
+    store.replace_range(DateTime::<Utc>::MIN_UTC, cutoff, &histories).await?;
+    Ok(before - after)
+}
+
+/// Rollups of the runs from `from` up to but excluding `to` of every region,
+/// or of those whose name, URL or ID is `region`
+///
+/// # Errors
+/// Returns an error when the store cannot be read or the interval is invalid
+pub async fn rollup(
+    store: &dyn HistoryStore,
+    region: Option<&str>,
+    interval: Duration,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<Rollup>> {
+    Rollup::step(interval)?;
+    store
+        .query_range(from, to)
+        .await?
+        .iter()
+        .filter(|history| {
+            region.map_or(true, |region| {
+                history.region_name.eq_ignore_ascii_case(region)
+                    || history.region_url == region
+                    || history.region_id == region
+            })
+        })
+        .map(|history| Rollup::new(history, interval, from, to))
+        .collect()
+}
+
+/// Compact `store` every `policy.interval` in the background
+pub fn spawn_compaction(store: Arc<dyn HistoryStore>, policy: RetentionPolicy) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(policy.interval);
+        loop {
+            interval.tick().await;
+            match compact(store.as_ref(), &policy, TimeUtils::now()).await {
+                Ok(0) => debug!("History already compacted"),
+                Ok(removed) => info!("Compacted history to {} fewer runs", removed),
+                Err(e) => warn!("History compaction failed: {}", e),
+            }
+        }
+    })
+}
+
+/// One stored run with the region it belongs to
+#[cfg(any(feature = "sqlite", feature = "postgres", feature = "sled"))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredRun {
+    region_url: String,
+    region_id: String,
+    region_name: String,
+    stats: PingStats,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgres", feature = "sled"))]
+impl StoredRun {
+    /// Every run of `histories`
+    fn all(histories: &[TestHistory]) -> impl Iterator<Item = Self> + '_ {
+        histories.iter().flat_map(|history| {
+            history.historical_data.iter().map(|stats| Self {
+                region_url: history.region_url.clone(),
+                region_id: history.region_id.clone(),
+                region_name: history.region_name.clone(),
+                stats: stats.clone(),
+            })
+        })
+    }
+}
+
+/// Histories of `runs` ordered by region URL then test time, named after
+/// each region's latest run
+#[cfg(any(feature = "sqlite", feature = "postgres", feature = "sled"))]
+fn group_runs(runs: impl IntoIterator<Item = StoredRun>) -> Vec<TestHistory> {
+    let mut histories: Vec<TestHistory> = Vec::new();
+    for run in runs {
+        match histories.last_mut() {
+            Some(history) if history.region_url == run.region_url => {
+                history.region_id = run.region_id;
+                history.region_name = run.region_name;
+                history.historical_data.push(run.stats);
+            }
+            _ => {
+                let mut history = TestHistory::new(run.region_id, run.region_name, run.region_url);
+                history.historical_data.push(run.stats);
+                histories.push(history);
+            }
+        }
+    }
+    histories
+}
+
+/// Test time of `stats` in milliseconds since the epoch
+#[cfg(any(feature = "sqlite", feature = "postgres", feature = "sled"))]
+const fn run_millis(stats: &PingStats) -> i64 {
+    stats.test_time.timestamp_millis()
+}
+
+/// History kept in a JSON file, rewritten on every save
+#[derive(Debug, Clone)]
+pub struct JsonHistoryStore {
+    path: PathBuf,
+}
+
+impl JsonHistoryStore {
+    /// Store in the JSON file at `path`, created on the first save
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl HistoryStore for JsonHistoryStore {
+    fn save_stats<'a>(&'a self, histories: &'a [TestHistory]) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(async move {
+            let mut merged = HistoryArchive::new(TestHistory::load_all(&self.path)?);
+            let added = merged.merge(HistoryArchive::new(histories.to_vec()));
+            TestHistory::save_all(&merged.histories, &self.path)?;
+            Ok(added)
+        })
+    }
+
+    fn load_history(&self) -> BoxFuture<'_, Result<Vec<TestHistory>>> {
+        Box::pin(async move { TestHistory::load_all(&self.path) })
+    }
+
+    fn query_range(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> BoxFuture<'_, Result<Vec<TestHistory>>> {
+        Box::pin(async move {
+            let mut histories = TestHistory::load_all(&self.path)?;
+            for history in &mut histories {
+                history
+                    .historical_data
+                    .retain(|stats| stats.test_time >= from && stats.test_time < to);
+            }
+            histories.retain(|history| !history.historical_data.is_empty());
+            Ok(histories)
+        })
+    }
+
+    fn replace_range<'a>(
+        &'a self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        histories: &'a [TestHistory],
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut kept = TestHistory::load_all(&self.path)?;
+            for history in &mut kept {
+                history
+                    .historical_data
+                    .retain(|stats| stats.test_time < from || stats.test_time >= to);
+            }
+            let mut merged = HistoryArchive::new(kept);
+            merged.merge(HistoryArchive::new(histories.to_vec()));
+            merged.histories.retain(|history| !history.historical_data.is_empty());
+            TestHistory::save_all(&merged.histories, &self.path)
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteHistoryStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+
+    use chrono::{DateTime, Utc};
+    use futures::future::BoxFuture;
+    use rusqlite::{params, params_from_iter, Connection, Transaction};
+
+    use super::{group_runs, run_millis, HistoryStore, StoredRun};
+    use crate::error::{CloudPingError, Result};
+    use crate::models::TestHistory;
+
+    const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS runs (
+        region_url TEXT NOT NULL,
+        test_time INTEGER NOT NULL,
+        region_id TEXT NOT NULL,
+        region_name TEXT NOT NULL,
+        stats TEXT NOT NULL,
+        PRIMARY KEY (region_url, test_time)
+    )";
+
+    /// History kept in a `SQLite` database file
+    #[derive(Debug, Clone)]
+    pub struct SqliteHistoryStore {
+        connection: Arc<Mutex<Connection>>,
+    }
+
+    impl SqliteHistoryStore {
+        /// Open or create the database at `path`
+        ///
+        /// # Errors
+        /// Returns an error when the database cannot be opened or its table created
+        pub fn open(path: &Path) -> Result<Self> {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)?;
+            }
+            let connection = Connection::open(path)
+                .and_then(|connection| connection.execute(SCHEMA, []).map(|_| connection))
+                .map_err(|e| {
+                    CloudPingError::data_loading(format!("Cannot open history database {}: {e}", path.display()))
+                })?;
+            Ok(Self {
+                connection: Arc::new(Mutex::new(connection)),
+            })
+        }
+
+        /// Run `query` on the connection off the async runtime
+        async fn with_connection<T: Send + 'static>(
+            &self,
+            query: impl FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+        ) -> Result<T> {
+            let connection = Arc::clone(&self.connection);
+            tokio::task::spawn_blocking(move || {
+                let mut connection = connection
+                    .lock()
+                    .map_err(|_| CloudPingError::system("history database lock poisoned"))?;
+                query(&mut connection)
+                    .map_err(|e| CloudPingError::data_loading(format!("History database query failed: {e}")))
+            })
+            .await
+            .map_err(|e| CloudPingError::system(format!("History database task failed: {e}")))?
+        }
+
+        /// Runs selected by `sql`, which must return region URL, ID, name and
+        /// stats ordered by region URL then test time
+        async fn select(&self, sql: &'static str, bounds: Vec<i64>) -> Result<Vec<TestHistory>> {
+            let rows = self
+                .with_connection(move |connection| {
+                    let mut statement = connection.prepare(sql)?;
+                    let rows = statement.query_map(params_from_iter(bounds), |row| {
+                        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get::<_, String>(3)?))
+                    })?;
+                    rows.collect::<rusqlite::Result<Vec<(String, String, String, String)>>>()
+                })
+                .await?;
+            let runs = rows
+                .into_iter()
+                .map(|(region_url, region_id, region_name, stats)| {
+                    Ok(StoredRun {
+                        region_url,
+                        region_id,
+                        region_name,
+                        stats: serde_json::from_str(&stats)?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(group_runs(runs))
+        }
+    }
+
+    /// Insert runs with their stats as JSON, skipping those already stored
+    fn insert_runs(transaction: &Transaction<'_>, runs: &[(String, StoredRun)]) -> rusqlite::Result<usize> {
+        let mut insert = transaction.prepare(
+            "INSERT OR IGNORE INTO runs (region_url, test_time, region_id, region_name, stats)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        let mut added = 0;
+        for (stats, run) in runs {
+            added += insert.execute(params![
+                run.region_url,
+                run_millis(&run.stats),
+                run.region_id,
+                run.region_name,
+                stats
+            ])?;
+        }
+        Ok(added)
+    }
+
+    /// Every run of `histories` paired with its stats as JSON
+    fn encode_runs(histories: &[TestHistory]) -> Result<Vec<(String, StoredRun)>> {
+        StoredRun::all(histories)
+            .map(|run| Ok((serde_json::to_string(&run.stats)?, run)))
+            .collect()
+    }
+
+    impl HistoryStore for SqliteHistoryStore {
+        fn save_stats<'a>(&'a self, histories: &'a [TestHistory]) -> BoxFuture<'a, Result<usize>> {
+            Box::pin(async move {
+                let runs = encode_runs(histories)?;
+                self.with_connection(move |connection| {
+                    let transaction = connection.transaction()?;
+                    let added = insert_runs(&transaction, &runs)?;
+                    transaction.commit()?;
+                    Ok(added)
+                })
+                .await
+            })
+        }
+
+        fn load_history(&self) -> BoxFuture<'_, Result<Vec<TestHistory>>> {
+            Box::pin(self.select(
+                "SELECT region_url, region_id, region_name, stats FROM (
+                     SELECT *, ROW_NUMBER() OVER (PARTITION BY region_url ORDER BY test_time DESC) AS recent
+                     FROM runs
+                 ) WHERE recent <= ?1 ORDER BY region_url, test_time",
+                vec![i64::try_from(TestHistory::MAX_RUNS).unwrap_or(i64::MAX)],
+            ))
+        }
+
+        fn query_range(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> BoxFuture<'_, Result<Vec<TestHistory>>> {
+            Box::pin(self.select(
+                "SELECT region_url, region_id, region_name, stats FROM runs
+                 WHERE test_time >= ?1 AND test_time < ?2 ORDER BY region_url, test_time",
+                vec![from.timestamp_millis(), to.timestamp_millis()],
+            ))
+        }
+
+        fn replace_range<'a>(
+            &'a self,
+            from: DateTime<Utc>,
+            to: DateTime<Utc>,
+            histories: &'a [TestHistory],
+        ) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async move {
+                let runs = encode_runs(histories)?;
+                self.with_connection(move |connection| {
+                    let transaction = connection.transaction()?;
+                    transaction.execute(
+                        "DELETE FROM runs WHERE test_time >= ?1 AND test_time < ?2",
+                        [from.timestamp_millis(), to.timestamp_millis()],
+                    )?;
+                    insert_runs(&transaction, &runs)?;
+                    transaction.commit()
+                })
+                .await
+            })
+        }
+    }
+}
+
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresHistoryStore;
+
+#[cfg(feature = "postgres")]
+mod postgres {
+    use std::io;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+
+    use chrono::{DateTime, Utc};
+    use futures::future::BoxFuture;
+    use rustls::pki_types::{InvalidDnsNameError, ServerName};
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use tokio_postgres::tls::{ChannelBinding, MakeTlsConnect, TlsConnect, TlsStream};
+    use tokio_postgres::types::ToSql;
+    use tokio::sync::Mutex;
+    use tokio_postgres::{Client, GenericClient, Row};
+    use tracing::warn;
+
+    use super::{group_runs, run_millis, HistoryStore, StoredRun};
+    use crate::error::{CloudPingError, Result};
+    use crate::models::TestHistory;
+    use crate::tls::{timed_client_config, CertValidations};
+    use crate::transport::TlsConfig;
+
+    const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS runs (
+        region_url TEXT NOT NULL,
+        test_time BIGINT NOT NULL,
+        region_id TEXT NOT NULL,
+        region_name TEXT NOT NULL,
+        stats TEXT NOT NULL,
+        PRIMARY KEY (region_url, test_time)
+    )";
+
+    /// History kept in a Postgres database shared by several machines
+    #[derive(Debug)]
+    pub struct PostgresHistoryStore {
+        // Locked so a compaction's transaction does not take in other writes
+        client: Mutex<Client>,
+    }
+
+    impl PostgresHistoryStore {
+        /// Connect to the database at `url` and create the runs table
+        ///
+        /// The URL's `sslmode` says whether to speak TLS: `disable`, `prefer`
+        /// (the default) or `require`. TLS verifies the server against the
+        /// web PKI roots and presents the client certificate of `tls`, if any.
+        ///
+        /// # Errors
+        /// Returns an error when the client certificate cannot be read, or a
+        /// network error when the database cannot be reached or set up
+        pub async fn connect(url: &str, tls: &TlsConfig) -> Result<Self> {
+            let mut tls_config = timed_client_config(tls, &CertValidations::default())?;
+            // Postgres rejects ALPN other than its own, and needs none
+            tls_config.alpn_protocols.clear();
+            let connector = RustlsConnect(Arc::new(tls_config));
+            let (client, connection) = tokio_postgres::connect(url, connector).await.map_err(|e| query_error(&e))?;
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    warn!("History database connection closed: {}", e);
+                }
+            });
+            client.batch_execute(SCHEMA).await.map_err(|e| query_error(&e))?;
+            Ok(Self {
+                client: Mutex::new(client),
+            })
+        }
+
+        async fn select(&self, sql: &str, bounds: Vec<i64>) -> Result<Vec<TestHistory>> {
+            let params: Vec<&(dyn ToSql + Sync)> = bounds.iter().map(|bound| bound as _).collect();
+            let rows = self
+                .client
+                .lock()
+                .await
+                .query(sql, &params)
+                .await
+                .map_err(|e| query_error(&e))?;
+            let runs = rows.iter().map(stored_run).collect::<Result<Vec<_>>>()?;
+            Ok(group_runs(runs))
+        }
+    }
+
+    /// Makes TLS connections to the database with rustls
+    #[derive(Clone)]
+    struct RustlsConnect(Arc<rustls::ClientConfig>);
+
+    impl<S> MakeTlsConnect<S> for RustlsConnect
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        type Stream = RustlsStream<S>;
+        type TlsConnect = RustlsConnector;
+        type Error = InvalidDnsNameError;
+
+        fn make_tls_connect(&mut self, domain: &str) -> std::result::Result<RustlsConnector, InvalidDnsNameError> {
+            Ok(RustlsConnector {
+                config: Arc::clone(&self.0),
+                server_name: ServerName::try_from(domain.to_string())?,
+            })
+        }
+    }
+
+    /// TLS handshake with one database host
+    struct RustlsConnector {
+        config: Arc<rustls::ClientConfig>,
+        server_name: ServerName<'static>,
+    }
+
+    impl<S> TlsConnect<S> for RustlsConnector
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        type Stream = RustlsStream<S>;
+        type Error = io::Error;
+        type Future = BoxFuture<'static, io::Result<RustlsStream<S>>>;
+
+        fn connect(self, stream: S) -> Self::Future {
+            Box::pin(async move {
+                tokio_rustls::TlsConnector::from(self.config)
+                    .connect(self.server_name, stream)
+                    .await
+                    .map(RustlsStream)
+            })
+        }
+    }
+
+    /// Database connection over TLS
+    struct RustlsStream<S>(tokio_rustls::client::TlsStream<S>);
+
+    impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for RustlsStream<S> {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_read(cx, buf)
+        }
+    }
+
+    impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for RustlsStream<S> {
+        fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.0).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_shutdown(cx)
+        }
+    }
+
+    impl<S: AsyncRead + AsyncWrite + Unpin> TlsStream for RustlsStream<S> {
+        fn channel_binding(&self) -> ChannelBinding {
+            ChannelBinding::none()
+        }
+    }
+
+    fn query_error(e: &tokio_postgres::Error) -> CloudPingError {
+        CloudPingError::network(format!("History database query failed: {e}"))
+    }
+
+    fn stored_run(row: &Row) -> Result<StoredRun> {
+        Ok(StoredRun {
+            region_url: row.get(0),
+            region_id: row.get(1),
+            region_name: row.get(2),
+            stats: serde_json::from_str(row.get(3))?,
+        })
+    }
+
+    /// Insert the runs of `histories`, skipping those already stored
+    async fn insert_runs(client: &(impl GenericClient + Sync), histories: &[TestHistory]) -> Result<usize> {
+        let insert = client
+            .prepare(
+                "INSERT INTO runs (region_url, test_time, region_id, region_name, stats)
+                 VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING",
+            )
+            .await
+            .map_err(|e| query_error(&e))?;
+        let mut added = 0;
+        for run in StoredRun::all(histories) {
+            let stats = serde_json::to_string(&run.stats)?;
+            added += client
+                .execute(
+                    &insert,
+                    &[&run.region_url, &run_millis(&run.stats), &run.region_id, &run.region_name, &stats],
+                )
+                .await
+                .map_err(|e| query_error(&e))?;
+        }
+        Ok(usize::try_from(added).unwrap_or(usize::MAX))
+    }
+
+    impl HistoryStore for PostgresHistoryStore {
+        fn save_stats<'a>(&'a self, histories: &'a [TestHistory]) -> BoxFuture<'a, Result<usize>> {
+            Box::pin(async move { insert_runs(&*self.client.lock().await, histories).await })
+        }
+
+        fn load_history(&self) -> BoxFuture<'_, Result<Vec<TestHistory>>> {
+            Box::pin(self.select(
+                "SELECT region_url, region_id, region_name, stats FROM (
+                     SELECT *, ROW_NUMBER() OVER (PARTITION BY region_url ORDER BY test_time DESC) AS recent
+                     FROM runs
+                 ) AS ranked WHERE recent <= $1 ORDER BY region_url, test_time",
+                vec![i64::try_from(TestHistory::MAX_RUNS).unwrap_or(i64::MAX)],
+            ))
+        }
+
+        fn query_range(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> BoxFuture<'_, Result<Vec<TestHistory>>> {
+            Box::pin(self.select(
+                "SELECT region_url, region_id, region_name, stats FROM runs
+                 WHERE test_time >= $1 AND test_time < $2 ORDER BY region_url, test_time",
+                vec![from.timestamp_millis(), to.timestamp_millis()],
+            ))
+        }
+
+        fn replace_range<'a>(
+            &'a self,
+            from: DateTime<Utc>,
+            to: DateTime<Utc>,
+            histories: &'a [TestHistory],
+        ) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async move {
+                let mut client = self.client.lock().await;
+                let transaction = client.transaction().await.map_err(|e| query_error(&e))?;
+                transaction
+                    .execute(
+                        "DELETE FROM runs WHERE test_time >= $1 AND test_time < $2",
+                        &[&from.timestamp_millis(), &to.timestamp_millis()],
+                    )
+                    .await
+                    .map_err(|e| query_error(&e))?;
+                insert_runs(&transaction, histories).await?;
+                let committed = transaction.commit().await.map_err(|e| query_error(&e));
+                drop(client);
+                committed
+            })
+        }
+    }
+}
+
+#[cfg(feature = "sled")]
+pub use self::sled::SledHistoryStore;
+
+#[cfg(feature = "sled")]
+mod sled {
+    use std::path::Path;
+
+    use chrono::{DateTime, Utc};
+    use futures::future::BoxFuture;
+
+    use super::{group_runs, run_millis, HistoryStore, StoredRun};
+    use crate::error::{CloudPingError, Result};
+    use crate::models::TestHistory;
+
+    /// History kept in an embedded sled database directory
+    ///
+    /// Keys are the region URL, a zero byte and the big-endian test time, so
+    /// each region's runs are stored next to each other in test order.
+    #[derive(Debug, Clone)]
+    pub struct SledHistoryStore {
+        db: ::sled::Db,
+    }
+
+    impl SledHistoryStore {
+        /// Open or create the database in the directory `path`
+        ///
+        /// # Errors
+        /// Returns an error when the database cannot be opened
+        pub fn open(path: &Path) -> Result<Self> {
+            let db = ::sled::open(path).map_err(|e| {
+                CloudPingError::data_loading(format!("Cannot open history database {}: {e}", path.display()))
+            })?;
+            Ok(Self { db })
+        }
+
+        /// Key prefix of every stored region, found by seeking past each
+        /// region's runs rather than reading them
+        fn region_prefixes(&self) -> Result<Vec<Vec<u8>>> {
+            let mut prefixes = Vec::new();
+            let mut start = Vec::new();
+            while let Some(entry) = self.db.range(start.as_slice()..).next() {
+                let (key, _) = entry.map_err(|e| db_error(&e))?;
+                let prefix = key[..key.len().saturating_sub(8)].to_vec();
+                // URLs hold no zero byte, so the URL followed by 1 sorts after all its runs
+                start.clone_from(&prefix);
+                if let Some(last) = start.last_mut() {
+                    *last = 1;
+                }
+                prefixes.push(prefix);
+            }
+            Ok(prefixes)
+        }
+
+        /// Runs of each region between `from` and `to`, in key order
+        fn runs_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<StoredRun>> {
+            let mut runs = Vec::new();
+            if from >= to {
+                return Ok(runs);
+            }
+            for prefix in self.region_prefixes()? {
+                let range = time_key(&prefix, from.timestamp_millis())..time_key(&prefix, to.timestamp_millis());
+                for value in self.db.range(range).values() {
+                    runs.push(parse_run(&value.map_err(|e| db_error(&e))?)?);
+                }
+            }
+            Ok(runs)
+        }
+    }
+
+    fn parse_run(value: &[u8]) -> Result<StoredRun> {
+        Ok(serde_json::from_slice(value)?)
+    }
+
+    fn db_error(e: &::sled::Error) -> CloudPingError {
+        CloudPingError::data_loading(format!("History database query failed: {e}"))
+    }
+
+    fn key(run: &StoredRun) -> Vec<u8> {
+        let mut prefix = Vec::with_capacity(run.region_url.len() + 1);
+        prefix.extend_from_slice(run.region_url.as_bytes());
+        prefix.push(0);
+        time_key(&prefix, run_millis(&run.stats))
+    }
+
+    /// Key of the run at `millis` of the region whose keys start with `prefix`
+    fn time_key(prefix: &[u8], millis: i64) -> Vec<u8> {
+        // Runs are never older than the epoch, so unsigned big-endian times sort in order
+        let millis = u64::try_from(millis).unwrap_or(0);
+        let mut key = Vec::with_capacity(prefix.len() + 8);
+        key.extend_from_slice(prefix);
+        key.extend_from_slice(&millis.to_be_bytes());
+        key
+    }
+
+    impl HistoryStore for SledHistoryStore {
+        fn save_stats<'a>(&'a self, histories: &'a [TestHistory]) -> BoxFuture<'a, Result<usize>> {
+            Box::pin(async move {
+                let mut added = 0;
+                for run in StoredRun::all(histories) {
+                    let value = serde_json::to_vec(&run)?;
+                    let inserted = self
+                        .db
+                        .compare_and_swap(key(&run), None::<&[u8]>, Some(value))
+                        .map_err(|e| db_error(&e))?;
+                    if inserted.is_ok() {
+                        added += 1;
+                    }
+                }
+                self.db.flush_async().await.map_err(|e| db_error(&e))?;
+                Ok(added)
+            })
+        }
+
+        fn load_history(&self) -> BoxFuture<'_, Result<Vec<TestHistory>>> {
+            Box::pin(async move {
+                let mut runs = Vec::new();
+                for prefix in self.region_prefixes()? {
+                    let latest = self
+                        .db
+                        .scan_prefix(&prefix)
+                        .values()
+                        .rev()
+                        .take(TestHistory::MAX_RUNS)
+                        .map(|value| parse_run(&value.map_err(|e| db_error(&e))?))
+                        .collect::<Result<Vec<_>>>()?;
+                    runs.extend(latest.into_iter().rev());
+                }
+                Ok(group_runs(runs))
+            })
+        }
+
+        fn query_range(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> BoxFuture<'_, Result<Vec<TestHistory>>> {
+            Box::pin(async move { Ok(group_runs(self.runs_between(from, to)?)) })
+        }
+
+        fn replace_range<'a>(
+            &'a self,
+            from: DateTime<Utc>,
+            to: DateTime<Utc>,
+            histories: &'a [TestHistory],
+        ) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async move {
+                let mut batch = ::sled::Batch::default();
+                for run in self.runs_between(from, to)? {
+                    batch.remove(key(&run));
+                }
+                for run in StoredRun::all(histories) {
+                    batch.insert(key(&run), serde_json::to_vec(&run)?);
+                }
+                self.db.apply_batch(batch).map_err(|e| db_error(&e))?;
+                self.db.flush_async().await.map_err(|e| db_error(&e))?;
+                Ok(())
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PingStats;
+    use chrono::Duration;
+
+    fn history(url: &str, hours: &[i64]) -> TestHistory {
+        let start = DateTime::parse_from_rfc3339("2026-03-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut history = TestHistory::new("id".to_string(), format!("{url} name"), url.to_string());
+        for &hour in hours {
+            let mut stats = PingStats::new(10);
+            stats.test_time = start + Duration::hours(hour);
+            history.add_test_result(stats);
+        }
+        history
+    }
+
+    async fn check_store(store: &dyn HistoryStore) {
+        let saved = [history("https://a.example.com", &[0, 1, 2]), history("https://b.example.com", &[1])];
+        assert_eq!(store.save_stats(&saved).await.unwrap(), 4);
+        // Saving overlapping runs again only adds the new ones
+        let again = [history("https://a.example.com", &[2, 3])];
+        assert_eq!(store.save_stats(&again).await.unwrap(), 1);
+
+        let loaded = store.load_history().await.unwrap();
+        assert_eq!(loaded.len(), 2);
+        let a = loaded.iter().find(|h| h.region_url == "https://a.example.com").unwrap();
+        assert_eq!(a.historical_data.len(), 4);
+        assert!(a.historical_data.windows(2).all(|w| w[0].test_time < w[1].test_time));
+
+        let from = saved[0].historical_data[1].test_time;
+        let range = store.query_range(from, from + Duration::hours(2)).await.unwrap();
+        let runs: usize = range.iter().map(|h| h.historical_data.len()).sum();
+        assert_eq!(runs, 3);
+
+        // Months later each region's runs fold into one daily aggregate
+        let policy = RetentionPolicy::default();
+        let now = from + Duration::days(100);
+        assert_eq!(compact(store, &policy, now).await.unwrap(), 3);
+        assert_eq!(compact(store, &policy, now).await.unwrap(), 0);
+        let loaded = store.load_history().await.unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert!(loaded.iter().all(|h| h.historical_data.len() == 1));
+    }
+
+    #[tokio::test]
+    async fn test_save_buffered_replays_after_outage() {
+        let dir = tempfile::tempdir().unwrap();
+        let wal = dir.path().join("history-wal.log");
+        // A store under a regular file cannot be written
+        std::fs::write(dir.path().join("file"), "").unwrap();
+        let down = JsonHistoryStore::new(dir.path().join("file").join("history.json"));
+        let first = [history("https://a.example.com", &[0, 1])];
+        assert_eq!(save_buffered(&down, &wal, &first).await.unwrap(), 0);
+        assert!(wal.exists());
+
+        let up = JsonHistoryStore::new(dir.path().join("history.json"));
+        let second = [history("https://b.example.com", &[2])];
+        assert_eq!(save_buffered(&up, &wal, &second).await.unwrap(), 3);
+        assert_eq!(up.load_history().await.unwrap().len(), 2);
+        assert!(!wal.exists());
+    }
+
+    #[tokio::test]
+    async fn test_json_store() {
+        let dir = tempfile::tempdir().unwrap();
+        check_store(&JsonHistoryStore::new(dir.path().join("history.json"))).await;
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_sqlite_store() {
+        let dir = tempfile::tempdir().unwrap();
+        check_store(&SqliteHistoryStore::open(&dir.path().join("history.db")).unwrap()).await;
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_store() {
+        let dir = tempfile::tempdir().unwrap();
+        check_store(&SledHistoryStore::open(&dir.path().join("history")).unwrap()).await;
+    }
+
+    #[test]
+    fn test_backend_inference() {
+        assert_eq!(HistoryBackend::for_location(None, "history.json"), HistoryBackend::Json);
+        assert_eq!(HistoryBackend::for_location(None, "history.db"), HistoryBackend::Sqlite);
+        assert_eq!(
+            HistoryBackend::for_location(None, "postgres://cloud-ping@db/history"),
+            HistoryBackend::Postgres
+        );
+        assert_eq!(
+            HistoryBackend::for_location(Some(HistoryBackend::Sled), "history.json"),
+            HistoryBackend::Sled
+        );
+    }
+}
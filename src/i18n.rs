@@ -0,0 +1,671 @@
+//! Report translations and locale-aware formatting
+//!
+//! A [`Locale`] selects the language of report text from a built-in message
+//! catalog, along with the separators and date layout used for numbers and
+//! timestamps. Every language translates every [`Message`]; the compiler
+//! rejects a catalog with a missing entry. Text produced elsewhere, such as
+//! environment summaries and error messages, stays in English.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use num_format::ToFormattedString;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CloudPingError, Result};
+use crate::models::{AlertSeverity, HealthStatus};
+
+/// Language and number format of generated reports
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    /// English, with `1,234.5` and ISO dates
+    #[default]
+    En,
+    /// German, with `1.234,5` and `31.12.2024` dates
+    De,
+    /// French, with `1 234,5` and `31/12/2024` dates
+    Fr,
+    /// Spanish, with `1.234,5` and `31/12/2024` dates
+    Es,
+}
+
+/// Report text that is translated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Message {
+    /// "Generated {date}" under a report title
+    Generated,
+    /// "Updated {date}" under a status page title
+    Updated,
+    /// Label of the test environment summary
+    Environment,
+    /// Label of the run labels
+    Labels,
+    /// Heading of the benchmark ranking
+    Ranking,
+    /// Column of ranking positions
+    Rank,
+    /// Column of region names
+    Region,
+    /// Column of scores
+    Score,
+    /// Column of grades
+    Grade,
+    /// Column of health statuses
+    Health,
+    /// Column of average latencies
+    Latency,
+    /// Column of packet loss
+    Loss,
+    /// Heading of concurrency reductions during a run
+    ConcurrencyAdjustments,
+    /// Why concurrency was reduced
+    ConcurrencyAdjustmentsNote,
+    /// Heading of control-normalized latencies
+    ControlNormalizedLatency,
+    /// Takes the control endpoint and its median latency
+    ControlNormalizedNote,
+    /// Column of measured latencies
+    Raw,
+    /// Column of control endpoint latencies
+    Control,
+    /// Column of normalized latencies
+    Normalized,
+    /// Control endpoint that never answered
+    NoAnswer,
+    /// Heading of regions that failed
+    FailedRegions,
+    /// Column of failure causes
+    Cause,
+    /// Column of error messages
+    Error,
+    /// Heading of uptime figures
+    Availability,
+    /// Column of endpoint names
+    Endpoint,
+    /// Heading of monitored endpoints
+    Endpoints,
+    /// Column of current up or down states
+    Status,
+    /// Column of times the current state began
+    Since,
+    /// Endpoint answering
+    Up,
+    /// Endpoint not answering
+    Down,
+    /// Endpoint not probed yet
+    Pending,
+    /// Heading of the hourly latency heatmap
+    LatencyByTimeOfDay,
+    /// Takes the UTC offset the hours are shown in
+    AverageLatencyPerHour,
+    /// Takes the number of runs
+    Runs,
+    /// Column of the slowest hour per region
+    WorstHour,
+    /// Column of recent latencies per region
+    Trend,
+    /// Column of score per unit of relative cost
+    Value,
+    /// Takes the region, its carbon intensity and its score
+    GreenestRegion,
+    /// Heading of the region-to-region latency matrix
+    InterRegionLatency,
+    /// Takes the route factor
+    InterRegionNote,
+    /// Heading of the regions grouped by autonomous system
+    Networks,
+    /// Column of autonomous system numbers
+    AutonomousSystem,
+    /// Column of the organization operating an autonomous system
+    Organization,
+    /// Column of slow regions out of all regions behind a system
+    Slow,
+    /// Column of region names
+    Regions,
+    /// Takes the number of slow regions and the autonomous system
+    SlowBehindNetwork,
+    /// Heading of detected latency shifts
+    LatencyChanges,
+    /// Column of uptime over the last 24 hours
+    UptimeDay,
+    /// Heading of recent alerts
+    RecentAlerts,
+    /// Shown when there are no alerts
+    NoAlerts,
+    /// Column of alert times
+    Time,
+    /// Column of alert severities
+    Severity,
+    /// Column of alert descriptions
+    Alert,
+    /// Health status
+    Excellent,
+    /// Health status
+    Good,
+    /// Health status
+    Fair,
+    /// Health status
+    Poor,
+    /// Health status and alert severity
+    Critical,
+    /// Alert severity
+    Warning,
+    /// Alert severity
+    Info,
+    /// Health status without data
+    Unknown,
+}
+
+impl Locale {
+    /// Every supported locale
+    pub const ALL: [Self; 4] = [Self::En, Self::De, Self::Fr, Self::Es];
+
+    /// Language code, as used in the config and the HTML `lang` attribute
+    #[must_use]
+    pub const fn code(self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::De => "de",
+            Self::Fr => "fr",
+            Self::Es => "es",
+        }
+    }
+
+    /// Translation of `message`
+    #[must_use]
+    pub const fn text(self, message: Message) -> &'static str {
+        match self {
+            Self::En => english(message),
+            Self::De => german(message),
+            Self::Fr => french(message),
+            Self::Es => spanish(message),
+        }
+    }
+
+    /// Translation of `message` with each `{}` replaced by the next of `args`
+    #[must_use]
+    pub fn format(self, message: Message, args: &[&str]) -> String {
+        let mut parts = self.text(message).split("{}");
+        let mut text = parts.next().unwrap_or_default().to_string();
+        for (part, arg) in parts.zip(args.iter().chain(std::iter::repeat(&""))) {
+            text.push_str(arg);
+            text.push_str(part);
+        }
+        text
+    }
+
+    /// Translated label of a health status
+    #[must_use]
+    pub const fn health(self, status: HealthStatus) -> &'static str {
+        self.text(match status {
+            HealthStatus::Excellent => Message::Excellent,
+            HealthStatus::Good => Message::Good,
+            HealthStatus::Fair => Message::Fair,
+            HealthStatus::Poor => Message::Poor,
+            HealthStatus::Critical => Message::Critical,
+            HealthStatus::Unknown => Message::Unknown,
+        })
+    }
+
+    /// Translated label of an alert severity
+    #[must_use]
+    pub const fn severity(self, severity: AlertSeverity) -> &'static str {
+        self.text(match severity {
+            AlertSeverity::Critical => Message::Critical,
+            AlertSeverity::Warning => Message::Warning,
+            AlertSeverity::Info => Message::Info,
+        })
+    }
+
+    const fn numbers(self) -> num_format::Locale {
+        match self {
+            Self::En => num_format::Locale::en,
+            Self::De => num_format::Locale::de,
+            Self::Fr => num_format::Locale::fr,
+            Self::Es => num_format::Locale::es,
+        }
+    }
+
+    /// Count with the locale's thousands separator
+    #[must_use]
+    pub fn format_count(self, count: usize) -> String {
+        count.to_formatted_string(&self.numbers())
+    }
+
+    /// Number with `precision` decimals and the locale's separators
+    #[must_use]
+    pub fn format_number(self, value: f64, precision: usize) -> String {
+        if !value.is_finite() {
+            return value.to_string();
+        }
+        let numbers = self.numbers();
+        let fixed = format!("{:.precision$}", value.abs());
+        let (whole, fraction) = fixed.split_once('.').unwrap_or((&fixed, ""));
+        let mut text = String::new();
+        if value.is_sign_negative() && fixed.bytes().any(|b| b.is_ascii_digit() && b != b'0') {
+            text.push_str(numbers.minus_sign());
+        }
+        match whole.parse::<u64>() {
+            Ok(whole) => text.push_str(&whole.to_formatted_string(&numbers)),
+            Err(_) => text.push_str(whole),
+        }
+        if !fraction.is_empty() {
+            text.push_str(numbers.decimal());
+            text.push_str(fraction);
+        }
+        text
+    }
+
+    /// Percentage with one decimal
+    #[must_use]
+    pub fn format_percentage(self, value: f64) -> String {
+        self.format_percentage_with(value, 1)
+    }
+
+    /// Percentage with `precision` decimals
+    #[must_use]
+    pub fn format_percentage_with(self, value: f64, precision: usize) -> String {
+        let number = self.format_number(value, precision);
+        match self {
+            // French typography puts a non-breaking space before the sign
+            Self::Fr => format!("{number}\u{a0}%"),
+            Self::En | Self::De | Self::Es => format!("{number}%"),
+        }
+    }
+
+    /// Latency with two decimals
+    #[must_use]
+    pub fn format_latency_ms(self, value: f64) -> String {
+        format!("{}ms", self.format_number(value, 2))
+    }
+
+    /// Score with one decimal
+    #[must_use]
+    pub fn format_score(self, score: f64) -> String {
+        self.format_number(score, 1)
+    }
+
+    /// Date and time in the locale's usual layout, always in UTC
+    #[must_use]
+    pub fn format_datetime(self, timestamp: &DateTime<Utc>) -> String {
+        let layout = match self {
+            Self::En => "%Y-%m-%d %H:%M:%S UTC",
+            Self::De => "%d.%m.%Y %H:%M:%S UTC",
+            Self::Fr | Self::Es => "%d/%m/%Y %H:%M:%S UTC",
+        };
+        timestamp.format(layout).to_string()
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Locale {
+    type Err = CloudPingError;
+
+    /// Accepts a language code, optionally with a region such as `de-AT` or `fr_CA.UTF-8`
+    fn from_str(value: &str) -> Result<Self> {
+        let language = value
+            .split(['-', '_', '.'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|locale| locale.code() == language)
+            .ok_or_else(|| {
+                let known: Vec<&str> = Self::ALL.iter().map(|locale| locale.code()).collect();
+                CloudPingError::validation(
+                    "locale",
+                    format!(
+                        "unknown locale '{value}', expected one of {}",
+                        known.join(", ")
+                    ),
+                )
+            })
+    }
+}
+
+const fn english(message: Message) -> &'static str {
+    match message {
+        Message::Generated => "Generated {}",
+        Message::Updated => "Updated {}",
+        Message::Environment => "Environment",
+        Message::Labels => "Labels",
+        Message::Ranking => "Ranking",
+        Message::Rank => "Rank",
+        Message::Region => "Region",
+        Message::Score => "Score",
+        Message::Grade => "Grade",
+        Message::Health => "Health",
+        Message::Latency => "Latency",
+        Message::Loss => "Loss",
+        Message::ConcurrencyAdjustments => "Concurrency adjustments",
+        Message::ConcurrencyAdjustmentsNote => {
+            "The reference endpoint slowed down during the run, so fewer regions were tested at once."
+        }
+        Message::ControlNormalizedLatency => "Control-normalized latency",
+        Message::ControlNormalizedNote => {
+            "Control endpoint {}: {} median. Normalized latency subtracts the control latency \
+             measured while each region was tested."
+        }
+        Message::Raw => "Raw",
+        Message::Control => "Control",
+        Message::Normalized => "Normalized",
+        Message::NoAnswer => "no answer",
+        Message::FailedRegions => "Failed regions",
+        Message::Cause => "Cause",
+        Message::Error => "Error",
+        Message::Availability => "Availability",
+        Message::Endpoint => "Endpoint",
+        Message::Endpoints => "Endpoints",
+        Message::Status => "Status",
+        Message::Since => "Since",
+        Message::Up => "Up",
+        Message::Down => "Down",
+        Message::Pending => "Pending",
+        Message::LatencyByTimeOfDay => "Latency by time of day",
+        Message::AverageLatencyPerHour => "Average latency per hour (UTC{}).",
+        Message::Runs => "{} runs",
+        Message::WorstHour => "Worst hour",
+        Message::Trend => "Trend",
+        Message::Value => "Value",
+        Message::GreenestRegion => "Greenest acceptable region: {} ({} gCO2/kWh, score {})",
+        Message::InterRegionLatency => "Region-to-region latency",
+        Message::InterRegionNote => "Estimated round trips in ms from great-circle distance with a route factor of {}. Bold values are measured.",
+        Message::Networks => "Networks (ASN)",
+        Message::AutonomousSystem => "AS",
+        Message::Organization => "Organization",
+        Message::Slow => "Slow",
+        Message::Regions => "Regions",
+        Message::SlowBehindNetwork => "All {} slow regions are behind {}.",
+        Message::LatencyChanges => "Latency changes",
+        Message::UptimeDay => "Uptime (24h)",
+        Message::RecentAlerts => "Recent alerts",
+        Message::NoAlerts => "No alerts.",
+        Message::Time => "Time",
+        Message::Severity => "Severity",
+        Message::Alert => "Alert",
+        Message::Excellent => "Excellent",
+        Message::Good => "Good",
+        Message::Fair => "Fair",
+        Message::Poor => "Poor",
+        Message::Critical => "Critical",
+        Message::Warning => "Warning",
+        Message::Info => "Info",
+        Message::Unknown => "Unknown",
+    }
+}
+
+#[allow(clippy::match_same_arms)] // one arm per message, even where words coincide
+const fn german(message: Message) -> &'static str {
+    match message {
+        Message::Generated => "Erstellt am {}",
+        Message::Updated => "Aktualisiert am {}",
+        Message::Environment => "Umgebung",
+        Message::Labels => "Labels",
+        Message::Ranking => "Rangliste",
+        Message::Rank => "Rang",
+        Message::Region => "Region",
+        Message::Score => "Punktzahl",
+        Message::Grade => "Note",
+        Message::Health => "Zustand",
+        Message::Latency => "Latenz",
+        Message::Loss => "Verlust",
+        Message::ConcurrencyAdjustments => "Anpassungen der Parallelität",
+        Message::ConcurrencyAdjustmentsNote => {
+            "Der Referenz-Endpunkt wurde während des Laufs langsamer, daher wurden weniger \
+             Regionen gleichzeitig getestet."
+        }
+        Message::ControlNormalizedLatency => "Kontrollnormalisierte Latenz",
+        Message::ControlNormalizedNote => {
+            "Kontroll-Endpunkt {}: Median {}. Die normalisierte Latenz zieht die Kontrolllatenz \
+             ab, die während des Tests der jeweiligen Region gemessen wurde."
+        }
+        Message::Raw => "Roh",
+        Message::Control => "Kontrolle",
+        Message::Normalized => "Normalisiert",
+        Message::NoAnswer => "keine Antwort",
+        Message::FailedRegions => "Fehlgeschlagene Regionen",
+        Message::Cause => "Ursache",
+        Message::Error => "Fehler",
+        Message::Availability => "Verfügbarkeit",
+        Message::Endpoint => "Endpunkt",
+        Message::Endpoints => "Endpunkte",
+        Message::Status => "Status",
+        Message::Since => "Seit",
+        Message::Up => "Erreichbar",
+        Message::Down => "Ausgefallen",
+        Message::Pending => "Ausstehend",
+        Message::LatencyByTimeOfDay => "Latenz nach Tageszeit",
+        Message::AverageLatencyPerHour => "Durchschnittliche Latenz pro Stunde (UTC{}).",
+        Message::Runs => "{} Läufe",
+        Message::WorstHour => "Schlechteste Stunde",
+        Message::Trend => "Verlauf",
+        Message::Value => "Preis-Leistung",
+        Message::GreenestRegion => "Klimafreundlichste geeignete Region: {} ({} gCO2/kWh, Punktzahl {})",
+        Message::InterRegionLatency => "Latenz zwischen Regionen",
+        Message::InterRegionNote => "Geschätzte Umlaufzeiten in ms aus der Großkreisentfernung mit einem Routenfaktor von {}. Fett gedruckte Werte sind gemessen.",
+        Message::Networks => "Netzwerke (ASN)",
+        Message::AutonomousSystem => "AS",
+        Message::Organization => "Organisation",
+        Message::Slow => "Langsam",
+        Message::Regions => "Regionen",
+        Message::SlowBehindNetwork => "Alle {} langsamen Regionen liegen hinter {}.",
+        Message::LatencyChanges => "Latenzänderungen",
+        Message::UptimeDay => "Verfügbarkeit (24 h)",
+        Message::RecentAlerts => "Aktuelle Warnungen",
+        Message::NoAlerts => "Keine Warnungen.",
+        Message::Time => "Zeit",
+        Message::Severity => "Schweregrad",
+        Message::Alert => "Warnung",
+        Message::Excellent => "Ausgezeichnet",
+        Message::Good => "Gut",
+        Message::Fair => "Mittel",
+        Message::Poor => "Schlecht",
+        Message::Critical => "Kritisch",
+        Message::Warning => "Warnung",
+        Message::Info => "Info",
+        Message::Unknown => "Unbekannt",
+    }
+}
+
+const fn french(message: Message) -> &'static str {
+    match message {
+        Message::Generated => "Généré le {}",
+        Message::Updated => "Mis à jour le {}",
+        Message::Environment => "Environnement",
+        Message::Labels => "Étiquettes",
+        Message::Ranking => "Classement",
+        Message::Rank => "Rang",
+        Message::Region => "Région",
+        Message::Score => "Score",
+        Message::Grade => "Note",
+        Message::Health => "État",
+        Message::Latency => "Latence",
+        Message::Loss => "Perte",
+        Message::ConcurrencyAdjustments => "Ajustements de la concurrence",
+        Message::ConcurrencyAdjustmentsNote => {
+            "Le point de référence a ralenti pendant l'exécution, moins de régions ont donc été \
+             testées simultanément."
+        }
+        Message::ControlNormalizedLatency => "Latence normalisée par le contrôle",
+        Message::ControlNormalizedNote => {
+            "Point de contrôle {} : médiane {}. La latence normalisée soustrait la latence de \
+             contrôle mesurée pendant le test de chaque région."
+        }
+        Message::Raw => "Brute",
+        Message::Control => "Contrôle",
+        Message::Normalized => "Normalisée",
+        Message::NoAnswer => "pas de réponse",
+        Message::FailedRegions => "Régions en échec",
+        Message::Cause => "Cause",
+        Message::Error => "Erreur",
+        Message::Availability => "Disponibilité",
+        Message::Endpoint => "Point de terminaison",
+        Message::Endpoints => "Points de terminaison",
+        Message::Status => "Statut",
+        Message::Since => "Depuis",
+        Message::Up => "Disponible",
+        Message::Down => "Indisponible",
+        Message::Pending => "En attente",
+        Message::LatencyByTimeOfDay => "Latence selon l'heure",
+        Message::AverageLatencyPerHour => "Latence moyenne par heure (UTC{}).",
+        Message::Runs => "{} exécutions",
+        Message::WorstHour => "Pire heure",
+        Message::Trend => "Tendance",
+        Message::Value => "Rapport qualité-prix",
+        Message::GreenestRegion => "Région acceptable la plus verte : {} ({} gCO2/kWh, score {})",
+        Message::InterRegionLatency => "Latence entre régions",
+        Message::InterRegionNote => "Allers-retours estimés en ms d'après la distance orthodromique avec un facteur de route de {}. Les valeurs en gras sont mesurées.",
+        Message::Networks => "Réseaux (ASN)",
+        Message::AutonomousSystem => "AS",
+        Message::Organization => "Organisation",
+        Message::Slow => "Lentes",
+        Message::Regions => "Régions",
+        Message::SlowBehindNetwork => "Les {} régions lentes sont toutes derrière {}.",
+        Message::LatencyChanges => "Changements de latence",
+        Message::UptimeDay => "Disponibilité (24 h)",
+        Message::RecentAlerts => "Alertes récentes",
+        Message::NoAlerts => "Aucune alerte.",
+        Message::Time => "Heure",
+        Message::Severity => "Gravité",
+        Message::Alert => "Alerte",
+        Message::Excellent => "Excellent",
+        Message::Good => "Bon",
+        Message::Fair => "Moyen",
+        Message::Poor => "Mauvais",
+        Message::Critical => "Critique",
+        Message::Warning => "Avertissement",
+        Message::Info => "Info",
+        Message::Unknown => "Inconnu",
+    }
+}
+
+#[allow(clippy::match_same_arms)] // one arm per message, even where words coincide
+const fn spanish(message: Message) -> &'static str {
+    match message {
+        Message::Generated => "Generado el {}",
+        Message::Updated => "Actualizado el {}",
+        Message::Environment => "Entorno",
+        Message::Labels => "Etiquetas",
+        Message::Ranking => "Clasificación",
+        Message::Rank => "Puesto",
+        Message::Region => "Región",
+        Message::Score => "Puntuación",
+        Message::Grade => "Nota",
+        Message::Health => "Estado",
+        Message::Latency => "Latencia",
+        Message::Loss => "Pérdida",
+        Message::ConcurrencyAdjustments => "Ajustes de concurrencia",
+        Message::ConcurrencyAdjustmentsNote => {
+            "El punto de referencia se ralentizó durante la ejecución, por lo que se probaron \
+             menos regiones a la vez."
+        }
+        Message::ControlNormalizedLatency => "Latencia normalizada por control",
+        Message::ControlNormalizedNote => {
+            "Punto de control {}: mediana {}. La latencia normalizada resta la latencia de \
+             control medida mientras se probaba cada región."
+        }
+        Message::Raw => "Bruta",
+        Message::Control => "Control",
+        Message::Normalized => "Normalizada",
+        Message::NoAnswer => "sin respuesta",
+        Message::FailedRegions => "Regiones fallidas",
+        Message::Cause => "Causa",
+        Message::Error => "Error",
+        Message::Availability => "Disponibilidad",
+        Message::Endpoint => "Punto final",
+        Message::Endpoints => "Puntos finales",
+        Message::Status => "Estado",
+        Message::Since => "Desde",
+        Message::Up => "Activo",
+        Message::Down => "Caído",
+        Message::Pending => "Pendiente",
+        Message::LatencyByTimeOfDay => "Latencia según la hora del día",
+        Message::AverageLatencyPerHour => "Latencia media por hora (UTC{}).",
+        Message::Runs => "{} ejecuciones",
+        Message::WorstHour => "Peor hora",
+        Message::Trend => "Tendencia",
+        Message::Value => "Relación calidad-precio",
+        Message::GreenestRegion => "Región aceptable más ecológica: {} ({} gCO2/kWh, puntuación {})",
+        Message::InterRegionLatency => "Latencia entre regiones",
+        Message::InterRegionNote => "Viajes de ida y vuelta estimados en ms según la distancia ortodrómica con un factor de ruta de {}. Los valores en negrita son medidos.",
+        Message::Networks => "Redes (ASN)",
+        Message::AutonomousSystem => "AS",
+        Message::Organization => "Organización",
+        Message::Slow => "Lentas",
+        Message::Regions => "Regiones",
+        Message::SlowBehindNetwork => "Las {} regiones lentas están todas detrás de {}.",
+        Message::LatencyChanges => "Cambios de latencia",
+        Message::UptimeDay => "Disponibilidad (24 h)",
+        Message::RecentAlerts => "Alertas recientes",
+        Message::NoAlerts => "Sin alertas.",
+        Message::Time => "Hora",
+        Message::Severity => "Gravedad",
+        Message::Alert => "Alerta",
+        Message::Excellent => "Excelente",
+        Message::Good => "Bueno",
+        Message::Fair => "Regular",
+        Message::Poor => "Malo",
+        Message::Critical => "Crítico",
+        Message::Warning => "Advertencia",
+        Message::Info => "Info",
+        Message::Unknown => "Desconocido",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_number_formatting() {
+        assert_eq!(Locale::En.format_number(1234.5, 1), "1,234.5");
+        assert_eq!(Locale::De.format_number(1234.5, 1), "1.234,5");
+        assert_eq!(Locale::Fr.format_number(1234.5, 1), "1\u{202f}234,5");
+        assert_eq!(Locale::De.format_number(-0.04, 1), "0,0");
+        assert_eq!(Locale::De.format_number(-12.0, 0), "-12");
+        assert_eq!(Locale::En.format_latency_ms(45.678), "45.68ms");
+        assert_eq!(Locale::De.format_percentage(95.678), "95,7%");
+        assert_eq!(Locale::Fr.format_percentage(95.678), "95,7\u{a0}%");
+        assert_eq!(Locale::Es.format_count(1_500_000), "1.500.000");
+
+        let timestamp = DateTime::parse_from_rfc3339("2024-12-31T08:05:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            Locale::En.format_datetime(&timestamp),
+            "2024-12-31 08:05:00 UTC"
+        );
+        assert_eq!(
+            Locale::De.format_datetime(&timestamp),
+            "31.12.2024 08:05:00 UTC"
+        );
+    }
+
+    #[test]
+    fn test_locale_parsing_and_messages() {
+        assert_eq!("de".parse::<Locale>().unwrap(), Locale::De);
+        assert_eq!("fr_CA.UTF-8".parse::<Locale>().unwrap(), Locale::Fr);
+        assert_eq!("ES-mx".parse::<Locale>().unwrap(), Locale::Es);
+        assert!("ja".parse::<Locale>().is_err());
+
+        assert_eq!(Locale::En.format(Message::Runs, &["3"]), "3 runs");
+        assert_eq!(
+            Locale::De
+                .format(Message::ControlNormalizedNote, &["a", "b"])
+                .split_once('.')
+                .unwrap()
+                .0,
+            "Kontroll-Endpunkt a: Median b"
+        );
+        assert_eq!(Locale::Fr.health(HealthStatus::Poor), "Mauvais");
+        assert_eq!(Locale::Es.severity(AlertSeverity::Warning), "Advertencia");
+    }
+}
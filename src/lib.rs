@@ -29,23 +29,80 @@ pub mod benchmark;
 pub mod display;
 pub mod data_loader;
 pub mod network;
+pub mod transport;
+pub mod tls;
 pub mod probe;
+pub mod probe_sink;
+pub mod self_metrics;
+pub mod supervisor;
 pub mod aggregator;
+pub mod sharded_aggregator;
+pub mod score_snapshot;
+pub mod events;
+pub mod plugins;
+pub mod otlp;
+pub mod scripting;
+pub mod alerting;
+pub mod collector;
 pub mod monitoring;
+pub mod endpoint_store;
+pub mod environment;
+pub mod report;
+pub mod badge;
+pub mod i18n;
+pub mod server;
+pub mod provider_status;
+pub mod community;
+pub mod feed;
+pub mod pricing;
+pub mod carbon;
+pub mod asn;
+pub mod mmdb;
+pub mod race;
+pub mod websocket;
+pub mod banner;
+pub mod ntp;
+pub mod stun;
+pub mod rate_limit;
+pub mod budget;
+pub mod connection_budget;
+pub mod adaptive;
+pub mod control;
+pub mod doctor;
+pub mod history_store;
+pub mod reload;
+pub mod setup;
+pub mod simulation;
+pub mod soak;
+pub mod trace_sampling;
+pub mod transaction;
+pub mod udp;
+pub mod recipe;
+pub mod speed_test;
+pub mod bufferbloat;
+pub mod wal;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 pub mod ui_utils;
 pub mod time_utils;
 pub mod collection_utils;
 pub mod format_utils;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "geoip")]
+pub mod geoip;
+#[cfg(feature = "capture")]
+pub mod capture;
 
 #[cfg(test)]
 mod tests;
 
 // Re-export commonly used types
-pub use config::{AppConfig, OutputFormat};
+pub use config::{AppConfig, ConfigProfile, OutputFormat};
 pub use error::{CloudPingError, ErrorContext, Result};
 pub use models::{
     CloudProvider, Coordinates, PingStats, Region, TestHistory, PerformanceSummary,
-    Endpoint, ProbeType, AggregatorState, AggregatorStateBuilder, Alert, AlertType, ProbeRecord,
+    Endpoint, ProbeType, BenchmarkRun, TestEnvironment, AgentInfo, AgentReport, AggregatorState, AggregatorStateBuilder, Alert, AlertEnvelope, AlertType, ProbeRecord,
     AlgorithmWeights, ComprehensiveScoreResult, ScoreComponents, HealthStatus, ScoringAdapter
 };
 pub use ui_utils::{ProgressBarFactory, DisplayUtils};
@@ -53,9 +110,32 @@ pub use benchmark::ConnectionBenchmark;
 pub use display::DisplayFormatter;
 pub use data_loader::DataLoader;
 pub use network::NetworkTester;
-pub use monitoring::NetworkMonitoringSystem;
+pub use transport::{HttpTransport, ReqwestTransport};
+pub use monitoring::{MonitoringSettings, NetworkMonitoringSystem};
 pub use probe::ProbeRunner;
 pub use aggregator::StreamingAggregator;
+pub use alerting::{AlertManager, Incident};
+pub use collector::Collector;
+pub use environment::EnvironmentCapture;
+pub use report::{HtmlReport, StatusPage};
+pub use i18n::Locale;
+pub use server::ApiServer;
+pub use provider_status::{OutageCorrelator, ProviderStatusClient};
+pub use community::{CommunityClient, CommunitySubmission};
+pub use pricing::PricingClient;
+pub use carbon::CarbonClient;
+pub use race::ConnectionRacer;
+pub use simulation::{Simulator, SyntheticScenario};
+pub use soak::SoakTest;
+pub use transaction::TransactionRunner;
+pub use udp::UdpReachabilityTest;
+pub use recipe::RecipeRunner;
+pub use speed_test::SpeedTester;
+pub use bufferbloat::BufferbloatTest;
+pub use connection_budget::ConnectionBudget;
+pub use doctor::{Doctor, DoctorReport};
+pub use reload::ConfigWatcher;
+pub use history_store::{HistoryBackend, HistoryStore};
 
 /// Library version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
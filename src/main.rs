@@ -1,10 +1,16 @@
 use clap::{Parser, Subcommand};
 use console::style;
-use tracing::{info, Level};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn, Level};
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 
 use cloud_ping::{
-    AppConfig, ConnectionBenchmark, DisplayFormatter, Result, VERSION,
+    aggregator::AggregatorConfig, AgentInfo, AgentReport, AppConfig, Collector, CommunityClient,
+    CommunitySubmission,
+    models::{AvailabilityLedger, AvailabilityReport, HistoryArchive, TickBudget, WeightProfile},
+    doctor::CheckStatus, setup, CloudPingError, ConnectionBenchmark, DisplayFormatter, DisplayUtils, Doctor, HistoryStore, HtmlReport, OutageCorrelator, OutputFormat, ProviderStatusClient,
+    Result, Simulator, SyntheticScenario, VERSION,
+    ui_utils::TableLayout,
 };
 
 /// Cloud Ping - Network Performance Testing Tool
@@ -21,6 +27,18 @@ struct Cli {
     #[arg(short, long)]
     data_file: Option<String>,
 
+    /// Apply a named profile from the config file (e.g. quick, thorough, gaming)
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Plain ASCII output without emoji or box drawing, overriding `ascii_only`
+    #[arg(long, global = true)]
+    ascii: bool,
+
+    /// Print tables at full width instead of fitting them to the terminal
+    #[arg(long, global = true)]
+    wide: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -30,9 +48,9 @@ struct Cli {
 enum Commands {
     /// Run a comprehensive benchmark
     Benchmark {
-        /// Number of pings per region
-        #[arg(short, long, default_value = "10")]
-        count: usize,
+        /// Number of pings per region (defaults to `default_ping_count`)
+        #[arg(short, long)]
+        count: Option<usize>,
 
         /// Filter by provider name
         #[arg(short, long)]
@@ -41,12 +59,384 @@ enum Commands {
         /// Filter by region name
         #[arg(short, long)]
         region: Option<String>,
+
+        /// Write results as an agent report for a collector to ingest
+        #[arg(long)]
+        agent_report: Option<String>,
+
+        /// Check provider status pages for incidents matching degraded regions
+        #[arg(long)]
+        check_status: bool,
+
+        /// Write an HTML report to this path
+        #[arg(long)]
+        html: Option<String>,
+
+        /// Write SVG score badges for every region and provider into this directory
+        #[arg(long, value_name = "DIR")]
+        badges: Option<String>,
+
+        /// Language of the HTML report: en, de, fr or es (defaults to `locale`)
+        #[arg(long, requires = "html")]
+        locale: Option<cloud_ping::Locale>,
+
+        /// Rank by score, latency, p95, loss, gaming, provider or value (defaults to `sort_by`)
+        #[arg(long)]
+        sort_by: Option<cloud_ping::models::SortKey>,
+
+        /// Only rank regions graded this or better, e.g. B
+        #[arg(long)]
+        min_grade: Option<char>,
+
+        /// Only rank regions of this provider (repeatable)
+        #[arg(long = "only-provider", value_name = "PROVIDER")]
+        only_providers: Vec<String>,
+
+        /// Only rank regions in this country (repeatable)
+        #[arg(long = "only-country", value_name = "COUNTRY")]
+        only_countries: Vec<String>,
+
+        /// Rank at most this many regions per provider
+        #[arg(long)]
+        per_provider: Option<usize>,
+
+        /// Rank at most this many regions
+        #[arg(long)]
+        top: Option<usize>,
+
+        /// Explain the score of regions whose name contains this text (repeatable)
+        #[arg(long, value_name = "REGION")]
+        explain: Vec<String>,
+
+        /// Pick this many regions spanning providers and continents, as set in `footprint`
+        #[arg(long, value_name = "K")]
+        footprint: Option<usize>,
+
+        /// Rank regions by how often a round trip fits the game server tick budget
+        #[arg(long)]
+        gaming: bool,
+
+        /// Server tick rate in Hz for --gaming (defaults to `gaming_tick_rate_hz`)
+        #[arg(long, requires = "gaming")]
+        tick_rate: Option<f64>,
+
+        /// Compare latency with community results from `community_endpoint`
+        #[arg(long)]
+        community: bool,
+
+        /// Show the number of requests, expected duration and data usage without running
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Stop after this long (e.g. "2m") and keep partial results, overriding `run_deadline`
+        #[arg(long, value_parser = humantime::parse_duration)]
+        deadline: Option<std::time::Duration>,
+
+        /// Attach a key=value label to the run (repeatable), added to the `labels` from the config
+        #[arg(long = "label", value_name = "KEY=VALUE", value_parser = cloud_ping::models::parse_label)]
+        labels: Vec<(String, String)>,
     },
     /// Run a quick test with fewer pings
     Quick {
-        /// Number of pings per region (default: 3)
-        #[arg(short, long, default_value = "3")]
-        count: usize,
+        /// Number of pings per region (defaults to `quick_ping_count`)
+        #[arg(short, long)]
+        count: Option<usize>,
+    },
+    /// Merge agent reports into a single multi-vantage ranking
+    Collect {
+        /// Agent report files produced with `benchmark --agent-report`
+        reports: Vec<String>,
+
+        /// Keep taking reports and probe batches pushed by agents over HTTP on
+        /// this address, such as 0.0.0.0:9090; the endpoints are unauthenticated
+        #[arg(long)]
+        listen: Option<std::net::SocketAddr>,
+
+        /// Output format for the vantage matrix
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Write the saved test history of all regions to a portable archive
+    ExportHistory {
+        /// Archive file to create
+        output: String,
+        /// Only export runs from this long ago until now, such as 7d
+        #[arg(long, value_parser = humantime::parse_duration)]
+        since: Option<std::time::Duration>,
+    },
+    /// Show the saved test history in time buckets with average, p95 and loss per bucket
+    Rollup {
+        /// Region name, URL or ID; every region when omitted
+        region: Option<String>,
+        /// Roll up runs from this long ago until now
+        #[arg(long, value_parser = humantime::parse_duration, default_value = "24h")]
+        since: std::time::Duration,
+        /// Bucket length, such as 1m, 1h or 1d
+        #[arg(short, long, value_parser = humantime::parse_duration, default_value = "1h")]
+        interval: std::time::Duration,
+        /// Output format for the rollup
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Merge history archives into the saved test history
+    ImportHistory {
+        /// Archive files produced with `export-history`
+        #[arg(required = true)]
+        archives: Vec<String>,
+    },
+    /// Replay recorded or synthetic probes through scoring and alerting, offline
+    Simulate {
+        /// Recorded probe records, as a JSON array or one record per line
+        #[arg(long, conflicts_with = "synthetic", required_unless_present = "synthetic")]
+        replay: Option<String>,
+
+        /// Scenario file describing synthetic latency and loss per endpoint
+        #[arg(long)]
+        synthetic: Option<String>,
+
+        /// Seed for synthetic generation; the same seed gives the same run
+        #[arg(long, default_value = "0")]
+        seed: u64,
+
+        /// Output format for the simulation report
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Re-rank saved results under candidate scoring weights, without testing again
+    WhatIf {
+        /// Agent report to re-rank; the last saved run of each region is used otherwise
+        #[arg(long)]
+        report: Option<String>,
+
+        /// Candidate weights as NAME=LATENCY,JITTER,LOSS,CONSISTENCY,AVAILABILITY (repeatable)
+        #[arg(short, long = "weights", value_name = "NAME=WEIGHTS")]
+        weights: Vec<WeightProfile>,
+
+        /// Output format for the comparison
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Score and rank round trips measured elsewhere, without testing
+    Score {
+        /// JSON array of endpoints, each with a name, samples in ms (null for a failed probe) and failures
+        file: String,
+
+        /// Write the ranking as an HTML report to this path
+        #[arg(long)]
+        html: Option<String>,
+
+        /// Output format for the ranking
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Estimate round trips between regions from their distance and your measurements
+    Matrix {
+        /// Only include regions of this provider
+        #[arg(short, long)]
+        provider: Option<String>,
+
+        /// Only include regions whose name contains this text
+        #[arg(short, long)]
+        region: Option<String>,
+
+        /// CSV of measured region pairs (from,to,rtt_ms) used instead of estimates
+        #[arg(long)]
+        dataset: Option<String>,
+
+        /// Write the matrix as an HTML heatmap to this path
+        #[arg(long)]
+        html: Option<String>,
+
+        /// Output format for the matrix
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Race connections to every address of each endpoint, IPv6 against IPv4, as browsers do
+    Race {
+        /// Only race regions of this provider
+        #[arg(short, long)]
+        provider: Option<String>,
+
+        /// Only race regions whose name contains this text
+        #[arg(short, long)]
+        region: Option<String>,
+
+        /// Number of races per endpoint
+        #[arg(short = 'n', long, default_value_t = 3)]
+        rounds: usize,
+
+        /// Head start of each connection attempt before the next one begins
+        #[arg(long, value_parser = humantime::parse_duration, default_value = "250ms")]
+        attempt_delay: std::time::Duration,
+
+        /// Output format for the races
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Run a scripted multi-step HTTP check against each region and time every step
+    Check {
+        /// TOML script of the steps to run
+        script: std::path::PathBuf,
+
+        /// Only check regions of this provider
+        #[arg(short, long)]
+        provider: Option<String>,
+
+        /// Only check regions whose name contains this text
+        #[arg(short, long)]
+        region: Option<String>,
+
+        /// Output format for the check results
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Ping regions at a steady rate for a set time and report per-minute p95 and availability
+    Soak {
+        /// Only soak test regions of this provider
+        #[arg(short, long)]
+        provider: Option<String>,
+
+        /// Only soak test regions whose name contains this text
+        #[arg(short, long)]
+        region: Option<String>,
+
+        /// How long to keep pinging
+        #[arg(short, long, value_parser = humantime::parse_duration, default_value = "30m")]
+        duration: std::time::Duration,
+
+        /// Time between pings to each region
+        #[arg(short, long, value_parser = humantime::parse_duration, default_value = "5s")]
+        interval: std::time::Duration,
+
+        /// Width of the time buckets statistics are grouped into
+        #[arg(long, value_parser = humantime::parse_duration, default_value = "1m")]
+        bucket: std::time::Duration,
+
+        /// Output format for the soak test
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Check which UDP ports, such as QUIC on 443, each region answers on from this network
+    Udp {
+        /// Only check regions of this provider
+        #[arg(short, long)]
+        provider: Option<String>,
+
+        /// Only check regions whose name contains this text
+        #[arg(short, long)]
+        region: Option<String>,
+
+        /// Comma-separated ports to check, each optionally followed by /quic, /stun or /udp;
+        /// /stun ports are checked on the configured `stun_servers` rather than the regions
+        #[arg(long, default_value = cloud_ping::models::DEFAULT_UDP_PORTS)]
+        ports: String,
+
+        /// Time to wait for each port to answer
+        #[arg(short, long, value_parser = humantime::parse_duration, default_value = "2s")]
+        timeout: std::time::Duration,
+
+        /// Output format for the reachability matrix
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Check regions against application recipes, such as video-call or git-hosting, and report pass or fail
+    Recipe {
+        /// Recipes to check; every recipe when omitted
+        names: Vec<String>,
+
+        /// List the available recipes and their limits instead of checking
+        #[arg(long)]
+        list: bool,
+
+        /// Only check regions of this provider
+        #[arg(short, long)]
+        provider: Option<String>,
+
+        /// Only check regions whose name contains this text
+        #[arg(short, long)]
+        region: Option<String>,
+
+        /// Number of pings per region (defaults to `quick_ping_count`)
+        #[arg(short = 'n', long)]
+        count: Option<usize>,
+
+        /// Time to wait for each UDP port a recipe needs to answer
+        #[arg(long, value_parser = humantime::parse_duration, default_value = "2s")]
+        udp_timeout: std::time::Duration,
+
+        /// Output format for the results
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Measure latency under load: round trips while bulk downloads saturate the link, graded per region
+    Bufferbloat {
+        /// Only test regions of this provider
+        #[arg(short, long)]
+        provider: Option<String>,
+
+        /// Only test regions whose name contains this text
+        #[arg(short, long)]
+        region: Option<String>,
+
+        /// Round trips per region, idle and under load each (defaults to `quick_ping_count`)
+        #[arg(short = 'n', long)]
+        count: Option<usize>,
+
+        /// Bytes each of the parallel downloads fetches; raise it for links
+        /// fast enough to finish the downloads before round trips are taken
+        #[arg(long, default_value_t = 10_000_000)]
+        bytes: u64,
+
+        /// Download URL loading the link, instead of each region's speed-test endpoint or the Cloudflare speed API
+        #[arg(long)]
+        load_url: Option<String>,
+
+        /// Output format for the results
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Check the config, data file, network access and clock for common problems
+    Doctor {
+        /// Output format for the report
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Manage the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Monitor all regions continuously and serve a status page over HTTP
+    Monitor {
+        /// Address for the status page and JSON API to listen on
+        #[arg(short, long, default_value = "127.0.0.1:8080")]
+        listen: std::net::SocketAddr,
+    },
+    /// Run as a remote agent controlled over gRPC
+    #[cfg(feature = "grpc")]
+    Agent {
+        /// Address for the gRPC control plane to listen on
+        #[arg(short, long, default_value = "127.0.0.1:50051")]
+        listen: std::net::SocketAddr,
+    },
+}
+
+/// Config file commands
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Write a commented config file with default settings
+    Init {
+        /// File to write (defaults to the standard config location)
+        #[arg(long)]
+        path: Option<std::path::PathBuf>,
+
+        /// Overwrite an existing file
+        #[arg(long)]
+        force: bool,
+
+        /// Ask for the most common settings before writing
+        #[arg(short, long)]
+        interactive: bool,
     },
 }
 
@@ -65,18 +455,110 @@ async fn run(cli: Cli) -> Result<()> {
     init_logging(cli.verbose);
     
     info!("Starting Cloud Ping RS v{}", VERSION);
+    DisplayUtils::set_ascii_only(cli.ascii);
+    DisplayUtils::set_table_layout(TableLayout {
+        wide: cli.wide,
+        ..TableLayout::new()
+    });
+
+    // Writing a new config file must not depend on the current one loading
+    if let Some(Commands::Config { action: ConfigAction::Init { path, force, interactive } }) = &cli.command {
+        return init_config(path.clone(), *force, *interactive);
+    }
+
+    // The doctor reports a broken config instead of falling back to defaults
+    if let Some(Commands::Doctor { format }) = &cli.command {
+        return run_doctor(cli.profile.as_deref(), cli.data_file, format).await;
+    }
     
     // Load configuration
-    let config = AppConfig::load().unwrap_or_else(|e| {
-        eprintln!("Warning: Failed to load config, using defaults: {}", e);
-        AppConfig::default()
+    let mut config = match &cli.profile {
+        Some(profile) => AppConfig::load_profile(profile)?,
+        None => AppConfig::load().unwrap_or_else(|e| {
+            eprintln!("Warning: Failed to load config, using defaults: {}", e);
+            AppConfig::default()
+        }),
+    };
+    config.ascii_only |= cli.ascii;
+    DisplayUtils::set_ascii_only(config.ascii_only);
+    DisplayUtils::set_table_layout(TableLayout {
+        wide: cli.wide,
+        drop_order: config.table_drop_order.clone(),
     });
+    if let Some(Commands::Benchmark {
+        deadline,
+        labels,
+        sort_by,
+        min_grade,
+        only_providers,
+        only_countries,
+        per_provider,
+        top,
+        footprint,
+        ..
+    }) = &cli.command
+    {
+        if let Some(deadline) = deadline {
+            config.run_deadline = Some(*deadline);
+        }
+        if let Some(sort_by) = sort_by {
+            config.sort_by = *sort_by;
+        }
+        let filter = &mut config.report_filter;
+        if let Some(grade) = min_grade {
+            filter.min_grade = Some(grade.to_ascii_uppercase());
+        }
+        if !only_providers.is_empty() {
+            filter.providers.clone_from(only_providers);
+        }
+        if !only_countries.is_empty() {
+            filter.countries.clone_from(only_countries);
+        }
+        filter.per_provider = per_provider.or(filter.per_provider);
+        filter.limit = top.or(filter.limit);
+        filter.validate()?;
+        if let Some(count) = footprint {
+            config.footprint = config.footprint.clone().with_count(*count);
+            config.footprint.validate()?;
+        }
+        config.labels.extend(labels.iter().cloned());
+    }
     
+    // Collector mode works on pushed reports and needs no local data file
+    if let Some(Commands::Collect { reports, listen, format }) = &cli.command {
+        return run_collector(&config, reports, *listen, format).await;
+    }
+
+    // History archives only touch the history store
+    match &cli.command {
+        Some(Commands::ExportHistory { output, since }) => return export_history(&config, output, *since).await,
+        Some(Commands::ImportHistory { archives }) => return import_history(&config, archives).await,
+        Some(Commands::Rollup { region, since, interval, format }) => {
+            return history_rollup(&config, region.as_deref(), *since, *interval, format).await;
+        }
+        _ => {}
+    }
+
+    // Simulations run entirely offline
+    if let Some(Commands::Simulate { replay, synthetic, seed, format }) = &cli.command {
+        return run_simulation(&config, replay.as_deref(), synthetic.as_deref(), *seed, format).await;
+    }
+    if let Some(Commands::WhatIf { report, weights, format }) = &cli.command {
+        return run_what_if(&config, report.as_deref(), weights, format).await;
+    }
+    if let Some(Commands::Score { file, html, format }) = &cli.command {
+        return score_external_samples(&config, file, html.as_deref(), format).await;
+    }
+
     // Use custom data file if specified
     let data_file = cli.data_file.unwrap_or_else(|| config.data_file.clone());
     
     // Run the benchmark
-    let mut benchmark = ConnectionBenchmark::new(config)?;
+    // One connection cap shared by the benchmark and, in agent and monitor modes, the probes
+    let connection_budget = cloud_ping::ConnectionBudget::new(config.max_connections);
+    let mut benchmark = ConnectionBenchmark::builder(config)
+        .connection_budget(connection_budget)
+        .build()?;
     benchmark.load_cloud_providers(&data_file).await?;
     
     // Check if regions were loaded
@@ -88,20 +570,196 @@ async fn run(cli: Cli) -> Result<()> {
     
     // Execute the appropriate command
     match cli.command {
-        Some(Commands::Benchmark { count, provider, region }) => {
+        Some(Commands::Benchmark { count, provider, region, agent_report, check_status, html, badges, explain, footprint, locale, gaming, tick_rate, community, dry_run, .. }) => {
+            let count = count.unwrap_or(benchmark.config().default_ping_count);
+            if dry_run {
+                DisplayFormatter::display_plan(&benchmark.plan(count, provider, region)?);
+                return Ok(());
+            }
+
             info!("Running benchmark with {} pings per region", count);
-            let results = benchmark.run_filtered_benchmark(count, provider, region).await?;
-            display_results(&results, &benchmark);
+            let history_store = cloud_ping::history_store::open(benchmark.config()).await?;
+            if let Some(store) = &history_store {
+                // Runs of this benchmark are buffered until the store is back
+                match store.load_history().await {
+                    Ok(histories) => benchmark.restore_test_history(histories),
+                    Err(e) => warn!("Failed to load test history, comparing with no earlier runs: {}", e),
+                }
+            }
+
+            let run = benchmark
+                .run_benchmark_with_environment(count, provider, region, &cancel_on_interrupt())
+                .await?;
+            // JSON and CSV output carries only the machine-readable reports
+            let table = matches!(benchmark.config().output_format, OutputFormat::Table);
+            if table {
+                DisplayFormatter::display_test_environment(&run.environment);
+                if !run.labels.is_empty() {
+                    println!("Labels: {}", cloud_ping::models::labels_summary(&run.labels));
+                }
+                display_results(&run.results, &benchmark);
+            }
+            print_ranking(&benchmark.ranking(&run.results), &benchmark)?;
+            explain_scores(&run.results, &explain, &benchmark)?;
+            if footprint.is_some() {
+                print_footprint(&run.results, &benchmark)?;
+            }
+            if table {
+                if run.incomplete {
+                    DisplayFormatter::display_incomplete_run(&run.results);
+                }
+                DisplayFormatter::display_failed_regions(&run.failures);
+                DisplayFormatter::display_concurrency_adjustments(&run.concurrency_adjustments);
+                if let Some(control) = &run.control {
+                    DisplayFormatter::display_normalized_latency(&run.results, control);
+                }
+                DisplayFormatter::display_beyond_isp_latency(&run.results, &run.environment);
+                DisplayFormatter::display_data_usage(&run.results);
+            } else {
+                if run.incomplete {
+                    warn!("The run stopped early, so these results are partial");
+                }
+                if !run.failures.is_empty() {
+                    warn!("{} regions failed", run.failures.len());
+                }
+            }
+
+            if gaming && table {
+                let budget = TickBudget::new(tick_rate.unwrap_or(benchmark.config().gaming_tick_rate_hz))?;
+                DisplayFormatter::display_tick_budget(&benchmark.scorable_results(&run.results), &budget);
+            }
+
+            if check_status {
+                check_provider_status(&run.results, &benchmark).await?;
+            }
+
+            let availability = update_availability_ledger(&run, benchmark.config())?;
+
+            if community || benchmark.config().community_sharing {
+                share_with_community(&run, &benchmark, community).await?;
+            }
+
+            let local_offset = *chrono::Local::now().offset();
+            let histories = benchmark.get_all_test_histories();
+            if let Some(store) = &history_store {
+                let wal = std::path::Path::new(&benchmark.config().history_wal_file);
+                cloud_ping::history_store::save_buffered(store.as_ref(), wal, &histories).await?;
+                if let Some(policy) = &benchmark.config().history_retention {
+                    // The run is saved already; a failed compaction is retried after the next one
+                    let now = cloud_ping::time_utils::TimeUtils::now();
+                    if let Err(e) = cloud_ping::history_store::compact(store.as_ref(), policy, now).await {
+                        warn!("Failed to compact test history: {}", e);
+                    }
+                }
+                if table {
+                    DisplayFormatter::display_time_of_day(&histories, local_offset);
+                    DisplayFormatter::display_change_points(&histories);
+                    DisplayFormatter::display_route_changes(&histories);
+                }
+            }
+
+            if let Some(path) = html {
+                let mut report = HtmlReport::new("Cloud Ping Report")
+                    .locale(locale.unwrap_or(benchmark.config().locale))
+                    .sort_by(benchmark.config().sort_by)
+                    .filter(benchmark.config().report_filter.clone())
+                    .green(benchmark.config().green.clone())
+                    .results(&benchmark.scorable_results(&run.results), benchmark.weights())
+                    .environment(run.environment.clone())
+                    .labels(run.labels.clone())
+                    .failures(run.failures.clone())
+                    .concurrency_adjustments(run.concurrency_adjustments.clone());
+                if let Some(control) = &run.control {
+                    report = report.control(control.clone());
+                }
+                if let Some(availability) = availability {
+                    report = report.availability(availability);
+                }
+                if history_store.is_some() {
+                    report = report.history(histories, local_offset);
+                }
+                report.write(std::path::Path::new(&path)).await?;
+                info!("Wrote HTML report to {}", path);
+            }
+
+            if let Some(dir) = badges {
+                let ranked = cloud_ping::models::ScoringAdapter::get_sorted_results(
+                    &benchmark.scorable_results(&run.results),
+                    benchmark.weights(),
+                );
+                let written = cloud_ping::badge::write_badges(std::path::Path::new(&dir), &ranked)?;
+                info!("Wrote {} badges to {}", written, dir);
+            }
+
+            if let Some(path) = agent_report {
+                write_agent_report(&path, benchmark.config(), run).await?;
+            }
         }
         Some(Commands::Quick { count }) => {
+            let count = count.unwrap_or(benchmark.config().quick_ping_count);
             info!("Running quick test with {} pings per region", count);
             let results = benchmark.run_filtered_benchmark(count, None, None).await?;
             display_results(&results, &benchmark);
+            DisplayFormatter::display_data_usage_total(&results);
+        }
+        Some(Commands::Matrix { provider, region, dataset, html, format }) => {
+            let regions = benchmark.collect_filtered_regions(provider, region);
+            latency_matrix(&regions, benchmark.config(), dataset.as_deref(), html.as_deref(), &format).await?;
+        }
+        Some(Commands::Race { provider, region, rounds, attempt_delay, format }) => {
+            let regions = benchmark.collect_filtered_regions(provider, region);
+            race_connections(&regions, benchmark.config(), rounds, attempt_delay, &format).await?;
+        }
+        Some(Commands::Check { script, provider, region, format }) => {
+            let regions = benchmark.collect_filtered_regions(provider, region);
+            run_transaction_check(&regions, benchmark.config(), &script, &format).await?;
+        }
+        Some(Commands::Soak { provider, region, duration, interval, bucket, format }) => {
+            let regions = benchmark.collect_filtered_regions(provider, region);
+            soak_test(&regions, benchmark.config(), duration, interval, bucket, &format).await?;
         }
+        Some(Commands::Udp { provider, region, ports, timeout, format }) => {
+            let regions = benchmark.collect_filtered_regions(provider, region);
+            udp_matrix(&regions, benchmark.config(), &ports, timeout, &format).await?;
+        }
+        Some(Commands::Recipe { names, list, provider, region, count, udp_timeout, format }) => {
+            let recipes = cloud_ping::models::Recipe::resolve(&names, &benchmark.config().recipes)?;
+            if list {
+                DisplayFormatter::display_recipes(&recipes);
+            } else {
+                let regions = benchmark.collect_filtered_regions(provider, region);
+                let count = count.unwrap_or(benchmark.config().quick_ping_count);
+                check_recipes(&regions, benchmark.config(), &recipes, count, udp_timeout, &format).await?;
+            }
+        }
+        Some(Commands::Bufferbloat { provider, region, count, bytes, load_url, format }) => {
+            let regions = benchmark.collect_filtered_regions(provider, region);
+            let count = count.unwrap_or(benchmark.config().quick_ping_count);
+            measure_bufferbloat(&regions, benchmark.config(), count, bytes, load_url, &format).await?;
+        }
+        Some(Commands::Monitor { listen }) => {
+            info!("Monitoring {} regions with status page on {}", all_regions.len(), listen);
+            run_status_server(&benchmark, &all_regions, listen, cli.profile).await?;
+        }
+        #[cfg(feature = "grpc")]
+        Some(Commands::Agent { listen }) => {
+            info!("Running as remote agent on {}", listen);
+            run_agent(benchmark, &all_regions, listen, cli.profile).await?;
+        }
+        Some(Commands::Collect { .. }) => unreachable!("collector mode handled above"),
+        Some(Commands::ExportHistory { .. } | Commands::ImportHistory { .. } | Commands::Rollup { .. }) => {
+            unreachable!("history commands handled above")
+        }
+        Some(Commands::Simulate { .. }) => unreachable!("simulation handled above"),
+        Some(Commands::WhatIf { .. }) => unreachable!("what-if comparison handled above"),
+        Some(Commands::Score { .. }) => unreachable!("scoring of external samples handled above"),
+        Some(Commands::Config { .. }) => unreachable!("config commands handled above"),
+        Some(Commands::Doctor { .. }) => unreachable!("doctor handled above"),
         None => {
-            // Default: run benchmark with 10 pings
-            info!("Running default benchmark with 10 pings per region");
-            let results = benchmark.run_filtered_benchmark(10, None, None).await?;
+            // Default: run benchmark with `default_ping_count` pings
+            let count = benchmark.config().default_ping_count;
+            info!("Running default benchmark with {} pings per region", count);
+            let results = benchmark.run_filtered_benchmark(count, None, None).await?;
             display_results(&results, &benchmark);
         }
     }
@@ -109,6 +767,779 @@ async fn run(cli: Cli) -> Result<()> {
     Ok(())
 }
 
+/// Token cancelled by the first Ctrl-C so a run can stop with partial results;
+/// a second Ctrl-C exits immediately
+fn cancel_on_interrupt() -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let on_interrupt = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("Interrupted, stopping with partial results (Ctrl-C again to abort)");
+            on_interrupt.cancel();
+        }
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+    cancel
+}
+
+/// Configured history store, required by the archive commands
+async fn history_store(config: &AppConfig) -> Result<Box<dyn HistoryStore>> {
+    cloud_ping::history_store::open(config)
+        .await?
+        .ok_or_else(|| cloud_ping::CloudPingError::config("history_file is not set in the configuration"))
+}
+
+/// Write the saved test history, or its runs since some time ago, to a versioned archive
+async fn export_history(config: &AppConfig, output: &str, since: Option<std::time::Duration>) -> Result<()> {
+    let from = match since {
+        Some(since) => {
+            let since = chrono::Duration::from_std(since)
+                .map_err(|_| CloudPingError::validation("since", "is too long"))?;
+            cloud_ping::time_utils::TimeUtils::now() - since
+        }
+        None => chrono::DateTime::<chrono::Utc>::MIN_UTC,
+    };
+    let histories = history_store(config)
+        .await?
+        .query_range(from, chrono::DateTime::<chrono::Utc>::MAX_UTC)
+        .await?;
+    let runs: usize = histories.iter().map(|history| history.historical_data.len()).sum();
+    HistoryArchive::new(histories).write(std::path::Path::new(output))?;
+    println!("Exported {} runs to {}", runs, output);
+    Ok(())
+}
+
+/// Merge archives into the saved test history
+async fn import_history(config: &AppConfig, archives: &[String]) -> Result<()> {
+    let store = history_store(config).await?;
+    for archive in archives {
+        let added = store
+            .save_stats(&HistoryArchive::read(std::path::Path::new(archive))?.histories)
+            .await?;
+        println!("Imported {} new runs from {}", added, archive);
+    }
+    Ok(())
+}
+
+/// Print the saved runs from `since` ago until now in buckets of `interval`
+async fn history_rollup(
+    config: &AppConfig,
+    region: Option<&str>,
+    since: std::time::Duration,
+    interval: std::time::Duration,
+    format: &OutputFormat,
+) -> Result<()> {
+    let to = cloud_ping::time_utils::TimeUtils::now();
+    let from = chrono::Duration::from_std(since)
+        .ok()
+        .and_then(|since| to.checked_sub_signed(since))
+        .ok_or_else(|| CloudPingError::validation("since", "is too long"))?;
+    let store = history_store(config).await?;
+    let rollups = cloud_ping::history_store::rollup(store.as_ref(), region, interval, from, to).await?;
+
+    match format {
+        OutputFormat::Table => DisplayFormatter::display_rollups(&rollups),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&rollups)?),
+        OutputFormat::Csv => print!("{}", cloud_ping::models::Rollup::to_csv(&rollups)),
+    }
+    Ok(())
+}
+
+/// Write a commented config file, optionally filled in through the setup wizard
+fn init_config(path: Option<std::path::PathBuf>, force: bool, interactive: bool) -> Result<()> {
+    let path = path
+        .or_else(AppConfig::config_path)
+        .ok_or_else(|| CloudPingError::config("Cannot determine config directory"))?;
+    if path.exists() && !force {
+        return Err(CloudPingError::config(format!(
+            "{} already exists (use --force to overwrite)",
+            path.display()
+        )));
+    }
+
+    let config = if interactive {
+        setup::run_wizard(AppConfig::default())?
+    } else {
+        AppConfig::default()
+    };
+    setup::write_commented_config(&config, &path, force)?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+/// Run the environment checks and fail when any check failed
+async fn run_doctor(profile: Option<&str>, data_file: Option<String>, format: &OutputFormat) -> Result<()> {
+    let loaded = match profile {
+        Some(profile) => AppConfig::load_profile(profile),
+        None => AppConfig::load(),
+    };
+    let report = Doctor::new(loaded, data_file).run().await;
+
+    match format {
+        OutputFormat::Table => DisplayFormatter::display_doctor_report(&report),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Csv => print!("{}", report.to_csv()),
+    }
+
+    if report.passed() {
+        Ok(())
+    } else {
+        Err(CloudPingError::validation(
+            "doctor",
+            format!("{} check(s) failed", report.count(CheckStatus::Fail)),
+        ))
+    }
+}
+
+/// Run recorded or synthetic probes through the monitoring pipeline and report the outcome
+async fn run_simulation(
+    config: &AppConfig,
+    replay: Option<&str>,
+    synthetic: Option<&str>,
+    seed: u64,
+    format: &OutputFormat,
+) -> Result<()> {
+    let records = match (replay, synthetic) {
+        (Some(path), _) => cloud_ping::simulation::load_records(std::path::Path::new(path))?,
+        (None, Some(path)) => SyntheticScenario::load(std::path::Path::new(path))?.generate(seed)?,
+        (None, None) => unreachable!("clap requires --replay or --synthetic"),
+    };
+
+    let report = Simulator::new(monitoring_config(config).aggregator_config)
+        .run(records)
+        .await;
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Table | OutputFormat::Csv => DisplayFormatter::display_simulation(&report),
+    }
+    Ok(())
+}
+
+/// The last saved run of each region in `store`
+async fn last_saved_runs(store: &dyn HistoryStore) -> Result<Vec<(String, cloud_ping::PingStats)>> {
+    Ok(store
+        .load_history()
+        .await?
+        .into_iter()
+        .filter_map(|history| {
+            let last = history.historical_data.last()?.clone();
+            Some((history.region_name, last))
+        })
+        .collect())
+}
+
+/// Estimate region-to-region latency, calibrated on the last saved run of each region
+async fn latency_matrix(
+    regions: &[cloud_ping::models::Region],
+    config: &AppConfig,
+    dataset: Option<&str>,
+    html: Option<&str>,
+    format: &OutputFormat,
+) -> Result<()> {
+    use cloud_ping::models::{InterRegionDataset, LatencyMatrix};
+
+    let dataset = dataset
+        .map(|path| InterRegionDataset::load(std::path::Path::new(path)))
+        .transpose()?
+        .unwrap_or_default();
+    let measurements = match cloud_ping::history_store::open(config).await? {
+        Some(store) => last_saved_runs(&*store).await?,
+        None => Vec::new(),
+    };
+    let matrix = LatencyMatrix::estimate(regions, &measurements, config.client_coordinates.as_ref(), &dataset);
+
+    match format {
+        OutputFormat::Table => DisplayFormatter::display_latency_matrix(&matrix),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&matrix)?),
+        OutputFormat::Csv => print!("{}", matrix.to_csv()),
+    }
+    if let Some(path) = html {
+        HtmlReport::new("Region-to-region latency")
+            .locale(config.locale)
+            .latency_matrix(matrix)
+            .write(std::path::Path::new(path))
+            .await?;
+        info!("Wrote latency matrix to {}", path);
+    }
+    Ok(())
+}
+
+/// Race connections to the addresses of each region's endpoint
+///
+/// Endpoints are raced up to `max_threads` at a time; regions whose host
+/// does not resolve are skipped with a warning.
+async fn race_connections(
+    regions: &[cloud_ping::models::Region],
+    config: &AppConfig,
+    rounds: usize,
+    attempt_delay: std::time::Duration,
+    format: &OutputFormat,
+) -> Result<()> {
+    use cloud_ping::models::RaceResult;
+    use futures::stream::{self, StreamExt};
+
+    let racer = cloud_ping::ConnectionRacer::new(attempt_delay, config.get_timeout());
+    let results: Vec<RaceResult> = stream::iter(regions)
+        .map(|region| {
+            let racer = &racer;
+            async move {
+                racer
+                    .race_region(region, rounds.max(1))
+                    .await
+                    .map_err(|e| tracing::warn!("Skipping {}: {}", region.name, e))
+                    .ok()
+            }
+        })
+        .buffered(config.max_threads.max(1))
+        .filter_map(std::future::ready)
+        .collect()
+        .await;
+
+    match format {
+        OutputFormat::Table => DisplayFormatter::display_race_results(&results),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&results)?),
+        OutputFormat::Csv => print!("{}", RaceResult::to_csv(&results)),
+    }
+    Ok(())
+}
+
+/// Run the transaction in `script` against every region, `max_threads` at a time
+async fn run_transaction_check(
+    regions: &[cloud_ping::models::Region],
+    config: &AppConfig,
+    script: &std::path::Path,
+    format: &OutputFormat,
+) -> Result<()> {
+    use cloud_ping::models::{Transaction, TransactionResult};
+    use futures::stream::{self, StreamExt};
+
+    let transaction = Transaction::load(script)?;
+    let runner = cloud_ping::TransactionRunner::new(config)?;
+    let results: Vec<TransactionResult> = stream::iter(regions)
+        .map(|region| runner.run(&transaction, region))
+        .buffered(config.max_threads.max(1))
+        .collect()
+        .await;
+
+    match format {
+        OutputFormat::Table => DisplayFormatter::display_transaction_results(&results),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&results)?),
+        OutputFormat::Csv => print!("{}", TransactionResult::to_csv(&results)),
+    }
+    Ok(())
+}
+
+/// Soak test `regions` until `duration` is up or the run is interrupted
+async fn soak_test(
+    regions: &[cloud_ping::models::Region],
+    config: &AppConfig,
+    duration: std::time::Duration,
+    interval: std::time::Duration,
+    bucket: std::time::Duration,
+    format: &OutputFormat,
+) -> Result<()> {
+    use cloud_ping::models::SoakResult;
+
+    let tester = cloud_ping::NetworkTester::new(config.clone())?;
+    let soak = cloud_ping::SoakTest::new(tester, duration, interval, bucket)?;
+    // On stderr so JSON and CSV output stays parseable
+    eprintln!(
+        "Soak testing {} regions every {} for {} (Ctrl-C stops early)",
+        regions.len(),
+        humantime::format_duration(interval),
+        humantime::format_duration(duration)
+    );
+    let results = soak.run(regions, &cancel_on_interrupt()).await;
+
+    match format {
+        OutputFormat::Table => DisplayFormatter::display_soak_results(&results),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&results)?),
+        OutputFormat::Csv => print!("{}", SoakResult::to_csv(&results)),
+    }
+    Ok(())
+}
+
+/// Check `ports` of `regions`, and STUN ports of the configured STUN
+/// servers, and show which answer from this network
+async fn udp_matrix(
+    regions: &[cloud_ping::models::Region],
+    config: &AppConfig,
+    ports: &str,
+    timeout: std::time::Duration,
+    format: &OutputFormat,
+) -> Result<()> {
+    use cloud_ping::models::{UdpMatrixRow, UdpPort, UdpProtocol};
+
+    let (stun, ports): (Vec<UdpPort>, Vec<UdpPort>) = UdpPort::parse_list(ports)?
+        .into_iter()
+        .partition(|port| port.protocol == UdpProtocol::Stun);
+    if !stun.is_empty() && config.stun_servers.is_empty() {
+        return Err(CloudPingError::validation("stun_servers", "must list a STUN server to check /stun ports on"));
+    }
+    let mut rows = if ports.is_empty() {
+        Vec::new()
+    } else {
+        cloud_ping::UdpReachabilityTest::new(ports, timeout)?.run(regions).await
+    };
+    if !stun.is_empty() {
+        let test = cloud_ping::UdpReachabilityTest::new(stun, timeout)?;
+        for server in &config.stun_servers {
+            rows.push(test.check_host(server).await);
+        }
+    }
+
+    match format {
+        OutputFormat::Table => DisplayFormatter::display_udp_matrix(&rows),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+        OutputFormat::Csv => print!("{}", UdpMatrixRow::to_csv(&rows)),
+    }
+    Ok(())
+}
+
+/// Check `regions` against `recipes` and show pass or fail per region and recipe
+async fn check_recipes(
+    regions: &[cloud_ping::models::Region],
+    config: &AppConfig,
+    recipes: &[(String, cloud_ping::models::Recipe)],
+    count: usize,
+    udp_timeout: std::time::Duration,
+    format: &OutputFormat,
+) -> Result<()> {
+    use cloud_ping::models::RecipeResult;
+
+    let runner = cloud_ping::RecipeRunner::new(config, count, udp_timeout)?;
+    let results = runner.run(recipes, regions).await?;
+
+    match format {
+        OutputFormat::Table => DisplayFormatter::display_recipe_results(&results),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&results)?),
+        OutputFormat::Csv => print!("{}", RecipeResult::to_csv(&results)),
+    }
+    Ok(())
+}
+
+/// Measure latency under load to `regions` and show the bufferbloat grade of each
+async fn measure_bufferbloat(
+    regions: &[cloud_ping::models::Region],
+    config: &AppConfig,
+    count: usize,
+    bytes: u64,
+    load_url: Option<String>,
+    format: &OutputFormat,
+) -> Result<()> {
+    use cloud_ping::models::BufferbloatResult;
+
+    let test = cloud_ping::BufferbloatTest::new(config, count, bytes, load_url)?;
+    eprintln!("Loading the link with {}", test.describe_load(regions));
+    let results = test.run(regions).await;
+
+    match format {
+        OutputFormat::Table => DisplayFormatter::display_bufferbloat(&results),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&results)?),
+        OutputFormat::Csv => print!("{}", BufferbloatResult::to_csv(&results)),
+    }
+    Ok(())
+}
+
+/// Compare the ranking of saved results under the current and candidate weights
+///
+/// Candidates are the weights of config profiles followed by `--weights`.
+async fn run_what_if(
+    config: &AppConfig,
+    report: Option<&str>,
+    candidates: &[WeightProfile],
+    format: &OutputFormat,
+) -> Result<()> {
+    let results: Vec<(String, cloud_ping::PingStats)> = match report {
+        Some(path) => serde_json::from_str::<AgentReport>(&std::fs::read_to_string(path)?)?.results,
+        None => last_saved_runs(&*history_store(config).await?).await?,
+    };
+    if results.is_empty() {
+        return Err(CloudPingError::validation("report", "no saved results to compare"));
+    }
+
+    let mut profiles = vec![WeightProfile::new("current", config.weights.clone().unwrap_or_default())];
+    profiles.extend(config.profiles.iter().filter_map(|(name, profile)| {
+        profile.weights.clone().map(|weights| WeightProfile::new(name.clone(), weights))
+    }));
+    profiles.extend(candidates.iter().cloned());
+    if profiles.len() < 2 {
+        return Err(CloudPingError::validation(
+            "weights",
+            "nothing to compare; pass --weights or set weights in a config profile",
+        ));
+    }
+
+    let comparison = cloud_ping::ScoringAdapter::compare_weights(&results, &profiles);
+    match format {
+        OutputFormat::Table => DisplayFormatter::display_what_if(&comparison),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&comparison)?),
+        OutputFormat::Csv => print!("{}", comparison.to_csv()),
+    }
+    Ok(())
+}
+
+/// Score and rank round trips measured elsewhere, as a benchmark of the same regions would
+async fn score_external_samples(
+    config: &AppConfig,
+    file: &str,
+    html: Option<&str>,
+    format: &OutputFormat,
+) -> Result<()> {
+    use cloud_ping::models::ExternalSamples;
+
+    let samples: Vec<ExternalSamples> = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+    if samples.is_empty() {
+        return Err(CloudPingError::validation("file", "no samples to score"));
+    }
+    let results = samples
+        .into_iter()
+        .map(|samples| samples.jitter_algorithm(config.jitter_algorithm).result())
+        .collect::<Result<Vec<_>>>()?;
+
+    let benchmark = ConnectionBenchmark::builder(AppConfig {
+        output_format: format.clone(),
+        ..config.clone()
+    })
+    .build()?;
+    if matches!(format, OutputFormat::Table) {
+        display_results(&results, &benchmark);
+    }
+    print_ranking(&benchmark.ranking(&results), &benchmark)?;
+    if let Some(path) = html {
+        HtmlReport::new("Cloud Ping Report")
+            .locale(config.locale)
+            .sort_by(config.sort_by)
+            .filter(config.report_filter.clone())
+            .green(config.green.clone())
+            .results(&benchmark.scorable_results(&results), benchmark.weights())
+            .write(std::path::Path::new(path))
+            .await?;
+        info!("Wrote HTML report to {}", path);
+    }
+    Ok(())
+}
+
+/// Monitoring settings derived from the application config
+fn monitoring_config(config: &AppConfig) -> cloud_ping::monitoring::MonitoringConfig {
+    let mut monitoring_config = cloud_ping::monitoring::MonitoringConfig {
+        availability_ledger_path: config.availability_ledger.as_ref().map(std::path::PathBuf::from),
+        ..Default::default()
+    };
+    config.monitoring.apply(&mut monitoring_config);
+    if let Some(weights) = &config.weights {
+        monitoring_config.aggregator_config.weights = weights.clone();
+    }
+    monitoring_config.aggregator_config.jitter_algorithm = config.jitter_algorithm;
+    monitoring_config.probe_config.max_requests_per_host_per_second =
+        config.max_requests_per_host_per_second;
+    monitoring_config.probe_config.budget = config.probe_budget.clone();
+    monitoring_config.probe_config.trace_sampling = config.trace_sampling;
+    monitoring_config
+}
+
+/// Monitoring settings whose probes share the benchmark's connection budget
+fn shared_monitoring_config(benchmark: &ConnectionBenchmark) -> cloud_ping::monitoring::MonitoringConfig {
+    let mut monitoring_config = monitoring_config(benchmark.config());
+    monitoring_config.probe_config.connection_budget = benchmark.connection_budget().cloned();
+    monitoring_config
+}
+
+/// Monitoring system forwarding its probe records to `monitoring.probe_sink`,
+/// exporting its metrics to `monitoring.otlp` and keeping endpoint changes in
+/// `monitoring.endpoints_file`, if set
+fn monitoring_system(benchmark: &ConnectionBenchmark) -> Result<cloud_ping::NetworkMonitoringSystem> {
+    let config = benchmark.config();
+    let mut monitoring = cloud_ping::NetworkMonitoringSystem::new(shared_monitoring_config(benchmark))
+        .with_websocket_client(cloud_ping::websocket::client(config)?);
+    if let Some(path) = &config.monitoring.endpoints_file {
+        monitoring = monitoring.with_endpoint_store(cloud_ping::endpoint_store::EndpointStore::new(path));
+    }
+    if let Some(interval) = config.monitoring.status_poll_interval {
+        let client = ProviderStatusClient::new(config, config.status_feeds.clone())?;
+        monitoring = monitoring.with_status_polling(client, interval);
+    }
+    if let Some(settings) = &config.monitoring.otlp {
+        let sink = cloud_ping::otlp::OtlpMetricsSink::new(config, settings)?;
+        monitoring
+            .plugins()
+            .register_metrics_sink(cloud_ping::otlp::OTLP_SINK_NAME, std::sync::Arc::new(sink));
+    }
+    Ok(match &config.monitoring.probe_sink {
+        Some(settings) => {
+            let sink = cloud_ping::probe_sink::HttpProbeSink::new(config, &settings.url)?;
+            monitoring.with_probe_sink(std::sync::Arc::new(sink), settings.clone())
+        }
+        None => monitoring,
+    })
+}
+
+/// Reload the monitoring configuration on SIGHUP or when the config file changes
+///
+/// A configuration that fails to load or validate is logged and the current
+/// one is kept.
+fn reload_monitoring_config(
+    monitoring: &std::sync::Arc<cloud_ping::NetworkMonitoringSystem>,
+    benchmark: &ConnectionBenchmark,
+    profile: Option<String>,
+) {
+    let monitoring = std::sync::Arc::clone(monitoring);
+    let connection_budget = benchmark.connection_budget().cloned();
+    cloud_ping::ConfigWatcher::new(AppConfig::config_path()).spawn(move || {
+        let loaded = match &profile {
+            Some(profile) => AppConfig::load_profile(profile),
+            None => AppConfig::load().and_then(|config| config.validate().map(|()| config)),
+        };
+        match loaded {
+            Ok(config) => {
+                let mut monitoring_config = monitoring_config(&config);
+                monitoring_config.probe_config.connection_budget = connection_budget.clone();
+                monitoring.reload(&monitoring_config);
+            }
+            Err(e) => tracing::warn!("Keeping the current configuration: {}", e),
+        }
+    });
+}
+
+/// Serve the status page and JSON API while monitoring all loaded regions
+async fn run_status_server(
+    benchmark: &ConnectionBenchmark,
+    regions: &[cloud_ping::Region],
+    listen: std::net::SocketAddr,
+    profile: Option<String>,
+) -> Result<()> {
+    use std::sync::Arc;
+
+    let monitoring = Arc::new(monitoring_system(benchmark)?);
+    monitoring.add_endpoints_from_regions(regions).await;
+    if let Some(url) = &benchmark.config().control.url {
+        monitoring.add_control_endpoint(url).await?;
+    }
+    let settings = &benchmark.config().monitoring;
+    monitoring.add_url_endpoints(&settings.websocket_endpoints).await?;
+    monitoring.add_url_endpoints(&settings.banner_endpoints).await?;
+    monitoring.add_ntp_servers(&settings.ntp_servers).await?;
+    for namespace in &settings.namespaces {
+        monitoring.add_namespace(namespace, regions).await?;
+    }
+    monitoring.restore_endpoints().await?;
+    reload_monitoring_config(&monitoring, benchmark, profile);
+    let history: Option<Arc<dyn HistoryStore>> =
+        cloud_ping::history_store::open(benchmark.config()).await?.map(Arc::from);
+    spawn_history_compaction(benchmark.config(), history.as_ref());
+
+    let monitoring_task = Arc::clone(&monitoring);
+    tokio::spawn(async move {
+        if let Err(e) = monitoring_task.start().await {
+            tracing::error!("Monitoring stopped: {}", e);
+        }
+    });
+
+    let mut server = cloud_ping::ApiServer::new(monitoring)
+        .locale(benchmark.config().locale)
+        .access(&benchmark.config().api)?;
+    if let Some(history) = history {
+        server = server.history(history);
+    }
+    server.serve(listen).await
+}
+
+/// Keep the history store within `history_retention` while monitoring
+fn spawn_history_compaction(config: &AppConfig, store: Option<&std::sync::Arc<dyn HistoryStore>>) {
+    if let (Some(policy), Some(store)) = (config.history_retention, store) {
+        cloud_ping::history_store::spawn_compaction(std::sync::Arc::clone(store), policy);
+    }
+}
+
+/// Serve the gRPC control plane while monitoring all loaded regions
+#[cfg(feature = "grpc")]
+async fn run_agent(
+    benchmark: ConnectionBenchmark,
+    regions: &[cloud_ping::Region],
+    listen: std::net::SocketAddr,
+    profile: Option<String>,
+) -> Result<()> {
+    use std::sync::Arc;
+
+    let monitoring = Arc::new(monitoring_system(&benchmark)?);
+    monitoring.add_endpoints_from_regions(regions).await;
+    if let Some(url) = &benchmark.config().control.url {
+        monitoring.add_control_endpoint(url).await?;
+    }
+    let settings = &benchmark.config().monitoring;
+    monitoring.add_url_endpoints(&settings.websocket_endpoints).await?;
+    monitoring.add_url_endpoints(&settings.banner_endpoints).await?;
+    monitoring.add_ntp_servers(&settings.ntp_servers).await?;
+    for namespace in &settings.namespaces {
+        monitoring.add_namespace(namespace, regions).await?;
+    }
+    monitoring.restore_endpoints().await?;
+    reload_monitoring_config(&monitoring, &benchmark, profile);
+    let history: Option<Arc<dyn HistoryStore>> =
+        cloud_ping::history_store::open(benchmark.config()).await?.map(Arc::from);
+    spawn_history_compaction(benchmark.config(), history.as_ref());
+
+    let monitoring_task = Arc::clone(&monitoring);
+    tokio::spawn(async move {
+        if let Err(e) = monitoring_task.start().await {
+            tracing::error!("Monitoring stopped: {}", e);
+        }
+    });
+
+    let api = benchmark.config().api.clone();
+    cloud_ping::grpc::ControlPlaneService::new(benchmark, monitoring)
+        .access(&api)?
+        .serve(listen)
+        .await
+}
+
+/// Annotate degraded results with incidents declared on provider status pages
+async fn check_provider_status(
+    results: &[(String, cloud_ping::PingStats)],
+    benchmark: &ConnectionBenchmark,
+) -> Result<()> {
+    let config = benchmark.config();
+    let client = ProviderStatusClient::new(config, config.status_feeds.clone())?;
+    let correlator = OutageCorrelator::new(client.fetch_incidents().await);
+    info!("Found {} active provider incidents", correlator.incidents().len());
+
+    let regions: Vec<(String, cloud_ping::Region)> = benchmark
+        .providers()
+        .iter()
+        .flat_map(|provider| {
+            provider
+                .regions
+                .iter()
+                .map(|region| (provider.name.clone(), region.clone()))
+        })
+        .collect();
+
+    DisplayFormatter::display_incident_annotations(&correlator.annotate_results(results, &regions));
+    Ok(())
+}
+
+/// Upload anonymised results if sharing is enabled and optionally compare with the community
+async fn share_with_community(
+    run: &cloud_ping::BenchmarkRun,
+    benchmark: &ConnectionBenchmark,
+    compare: bool,
+) -> Result<()> {
+    let config = benchmark.config();
+    let endpoint = config.community_endpoint.as_deref().ok_or_else(|| {
+        cloud_ping::CloudPingError::config("community_endpoint is not set in the configuration")
+    })?;
+    let client = CommunityClient::new(config, endpoint)?;
+    let submissions = CommunitySubmission::from_results(
+        &benchmark.scorable_results(&run.results),
+        benchmark.providers(),
+        &run.environment,
+        benchmark.weights(),
+    );
+
+    if config.community_sharing {
+        match client.publish(&submissions).await {
+            Ok(()) => info!("Shared {} results with the community", submissions.len()),
+            Err(e) => tracing::warn!("Failed to share results: {}", e),
+        }
+    }
+
+    if compare {
+        DisplayFormatter::display_community_comparison(&client.compare(&submissions).await);
+    }
+    Ok(())
+}
+
+/// Record run results in the configured availability ledger and report uptime
+fn update_availability_ledger(
+    run: &cloud_ping::BenchmarkRun,
+    config: &AppConfig,
+) -> Result<Option<AvailabilityReport>> {
+    let Some(path) = &config.availability_ledger else {
+        return Ok(None);
+    };
+    let path = std::path::Path::new(path);
+
+    let mut ledger = AvailabilityLedger::load(path)?;
+    for (name, stats) in &run.results {
+        ledger.record(name, stats.is_successful(), run.started_at);
+    }
+    ledger.save(path)?;
+
+    Ok(Some(ledger.report(run.started_at)))
+}
+
+/// Save a benchmark run as an agent report for a collector
+async fn write_agent_report(
+    path: &str,
+    config: &AppConfig,
+    run: cloud_ping::BenchmarkRun,
+) -> Result<()> {
+    let agent = AgentInfo::local(&config.agent_id, &config.agent_location);
+    let mut report = AgentReport::new(agent, run.results);
+    report.environment = Some(run.environment);
+    report.labels = run.labels;
+    tokio::fs::write(path, serde_json::to_string_pretty(&report)?).await?;
+    info!("Wrote agent report to {}", path);
+    Ok(())
+}
+
+/// Ingest agent report files and display or export the merged results
+async fn run_collector(
+    config: &AppConfig,
+    paths: &[String],
+    listen: Option<std::net::SocketAddr>,
+    format: &OutputFormat,
+) -> Result<()> {
+    if paths.is_empty() && listen.is_none() {
+        return Err(CloudPingError::validation(
+            "reports",
+            "Give agent report files to merge, or --listen to take pushes from agents",
+        ));
+    }
+
+    let mut aggregator_config = AggregatorConfig {
+        jitter_algorithm: config.jitter_algorithm,
+        ..AggregatorConfig::default()
+    };
+    if let Some(weights) = &config.weights {
+        aggregator_config.weights = weights.clone();
+    }
+    let (mut collector, _alerts) = Collector::new(aggregator_config);
+
+    for path in paths {
+        let content = tokio::fs::read_to_string(path).await?;
+        let report: AgentReport = serde_json::from_str(&content)?;
+        collector.ingest_report(report);
+    }
+
+    if let Some(addr) = listen {
+        return cloud_ping::collector::serve(collector, addr).await;
+    }
+
+    let matrix = collector.vantage_matrix();
+    let recommendation = collector.majority_best_region();
+
+    match format {
+        OutputFormat::Table => {
+            DisplayFormatter::display_vantage_ranking(&collector.multi_vantage_ranking());
+            DisplayFormatter::display_vantage_matrix(&matrix);
+            DisplayFormatter::display_majority_recommendation(recommendation.as_ref());
+        }
+        OutputFormat::Json => {
+            let export = serde_json::json!({
+                "matrix": matrix,
+                "recommendation": recommendation,
+            });
+            println!("{}", serde_json::to_string_pretty(&export)?);
+        }
+        OutputFormat::Csv => print!("{}", matrix.to_csv()),
+    }
+
+    Ok(())
+}
+
 /// Display benchmark results
 fn display_results(results: &[(String, cloud_ping::PingStats)], benchmark: &ConnectionBenchmark) {
     if results.is_empty() {
@@ -116,6 +1547,20 @@ fn display_results(results: &[(String, cloud_ping::PingStats)], benchmark: &Conn
         return;
     }
     
+    // Low-quality measurements are excluded from scoring by default
+    let scorable = benchmark.scorable_results(results);
+    if scorable.len() < results.len() {
+        println!(
+            "Excluded {} low-quality measurement(s) from scoring",
+            results.len() - scorable.len()
+        );
+    }
+    if scorable.is_empty() {
+        eprintln!("No results to score");
+        return;
+    }
+    let results = &scorable;
+
     // Calculate the average score
     let total_score: f64 = results
         .iter()
@@ -135,6 +1580,82 @@ fn display_results(results: &[(String, cloud_ping::PingStats)], benchmark: &Conn
     DisplayFormatter::display_simple_score(average_score);
 }
 
+/// Print the filtered ranking in the configured output format
+fn print_ranking(ranked: &[cloud_ping::models::RankedResult], benchmark: &ConnectionBenchmark) -> Result<()> {
+    use cloud_ping::models::RankedRegion;
+
+    if ranked.is_empty() {
+        if !benchmark.config().report_filter.is_empty() {
+            eprintln!("No regions match the report filter");
+        }
+        return Ok(());
+    }
+    match benchmark.config().output_format {
+        OutputFormat::Table => {
+            DisplayFormatter::generate_ranking_report(ranked, &benchmark.get_all_test_histories());
+            if let Some(green) = benchmark.config().green.recommend(ranked) {
+                DisplayFormatter::display_green_recommendation(&green);
+            }
+            DisplayFormatter::display_edge_pops(ranked);
+            DisplayFormatter::display_asn_breakdown(ranked);
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&RankedRegion::from_ranking(ranked))?),
+        OutputFormat::Csv => print!("{}", RankedRegion::to_csv(&RankedRegion::from_ranking(ranked))),
+    }
+    Ok(())
+}
+
+/// Pick the deployment footprint set in `footprint` from the ranked regions
+fn print_footprint(results: &[(String, cloud_ping::PingStats)], benchmark: &ConnectionBenchmark) -> Result<()> {
+    let footprint = match benchmark.config().footprint.select(&benchmark.ranking(results)) {
+        Ok(footprint) => footprint,
+        Err(e) => {
+            eprintln!("No deployment footprint: {e}");
+            return Ok(());
+        }
+    };
+    match benchmark.config().output_format {
+        OutputFormat::Table => DisplayFormatter::display_footprint(&footprint),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&footprint)?),
+        OutputFormat::Csv => print!("{}", cloud_ping::models::RankedRegion::to_csv(&footprint.regions)),
+    }
+    Ok(())
+}
+
+/// Explain the scores of regions whose name contains one of `patterns`, ignoring case
+fn explain_scores(
+    results: &[(String, cloud_ping::PingStats)],
+    patterns: &[String],
+    benchmark: &ConnectionBenchmark,
+) -> Result<()> {
+    if patterns.is_empty() {
+        return Ok(());
+    }
+    let ranked = cloud_ping::models::ScoringAdapter::get_sorted_results(
+        &benchmark.scorable_results(results),
+        benchmark.weights(),
+    );
+    for pattern in patterns {
+        let needle = pattern.to_lowercase();
+        let mut matched = false;
+        for (_, name, _, result) in ranked.iter().filter(|(_, name, _, _)| name.to_lowercase().contains(&needle)) {
+            matched = true;
+            let explanation = result.explain();
+            match benchmark.config().output_format {
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&serde_json::json!({ "region": name, "explanation": explanation }))?
+                ),
+                OutputFormat::Table | OutputFormat::Csv => DisplayFormatter::display_score_explanation(name, &explanation),
+            }
+        }
+        if !matched {
+            eprintln!("No scored region matches '{pattern}'");
+        }
+    }
+    Ok(())
+}
+
 /// Initialize structured logging with appropriate level
 fn init_logging(verbose: bool) {
     let level = if verbose { Level::DEBUG } else { Level::INFO };
@@ -143,6 +1664,8 @@ fn init_logging(verbose: bool) {
         .with_max_level(level)
         .with_env_filter(EnvFilter::from_default_env())
         .with_target(false)
+        // Logs stay off stdout, which carries the JSON and CSV output
+        .with_writer(std::io::stderr)
         .with_thread_ids(false)
         .with_thread_names(false)
         .compact()
@@ -0,0 +1,395 @@
+//! MMDB reader
+//!
+//! Reads the MMDB format used by IP geolocation and IP-to-ASN databases such as
+//! GeoLite2-City and GeoLite2-ASN: a binary search tree over address bits
+//! whose leaves point into a data section of typed values. The whole file is
+//! held in memory; lookups walk the tree and decode the record they reach.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+use crate::error::{CloudPingError, Result};
+
+/// Marks the start of the metadata at the end of a MMDB file
+const MMDB_METADATA_MARKER: &[u8] = b"\xAB\xCD\xEFMaxMind.com";
+
+/// Value in a MMDB data section
+#[derive(Debug, Clone, PartialEq)]
+pub enum MmdbValue {
+    /// UTF-8 string
+    String(String),
+    /// Unsigned integer of up to 128 bits
+    Unsigned(u128),
+    /// Signed 32-bit integer
+    Signed(i32),
+    /// Double or float
+    Float(f64),
+    /// Boolean
+    Bool(bool),
+    /// Raw bytes or a data cache container, which are not decoded
+    Bytes,
+    /// Map with string keys, in file order
+    Map(Vec<(String, Self)>),
+    /// Array
+    Array(Vec<Self>),
+}
+
+impl MmdbValue {
+    /// Value of `key` in a map
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            Self::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Value at a path of map keys, e.g. `["location", "latitude"]`
+    #[must_use]
+    pub fn get_path(&self, path: &[&str]) -> Option<&Self> {
+        path.iter().try_fold(self, |value, key| value.get(key))
+    }
+
+    /// Integer value, when it fits in 32 bits
+    #[must_use]
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            Self::Unsigned(value) => u32::try_from(*value).ok(),
+            Self::Signed(value) => u32::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    /// Floating point value
+    #[must_use]
+    pub const fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// String value
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Record found for an address
+#[derive(Debug, Clone, PartialEq)]
+pub struct MmdbRecord {
+    /// Decoded record, usually a map
+    pub value: MmdbValue,
+    /// Network the record applies to, e.g. `8.8.8.0/24`
+    pub network: String,
+}
+
+/// Reader of MMDB files
+#[derive(Debug, Clone)]
+pub struct MmdbReader {
+    data: Vec<u8>,
+    node_count: u32,
+    record_size: u16,
+    ip_version: u16,
+    data_start: usize,
+}
+
+impl MmdbReader {
+    /// Read a MMDB file
+    ///
+    /// # Errors
+    /// Returns an error when the file cannot be read or is not a MMDB
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::from_bytes(std::fs::read(path)?).map_err(|e| {
+            CloudPingError::data_loading(format!("Invalid MMDB file {}: {e}", path.display()))
+        })
+    }
+
+    /// Parse a MMDB held in memory
+    ///
+    /// # Errors
+    /// Returns a message when the metadata is missing or describes an unsupported tree
+    pub fn from_bytes(data: Vec<u8>) -> std::result::Result<Self, String> {
+        let marker = data
+            .windows(MMDB_METADATA_MARKER.len())
+            .rposition(|window| window == MMDB_METADATA_MARKER)
+            .ok_or("no metadata section")?;
+        let metadata_start = marker + MMDB_METADATA_MARKER.len();
+        let (metadata, _) = decode(&data[metadata_start..], 0, 0)?;
+        let field = |key: &str| {
+            metadata
+                .get(key)
+                .and_then(MmdbValue::as_u32)
+                .ok_or_else(|| format!("metadata has no {key}"))
+        };
+        let node_count = field("node_count")?;
+        let record_size = u16::try_from(field("record_size")?).map_err(|e| e.to_string())?;
+        let ip_version = u16::try_from(field("ip_version")?).map_err(|e| e.to_string())?;
+        if ![24, 28, 32].contains(&record_size) {
+            return Err(format!("unsupported record size {record_size}"));
+        }
+        let tree_size = usize::from(record_size) * 2 / 8 * node_count as usize;
+        let data_start = tree_size + 16;
+        if data_start > marker {
+            return Err("search tree is larger than the file".to_string());
+        }
+        Ok(Self {
+            data,
+            node_count,
+            record_size,
+            ip_version,
+            data_start,
+        })
+    }
+
+    /// Record of the network containing `ip`, `None` when the file has none
+    #[must_use]
+    pub fn lookup(&self, ip: IpAddr) -> Option<MmdbRecord> {
+        let (bits, start_depth) = match ip {
+            // IPv4 addresses sit under ::/96 in an IPv6 tree
+            IpAddr::V4(v4) if self.ip_version == 6 => (u128::from(u32::from(v4)), 96),
+            IpAddr::V4(v4) => (u128::from(u32::from(v4)) << 96, 0),
+            IpAddr::V6(v6) if self.ip_version == 6 => (u128::from(v6), 0),
+            IpAddr::V6(_) => return None,
+        };
+        let mut node = 0;
+        let mut depth: u32 = 0;
+        while depth < 128 && node < self.node_count {
+            let bit = if depth < start_depth {
+                0
+            } else {
+                u8::from(bits & (1 << (127 - depth)) != 0)
+            };
+            node = self.record(node, bit)?;
+            depth += 1;
+        }
+        if node <= self.node_count {
+            return None;
+        }
+        let offset = (node - self.node_count) as usize - 16;
+        let (value, _) = decode(&self.data[self.data_start..], offset, 0).ok()?;
+        Some(MmdbRecord {
+            value,
+            network: Self::network(ip, depth.saturating_sub(start_depth)),
+        })
+    }
+
+    /// `ip` masked to its first `len` bits, as `address/len`
+    fn network(ip: IpAddr, len: u32) -> String {
+        let network = match ip {
+            IpAddr::V4(v4) => {
+                let mask = u32::MAX.checked_shl(32 - len).unwrap_or(0);
+                IpAddr::from(Ipv4Addr::from(u32::from(v4) & mask))
+            }
+            IpAddr::V6(v6) => {
+                let mask = u128::MAX.checked_shl(128 - len).unwrap_or(0);
+                IpAddr::from(Ipv6Addr::from(u128::from(v6) & mask))
+            }
+        };
+        format!("{network}/{len}")
+    }
+
+    /// Left (`bit` 0) or right record of tree node `node`
+    fn record(&self, node: u32, bit: u8) -> Option<u32> {
+        let node_bytes = usize::from(self.record_size) * 2 / 8;
+        let start = node as usize * node_bytes;
+        let bytes = self.data.get(start..start + node_bytes)?;
+        let be = |slice: &[u8]| slice.iter().fold(0u32, |value, byte| value << 8 | u32::from(*byte));
+        Some(match (self.record_size, bit) {
+            (24, 0) => be(&bytes[0..3]),
+            (24, _) => be(&bytes[3..6]),
+            (28, 0) => u32::from(bytes[3] >> 4) << 24 | be(&bytes[0..3]),
+            (28, _) => u32::from(bytes[3] & 0x0F) << 24 | be(&bytes[4..7]),
+            (_, 0) => be(&bytes[0..4]),
+            _ => be(&bytes[4..8]),
+        })
+    }
+}
+
+/// Decode the value at `offset` of a data section, returning it and the offset after it
+///
+/// Pointers are followed up to a small depth, which well-formed files never exceed.
+fn decode(section: &[u8], offset: usize, depth: u8) -> std::result::Result<(MmdbValue, usize), String> {
+    if depth > 8 {
+        return Err("pointers nested too deeply".to_string());
+    }
+    let byte = |at: usize| section.get(at).copied().ok_or("data ends early");
+    let bytes = |at: usize, len: usize| section.get(at..at + len).ok_or("data ends early");
+    let be = |slice: &[u8]| slice.iter().fold(0u128, |value, byte| value << 8 | u128::from(*byte));
+
+    let control = byte(offset)?;
+    let mut at = offset + 1;
+    let mut kind = control >> 5;
+    if kind == 1 {
+        let size = (control >> 3) & 0x3;
+        let extra = usize::from(size) + 1;
+        let value = be(bytes(at, extra)?);
+        let high = u128::from(control & 0x7);
+        let target = match size {
+            0 => high << 8 | value,
+            1 => (high << 16 | value) + 2048,
+            2 => (high << 24 | value) + 526_336,
+            _ => value,
+        };
+        let target = usize::try_from(target).map_err(|e| e.to_string())?;
+        let (value, _) = decode(section, target, depth + 1)?;
+        return Ok((value, at + extra));
+    }
+    if kind == 0 {
+        kind = 7 + byte(at)?;
+        at += 1;
+    }
+    let mut size = usize::from(control & 0x1F);
+    match size {
+        29 => {
+            size = 29 + usize::from(byte(at)?);
+            at += 1;
+        }
+        30 => {
+            size = 285 + usize::try_from(be(bytes(at, 2)?)).map_err(|e| e.to_string())?;
+            at += 2;
+        }
+        31 => {
+            size = 65_821 + usize::try_from(be(bytes(at, 3)?)).map_err(|e| e.to_string())?;
+            at += 3;
+        }
+        _ => {}
+    }
+
+    match kind {
+        2 => {
+            let text = String::from_utf8_lossy(bytes(at, size)?).into_owned();
+            Ok((MmdbValue::String(text), at + size))
+        }
+        3 => {
+            let raw: [u8; 8] = bytes(at, 8)?.try_into().map_err(|_| "bad double")?;
+            Ok((MmdbValue::Float(f64::from_be_bytes(raw)), at + 8))
+        }
+        4 | 12 => Ok((MmdbValue::Bytes, at + size)),
+        5 | 6 | 9 | 10 => Ok((MmdbValue::Unsigned(be(bytes(at, size)?)), at + size)),
+        8 => {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)] // at most 4 bytes
+            let value = be(bytes(at, size)?) as u32 as i32;
+            Ok((MmdbValue::Signed(value), at + size))
+        }
+        7 => {
+            let mut entries = Vec::with_capacity(size.min(64));
+            for _ in 0..size {
+                let (key, next) = decode(section, at, depth)?;
+                let (value, next) = decode(section, next, depth)?;
+                let MmdbValue::String(key) = key else {
+                    return Err("map key is not a string".to_string());
+                };
+                entries.push((key, value));
+                at = next;
+            }
+            Ok((MmdbValue::Map(entries), at))
+        }
+        11 => {
+            let mut items = Vec::with_capacity(size.min(64));
+            for _ in 0..size {
+                let (item, next) = decode(section, at, depth)?;
+                items.push(item);
+                at = next;
+            }
+            Ok((MmdbValue::Array(items), at))
+        }
+        14 => Ok((MmdbValue::Bool(size != 0), at)),
+        15 => {
+            let raw: [u8; 4] = bytes(at, 4)?.try_into().map_err(|_| "bad float")?;
+            Ok((MmdbValue::Float(f64::from(f32::from_be_bytes(raw))), at + 4))
+        }
+        other => Err(format!("unsupported data type {other}")),
+    }
+}
+
+/// Encoders for building small databases in tests
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::MMDB_METADATA_MARKER;
+
+    pub fn string(value: &str) -> Vec<u8> {
+        let len = u8::try_from(value.len()).unwrap();
+        // Lengths from 29 are stored in the byte after the control byte
+        let mut bytes = if len < 29 {
+            vec![(2 << 5) | len]
+        } else {
+            vec![(2 << 5) | 0x1D, len - 29]
+        };
+        bytes.extend_from_slice(value.as_bytes());
+        bytes
+    }
+
+    pub fn uint32(value: u32) -> Vec<u8> {
+        let mut bytes = vec![(6 << 5) | 4];
+        bytes.extend_from_slice(&value.to_be_bytes());
+        bytes
+    }
+
+    pub fn double(value: f64) -> Vec<u8> {
+        let mut bytes = vec![(3 << 5) | 8];
+        bytes.extend_from_slice(&value.to_be_bytes());
+        bytes
+    }
+
+    pub fn map(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut bytes = vec![(7 << 5) | u8::try_from(entries.len()).unwrap()];
+        for (key, value) in entries {
+            bytes.extend(string(key));
+            bytes.extend_from_slice(value);
+        }
+        bytes
+    }
+
+    /// IPv4 database of one node: addresses starting with a 0 bit map to
+    /// `record`, the rest to nothing
+    pub fn database(record: &[u8]) -> Vec<u8> {
+        let node_count = 1u32;
+        let pointer = node_count + 16;
+        let mut file = Vec::new();
+        file.extend_from_slice(&pointer.to_be_bytes()[1..]);
+        file.extend_from_slice(&node_count.to_be_bytes()[1..]);
+        file.extend_from_slice(&[0; 16]);
+        file.extend_from_slice(record);
+        file.extend_from_slice(MMDB_METADATA_MARKER);
+        file.extend(map(&[
+            ("node_count", uint32(node_count)),
+            ("record_size", uint32(24)),
+            ("ip_version", uint32(4)),
+        ]));
+        file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{database, double, map, string};
+    use super::*;
+
+    #[test]
+    fn test_mmdb_lookup() {
+        let reader = MmdbReader::from_bytes(database(&map(&[
+            ("city", map(&[("names", map(&[("en", string("Frankfurt am Main"))]))])),
+            ("location", map(&[("latitude", double(50.11)), ("longitude", double(8.68))])),
+        ])))
+        .unwrap();
+
+        let record = reader.lookup("10.1.2.3".parse().unwrap()).unwrap();
+        assert_eq!(record.network, "0.0.0.0/1");
+        assert_eq!(
+            record.value.get_path(&["city", "names", "en"]).and_then(MmdbValue::as_str),
+            Some("Frankfurt am Main")
+        );
+        assert_eq!(
+            record.value.get_path(&["location", "longitude"]).and_then(MmdbValue::as_f64),
+            Some(8.68)
+        );
+        assert_eq!(reader.lookup("192.0.2.1".parse().unwrap()), None);
+        assert_eq!(reader.lookup("2001:db8::1".parse().unwrap()), None);
+        assert!(MmdbReader::from_bytes(b"not a database".to_vec()).is_err());
+    }
+}
@@ -1,19 +1,119 @@
 //! Core data models - now organized into submodules for better maintainability
 
 // Re-export all public types from submodules
-pub use self::endpoint::{Endpoint, ProbeType};
-pub use self::metrics::{AggregatorState, AggregatorStateBuilder, HealthStatus, RingBuffer};
-pub use self::probe::{Alert, AlertSeverity, AlertType, ProbeRecord};
+pub use self::agent::{vantage_key, AgentInfo, AgentProbeBatch, AgentReport};
+pub use self::alert_envelope::{
+    alert_json_schema, AlertEndpoint, AlertEnvelope, MetricsSnapshot, ALERT_SCHEMA_VERSION,
+};
+pub use self::asn::{AsnBreakdown, AsnGroup, AsnInfo};
+pub use self::archive::{HistoryArchive, HISTORY_ARCHIVE_VERSION};
+pub use self::availability::{
+    AvailabilityLedger, AvailabilityReport, AvailabilityState, EndpointAvailability,
+};
+pub use self::bufferbloat::{BufferbloatGrade, BufferbloatResult};
+pub use self::carbon::{CarbonIntensity, GreenRecommendation, GreenThreshold};
+pub use self::certificate::{CertValidation, CertValidationSummary};
+pub use self::changepoint::ChangePoint;
+pub use self::concurrency::ConcurrencyAdjustment;
+pub use self::control::{ControlSample, ControlSeries};
+pub use self::country::{country_code, country_from_name, same_country};
+pub use self::edge::{Cdn, EdgePop, EdgePopCount};
+pub use self::endpoint::{namespaced_id, Endpoint, ProbeType};
+pub use self::environment::{BenchmarkRun, InterfaceType, NatType, TestEnvironment};
+pub use self::footprint::{continent_of, Footprint, FootprintConstraints};
+pub use self::exp_histogram::ExponentialHistogram;
+pub use self::external::ExternalSamples;
+pub use self::failure::{FailureKind, RegionFailure, RunReport};
+pub use self::inter_region::{
+    DatasetEntry, EstimateSource, InterRegionDataset, LatencyEstimate, LatencyMatrix,
+};
+pub use self::jitter::JitterAlgorithm;
+pub use self::labels::{labels_summary, parse_label, validate_labels, RunLabels};
+pub use self::loss::LossPattern;
+pub use self::metrics::{
+    AggregatorState, AggregatorStateBuilder, HealthStatus, RingBuffer, ScorePoint,
+    DEFAULT_SCORE_HISTORY,
+};
+pub use self::notes::RegionNotes;
+pub use self::plan::BenchmarkPlan;
+pub use self::pool::{ConnectionReuse, ConnectionUse};
+pub use self::pricing::{RegionCost, RegionPricing};
+pub use self::probe::{Alert, AlertSeverity, AlertType, ProbeRecord, RootCauseHint};
+pub use self::quality::{MeasurementQuality, QualityFlag};
+pub use self::race::{AddressTiming, IpFamily, RaceAttempt, RaceResult, RaceRound};
+pub use self::ranking::{RankedRegion, ReportFilter};
+pub use self::recipe::{
+    CheckOutcome, Recipe, RecipeCheck, RecipeMeasurements, RecipeResult, RecipeVerdict,
+};
 pub use self::region::{CloudProvider, Coordinates, Region};
-pub use self::scoring::{AlgorithmWeights, ComprehensiveScoreResult, ScoreComponents};
-pub use self::scoring::utils::ScoringAdapter;
+pub use self::route::{RouteChange, RouteChangeKind, RoutePath};
+pub use self::retention::{Granularity, RetentionPolicy, AGGREGATE_LATENCIES};
+pub use self::rollup::{Rollup, RollupBucket, DEFAULT_ROLLUP_INTERVAL};
+pub use self::scoring::{
+    AlgorithmWeights, ComponentExplanation, ComprehensiveScoreResult, ScoreComponents,
+    ScoreExplanation, ScoreInputs, TickBudget, TickBudgetResult, WeightProfile, WhatIfComparison,
+};
+pub use self::scoring::utils::{RankedResult, ScoringAdapter, SortKey};
+pub use self::seasonality::HourlyLatency;
+pub use self::soak::{SoakBucket, SoakResult, SoakSample};
+pub use self::speed_test::{
+    SpeedTestEndpoint, SpeedTestKind, SpeedTestResult, CLOUDFLARE_SPEED_URL,
+};
 pub use self::stats::{PerformanceSummary, PingStats, TestHistory};
+pub use self::success::{
+    SuccessCriteria, SUCCESS_MAX_RTT_METADATA_KEY, SUCCESS_STATUS_METADATA_KEY,
+};
+pub use self::transaction::{
+    Assertion, Extract, StepResult, Transaction, TransactionResult, TransactionStep,
+};
+pub use self::udp::{
+    UdpCheck, UdpMatrixRow, UdpPort, UdpProtocol, UdpReachability, DEFAULT_UDP_PORTS,
+};
 
 // Submodules
+pub mod agent;
+pub mod alert_envelope;
+pub mod archive;
+pub mod asn;
+pub mod availability;
+pub mod bufferbloat;
+pub mod carbon;
+pub mod certificate;
+pub mod changepoint;
+pub mod concurrency;
+pub mod control;
+pub mod country;
+pub mod edge;
 pub mod endpoint;
+pub mod environment;
+pub mod exp_histogram;
+pub mod external;
+pub mod failure;
+pub mod footprint;
+pub mod inter_region;
+pub mod jitter;
+pub mod labels;
+pub mod loss;
 pub mod metrics;
+pub mod notes;
+pub mod plan;
+pub mod pool;
+pub mod pricing;
 pub mod probe;
+pub mod quality;
+pub mod race;
+pub mod ranking;
+pub mod recipe;
 pub mod region;
+pub mod retention;
+pub mod rollup;
+pub mod route;
 pub mod scoring;
+pub mod seasonality;
+pub mod soak;
+pub mod speed_test;
 pub mod stats;
+pub mod success;
+pub mod transaction;
+pub mod udp;
 pub mod utils;
\ No newline at end of file
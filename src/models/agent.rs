@@ -0,0 +1,145 @@
+//! Remote agent identity and pushed result payloads
+//!
+//! Agents running in different locations push their measurements to a
+//! collector, which merges them into a single multi-vantage view.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::probe::ProbeRecord;
+use super::environment::TestEnvironment;
+use super::labels::RunLabels;
+use super::region::Coordinates;
+use super::stats::PingStats;
+use crate::time_utils::TimeUtils;
+
+/// Identity and location of a measuring agent (vantage point)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AgentInfo {
+    /// Unique identifier of the agent
+    pub agent_id: String,
+    /// Human-readable location (e.g. "Frankfurt office")
+    #[serde(default)]
+    pub location: String,
+    /// Geographic coordinates of the agent, if known
+    #[serde(default)]
+    pub coordinates: Option<Coordinates>,
+}
+
+impl AgentInfo {
+    /// Create agent info without coordinates
+    #[must_use]
+    pub const fn new(agent_id: String, location: String) -> Self {
+        Self {
+            agent_id,
+            location,
+            coordinates: None,
+        }
+    }
+
+    /// Identify the local machine as `agent_id`; when that is empty, as the
+    /// `HOSTNAME` variable, else `/etc/hostname`, else `local`, with a warning
+    /// naming the ID picked so agents sharing a host name can be told apart
+    #[must_use]
+    pub fn local(agent_id: &str, location: &str) -> Self {
+        let agent_id = if agent_id.trim().is_empty() {
+            let fallback = std::env::var("HOSTNAME")
+                .ok()
+                .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| "local".to_string());
+            warn!("agent_id is not set; identifying this agent as {}", fallback);
+            fallback
+        } else {
+            agent_id.to_string()
+        };
+
+        Self::new(agent_id, location.to_string())
+    }
+
+    /// Label used in reports (`agent_id (location)`)
+    #[must_use]
+    pub fn label(&self) -> String {
+        if self.location.is_empty() {
+            self.agent_id.clone()
+        } else {
+            format!("{} ({})", self.agent_id, self.location)
+        }
+    }
+}
+
+/// Benchmark results pushed by an agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentReport {
+    /// Agent that produced the results
+    pub agent: AgentInfo,
+    /// Per-region results as returned by the benchmark
+    pub results: Vec<(String, PingStats)>,
+    /// When the report was produced
+    #[serde(default = "Utc::now")]
+    pub generated_at: DateTime<Utc>,
+    /// Local environment of the agent during the run
+    #[serde(default)]
+    pub environment: Option<TestEnvironment>,
+    /// Labels of the run the results came from
+    #[serde(default)]
+    pub labels: RunLabels,
+}
+
+impl AgentReport {
+    /// Create a report stamped with the current time
+    #[must_use]
+    pub fn new(agent: AgentInfo, results: Vec<(String, PingStats)>) -> Self {
+        Self {
+            agent,
+            results,
+            generated_at: TimeUtils::now(),
+            environment: None,
+            labels: RunLabels::new(),
+        }
+    }
+}
+
+/// Batch of streaming probe records pushed by an agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentProbeBatch {
+    /// Agent that produced the records
+    pub agent: AgentInfo,
+    /// Probe records in the order they were taken
+    pub records: Vec<ProbeRecord>,
+}
+
+/// Key under which per-agent state for an endpoint is aggregated
+#[must_use]
+pub fn vantage_key(agent_id: &str, endpoint_id: &str) -> String {
+    format!("{endpoint_id}@{agent_id}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_label() {
+        let agent = AgentInfo::new("agent-1".to_string(), "Frankfurt".to_string());
+        assert_eq!(agent.label(), "agent-1 (Frankfurt)");
+
+        let agent = AgentInfo::new("agent-2".to_string(), String::new());
+        assert_eq!(agent.label(), "agent-2");
+    }
+
+    #[test]
+    fn test_agent_report_round_trip() {
+        let agent = AgentInfo::local("agent-1", "Tokyo");
+        let report = AgentReport::new(agent, vec![("Region".to_string(), PingStats::new(3))]);
+
+        let json = serde_json::to_string(&report).unwrap();
+        let decoded: AgentReport = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(decoded.agent.agent_id, "agent-1");
+        assert_eq!(decoded.results.len(), 1);
+        assert_eq!(vantage_key("agent-1", "eu-west-1"), "eu-west-1@agent-1");
+    }
+}
@@ -0,0 +1,361 @@
+//! Versioned alert payload for external consumers
+//!
+//! [`Alert`] is an internal type and may change with the monitoring code. An
+//! [`AlertEnvelope`] wraps it in a stable, documented shape: a schema version,
+//! the alert ID, endpoint metadata, the severity and the metrics at the time
+//! the alert fired. [`alert_json_schema`] describes that shape so webhook and
+//! API consumers can validate payloads.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::endpoint::{Endpoint, ProbeType};
+use super::metrics::AggregatorState;
+use super::probe::{Alert, AlertSeverity, AlertType, RootCauseHint};
+
+/// Version of the envelope format; bumped whenever its fields change
+pub const ALERT_SCHEMA_VERSION: u32 = 3;
+
+/// Endpoint metrics when an alert fired, over the short window
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MetricsSnapshot {
+    /// Score of the endpoint after the triggering probe
+    pub score: Option<f64>,
+    /// Median round-trip time, absent without successful probes
+    pub latency_p50_ms: Option<f64>,
+    /// 90th percentile round-trip time
+    pub latency_p90_ms: Option<f64>,
+    /// Smoothed jitter
+    pub jitter_ms: f64,
+    /// Failed probes in percent
+    pub packet_loss_percent: f64,
+    /// Successful probes in percent
+    pub availability_percent: f64,
+    /// Probes in the short window
+    pub samples: usize,
+}
+
+impl MetricsSnapshot {
+    /// Snapshot the cached short-window metrics of `state`
+    #[must_use]
+    pub fn from_state(state: &AggregatorState) -> Self {
+        let finite = |value: f64| value.is_finite().then_some(value);
+        Self {
+            score: state.last_score,
+            latency_p50_ms: finite(state.cached_p50_short),
+            latency_p90_ms: finite(state.cached_p90_short),
+            jitter_ms: state.ewma_jitter_ms,
+            packet_loss_percent: state.cached_loss_short,
+            availability_percent: state.cached_avail_short,
+            samples: state.circular_buffer_short.len(),
+        }
+    }
+}
+
+/// Endpoint an alert is about
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AlertEndpoint {
+    /// Endpoint ID as used by the monitoring system
+    pub id: String,
+    /// Display name, when the endpoint has one
+    pub name: Option<String>,
+    /// Host name or address; absent for endpoints no longer configured
+    pub host: Option<String>,
+    /// Port probed
+    pub port: Option<u16>,
+    /// Probe method
+    pub probe_type: Option<ProbeType>,
+    /// Remaining endpoint metadata such as provider and region
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl AlertEndpoint {
+    /// Metadata of `endpoint`, or only `id` when the endpoint is unknown
+    #[must_use]
+    pub fn new(id: &str, endpoint: Option<&Endpoint>) -> Self {
+        let Some(endpoint) = endpoint else {
+            return Self {
+                id: id.to_string(),
+                name: None,
+                host: None,
+                port: None,
+                probe_type: None,
+                metadata: BTreeMap::new(),
+            };
+        };
+        let mut metadata: BTreeMap<String, String> = endpoint
+            .metadata
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        Self {
+            id: id.to_string(),
+            name: metadata.remove("name"),
+            host: Some(endpoint.host.clone()),
+            port: Some(endpoint.port),
+            probe_type: Some(endpoint.probe_type),
+            metadata,
+        }
+    }
+}
+
+/// Stable, versioned representation of an alert
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AlertEnvelope {
+    /// Always [`ALERT_SCHEMA_VERSION`] for envelopes produced by this build
+    pub schema_version: u32,
+    /// Unique alert ID
+    pub id: String,
+    /// When the condition was detected
+    pub timestamp: DateTime<Utc>,
+    /// Condition in snake case, e.g. `score_drop`
+    pub kind: String,
+    /// Info, Warning or Critical
+    pub severity: AlertSeverity,
+    /// Human-readable summary
+    pub summary: String,
+    /// Condition-specific values, e.g. `old_score` and `new_score`
+    pub details: serde_json::Map<String, serde_json::Value>,
+    /// Endpoint the alert is about
+    pub endpoint: AlertEndpoint,
+    /// Namespace the endpoint is monitored in, if any
+    pub namespace: Option<String>,
+    /// Metrics when the alert fired, if recorded
+    pub metrics: Option<MetricsSnapshot>,
+    /// Declared provider incident coinciding with the alert
+    pub provider_incident: Option<String>,
+    /// Likely cause from comparing with other endpoints
+    pub hint: Option<RootCauseHint>,
+    /// Whether the alert has been acknowledged
+    pub acknowledged: bool,
+}
+
+impl AlertEnvelope {
+    /// Wrap `alert`, adding metadata from `endpoint` when it is known
+    #[must_use]
+    pub fn new(alert: &Alert, endpoint: Option<&Endpoint>) -> Self {
+        Self {
+            schema_version: ALERT_SCHEMA_VERSION,
+            id: alert.id.clone(),
+            timestamp: alert.timestamp,
+            kind: alert.alert_type.kind().to_string(),
+            severity: alert.severity(),
+            summary: alert.description(),
+            details: alert_details(&alert.alert_type),
+            endpoint: AlertEndpoint::new(&alert.endpoint_id, endpoint),
+            namespace: alert.namespace.clone(),
+            metrics: alert.metrics.clone(),
+            provider_incident: alert.provider_incident.clone(),
+            hint: alert.hint.clone(),
+            acknowledged: alert.acknowledged,
+        }
+    }
+}
+
+/// Numeric fields of the alert type variant, without the variant name
+///
+/// The name and severity of a scripted condition are left out; they are in
+/// the summary and the severity already.
+fn alert_details(alert_type: &AlertType) -> serde_json::Map<String, serde_json::Value> {
+    match serde_json::to_value(alert_type) {
+        Ok(serde_json::Value::Object(variant)) => variant
+            .into_iter()
+            .next()
+            .and_then(|(_, fields)| match fields {
+                serde_json::Value::Object(fields) => {
+                    Some(fields.into_iter().filter(|(_, value)| value.is_number()).collect())
+                }
+                _ => None,
+            })
+            .unwrap_or_default(),
+        _ => serde_json::Map::new(),
+    }
+}
+
+/// JSON Schema (draft 2020-12) of [`AlertEnvelope`]
+#[must_use]
+pub fn alert_json_schema() -> serde_json::Value {
+    let nullable_number = serde_json::json!({ "type": ["number", "null"] });
+    serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": format!("https://cloud-ping.dev/schemas/alert-envelope-v{ALERT_SCHEMA_VERSION}.json"),
+        "title": "cloud-ping alert",
+        "type": "object",
+        "additionalProperties": false,
+        "required": [
+            "schema_version", "id", "timestamp", "kind", "severity", "summary",
+            "details", "endpoint", "namespace", "metrics", "provider_incident",
+            "hint", "acknowledged"
+        ],
+        "properties": {
+            "schema_version": { "const": ALERT_SCHEMA_VERSION },
+            "id": { "type": "string" },
+            "timestamp": { "type": "string", "format": "date-time" },
+            "kind": { "enum": AlertType::KINDS },
+            "severity": { "enum": ["Info", "Warning", "Critical"] },
+            "summary": { "type": "string" },
+            "details": {
+                "type": "object",
+                "additionalProperties": { "type": "number" }
+            },
+            "endpoint": {
+                "type": "object",
+                "additionalProperties": false,
+                "required": ["id", "name", "host", "port", "probe_type", "metadata"],
+                "properties": {
+                    "id": { "type": "string" },
+                    "name": { "type": ["string", "null"] },
+                    "host": { "type": ["string", "null"] },
+                    "port": { "type": ["integer", "null"], "minimum": 0, "maximum": 65535 },
+                    "probe_type": { "enum": ["TCP", "HTTP", "ICMP", null] },
+                    "metadata": {
+                        "type": "object",
+                        "additionalProperties": { "type": "string" }
+                    }
+                }
+            },
+            "namespace": { "type": ["string", "null"] },
+            "metrics": {
+                "type": ["object", "null"],
+                "additionalProperties": false,
+                "required": [
+                    "score", "latency_p50_ms", "latency_p90_ms", "jitter_ms",
+                    "packet_loss_percent", "availability_percent", "samples"
+                ],
+                "properties": {
+                    "score": nullable_number,
+                    "latency_p50_ms": nullable_number,
+                    "latency_p90_ms": nullable_number,
+                    "jitter_ms": { "type": "number" },
+                    "packet_loss_percent": { "type": "number" },
+                    "availability_percent": { "type": "number" },
+                    "samples": { "type": "integer", "minimum": 0 }
+                }
+            },
+            "provider_incident": { "type": ["string", "null"] },
+            "hint": {
+                "oneOf": [
+                    { "type": "null" },
+                    {
+                        "type": "object",
+                        "additionalProperties": false,
+                        "required": ["cause"],
+                        "properties": {
+                            "cause": { "enum": ["resolver_issue", "local_network"] }
+                        }
+                    },
+                    {
+                        "type": "object",
+                        "additionalProperties": false,
+                        "required": ["cause", "provider"],
+                        "properties": {
+                            "cause": { "const": "provider_side" },
+                            "provider": { "type": "string" }
+                        }
+                    }
+                ]
+            },
+            "acknowledged": { "type": "boolean" }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_fields_match_schema() {
+        let mut endpoint = Endpoint::new(
+            "aws-eu".to_string(),
+            "eu.example.com".to_string(),
+            443,
+            ProbeType::HTTP,
+        );
+        endpoint
+            .metadata
+            .insert("name".to_string(), "Frankfurt".to_string());
+        endpoint
+            .metadata
+            .insert("provider".to_string(), "AWS".to_string());
+        let mut alert = Alert::new(
+            "aws-eu".to_string(),
+            AlertType::ScoreDrop {
+                old_score: 90.0,
+                new_score: 45.0,
+            },
+        );
+        alert.metrics = Some(MetricsSnapshot::default());
+
+        let envelope = AlertEnvelope::new(&alert, Some(&endpoint));
+        assert_eq!(envelope.kind, "score_drop");
+        assert_eq!(envelope.endpoint.name.as_deref(), Some("Frankfurt"));
+        assert_eq!(envelope.endpoint.metadata["provider"], "AWS");
+        assert_eq!(envelope.details["new_score"], 45.0);
+
+        // Every serialized field is declared and every required field present
+        let schema = alert_json_schema();
+        let value = serde_json::to_value(&envelope).unwrap();
+        for (path, schema, value) in [
+            ("", &schema, &value),
+            (
+                "endpoint",
+                &schema["properties"]["endpoint"],
+                &value["endpoint"],
+            ),
+            (
+                "metrics",
+                &schema["properties"]["metrics"],
+                &value["metrics"],
+            ),
+        ] {
+            let fields = value.as_object().unwrap();
+            let required = schema["required"].as_array().unwrap();
+            assert_eq!(fields.len(), required.len(), "{path}");
+            for field in required {
+                assert!(
+                    fields.contains_key(field.as_str().unwrap()),
+                    "{path}.{field}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_schema_lists_every_alert_kind() {
+        let kinds = alert_json_schema()["properties"]["kind"]["enum"].clone();
+        for alert_type in [
+            AlertType::ScoreDrop {
+                old_score: 1.0,
+                new_score: 0.0,
+            },
+            AlertType::SustainedLoss { loss_percent: 1.0 },
+            AlertType::AvailabilityLow { availability: 1.0 },
+            AlertType::HighLatency { latency_ms: 1.0 },
+            AlertType::HighJitter { jitter_ms: 1.0 },
+            AlertType::ClockDrift { offset_ms: 1.0 },
+            AlertType::ProbeLoopFailing { restarts: 3 },
+            AlertType::Scripted {
+                name: "slow".to_string(),
+                severity: AlertSeverity::Warning,
+            },
+        ] {
+            assert!(kinds
+                .as_array()
+                .unwrap()
+                .contains(&alert_type.kind().into()));
+        }
+
+        // Alerts from unknown endpoints keep only their ID
+        let alert = Alert::new(
+            "gone".to_string(),
+            AlertType::HighJitter { jitter_ms: 80.0 },
+        );
+        let envelope = AlertEnvelope::new(&alert, None);
+        assert_eq!(envelope.endpoint.host, None);
+        assert_eq!(envelope.schema_version, ALERT_SCHEMA_VERSION);
+        assert!(envelope.metrics.is_none());
+    }
+}
@@ -0,0 +1,193 @@
+//! Portable test history archives
+//!
+//! An archive wraps the history of every region with a format version, so
+//! history can be moved between machines or shared and merged into an
+//! existing history without losing runs on either side.
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::stats::TestHistory;
+use crate::error::{CloudPingError, Result};
+use crate::time_utils::TimeUtils;
+
+/// Archive format version written by this build
+pub const HISTORY_ARCHIVE_VERSION: u32 = 1;
+
+/// Test history of all regions in a versioned, portable file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryArchive {
+    /// Format version the archive was written with
+    pub format_version: u32,
+    /// Version of cloud-ping that created the archive
+    pub created_by: String,
+    /// When the archive was created
+    pub created_at: DateTime<Utc>,
+    /// Per-region history
+    pub histories: Vec<TestHistory>,
+}
+
+impl HistoryArchive {
+    /// Archive the given histories with the current format version
+    #[must_use]
+    pub fn new(histories: Vec<TestHistory>) -> Self {
+        Self {
+            format_version: HISTORY_ARCHIVE_VERSION,
+            created_by: crate::VERSION.to_string(),
+            created_at: TimeUtils::now(),
+            histories,
+        }
+    }
+
+    /// Read an archive, rejecting versions newer than this build understands
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let archive: Self = serde_json::from_str(&content).map_err(|e| {
+            CloudPingError::data_loading(format!("Invalid history archive {}: {e}", path.display()))
+        })?;
+
+        if archive.format_version > HISTORY_ARCHIVE_VERSION {
+            return Err(CloudPingError::data_loading(format!(
+                "History archive {} uses format version {}, newer than supported version {HISTORY_ARCHIVE_VERSION}",
+                path.display(),
+                archive.format_version
+            )));
+        }
+        Ok(archive)
+    }
+
+    /// Write the archive as JSON
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Merge another archive's histories into this one
+    ///
+    /// Regions are matched by URL, since region IDs are generated per load.
+    /// Returns the number of runs that were not already present.
+    pub fn merge(&mut self, other: Self) -> usize {
+        let mut added = 0;
+        for history in other.histories {
+            if let Some(existing) = self
+                .histories
+                .iter_mut()
+                .find(|existing| existing.region_url == history.region_url)
+            {
+                added += existing.merge(history);
+            } else {
+                added += history.historical_data.len();
+                self.histories.push(history);
+            }
+        }
+        added
+    }
+}
+
+impl TestHistory {
+    /// Add runs from another history of the same region, skipping runs
+    /// already present and keeping the newest [`Self::MAX_RUNS`]; returns
+    /// the number of runs added and kept
+    pub fn merge(&mut self, other: Self) -> usize {
+        let before = self.historical_data.len();
+        let existing: std::collections::HashSet<DateTime<Utc>> =
+            self.historical_data.iter().map(|stats| stats.test_time).collect();
+        self.historical_data.extend(
+            other
+                .historical_data
+                .into_iter()
+                .filter(|stats| !existing.contains(&stats.test_time)),
+        );
+        self.historical_data.sort_by_key(|stats| stats.test_time);
+        self.historical_data.dedup_by_key(|stats| stats.test_time);
+        if self.historical_data.len() == before {
+            return 0;
+        }
+
+        // Older imported runs make way for newer ones rather than the reverse
+        let excess = self.historical_data.len().saturating_sub(Self::MAX_RUNS);
+        self.historical_data.drain(..excess);
+        self.last_updated = TimeUtils::now();
+        self.historical_data
+            .iter()
+            .filter(|stats| !existing.contains(&stats.test_time))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PingStats;
+
+    fn history(url: &str, days: &[i64]) -> TestHistory {
+        let start = DateTime::parse_from_rfc3339("2026-03-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut history = TestHistory::new("id".to_string(), url.to_string(), url.to_string());
+        for &day in days {
+            let mut stats = PingStats::new(10);
+            stats.test_time = start + chrono::Duration::days(day);
+            history.add_test_result(stats);
+        }
+        history
+    }
+
+    #[test]
+    fn test_merge_deduplicates_runs() {
+        let mut local = HistoryArchive::new(vec![history("https://eu.example.com", &[0, 1, 2])]);
+        let remote = HistoryArchive::new(vec![
+            history("https://eu.example.com", &[2, 3]),
+            history("https://us.example.com", &[0]),
+        ]);
+
+        assert_eq!(local.merge(remote.clone()), 2);
+        assert_eq!(local.histories.len(), 2);
+        assert_eq!(local.histories[0].historical_data.len(), 4);
+
+        // Merging the same archive again adds nothing
+        assert_eq!(local.merge(remote), 0);
+    }
+
+    #[test]
+    fn test_merge_into_full_history_keeps_newest_runs() {
+        let max_runs = i64::try_from(TestHistory::MAX_RUNS).unwrap();
+        let full: Vec<i64> = (0..max_runs).collect();
+        let mut local = history("https://eu.example.com", &full);
+
+        // Runs older than every kept one are dropped rather than the newest
+        assert_eq!(local.merge(history("https://eu.example.com", &[-3, -2, -1])), 0);
+        assert_eq!(local.historical_data.len(), TestHistory::MAX_RUNS);
+        let newest = local.historical_data.last().unwrap().test_time;
+
+        // Newer runs push out the oldest
+        assert_eq!(local.merge(history("https://eu.example.com", &[-1, max_runs, max_runs + 1])), 2);
+        assert_eq!(local.historical_data.len(), TestHistory::MAX_RUNS);
+        assert!(local.historical_data.last().unwrap().test_time > newest);
+        assert!(local.historical_data.windows(2).all(|pair| pair[0].test_time < pair[1].test_time));
+    }
+
+    #[test]
+    fn test_round_trip_and_version_check() {
+        let dir = std::env::temp_dir().join(format!("cloud-ping-archive-{}", std::process::id()));
+        let path = dir.join("history.json");
+
+        let archive = HistoryArchive::new(vec![history("https://eu.example.com", &[0, 1])]);
+        archive.write(&path).unwrap();
+        let restored = HistoryArchive::read(&path).unwrap();
+        assert_eq!(restored.format_version, HISTORY_ARCHIVE_VERSION);
+        assert_eq!(restored.histories[0].historical_data.len(), 2);
+
+        let mut future = archive;
+        future.format_version = HISTORY_ARCHIVE_VERSION + 1;
+        future.write(&path).unwrap();
+        assert!(HistoryArchive::read(&path).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
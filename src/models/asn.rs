@@ -0,0 +1,174 @@
+//! Autonomous systems behind tested endpoints
+//!
+//! With ASN enrichment on, every endpoint's address is resolved to the
+//! autonomous system announcing it. [`AsnBreakdown`] groups a ranking by
+//! that system, so slow regions that share a network, such as one transit
+//! provider or CDN, stand out from regions that are slow on their own.
+
+use std::fmt;
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+
+use super::metrics::HealthStatus;
+use super::scoring::utils::RankedResult;
+
+/// Autonomous system an endpoint's address belongs to
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AsnInfo {
+    /// Address the endpoint's host resolved to
+    pub ip: IpAddr,
+    /// Autonomous system number
+    pub asn: u32,
+    /// Organization operating the system, empty when unknown
+    #[serde(default)]
+    pub organization: String,
+    /// Announced prefix containing the address, e.g. `8.8.8.0/24`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    /// Country the system is registered in, as an ISO code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+}
+
+impl fmt::Display for AsnInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.organization.is_empty() {
+            write!(f, "AS{}", self.asn)
+        } else {
+            write!(f, "AS{} {}", self.asn, self.organization)
+        }
+    }
+}
+
+/// Ranked regions behind one autonomous system
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AsnGroup {
+    /// Autonomous system number
+    pub asn: u32,
+    /// Organization operating the system, empty when unknown
+    pub organization: String,
+    /// Regions behind the system, in ranking order
+    pub regions: Vec<String>,
+    /// Regions behind the system in poor or critical health
+    pub slow: Vec<String>,
+    /// Mean average latency of the regions that answered, absent when none did
+    pub avg_latency_ms: Option<f64>,
+}
+
+/// A ranking grouped by autonomous system
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AsnBreakdown {
+    /// Systems with the most regions first; ties by number
+    pub groups: Vec<AsnGroup>,
+    /// Regions whose system is unknown, in ranking order
+    pub unresolved: Vec<String>,
+    /// Regions in poor or critical health whose system is unknown
+    pub slow_unresolved: usize,
+}
+
+impl AsnBreakdown {
+    /// Group `ranked` by the autonomous system of each region
+    #[must_use]
+    pub fn from_ranking(ranked: &[RankedResult]) -> Self {
+        let mut breakdown = Self::default();
+        let mut latencies: Vec<Vec<f64>> = Vec::new();
+        for (_, name, stats, _) in ranked {
+            let slow = matches!(
+                stats.health_status(),
+                HealthStatus::Poor | HealthStatus::Critical
+            );
+            let Some(info) = &stats.asn else {
+                breakdown.unresolved.push(name.clone());
+                breakdown.slow_unresolved += usize::from(slow);
+                continue;
+            };
+            let index = breakdown
+                .groups
+                .iter()
+                .position(|group| group.asn == info.asn)
+                .unwrap_or_else(|| {
+                    breakdown.groups.push(AsnGroup {
+                        asn: info.asn,
+                        organization: info.organization.clone(),
+                        regions: Vec::new(),
+                        slow: Vec::new(),
+                        avg_latency_ms: None,
+                    });
+                    latencies.push(Vec::new());
+                    breakdown.groups.len() - 1
+                });
+            let group = &mut breakdown.groups[index];
+            group.regions.push(name.clone());
+            if slow {
+                group.slow.push(name.clone());
+            }
+            if stats.is_successful() {
+                latencies[index].push(stats.avg);
+            }
+        }
+        for (group, latencies) in breakdown.groups.iter_mut().zip(&latencies) {
+            group.avg_latency_ms = (!latencies.is_empty()).then(|| statistical::mean(latencies));
+        }
+        breakdown
+            .groups
+            .sort_by(|a, b| b.regions.len().cmp(&a.regions.len()).then(a.asn.cmp(&b.asn)));
+        breakdown
+    }
+
+    /// The system every slow region is behind, when there are at least two
+    /// slow regions and none of them has an unknown system
+    #[must_use]
+    pub fn slow_behind_one(&self) -> Option<&AsnGroup> {
+        let slow: usize = self.groups.iter().map(|group| group.slow.len()).sum();
+        if slow < 2 || self.slow_unresolved > 0 {
+            return None;
+        }
+        self.groups.iter().find(|group| group.slow.len() == slow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AlgorithmWeights, PingStats, ScoringAdapter};
+
+    #[test]
+    fn test_asn_breakdown() {
+        let region = |name: &str, avg: f64, asn: Option<u32>| {
+            let mut stats = PingStats::new(10);
+            stats.successful_pings = 10;
+            stats.avg = avg;
+            stats.latencies = vec![avg; 10];
+            stats.asn = asn.map(|asn| AsnInfo {
+                ip: "192.0.2.1".parse().unwrap(),
+                asn,
+                organization: format!("NET{asn}"),
+                prefix: None,
+                country: None,
+            });
+            (name.to_string(), stats)
+        };
+        let ranked = ScoringAdapter::get_sorted_results(
+            &[
+                region("Frankfurt", 20.0, Some(16509)),
+                region("Sydney", 400.0, Some(13335)),
+                region("Tokyo", 300.0, Some(13335)),
+                region("Paris", 30.0, Some(16509)),
+                region("Oslo", 40.0, None),
+            ],
+            &AlgorithmWeights::default(),
+        );
+
+        let breakdown = AsnBreakdown::from_ranking(&ranked);
+        assert_eq!(breakdown.groups.len(), 2);
+        assert_eq!(breakdown.unresolved, ["Oslo"]);
+        let slow = breakdown.slow_behind_one().unwrap();
+        assert_eq!(slow.asn, 13335);
+        assert_eq!(slow.slow, ["Tokyo", "Sydney"]);
+        assert_eq!(slow.avg_latency_ms, Some(350.0));
+        assert_eq!(breakdown.groups[0].asn, 13335);
+        assert_eq!(breakdown.groups[1].regions, ["Frankfurt", "Paris"]);
+        assert_eq!(breakdown.groups[1].organization, "NET16509");
+    }
+}
@@ -0,0 +1,289 @@
+//! Availability ledger with status-page style uptime windows
+//!
+//! Records only up/down state transitions per endpoint, which keeps the
+//! ledger small enough to persist as JSON while still allowing exact uptime
+//! percentages for any window.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::collection_utils::CollectionUtils;
+use crate::error::{CloudPingError, Result};
+
+/// Longest window reported; older transitions are pruned
+const RETENTION_DAYS: i64 = 30;
+
+/// Up/down state of an endpoint
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AvailabilityState {
+    /// Endpoint answered
+    Up,
+    /// Endpoint failed to answer
+    Down,
+}
+
+impl AvailabilityState {
+    /// State for a probe outcome
+    #[must_use]
+    pub const fn from_success(success: bool) -> Self {
+        if success {
+            Self::Up
+        } else {
+            Self::Down
+        }
+    }
+}
+
+/// A change of state at a point in time
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StateTransition {
+    /// New state
+    pub state: AvailabilityState,
+    /// When the state was first observed
+    pub at: DateTime<Utc>,
+}
+
+/// Uptime figures for one endpoint
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EndpointAvailability {
+    /// Endpoint identifier
+    pub endpoint_id: String,
+    /// Most recently observed state
+    pub current_state: AvailabilityState,
+    /// When the current state began
+    pub since: DateTime<Utc>,
+    /// Uptime over the last 24 hours, in percent
+    pub uptime_24h: Option<f64>,
+    /// Uptime over the last 7 days, in percent
+    pub uptime_7d: Option<f64>,
+    /// Uptime over the last 30 days, in percent
+    pub uptime_30d: Option<f64>,
+}
+
+/// Availability of all tracked endpoints
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AvailabilityReport {
+    /// When the report was computed
+    pub generated_at: DateTime<Utc>,
+    /// Per-endpoint figures, ordered by endpoint ID
+    pub endpoints: Vec<EndpointAvailability>,
+}
+
+/// Per-endpoint history of up/down transitions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AvailabilityLedger {
+    transitions: HashMap<String, Vec<StateTransition>>,
+}
+
+impl AvailabilityLedger {
+    /// Create an empty ledger
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            transitions: CollectionUtils::new_hashmap(),
+        }
+    }
+
+    /// Load a ledger from a JSON file, starting empty if the file does not exist
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(|e| {
+            CloudPingError::data_loading(format!(
+                "Invalid availability ledger {}: {e}",
+                path.display()
+            ))
+        })
+    }
+
+    /// Persist the ledger as JSON
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Record an observation; returns `true` when it changed the endpoint's state
+    pub fn record(&mut self, endpoint_id: &str, success: bool, at: DateTime<Utc>) -> bool {
+        let state = AvailabilityState::from_success(success);
+        let history = self.transitions.entry(endpoint_id.to_string()).or_default();
+
+        if history.last().is_some_and(|last| last.state == state) {
+            return false;
+        }
+
+        history.push(StateTransition { state, at });
+        Self::prune(history, at);
+        true
+    }
+
+    /// Drop transitions older than the retention window, keeping the state at its start
+    fn prune(history: &mut Vec<StateTransition>, now: DateTime<Utc>) {
+        let cutoff = now - Duration::days(RETENTION_DAYS);
+        let stale = history.iter().take_while(|t| t.at < cutoff).count();
+
+        if stale > 1 {
+            history.drain(..stale - 1);
+        }
+    }
+
+    /// Transitions recorded for an endpoint, oldest first
+    #[must_use]
+    pub fn transitions(&self, endpoint_id: &str) -> &[StateTransition] {
+        self.transitions.get(endpoint_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Uptime percentage over `window` ending at `now`
+    ///
+    /// Only time since the endpoint was first observed counts, so a new
+    /// endpoint is not penalised for the part of the window before it existed.
+    #[must_use]
+    pub fn uptime_percent(
+        &self,
+        endpoint_id: &str,
+        window: Duration,
+        now: DateTime<Utc>,
+    ) -> Option<f64> {
+        let history = self.transitions.get(endpoint_id)?;
+        let first = history.first()?;
+        let window_start = (now - window).max(first.at);
+
+        let observed = (now - window_start).num_milliseconds();
+        if observed <= 0 {
+            return Some(if history.last()?.state == AvailabilityState::Up {
+                100.0
+            } else {
+                0.0
+            });
+        }
+
+        let mut up_ms = 0;
+        for (i, transition) in history.iter().enumerate() {
+            if transition.state != AvailabilityState::Up {
+                continue;
+            }
+            let end = history.get(i + 1).map_or(now, |next| next.at).min(now);
+            let start = transition.at.max(window_start);
+            if end > start {
+                up_ms += (end - start).num_milliseconds();
+            }
+        }
+
+        Some(ratio_percent(up_ms, observed))
+    }
+
+    /// Availability report over the standard 24h/7d/30d windows
+    #[must_use]
+    pub fn report(&self, now: DateTime<Utc>) -> AvailabilityReport {
+        let mut endpoints: Vec<EndpointAvailability> = self
+            .transitions
+            .iter()
+            .filter_map(|(endpoint_id, history)| {
+                let last = history.last()?;
+                Some(EndpointAvailability {
+                    endpoint_id: endpoint_id.clone(),
+                    current_state: last.state,
+                    since: last.at,
+                    uptime_24h: self.uptime_percent(endpoint_id, Duration::hours(24), now),
+                    uptime_7d: self.uptime_percent(endpoint_id, Duration::days(7), now),
+                    uptime_30d: self.uptime_percent(
+                        endpoint_id,
+                        Duration::days(RETENTION_DAYS),
+                        now,
+                    ),
+                })
+            })
+            .collect();
+        endpoints.sort_by(|a, b| a.endpoint_id.cmp(&b.endpoint_id));
+
+        AvailabilityReport {
+            generated_at: now,
+            endpoints,
+        }
+    }
+}
+
+/// `part / whole` as a percentage, for millisecond counts well within f64 precision
+fn ratio_percent(part: i64, whole: i64) -> f64 {
+    let part = f64::from(i32::try_from(part / 1000).unwrap_or(i32::MAX));
+    let whole = f64::from(i32::try_from(whole / 1000).unwrap_or(i32::MAX));
+    if whole <= 0.0 {
+        return 100.0;
+    }
+    (part / whole * 100.0).clamp(0.0, 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hours: i64) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-10-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+            + Duration::hours(hours)
+    }
+
+    #[test]
+    fn test_only_transitions_recorded() {
+        let mut ledger = AvailabilityLedger::new();
+        assert!(ledger.record("eu", true, at(0)));
+        assert!(!ledger.record("eu", true, at(1)));
+        assert!(ledger.record("eu", false, at(2)));
+        assert!(ledger.record("eu", true, at(3)));
+
+        assert_eq!(ledger.transitions("eu").len(), 3);
+        assert!(ledger.transitions("missing").is_empty());
+    }
+
+    #[test]
+    fn test_uptime_windows() {
+        let mut ledger = AvailabilityLedger::new();
+        // Up for 6 days, down for 6 hours, then up again
+        ledger.record("eu", true, at(0));
+        ledger.record("eu", false, at(144));
+        ledger.record("eu", true, at(150));
+        let now = at(168);
+
+        let day = ledger
+            .uptime_percent("eu", Duration::hours(24), now)
+            .unwrap();
+        assert!((day - 75.0).abs() < 0.01);
+
+        // Only the 7 days observed count towards the 30 day window
+        let month = ledger
+            .uptime_percent("eu", Duration::days(30), now)
+            .unwrap();
+        assert!((month - (162.0 / 168.0 * 100.0)).abs() < 0.01);
+
+        let report = ledger.report(now);
+        assert_eq!(report.endpoints.len(), 1);
+        assert_eq!(report.endpoints[0].current_state, AvailabilityState::Up);
+    }
+
+    #[test]
+    fn test_ledger_round_trip() {
+        let mut ledger = AvailabilityLedger::new();
+        ledger.record("eu", false, at(0));
+
+        let path = std::env::temp_dir().join(format!("ledger-{}.json", uuid::Uuid::new_v4()));
+        ledger.save(&path).unwrap();
+        let loaded = AvailabilityLedger::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.transitions("eu"), ledger.transitions("eu"));
+        assert_eq!(
+            loaded.uptime_percent("eu", Duration::hours(1), at(1)),
+            Some(0.0)
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! Latency under load and the bufferbloat grade it earns
+//!
+//! Latency measured on an idle link understates what users feel: while a
+//! download or a video call fills the link, oversized buffers along the
+//! path queue packets and every round trip waits behind them. A
+//! [`BufferbloatResult`] compares the median round trip to a region with the
+//! link idle and with a bulk transfer saturating it, and grades the increase
+//! on the scale common speed tests use.
+
+use std::fmt::{self, Write as _};
+
+use serde::{Deserialize, Serialize};
+
+use super::utils::percentile;
+use crate::collector::csv_field;
+
+/// Grade of a latency increase under load, best first
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BufferbloatGrade {
+    /// Under 5 ms added
+    #[serde(rename = "A+")]
+    APlus,
+    /// Under 30 ms added
+    A,
+    /// Under 60 ms added
+    B,
+    /// Under 200 ms added
+    C,
+    /// Under 400 ms added
+    D,
+    /// 400 ms or more added
+    F,
+}
+
+impl BufferbloatGrade {
+    /// Upper bounds in ms of the latency increase of each grade but `F`
+    pub const BOUNDS_MS: [(Self, f64); 5] = [
+        (Self::APlus, 5.0),
+        (Self::A, 30.0),
+        (Self::B, 60.0),
+        (Self::C, 200.0),
+        (Self::D, 400.0),
+    ];
+
+    /// Grade of a latency increase of `increase_ms`
+    #[must_use]
+    pub fn of(increase_ms: f64) -> Self {
+        Self::BOUNDS_MS
+            .iter()
+            .find(|(_, bound)| increase_ms < *bound)
+            .map_or(Self::F, |(grade, _)| *grade)
+    }
+}
+
+impl fmt::Display for BufferbloatGrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::APlus => "A+",
+            Self::A => "A",
+            Self::B => "B",
+            Self::C => "C",
+            Self::D => "D",
+            Self::F => "F",
+        })
+    }
+}
+
+/// Round trips to one region with the link idle and under load
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BufferbloatResult {
+    /// Region the round trips were measured to
+    pub region: String,
+    /// URL the bulk transfer loading the link came from
+    pub load_url: String,
+    /// Median round trip with the link idle, in milliseconds
+    pub idle_ms: Option<f64>,
+    /// Median round trip during the bulk transfer, in milliseconds
+    pub loaded_ms: Option<f64>,
+    /// Round trips that succeeded during the bulk transfer
+    pub loaded_samples: usize,
+    /// Throughput of the bulk transfer in megabits per second
+    pub download_mbps: Option<f64>,
+    /// Grade of the increase; absent unless both medians were measured
+    pub grade: Option<BufferbloatGrade>,
+}
+
+impl BufferbloatResult {
+    /// Result from the round trips measured idle and loaded, in milliseconds
+    #[must_use]
+    pub fn from_samples(
+        region: String,
+        load_url: String,
+        idle: &[f64],
+        loaded: &[f64],
+        download_mbps: Option<f64>,
+    ) -> Self {
+        let median = |samples: &[f64]| (!samples.is_empty()).then(|| percentile(samples, 50.0));
+        let mut result = Self {
+            region,
+            load_url,
+            idle_ms: median(idle),
+            loaded_ms: median(loaded),
+            loaded_samples: loaded.len(),
+            download_mbps,
+            grade: None,
+        };
+        result.grade = result.increase_ms().map(BufferbloatGrade::of);
+        result
+    }
+
+    /// Latency the load added in milliseconds, never negative
+    #[must_use]
+    pub fn increase_ms(&self) -> Option<f64> {
+        Some((self.loaded_ms? - self.idle_ms?).max(0.0))
+    }
+
+    /// Grade of the local network: the median increase over every graded
+    /// region, since the queue that grows under load is usually on the
+    /// access link all regions share
+    #[must_use]
+    pub fn network_grade(results: &[Self]) -> Option<(BufferbloatGrade, f64)> {
+        let increases: Vec<f64> = results.iter().filter_map(Self::increase_ms).collect();
+        if increases.is_empty() {
+            return None;
+        }
+        let increase = percentile(&increases, 50.0);
+        Some((BufferbloatGrade::of(increase), increase))
+    }
+
+    /// One CSV line per result, with a header row
+    #[must_use]
+    pub fn to_csv(results: &[Self]) -> String {
+        let number = |value: Option<f64>| value.map_or_else(String::new, |value| format!("{value:.1}"));
+        let mut csv = String::from("region,idle_ms,loaded_ms,increase_ms,grade,loaded_samples,download_mbps,load_url\n");
+        for result in results {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{}",
+                csv_field(&result.region),
+                number(result.idle_ms),
+                number(result.loaded_ms),
+                number(result.increase_ms()),
+                result.grade.map_or_else(String::new, |grade| grade.to_string()),
+                result.loaded_samples,
+                number(result.download_mbps),
+                csv_field(&result.load_url)
+            );
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bufferbloat_grades_the_latency_increase() {
+        assert_eq!(BufferbloatGrade::of(0.0), BufferbloatGrade::APlus);
+        assert_eq!(BufferbloatGrade::of(30.0), BufferbloatGrade::B);
+        assert_eq!(BufferbloatGrade::of(450.0), BufferbloatGrade::F);
+        assert!(BufferbloatGrade::APlus < BufferbloatGrade::A);
+
+        let bloated = BufferbloatResult::from_samples(
+            "Frankfurt".to_string(),
+            "https://speed.cloudflare.com/__down?bytes=1".to_string(),
+            &[20.0, 22.0, 21.0],
+            &[95.0, 120.0, 110.0, 140.0],
+            Some(250.0),
+        );
+        assert_eq!(bloated.increase_ms(), Some(94.0));
+        assert_eq!(bloated.grade, Some(BufferbloatGrade::C));
+
+        let clean = BufferbloatResult::from_samples("Paris".to_string(), String::new(), &[30.0], &[31.0], None);
+        let unloaded = BufferbloatResult::from_samples("Oslo".to_string(), String::new(), &[30.0], &[], None);
+        assert_eq!(unloaded.grade, None);
+        assert_eq!(
+            BufferbloatResult::network_grade(&[bloated.clone(), clean.clone(), unloaded]),
+            Some((BufferbloatGrade::B, 47.5))
+        );
+        assert_eq!(serde_json::to_value(clean.grade).unwrap(), "A+");
+
+        let csv = BufferbloatResult::to_csv(&[bloated]);
+        assert!(csv.lines().nth(1).unwrap().starts_with("Frankfurt,21.0,115.0,94.0,C,4,250.0,"));
+    }
+}
@@ -0,0 +1,256 @@
+//! Carbon intensity of regions and the greenest acceptable region
+//!
+//! Carbon intensity, in grams of CO2 per kWh of the grid a region runs on,
+//! comes from the `carbon_g_per_kwh` metadata of a region in the data file,
+//! from a feed fetched from `carbon_url`, and from a static `carbon_file`,
+//! each overriding the one before. [`GreenThreshold::recommend`] picks the
+//! region with the lowest intensity among those fast and good enough.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::region::Region;
+use super::scoring::utils::RankedResult;
+use crate::error::{CloudPingError, Result};
+
+/// Region metadata key holding the carbon intensity in gCO2/kWh
+pub const CARBON_METADATA_KEY: &str = "carbon_g_per_kwh";
+
+/// Carbon intensity in gCO2/kWh per region, keyed by region name or URL
+///
+/// ```toml
+/// "Stockholm" = 25.0
+/// "https://ap-south-1.example.com" = 630.0
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(transparent)]
+pub struct CarbonIntensity(BTreeMap<String, f64>);
+
+impl CarbonIntensity {
+    /// Read a TOML carbon intensity file
+    ///
+    /// # Errors
+    /// Returns an error when the file cannot be read, is not a table of
+    /// intensities or has a negative one
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let intensity: Self = toml::from_str(&content).map_err(|e| {
+            CloudPingError::data_loading(format!("Invalid carbon file {}: {e}", path.display()))
+        })?;
+        intensity.validate()?;
+        Ok(intensity)
+    }
+
+    /// # Errors
+    /// Returns a validation error naming the first region with a negative intensity
+    pub fn validate(&self) -> Result<()> {
+        match self.0.iter().find(|(_, value)| !value.is_finite() || **value < 0.0) {
+            Some((key, _)) => Err(CloudPingError::validation(
+                format!("carbon.{key}"),
+                "intensity must not be negative",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Add the intensities of `other`, which win over those already set
+    /// under the same key in any case
+    pub fn merge(&mut self, other: Self) {
+        for (key, value) in other.0 {
+            self.0.retain(|existing, _| !existing.eq_ignore_ascii_case(&key));
+            self.0.insert(key, value);
+        }
+    }
+
+    /// Intensity of `region`: its metadata overridden by the entry for its
+    /// name, then by the entry for its URL
+    ///
+    /// Names are compared case-insensitively; an unparsable metadata value is ignored.
+    #[must_use]
+    pub fn for_region(&self, region: &Region) -> Option<f64> {
+        let from_metadata = region
+            .metadata
+            .get(CARBON_METADATA_KEY)
+            .and_then(|value| value.trim().parse::<f64>().ok());
+        let by_name = self
+            .0
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(&region.name))
+            .map(|(_, value)| *value);
+        from_metadata
+            .into_iter()
+            .chain(by_name)
+            .chain(self.0.get(&region.url).copied())
+            .last()
+    }
+}
+
+/// What a region must reach to be recommended as the greenest
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GreenThreshold {
+    /// Lowest acceptable overall score
+    #[serde(default = "default_min_score")]
+    pub min_score: f64,
+    /// Highest acceptable average latency in milliseconds; any latency when unset
+    #[serde(default)]
+    pub max_latency_ms: Option<f64>,
+}
+
+const fn default_min_score() -> f64 {
+    70.0
+}
+
+impl Default for GreenThreshold {
+    fn default() -> Self {
+        Self {
+            min_score: default_min_score(),
+            max_latency_ms: None,
+        }
+    }
+}
+
+/// Lowest-carbon region that meets a [`GreenThreshold`]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GreenRecommendation {
+    /// Recommended region
+    pub region: String,
+    /// Provider of the region, empty when unknown
+    pub provider: String,
+    /// Carbon intensity of the region in gCO2/kWh
+    pub carbon_g_per_kwh: f64,
+    /// Overall score of the region
+    pub score: f64,
+    /// Average latency of the region
+    pub latency_ms: f64,
+    /// Best scoring region, for comparison
+    pub top_region: String,
+    /// Carbon intensity of the best scoring region, absent when unknown
+    pub top_carbon_g_per_kwh: Option<f64>,
+}
+
+impl GreenThreshold {
+    /// # Errors
+    /// Returns a validation error naming the first invalid setting
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=100.0).contains(&self.min_score) {
+            return Err(CloudPingError::validation(
+                "green.min_score",
+                "must be between 0 and 100",
+            ));
+        }
+        if self.max_latency_ms.is_some_and(|latency| latency <= 0.0) {
+            return Err(CloudPingError::validation(
+                "green.max_latency_ms",
+                "must be greater than 0",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Region of `ranked` with the lowest carbon intensity among those that
+    /// answered and meet the threshold; the higher score wins a tie
+    ///
+    /// Returns `None` when no such region has a known intensity.
+    #[must_use]
+    pub fn recommend(&self, ranked: &[RankedResult]) -> Option<GreenRecommendation> {
+        let top = ranked.iter().max_by(|a, b| a.0.total_cmp(&b.0))?;
+        let (carbon, (score, name, stats, _)) = ranked
+            .iter()
+            .filter(|(score, _, stats, _)| {
+                stats.is_successful()
+                    && *score >= self.min_score
+                    && self.max_latency_ms.map_or(true, |max| stats.avg <= max)
+            })
+            .filter_map(|result| result.2.carbon_g_per_kwh.map(|carbon| (carbon, result)))
+            .min_by(|a, b| a.0.total_cmp(&b.0).then_with(|| b.1 .0.total_cmp(&a.1 .0)))?;
+        Some(GreenRecommendation {
+            region: name.clone(),
+            provider: stats.provider.clone(),
+            carbon_g_per_kwh: carbon,
+            score: *score,
+            latency_ms: stats.avg,
+            top_region: top.1.clone(),
+            top_carbon_g_per_kwh: top.2.carbon_g_per_kwh,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AlgorithmWeights, PingStats, ScoringAdapter};
+
+    #[test]
+    fn test_carbon_intensity() {
+        let mut intensity: CarbonIntensity = toml::from_str(
+            r#"
+            stockholm = 40.0
+            "https://eu-north.example.com" = 25.0
+            "#,
+        )
+        .unwrap();
+        intensity.merge(toml::from_str(r#""Paris" = 55.0"#).unwrap());
+        let mut region = Region::builder(
+            "Stockholm".to_string(),
+            "https://eu-north.example.com".to_string(),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+        region
+            .metadata
+            .insert(CARBON_METADATA_KEY.to_string(), "90".to_string());
+
+        assert_eq!(intensity.for_region(&region), Some(25.0));
+        region.url = "https://other.example.com".to_string();
+        assert_eq!(intensity.for_region(&region), Some(40.0));
+        // A later source wins even when it spells the name differently
+        intensity.merge(toml::from_str(r#""STOCKHOLM" = 30.0"#).unwrap());
+        assert_eq!(intensity.for_region(&region), Some(30.0));
+        assert_eq!(CarbonIntensity::default().for_region(&region), Some(90.0));
+        assert!(toml::from_str::<CarbonIntensity>("x = -1.0").unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_green_recommendation() {
+        let region = |name: &str, avg: f64, carbon: Option<f64>| {
+            let mut stats = PingStats::new(10);
+            stats.successful_pings = 10;
+            stats.avg = avg;
+            stats.latencies = vec![avg; 10];
+            stats.carbon_g_per_kwh = carbon;
+            (name.to_string(), stats)
+        };
+        let mut lossy = region("Sydney", 900.0, Some(5.0));
+        lossy.1.successful_pings = 6;
+        let ranked = ScoringAdapter::get_sorted_results(
+            &[
+                region("Frankfurt", 10.0, Some(380.0)),
+                region("Stockholm", 45.0, Some(25.0)),
+                lossy,
+                region("Paris", 20.0, None),
+            ],
+            &AlgorithmWeights::default(),
+        );
+
+        // Sydney is greener but too slow and lossy to score 70
+        let green = GreenThreshold::default().recommend(&ranked).unwrap();
+        assert_eq!(green.region, "Stockholm");
+        assert_eq!(green.top_region, "Frankfurt");
+        assert_eq!(green.top_carbon_g_per_kwh, Some(380.0));
+
+        let strict = GreenThreshold {
+            max_latency_ms: Some(30.0),
+            ..GreenThreshold::default()
+        };
+        assert_eq!(strict.recommend(&ranked).unwrap().region, "Frankfurt");
+        let impossible = GreenThreshold {
+            max_latency_ms: Some(5.0),
+            ..GreenThreshold::default()
+        };
+        assert_eq!(impossible.recommend(&ranked), None);
+        assert!(GreenThreshold { min_score: 120.0, max_latency_ms: None }.validate().is_err());
+    }
+}
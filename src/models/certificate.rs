@@ -0,0 +1,123 @@
+//! Certificate validation timing
+//!
+//! A TLS handshake includes validating the server's certificate: building
+//! the chain from the leaf through its issuers to a trusted root and
+//! checking every signature on the way. With `validate_certificates` on,
+//! each validation is timed on its own, so a slow handshake can be put down
+//! to the chain rather than the network. Whether the server stapled an OCSP
+//! response to the handshake is recorded alongside; the staple itself is not
+//! checked.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Metadata key for the number of certificate validations during a test
+pub const CERT_VALIDATIONS_METADATA_KEY: &str = "cert_validations";
+
+/// Metadata key for the average certificate validation time
+pub const CERT_VALIDATION_AVG_MS_METADATA_KEY: &str = "cert_validation_avg_ms";
+
+/// Metadata key for the longest certificate validation time
+pub const CERT_VALIDATION_MAX_MS_METADATA_KEY: &str = "cert_validation_max_ms";
+
+/// Metadata key for the number of validations whose handshake had a stapled OCSP response
+pub const OCSP_STAPLED_METADATA_KEY: &str = "ocsp_stapled";
+
+/// Metadata key for the number of certificates the server presented
+pub const CERT_CHAIN_LENGTH_METADATA_KEY: &str = "cert_chain_length";
+
+/// Validation of the certificate presented in one TLS handshake
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct CertValidation {
+    /// Time spent building and verifying the chain in milliseconds
+    pub validation_ms: f64,
+    /// Whether the server stapled an OCSP response
+    pub ocsp_stapled: bool,
+    /// Certificates the server presented, the leaf included
+    pub chain_length: usize,
+}
+
+/// Certificate validations over the pings of one test
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CertValidationSummary {
+    /// Validations during the test, one per new connection
+    pub validations: usize,
+    /// Average validation time in milliseconds
+    pub avg_ms: f64,
+    /// Longest validation time in milliseconds
+    pub max_ms: f64,
+    /// Validations whose handshake had a stapled OCSP response
+    pub ocsp_stapled: usize,
+    /// Certificates presented in the last handshake, the leaf included
+    pub chain_length: usize,
+}
+
+impl CertValidationSummary {
+    /// Summarize `validations` in the order they happened, `None` when there are none
+    #[must_use]
+    pub fn from_validations(validations: &[CertValidation]) -> Option<Self> {
+        let last = validations.last()?;
+        let times: Vec<f64> = validations.iter().map(|validation| validation.validation_ms).collect();
+        Some(Self {
+            validations: validations.len(),
+            avg_ms: statistical::mean(&times),
+            max_ms: times.iter().copied().fold(0.0, f64::max),
+            ocsp_stapled: validations.iter().filter(|validation| validation.ocsp_stapled).count(),
+            chain_length: last.chain_length,
+        })
+    }
+
+    /// Store the summary in `metadata`
+    pub fn write_metadata(&self, metadata: &mut HashMap<String, String>) {
+        metadata.insert(CERT_VALIDATIONS_METADATA_KEY.to_string(), self.validations.to_string());
+        metadata.insert(CERT_VALIDATION_AVG_MS_METADATA_KEY.to_string(), format!("{:.3}", self.avg_ms));
+        metadata.insert(CERT_VALIDATION_MAX_MS_METADATA_KEY.to_string(), format!("{:.3}", self.max_ms));
+        metadata.insert(OCSP_STAPLED_METADATA_KEY.to_string(), self.ocsp_stapled.to_string());
+        metadata.insert(CERT_CHAIN_LENGTH_METADATA_KEY.to_string(), self.chain_length.to_string());
+    }
+
+    /// Read a summary stored by [`Self::write_metadata`], absent when it is missing
+    #[must_use]
+    pub fn from_metadata(metadata: &HashMap<String, String>) -> Option<Self> {
+        let count = |key| metadata.get(key)?.parse::<usize>().ok();
+        let ms = |key| metadata.get(key)?.parse::<f64>().ok();
+        Some(Self {
+            validations: count(CERT_VALIDATIONS_METADATA_KEY)?,
+            avg_ms: ms(CERT_VALIDATION_AVG_MS_METADATA_KEY)?,
+            max_ms: ms(CERT_VALIDATION_MAX_MS_METADATA_KEY)?,
+            ocsp_stapled: count(OCSP_STAPLED_METADATA_KEY).unwrap_or(0),
+            chain_length: count(CERT_CHAIN_LENGTH_METADATA_KEY).unwrap_or(0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cert_validation_summary() {
+        let validation = |validation_ms, ocsp_stapled| CertValidation {
+            validation_ms,
+            ocsp_stapled,
+            chain_length: 3,
+        };
+        assert_eq!(CertValidationSummary::from_validations(&[]), None);
+
+        let summary = CertValidationSummary::from_validations(&[
+            validation(1.5, true),
+            validation(0.5, false),
+        ])
+        .unwrap();
+        assert_eq!(summary.validations, 2);
+        assert!((summary.avg_ms - 1.0).abs() < 1e-9);
+        assert!((summary.max_ms - 1.5).abs() < 1e-9);
+        assert_eq!((summary.ocsp_stapled, summary.chain_length), (1, 3));
+
+        let mut metadata = HashMap::new();
+        summary.write_metadata(&mut metadata);
+        assert_eq!(metadata[OCSP_STAPLED_METADATA_KEY], "1");
+        assert_eq!(CertValidationSummary::from_metadata(&metadata), Some(summary));
+    }
+}
@@ -0,0 +1,220 @@
+//! Change-point detection in test history
+//!
+//! Finds step changes in a region's latency with binary segmentation: the
+//! series is split where a shift in mean explains the most variance, and each
+//! half is searched again while the improvement beats a BIC-style penalty
+//! scaled by the noise level. This pinpoints "latency stepped up by 30 ms on
+//! March 3" where a linear trend would only report a gentle slope.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::stats::TestHistory;
+use super::utils::percentile;
+
+/// Fewest runs on either side of a change
+const MIN_SEGMENT_LEN: usize = 3;
+
+/// Smallest shift in mean latency reported as a change
+const MIN_SHIFT_MS: f64 = 5.0;
+
+/// Multiplier of `σ² ln n` a split must reduce the squared error by
+const PENALTY_FACTOR: f64 = 3.0;
+
+/// Scale turning the median absolute successive difference into a noise σ
+/// (`0.6745 * sqrt(2)` for normally distributed noise)
+const MAD_DIFF_TO_SIGMA: f64 = 0.953_9;
+
+/// A step change in mean latency
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChangePoint {
+    /// Time of the first run after the change
+    pub at: DateTime<Utc>,
+    /// Mean latency of the segment before the change
+    pub before_ms: f64,
+    /// Mean latency of the segment after the change
+    pub after_ms: f64,
+    /// Whether the path to the region changed with the step, pointing at
+    /// routing rather than the provider
+    #[serde(default)]
+    pub path_changed: bool,
+}
+
+impl ChangePoint {
+    /// Signed size of the step; positive when latency went up
+    #[must_use]
+    pub fn delta_ms(&self) -> f64 {
+        self.after_ms - self.before_ms
+    }
+
+    /// Sentence describing the change for a region
+    #[must_use]
+    pub fn describe(&self, region: &str) -> String {
+        let direction = if self.delta_ms() > 0.0 { "up" } else { "down" };
+        format!(
+            "Latency to {region} stepped {direction} by {:.0} ms on {} ({:.1} ms -> {:.1} ms){}",
+            self.delta_ms().abs(),
+            self.at.format("%Y-%m-%d"),
+            self.before_ms,
+            self.after_ms,
+            if self.path_changed { ", path changed" } else { "" }
+        )
+    }
+}
+
+impl TestHistory {
+    /// Step changes in average latency across successful runs, oldest first
+    #[must_use]
+    pub fn detect_change_points(&self) -> Vec<ChangePoint> {
+        let runs: Vec<_> = self
+            .historical_data
+            .iter()
+            .filter(|stats| stats.is_successful())
+            .collect();
+        let series: Vec<f64> = runs.iter().map(|stats| stats.avg).collect();
+
+        let mut boundaries = detect_mean_shifts(&series);
+        boundaries.sort_unstable();
+        let route_changes = self.detect_route_changes();
+
+        let mut segment_start = 0;
+        let mut change_points = Vec::with_capacity(boundaries.len());
+        for (i, &boundary) in boundaries.iter().enumerate() {
+            let segment_end = boundaries.get(i + 1).copied().unwrap_or(series.len());
+            let (last_before, at) = (runs[boundary - 1].test_time, runs[boundary].test_time);
+            change_points.push(ChangePoint {
+                at,
+                before_ms: statistical::mean(&series[segment_start..boundary]),
+                after_ms: statistical::mean(&series[boundary..segment_end]),
+                path_changed: route_changes
+                    .iter()
+                    .any(|change| change.at > last_before && change.at <= at),
+            });
+            segment_start = boundary;
+        }
+        change_points
+    }
+}
+
+/// Indices where a new mean level starts, found by binary segmentation
+#[must_use]
+pub fn detect_mean_shifts(series: &[f64]) -> Vec<usize> {
+    if series.len() < 2 * MIN_SEGMENT_LEN {
+        return Vec::new();
+    }
+
+    let sigma = noise_sigma(series);
+    let n = f64::from(u32::try_from(series.len()).unwrap_or(u32::MAX));
+    let penalty = PENALTY_FACTOR * sigma.powi(2) * n.ln();
+
+    let mut boundaries = Vec::new();
+    let mut pending = vec![(0, series.len())];
+    while let Some((start, end)) = pending.pop() {
+        if let Some(split) = best_split(&series[start..end], penalty) {
+            boundaries.push(start + split);
+            pending.push((start, start + split));
+            pending.push((start + split, end));
+        }
+    }
+    boundaries
+}
+
+/// Split point of `segment` that most reduces squared error, if it beats `penalty`
+fn best_split(segment: &[f64], penalty: f64) -> Option<usize> {
+    if segment.len() < 2 * MIN_SEGMENT_LEN {
+        return None;
+    }
+
+    let total: f64 = segment.iter().sum();
+    let len = f64::from(u32::try_from(segment.len()).ok()?);
+    let mut left_sum = 0.0;
+    let mut best: Option<(usize, f64)> = None;
+
+    for (i, value) in segment
+        .iter()
+        .enumerate()
+        .take(segment.len() - MIN_SEGMENT_LEN)
+    {
+        left_sum += value;
+        let split = i + 1;
+        if split < MIN_SEGMENT_LEN {
+            continue;
+        }
+
+        let left_len = f64::from(u32::try_from(split).ok()?);
+        let right_len = len - left_len;
+        let shift = (total - left_sum) / right_len - left_sum / left_len;
+        // Reduction in squared error from fitting two means instead of one
+        let gain = left_len * right_len / len * shift.powi(2);
+
+        if shift.abs() >= MIN_SHIFT_MS && best.map_or(true, |(_, best_gain)| gain > best_gain) {
+            best = Some((split, gain));
+        }
+    }
+
+    best.filter(|&(_, gain)| gain > penalty)
+        .map(|(split, _)| split)
+}
+
+/// Robust noise level from successive differences, unaffected by the steps themselves
+fn noise_sigma(series: &[f64]) -> f64 {
+    let diffs: Vec<f64> = series.windows(2).map(|w| (w[1] - w[0]).abs()).collect();
+    let sigma = percentile(&diffs, 50.0) / MAD_DIFF_TO_SIGMA;
+    // A perfectly flat series would make any step significant; assume 1 ms of noise
+    sigma.max(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{PingStats, RoutePath};
+
+    #[test]
+    fn test_detects_single_step() {
+        let series = [20.0, 21.0, 19.5, 20.5, 20.0, 50.0, 51.0, 49.0, 50.5, 50.0];
+        assert_eq!(detect_mean_shifts(&series), vec![5]);
+
+        // Noise alone is not a change
+        let noisy = [20.0, 24.0, 18.0, 23.0, 19.0, 22.0, 17.0, 24.0, 20.0, 21.0];
+        assert!(detect_mean_shifts(&noisy).is_empty());
+    }
+
+    #[test]
+    fn test_history_change_points() {
+        let mut history = TestHistory::new(
+            "eu".to_string(),
+            "Frankfurt".to_string(),
+            "https://eu.example.com".to_string(),
+        );
+        let start = DateTime::parse_from_rfc3339("2026-03-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let levels = [
+            20.0, 20.0, 21.0, 20.0, 50.0, 51.0, 50.0, 49.0, 20.0, 21.0, 20.0,
+        ];
+
+        for (day, avg) in (0..).zip(levels) {
+            let mut stats = PingStats::new(10);
+            stats.successful_pings = 10;
+            stats.avg = avg;
+            stats.test_time = start + chrono::Duration::days(day);
+            // The way back down came with a new transit provider
+            stats.route = Some(RoutePath {
+                final_asn: Some(if day < 8 { 3320 } else { 1299 }),
+                ..RoutePath::default()
+            });
+            history.add_test_result(stats);
+        }
+
+        let changes = history.detect_change_points();
+        assert_eq!(changes.len(), 2);
+        assert!((changes[0].delta_ms() - 29.0).abs() < 1.0);
+        assert!(!changes[0].path_changed);
+        assert!(changes[0]
+            .describe("Frankfurt")
+            .starts_with("Latency to Frankfurt stepped up by 30 ms on 2026-03-05"));
+        assert!(changes[1].delta_ms() < 0.0);
+        assert!(changes[1].path_changed);
+        assert!(changes[1].describe("Frankfurt").ends_with(", path changed"));
+    }
+}
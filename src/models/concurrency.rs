@@ -0,0 +1,34 @@
+//! Concurrency changes made during a run
+//!
+//! When the adaptive controller detects that the local uplink is saturated it
+//! lowers the number of regions tested at once. Each change is recorded so
+//! reports can show that later measurements ran at reduced concurrency.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// One reduction of the number of regions tested at once
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConcurrencyAdjustment {
+    /// When the change was made
+    pub at: DateTime<Utc>,
+    /// Regions allowed at once before the change
+    pub from_threads: usize,
+    /// Regions allowed at once after the change
+    pub to_threads: usize,
+    /// Reference endpoint latency that triggered the change, in milliseconds
+    pub reference_ms: f64,
+    /// Reference endpoint latency measured before the run, in milliseconds
+    pub baseline_ms: f64,
+}
+
+impl ConcurrencyAdjustment {
+    /// One-line description for reports
+    #[must_use]
+    pub fn summary(&self) -> String {
+        format!(
+            "{} -> {} regions at once (reference {:.1}ms, {:.1}ms before the run)",
+            self.from_threads, self.to_threads, self.reference_ms, self.baseline_ms
+        )
+    }
+}
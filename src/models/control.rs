@@ -0,0 +1,142 @@
+//! Control endpoint measurements
+//!
+//! Local conditions such as a busy Wi-Fi network or a congested ISP add the
+//! same delay to every region tested at that moment. A control endpoint is
+//! pinged throughout a run; subtracting its latency while a region was tested
+//! gives a normalized latency that stays comparable across runs taken under
+//! different local conditions.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::stats::PingStats;
+
+/// One ping of the control endpoint
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ControlSample {
+    /// When the ping was sent
+    pub at: DateTime<Utc>,
+    /// Round-trip time in milliseconds, `None` if the ping failed
+    pub latency_ms: Option<f64>,
+}
+
+/// Control endpoint pings taken during a run
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ControlSeries {
+    /// Control endpoint URL
+    pub url: String,
+    /// Pings in the order they were sent
+    pub samples: Vec<ControlSample>,
+}
+
+impl ControlSeries {
+    /// Median control latency over the whole run
+    #[must_use]
+    pub fn baseline_ms(&self) -> Option<f64> {
+        median(self.samples.iter().filter_map(|sample| sample.latency_ms))
+    }
+
+    /// Median control latency between `start` and `end`, or over the whole run
+    /// when no successful ping fell in that window
+    #[must_use]
+    pub fn latency_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Option<f64> {
+        median(
+            self.samples
+                .iter()
+                .filter(|sample| (start..=end).contains(&sample.at))
+                .filter_map(|sample| sample.latency_ms),
+        )
+        .or_else(|| self.baseline_ms())
+    }
+
+    /// Record the control latency while `stats` was measured and the latency normalized against it
+    pub fn normalize(&self, stats: &mut PingStats) {
+        let duration_ms = i64::try_from(stats.test_duration_ms).unwrap_or(i64::MAX);
+        let end = stats.test_time + chrono::Duration::milliseconds(duration_ms);
+        let Some(control_ms) = self.latency_between(stats.test_time, end) else {
+            return;
+        };
+
+        stats.control_latency_ms = Some(control_ms);
+        stats.normalized_avg = stats
+            .is_successful()
+            .then_some((stats.avg - control_ms).max(0.0));
+    }
+}
+
+fn median(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let mut values: Vec<f64> = values.collect();
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    Some(if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(start: DateTime<Utc>, latencies: &[Option<f64>]) -> ControlSeries {
+        ControlSeries {
+            url: "https://control.example.com".to_string(),
+            samples: latencies
+                .iter()
+                .zip(0..)
+                .map(|(&latency_ms, second)| ControlSample {
+                    at: start + chrono::Duration::seconds(second),
+                    latency_ms,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_latency_between_uses_window_then_whole_run() {
+        let start = Utc::now();
+        let control = series(
+            start,
+            &[Some(10.0), Some(12.0), None, Some(40.0), Some(44.0)],
+        );
+
+        assert_eq!(control.baseline_ms(), Some(26.0));
+        assert_eq!(
+            control.latency_between(start, start + chrono::Duration::seconds(2)),
+            Some(11.0)
+        );
+        assert_eq!(
+            control.latency_between(
+                start + chrono::Duration::seconds(3),
+                start + chrono::Duration::seconds(4)
+            ),
+            Some(42.0)
+        );
+        // Nothing in the window falls back to the whole run
+        let later = start + chrono::Duration::minutes(5);
+        assert_eq!(control.latency_between(later, later), Some(26.0));
+        assert_eq!(ControlSeries::default().baseline_ms(), None);
+    }
+
+    #[test]
+    fn test_normalize_subtracts_control_latency() {
+        let mut stats = PingStats::new(5);
+        stats.successful_pings = 5;
+        stats.avg = 55.0;
+        stats.test_duration_ms = 2_000;
+        let control = series(stats.test_time, &[Some(15.0), Some(25.0)]);
+
+        control.normalize(&mut stats);
+        assert_eq!(stats.control_latency_ms, Some(20.0));
+        assert_eq!(stats.normalized_avg, Some(35.0));
+
+        // Regions that never answered keep no normalized latency
+        let mut failed = PingStats::new(5);
+        control.normalize(&mut failed);
+        assert_eq!(failed.normalized_avg, None);
+    }
+}
@@ -0,0 +1,198 @@
+//! Countries of regions
+//!
+//! Data files rarely set a region's `country`, but most region names say
+//! where the region is, e.g. `Frankfurt (Germany)`, `UK South` or
+//! `eu-west-3 (Paris)`. [`country_from_name`] reads the ISO 3166-1 alpha-2
+//! code from the countries, cities and region code prefixes it knows, and
+//! [`country_code`] turns a country typed as a code or a name into its code
+//! so report filters can take either.
+
+/// Places and the country code they stand for, countries first so that
+/// `Santiago de Querétaro (Mexico)` is Mexican and not Chilean
+const PLACE_COUNTRIES: &[(&str, &str)] = &[
+    ("united states", "US"),
+    ("usa", "US"),
+    ("us", "US"),
+    ("united kingdom", "GB"),
+    ("uk", "GB"),
+    ("united arab emirates", "AE"),
+    ("uae", "AE"),
+    ("saudi arabia", "SA"),
+    ("south africa", "ZA"),
+    ("south korea", "KR"),
+    ("korea", "KR"),
+    ("new zealand", "NZ"),
+    ("hong kong", "HK"),
+    ("netherlands", "NL"),
+    ("germany", "DE"),
+    ("france", "FR"),
+    ("ireland", "IE"),
+    ("belgium", "BE"),
+    ("luxembourg", "LU"),
+    ("switzerland", "CH"),
+    ("austria", "AT"),
+    ("italy", "IT"),
+    ("spain", "ES"),
+    ("portugal", "PT"),
+    ("poland", "PL"),
+    ("sweden", "SE"),
+    ("norway", "NO"),
+    ("finland", "FI"),
+    ("denmark", "DK"),
+    ("serbia", "RS"),
+    ("türkiye", "TR"),
+    ("turkey", "TR"),
+    ("israel", "IL"),
+    ("qatar", "QA"),
+    ("bahrain", "BH"),
+    ("egypt", "EG"),
+    ("nigeria", "NG"),
+    ("kenya", "KE"),
+    ("india", "IN"),
+    ("japan", "JP"),
+    ("china", "CN"),
+    ("taiwan", "TW"),
+    ("singapore", "SG"),
+    ("malaysia", "MY"),
+    ("indonesia", "ID"),
+    ("thailand", "TH"),
+    ("australia", "AU"),
+    ("canada", "CA"),
+    ("mexico", "MX"),
+    ("brazil", "BR"),
+    ("chile", "CL"),
+    ("colombia", "CO"),
+    ("argentina", "AR"),
+    ("amsterdam", "NL"),
+    ("frankfurt", "DE"),
+    ("berlin", "DE"),
+    ("london", "GB"),
+    ("paris", "FR"),
+    ("dublin", "IE"),
+    ("zurich", "CH"),
+    ("milan", "IT"),
+    ("turin", "IT"),
+    ("madrid", "ES"),
+    ("stockholm", "SE"),
+    ("warsaw", "PL"),
+    ("johannesburg", "ZA"),
+    ("cape town", "ZA"),
+    ("dubai", "AE"),
+    ("abu dhabi", "AE"),
+    ("doha", "QA"),
+    ("tel aviv", "IL"),
+    ("mumbai", "IN"),
+    ("delhi", "IN"),
+    ("bangalore", "IN"),
+    ("chennai", "IN"),
+    ("hyderabad", "IN"),
+    ("tokyo", "JP"),
+    ("osaka", "JP"),
+    ("seoul", "KR"),
+    ("beijing", "CN"),
+    ("ningxia", "CN"),
+    ("taipei", "TW"),
+    ("jakarta", "ID"),
+    ("bangkok", "TH"),
+    ("sydney", "AU"),
+    ("melbourne", "AU"),
+    ("toronto", "CA"),
+    ("montreal", "CA"),
+    ("montréal", "CA"),
+    ("sao paulo", "BR"),
+    ("são paulo", "BR"),
+    ("santiago", "CL"),
+    ("new york", "US"),
+    ("san francisco", "US"),
+    ("silicon valley", "US"),
+    ("virginia", "US"),
+    ("washington", "US"),
+    ("chicago", "US"),
+    ("dallas", "US"),
+    ("seattle", "US"),
+    ("miami", "US"),
+    ("atlanta", "US"),
+    ("los angeles", "US"),
+    ("ashburn", "US"),
+    ("newark", "US"),
+];
+
+/// Lowercase words of `text` separated by single spaces, with a space on either end
+fn words(text: &str) -> String {
+    let text = text.to_lowercase().replace(|c: char| !c.is_alphanumeric(), " ");
+    let mut words = String::from(" ");
+    for word in text.split_whitespace() {
+        words.push_str(word);
+        words.push(' ');
+    }
+    words
+}
+
+/// Country code of the first country, city or region code prefix in `name`
+/// that [`PLACE_COUNTRIES`] knows, as whole words ignoring case and punctuation
+#[must_use]
+pub fn country_from_name(name: &str) -> Option<&'static str> {
+    let name = words(name);
+    PLACE_COUNTRIES
+        .iter()
+        .find(|(place, _)| name.contains(&format!(" {place} ")))
+        .map(|(_, code)| *code)
+}
+
+/// Upper-case ISO 3166-1 alpha-2 code of `country`, given as a code or as
+/// a place [`country_from_name`] knows, e.g. `GB` for `uk` or `United Kingdom`
+#[must_use]
+pub fn country_code(country: &str) -> Option<String> {
+    let country = country.trim();
+    let place = words(country);
+    PLACE_COUNTRIES
+        .iter()
+        .find(|(known, _)| place.trim() == *known)
+        .map(|(_, code)| (*code).to_string())
+        .or_else(|| {
+            (country.len() == 2 && country.chars().all(|c| c.is_ascii_alphabetic()))
+                .then(|| country.to_ascii_uppercase())
+        })
+}
+
+/// Whether `a` and `b` name the same country, as codes or places, ignoring case
+#[must_use]
+pub fn same_country(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+        || country_code(a).is_some_and(|code| country_code(b).as_deref() == Some(code.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_country_from_name() {
+        let country = |name| country_from_name(name);
+        assert_eq!(country("Frankfurt (Germany)"), Some("DE"));
+        assert_eq!(country("UK South (United Kingdom)"), Some("GB"));
+        assert_eq!(country("Newport (UK West)"), Some("GB"));
+        assert_eq!(country("us-east-1 (Virginia)"), Some("US"));
+        assert_eq!(country("eu-west-3 (Paris)"), Some("FR"));
+        assert_eq!(country("AMS1 (Amsterdam, Netherlands)"), Some("NL"));
+        assert_eq!(country("Santiago de Querétaro (Mexico)"), Some("MX"));
+        assert_eq!(country("southamerica-east1 (São Paulo)"), Some("BR"));
+        assert_eq!(country("Chicago, IL (US Central)"), Some("US"));
+        assert_eq!(country("Istanbul (Türkiye)"), Some("TR"));
+        assert_eq!(country("West Europe (Europe)"), None);
+        assert_eq!(country("Businessville"), None);
+    }
+
+    #[test]
+    fn test_country_code() {
+        assert_eq!(country_code("United Kingdom").as_deref(), Some("GB"));
+        assert_eq!(country_code(" uk ").as_deref(), Some("GB"));
+        assert_eq!(country_code("de").as_deref(), Some("DE"));
+        assert_eq!(country_code("Atlantis"), None);
+
+        assert!(same_country("Germany", "DE"));
+        assert!(same_country("france", "France"));
+        assert!(same_country("Atlantis", "atlantis"));
+        assert!(!same_country("Germany", "FR"));
+    }
+}
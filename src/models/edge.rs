@@ -0,0 +1,165 @@
+//! CDN edge detection
+//!
+//! A CDN endpoint answers from whichever edge POP (point of presence) the
+//! client is routed to, so its latency says more about that POP than about
+//! the CDN. [`EdgePop::from_headers`] identifies the POP from the headers
+//! each CDN adds to its responses:
+//!
+//! - Cloudflare: `cf-ray: 8a1b2c3d4e5f6789-FRA`
+//! - Fastly: `x-served-by: cache-fra-eddf8230046-FRA, cache-lhr7345-LHR`,
+//!   where the last cache is the edge that answered the client
+//! - Amazon `CloudFront`: `x-amz-cf-pop: FRA56-P1`
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// CDN that served a response
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Cdn {
+    /// Cloudflare, identified by `cf-ray`
+    Cloudflare,
+    /// Fastly, identified by `x-served-by`
+    Fastly,
+    /// Amazon `CloudFront`, identified by `x-amz-cf-pop`
+    CloudFront,
+}
+
+impl fmt::Display for Cdn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Cloudflare => "Cloudflare",
+            Self::Fastly => "Fastly",
+            Self::CloudFront => "CloudFront",
+        })
+    }
+}
+
+/// Edge POP that served a response
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EdgePop {
+    /// CDN the POP belongs to
+    pub cdn: Cdn,
+    /// POP code as the CDN reports it, e.g. `FRA` or `FRA56-P1`
+    pub pop: String,
+}
+
+impl EdgePop {
+    /// Identify the POP from response headers, looked up by lower-case name
+    ///
+    /// Returns `None` when no CDN header is present or it cannot be parsed.
+    pub fn from_headers<'a>(header: impl Fn(&str) -> Option<&'a str>) -> Option<Self> {
+        let edge = |cdn, pop: &str| {
+            let pop = pop.trim();
+            (!pop.is_empty() && pop.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+                .then(|| Self {
+                    cdn,
+                    pop: pop.to_ascii_uppercase(),
+                })
+        };
+        if let Some(ray) = header("cf-ray") {
+            return edge(Cdn::Cloudflare, ray.rsplit_once('-')?.1);
+        }
+        if let Some(pop) = header("x-amz-cf-pop") {
+            return edge(Cdn::CloudFront, pop);
+        }
+        let served_by = header("x-served-by")?;
+        let cache = served_by.rsplit(',').next()?.trim();
+        if !cache.starts_with("cache-") {
+            return None;
+        }
+        edge(Cdn::Fastly, cache.rsplit_once('-')?.1)
+    }
+}
+
+impl fmt::Display for EdgePop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.cdn, self.pop)
+    }
+}
+
+/// Successful pings one edge POP served during a test
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EdgePopCount {
+    /// POP that served the pings
+    pub edge: EdgePop,
+    /// Number of successful pings it served
+    pub pings: usize,
+    /// Average latency of those pings in milliseconds
+    pub avg_ms: f64,
+}
+
+impl EdgePopCount {
+    /// Count the POPs of successful pings given as `(POP, latency in ms)`,
+    /// most used first; ties are ordered by POP
+    #[must_use]
+    pub fn tally(pings: impl IntoIterator<Item = (EdgePop, f64)>) -> Vec<Self> {
+        let mut counts: Vec<Self> = Vec::new();
+        for (edge, latency_ms) in pings {
+            match counts.iter_mut().find(|count| count.edge == edge) {
+                Some(count) => {
+                    count.avg_ms += latency_ms;
+                    count.pings += 1;
+                }
+                None => counts.push(Self {
+                    edge,
+                    pings: 1,
+                    avg_ms: latency_ms,
+                }),
+            }
+        }
+        for count in &mut counts {
+            #[allow(clippy::cast_precision_loss)] // ping counts are far below 2^52
+            let pings = count.pings as f64;
+            count.avg_ms /= pings;
+        }
+        counts.sort_by(|a, b| b.pings.cmp(&a.pings).then_with(|| a.edge.cmp(&b.edge)));
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identify(headers: &[(&str, &'static str)]) -> Option<EdgePop> {
+        let headers = headers.to_vec();
+        EdgePop::from_headers(|name| {
+            headers
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| *value)
+        })
+    }
+
+    #[test]
+    fn test_edge_pop_from_headers() {
+        let pop = |cdn, pop: &str| Some(EdgePop { cdn, pop: pop.to_string() });
+        assert_eq!(identify(&[("cf-ray", "8a1b2c3d4e5f6789-fra")]), pop(Cdn::Cloudflare, "FRA"));
+        assert_eq!(identify(&[("x-amz-cf-pop", "FRA56-P1")]), pop(Cdn::CloudFront, "FRA56-P1"));
+        assert_eq!(
+            identify(&[("x-served-by", "cache-fra-eddf8230046-FRA, cache-lhr7345-LHR")]),
+            pop(Cdn::Fastly, "LHR")
+        );
+        assert_eq!(identify(&[("x-served-by", "web-03")]), None);
+        assert_eq!(identify(&[("cf-ray", "8a1b2c3d4e5f6789")]), None);
+        assert_eq!(identify(&[("server", "nginx")]), None);
+    }
+
+    #[test]
+    fn test_edge_pop_tally() {
+        let fra = EdgePop { cdn: Cdn::Cloudflare, pop: "FRA".to_string() };
+        let ams = EdgePop { cdn: Cdn::Cloudflare, pop: "AMS".to_string() };
+        let counts = EdgePopCount::tally([
+            (ams.clone(), 30.0),
+            (fra.clone(), 10.0),
+            (fra.clone(), 14.0),
+        ]);
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[0].edge, fra);
+        assert_eq!(counts[0].pings, 2);
+        assert!((counts[0].avg_ms - 12.0).abs() < 1e-9);
+        assert_eq!(counts[1].edge.to_string(), "Cloudflare AMS");
+    }
+}
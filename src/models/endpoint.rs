@@ -11,6 +11,18 @@ pub enum ProbeType {
     TCP,
     HTTP,
     ICMP,
+    /// WebSocket handshake, then optionally a ping/pong round trip
+    WebSocket,
+    /// TCP connect, then the mail server's `220` greeting
+    SMTP,
+    /// TCP connect, then the mail server's `* OK` greeting
+    IMAP,
+    /// TCP connect, then a `PING` answered with `PONG`
+    Redis,
+    /// TCP connect, then an `SSLRequest` answered by the server
+    Postgres,
+    /// SNTP query measuring the local clock's offset and the round-trip delay
+    NTP,
 }
 
 impl Default for ProbeType {
@@ -25,9 +37,21 @@ impl ProbeType {
             ProbeType::TCP => 80,
             ProbeType::HTTP => 80,
             ProbeType::ICMP => 0, // ICMP doesn't use ports
+            ProbeType::WebSocket => 443,
+            ProbeType::SMTP => 25,
+            ProbeType::IMAP => 143,
+            ProbeType::Redis => 6379,
+            ProbeType::Postgres => 5432,
+            ProbeType::NTP => 123,
         }
     }
 
+    /// Whether the probe waits for an application protocol greeting after connecting
+    #[must_use]
+    pub const fn is_banner(&self) -> bool {
+        matches!(self, Self::SMTP | Self::IMAP | Self::Redis | Self::Postgres)
+    }
+
     /// # OPS: ICMP requires root privileges on most systems
     pub fn requires_privileges(&self) -> bool {
         matches!(self, ProbeType::ICMP)
@@ -42,6 +66,24 @@ pub struct Endpoint {
     pub port: u16,
     pub probe_type: ProbeType,
     pub metadata: HashMap<String, String>,
+    /// Tenant or environment the endpoint is monitored for; its metrics,
+    /// thresholds and alerts are kept apart from other namespaces
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// Whether the endpoint is probed; a disabled endpoint keeps its
+    /// configuration but is not probed until enabled again
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+}
+
+const fn enabled_by_default() -> bool {
+    true
+}
+
+/// ID of endpoint `id` monitored in `namespace`, unique across namespaces
+#[must_use]
+pub fn namespaced_id(namespace: &str, id: &str) -> String {
+    format!("{namespace}/{id}")
 }
 
 impl Endpoint {
@@ -52,6 +94,8 @@ impl Endpoint {
             port,
             probe_type,
             metadata: crate::collection_utils::CollectionUtils::new_hashmap(),
+            namespace: None,
+            enabled: true,
         }
     }
 
@@ -68,9 +112,19 @@ impl Endpoint {
             port,
             probe_type,
             metadata,
+            namespace: None,
+            enabled: true,
         }
     }
 
+    /// Move the endpoint into `namespace`, prefixing its ID with the namespace
+    #[must_use]
+    pub fn in_namespace(mut self, namespace: &str) -> Self {
+        self.id = namespaced_id(namespace, &self.id);
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+
     pub fn address(&self) -> String {
         if self.probe_type == ProbeType::ICMP {
             self.host.clone()
@@ -119,6 +173,11 @@ mod tests {
         assert_eq!(ProbeType::TCP.default_port(), 80);
         assert_eq!(ProbeType::HTTP.default_port(), 80);
         assert_eq!(ProbeType::ICMP.default_port(), 0);
+        assert_eq!(ProbeType::WebSocket.default_port(), 443);
+        assert_eq!(ProbeType::Postgres.default_port(), 5432);
+        assert_eq!(ProbeType::NTP.default_port(), 123);
+        assert!(ProbeType::Redis.is_banner());
+        assert!(!ProbeType::TCP.is_banner());
         
         assert!(!ProbeType::TCP.requires_privileges());
         assert!(!ProbeType::HTTP.requires_privileges());
@@ -0,0 +1,265 @@
+//! Local test environment snapshot
+//!
+//! Captured alongside benchmark results so that differences between runs can
+//! be traced back to the network the client was on (Wi-Fi versus Ethernet, a
+//! different ISP, a slow home gateway, a NAT that breaks peer-to-peer traffic).
+
+use std::net::{IpAddr, SocketAddr};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::concurrency::ConcurrencyAdjustment;
+use super::control::ControlSeries;
+use super::failure::RegionFailure;
+use super::labels::RunLabels;
+use super::stats::PingStats;
+use super::utils::generate_uuid;
+use crate::time_utils::TimeUtils;
+
+/// Kind of network interface carrying the default route
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum InterfaceType {
+    /// Wired Ethernet
+    Ethernet,
+    /// Wireless LAN
+    Wifi,
+    /// Mobile broadband
+    Cellular,
+    /// Could not be determined on this platform
+    #[default]
+    Unknown,
+}
+
+impl std::fmt::Display for InterfaceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Ethernet => "ethernet",
+            Self::Wifi => "wifi",
+            Self::Cellular => "cellular",
+            Self::Unknown => "unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How the local network maps UDP traffic to public addresses, from STUN
+///
+/// Games and calls connect peers directly over UDP where they can. Behind a
+/// cone NAT a peer can reach the mapping a STUN server saw; behind a
+/// symmetric NAT every destination gets a new mapping, so their traffic has
+/// to go through a relay instead, adding latency.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NatType {
+    /// The local address is public, no NAT in the way
+    Open,
+    /// The same public mapping for every destination (endpoint-independent mapping)
+    Cone,
+    /// A different public mapping per destination (endpoint-dependent mapping)
+    Symmetric,
+    /// No STUN server answered over UDP
+    UdpBlocked,
+    /// Only one server answered, which cannot tell cone from symmetric NAT,
+    /// or no STUN server could be asked
+    Unknown,
+}
+
+impl NatType {
+    /// Whether peers will likely need a relay to reach this client
+    #[must_use]
+    pub const fn needs_relay(self) -> bool {
+        matches!(self, Self::Symmetric | Self::UdpBlocked)
+    }
+}
+
+impl std::fmt::Display for NatType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Open => "no NAT",
+            Self::Cone => "cone NAT",
+            Self::Symmetric => "symmetric NAT",
+            Self::UdpBlocked => "UDP blocked",
+            Self::Unknown => "NAT type unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Snapshot of the local network environment at test time
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct TestEnvironment {
+    /// When the snapshot was taken
+    #[serde(default = "Utc::now")]
+    pub captured_at: DateTime<Utc>,
+    /// Operating system and architecture (e.g. `linux/x86_64`)
+    pub os: String,
+    /// Interface carrying the default route
+    #[serde(default)]
+    pub interface_name: Option<String>,
+    /// Kind of that interface
+    #[serde(default)]
+    pub interface_type: InterfaceType,
+    /// Default gateway address
+    #[serde(default)]
+    pub default_gateway: Option<IpAddr>,
+    /// Round-trip time to the default gateway
+    #[serde(default)]
+    pub gateway_rtt_ms: Option<f64>,
+    /// First router beyond the local gateway, usually the ISP edge
+    #[serde(default)]
+    pub isp_first_hop: Option<String>,
+    /// Round-trip time to the ISP first hop
+    #[serde(default)]
+    pub isp_first_hop_rtt_ms: Option<f64>,
+    /// Public IP address (only with the optional lookup enabled)
+    #[serde(default)]
+    pub public_ip: Option<String>,
+    /// Autonomous system of the public IP (e.g. `AS3320 Deutsche Telekom AG`)
+    #[serde(default)]
+    pub asn: Option<String>,
+    /// Country of the public IP (ISO 3166-1 alpha-2)
+    #[serde(default)]
+    pub country: Option<String>,
+    /// Public address and port STUN servers saw the local UDP socket as
+    #[serde(default)]
+    pub public_mapping: Option<SocketAddr>,
+    /// NAT behaviour found with STUN, absent when no STUN servers are configured
+    #[serde(default)]
+    pub nat_type: Option<NatType>,
+}
+
+impl TestEnvironment {
+    /// Latency spent before traffic leaves the ISP edge (first hop, else gateway)
+    #[must_use]
+    pub fn local_rtt_ms(&self) -> Option<f64> {
+        self.isp_first_hop_rtt_ms.or(self.gateway_rtt_ms)
+    }
+
+    /// Part of a region's RTT spent beyond the ISP first hop
+    #[must_use]
+    pub fn beyond_isp_ms(&self, region_rtt_ms: f64) -> Option<f64> {
+        self.local_rtt_ms()
+            .map(|local| (region_rtt_ms - local).max(0.0))
+    }
+
+    /// Share of a region's RTT spent before the ISP edge, in percent
+    #[must_use]
+    pub fn local_share_percent(&self, region_rtt_ms: f64) -> Option<f64> {
+        if region_rtt_ms <= 0.0 {
+            return None;
+        }
+        self.local_rtt_ms()
+            .map(|local| (local / region_rtt_ms * 100.0).min(100.0))
+    }
+
+    /// One-line summary for reports
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut parts = vec![self.os.clone(), self.interface_type.to_string()];
+        if let Some(rtt) = self.gateway_rtt_ms {
+            parts.push(format!("gateway {rtt:.1} ms"));
+        }
+        if let Some(rtt) = self.isp_first_hop_rtt_ms {
+            parts.push(format!("ISP first hop {rtt:.1} ms"));
+        }
+        if let Some(asn) = &self.asn {
+            parts.push(asn.clone());
+        }
+        if let Some(nat_type) = self.nat_type {
+            parts.push(nat_type.to_string());
+        }
+        parts.join(", ")
+    }
+}
+
+/// A benchmark run together with the environment it ran in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkRun {
+    /// Unique run identifier
+    #[serde(default = "generate_uuid")]
+    pub id: String,
+    /// When the run started
+    pub started_at: DateTime<Utc>,
+    /// Local environment at the start of the run
+    pub environment: TestEnvironment,
+    /// Per-region results
+    pub results: Vec<(String, PingStats)>,
+    /// The run was cancelled or hit its deadline, so some regions are partial or missing
+    #[serde(default)]
+    pub incomplete: bool,
+    /// Regions that failed, were skipped or could not be tested
+    #[serde(default)]
+    pub failures: Vec<RegionFailure>,
+    /// Concurrency reductions made because the local uplink looked saturated
+    #[serde(default)]
+    pub concurrency_adjustments: Vec<ConcurrencyAdjustment>,
+    /// Control endpoint pings taken during the run, if a control endpoint is configured
+    #[serde(default)]
+    pub control: Option<ControlSeries>,
+    /// Labels describing where and how the run was taken
+    #[serde(default)]
+    pub labels: RunLabels,
+}
+
+impl BenchmarkRun {
+    /// Create a run record starting now
+    #[must_use]
+    pub fn new(environment: TestEnvironment, results: Vec<(String, PingStats)>) -> Self {
+        Self {
+            id: generate_uuid(),
+            started_at: TimeUtils::now(),
+            environment,
+            results,
+            incomplete: false,
+            failures: Vec::new(),
+            concurrency_adjustments: Vec::new(),
+            control: None,
+            labels: RunLabels::new(),
+        }
+    }
+
+    /// Bytes sent and received across all regions of the run
+    #[must_use]
+    pub fn data_usage_bytes(&self) -> u64 {
+        self.results
+            .iter()
+            .map(|(_, stats)| stats.data_usage_bytes())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_environment_summary() {
+        let environment = TestEnvironment {
+            os: "linux/x86_64".to_string(),
+            interface_type: InterfaceType::Wifi,
+            gateway_rtt_ms: Some(2.345),
+            asn: Some("AS3320 Deutsche Telekom AG".to_string()),
+            ..TestEnvironment::default()
+        };
+
+        assert_eq!(
+            environment.summary(),
+            "linux/x86_64, wifi, gateway 2.3 ms, AS3320 Deutsche Telekom AG"
+        );
+        let behind_nat = TestEnvironment {
+            nat_type: Some(NatType::Symmetric),
+            ..environment.clone()
+        };
+        assert!(behind_nat.summary().ends_with("Deutsche Telekom AG, symmetric NAT"));
+        assert!(NatType::Symmetric.needs_relay() && !NatType::Cone.needs_relay());
+
+        assert!((environment.beyond_isp_ms(20.0).unwrap() - 17.655).abs() < 1e-9);
+        assert!((environment.beyond_isp_ms(1.0).unwrap()).abs() < f64::EPSILON);
+
+        let json = serde_json::to_string(&environment).unwrap();
+        let decoded: TestEnvironment = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, environment);
+    }
+}
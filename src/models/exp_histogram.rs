@@ -0,0 +1,214 @@
+//! Base-2 exponential histograms of round-trip times
+//!
+//! Averages and a p95 gauge say little about the tail: two p95 values from
+//! different agents cannot be combined, and the p99.9 is lost altogether. An
+//! [`ExponentialHistogram`] keeps every round trip in buckets whose bounds
+//! grow by a constant factor, the layout of the OpenTelemetry exponential
+//! histogram. Any quantile can be read back within the bucket's relative
+//! error, and backends merge the histograms of many agents exactly.
+//!
+//! The histogram starts at the finest scale and halves its resolution each
+//! time the recorded values would need more than [`MAX_BUCKETS`] buckets, so
+//! its size stays bounded whatever the range of round trips.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Buckets kept before the resolution is halved, as OpenTelemetry SDKs default to
+pub const MAX_BUCKETS: usize = 160;
+
+/// Scale of an empty histogram; bucket bounds grow by 2^(2^-20)
+pub const MAX_SCALE: i32 = 20;
+
+/// Coarsest scale; bucket bounds grow by 2^1024
+const MIN_SCALE: i32 = -10;
+
+/// Cumulative histogram with base-2 exponential buckets
+///
+/// Bucket `i` holds the values in `(base^i, base^(i+1)]`, with
+/// `base = 2^(2^-scale)`. Zero goes to `zero_count`; negative values are
+/// not recorded.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExponentialHistogram {
+    /// Resolution of the buckets
+    pub scale: i32,
+    /// Index of the first bucket in `counts`
+    pub offset: i32,
+    /// Values per bucket, from the bucket at `offset` on
+    pub counts: Vec<u64>,
+    /// Values that were zero
+    pub zero_count: u64,
+    /// Values recorded
+    pub count: u64,
+    /// Sum of the values recorded
+    pub sum: f64,
+    /// Smallest value recorded
+    pub min: Option<f64>,
+    /// Largest value recorded
+    pub max: Option<f64>,
+    /// Time of the first value; the start of the cumulative series
+    pub start: Option<DateTime<Utc>>,
+}
+
+impl Default for ExponentialHistogram {
+    fn default() -> Self {
+        Self {
+            scale: MAX_SCALE,
+            offset: 0,
+            counts: Vec::new(),
+            zero_count: 0,
+            count: 0,
+            sum: 0.0,
+            min: None,
+            max: None,
+            start: None,
+        }
+    }
+}
+
+impl ExponentialHistogram {
+    /// Record `value` measured at `at`; negative and non-finite values are ignored
+    pub fn record(&mut self, value: f64, at: DateTime<Utc>) {
+        if !value.is_finite() || value < 0.0 {
+            return;
+        }
+        self.start.get_or_insert(at);
+        self.count += 1;
+        self.sum += value;
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+        if value == 0.0 {
+            self.zero_count += 1;
+            return;
+        }
+
+        let mut index = bucket_index(value, self.scale);
+        if !self.counts.is_empty() {
+            let (mut low, mut high) = (self.offset.min(index), self.last_index().max(index));
+            let mut change = 0;
+            while high - low >= i32_len(MAX_BUCKETS) && self.scale - change > MIN_SCALE {
+                low >>= 1;
+                high >>= 1;
+                change += 1;
+            }
+            self.downscale(change);
+            index >>= change;
+        }
+
+        if self.counts.is_empty() {
+            self.offset = index;
+            self.counts.push(0);
+        } else if index < self.offset {
+            let grow = usize::try_from(self.offset - index).unwrap_or(0);
+            self.counts.splice(0..0, std::iter::repeat(0).take(grow));
+            self.offset = index;
+        } else if index > self.last_index() {
+            let grow = usize::try_from(index - self.last_index()).unwrap_or(0);
+            self.counts.extend(std::iter::repeat(0).take(grow));
+        }
+        let slot = usize::try_from(index - self.offset).unwrap_or(0);
+        self.counts[slot] += 1;
+    }
+
+    /// Lower and upper bound of bucket `index` at the current scale
+    #[must_use]
+    pub fn bucket_bounds(&self, index: i32) -> (f64, f64) {
+        let width = (-f64::from(self.scale)).exp2();
+        ((f64::from(index) * width).exp2(), (f64::from(index + 1) * width).exp2())
+    }
+
+    /// Value below which a fraction `q` of the values lie, from the bucket
+    /// holding it; `None` when nothing was recorded
+    ///
+    /// The estimate is the bucket's geometric midpoint, clamped to the
+    /// recorded minimum and maximum.
+    #[must_use]
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let (min, max) = (self.min?, self.max?);
+        #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        if rank <= self.zero_count {
+            return Some(0.0);
+        }
+        let mut seen = self.zero_count;
+        for (index, count) in (self.offset..).zip(&self.counts) {
+            seen += count;
+            if seen >= rank {
+                let (lower, upper) = self.bucket_bounds(index);
+                return Some((lower * upper).sqrt().clamp(min, max));
+            }
+        }
+        Some(max)
+    }
+
+    fn last_index(&self) -> i32 {
+        self.offset + i32_len(self.counts.len()) - 1
+    }
+
+    /// Merge the buckets `change` scales coarser
+    fn downscale(&mut self, change: i32) {
+        if change <= 0 {
+            return;
+        }
+        self.scale -= change;
+        let offset = self.offset >> change;
+        let mut counts: Vec<u64> = Vec::with_capacity(self.counts.len());
+        for (index, count) in (self.offset..).zip(&self.counts) {
+            let slot = usize::try_from((index >> change) - offset).unwrap_or(0);
+            if slot >= counts.len() {
+                counts.resize(slot + 1, 0);
+            }
+            counts[slot] += count;
+        }
+        self.offset = offset;
+        self.counts = counts;
+    }
+}
+
+/// Index of the bucket holding the positive `value` at `scale`
+fn bucket_index(value: f64, scale: i32) -> i32 {
+    #[allow(clippy::cast_possible_truncation)] // log2 of a finite f64 is within ±1075, times at most 2^20
+    let index = (value.log2() * f64::from(scale).exp2()).ceil() as i32 - 1;
+    index
+}
+
+fn i32_len(len: usize) -> i32 {
+    i32::try_from(len).unwrap_or(i32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_histogram_keeps_the_tail() {
+        let at = Utc::now();
+        let mut histogram = ExponentialHistogram::default();
+        histogram.record(0.0, at);
+        histogram.record(-1.0, at);
+        for i in 1..=1000u32 {
+            // A tail of 10 slow round trips behind 990 fast ones
+            let rtt = if i > 990 { 800.0 + f64::from(i) } else { 20.0 + f64::from(i % 10) };
+            histogram.record(rtt, at);
+        }
+
+        assert_eq!(histogram.count, 1001);
+        assert_eq!(histogram.zero_count, 1);
+        assert_eq!(histogram.counts.iter().sum::<u64>(), 1000);
+        assert!(histogram.counts.len() <= MAX_BUCKETS);
+        assert!(histogram.scale < MAX_SCALE);
+        assert_eq!(histogram.start, Some(at));
+        assert_eq!((histogram.min, histogram.max), (Some(0.0), Some(1800.0)));
+
+        // Every bucket's bounds hold the values counted in it
+        let (lower, upper) = histogram.bucket_bounds(bucket_index(25.0, histogram.scale));
+        assert!(lower < 25.0 && 25.0 <= upper);
+
+        let p50 = histogram.quantile(0.5).unwrap();
+        assert!((p50 - 25.0).abs() / 25.0 < 0.05, "p50 {p50}");
+        let p999 = histogram.quantile(0.999).unwrap();
+        assert!((p999 - 1799.0).abs() / 1799.0 < 0.05, "p999 {p999}");
+        assert_eq!(histogram.quantile(0.0), Some(0.0));
+        assert_eq!(ExponentialHistogram::default().quantile(0.5), None);
+    }
+}
@@ -0,0 +1,248 @@
+//! Scoring round trips measured elsewhere
+//!
+//! Some teams already collect round trips with their own agents, synthetic
+//! checks or browser beacons, and only want them scored. [`ExternalSamples`]
+//! turns such samples and a count of failed probes into the [`PingStats`] a
+//! benchmark run produces, so scores, suitability, rankings and reports
+//! treat them like any other measurement, or into an [`AggregatorState`] for
+//! the score monitoring computes over its windows.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::failure::FailureKind;
+use super::jitter::JitterAlgorithm;
+use super::loss::LossPattern;
+use super::metrics::AggregatorState;
+use super::probe::ProbeRecord;
+use super::stats::PingStats;
+use crate::error::{CloudPingError, Result};
+
+/// Smoothing factor of the EWMA jitter when samples are replayed into an
+/// [`AggregatorState`], the monitoring default
+const EWMA_ALPHA: f64 = 1.0 / 16.0;
+
+/// Round trips of one endpoint measured outside cloud-ping
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExternalSamples {
+    /// Name the results are reported under, usually the region's
+    pub name: String,
+    /// Round trips in milliseconds, in the order taken; `None` (`null`) is a failed probe
+    pub samples: Vec<Option<f64>>,
+    /// Failed probes besides those in `samples`, whose order is unknown
+    #[serde(default)]
+    pub failures: usize,
+    /// Most common cause of the failures, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_kind: Option<FailureKind>,
+    /// Provider of the endpoint, empty when unknown
+    #[serde(default)]
+    pub provider: String,
+    /// Country of the endpoint, empty when unknown
+    #[serde(default)]
+    pub country: String,
+    /// When the samples were taken; the time they are scored when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub measured_at: Option<DateTime<Utc>>,
+    /// How jitter is derived from the round trips; not read from files, as
+    /// it follows the configured `jitter_algorithm`
+    #[serde(skip)]
+    pub jitter_algorithm: JitterAlgorithm,
+}
+
+impl ExternalSamples {
+    /// Successful round trips `rtts_ms`, in milliseconds, reported as `name`
+    #[must_use]
+    pub fn new(name: impl Into<String>, rtts_ms: Vec<f64>) -> Self {
+        Self::from_outcomes(name, rtts_ms.into_iter().map(Some).collect())
+    }
+
+    /// Probe outcomes in the order taken: the round trip in milliseconds, or
+    /// `None` for a failed probe
+    #[must_use]
+    pub fn from_outcomes(name: impl Into<String>, outcomes: Vec<Option<f64>>) -> Self {
+        Self {
+            name: name.into(),
+            samples: outcomes,
+            failures: 0,
+            failure_kind: None,
+            provider: String::new(),
+            country: String::new(),
+            measured_at: None,
+            jitter_algorithm: JitterAlgorithm::default(),
+        }
+    }
+
+    /// Set the number of failed probes whose order is unknown; they are
+    /// spread evenly among the samples, so they count as isolated losses
+    #[must_use]
+    pub const fn failures(mut self, count: usize) -> Self {
+        self.failures = count;
+        self
+    }
+
+    /// Set the most common cause of the failures
+    #[must_use]
+    pub const fn failure_kind(mut self, kind: FailureKind) -> Self {
+        self.failure_kind = Some(kind);
+        self
+    }
+
+    /// Set the provider of the endpoint
+    #[must_use]
+    pub fn provider(mut self, provider: impl Into<String>) -> Self {
+        self.provider = provider.into();
+        self
+    }
+
+    /// Set the country of the endpoint
+    #[must_use]
+    pub fn country(mut self, country: impl Into<String>) -> Self {
+        self.country = country.into();
+        self
+    }
+
+    /// Set when the samples were taken
+    #[must_use]
+    pub const fn measured_at(mut self, at: DateTime<Utc>) -> Self {
+        self.measured_at = Some(at);
+        self
+    }
+
+    /// Set the jitter algorithm
+    #[must_use]
+    pub const fn jitter_algorithm(mut self, algorithm: JitterAlgorithm) -> Self {
+        self.jitter_algorithm = algorithm;
+        self
+    }
+
+    /// Every probe in order, the failures of unknown order spread among the samples
+    ///
+    /// # Errors
+    /// Returns a validation error when there are no probes, or a round trip
+    /// is not a positive number of milliseconds
+    pub fn outcomes(&self) -> Result<Vec<Option<f64>>> {
+        if let Some(rtt) = self.samples.iter().flatten().find(|rtt| !rtt.is_finite() || **rtt <= 0.0) {
+            return Err(CloudPingError::validation(
+                format!("{}.samples", self.name),
+                format!("`{rtt}` is not a positive round trip in milliseconds"),
+            ));
+        }
+        let total = self.samples.len() + self.failures;
+        if total == 0 {
+            return Err(CloudPingError::validation(format!("{}.samples", self.name), "no samples to score"));
+        }
+
+        // Probe `i` is a failure when the running share of failures steps up at it
+        let mut samples = self.samples.iter().copied();
+        Ok((0..total)
+            .map(|i| {
+                let spread_failure = (i + 1) * self.failures / total > i * self.failures / total;
+                if spread_failure {
+                    None
+                } else {
+                    samples.next().flatten()
+                }
+            })
+            .collect())
+    }
+
+    /// Statistics of the samples, as a benchmark run of the endpoint would report them
+    ///
+    /// # Errors
+    /// Returns a validation error for the samples [`Self::outcomes`] rejects
+    pub fn ping_stats(&self) -> Result<PingStats> {
+        let outcomes = self.outcomes()?;
+        let mut stats = PingStats::new(outcomes.len());
+        stats.test_time = self.measured_at.unwrap_or(stats.test_time);
+        stats.provider.clone_from(&self.provider);
+        stats.country.clone_from(&self.country);
+        // Failed pings hold a zero latency, as in a benchmark run
+        stats.latencies = outcomes.iter().map(|rtt| rtt.unwrap_or(0.0)).collect();
+        stats.loss_pattern = LossPattern::from_outcomes(outcomes.iter().map(Option::is_some));
+        let successful_latencies: Vec<f64> = outcomes.iter().copied().flatten().collect();
+        if successful_latencies.len() < outcomes.len() {
+            stats.failure_kind = Some(self.failure_kind.unwrap_or(FailureKind::Other));
+        }
+        stats.summarize(&successful_latencies, self.jitter_algorithm);
+        Ok(stats)
+    }
+
+    /// The name and statistics of the samples, as a benchmark reports a region
+    ///
+    /// # Errors
+    /// Returns a validation error for the samples [`Self::outcomes`] rejects
+    pub fn result(&self) -> Result<(String, PingStats)> {
+        Ok((self.name.clone(), self.ping_stats()?))
+    }
+
+    /// Aggregator state of endpoint `endpoint_id` holding every sample in
+    /// both windows, ready for [`crate::models::scoring::compute_score`]
+    ///
+    /// # Errors
+    /// Returns a validation error for the samples [`Self::outcomes`] rejects
+    pub fn aggregator_state(&self, endpoint_id: impl Into<String>) -> Result<AggregatorState> {
+        let endpoint_id = endpoint_id.into();
+        let outcomes = self.outcomes()?;
+        let mut state = AggregatorState::builder(endpoint_id.clone())
+            .short_window(outcomes.len())
+            .long_window(outcomes.len())
+            .jitter_algorithm(self.jitter_algorithm)
+            .build();
+        let timestamp = self.measured_at.unwrap_or_else(crate::time_utils::TimeUtils::now);
+        for rtt in outcomes {
+            let mut record = rtt.map_or_else(
+                || ProbeRecord::failure(endpoint_id.clone(), self.failure_kind.map(|kind| kind.to_string())),
+                |rtt| ProbeRecord::success(endpoint_id.clone(), rtt),
+            );
+            record.timestamp = timestamp;
+            state.push_record(record, EWMA_ALPHA);
+        }
+        state.recompute_short_aggregates();
+        state.recompute_long_aggregates();
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::scoring::compute_score;
+    use crate::models::{AlgorithmWeights, ScoringAdapter};
+
+    #[test]
+    fn test_external_samples_score_like_measurements() {
+        let samples = ExternalSamples::new("Frankfurt", vec![20.0, 24.0, 22.0, 26.0, 23.0, 21.0])
+            .failures(2)
+            .provider("aws");
+        let outcomes = samples.outcomes().unwrap();
+        assert_eq!(outcomes.len(), 8);
+        assert_eq!(outcomes.iter().filter(|rtt| rtt.is_none()).count(), 2);
+        assert!(outcomes.windows(2).all(|pair| pair[0].is_some() || pair[1].is_some()));
+
+        let stats = samples.ping_stats().unwrap();
+        assert_eq!((stats.total_pings, stats.successful_pings), (8, 6));
+        assert!((stats.packet_loss - 25.0).abs() < 1e-9);
+        assert!((stats.avg - 22.666).abs() < 0.01);
+        assert_eq!((stats.min, stats.max), (20.0, 26.0));
+        assert!(stats.jitter > 0.0);
+        assert_eq!(stats.failure_kind, Some(FailureKind::Other));
+        assert_eq!(stats.provider, "aws");
+
+        let weights = AlgorithmWeights::default();
+        let clean = ExternalSamples::new("Paris", vec![20.0, 24.0, 22.0, 26.0, 23.0, 21.0]).result().unwrap();
+        let ranked = ScoringAdapter::get_sorted_results(&[samples.result().unwrap(), clean], &weights);
+        assert_eq!(ranked[0].1, "Paris");
+        assert!(ranked[0].3.suitability.gaming > ranked[1].3.suitability.gaming);
+
+        let state = samples.aggregator_state("aws:eu-central-1").unwrap();
+        assert_eq!(state.circular_buffer_long.len(), 8);
+        assert!((state.cached_avail_long - 75.0).abs() < 1e-9);
+        assert!(compute_score(&state, &weights).score > 0.0);
+
+        assert!(ExternalSamples::new("Empty", Vec::new()).ping_stats().is_err());
+        assert!(ExternalSamples::new("Negative", vec![-1.0]).ping_stats().is_err());
+        let failed = ExternalSamples::from_outcomes("Down", vec![None, None]).ping_stats().unwrap();
+        assert_eq!((failed.packet_loss, failed.avg), (100.0, 0.0));
+    }
+}
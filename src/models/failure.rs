@@ -0,0 +1,192 @@
+//! Per-region failure reporting
+//!
+//! A benchmark run can lose regions in several ways: every ping fails, the
+//! run is cancelled before a region starts, or a region's task crashes.
+//! [`RunReport`] keeps those failures next to the successful results, with a
+//! [`FailureKind`] that says what went wrong.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::concurrency::ConcurrencyAdjustment;
+use super::control::ControlSeries;
+use super::stats::PingStats;
+
+/// Why a request or region failed
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FailureKind {
+    /// No response within the attempt timeout
+    Timeout,
+    /// The ping's retries ran out of time before one succeeded
+    PingBudget,
+    /// The region's time ran out while pinging it
+    RegionBudget,
+    /// The host name could not be resolved
+    Dns,
+    /// The connection was refused, reset or closed
+    Connection,
+    /// Certificate or TLS handshake failure
+    Tls,
+    /// The server answered with an unsuccessful status
+    HttpStatus {
+        /// Status code of the response
+        status: u16,
+    },
+    /// The service answered with something other than its protocol's greeting
+    Protocol,
+    /// The run was cancelled before the region completed any ping
+    Cancelled,
+    /// The region's test task failed inside cloud-ping
+    Internal,
+    /// Any other error
+    Other,
+}
+
+impl FailureKind {
+    /// Classify a request failure from its error message and status code
+    #[must_use]
+    pub fn classify(message: &str, status_code: Option<u16>) -> Self {
+        if let Some(status) = status_code.filter(|&status| status != 408) {
+            return Self::HttpStatus { status };
+        }
+
+        let message = message.to_lowercase();
+        let mentions = |needles: &[&str]| needles.iter().any(|needle| message.contains(needle));
+        if status_code == Some(408) || mentions(&["timeout", "timed out"]) {
+            Self::Timeout
+        } else if mentions(&["dns", "failed to lookup", "resolve"]) {
+            Self::Dns
+        } else if mentions(&["certificate", "tls", "handshake"]) {
+            Self::Tls
+        } else if mentions(&["connect", "refused", "reset", "closed", "broken pipe"]) {
+            Self::Connection
+        } else if mentions(&["cancel"]) {
+            Self::Cancelled
+        } else {
+            Self::Other
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "timeout"),
+            Self::PingBudget => write!(f, "ping budget"),
+            Self::RegionBudget => write!(f, "region budget"),
+            Self::Dns => write!(f, "DNS resolution"),
+            Self::Connection => write!(f, "connection"),
+            Self::Tls => write!(f, "TLS"),
+            Self::HttpStatus { status } => write!(f, "HTTP {status}"),
+            Self::Protocol => write!(f, "protocol"),
+            Self::Cancelled => write!(f, "cancelled"),
+            Self::Internal => write!(f, "internal error"),
+            Self::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// A region that produced no usable result
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RegionFailure {
+    /// Region name
+    pub region: String,
+    /// Region URL
+    pub url: String,
+    /// What went wrong
+    pub kind: FailureKind,
+    /// Error message of the last failure
+    pub message: String,
+}
+
+/// Successful results and per-region failures of a benchmark run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunReport {
+    /// Per-region results, including regions where every ping failed
+    pub results: Vec<(String, PingStats)>,
+    /// Regions that failed, were skipped or could not be tested
+    pub failures: Vec<RegionFailure>,
+    /// The run was cancelled or hit its deadline
+    pub incomplete: bool,
+    /// Concurrency reductions made because the local uplink looked saturated
+    #[serde(default)]
+    pub concurrency_adjustments: Vec<ConcurrencyAdjustment>,
+    /// Control endpoint pings taken during the run, if a control endpoint is configured
+    #[serde(default)]
+    pub control: Option<ControlSeries>,
+}
+
+impl RunReport {
+    /// Record a region whose every ping failed as a failure, keeping its stats
+    pub fn push_result(&mut self, name: String, url: &str, stats: PingStats) {
+        if !stats.is_successful() && stats.total_pings > 0 {
+            self.failures.push(RegionFailure {
+                region: name.clone(),
+                url: url.to_string(),
+                kind: stats.failure_kind.unwrap_or(FailureKind::Other),
+                message: stats.error_message.clone(),
+            });
+        }
+        self.results.push((name, stats));
+    }
+
+    /// Whether any region failed
+    #[must_use]
+    pub fn has_failures(&self) -> bool {
+        !self.failures.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            FailureKind::classify("Timeout after 5000ms", Some(408)),
+            FailureKind::Timeout
+        );
+        assert_eq!(
+            FailureKind::classify(
+                "error sending request: dns error: failed to lookup address",
+                None
+            ),
+            FailureKind::Dns
+        );
+        assert_eq!(
+            FailureKind::classify("tcp connect error: Connection refused", None),
+            FailureKind::Connection
+        );
+        assert_eq!(
+            FailureKind::classify("HTTP 503", Some(503)),
+            FailureKind::HttpStatus { status: 503 }
+        );
+        assert_eq!(FailureKind::classify("weird", None), FailureKind::Other);
+        assert_eq!(
+            FailureKind::HttpStatus { status: 503 }.to_string(),
+            "HTTP 503"
+        );
+    }
+
+    #[test]
+    fn test_unreachable_regions_are_failures() {
+        let mut report = RunReport::default();
+
+        let mut reachable = PingStats::new(5);
+        reachable.successful_pings = 5;
+        report.push_result("eu".to_string(), "https://eu.example.com", reachable);
+
+        let mut unreachable = PingStats::new(5);
+        unreachable.failure_kind = Some(FailureKind::Dns);
+        unreachable.error_message = "All retry attempts failed".to_string();
+        report.push_result("us".to_string(), "https://us.example.com", unreachable);
+
+        assert_eq!(report.results.len(), 2);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].region, "us");
+        assert_eq!(report.failures[0].kind, FailureKind::Dns);
+    }
+}
@@ -0,0 +1,384 @@
+//! Multi-region deployment footprints
+//!
+//! Rather than a single winner, [`FootprintConstraints::select`] picks the K
+//! ranked regions with the highest total score that still give redundancy:
+//! a minimum number of distinct providers and continents, and a minimum grade
+//! for every region. Continents are derived from the country of a region,
+//! which the data file, the region's name or IP geolocation gives; regions
+//! without a country count towards no continent.
+
+use serde::{Deserialize, Serialize};
+
+use super::country::country_code;
+use super::ranking::{RankedRegion, GRADES};
+use super::scoring::utils::RankedResult;
+use crate::error::{CloudPingError, Result};
+
+/// Country codes per continent
+const CONTINENTS: [(&str, &[&str]); 6] = [
+    (
+        "Africa",
+        &[
+            "AO", "BF", "BI", "BJ", "BW", "CD", "CF", "CG", "CI", "CM", "CV", "DJ", "DZ", "EG",
+            "ER", "ET", "GA", "GH", "GM", "GN", "GQ", "GW", "KE", "KM", "LR", "LS", "LY", "MA",
+            "MG", "ML", "MR", "MU", "MW", "MZ", "NA", "NE", "NG", "RE", "RW", "SC", "SD", "SL",
+            "SN", "SO", "SS", "ST", "SZ", "TD", "TG", "TN", "TZ", "UG", "ZA", "ZM", "ZW",
+        ],
+    ),
+    (
+        "Asia",
+        &[
+            "AE", "AF", "AM", "AZ", "BD", "BH", "BN", "BT", "CN", "GE", "HK", "ID", "IL", "IN",
+            "IQ", "IR", "JO", "JP", "KG", "KH", "KP", "KR", "KW", "KZ", "LA", "LB", "LK", "MM",
+            "MN", "MO", "MV", "MY", "NP", "OM", "PH", "PK", "PS", "QA", "SA", "SG", "SY", "TH",
+            "TJ", "TL", "TM", "TR", "TW", "UZ", "VN", "YE",
+        ],
+    ),
+    (
+        "Europe",
+        &[
+            "AD", "AL", "AT", "BA", "BE", "BG", "BY", "CH", "CY", "CZ", "DE", "DK", "EE", "ES",
+            "FI", "FO", "FR", "GB", "GI", "GR", "HR", "HU", "IE", "IM", "IS", "IT", "JE", "LI",
+            "LT", "LU", "LV", "MC", "MD", "ME", "MK", "MT", "NL", "NO", "PL", "PT", "RO", "RS",
+            "RU", "SE", "SI", "SK", "SM", "UA", "UK", "VA", "XK",
+        ],
+    ),
+    (
+        "North America",
+        &[
+            "AG", "BB", "BM", "BS", "BZ", "CA", "CR", "CU", "DM", "DO", "GD", "GL", "GT", "HN",
+            "HT", "JM", "KN", "KY", "LC", "MX", "NI", "PA", "PR", "SV", "TT", "US", "VC",
+        ],
+    ),
+    (
+        "Oceania",
+        &[
+            "AU", "FJ", "FM", "GU", "KI", "MH", "NC", "NR", "NZ", "PF", "PG", "PW", "SB", "TO",
+            "TV", "VU", "WS",
+        ],
+    ),
+    (
+        "South America",
+        &["AR", "BO", "BR", "CL", "CO", "EC", "GF", "GY", "PE", "PY", "SR", "UY", "VE"],
+    ),
+];
+
+/// Continent of a country, given as an ISO 3166-1 alpha-2 code or a name
+/// [`country_code`] knows, compared case-insensitively
+#[must_use]
+pub fn continent_of(country: &str) -> Option<&'static str> {
+    let code = country_code(country)?;
+    CONTINENTS
+        .iter()
+        .find(|(_, codes)| codes.iter().any(|c| c.eq_ignore_ascii_case(&code)))
+        .map(|(continent, _)| *continent)
+}
+
+/// Size and redundancy a deployment footprint must have
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FootprintConstraints {
+    /// Regions in the footprint
+    #[serde(default = "default_count")]
+    pub count: usize,
+    /// Distinct providers the regions must span
+    #[serde(default = "default_min_spread")]
+    pub min_providers: usize,
+    /// Distinct continents the regions must span
+    #[serde(default = "default_min_spread")]
+    pub min_continents: usize,
+    /// Grade every region must reach, e.g. `B` allows A and B
+    #[serde(default = "default_min_grade")]
+    pub min_grade: Option<char>,
+}
+
+const fn default_count() -> usize {
+    3
+}
+
+const fn default_min_spread() -> usize {
+    2
+}
+
+#[allow(clippy::unnecessary_wraps)]
+const fn default_min_grade() -> Option<char> {
+    Some('B')
+}
+
+impl Default for FootprintConstraints {
+    fn default() -> Self {
+        Self {
+            count: default_count(),
+            min_providers: default_min_spread(),
+            min_continents: default_min_spread(),
+            min_grade: default_min_grade(),
+        }
+    }
+}
+
+/// Regions chosen for a deployment, with the spread they give
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Footprint {
+    /// Chosen regions, best score first; `rank` is their place in the ranking
+    pub regions: Vec<RankedRegion>,
+    /// Distinct providers of the regions
+    pub providers: Vec<String>,
+    /// Distinct continents of the regions
+    pub continents: Vec<String>,
+    /// Average score of the regions
+    pub average_score: f64,
+}
+
+/// A candidate region as the search sees it
+struct Candidate {
+    index: usize,
+    score: f64,
+    provider: Option<String>,
+    continent: Option<&'static str>,
+}
+
+impl FootprintConstraints {
+    /// These constraints for a footprint of `count` regions, asking for at
+    /// most `count` providers and continents so a single region is allowed
+    #[must_use]
+    pub fn with_count(mut self, count: usize) -> Self {
+        self.count = count;
+        self.min_providers = self.min_providers.min(count);
+        self.min_continents = self.min_continents.min(count);
+        self
+    }
+
+    /// # Errors
+    /// Returns a validation error naming the first invalid setting
+    pub fn validate(&self) -> Result<()> {
+        if self.count == 0 {
+            return Err(CloudPingError::validation("footprint.count", "must be greater than 0"));
+        }
+        if self.min_providers > self.count {
+            return Err(CloudPingError::validation(
+                "footprint.min_providers",
+                format!("cannot exceed footprint.count ({})", self.count),
+            ));
+        }
+        if self.min_continents > self.count {
+            return Err(CloudPingError::validation(
+                "footprint.min_continents",
+                format!("cannot exceed footprint.count ({})", self.count),
+            ));
+        }
+        if let Some(grade) = self.min_grade {
+            if !GRADES.contains(&grade) {
+                return Err(CloudPingError::validation(
+                    "footprint.min_grade",
+                    format!("'{grade}' is not a grade, expected one of A, B, C, D or F"),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The `count` regions of `ranked` with the highest total score that meet the constraints
+    ///
+    /// # Errors
+    /// Returns a validation error when no set of regions meets the constraints
+    pub fn select(&self, ranked: &[RankedResult]) -> Result<Footprint> {
+        let max_grade = self
+            .min_grade
+            .and_then(|grade| GRADES.iter().position(|&g| g == grade));
+        let mut candidates: Vec<Candidate> = ranked
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, _, _, result))| {
+                max_grade.map_or(true, |max| {
+                    GRADES
+                        .iter()
+                        .position(|&g| g == result.grade)
+                        .is_some_and(|rank| rank <= max)
+                })
+            })
+            .map(|(index, (score, _, stats, _))| Candidate {
+                index,
+                score: *score,
+                provider: (!stats.provider.trim().is_empty()).then(|| stats.provider.trim().to_lowercase()),
+                continent: continent_of(&stats.country),
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+        let infeasible = || {
+            CloudPingError::validation(
+                "footprint",
+                format!(
+                    "no {} regions{} span {} providers and {} continents",
+                    self.count,
+                    self.min_grade
+                        .map_or_else(String::new, |grade| format!(" graded {grade} or better")),
+                    self.min_providers,
+                    self.min_continents
+                ),
+            )
+        };
+        // Rule out impossible constraints before searching, which would otherwise try every set
+        if candidates.len() < self.count
+            || distinct(candidates.iter().map(|c| c.provider.as_deref())) < self.min_providers
+            || distinct(candidates.iter().map(|c| c.continent)) < self.min_continents
+        {
+            return Err(infeasible());
+        }
+
+        let mut search = Search {
+            candidates: &candidates,
+            constraints: self,
+            prefix: std::iter::once(0.0)
+                .chain(candidates.iter().scan(0.0, |sum, c| {
+                    *sum += c.score;
+                    Some(*sum)
+                }))
+                .collect(),
+            chosen: Vec::with_capacity(self.count),
+            best: None,
+        };
+        search.run(0, 0.0);
+        let (total, chosen) = search.best.ok_or_else(infeasible)?;
+
+        let flattened = RankedRegion::from_ranking(ranked);
+        let regions: Vec<RankedRegion> = chosen
+            .iter()
+            .map(|&i| flattened[candidates[i].index].clone())
+            .collect();
+        let mut providers: Vec<String> = Vec::new();
+        let mut continents: Vec<String> = Vec::new();
+        for region in &regions {
+            if !region.provider.is_empty() && !providers.iter().any(|p| p.eq_ignore_ascii_case(&region.provider)) {
+                providers.push(region.provider.clone());
+            }
+            if let Some(continent) = continent_of(&region.country) {
+                if !continents.iter().any(|c| c == continent) {
+                    continents.push(continent.to_string());
+                }
+            }
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let average_score = total / self.count as f64;
+        Ok(Footprint {
+            regions,
+            providers,
+            continents,
+            average_score,
+        })
+    }
+}
+
+/// Number of distinct known values
+fn distinct<T: PartialEq>(values: impl Iterator<Item = Option<T>>) -> usize {
+    let mut seen: Vec<T> = Vec::new();
+    for value in values.flatten() {
+        if !seen.contains(&value) {
+            seen.push(value);
+        }
+    }
+    seen.len()
+}
+
+/// Branch and bound over candidates sorted by score, best first
+struct Search<'a> {
+    candidates: &'a [Candidate],
+    constraints: &'a FootprintConstraints,
+    /// `prefix[i]` is the total score of the first `i` candidates
+    prefix: Vec<f64>,
+    chosen: Vec<usize>,
+    best: Option<(f64, Vec<usize>)>,
+}
+
+impl Search<'_> {
+    fn run(&mut self, start: usize, total: f64) {
+        let slots = self.constraints.count - self.chosen.len();
+        let providers = distinct(self.chosen.iter().map(|&i| self.candidates[i].provider.as_deref()));
+        let continents = distinct(self.chosen.iter().map(|&i| self.candidates[i].continent));
+        // Each remaining slot adds at most one provider and one continent
+        if providers + slots < self.constraints.min_providers
+            || continents + slots < self.constraints.min_continents
+        {
+            return;
+        }
+        if slots == 0 {
+            if self.best.as_ref().map_or(true, |(best, _)| total > *best) {
+                self.best = Some((total, self.chosen.clone()));
+            }
+            return;
+        }
+
+        for i in start..=self.candidates.len() - slots {
+            // The next candidates are the best still available, so no later start does better
+            let bound = total + self.prefix[i + slots] - self.prefix[i];
+            if self.best.as_ref().is_some_and(|(best, _)| bound <= *best) {
+                break;
+            }
+            self.chosen.push(i);
+            self.run(i + 1, total + self.candidates[i].score);
+            self.chosen.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AlgorithmWeights, PingStats, ScoringAdapter};
+
+    #[test]
+    fn test_select_footprint() {
+        let region = |name: &str, avg: f64, provider: &str, country: &str| {
+            let mut stats = PingStats::new(10);
+            stats.successful_pings = 10;
+            stats.avg = avg;
+            stats.latencies = vec![avg; 10];
+            stats.provider = provider.to_string();
+            stats.country = country.to_string();
+            (name.to_string(), stats)
+        };
+        let ranked = ScoringAdapter::get_sorted_results(
+            &[
+                region("Frankfurt", 10.0, "AWS", "DE"),
+                region("Paris", 12.0, "AWS", "FR"),
+                region("London", 14.0, "AWS", "gb"),
+                region("Virginia", 40.0, "AWS", "US"),
+                region("Amsterdam", 18.0, "GCP", "NL"),
+                region("Sydney", 900.0, "GCP", "AU"),
+            ],
+            &AlgorithmWeights::default(),
+        );
+
+        // The best three are all AWS in Europe; Amsterdam and Virginia bring the spread
+        let footprint = FootprintConstraints::default().select(&ranked).unwrap();
+        let names: Vec<&str> = footprint.regions.iter().map(|r| r.region.as_str()).collect();
+        assert_eq!(names, ["Frankfurt", "Amsterdam", "Virginia"]);
+        assert_eq!(footprint.providers, ["AWS", "GCP"]);
+        assert_eq!(footprint.continents, ["Europe", "North America"]);
+        assert_eq!(footprint.regions[1].rank, 4);
+
+        let loose = FootprintConstraints {
+            min_providers: 1,
+            min_continents: 1,
+            ..FootprintConstraints::default()
+        };
+        let names: Vec<String> = loose.select(&ranked).unwrap().regions.into_iter().map(|r| r.region).collect();
+        assert_eq!(names, ["Frankfurt", "Paris", "London"]);
+
+        // Sydney is the only other continent but is graded below B
+        let three_continents = FootprintConstraints {
+            min_continents: 3,
+            ..FootprintConstraints::default()
+        };
+        assert!(three_continents.select(&ranked).is_err());
+
+        assert_eq!(continent_of("br"), Some("South America"));
+        assert_eq!(continent_of(""), None);
+        assert!(FootprintConstraints { count: 1, ..FootprintConstraints::default() }.validate().is_err());
+
+        let single = FootprintConstraints::default().with_count(1);
+        assert!(single.validate().is_ok());
+        let names: Vec<String> = single.select(&ranked).unwrap().regions.into_iter().map(|r| r.region).collect();
+        assert_eq!(names, ["Frankfurt"]);
+        assert_eq!(continent_of("Germany"), Some("Europe"));
+    }
+}
@@ -0,0 +1,325 @@
+//! Region-to-region latency estimates
+//!
+//! Estimates the round trip between every pair of regions from the
+//! great-circle distance between them: the fiber round trip over that
+//! distance times a route factor for cables that do not follow the great
+//! circle. The route factor is calibrated against the client's own
+//! measurements when the client's coordinates are known, comparing each
+//! region's fastest ping with its fiber floor. Pairs found in an optional
+//! inter-region dataset, such as published provider latencies, use the
+//! dataset value instead.
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::quality::MeasurementQuality;
+use super::region::{Coordinates, Region};
+use super::stats::PingStats;
+use crate::collector::csv_field;
+use crate::error::{CloudPingError, Result};
+
+/// Route factor used when no measurement can calibrate one
+pub const DEFAULT_ROUTE_FACTOR: f64 = 1.5;
+
+/// Closer regions are not used for calibration; their pings are mostly server and handshake time
+const MIN_CALIBRATION_DISTANCE_KM: f64 = 500.0;
+
+/// Bounds of a calibrated route factor
+const ROUTE_FACTOR_RANGE: (f64, f64) = (1.0, 4.0);
+
+/// Where an estimate came from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EstimateSource {
+    /// Taken from the inter-region dataset
+    Dataset,
+    /// Derived from the great-circle distance and the route factor
+    Distance,
+}
+
+/// Estimated round trip between two regions
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct LatencyEstimate {
+    /// Round-trip time in milliseconds
+    pub rtt_ms: f64,
+    /// Where the estimate came from
+    pub source: EstimateSource,
+}
+
+/// Measured round trip between two regions in an inter-region dataset
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DatasetEntry {
+    /// One region name
+    pub from: String,
+    /// The other region name
+    pub to: String,
+    /// Round-trip time in milliseconds
+    pub rtt_ms: f64,
+}
+
+/// Measured region-to-region round trips, e.g. published by a provider
+///
+/// Read from CSV with a `from,to,rtt_ms` header. Pairs apply in both
+/// directions and region names are compared case-insensitively.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct InterRegionDataset {
+    /// Measured pairs
+    pub entries: Vec<DatasetEntry>,
+}
+
+impl InterRegionDataset {
+    /// Read a CSV dataset
+    ///
+    /// # Errors
+    /// Returns an error when the file cannot be read or a line is not `from,to,rtt_ms`
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(&content).map_err(|e| {
+            CloudPingError::data_loading(format!("Invalid inter-region dataset {}: {e}", path.display()))
+        })
+    }
+
+    /// Parse CSV lines of `from,to,rtt_ms`, skipping the header, blank lines and `#` comments
+    ///
+    /// # Errors
+    /// Returns a message naming the first line that cannot be parsed
+    pub fn parse(content: &str) -> std::result::Result<Self, String> {
+        let mut entries = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || (number == 0 && line.starts_with("from,")) {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [from, to, rtt] = fields[..] else {
+                return Err(format!("line {}: expected from,to,rtt_ms", number + 1));
+            };
+            let rtt_ms = rtt
+                .parse::<f64>()
+                .ok()
+                .filter(|rtt| rtt.is_finite() && *rtt >= 0.0)
+                .ok_or_else(|| format!("line {}: '{rtt}' is not a round-trip time", number + 1))?;
+            entries.push(DatasetEntry {
+                from: from.to_string(),
+                to: to.to_string(),
+                rtt_ms,
+            });
+        }
+        Ok(Self { entries })
+    }
+
+    /// Measured round trip between `a` and `b`, in either direction
+    #[must_use]
+    pub fn lookup(&self, a: &str, b: &str) -> Option<f64> {
+        self.entries
+            .iter()
+            .find(|entry| {
+                (entry.from.eq_ignore_ascii_case(a) && entry.to.eq_ignore_ascii_case(b))
+                    || (entry.from.eq_ignore_ascii_case(b) && entry.to.eq_ignore_ascii_case(a))
+            })
+            .map(|entry| entry.rtt_ms)
+    }
+
+    fn mentions(&self, name: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.from.eq_ignore_ascii_case(name) || entry.to.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Estimated round trips between every pair of regions
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LatencyMatrix {
+    /// Region names, in row and column order
+    pub regions: Vec<String>,
+    /// Factor the fiber round trip over the great circle is multiplied by
+    pub route_factor: f64,
+    /// Client measurements the route factor was calibrated on; 0 for the default
+    pub calibration_samples: usize,
+    /// `rows[i][j]` is the estimate from `regions[i]` to `regions[j]`, absent
+    /// on the diagonal and without coordinates or a dataset entry
+    pub rows: Vec<Vec<Option<LatencyEstimate>>>,
+}
+
+impl LatencyMatrix {
+    /// Estimate round trips between `regions`
+    ///
+    /// Regions are included when they have coordinates or appear in
+    /// `dataset`. `measurements` from `client` calibrate the route factor.
+    #[must_use]
+    pub fn estimate(
+        regions: &[Region],
+        measurements: &[(String, PingStats)],
+        client: Option<&Coordinates>,
+        dataset: &InterRegionDataset,
+    ) -> Self {
+        let included: Vec<&Region> = regions
+            .iter()
+            .filter(|region| region.coordinates.is_some() || dataset.mentions(&region.name))
+            .collect();
+        let (route_factor, calibration_samples) = client
+            .map_or((DEFAULT_ROUTE_FACTOR, 0), |client| {
+                Self::calibrate(regions, measurements, client)
+            });
+
+        let rows = included
+            .iter()
+            .map(|from| {
+                included
+                    .iter()
+                    .map(|to| {
+                        if std::ptr::eq(*from, *to) {
+                            return None;
+                        }
+                        if let Some(rtt_ms) = dataset.lookup(&from.name, &to.name) {
+                            return Some(LatencyEstimate {
+                                rtt_ms,
+                                source: EstimateSource::Dataset,
+                            });
+                        }
+                        from.distance_to(to).map(|distance_km| LatencyEstimate {
+                            rtt_ms: MeasurementQuality::fiber_floor_ms(distance_km) * route_factor,
+                            source: EstimateSource::Distance,
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            regions: included.iter().map(|region| region.name.clone()).collect(),
+            route_factor,
+            calibration_samples,
+            rows,
+        }
+    }
+
+    /// Median ratio of each measured region's fastest ping to its fiber floor
+    /// from `client`, with the number of regions it was taken over
+    #[must_use]
+    pub fn calibrate(
+        regions: &[Region],
+        measurements: &[(String, PingStats)],
+        client: &Coordinates,
+    ) -> (f64, usize) {
+        let ratios: Vec<f64> = measurements
+            .iter()
+            .filter(|(_, stats)| stats.is_successful())
+            .filter_map(|(name, stats)| {
+                let target = regions.iter().find(|region| region.name == *name)?.coordinates.as_ref()?;
+                let distance_km = client.distance_to(target);
+                (distance_km >= MIN_CALIBRATION_DISTANCE_KM)
+                    .then(|| stats.min / MeasurementQuality::fiber_floor_ms(distance_km))
+            })
+            .collect();
+        if ratios.is_empty() {
+            return (DEFAULT_ROUTE_FACTOR, 0);
+        }
+        let (low, high) = ROUTE_FACTOR_RANGE;
+        (statistical::median(&ratios).clamp(low, high), ratios.len())
+    }
+
+    /// Estimate between the regions named `from` and `to`
+    #[must_use]
+    pub fn get(&self, from: &str, to: &str) -> Option<&LatencyEstimate> {
+        let index = |name: &str| self.regions.iter().position(|region| region == name);
+        self.rows.get(index(from)?)?.get(index(to)?)?.as_ref()
+    }
+
+    /// Lowest and highest estimate, absent when there is none
+    #[must_use]
+    pub fn range(&self) -> Option<(f64, f64)> {
+        self.rows
+            .iter()
+            .flatten()
+            .flatten()
+            .map(|estimate| estimate.rtt_ms)
+            .fold(None, |range, rtt| match range {
+                None => Some((rtt, rtt)),
+                Some((low, high)) => Some((f64::min(low, rtt), f64::max(high, rtt))),
+            })
+    }
+
+    /// One CSV line per ordered pair with an estimate, with a header row
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("from,to,rtt_ms,source\n");
+        for (from, row) in self.regions.iter().zip(&self.rows) {
+            for (to, estimate) in self.regions.iter().zip(row) {
+                if let Some(estimate) = estimate {
+                    let source = match estimate.source {
+                        EstimateSource::Dataset => "dataset",
+                        EstimateSource::Distance => "distance",
+                    };
+                    let _ = writeln!(
+                        csv,
+                        "{},{},{:.1},{source}",
+                        csv_field(from),
+                        csv_field(to),
+                        estimate.rtt_ms
+                    );
+                }
+            }
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(name: &str, coordinates: Option<(f64, f64)>) -> Region {
+        let mut builder = Region::builder(name.to_string(), format!("https://{name}.example.com"))
+            .unwrap();
+        if let Some((latitude, longitude)) = coordinates {
+            builder = builder.coordinates(latitude, longitude).unwrap();
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_latency_matrix() {
+        let regions = [
+            region("frankfurt", Some((50.11, 8.68))),
+            region("virginia", Some((38.95, -77.45))),
+            region("tokyo", Some((35.68, 139.69))),
+            region("mumbai", None),
+            region("paris", None),
+        ];
+        let dataset = InterRegionDataset::parse(
+            "from,to,rtt_ms\n# published\nTokyo,Mumbai,120\nFRANKFURT,tokyo, 230.5\n",
+        )
+        .unwrap();
+
+        // Virginia answers at twice its fiber floor from Frankfurt
+        let client = Coordinates::new(50.11, 8.68).unwrap();
+        let distance = client.distance_to(regions[1].coordinates.as_ref().unwrap());
+        let mut stats = PingStats::new(5);
+        stats.successful_pings = 5;
+        stats.min = 2.0 * MeasurementQuality::fiber_floor_ms(distance);
+        let measurements = [("virginia".to_string(), stats)];
+
+        let matrix = LatencyMatrix::estimate(&regions, &measurements, Some(&client), &dataset);
+        assert_eq!(matrix.regions, ["frankfurt", "virginia", "tokyo", "mumbai"]);
+        assert_eq!(matrix.calibration_samples, 1);
+        assert!((matrix.route_factor - 2.0).abs() < 1e-9);
+
+        let estimate = matrix.get("virginia", "frankfurt").unwrap();
+        assert_eq!(estimate.source, EstimateSource::Distance);
+        assert!((estimate.rtt_ms - 2.0 * MeasurementQuality::fiber_floor_ms(distance)).abs() < 1e-9);
+        assert_eq!(matrix.get("tokyo", "frankfurt").unwrap().rtt_ms, 230.5);
+        assert_eq!(matrix.get("mumbai", "tokyo").unwrap().source, EstimateSource::Dataset);
+        assert_eq!(matrix.get("mumbai", "frankfurt"), None);
+        assert_eq!(matrix.get("tokyo", "tokyo"), None);
+        assert!(matrix.to_csv().contains("\ntokyo,mumbai,120.0,dataset\n"));
+
+        let uncalibrated = LatencyMatrix::estimate(&regions, &[], None, &InterRegionDataset::default());
+        assert_eq!(uncalibrated.regions.len(), 3);
+        assert_eq!(uncalibrated.route_factor, DEFAULT_ROUTE_FACTOR);
+        assert!(InterRegionDataset::parse("a,b").is_err());
+        assert!(InterRegionDataset::parse("a,b,-3").is_err());
+    }
+}
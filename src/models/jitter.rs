@@ -0,0 +1,96 @@
+//! Jitter estimation algorithms
+//!
+//! The same algorithm is used for batch benchmark statistics and for the
+//! aggregator's streaming estimate, so scores stay comparable between the
+//! two paths.
+
+use serde::{Deserialize, Serialize};
+
+/// How jitter is derived from a series of round-trip times
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum JitterAlgorithm {
+    /// Mean absolute difference between consecutive samples
+    #[default]
+    ConsecutiveDiff,
+    /// RFC 3550 interarrival jitter: consecutive differences smoothed with gain 1/16
+    Rfc3550,
+    /// Standard deviation of the samples around their mean
+    StdDev,
+}
+
+impl JitterAlgorithm {
+    /// Gain used by RFC 3550 interarrival jitter, independent of any EWMA setting
+    pub const RFC3550_GAIN: f64 = 1.0 / 16.0;
+
+    /// Jitter of a complete series of round-trip times, in milliseconds
+    #[must_use]
+    pub fn compute(self, samples: &[f64]) -> f64 {
+        if samples.len() < 2 {
+            return 0.0;
+        }
+
+        let deltas: Vec<f64> = samples.windows(2).map(|w| (w[1] - w[0]).abs()).collect();
+        match self {
+            Self::ConsecutiveDiff => statistical::mean(&deltas),
+            Self::Rfc3550 => deltas.iter().fold(0.0, |jitter, delta| {
+                (delta - jitter).mul_add(Self::RFC3550_GAIN, jitter)
+            }),
+            Self::StdDev => {
+                let mean = statistical::mean(samples);
+                statistical::mean(
+                    &samples
+                        .iter()
+                        .map(|rtt| (rtt - mean).powi(2))
+                        .collect::<Vec<_>>(),
+                )
+                .sqrt()
+            }
+        }
+    }
+
+    /// Name as written in the configuration file
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::ConsecutiveDiff => "consecutive_diff",
+            Self::Rfc3550 => "rfc3550",
+            Self::StdDev => "std_dev",
+        }
+    }
+}
+
+impl std::fmt::Display for JitterAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_algorithms() {
+        let samples = [10.0, 14.0, 10.0, 14.0];
+
+        assert!((JitterAlgorithm::ConsecutiveDiff.compute(&samples) - 4.0).abs() < 1e-9);
+        assert!((JitterAlgorithm::StdDev.compute(&samples) - 2.0).abs() < 1e-9);
+
+        // 4/16, then 4/16 + (4 - 4/16)/16, ...
+        let expected = (0..3).fold(0.0, |j: f64, _| j + (4.0 - j) / 16.0);
+        assert!((JitterAlgorithm::Rfc3550.compute(&samples) - expected).abs() < 1e-9);
+
+        assert!(JitterAlgorithm::Rfc3550.compute(&[42.0]).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_config_names() {
+        let algorithm: JitterAlgorithm = serde_json::from_str("\"rfc3550\"").unwrap();
+        assert_eq!(algorithm, JitterAlgorithm::Rfc3550);
+        assert_eq!(
+            serde_json::to_string(&JitterAlgorithm::StdDev).unwrap(),
+            format!("\"{}\"", JitterAlgorithm::StdDev)
+        );
+    }
+}
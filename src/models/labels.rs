@@ -0,0 +1,118 @@
+//! Free-form labels attached to benchmark runs
+//!
+//! Labels such as `isp=comcast` or `location=office` describe where and how a
+//! run was taken. They are copied onto every result of the run, so saved
+//! history, archives and agent reports can later be grouped and compared by
+//! environment.
+
+use std::collections::BTreeMap;
+
+use crate::error::{CloudPingError, Result};
+
+use super::stats::{PingStats, TestHistory};
+
+/// Labels of a run, sorted by key
+pub type RunLabels = BTreeMap<String, String>;
+
+/// Parse a `key=value` label as given on the command line
+pub fn parse_label(label: &str) -> Result<(String, String)> {
+    let (key, value) = label.split_once('=').ok_or_else(|| {
+        CloudPingError::validation("label", format!("'{label}' is not of the form key=value"))
+    })?;
+    let key = key.trim();
+    validate_key(key)?;
+    Ok((key.to_string(), value.trim().to_string()))
+}
+
+/// Check that every label key is usable for grouping
+pub fn validate_labels(labels: &RunLabels) -> Result<()> {
+    labels.keys().try_for_each(|key| validate_key(key))
+}
+
+fn validate_key(key: &str) -> Result<()> {
+    if key.is_empty() {
+        return Err(CloudPingError::validation("label", "key must not be empty"));
+    }
+    if key.contains(|c: char| c.is_whitespace() || c == '=') {
+        return Err(CloudPingError::validation(
+            "label",
+            format!("key '{key}' must not contain whitespace or '='"),
+        ));
+    }
+    Ok(())
+}
+
+/// Labels as `key=value` pairs separated by commas, for reports
+#[must_use]
+pub fn labels_summary(labels: &RunLabels) -> String {
+    labels
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl TestHistory {
+    /// Saved results grouped by the value of label `key`; unlabelled results are left out
+    #[must_use]
+    pub fn results_by_label(&self, key: &str) -> BTreeMap<String, Vec<&PingStats>> {
+        let mut groups: BTreeMap<String, Vec<&PingStats>> = BTreeMap::new();
+        for stats in &self.historical_data {
+            if let Some(value) = stats.labels.get(key) {
+                groups.entry(value.clone()).or_default().push(stats);
+            }
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_label() {
+        assert_eq!(
+            parse_label("isp=comcast").unwrap(),
+            ("isp".to_string(), "comcast".to_string())
+        );
+        // Only the first '=' separates key and value
+        assert_eq!(
+            parse_label(" note = a=b ").unwrap(),
+            ("note".to_string(), "a=b".to_string())
+        );
+        assert_eq!(parse_label("location=").unwrap().1, "");
+
+        assert!(parse_label("office").is_err());
+        assert!(parse_label("=office").is_err());
+        assert!(parse_label("home office=1").is_err());
+
+        let labels = RunLabels::from([("isp".to_string(), "comcast".to_string())]);
+        assert!(validate_labels(&labels).is_ok());
+        assert_eq!(labels_summary(&labels), "isp=comcast");
+    }
+
+    #[test]
+    fn test_history_grouped_by_label() {
+        let mut history = TestHistory::new(
+            "eu".to_string(),
+            "Frankfurt".to_string(),
+            "https://eu.example.com".to_string(),
+        );
+        for (location, avg) in [("office", 20.0), ("home", 35.0), ("office", 22.0)] {
+            let mut stats = PingStats::new(5);
+            stats.avg = avg;
+            stats
+                .labels
+                .insert("location".to_string(), location.to_string());
+            history.add_test_result(stats);
+        }
+        history.add_test_result(PingStats::new(5));
+
+        let groups = history.results_by_label("location");
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups["office"].len(), 2);
+        assert!((groups["home"][0].avg - 35.0).abs() < f64::EPSILON);
+        assert!(history.results_by_label("isp").is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+//! Loss pattern analysis
+//!
+//! Packet loss percentage alone cannot tell ten isolated drops from a single
+//! ten-second outage. Run-length analysis of consecutive failures, together
+//! with the transition probabilities of a two-state Gilbert-Elliott model,
+//! separates random loss from bursts that real-time media cannot conceal.
+
+use serde::{Deserialize, Serialize};
+
+/// Suitability penalty per lost probe in the longest burst beyond the first
+const BURST_PENALTY_PER_PROBE: f64 = 0.15;
+
+/// Lowest factor the burst penalty can reduce a suitability score to
+const MIN_BURST_FACTOR: f64 = 0.25;
+
+/// Run-length statistics of failures in a sequence of probes
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct LossPattern {
+    /// Number of probes analysed
+    pub total: usize,
+    /// Number of failed probes
+    pub lost: usize,
+    /// Number of runs of consecutive failures
+    pub burst_count: usize,
+    /// Longest run of consecutive failures
+    pub max_burst: usize,
+    /// Mean length of a failure run
+    pub mean_burst: f64,
+    /// Gilbert-Elliott probability of moving from success to failure
+    pub p_good_to_bad: f64,
+    /// Gilbert-Elliott probability of recovering from failure to success
+    pub p_bad_to_good: f64,
+    /// Observed mean burst length over the one expected for random loss
+    /// (1 means random, above 1 means bursty, 0 when nothing was lost)
+    pub burst_ratio: f64,
+}
+
+impl LossPattern {
+    /// Analyse probe outcomes in order, `true` meaning the probe succeeded
+    #[must_use]
+    pub fn from_outcomes(outcomes: impl IntoIterator<Item = bool>) -> Self {
+        let mut pattern = Self::default();
+        let mut current_burst = 0;
+        let mut previous: Option<bool> = None;
+        let (mut good, mut bad, mut good_to_bad, mut bad_to_good) = (0u32, 0u32, 0u32, 0u32);
+
+        for success in outcomes {
+            pattern.total += 1;
+
+            match previous {
+                Some(true) => {
+                    good += 1;
+                    if !success {
+                        good_to_bad += 1;
+                    }
+                }
+                Some(false) => {
+                    bad += 1;
+                    if success {
+                        bad_to_good += 1;
+                    }
+                }
+                None => {}
+            }
+
+            if success {
+                current_burst = 0;
+            } else {
+                pattern.lost += 1;
+                if current_burst == 0 {
+                    pattern.burst_count += 1;
+                }
+                current_burst += 1;
+                pattern.max_burst = pattern.max_burst.max(current_burst);
+            }
+
+            previous = Some(success);
+        }
+
+        if pattern.burst_count > 0 {
+            pattern.mean_burst = ratio(pattern.lost, pattern.burst_count);
+            let loss_rate = ratio(pattern.lost, pattern.total);
+            pattern.burst_ratio = pattern.mean_burst * (1.0 - loss_rate);
+        }
+        if good > 0 {
+            pattern.p_good_to_bad = f64::from(good_to_bad) / f64::from(good);
+        }
+        if bad > 0 {
+            pattern.p_bad_to_good = f64::from(bad_to_good) / f64::from(bad);
+        }
+
+        pattern
+    }
+
+    /// Whether failures cluster more than random loss would
+    #[must_use]
+    pub fn is_bursty(&self) -> bool {
+        self.max_burst > 1 && self.burst_ratio > 1.0
+    }
+
+    /// Factor (0.25-1) applied to streaming and voice suitability
+    ///
+    /// Isolated drops are concealed by jitter buffers and codecs; each extra
+    /// consecutive loss makes an audible or visible gap more likely.
+    #[must_use]
+    pub fn burst_factor(&self) -> f64 {
+        if self.max_burst <= 1 {
+            return 1.0;
+        }
+        let extra = f64::from(u32::try_from(self.max_burst - 1).unwrap_or(u32::MAX));
+        (1.0 / extra.mul_add(BURST_PENALTY_PER_PROBE, 1.0)).max(MIN_BURST_FACTOR)
+    }
+}
+
+fn ratio(part: usize, whole: usize) -> f64 {
+    let part = f64::from(u32::try_from(part).unwrap_or(u32::MAX));
+    let whole = f64::from(u32::try_from(whole).unwrap_or(u32::MAX));
+    part / whole
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcomes(pattern: &str) -> Vec<bool> {
+        pattern.chars().map(|c| c == '.').collect()
+    }
+
+    #[test]
+    fn test_isolated_versus_burst_loss() {
+        // Same 20% loss, spread out or in one block
+        let isolated = LossPattern::from_outcomes(outcomes("....x....x"));
+        let burst = LossPattern::from_outcomes(outcomes("........xx"));
+
+        assert_eq!(isolated.lost, burst.lost);
+        assert_eq!(isolated.burst_count, 2);
+        assert_eq!(isolated.max_burst, 1);
+        assert!(!isolated.is_bursty());
+        assert!((isolated.burst_factor() - 1.0).abs() < f64::EPSILON);
+
+        assert_eq!(burst.burst_count, 1);
+        assert_eq!(burst.max_burst, 2);
+        assert!((burst.burst_ratio - 1.6).abs() < 1e-9);
+        assert!(burst.is_bursty());
+        assert!(burst.burst_factor() < 1.0);
+    }
+
+    #[test]
+    fn test_gilbert_elliott_transitions() {
+        let pattern = LossPattern::from_outcomes(outcomes("..xxx..x"));
+
+        // 4 transitions out of success, 2 into failure; 3 out of failure, 1 recovery
+        assert!((pattern.p_good_to_bad - 0.5).abs() < 1e-9);
+        assert!((pattern.p_bad_to_good - 1.0 / 3.0).abs() < 1e-9);
+
+        let clean = LossPattern::from_outcomes(outcomes("...."));
+        assert_eq!(clean.lost, 0);
+        assert!(clean.burst_ratio.abs() < f64::EPSILON);
+        assert!(
+            LossPattern::from_outcomes(outcomes("xxxxxxxxxxxx")).burst_factor() >= MIN_BURST_FACTOR
+        );
+    }
+}
@@ -1,14 +1,46 @@
 //! Metrics collection and ring buffer implementation
 
+use std::cell::RefCell;
 use std::collections::VecDeque;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use super::exp_histogram::ExponentialHistogram;
+use super::jitter::JitterAlgorithm;
+use super::loss::LossPattern;
 use super::probe::ProbeRecord;
-use super::utils::percentile;
+use super::utils::{percentile, percentile_of_sorted, sort_values};
 
 /// Ring buffer for efficient sliding window operations
-#[derive(Debug, Clone)]
+///
+/// Serializes as its `capacity` and its `items`, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    from = "RingBufferRepr<T>",
+    bound(deserialize = "T: Deserialize<'de>")
+)]
 pub struct RingBuffer<T> {
+    capacity: usize,
+    #[serde(rename = "items")]
     data: VecDeque<T>,
+}
+
+/// Serialized form of [`RingBuffer`], trimmed to capacity on the way in
+#[derive(Deserialize)]
+struct RingBufferRepr<T> {
     capacity: usize,
+    items: VecDeque<T>,
+}
+
+impl<T> From<RingBufferRepr<T>> for RingBuffer<T> {
+    fn from(repr: RingBufferRepr<T>) -> Self {
+        let mut data = repr.items;
+        let excess = data.len().saturating_sub(repr.capacity);
+        data.drain(..excess);
+        Self {
+            capacity: repr.capacity,
+            data,
+        }
+    }
 }
 
 impl<T> RingBuffer<T> {
@@ -21,19 +53,44 @@ impl<T> RingBuffer<T> {
         }
     }
 
-    /// Push an item, removing oldest if at capacity
-    pub fn push(&mut self, item: T) {
-        if self.data.len() >= self.capacity {
-            self.data.pop_front();
+    /// Push an item, removing and returning the oldest if at capacity
+    pub fn push(&mut self, item: T) -> Option<T> {
+        let evicted = if self.data.len() >= self.capacity {
+            self.data.pop_front()
+        } else {
+            None
+        };
+        if self.data.len() == self.data.capacity() {
+            // Grow by doubling as usual, but never past the buffer's capacity
+            let additional = self.data.len().max(4).min(self.capacity - self.data.len());
+            self.data.reserve_exact(additional);
         }
         self.data.push_back(item);
+        evicted
     }
 
-    /// Get iterator over items
-    pub fn iter(&self) -> impl Iterator<Item = &T> {
+    /// Get iterator over items, oldest first
+    #[must_use]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator {
         self.data.iter()
     }
 
+    /// Get iterator over items, most recent first
+    pub fn iter_recent(&self) -> impl Iterator<Item = &T> {
+        self.data.iter().rev()
+    }
+
+    /// Items as two slices, oldest first, without moving anything
+    #[must_use]
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        self.data.as_slices()
+    }
+
+    /// Items as one slice, oldest first, rearranging storage once if it wrapped
+    pub fn make_contiguous(&mut self) -> &[T] {
+        self.data.make_contiguous()
+    }
+
     /// Get current length
     #[must_use]
     pub fn len(&self) -> usize {
@@ -52,8 +109,24 @@ impl<T> RingBuffer<T> {
         self.capacity
     }
 
+    /// Change the capacity, removing and returning the oldest items that no longer fit
+    pub fn set_capacity(&mut self, capacity: usize) -> Vec<T> {
+        let excess = self.data.len().saturating_sub(capacity);
+        let evicted = self.data.drain(..excess).collect();
+        self.data.shrink_to(capacity);
+        self.capacity = capacity;
+        evicted
+    }
+
+    /// Bytes allocated for the buffer's slots, not counting heap data the items own
+    #[must_use]
+    pub fn allocated_bytes(&self) -> usize {
+        self.data.capacity() * std::mem::size_of::<T>()
+    }
+
     /// Get items as slice (most recent first)
     #[must_use]
+    #[deprecated(note = "allocates on every call; use `iter_recent` or `make_contiguous`")]
     pub fn as_slice(&self) -> Vec<&T> {
         self.data.iter().rev().collect()
     }
@@ -63,6 +136,11 @@ impl<T> RingBuffer<T> {
         self.data.clear();
     }
 
+    /// Remove and return the oldest item
+    pub fn pop_oldest(&mut self) -> Option<T> {
+        self.data.pop_front()
+    }
+
     /// Get the most recent item
     #[must_use]
     pub fn latest(&self) -> Option<&T> {
@@ -76,8 +154,77 @@ impl<T> RingBuffer<T> {
     }
 }
 
+thread_local! {
+    /// Sorting space for percentiles, reused so they do not allocate per call
+    static SORT_SCRATCH: RefCell<Vec<f64>> = const { RefCell::new(Vec::new()) };
+}
+
+impl RingBuffer<f64> {
+    /// Sum of the values
+    #[must_use]
+    pub fn sum(&self) -> f64 {
+        self.data.iter().sum()
+    }
+
+    /// Mean of the values, `None` when empty
+    #[must_use]
+    pub fn mean(&self) -> Option<f64> {
+        let count = u32::try_from(self.data.len()).unwrap_or(u32::MAX);
+        (count > 0).then(|| self.sum() / f64::from(count))
+    }
+
+    /// Smallest value, `None` when empty
+    #[must_use]
+    pub fn min(&self) -> Option<f64> {
+        self.data.iter().copied().reduce(f64::min)
+    }
+
+    /// Largest value, `None` when empty
+    #[must_use]
+    pub fn max(&self) -> Option<f64> {
+        self.data.iter().copied().reduce(f64::max)
+    }
+
+    /// Percentile of the values, infinite when empty like `utils::percentile`
+    #[must_use]
+    pub fn percentile(&self, p: f64) -> f64 {
+        let [value] = self.percentiles([p]);
+        value
+    }
+
+    /// Several percentiles from one sort of a reused per-thread buffer
+    #[must_use]
+    pub fn percentiles<const N: usize>(&self, ps: [f64; N]) -> [f64; N] {
+        if self.data.is_empty() {
+            return [f64::INFINITY; N];
+        }
+        SORT_SCRATCH.with(|scratch| {
+            let mut sorted = scratch.borrow_mut();
+            sorted.clear();
+            sorted.extend(self.data.iter().copied());
+            sort_values(&mut sorted);
+            ps.map(|p| percentile_of_sorted(&sorted, p))
+        })
+    }
+}
+
+/// Scores kept per endpoint unless configured otherwise
+pub const DEFAULT_SCORE_HISTORY: usize = 60;
+
+/// An endpoint's score after one probe
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ScorePoint {
+    /// Time of the probe that produced the score
+    pub timestamp: DateTime<Utc>,
+    /// Score from 0 to 100
+    pub score: f64,
+}
+
 /// Aggregator state for per-endpoint metrics with optimized calculations
-#[derive(Debug, Clone)]
+///
+/// Serializes with its field names as keys, so snapshots can be stored and
+/// restored; infinite latency percentiles (no successful probes) become `null`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AggregatorState {
     // Endpoint identification
     pub endpoint_id: String,
@@ -86,9 +233,16 @@ pub struct AggregatorState {
     pub circular_buffer_short: RingBuffer<ProbeRecord>,
     pub circular_buffer_long: RingBuffer<ProbeRecord>,
     
+    /// Round-trip times of the successful probes in the short window, oldest first
+    pub rtts_short: RingBuffer<f64>,
+
     // Real-time metrics
     pub ewma_jitter_ms: f64,
     pub last_rtt_ms: Option<f64>,
+    /// Algorithm behind `ewma_jitter_ms`
+    pub jitter_algorithm: JitterAlgorithm,
+    ewma_rtt_mean_ms: Option<f64>,
+    ewma_rtt_variance: f64,
     
     // Counters for efficiency
     pub total_sent_long: usize,
@@ -97,21 +251,96 @@ pub struct AggregatorState {
     pub total_recv_short: usize,
     
     // Cached aggregates for fast reads
+    #[serde(with = "infinite_as_null")]
     pub cached_p50_short: f64,
+    #[serde(with = "infinite_as_null")]
     pub cached_p90_short: f64,
+    #[serde(with = "infinite_as_null")]
     pub cached_p99_short: f64,
     pub cached_loss_short: f64,
     pub cached_loss_long: f64,
     pub cached_avail_short: f64,
     pub cached_avail_long: f64,
+    /// Failure bursts within the short window
+    pub cached_loss_pattern_short: LossPattern,
     pub last_score: Option<f64>,
+    /// Recent scores with their probe times, oldest first
+    pub score_history: RingBuffer<ScorePoint>,
+    /// Every round trip since the state was created, for exporters that
+    /// take exponential histograms
+    #[serde(default)]
+    pub rtt_histogram: ExponentialHistogram,
     
     // Performance optimization: track if recalculation is needed
+    #[serde(skip, default)]
     dirty_short: bool,
+    /// Restored states recompute the long window at the next opportunity
+    #[serde(skip, default = "restored_dirty")]
     dirty_long: bool,
 }
 
+const fn restored_dirty() -> bool {
+    true
+}
+
+/// Non-finite values as `null`, which JSON cannot otherwise represent
+mod infinite_as_null {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[allow(clippy::trivially_copy_pass_by_ref)] // signature required by `serde(with)`
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_finite() {
+            serializer.serialize_some(value)
+        } else {
+            serializer.serialize_none()
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        Ok(Option::<f64>::deserialize(deserializer)?.unwrap_or(f64::INFINITY))
+    }
+}
+
 impl AggregatorState {
+    /// Bytes allocated for the slots of the endpoint's ring buffers
+    #[must_use]
+    pub fn buffer_bytes(&self) -> usize {
+        self.circular_buffer_short.allocated_bytes()
+            + self.circular_buffer_long.allocated_bytes()
+            + self.rtts_short.allocated_bytes()
+            + self.score_history.allocated_bytes()
+    }
+
+    /// Bytes the ring buffers of one endpoint take when full, for the given
+    /// window sizes and number of scores kept
+    #[must_use]
+    pub const fn full_buffer_bytes(w_short: usize, w_long: usize, score_history: usize) -> usize {
+        let record = std::mem::size_of::<ProbeRecord>();
+        w_short
+            .saturating_mul(record + std::mem::size_of::<f64>())
+            .saturating_add(w_long.saturating_mul(record))
+            .saturating_add(score_history.saturating_mul(std::mem::size_of::<ScorePoint>()))
+    }
+
+    /// Resize the short and long windows, dropping the oldest probes that no longer fit
+    pub fn resize_windows(&mut self, w_short: usize, w_long: usize) {
+        let evicted = self.circular_buffer_short.set_capacity(w_short);
+        for _ in evicted.iter().filter(|record| record.rtt_ms.is_some()) {
+            self.rtts_short.pop_oldest();
+        }
+        self.rtts_short.set_capacity(w_short);
+        self.circular_buffer_long.set_capacity(w_long);
+
+        self.total_sent_short = self.circular_buffer_short.len();
+        self.total_recv_short = self.circular_buffer_short.iter().filter(|r| r.success).count();
+        self.total_sent_long = self.circular_buffer_long.len();
+        self.total_recv_long = self.circular_buffer_long.iter().filter(|r| r.success).count();
+        self.dirty_short = true;
+        self.dirty_long = true;
+        self.recompute_short_aggregates();
+        self.recompute_long_aggregates();
+    }
+
     /// Create new aggregator state
     #[must_use]
     pub fn new(endpoint_id: String, w_short: usize, w_long: usize) -> Self {
@@ -119,8 +348,12 @@ impl AggregatorState {
             endpoint_id,
             circular_buffer_short: RingBuffer::new(w_short),
             circular_buffer_long: RingBuffer::new(w_long),
+            rtts_short: RingBuffer::new(w_short),
             ewma_jitter_ms: 0.0,
             last_rtt_ms: None,
+            jitter_algorithm: JitterAlgorithm::default(),
+            ewma_rtt_mean_ms: None,
+            ewma_rtt_variance: 0.0,
             total_sent_long: 0,
             total_recv_long: 0,
             total_sent_short: 0,
@@ -132,7 +365,10 @@ impl AggregatorState {
             cached_loss_long: 0.0,
             cached_avail_short: 0.0,
             cached_avail_long: 0.0,
+            cached_loss_pattern_short: LossPattern::default(),
             last_score: None,
+            score_history: RingBuffer::new(DEFAULT_SCORE_HISTORY),
+            rtt_histogram: ExponentialHistogram::default(),
             dirty_short: true,
             dirty_long: true,
         }
@@ -146,42 +382,94 @@ impl AggregatorState {
 
     /// Add a probe record and update all metrics
     pub fn add_record(&mut self, record: ProbeRecord, ewma_alpha: f64) {
-        // Push to both buffers
-        self.circular_buffer_short.push(record.clone());
-        self.circular_buffer_long.push(record.clone());
-
-        // Mark as dirty for recalculation
-        self.dirty_short = true;
-        self.dirty_long = true;
+        self.push_record(record, ewma_alpha);
+        self.recompute_short_aggregates();
+    }
 
-        // Update counts incrementally
-        self.update_counts();
+    /// Add a probe record, leaving short-window aggregates to
+    /// [`Self::recompute_short_aggregates`] after a batch of records
+    ///
+    /// A record stamped before the newest one, because the wall clock
+    /// stepped back, is restamped with [`ProbeRecord::timestamp_after`].
+    pub fn push_record(&mut self, mut record: ProbeRecord, ewma_alpha: f64) {
+        if let Some(newest) = self.circular_buffer_long.latest() {
+            record.timestamp = record.timestamp_after(newest);
+        }
 
         // Update EWMA jitter
         self.update_ewma_jitter(&record, ewma_alpha);
 
-        // Recompute short window aggregates immediately
-        self.recompute_short_aggregates();
-    }
+        // Push to both buffers, updating counts from what enters and leaves
+        let success = usize::from(record.success);
+        let rtt = record.rtt_ms;
+        let timestamp = record.timestamp;
+        let evicted_short = self.circular_buffer_short.push(record.clone());
+        let evicted_long = self.circular_buffer_long.push(record);
 
-    /// Update counters efficiently
-    fn update_counts(&mut self) {
+        // RTTs leave with their records; they are in the same order
+        if evicted_short.as_ref().is_some_and(|r| r.rtt_ms.is_some()) {
+            self.rtts_short.pop_oldest();
+        }
+        if let Some(rtt) = rtt {
+            self.rtts_short.push(rtt);
+            self.rtt_histogram.record(rtt, timestamp);
+        }
+        self.total_recv_short += success;
+        self.total_recv_long += success;
+        self.total_recv_short -= evicted_short.map_or(0, |r| usize::from(r.success));
+        self.total_recv_long -= evicted_long.map_or(0, |r| usize::from(r.success));
         self.total_sent_short = self.circular_buffer_short.len();
-        self.total_recv_short = self.circular_buffer_short.iter()
-            .filter(|r| r.success)
-            .count();
-
         self.total_sent_long = self.circular_buffer_long.len();
-        self.total_recv_long = self.circular_buffer_long.iter()
-            .filter(|r| r.success)
-            .count();
+
+        // Mark as dirty for recalculation
+        self.dirty_short = true;
+        self.dirty_long = true;
+    }
+
+    /// Record the score computed after a probe at `timestamp`
+    pub fn record_score(&mut self, timestamp: DateTime<Utc>, score: f64) {
+        self.last_score = Some(score);
+        self.score_history.push(ScorePoint { timestamp, score });
+    }
+
+    /// Median of the recorded scores, the baseline score drops are measured against
+    #[must_use]
+    pub fn score_baseline(&self) -> Option<f64> {
+        let scores: Vec<f64> = self.score_history.iter().map(|point| point.score).collect();
+        (!scores.is_empty()).then(|| percentile(&scores, 50.0))
     }
 
-    /// Update EWMA jitter calculation
+    /// Recorded scores, oldest first
+    #[must_use]
+    pub fn score_history(&self) -> Vec<ScorePoint> {
+        self.score_history.iter().copied().collect()
+    }
+
+    /// Update EWMA jitter calculation using the configured algorithm
     fn update_ewma_jitter(&mut self, record: &ProbeRecord, ewma_alpha: f64) {
+        if self.jitter_algorithm == JitterAlgorithm::StdDev {
+            if let Some(current_rtt) = record.rtt_ms {
+                // Exponentially weighted variance around an exponentially weighted mean
+                let mean = self.ewma_rtt_mean_ms.unwrap_or(current_rtt);
+                let diff = current_rtt - mean;
+                let increment = ewma_alpha * diff;
+                self.ewma_rtt_mean_ms = Some(mean + increment);
+                self.ewma_rtt_variance = (1.0 - ewma_alpha) * diff.mul_add(increment, self.ewma_rtt_variance);
+                self.ewma_jitter_ms = self.ewma_rtt_variance.sqrt();
+            }
+        }
+
         if let (Some(last_rtt), Some(current_rtt)) = (self.last_rtt_ms, record.rtt_ms) {
             let delta = (current_rtt - last_rtt).abs();
-            self.ewma_jitter_ms += (delta - self.ewma_jitter_ms) * ewma_alpha;
+            match self.jitter_algorithm {
+                JitterAlgorithm::ConsecutiveDiff => {
+                    self.ewma_jitter_ms += (delta - self.ewma_jitter_ms) * ewma_alpha;
+                }
+                JitterAlgorithm::Rfc3550 => {
+                    self.ewma_jitter_ms += (delta - self.ewma_jitter_ms) * JitterAlgorithm::RFC3550_GAIN;
+                }
+                JitterAlgorithm::StdDev => {}
+            }
         } else if record.rtt_ms.is_none() {
             // Treat timeout as large jitter penalty
             const MAX_JITTER_PENALTY: f64 = 100.0;
@@ -193,25 +481,14 @@ impl AggregatorState {
         }
     }
 
-    /// Recompute short window aggregates
-    fn recompute_short_aggregates(&mut self) {
+    /// Recompute short window aggregates after records were pushed
+    pub fn recompute_short_aggregates(&mut self) {
         if !self.dirty_short {
             return;
         }
 
-        let rtts: Vec<f64> = self.circular_buffer_short.iter()
-            .filter_map(|r| r.rtt_ms)
-            .collect();
-
-        if !rtts.is_empty() {
-            self.cached_p50_short = percentile(&rtts, 50.0);
-            self.cached_p90_short = percentile(&rtts, 90.0);
-            self.cached_p99_short = percentile(&rtts, 99.0);
-        } else {
-            self.cached_p50_short = f64::INFINITY;
-            self.cached_p90_short = f64::INFINITY;
-            self.cached_p99_short = f64::INFINITY;
-        }
+        [self.cached_p50_short, self.cached_p90_short, self.cached_p99_short] =
+            self.rtts_short.percentiles([50.0, 90.0, 99.0]);
 
         self.cached_loss_short = if self.total_sent_short > 0 {
             100.0 * (self.total_sent_short - self.total_recv_short) as f64 / self.total_sent_short as f64
@@ -225,6 +502,9 @@ impl AggregatorState {
             0.0
         };
 
+        self.cached_loss_pattern_short =
+            LossPattern::from_outcomes(self.circular_buffer_short.iter().map(|r| r.success));
+
         self.dirty_short = false;
     }
 
@@ -253,8 +533,7 @@ impl AggregatorState {
     #[must_use]
     pub fn recent_failure_count(&self, last_n: usize) -> usize {
         self.circular_buffer_short
-            .as_slice()
-            .iter()
+            .iter_recent()
             .take(last_n)
             .filter(|record| !record.success)
             .count()
@@ -263,15 +542,7 @@ impl AggregatorState {
     /// Get average RTT for short window
     #[must_use]
     pub fn avg_rtt_short(&self) -> f64 {
-        let rtts: Vec<f64> = self.circular_buffer_short.iter()
-            .filter_map(|r| r.rtt_ms)
-            .collect();
-        
-        if rtts.is_empty() {
-            0.0
-        } else {
-            rtts.iter().sum::<f64>() / rtts.len() as f64
-        }
+        self.rtts_short.mean().unwrap_or(0.0)
     }
 
     /// Check if we have enough data for reliable metrics
@@ -287,17 +558,7 @@ impl AggregatorState {
             return HealthStatus::Unknown;
         }
 
-        let loss = self.cached_loss_short;
-        let avg_rtt = self.avg_rtt_short();
-        let jitter = self.ewma_jitter_ms;
-
-        match (loss, avg_rtt, jitter) {
-            (l, r, j) if l <= 1.0 && r <= 50.0 && j <= 10.0 => HealthStatus::Excellent,
-            (l, r, j) if l <= 3.0 && r <= 100.0 && j <= 25.0 => HealthStatus::Good,
-            (l, r, j) if l <= 5.0 && r <= 200.0 && j <= 50.0 => HealthStatus::Fair,
-            (l, _, _) if l >= 10.0 => HealthStatus::Critical,
-            _ => HealthStatus::Poor,
-        }
+        HealthStatus::from_metrics(self.cached_loss_short, self.avg_rtt_short(), self.ewma_jitter_ms)
     }
 }
 
@@ -307,6 +568,8 @@ pub struct AggregatorStateBuilder {
     endpoint_id: String,
     w_short: usize,
     w_long: usize,
+    score_history: usize,
+    jitter_algorithm: JitterAlgorithm,
 }
 
 impl AggregatorStateBuilder {
@@ -317,6 +580,8 @@ impl AggregatorStateBuilder {
             endpoint_id,
             w_short: 100,
             w_long: 1000,
+            score_history: DEFAULT_SCORE_HISTORY,
+            jitter_algorithm: JitterAlgorithm::ConsecutiveDiff,
         }
     }
 
@@ -334,15 +599,33 @@ impl AggregatorStateBuilder {
         self
     }
 
+    /// Set how many scores are kept
+    #[must_use]
+    pub const fn score_history(mut self, size: usize) -> Self {
+        self.score_history = size;
+        self
+    }
+
+    /// Set the jitter algorithm
+    #[must_use]
+    pub const fn jitter_algorithm(mut self, algorithm: JitterAlgorithm) -> Self {
+        self.jitter_algorithm = algorithm;
+        self
+    }
+
     /// Build the AggregatorState
     #[must_use]
     pub fn build(self) -> AggregatorState {
-        AggregatorState::new(self.endpoint_id, self.w_short, self.w_long)
+        let mut state = AggregatorState::new(self.endpoint_id, self.w_short, self.w_long);
+        state.jitter_algorithm = self.jitter_algorithm;
+        state.score_history = RingBuffer::new(self.score_history);
+        state
     }
 }
 
 /// Health status enumeration
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum HealthStatus {
     Unknown,   // Health status is unknown due to insufficient data
     Excellent, // Excellent health with optimal performance
@@ -353,6 +636,31 @@ pub enum HealthStatus {
 }
 
 impl HealthStatus {
+    /// Classify packet loss in percent, average round-trip time and jitter
+    #[must_use]
+    pub fn from_metrics(loss_percent: f64, avg_rtt_ms: f64, jitter_ms: f64) -> Self {
+        match (loss_percent, avg_rtt_ms, jitter_ms) {
+            (l, r, j) if l <= 1.0 && r <= 50.0 && j <= 10.0 => Self::Excellent,
+            (l, r, j) if l <= 3.0 && r <= 100.0 && j <= 25.0 => Self::Good,
+            (l, r, j) if l <= 5.0 && r <= 200.0 && j <= 50.0 => Self::Fair,
+            (l, _, _) if l >= 10.0 => Self::Critical,
+            _ => Self::Poor,
+        }
+    }
+
+    /// Name for tables and CSV
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Unknown => "Unknown",
+            Self::Excellent => "Excellent",
+            Self::Good => "Good",
+            Self::Fair => "Fair",
+            Self::Poor => "Poor",
+            Self::Critical => "Critical",
+        }
+    }
+
     /// Get color for display
     #[must_use]
     pub const fn color(self) -> &'static str {
@@ -378,6 +686,25 @@ impl HealthStatus {
             Self::Critical => "💀",
         }
     }
+
+    /// Plain-ASCII stand-in for [`Self::emoji`]
+    #[must_use]
+    pub const fn ascii_marker(self) -> &'static str {
+        match self {
+            Self::Unknown => "[?]",
+            Self::Excellent => "[++]",
+            Self::Good => "[+]",
+            Self::Fair => "[~]",
+            Self::Poor => "[-]",
+            Self::Critical => "[!!]",
+        }
+    }
+}
+
+impl std::fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
 }
 
 #[cfg(test)]
@@ -394,10 +721,62 @@ mod tests {
         buffer.push(3);
         assert_eq!(buffer.len(), 3);
         
-        buffer.push(4); // Should remove 1
+        assert_eq!(buffer.push(4), Some(1));
         assert_eq!(buffer.len(), 3);
         assert_eq!(buffer.latest(), Some(&4));
         assert_eq!(buffer.oldest(), Some(&2));
+        assert!(buffer.iter_recent().eq([4, 3, 2].iter()));
+        assert_eq!(buffer.make_contiguous(), &[2, 3, 4]);
+    }
+
+    #[test]
+    fn test_receive_counters_follow_evictions() {
+        let mut state = AggregatorState::new("test".to_string(), 4, 6);
+        for i in 0..20 {
+            let record = if i % 3 == 0 {
+                ProbeRecord::failure("test".to_string(), None)
+            } else {
+                ProbeRecord::success("test".to_string(), 20.0)
+            };
+            state.add_record(record, 0.1);
+
+            let received = |buffer: &RingBuffer<ProbeRecord>| buffer.iter().filter(|r| r.success).count();
+            assert_eq!(state.total_recv_short, received(&state.circular_buffer_short));
+            assert_eq!(state.total_recv_long, received(&state.circular_buffer_long));
+        }
+        assert_eq!(state.total_sent_short, 4);
+        assert_eq!(state.total_sent_long, 6);
+        let rtts: Vec<f64> = state.circular_buffer_short.iter().filter_map(|r| r.rtt_ms).collect();
+        assert!(state.rtts_short.iter().eq(rtts.iter()));
+
+        // Narrower windows keep the newest probes and stay consistent
+        let newest = state.circular_buffer_long.latest().cloned();
+        state.resize_windows(2, 3);
+        assert_eq!((state.total_sent_short, state.total_sent_long), (2, 3));
+        assert_eq!(state.circular_buffer_long.latest().cloned().map(|r| r.timestamp), newest.map(|r| r.timestamp));
+        assert_eq!(state.total_recv_long, state.circular_buffer_long.iter().filter(|r| r.success).count());
+        let rtts: Vec<f64> = state.circular_buffer_short.iter().filter_map(|r| r.rtt_ms).collect();
+        assert!(state.rtts_short.iter().eq(rtts.iter()));
+        assert!(state.buffer_bytes() <= AggregatorState::full_buffer_bytes(2, 3, DEFAULT_SCORE_HISTORY));
+    }
+
+    #[test]
+    fn test_numeric_summaries() {
+        let mut buffer = RingBuffer::new(4);
+        assert_eq!(buffer.mean(), None);
+        assert_eq!(buffer.percentile(50.0), f64::INFINITY);
+
+        for value in [9.0, 1.0, 4.0, 2.0, 3.0] {
+            buffer.push(value);
+        }
+        assert_eq!(buffer.sum(), 10.0);
+        assert_eq!(buffer.mean(), Some(2.5));
+        assert_eq!((buffer.min(), buffer.max()), (Some(1.0), Some(4.0)));
+
+        let values: Vec<f64> = buffer.iter().copied().collect();
+        assert_eq!(buffer.percentiles([50.0, 90.0]), [percentile(&values, 50.0), percentile(&values, 90.0)]);
+        // Order is left untouched
+        assert!(buffer.iter().eq([1.0, 4.0, 2.0, 3.0].iter()));
     }
 
     #[test]
@@ -426,4 +805,76 @@ mod tests {
         
         assert_eq!(state.health_status(), HealthStatus::Excellent);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_streaming_jitter_algorithms() {
+        let rtts = [10.0, 14.0, 10.0, 14.0];
+        let stream = |algorithm| {
+            let mut state = AggregatorState::builder("test".to_string())
+                .jitter_algorithm(algorithm)
+                .build();
+            for rtt in rtts {
+                state.add_record(ProbeRecord::success("test".to_string(), rtt), 0.5);
+            }
+            state.ewma_jitter_ms
+        };
+
+        // RFC 3550 ignores the EWMA alpha and matches the batch computation
+        let rfc3550 = stream(JitterAlgorithm::Rfc3550);
+        assert!((rfc3550 - JitterAlgorithm::Rfc3550.compute(&rtts)).abs() < 1e-9);
+        assert!(stream(JitterAlgorithm::ConsecutiveDiff) > rfc3550);
+        assert!(stream(JitterAlgorithm::StdDev) > 0.0);
+    }
+
+    #[test]
+    fn test_state_round_trips_through_json() {
+        let mut state = AggregatorState::builder("test".to_string())
+            .short_window(5)
+            .long_window(20)
+            .jitter_algorithm(JitterAlgorithm::StdDev)
+            .build();
+        for i in 0..12 {
+            let record = if i % 4 == 0 {
+                ProbeRecord::failure("test".to_string(), None)
+            } else {
+                ProbeRecord::success("test".to_string(), 20.0 + f64::from(i))
+            };
+            state.add_record(record, 0.2);
+        }
+        state.record_score(Utc::now(), 87.5);
+
+        let json = serde_json::to_string(&state).unwrap();
+        let mut restored: AggregatorState = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.circular_buffer_short.len(), 5);
+        assert_eq!(restored.total_recv_long, state.total_recv_long);
+        assert_eq!(restored.score_history(), state.score_history());
+
+        // Hidden smoothing state carries over, so both continue alike
+        let next = ProbeRecord::success("test".to_string(), 45.0);
+        state.add_record(next.clone(), 0.2);
+        restored.add_record(next, 0.2);
+        assert!((restored.ewma_jitter_ms - state.ewma_jitter_ms).abs() < 1e-9);
+        assert!((restored.cached_p90_short - state.cached_p90_short).abs() < 1e-9);
+        assert_eq!(restored.health_status(), state.health_status());
+
+        // Without successful probes the percentiles are infinite and stored as null
+        let mut down = AggregatorState::new("down".to_string(), 5, 20);
+        down.add_record(ProbeRecord::failure("down".to_string(), None), 0.2);
+        let value = serde_json::to_value(&down).unwrap();
+        assert!(value["cached_p50_short"].is_null());
+        let restored: AggregatorState = serde_json::from_value(value).unwrap();
+        assert_eq!(restored.cached_p50_short, f64::INFINITY);
+    }
+
+    #[test]
+    fn test_ring_buffer_and_health_serde() {
+        let json = r#"{"capacity":2,"items":[1,2,3]}"#;
+        let buffer: RingBuffer<u32> = serde_json::from_str(json).unwrap();
+        assert!(buffer.iter().eq([2, 3].iter()));
+        assert_eq!(serde_json::to_string(&buffer).unwrap(), r#"{"capacity":2,"items":[2,3]}"#);
+
+        assert_eq!(serde_json::to_string(&HealthStatus::Excellent).unwrap(), r#""excellent""#);
+        let status: HealthStatus = serde_json::from_str(r#""critical""#).unwrap();
+        assert_eq!(status, HealthStatus::Critical);
+    }
+}
@@ -0,0 +1,119 @@
+//! Operational notes attached to regions
+//!
+//! Notes such as "behind Cloudflare" or "deprecated Q3" come from the
+//! `note` metadata of a region in the data file and from a user-supplied
+//! TOML notes file. They are copied onto each region's results, so they show
+//! up wherever those results are reported or exported.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::region::Region;
+use crate::error::{CloudPingError, Result};
+
+/// Region metadata key holding a note from the data file
+pub const NOTE_METADATA_KEY: &str = "note";
+
+/// One note or several for a region
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+enum NoteEntry {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl NoteEntry {
+    fn notes(&self) -> &[String] {
+        match self {
+            Self::One(note) => std::slice::from_ref(note),
+            Self::Many(notes) => notes,
+        }
+    }
+}
+
+/// Notes per region, keyed by region name or URL
+///
+/// ```toml
+/// "Frankfurt" = "behind Cloudflare"
+/// "https://ap-east-1.example.com" = ["deprecated Q3", "no IPv6"]
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct RegionNotes(BTreeMap<String, NoteEntry>);
+
+impl RegionNotes {
+    /// Read a notes file
+    ///
+    /// # Errors
+    /// Returns an error when the file cannot be read or is not a table of notes
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(&content).map_err(|e| {
+            CloudPingError::data_loading(format!("Invalid notes file {}: {e}", path.display()))
+        })
+    }
+
+    /// Parse notes from TOML
+    ///
+    /// # Errors
+    /// Returns the parse error when `content` is not a table of notes
+    pub fn parse(content: &str) -> std::result::Result<Self, toml::de::Error> {
+        toml::from_str(content)
+    }
+
+    /// Notes of `region`: its metadata note first, then file entries for its name or URL
+    ///
+    /// Names are compared case-insensitively; blank notes are skipped.
+    #[must_use]
+    pub fn for_region(&self, region: &Region) -> Vec<String> {
+        let from_file = self
+            .0
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(&region.name) || **key == region.url)
+            .flat_map(|(_, entry)| entry.notes());
+        let mut notes: Vec<String> = Vec::new();
+        for note in region.metadata.get(NOTE_METADATA_KEY).into_iter().chain(from_file) {
+            let note = note.trim();
+            if !note.is_empty() && !notes.iter().any(|n| n == note) {
+                notes.push(note.to_string());
+            }
+        }
+        notes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_notes() {
+        let notes = RegionNotes::parse(
+            r#"
+            frankfurt = "behind Cloudflare"
+            "https://eu.example.com" = ["deprecated Q3", "behind Cloudflare", " "]
+            "Paris" = "unrelated"
+            "#,
+        )
+        .unwrap();
+        let mut region = Region::builder(
+            "Frankfurt".to_string(),
+            "https://eu.example.com".to_string(),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+        region
+            .metadata
+            .insert(NOTE_METADATA_KEY.to_string(), "IPv4 only".to_string());
+
+        assert_eq!(
+            notes.for_region(&region),
+            ["IPv4 only", "behind Cloudflare", "deprecated Q3"]
+        );
+        assert_eq!(RegionNotes::default().for_region(&region), ["IPv4 only"]);
+        assert!(RegionNotes::parse("Frankfurt = 3").is_err());
+    }
+}
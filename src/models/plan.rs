@@ -0,0 +1,225 @@
+//! Dry-run estimates of a benchmark
+//!
+//! Lets users on metered or slow connections see how many requests a run will
+//! send, how long it should take and roughly how much data it uses before
+//! anything goes on the wire.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::region::Region;
+use crate::config::AppConfig;
+
+/// Assumed duration of a successful request, used for the expected duration
+const TYPICAL_REQUEST_MS: u64 = 100;
+
+/// Pause between consecutive pings to the same region
+const PING_GAP_MS: u64 = 10;
+
+/// Request line, headers, response headers and framing of one request
+const REQUEST_BYTES: u64 = 1_500;
+
+/// TCP and TLS handshake, paid once per host thanks to connection pooling
+const HANDSHAKE_BYTES: u64 = 6_000;
+
+/// What a benchmark run would do, without running it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BenchmarkPlan {
+    /// Regions that would be tested
+    pub region_count: usize,
+    /// Distinct hosts behind those regions
+    pub host_count: usize,
+    /// Pings per region
+    pub ping_count: usize,
+    /// Requests sent when no ping needs a retry
+    pub total_requests: u64,
+    /// Requests sent when every ping exhausts its retries
+    pub max_requests: u64,
+    /// Duration assuming fast, successful requests
+    #[serde(with = "humantime_serde")]
+    pub expected_duration: Duration,
+    /// Duration if every request times out after all retries
+    #[serde(with = "humantime_serde")]
+    pub worst_case_duration: Duration,
+    /// Approximate bytes sent and received when no ping needs a retry
+    pub estimated_bytes: u64,
+}
+
+impl BenchmarkPlan {
+    /// Estimate a run pinging each of `regions` `ping_count` times under `config`
+    ///
+    /// With `priority_scaling` on, each region's pings and retries are scaled by its priority.
+    #[must_use]
+    pub fn estimate(regions: &[Region], ping_count: usize, config: &AppConfig) -> Self {
+        let mut requests_per_host: HashMap<String, u64> = HashMap::new();
+        let (mut total_requests, mut max_requests) = (0, 0);
+        let (mut typical_region_ms, mut worst_region_ms) = (0, 0);
+        let millis = |duration: Duration| u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+        let attempt_ms = millis(config.attempt_timeout());
+        let ping_budget_ms = config.deadlines.ping.map(millis);
+        let region_budget_ms = config.deadlines.region.map(millis);
+        for region in regions {
+            let (region_pings, retries) = if config.priority_scaling {
+                (
+                    region.scaled_by_priority(ping_count),
+                    region.scaled_by_priority(config.retry_attempts),
+                )
+            } else {
+                (ping_count, config.retry_attempts)
+            };
+            let pings = u64::try_from(region_pings).unwrap_or(u64::MAX);
+            let attempts = u64::try_from(retries).unwrap_or(u64::MAX) + 1;
+            let host = url::Url::parse(&region.url)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+                .unwrap_or_else(|| region.url.clone());
+            *requests_per_host.entry(host).or_default() += pings;
+            total_requests += pings;
+            max_requests += pings.saturating_mul(attempts);
+
+            // Pings within a region are sequential; the slowest region bounds each wave
+            typical_region_ms = typical_region_ms.max(pings * (TYPICAL_REQUEST_MS + PING_GAP_MS));
+            // Budgets cut a ping's retries and a region's pings short
+            let mut worst_ping_ms =
+                attempts * attempt_ms + (attempts - 1) * config.retry_delay_ms;
+            if let Some(budget) = ping_budget_ms {
+                worst_ping_ms = worst_ping_ms.min(budget);
+            }
+            let mut region_ms = pings * (worst_ping_ms + PING_GAP_MS);
+            if let Some(budget) = region_budget_ms {
+                region_ms = region_ms.min(budget);
+            }
+            worst_region_ms = worst_region_ms.max(region_ms);
+        }
+
+        // Regions run in waves of `max_threads`
+        let region_count = regions.len();
+        let waves =
+            u64::try_from(region_count.div_ceil(config.max_threads.max(1))).unwrap_or(u64::MAX);
+
+        // The per-host rate limit can stretch a run beyond the concurrency estimate
+        let rate_limited_ms = requests_per_host
+            .values()
+            .map(|&requests| {
+                Self::rate_limited_ms(requests, config.max_requests_per_host_per_second)
+            })
+            .max()
+            .unwrap_or(0);
+
+        Self {
+            region_count,
+            host_count: requests_per_host.len(),
+            ping_count,
+            total_requests,
+            max_requests,
+            expected_duration: Duration::from_millis(
+                (waves * typical_region_ms).max(rate_limited_ms),
+            ),
+            worst_case_duration: Duration::from_millis(
+                (waves * worst_region_ms).max(rate_limited_ms),
+            ),
+            estimated_bytes: total_requests * REQUEST_BYTES
+                + u64::try_from(requests_per_host.len()).unwrap_or(u64::MAX) * HANDSHAKE_BYTES,
+        }
+    }
+
+    /// Time the token bucket needs to release `requests` after its initial burst
+    fn rate_limited_ms(requests: u64, requests_per_second: f64) -> u64 {
+        if requests_per_second <= 0.0 {
+            return 0;
+        }
+        let burst = requests_per_second.max(1.0);
+        let queued = f64::from(u32::try_from(requests).unwrap_or(u32::MAX)) - burst;
+        if queued <= 0.0 {
+            return 0;
+        }
+        Duration::from_secs_f64(queued / requests_per_second)
+            .as_millis()
+            .try_into()
+            .unwrap_or(u64::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(url: &str) -> Region {
+        serde_json::from_str(&format!(r#"{{"name": "{url}", "url": "{url}"}}"#)).unwrap()
+    }
+
+    #[test]
+    fn test_request_counts_and_data() {
+        let config = AppConfig {
+            retry_attempts: 2,
+            max_threads: 10,
+            ..Default::default()
+        };
+        let regions = [
+            region("https://a.example.com/ping"),
+            region("https://a.example.com/other"),
+            region("https://b.example.com/ping"),
+        ];
+
+        let plan = BenchmarkPlan::estimate(&regions, 4, &config);
+        assert_eq!(plan.region_count, 3);
+        assert_eq!(plan.host_count, 2);
+        assert_eq!(plan.total_requests, 12);
+        assert_eq!(plan.max_requests, 36);
+        assert_eq!(
+            plan.estimated_bytes,
+            12 * REQUEST_BYTES + 2 * HANDSHAKE_BYTES
+        );
+        // One wave of four sequential pings
+        assert_eq!(plan.expected_duration, Duration::from_millis(440));
+        assert!(plan.worst_case_duration > plan.expected_duration);
+    }
+
+    #[test]
+    fn test_concurrency_and_rate_limit_stretch_duration() {
+        let config = AppConfig {
+            max_threads: 1,
+            max_requests_per_host_per_second: 0.0,
+            ..Default::default()
+        };
+        let regions = [
+            region("https://a.example.com"),
+            region("https://b.example.com"),
+        ];
+        // Two waves when only one region runs at a time
+        let plan = BenchmarkPlan::estimate(&regions, 1, &config);
+        assert_eq!(plan.expected_duration, Duration::from_millis(220));
+
+        // Two regions on one host send 100 requests at 10/s: a burst of 10, then 9 seconds
+        let config = AppConfig {
+            max_requests_per_host_per_second: 10.0,
+            ..Default::default()
+        };
+        let regions = [
+            region("https://a.example.com/x"),
+            region("https://a.example.com/y"),
+        ];
+        let plan = BenchmarkPlan::estimate(&regions, 50, &config);
+        assert_eq!(plan.expected_duration, Duration::from_secs(9));
+    }
+
+    #[test]
+    fn test_priority_scaling_adds_requests() {
+        let config = AppConfig {
+            retry_attempts: 1,
+            priority_scaling: true,
+            ..Default::default()
+        };
+        let mut important = region("https://a.example.com");
+        important.priority = 2.0;
+        let regions = [important, region("https://b.example.com")];
+
+        let plan = BenchmarkPlan::estimate(&regions, 4, &config);
+        assert_eq!(plan.total_requests, 8 + 4);
+        assert_eq!(plan.max_requests, 8 * 3 + 4 * 2);
+        // Both regions share one wave, which lasts as long as the eight pings
+        assert_eq!(plan.expected_duration, Duration::from_millis(880));
+    }
+}
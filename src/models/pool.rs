@@ -0,0 +1,155 @@
+//! Connection pool reuse
+//!
+//! Pings are sent through a pooled HTTP client, so after the first ping to
+//! an endpoint most pings reuse its open connection and measure the
+//! steady-state round trip. A ping on a new connection also pays for the TCP
+//! and TLS handshakes. [`ConnectionReuse`] counts both kinds per test and is
+//! stored in `PingStats::metadata`, so a result dominated by new connections
+//! can be told apart from one measuring the round trip alone.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Metadata key for the number of pings sent on a new connection
+pub const CONNECTIONS_NEW_METADATA_KEY: &str = "connections_new";
+
+/// Metadata key for the number of pings sent on a pooled connection
+pub const CONNECTIONS_REUSED_METADATA_KEY: &str = "connections_reused";
+
+/// Metadata key for the number of new connections opened after the first
+pub const RECONNECTS_METADATA_KEY: &str = "reconnects";
+
+/// Key `reconnects` was stored under before, still read from saved results
+const LEGACY_POOL_EVICTIONS_METADATA_KEY: &str = "pool_evictions";
+
+/// Metadata key for the average latency of pings on a new connection
+pub const NEW_CONNECTION_AVG_MS_METADATA_KEY: &str = "new_connection_avg_ms";
+
+/// Metadata key for the average latency of pings on a pooled connection
+pub const REUSED_CONNECTION_AVG_MS_METADATA_KEY: &str = "reused_connection_avg_ms";
+
+/// Whether a request opened a connection or used a pooled one
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionUse {
+    /// The request opened a new connection
+    New,
+    /// The request was sent on a connection from the pool
+    Reused,
+}
+
+/// Connection reuse over the pings of one test
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ConnectionReuse {
+    /// Pings sent on a new connection
+    pub new: usize,
+    /// Pings sent on a pooled connection
+    pub reused: usize,
+    /// New connections opened after an earlier ping of the test already had
+    /// one, whether the pool evicted the connection, the server closed it or
+    /// it timed out, which a client cannot tell apart
+    pub reconnects: usize,
+    /// Average latency of pings on a new connection in milliseconds
+    pub new_avg_ms: Option<f64>,
+    /// Average latency of pings on a pooled connection in milliseconds
+    pub reused_avg_ms: Option<f64>,
+}
+
+impl ConnectionReuse {
+    /// Count pings given as `(connection use, latency in ms)` in the order they were sent
+    #[must_use]
+    pub fn tally(pings: impl IntoIterator<Item = (ConnectionUse, f64)>) -> Self {
+        let mut reuse = Self::default();
+        let (mut new_ms, mut reused_ms) = (Vec::new(), Vec::new());
+        for (connection, latency_ms) in pings {
+            match connection {
+                ConnectionUse::New => {
+                    reuse.reconnects += usize::from(reuse.new + reuse.reused > 0);
+                    reuse.new += 1;
+                    new_ms.push(latency_ms);
+                }
+                ConnectionUse::Reused => {
+                    reuse.reused += 1;
+                    reused_ms.push(latency_ms);
+                }
+            }
+        }
+        reuse.new_avg_ms = (!new_ms.is_empty()).then(|| statistical::mean(&new_ms));
+        reuse.reused_avg_ms = (!reused_ms.is_empty()).then(|| statistical::mean(&reused_ms));
+        reuse
+    }
+
+    /// Share of pings sent on a pooled connection, absent without pings
+    #[must_use]
+    pub fn reuse_ratio(&self) -> Option<f64> {
+        let total = self.new + self.reused;
+        #[allow(clippy::cast_precision_loss)] // ping counts are far below 2^52
+        let ratio = (total > 0).then(|| self.reused as f64 / total as f64);
+        ratio
+    }
+
+    /// Store the counts in `metadata`; averages are left out when there is no ping of their kind
+    pub fn write_metadata(&self, metadata: &mut HashMap<String, String>) {
+        metadata.insert(CONNECTIONS_NEW_METADATA_KEY.to_string(), self.new.to_string());
+        metadata.insert(CONNECTIONS_REUSED_METADATA_KEY.to_string(), self.reused.to_string());
+        metadata.insert(RECONNECTS_METADATA_KEY.to_string(), self.reconnects.to_string());
+        for (key, avg_ms) in [
+            (NEW_CONNECTION_AVG_MS_METADATA_KEY, self.new_avg_ms),
+            (REUSED_CONNECTION_AVG_MS_METADATA_KEY, self.reused_avg_ms),
+        ] {
+            if let Some(avg_ms) = avg_ms {
+                metadata.insert(key.to_string(), format!("{avg_ms:.1}"));
+            }
+        }
+    }
+
+    /// Read counts stored by [`Self::write_metadata`], absent when they are missing
+    #[must_use]
+    pub fn from_metadata(metadata: &HashMap<String, String>) -> Option<Self> {
+        let count = |key| metadata.get(key)?.parse::<usize>().ok();
+        let avg_ms = |key| metadata.get(key)?.parse::<f64>().ok();
+        Some(Self {
+            new: count(CONNECTIONS_NEW_METADATA_KEY)?,
+            reused: count(CONNECTIONS_REUSED_METADATA_KEY)?,
+            reconnects: count(RECONNECTS_METADATA_KEY)
+                .or_else(|| count(LEGACY_POOL_EVICTIONS_METADATA_KEY))
+                .unwrap_or(0),
+            new_avg_ms: avg_ms(NEW_CONNECTION_AVG_MS_METADATA_KEY),
+            reused_avg_ms: avg_ms(REUSED_CONNECTION_AVG_MS_METADATA_KEY),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_reuse() {
+        let reuse = ConnectionReuse::tally([
+            (ConnectionUse::New, 120.0),
+            (ConnectionUse::Reused, 20.0),
+            (ConnectionUse::Reused, 24.0),
+            (ConnectionUse::New, 110.0),
+        ]);
+        assert_eq!((reuse.new, reuse.reused, reuse.reconnects), (2, 2, 1));
+        assert_eq!(reuse.new_avg_ms, Some(115.0));
+        assert_eq!(reuse.reused_avg_ms, Some(22.0));
+        assert_eq!(reuse.reuse_ratio(), Some(0.5));
+
+        let mut metadata = HashMap::new();
+        reuse.write_metadata(&mut metadata);
+        assert_eq!(metadata[RECONNECTS_METADATA_KEY], "1");
+        assert_eq!(ConnectionReuse::from_metadata(&metadata), Some(reuse));
+        let reconnects = metadata.remove(RECONNECTS_METADATA_KEY).unwrap();
+        metadata.insert(LEGACY_POOL_EVICTIONS_METADATA_KEY.to_string(), reconnects);
+        assert_eq!(ConnectionReuse::from_metadata(&metadata).unwrap().reconnects, 1);
+        assert_eq!(ConnectionReuse::from_metadata(&HashMap::new()), None);
+
+        // A warm pool from an earlier test makes the first new connection a reconnect too
+        let warm = ConnectionReuse::tally([(ConnectionUse::Reused, 20.0), (ConnectionUse::New, 90.0)]);
+        assert_eq!(warm.reconnects, 1);
+        assert_eq!(ConnectionReuse::tally([]).reuse_ratio(), None);
+    }
+}
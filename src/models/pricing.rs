@@ -0,0 +1,216 @@
+//! Region pricing and price-performance
+//!
+//! Cost comes from the `egress_usd_per_gb` and `price_index` metadata of a
+//! region in the data file, from a pricing document fetched from
+//! `pricing_url`, and from a local `pricing_file`, each overriding the one
+//! before. [`RegionCost::price_performance`] divides a score by the region's
+//! cost relative to a typical region, so cheaper regions with the same score
+//! rank higher when sorting by value.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::region::Region;
+use crate::error::{CloudPingError, Result};
+
+/// Region metadata key holding the egress price in US dollars per GB
+pub const EGRESS_METADATA_KEY: &str = "egress_usd_per_gb";
+
+/// Region metadata key holding the instance price index, 1.0 for a typical region
+pub const PRICE_INDEX_METADATA_KEY: &str = "price_index";
+
+/// Egress price of a typical region, counted as a relative cost of 1
+pub const REFERENCE_EGRESS_USD_PER_GB: f64 = 0.09;
+
+/// What a region costs to run in and to serve from
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct RegionCost {
+    /// Egress price in US dollars per GB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub egress_usd_per_gb: Option<f64>,
+    /// Instance price relative to a typical region, e.g. 1.2 for 20% dearer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_index: Option<f64>,
+}
+
+impl RegionCost {
+    /// Cost read from region metadata; unparsable values are ignored
+    #[must_use]
+    pub fn from_metadata(metadata: &HashMap<String, String>) -> Self {
+        let value = |key: &str| metadata.get(key).and_then(|v| v.trim().parse::<f64>().ok());
+        Self {
+            egress_usd_per_gb: value(EGRESS_METADATA_KEY),
+            price_index: value(PRICE_INDEX_METADATA_KEY),
+        }
+    }
+
+    /// Whether no price is known
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.egress_usd_per_gb.is_none() && self.price_index.is_none()
+    }
+
+    /// Replace prices with those set in `other`
+    pub fn overlay(&mut self, other: &Self) {
+        self.egress_usd_per_gb = other.egress_usd_per_gb.or(self.egress_usd_per_gb);
+        self.price_index = other.price_index.or(self.price_index);
+    }
+
+    /// Cost relative to a typical region: the mean of the price index and the
+    /// egress price over [`REFERENCE_EGRESS_USD_PER_GB`], whichever are known
+    #[must_use]
+    pub fn relative_cost(&self) -> Option<f64> {
+        let parts: Vec<f64> = [
+            self.price_index,
+            self.egress_usd_per_gb.map(|egress| egress / REFERENCE_EGRESS_USD_PER_GB),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        (!parts.is_empty()).then(|| statistical::mean(&parts))
+    }
+
+    /// `score` per unit of relative cost; absent without a positive cost
+    #[must_use]
+    pub fn price_performance(&self, score: f64) -> Option<f64> {
+        self.relative_cost()
+            .filter(|cost| *cost > 0.0)
+            .map(|cost| score / cost)
+    }
+
+    /// # Errors
+    /// Returns a validation error when a price is negative or not a number
+    fn validate(&self, key: &str) -> Result<()> {
+        let valid = |price: Option<f64>| price.map_or(true, |p| p.is_finite() && p >= 0.0);
+        if valid(self.egress_usd_per_gb) && valid(self.price_index) {
+            Ok(())
+        } else {
+            Err(CloudPingError::validation(
+                format!("pricing.{key}"),
+                "prices must not be negative",
+            ))
+        }
+    }
+}
+
+/// Prices per region, keyed by region name, `provider/name` or URL
+///
+/// ```toml
+/// "Frankfurt" = { egress_usd_per_gb = 0.09, price_index = 1.1 }
+/// "AWS/eu-central-1 (Frankfurt)" = { price_index = 1.2 }
+/// "https://ap-east-1.example.com" = { price_index = 1.3 }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(transparent)]
+pub struct RegionPricing(BTreeMap<String, RegionCost>);
+
+impl RegionPricing {
+    /// Read a TOML pricing file
+    ///
+    /// # Errors
+    /// Returns an error when the file cannot be read, is not a table of
+    /// prices or has a negative price
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let pricing: Self = toml::from_str(&content).map_err(|e| {
+            CloudPingError::data_loading(format!("Invalid pricing file {}: {e}", path.display()))
+        })?;
+        pricing.validate()?;
+        Ok(pricing)
+    }
+
+    /// # Errors
+    /// Returns a validation error naming the first region with a negative price
+    pub fn validate(&self) -> Result<()> {
+        self.0.iter().try_for_each(|(key, cost)| cost.validate(key))
+    }
+
+    /// Add the prices of `other`, which win over prices already set under
+    /// the same key in any case
+    pub fn merge(&mut self, other: Self) {
+        for (key, cost) in other.0 {
+            let existing = self.0.keys().find(|existing| existing.eq_ignore_ascii_case(&key)).cloned();
+            let mut merged = existing.and_then(|existing| self.0.remove(&existing)).unwrap_or_default();
+            merged.overlay(&cost);
+            self.0.insert(key, merged);
+        }
+    }
+
+    /// Cost of `region`: its metadata overridden by the entry for its name,
+    /// then by the entry for `provider/name`, then by the entry for its URL
+    ///
+    /// Names are compared case-insensitively. Providers often share region
+    /// names, so a `provider/name` entry prices one provider's region alone.
+    #[must_use]
+    pub fn for_region(&self, region: &Region) -> RegionCost {
+        let mut cost = RegionCost::from_metadata(&region.metadata);
+        let mut keys = vec![region.name.clone()];
+        if !region.provider.is_empty() {
+            keys.push(format!("{}/{}", region.provider, region.name));
+        }
+        for wanted in &keys {
+            for (_, entry) in self.0.iter().filter(|(key, _)| key.eq_ignore_ascii_case(wanted)) {
+                cost.overlay(entry);
+            }
+        }
+        if let Some(entry) = self.0.get(&region.url) {
+            cost.overlay(entry);
+        }
+        cost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_pricing() {
+        let mut pricing: RegionPricing = toml::from_str(
+            r#"
+            frankfurt = { egress_usd_per_gb = 0.18 }
+            "https://eu.example.com" = { price_index = 0.5 }
+            "#,
+        )
+        .unwrap();
+        pricing.merge(toml::from_str(r#""Frankfurt" = { price_index = 1.5 }"#).unwrap());
+        let mut region = Region::builder(
+            "Frankfurt".to_string(),
+            "https://eu.example.com".to_string(),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+        region
+            .metadata
+            .insert(EGRESS_METADATA_KEY.to_string(), "0.05".to_string());
+
+        // Name entries override the metadata and the URL entry overrides both
+        let cost = pricing.for_region(&region);
+        assert_eq!(cost.egress_usd_per_gb, Some(0.18));
+        assert_eq!(cost.price_index, Some(0.5));
+        assert!((cost.relative_cost().unwrap() - 1.25).abs() < 1e-9);
+        assert!((cost.price_performance(70.0).unwrap() - 56.0).abs() < 1e-9);
+
+        pricing.merge(toml::from_str(r#""FRANKFURT" = { egress_usd_per_gb = 0.16 }"#).unwrap());
+        assert_eq!(pricing.for_region(&region).egress_usd_per_gb, Some(0.16));
+        assert_eq!(pricing.for_region(&region).price_index, Some(0.5));
+
+        // A provider entry prices that provider's region only
+        pricing.merge(toml::from_str(r#""gcp/Frankfurt" = { egress_usd_per_gb = 0.12 }"#).unwrap());
+        assert_eq!(pricing.for_region(&region).egress_usd_per_gb, Some(0.16));
+        region.provider = "GCP".to_string();
+        assert_eq!(pricing.for_region(&region).egress_usd_per_gb, Some(0.12));
+
+        let bare = RegionCost::from_metadata(&HashMap::new());
+        assert!(bare.is_empty());
+        assert_eq!(bare.price_performance(70.0), None);
+        let free = RegionCost { price_index: Some(0.0), ..RegionCost::default() };
+        assert_eq!(free.price_performance(70.0), None);
+
+        let negative: RegionPricing = toml::from_str(r#"x = { price_index = -1.0 }"#).unwrap();
+        assert!(negative.validate().is_err());
+    }
+}
@@ -1,10 +1,15 @@
 //! Probe record and alert definitions
 
+use std::fmt::Write as _;
+
 use chrono::{DateTime, Utc};
 use crate::time_utils::TimeUtils;
 use crate::format_utils::FormatUtils;
 use serde::{Deserialize, Serialize};
 
+use super::alert_envelope::MetricsSnapshot;
+use super::utils::generate_uuid;
+
 /// Individual probe record from a single test
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ProbeRecord {
@@ -13,6 +18,26 @@ pub struct ProbeRecord {
     pub rtt_ms: Option<f64>,        // Round-trip time in milliseconds (None if probe failed)
     pub success: bool,              // Whether the probe was successful
     pub error_code: Option<String>, // Error code if the probe failed
+    /// Time to set up the connection the round trip was timed on, for probes
+    /// that time the two apart (the handshake of a WebSocket probe)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub setup_ms: Option<f64>,
+    /// Offset of the server's clock from the local one in milliseconds, for
+    /// NTP probes; positive when the local clock is behind
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_offset_ms: Option<f64>,
+    /// Status the server answered with, for HTTP probes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// Position among the records of the aggregator that took it in, rising
+    /// with every record whatever the wall clock does; 0 until an aggregator
+    /// adds it to a window
+    #[serde(default)]
+    pub sequence: u64,
+    /// Monotonic time the record was created at, in nanoseconds since the
+    /// process's monotonic clock started; 0 for records without one
+    #[serde(default)]
+    pub monotonic_ns: u64,
 }
 
 impl ProbeRecord {
@@ -24,6 +49,11 @@ impl ProbeRecord {
             rtt_ms,
             success,
             error_code: None,
+            setup_ms: None,
+            clock_offset_ms: None,
+            status: None,
+            sequence: 0,
+            monotonic_ns: TimeUtils::monotonic_ns(),
         }
     }
 
@@ -35,6 +65,11 @@ impl ProbeRecord {
             rtt_ms: None,
             success: false,
             error_code: Some(error),
+            setup_ms: None,
+            clock_offset_ms: None,
+            status: None,
+            sequence: 0,
+            monotonic_ns: TimeUtils::monotonic_ns(),
         }
     }
 
@@ -46,6 +81,11 @@ impl ProbeRecord {
             rtt_ms: Some(rtt_ms),
             success: true,
             error_code: None,
+            setup_ms: None,
+            clock_offset_ms: None,
+            status: None,
+            sequence: 0,
+            monotonic_ns: TimeUtils::monotonic_ns(),
         }
     }
 
@@ -57,6 +97,11 @@ impl ProbeRecord {
             rtt_ms: None,
             success: false,
             error_code: error,
+            setup_ms: None,
+            clock_offset_ms: None,
+            status: None,
+            sequence: 0,
+            monotonic_ns: TimeUtils::monotonic_ns(),
         }
     }
 
@@ -74,6 +119,29 @@ impl ProbeRecord {
     pub fn rtt_or_default(&self, default: f64) -> f64 {
         self.rtt_ms.unwrap_or(default)
     }
+
+    /// Time of this record on the timeline of `previous`, an earlier record
+    /// of the same endpoint
+    ///
+    /// That is its own timestamp unless the wall clock stepped back in
+    /// between. Then it is `previous`'s timestamp advanced by the monotonic
+    /// time between the two, or `previous`'s timestamp when that is unknown,
+    /// so records never go back in time and catch up with the wall clock
+    /// once it passes them again.
+    #[must_use]
+    pub fn timestamp_after(&self, previous: &Self) -> DateTime<Utc> {
+        if self.timestamp >= previous.timestamp {
+            return self.timestamp;
+        }
+        let same_run = previous.sequence > 0
+            && self.sequence > previous.sequence
+            && self.monotonic_ns >= previous.monotonic_ns;
+        let elapsed_ns = if same_run { self.monotonic_ns - previous.monotonic_ns } else { 0 };
+        previous
+            .timestamp
+            .checked_add_signed(chrono::Duration::nanoseconds(i64::try_from(elapsed_ns).unwrap_or(i64::MAX)))
+            .unwrap_or(previous.timestamp)
+    }
 }
 
 /// Alert types for incident detection
@@ -84,9 +152,53 @@ pub enum AlertType {
     AvailabilityLow { availability: f64 },               // Alert for low availability
     HighLatency { latency_ms: f64 },                     // Alert for high latency
     HighJitter { jitter_ms: f64 },                      // Alert for high jitter
+    /// The local clock is off from an NTP server's by more than the threshold
+    ClockDrift {
+        /// Offset of the server's clock from the local one; positive when the local clock is behind
+        offset_ms: f64,
+    },
+    /// An endpoint's probe loop keeps panicking and being restarted
+    ProbeLoopFailing {
+        /// Restarts within the failure window
+        restarts: u32,
+    },
+    /// A condition from `monitoring.scripts` holds
+    Scripted {
+        /// Name of the script
+        name: String,
+        /// Severity the script is configured with
+        severity: AlertSeverity,
+    },
 }
 
 impl AlertType {
+    /// Every value of [`AlertType::kind`]
+    pub const KINDS: [&'static str; 8] = [
+        "score_drop",
+        "sustained_loss",
+        "availability_low",
+        "high_latency",
+        "high_jitter",
+        "clock_drift",
+        "probe_loop_failing",
+        "scripted",
+    ];
+
+    /// Stable snake-case name of the condition, used in alert envelopes
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::ScoreDrop { .. } => "score_drop",
+            Self::SustainedLoss { .. } => "sustained_loss",
+            Self::AvailabilityLow { .. } => "availability_low",
+            Self::HighLatency { .. } => "high_latency",
+            Self::HighJitter { .. } => "high_jitter",
+            Self::ClockDrift { .. } => "clock_drift",
+            Self::ProbeLoopFailing { .. } => "probe_loop_failing",
+            Self::Scripted { .. } => "scripted",
+        }
+    }
+
     /// Get alert severity level
     pub fn severity(&self) -> AlertSeverity {
         match self {
@@ -136,6 +248,17 @@ impl AlertType {
                     AlertSeverity::Info
                 }
             }
+            AlertType::ClockDrift { offset_ms } => {
+                if offset_ms.abs() >= 5000.0 {
+                    AlertSeverity::Critical
+                } else if offset_ms.abs() >= 500.0 {
+                    AlertSeverity::Warning
+                } else {
+                    AlertSeverity::Info
+                }
+            }
+            Self::ProbeLoopFailing { .. } => AlertSeverity::Critical,
+            Self::Scripted { severity, .. } => *severity,
         }
     }
 
@@ -157,6 +280,15 @@ impl AlertType {
             AlertType::HighJitter { jitter_ms } => {
                 format!("High jitter: {}", FormatUtils::format_latency_ms(*jitter_ms))
             }
+            AlertType::ClockDrift { offset_ms } => format!(
+                "Local clock {} {}",
+                FormatUtils::format_latency_ms(offset_ms.abs()),
+                if *offset_ms > 0.0 { "behind" } else { "ahead" }
+            ),
+            Self::ProbeLoopFailing { restarts } => {
+                format!("Probe loop crashed and was restarted {restarts} times")
+            }
+            Self::Scripted { name, .. } => format!("Script condition `{name}` holds"),
         }
     }
 }
@@ -187,25 +319,80 @@ impl AlertSeverity {
             AlertSeverity::Critical => "🚨",
         }
     }
+
+    /// Plain-ASCII stand-in for [`Self::emoji`]
+    #[must_use]
+    pub const fn ascii_marker(self) -> &'static str {
+        match self {
+            Self::Info => "[i]",
+            Self::Warning => "[!]",
+            Self::Critical => "[!!]",
+        }
+    }
+}
+
+/// Likely cause of an alert, from comparing it with other endpoints
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "cause", rename_all = "snake_case")]
+pub enum RootCauseHint {
+    /// Most failures are DNS lookups, pointing at the resolver
+    ResolverIssue,
+    /// Every endpoint of one provider is degraded while others are fine
+    ProviderSide {
+        /// Provider whose endpoints are all degraded
+        provider: String,
+    },
+    /// The control endpoint, or every provider at once, is degraded too
+    LocalNetwork,
+}
+
+impl std::fmt::Display for RootCauseHint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ResolverIssue => write!(f, "resolver issue"),
+            Self::ProviderSide { provider } => write!(f, "provider-side ({provider})"),
+            Self::LocalNetwork => write!(f, "local network"),
+        }
+    }
 }
 
 /// Alert with metadata
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Alert {
+    /// Unique alert ID
+    #[serde(default = "generate_uuid")]
+    pub id: String,
     pub endpoint_id: String,        // Unique identifier for the endpoint that triggered the alert
     pub alert_type: AlertType,      // Type of alert that was triggered
     pub timestamp: DateTime<Utc>,   // Timestamp when the alert was created
     pub acknowledged: bool,         // Whether the alert has been acknowledged
+    /// Declared provider incident coinciding with the alert
+    #[serde(default)]
+    pub provider_incident: Option<String>,
+    /// Endpoint metrics when the alert fired
+    #[serde(default)]
+    pub metrics: Option<MetricsSnapshot>,
+    /// Likely cause, when other endpoints point to one
+    #[serde(default)]
+    pub hint: Option<RootCauseHint>,
+    /// Namespace of the endpoint, for endpoints monitored in one
+    #[serde(default)]
+    pub namespace: Option<String>,
 }
 
 impl Alert {
     /// Create a new alert
     pub fn new(endpoint_id: String, alert_type: AlertType) -> Self {
         Self {
+            id: generate_uuid(),
             endpoint_id,
             alert_type,
             timestamp: TimeUtils::now(),
             acknowledged: false,
+            provider_incident: None,
+            metrics: None,
+            hint: None,
+            namespace: None,
         }
     }
 
@@ -214,9 +401,13 @@ impl Alert {
         self.alert_type.severity()
     }
 
-    /// Get alert description
+    /// Get alert description, with the likely cause when known
     pub fn description(&self) -> String {
-        self.alert_type.description()
+        let mut description = self.alert_type.description();
+        if let Some(hint) = &self.hint {
+            let _ = write!(description, " (likely {hint})");
+        }
+        description
     }
 
     /// Acknowledge the alert
@@ -249,6 +440,25 @@ mod tests {
         assert_eq!(timeout.error_code, Some("timeout".to_string()));
     }
 
+    #[test]
+    fn test_records_stay_ordered_when_the_wall_clock_steps_back() {
+        let mut first = ProbeRecord::success("test".to_string(), 20.0);
+        let mut second = ProbeRecord::success("test".to_string(), 21.0);
+        first.sequence = 1;
+        second.sequence = 2;
+        assert!(second.monotonic_ns >= first.monotonic_ns);
+        assert_eq!(second.timestamp_after(&first), second.timestamp);
+
+        // NTP stepped the clock back a minute between the two probes
+        second.timestamp = first.timestamp - chrono::Duration::minutes(1);
+        second.monotonic_ns = first.monotonic_ns + 5_000_000_000;
+        assert_eq!(second.timestamp_after(&first), first.timestamp + chrono::Duration::seconds(5));
+
+        // Records without a sequence are only kept from going back
+        second.sequence = 0;
+        assert_eq!(second.timestamp_after(&first), first.timestamp);
+    }
+
     #[test]
     fn test_alert_severity() {
         let critical_drop = AlertType::ScoreDrop { old_score: 90.0, new_score: 40.0 };
@@ -259,6 +469,10 @@ mod tests {
 
         let info_jitter = AlertType::HighJitter { jitter_ms: 30.0 };
         assert_eq!(info_jitter.severity(), AlertSeverity::Info);
+
+        let critical_drift = AlertType::ClockDrift { offset_ms: -6000.0 };
+        assert_eq!(critical_drift.severity(), AlertSeverity::Critical);
+        assert_eq!(critical_drift.description(), "Local clock 6000.00ms ahead");
     }
 
     #[test]
@@ -0,0 +1,165 @@
+//! Measurement quality checks
+//!
+//! Compares observed round-trip times against the physical lower bound set by
+//! the speed of light over the great-circle distance between the client and
+//! the region. Measurements under that bound cannot have reached the region and
+//! were most likely answered by a cache, CDN edge or anycast node.
+
+use serde::{Deserialize, Serialize};
+
+use super::region::Coordinates;
+use super::stats::PingStats;
+
+/// Speed of light in vacuum, in km per millisecond
+const LIGHT_SPEED_KM_PER_MS: f64 = 299.792;
+
+/// Propagation speed in optical fiber (about two thirds of c), in km per millisecond
+const FIBER_SPEED_KM_PER_MS: f64 = 200.0;
+
+/// HTTP status indicating a response served from cache
+const HTTP_NOT_MODIFIED: u16 = 304;
+
+/// Reason a measurement is considered low quality
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QualityFlag {
+    /// Fastest RTT is below the vacuum light-speed round trip: physically impossible
+    BelowLightSpeedFloor {
+        /// Minimum possible RTT for the distance
+        floor_ms: f64,
+        /// Fastest observed RTT
+        observed_ms: f64,
+    },
+    /// Average RTT is below what fiber allows, or responses were served from cache
+    SuspectedCache {
+        /// Minimum RTT over fiber for the distance
+        floor_ms: f64,
+        /// Average observed RTT
+        observed_ms: f64,
+    },
+}
+
+impl QualityFlag {
+    /// Short human-readable explanation
+    #[must_use]
+    pub fn description(&self) -> String {
+        match self {
+            Self::BelowLightSpeedFloor {
+                floor_ms,
+                observed_ms,
+            } => format!(
+                "RTT {observed_ms:.1} ms is below the light-speed floor of {floor_ms:.1} ms"
+            ),
+            Self::SuspectedCache {
+                floor_ms,
+                observed_ms,
+            } => format!(
+                "RTT {observed_ms:.1} ms suggests a cached or edge response (fiber floor {floor_ms:.1} ms)"
+            ),
+        }
+    }
+}
+
+/// Sanity checks for measurements against physical limits
+pub struct MeasurementQuality;
+
+impl MeasurementQuality {
+    /// Minimum round-trip time in vacuum over `distance_km`
+    #[must_use]
+    pub fn light_speed_floor_ms(distance_km: f64) -> f64 {
+        2.0 * distance_km / LIGHT_SPEED_KM_PER_MS
+    }
+
+    /// Minimum round-trip time over optical fiber along the great circle
+    #[must_use]
+    pub fn fiber_floor_ms(distance_km: f64) -> f64 {
+        2.0 * distance_km / FIBER_SPEED_KM_PER_MS
+    }
+
+    /// Check a measurement taken from `client` against a region at `target`
+    #[must_use]
+    pub fn assess(
+        stats: &PingStats,
+        client: &Coordinates,
+        target: &Coordinates,
+    ) -> Vec<QualityFlag> {
+        let mut flags = Vec::new();
+        if !stats.is_successful() {
+            return flags;
+        }
+
+        let distance_km = client.distance_to(target);
+        let light_floor = Self::light_speed_floor_ms(distance_km);
+        let fiber_floor = Self::fiber_floor_ms(distance_km);
+
+        if stats.min < light_floor {
+            flags.push(QualityFlag::BelowLightSpeedFloor {
+                floor_ms: light_floor,
+                observed_ms: stats.min,
+            });
+        }
+
+        if stats.avg < fiber_floor || stats.status_codes.contains(&HTTP_NOT_MODIFIED) {
+            flags.push(QualityFlag::SuspectedCache {
+                floor_ms: fiber_floor,
+                observed_ms: stats.avg,
+            });
+        }
+
+        flags
+    }
+
+    /// Assess a measurement and record any flags on it
+    pub fn flag(stats: &mut PingStats, client: &Coordinates, target: &Coordinates) {
+        stats.quality_flags = Self::assess(stats, client, target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(min: f64, avg: f64) -> PingStats {
+        let mut stats = PingStats::new(5);
+        stats.successful_pings = 5;
+        stats.min = min;
+        stats.avg = avg;
+        stats
+    }
+
+    #[test]
+    fn test_light_speed_floor() {
+        // London to New York is about 5570 km: ~37 ms in vacuum, ~56 ms in fiber
+        let london = Coordinates::new(51.5074, -0.1278).unwrap();
+        let new_york = Coordinates::new(40.7128, -74.0060).unwrap();
+        let distance = london.distance_to(&new_york);
+
+        assert!((MeasurementQuality::light_speed_floor_ms(distance) - 37.2).abs() < 1.0);
+        assert!((MeasurementQuality::fiber_floor_ms(distance) - 55.7).abs() < 1.0);
+
+        let impossible = MeasurementQuality::assess(&stats(5.0, 8.0), &london, &new_york);
+        assert_eq!(impossible.len(), 2);
+        assert!(matches!(
+            impossible[0],
+            QualityFlag::BelowLightSpeedFloor { .. }
+        ));
+
+        let plausible = MeasurementQuality::assess(&stats(70.0, 75.0), &london, &new_york);
+        assert!(plausible.is_empty());
+    }
+
+    #[test]
+    fn test_cached_responses_flagged() {
+        let here = Coordinates::new(50.1109, 8.6821).unwrap();
+        let mut cached = stats(12.0, 14.0);
+        cached.status_codes = vec![200, 304];
+
+        let mut flagged = cached.clone();
+        MeasurementQuality::flag(&mut flagged, &here, &here);
+        assert!(flagged.is_low_quality());
+
+        cached.status_codes = vec![200];
+        MeasurementQuality::flag(&mut cached, &here, &here);
+        assert!(!cached.is_low_quality());
+    }
+}
@@ -0,0 +1,306 @@
+//! Connection races across an endpoint's addresses
+//!
+//! Modern clients do not connect to the first address a host resolves to;
+//! they race its addresses Happy Eyeballs style (RFC 8305), starting with
+//! IPv6 and alternating families, giving each attempt a head start before
+//! the next one begins, and use whichever connection completes first. A
+//! [`RaceResult`] records such races against one endpoint: when each
+//! attempt started, how long its TCP connect took, which address won and
+//! by how much.
+
+use std::fmt;
+use std::fmt::Write as _;
+use std::net::{IpAddr, SocketAddr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::collector::csv_field;
+
+/// Address family of a raced address
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum IpFamily {
+    /// IPv6, tried first
+    Ipv6,
+    /// IPv4
+    Ipv4,
+}
+
+impl IpFamily {
+    /// Family of `ip`
+    #[must_use]
+    pub const fn of(ip: &IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(_) => Self::Ipv4,
+            IpAddr::V6(_) => Self::Ipv6,
+        }
+    }
+}
+
+impl fmt::Display for IpFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ipv6 => "IPv6",
+            Self::Ipv4 => "IPv4",
+        })
+    }
+}
+
+/// One connection attempt of a race
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RaceAttempt {
+    /// Address the attempt connected to
+    pub address: SocketAddr,
+    /// Milliseconds after the start of the race the attempt began
+    pub started_ms: f64,
+    /// Duration of the TCP connect in milliseconds, absent when it failed
+    pub connect_ms: Option<f64>,
+    /// Why the attempt failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RaceAttempt {
+    /// Milliseconds after the start of the race the connection completed
+    #[must_use]
+    pub fn finished_ms(&self) -> Option<f64> {
+        self.connect_ms.map(|connect_ms| self.started_ms + connect_ms)
+    }
+
+    /// Family of the attempted address
+    #[must_use]
+    pub const fn family(&self) -> IpFamily {
+        IpFamily::of(&self.address.ip())
+    }
+}
+
+/// One race over every address of an endpoint, attempts in start order
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RaceRound {
+    /// Attempts in the order they were started
+    pub attempts: Vec<RaceAttempt>,
+}
+
+impl RaceRound {
+    /// Attempts that connected, first to complete first
+    fn finishers(&self) -> Vec<&RaceAttempt> {
+        let mut finishers: Vec<&RaceAttempt> = self
+            .attempts
+            .iter()
+            .filter(|attempt| attempt.connect_ms.is_some())
+            .collect();
+        let finished = |attempt: &RaceAttempt| attempt.finished_ms().unwrap_or(f64::INFINITY);
+        finishers.sort_by(|a, b| finished(a).total_cmp(&finished(b)));
+        finishers
+    }
+
+    /// Attempt whose connection completed first, the one a client would use
+    #[must_use]
+    pub fn winner(&self) -> Option<&RaceAttempt> {
+        self.finishers().first().copied()
+    }
+
+    /// How much earlier the winner completed than the next attempt to
+    /// connect, absent when fewer than two connected
+    #[must_use]
+    pub fn margin_ms(&self) -> Option<f64> {
+        match self.finishers()[..] {
+            [winner, runner_up, ..] => Some(runner_up.finished_ms()? - winner.finished_ms()?),
+            _ => None,
+        }
+    }
+}
+
+/// Timings of one address over every round of a race
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AddressTiming {
+    /// Raced address
+    pub address: IpAddr,
+    /// Family of the address
+    pub family: IpFamily,
+    /// Rounds the address was attempted in
+    pub attempts: usize,
+    /// Rounds the address won
+    pub wins: usize,
+    /// Median TCP connect time in milliseconds, absent when it never connected
+    pub median_connect_ms: Option<f64>,
+    /// Rounds the address failed to connect in
+    pub failures: usize,
+}
+
+/// Races against one endpoint
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RaceResult {
+    /// Region the endpoint belongs to
+    pub region: String,
+    /// Host the addresses were resolved from
+    pub host: String,
+    /// Races in the order they were run
+    pub rounds: Vec<RaceRound>,
+}
+
+impl RaceResult {
+    /// Timings of every address in the order it was attempted
+    #[must_use]
+    pub fn addresses(&self) -> Vec<AddressTiming> {
+        let mut timings: Vec<(AddressTiming, Vec<f64>)> = Vec::new();
+        for round in &self.rounds {
+            let winner = round.winner().map(|attempt| attempt.address);
+            for attempt in &round.attempts {
+                let ip = attempt.address.ip();
+                let index = timings
+                    .iter()
+                    .position(|(timing, _)| timing.address == ip)
+                    .unwrap_or_else(|| {
+                        timings.push((
+                            AddressTiming {
+                                address: ip,
+                                family: IpFamily::of(&ip),
+                                attempts: 0,
+                                wins: 0,
+                                median_connect_ms: None,
+                                failures: 0,
+                            },
+                            Vec::new(),
+                        ));
+                        timings.len() - 1
+                    });
+                let (timing, connects) = &mut timings[index];
+                timing.attempts += 1;
+                timing.wins += usize::from(winner == Some(attempt.address));
+                match attempt.connect_ms {
+                    Some(connect_ms) => connects.push(connect_ms),
+                    None => timing.failures += 1,
+                }
+            }
+        }
+        timings
+            .into_iter()
+            .map(|(mut timing, connects)| {
+                timing.median_connect_ms = (!connects.is_empty()).then(|| statistical::median(&connects));
+                timing
+            })
+            .collect()
+    }
+
+    /// Family that won the most rounds, IPv6 on a tie
+    #[must_use]
+    pub fn winning_family(&self) -> Option<IpFamily> {
+        let wins = |family| {
+            self.rounds
+                .iter()
+                .filter(|round| round.winner().map(RaceAttempt::family) == Some(family))
+                .count()
+        };
+        let (ipv6, ipv4) = (wins(IpFamily::Ipv6), wins(IpFamily::Ipv4));
+        if ipv6 == 0 && ipv4 == 0 {
+            None
+        } else if ipv6 >= ipv4 {
+            Some(IpFamily::Ipv6)
+        } else {
+            Some(IpFamily::Ipv4)
+        }
+    }
+
+    /// Median margin of the winner over the runner-up across rounds
+    #[must_use]
+    pub fn median_margin_ms(&self) -> Option<f64> {
+        let margins: Vec<f64> = self.rounds.iter().filter_map(RaceRound::margin_ms).collect();
+        (!margins.is_empty()).then(|| statistical::median(&margins))
+    }
+
+    /// Fastest median connect time of any address in `family`
+    #[must_use]
+    pub fn best_connect_ms(&self, family: IpFamily) -> Option<f64> {
+        self.addresses()
+            .iter()
+            .filter(|timing| timing.family == family)
+            .filter_map(|timing| timing.median_connect_ms)
+            .min_by(f64::total_cmp)
+    }
+
+    /// One CSV line per attempted address of every result, with a header row
+    #[must_use]
+    pub fn to_csv(results: &[Self]) -> String {
+        let mut csv =
+            String::from("region,host,address,family,attempts,wins,failures,median_connect_ms\n");
+        for result in results {
+            for timing in result.addresses() {
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{},{},{},{},{}",
+                    csv_field(&result.region),
+                    csv_field(&result.host),
+                    timing.address,
+                    timing.family,
+                    timing.attempts,
+                    timing.wins,
+                    timing.failures,
+                    timing
+                        .median_connect_ms
+                        .map_or_else(String::new, |ms| format!("{ms:.1}"))
+                );
+            }
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attempt(address: &str, started_ms: f64, connect_ms: Option<f64>) -> RaceAttempt {
+        RaceAttempt {
+            address: address.parse().unwrap(),
+            started_ms,
+            connect_ms,
+            error: connect_ms.is_none().then(|| "refused".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_race_result() {
+        let rounds = vec![
+            // IPv6 is slow to connect; IPv4 starts 250 ms later and still wins
+            RaceRound {
+                attempts: vec![
+                    attempt("[2001:db8::1]:443", 0.0, Some(400.0)),
+                    attempt("192.0.2.1:443", 250.0, Some(20.0)),
+                    attempt("192.0.2.2:443", 500.0, None),
+                ],
+            },
+            RaceRound {
+                attempts: vec![
+                    attempt("[2001:db8::1]:443", 0.0, Some(30.0)),
+                    attempt("192.0.2.1:443", 250.0, Some(20.0)),
+                ],
+            },
+            RaceRound {
+                attempts: vec![attempt("[2001:db8::1]:443", 0.0, Some(40.0))],
+            },
+        ];
+        assert_eq!(rounds[0].winner().unwrap().family(), IpFamily::Ipv4);
+        assert_eq!(rounds[0].margin_ms(), Some(130.0));
+        assert_eq!(rounds[1].margin_ms(), Some(240.0));
+        assert_eq!(rounds[2].margin_ms(), None);
+
+        let result = RaceResult {
+            region: "frankfurt".to_string(),
+            host: "example.com".to_string(),
+            rounds,
+        };
+        assert_eq!(result.winning_family(), Some(IpFamily::Ipv6));
+        assert_eq!(result.median_margin_ms(), Some(185.0));
+        assert_eq!(result.best_connect_ms(IpFamily::Ipv4), Some(20.0));
+        assert_eq!(result.best_connect_ms(IpFamily::Ipv6), Some(40.0));
+
+        let addresses = result.addresses();
+        assert_eq!(addresses.len(), 3);
+        assert_eq!((addresses[0].attempts, addresses[0].wins), (3, 2));
+        assert_eq!((addresses[1].attempts, addresses[1].wins), (2, 1));
+        assert_eq!((addresses[2].failures, addresses[2].median_connect_ms), (1, None));
+        assert!(RaceResult::to_csv(&[result])
+            .contains("\nfrankfurt,example.com,192.0.2.2,IPv4,1,0,1,\n"));
+    }
+}
@@ -0,0 +1,319 @@
+//! Filtered rankings and their export shape
+//!
+//! A [`ReportFilter`] narrows a ranking down to the regions a report is
+//! about: a minimum grade, a set of providers or countries, and caps per
+//! provider and overall. It runs on the ranked list, so every output format
+//! shows the same regions in the same order. [`RankedRegion`] is one entry of
+//! that list as written to JSON and CSV.
+
+use std::fmt::Write as _;
+
+use serde::{Deserialize, Serialize};
+
+use super::country::same_country;
+use super::metrics::HealthStatus;
+use super::scoring::utils::RankedResult;
+use crate::collector::csv_field;
+use crate::error::{CloudPingError, Result};
+
+/// Grades from best to worst, as assigned by the scoring
+pub(super) const GRADES: [char; 5] = ['A', 'B', 'C', 'D', 'F'];
+
+/// Which ranked regions a report shows
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReportFilter {
+    /// Keep regions graded this or better, e.g. `B` keeps A and B
+    #[serde(default)]
+    pub min_grade: Option<char>,
+    /// Keep only these providers, compared case-insensitively; all when empty
+    #[serde(default)]
+    pub providers: Vec<String>,
+    /// Keep only these countries, as codes or names compared case-insensitively; all when empty
+    #[serde(default)]
+    pub countries: Vec<String>,
+    /// Keep the best ranked regions of each provider, at most this many
+    #[serde(default)]
+    pub per_provider: Option<usize>,
+    /// Keep the first regions of the ranking, at most this many
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl ReportFilter {
+    /// Whether the filter keeps every region
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// # Errors
+    /// Returns a validation error naming the first invalid setting
+    pub fn validate(&self) -> Result<()> {
+        if let Some(grade) = self.min_grade {
+            if !GRADES.contains(&grade) {
+                return Err(CloudPingError::validation(
+                    "report_filter.min_grade",
+                    format!("'{grade}' is not a grade, expected one of A, B, C, D or F"),
+                ));
+            }
+        }
+        if self.per_provider == Some(0) {
+            return Err(CloudPingError::validation(
+                "report_filter.per_provider",
+                "must be greater than 0",
+            ));
+        }
+        if self.limit == Some(0) {
+            return Err(CloudPingError::validation(
+                "report_filter.limit",
+                "must be greater than 0",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Regions of `ranked` the filter keeps, in ranking order
+    #[must_use]
+    pub fn apply(&self, ranked: Vec<RankedResult>) -> Vec<RankedResult> {
+        let rank_of = |grade: char| GRADES.iter().position(|&g| g == grade);
+        let max_rank = self.min_grade.and_then(rank_of);
+        let matches = |wanted: &[String], value: &str| {
+            wanted.is_empty() || wanted.iter().any(|w| w.eq_ignore_ascii_case(value))
+        };
+        let in_countries =
+            |country: &str| self.countries.is_empty() || self.countries.iter().any(|w| same_country(w, country));
+
+        let mut kept_per_provider: Vec<(String, usize)> = Vec::new();
+        let mut kept = Vec::new();
+        for result in ranked {
+            let (_, _, stats, score) = &result;
+            if max_rank.is_some_and(|max| rank_of(score.grade).map_or(true, |rank| rank > max))
+                || !matches(&self.providers, &stats.provider)
+                || !in_countries(&stats.country)
+            {
+                continue;
+            }
+            if let Some(cap) = self.per_provider {
+                let provider = stats.provider.to_lowercase();
+                match kept_per_provider.iter_mut().find(|(p, _)| *p == provider) {
+                    Some((_, count)) if *count >= cap => continue,
+                    Some((_, count)) => *count += 1,
+                    None => kept_per_provider.push((provider, 1)),
+                }
+            }
+            kept.push(result);
+            if self.limit.is_some_and(|limit| kept.len() >= limit) {
+                break;
+            }
+        }
+        kept
+    }
+}
+
+/// One region of a ranking, flattened for JSON and CSV output
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RankedRegion {
+    /// Position in the ranking, starting at 1
+    pub rank: usize,
+    /// Region name
+    pub region: String,
+    /// Provider of the region, empty when unknown
+    pub provider: String,
+    /// Country of the region, empty when unknown
+    pub country: String,
+    /// Overall score
+    pub score: f64,
+    /// Letter grade of the score
+    pub grade: char,
+    /// Health by loss, latency and jitter
+    pub health: HealthStatus,
+    /// Average latency, absent without successful pings
+    pub latency_ms: Option<f64>,
+    /// 95th percentile latency, absent without successful pings
+    pub p95_ms: Option<f64>,
+    /// Failed pings in percent
+    pub loss_percent: f64,
+    /// Gaming suitability score
+    pub gaming: f64,
+    /// Egress price in US dollars per GB, absent when unknown
+    pub egress_usd_per_gb: Option<f64>,
+    /// Instance price relative to a typical region, absent when unknown
+    pub price_index: Option<f64>,
+    /// Score per unit of relative cost, absent without prices
+    pub price_performance: Option<f64>,
+    /// Carbon intensity in gCO2/kWh, absent when unknown
+    pub carbon_g_per_kwh: Option<f64>,
+    /// CDN edge POP that served most pings, e.g. `Cloudflare FRA`; absent for non-CDN endpoints
+    pub edge_pop: Option<String>,
+    /// Autonomous system number of the endpoint, absent without ASN enrichment
+    pub asn: Option<u32>,
+    /// Organization operating the autonomous system, absent when unknown
+    pub as_organization: Option<String>,
+    /// Operational notes on the region
+    pub notes: Vec<String>,
+}
+
+impl RankedRegion {
+    /// Flatten a ranking, numbering regions in its order
+    #[must_use]
+    pub fn from_ranking(ranked: &[RankedResult]) -> Vec<Self> {
+        ranked
+            .iter()
+            .enumerate()
+            .map(|(i, (score, region, stats, result))| {
+                let reached = stats.is_successful();
+                Self {
+                    rank: i + 1,
+                    region: region.clone(),
+                    provider: stats.provider.clone(),
+                    country: stats.country.clone(),
+                    score: *score,
+                    grade: result.grade,
+                    health: stats.health_status(),
+                    latency_ms: reached.then_some(stats.avg),
+                    p95_ms: reached.then(|| stats.percentile_95()),
+                    loss_percent: stats.packet_loss,
+                    gaming: result.suitability.gaming,
+                    egress_usd_per_gb: stats.cost.egress_usd_per_gb,
+                    price_index: stats.cost.price_index,
+                    price_performance: stats.cost.price_performance(*score),
+                    carbon_g_per_kwh: stats.carbon_g_per_kwh,
+                    edge_pop: stats.edge_pop().map(ToString::to_string),
+                    asn: stats.asn.as_ref().map(|info| info.asn),
+                    as_organization: stats
+                        .asn
+                        .as_ref()
+                        .map(|info| info.organization.clone())
+                        .filter(|organization| !organization.is_empty()),
+                    notes: stats.notes.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Ranking as CSV with a header row; unreached latencies and unknown
+    /// prices, carbon intensities, edge POPs and ASNs are empty
+    ///
+    /// Notes share one column, separated by semicolons.
+    #[must_use]
+    pub fn to_csv(ranking: &[Self]) -> String {
+        let mut csv = String::from(
+            "rank,region,provider,country,score,grade,health,latency_ms,p95_ms,loss_percent,gaming,egress_usd_per_gb,price_index,price_performance,carbon_g_per_kwh,edge_pop,asn,as_organization,notes\n",
+        );
+        let optional = |value: Option<f64>| value.map_or_else(String::new, |v| format!("{v:.2}"));
+        for entry in ranking {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{:.1},{},{},{},{},{:.1},{:.1},{},{},{},{},{},{},{},{}",
+                entry.rank,
+                csv_field(&entry.region),
+                csv_field(&entry.provider),
+                csv_field(&entry.country),
+                entry.score,
+                entry.grade,
+                entry.health,
+                optional(entry.latency_ms),
+                optional(entry.p95_ms),
+                entry.loss_percent,
+                entry.gaming,
+                optional(entry.egress_usd_per_gb),
+                optional(entry.price_index),
+                optional(entry.price_performance),
+                optional(entry.carbon_g_per_kwh),
+                csv_field(entry.edge_pop.as_deref().unwrap_or_default()),
+                entry.asn.map_or_else(String::new, |asn| asn.to_string()),
+                csv_field(entry.as_organization.as_deref().unwrap_or_default()),
+                csv_field(&entry.notes.join("; "))
+            );
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AlgorithmWeights, PingStats, ScoringAdapter};
+
+    fn ranking() -> Vec<RankedResult> {
+        let region = |name: &str, avg: f64, provider: &str, country: &str| {
+            let mut stats = PingStats::new(10);
+            stats.successful_pings = 10;
+            stats.avg = avg;
+            stats.latencies = vec![avg; 10];
+            stats.provider = provider.to_string();
+            stats.country = country.to_string();
+            (name.to_string(), stats)
+        };
+        ScoringAdapter::get_sorted_results(
+            &[
+                region("Frankfurt", 15.0, "AWS", "Germany"),
+                region("Paris", 20.0, "AWS", "France"),
+                region("Dublin", 25.0, "AWS", "Ireland"),
+                region("Warsaw", 30.0, "GCP", "Poland"),
+                region("Sydney", 450.0, "GCP", "Australia"),
+            ],
+            &AlgorithmWeights::default(),
+        )
+    }
+
+    fn names(ranked: &[RankedResult]) -> Vec<&str> {
+        ranked.iter().map(|(_, name, _, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_report_filter() {
+        assert_eq!(ReportFilter::default().apply(ranking()).len(), 5);
+
+        let per_provider = ReportFilter {
+            per_provider: Some(1),
+            ..ReportFilter::default()
+        };
+        assert_eq!(names(&per_provider.apply(ranking())), ["Frankfurt", "Warsaw"]);
+
+        let countries = ReportFilter {
+            countries: vec!["france".to_string(), "Poland".to_string()],
+            limit: Some(1),
+            ..ReportFilter::default()
+        };
+        assert_eq!(names(&countries.apply(ranking())), ["Paris"]);
+
+        let codes = ReportFilter {
+            countries: vec!["de".to_string(), "IE".to_string()],
+            ..ReportFilter::default()
+        };
+        assert_eq!(names(&codes.apply(ranking())), ["Frankfurt", "Dublin"]);
+
+        let graded = ReportFilter {
+            min_grade: Some('B'),
+            providers: vec!["gcp".to_string()],
+            ..ReportFilter::default()
+        };
+        assert_eq!(names(&graded.apply(ranking())), ["Warsaw"]);
+
+        assert!(ReportFilter {
+            min_grade: Some('E'),
+            ..ReportFilter::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_ranked_region_csv() {
+        let mut ranked = ranking();
+        ranked[0].1 = "Frankfurt, DE".to_string();
+        ranked[0].2.cost.price_index = Some(0.5);
+        ranked[4].2.successful_pings = 0;
+        ranked[4].2.notes = vec!["deprecated Q3".to_string(), "no IPv6".to_string()];
+
+        let csv = RankedRegion::to_csv(&RankedRegion::from_ranking(&ranked));
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[1].starts_with("1,\"Frankfurt, DE\",AWS,Germany,"));
+        let value = format!(",,0.50,{:.2},", ranked[0].0 * 2.0);
+        assert!(lines[1].contains(&value), "{}", lines[1]);
+        assert!(lines[5].contains(",,,"), "{}", lines[5]);
+        assert!(lines[5].ends_with(",deprecated Q3; no IPv6"), "{}", lines[5]);
+    }
+}
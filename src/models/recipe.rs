@@ -0,0 +1,489 @@
+//! Suitability of regions for particular applications
+//!
+//! Whether a region suits a video call or a Git remote depends on more than
+//! its score: calls need little jitter and UDP to their STUN server, while
+//! cloning a repository needs quick TLS handshakes and a fast download. A [`Recipe`]
+//! bundles the measurements one kind of application depends on with the
+//! limits it tolerates, so a single `recipe` run answers "which regions work
+//! for this application" with pass or fail per region.
+//!
+//! A few recipes are built in ([`Recipe::built_in`]); `[recipes.<name>]` in
+//! the config adds more or replaces a built-in one of the same name.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+
+use serde::{Deserialize, Serialize};
+
+use super::stats::PingStats;
+use super::udp::{UdpCheck, UdpPort, UdpProtocol, UdpReachability};
+use crate::collector::csv_field;
+use crate::error::{CloudPingError, Result};
+
+/// Measurements an application depends on and the limits it tolerates
+///
+/// Limits left unset are not checked.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Recipe {
+    /// What the recipe stands for, shown with `recipe --list`
+    #[serde(default)]
+    pub description: String,
+    /// UDP ports that must answer, as `3478/stun`, `443/quic` or `500`
+    #[serde(default)]
+    pub udp_ports: Vec<String>,
+    /// Host whose UDP ports are checked instead of each region's host, such
+    /// as the STUN server of a call service
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stun_server: Option<String>,
+    /// URL downloaded to time throughput instead of each region's URL, whose
+    /// body is rarely large enough to time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<String>,
+    /// Highest average latency in milliseconds
+    #[serde(default)]
+    pub max_latency_ms: Option<f64>,
+    /// Highest 95th percentile latency in milliseconds
+    #[serde(default)]
+    pub max_p95_ms: Option<f64>,
+    /// Highest jitter in milliseconds
+    #[serde(default)]
+    pub max_jitter_ms: Option<f64>,
+    /// Highest packet loss in percent
+    #[serde(default)]
+    pub max_loss_percent: Option<f64>,
+    /// Longest TLS handshake in milliseconds
+    #[serde(default)]
+    pub max_tls_handshake_ms: Option<f64>,
+    /// Lowest download throughput in megabits per second
+    #[serde(default)]
+    pub min_throughput_mbps: Option<f64>,
+}
+
+impl Recipe {
+    /// Recipes available without any configuration, by name
+    #[must_use]
+    pub fn built_in() -> BTreeMap<String, Self> {
+        BTreeMap::from([
+            (
+                "video-call".to_string(),
+                Self {
+                    description: "Zoom-like calls: latency below 150 ms, jitter below 30 ms, loss below 1%"
+                        .to_string(),
+                    max_latency_ms: Some(150.0),
+                    max_jitter_ms: Some(30.0),
+                    max_loss_percent: Some(1.0),
+                    ..Self::default()
+                },
+            ),
+            (
+                "git-hosting".to_string(),
+                Self {
+                    description: "Git over HTTPS: quick TLS handshakes and latency".to_string(),
+                    max_latency_ms: Some(150.0),
+                    max_p95_ms: Some(300.0),
+                    max_tls_handshake_ms: Some(200.0),
+                    ..Self::default()
+                },
+            ),
+            (
+                "gaming".to_string(),
+                Self {
+                    description: "Real-time games: latency below 60 ms, jitter below 10 ms, loss below 0.5%"
+                        .to_string(),
+                    max_latency_ms: Some(60.0),
+                    max_p95_ms: Some(90.0),
+                    max_jitter_ms: Some(10.0),
+                    max_loss_percent: Some(0.5),
+                    ..Self::default()
+                },
+            ),
+        ])
+    }
+
+    /// Recipes called `names` among the built-in and `configured` ones, in
+    /// the order given; every recipe when `names` is empty
+    ///
+    /// # Errors
+    /// Returns a validation error naming the first unknown recipe
+    pub fn resolve(names: &[String], configured: &BTreeMap<String, Self>) -> Result<Vec<(String, Self)>> {
+        let mut recipes = Self::built_in();
+        recipes.extend(configured.iter().map(|(name, recipe)| (name.clone(), recipe.clone())));
+        if names.is_empty() {
+            return Ok(recipes.into_iter().collect());
+        }
+        names
+            .iter()
+            .map(|name| {
+                recipes.get(name).map(|recipe| (name.clone(), recipe.clone())).ok_or_else(|| {
+                    CloudPingError::validation(
+                        "recipe",
+                        format!(
+                            "`{name}` is not a recipe; known recipes are {}",
+                            recipes.keys().cloned().collect::<Vec<_>>().join(", ")
+                        ),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// # Errors
+    /// Returns a validation error naming the first invalid setting of the recipe `name`
+    pub fn validate(&self, name: &str) -> Result<()> {
+        let limits = [
+            ("max_latency_ms", self.max_latency_ms),
+            ("max_p95_ms", self.max_p95_ms),
+            ("max_jitter_ms", self.max_jitter_ms),
+            ("max_loss_percent", self.max_loss_percent),
+            ("max_tls_handshake_ms", self.max_tls_handshake_ms),
+            ("min_throughput_mbps", self.min_throughput_mbps),
+        ];
+        for (key, limit) in limits {
+            if limit.is_some_and(|limit| !limit.is_finite() || limit < 0.0) {
+                return Err(CloudPingError::validation(
+                    format!("recipes.{name}.{key}"),
+                    "must be a number of at least 0",
+                ));
+            }
+        }
+        self.udp_ports()
+            .map_err(|e| CloudPingError::validation(format!("recipes.{name}.udp_ports"), e.to_string()))?;
+        if let Some(server) = &self.stun_server {
+            if self.udp_ports.is_empty() || url::Url::parse(&format!("udp://{server}")).is_err() {
+                return Err(CloudPingError::validation(
+                    format!("recipes.{name}.stun_server"),
+                    "must be a host name or address, and needs udp_ports",
+                ));
+            }
+        }
+        if let Some(url) = &self.download_url {
+            let http = url::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+            if !http || self.min_throughput_mbps.is_none() {
+                return Err(CloudPingError::validation(
+                    format!("recipes.{name}.download_url"),
+                    "must be an HTTP or HTTPS URL, and needs min_throughput_mbps",
+                ));
+            }
+        }
+        if self.udp_ports.is_empty() && limits.iter().all(|(_, limit)| limit.is_none()) {
+            return Err(CloudPingError::validation(
+                format!("recipes.{name}"),
+                "must set at least one limit or UDP port",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Parsed `udp_ports`
+    ///
+    /// # Errors
+    /// Returns a validation error naming the first invalid port
+    pub fn udp_ports(&self) -> Result<Vec<UdpPort>> {
+        self.udp_ports.iter().map(|port| port.parse()).collect()
+    }
+
+    /// Whether checking the recipe needs a TLS handshake to be timed
+    #[must_use]
+    pub const fn needs_tls_handshake(&self) -> bool {
+        self.max_tls_handshake_ms.is_some()
+    }
+
+    /// Whether checking the recipe needs a download to be timed
+    #[must_use]
+    pub const fn needs_throughput(&self) -> bool {
+        self.min_throughput_mbps.is_some()
+    }
+
+    /// Check `measurements` of `region` against the limits of the recipe `name`
+    ///
+    /// A STUN port of the region's own host that does not answer stays
+    /// unmeasured rather than failing, as region hosts rarely run STUN; with
+    /// a `stun_server` it fails.
+    #[must_use]
+    pub fn evaluate(&self, name: &str, region: &str, measurements: &RecipeMeasurements) -> RecipeResult {
+        let stats = &measurements.stats;
+        let answered = stats.successful_pings > 0;
+        let mut checks: Vec<RecipeCheck> = [
+            ("latency_ms", self.max_latency_ms, answered.then_some(stats.avg)),
+            ("p95_ms", self.max_p95_ms, answered.then(|| stats.percentile_95())),
+            ("jitter_ms", self.max_jitter_ms, (stats.successful_pings > 1).then_some(stats.jitter)),
+            ("loss_percent", self.max_loss_percent, (stats.total_pings > 0).then_some(stats.packet_loss)),
+            ("tls_handshake_ms", self.max_tls_handshake_ms, measurements.tls_handshake_ms),
+        ]
+        .into_iter()
+        .filter_map(|(metric, limit, measured)| {
+            limit.map(|limit| RecipeCheck::limit(metric, limit, measured, |measured| measured <= limit))
+        })
+        .collect();
+        if let Some(limit) = self.min_throughput_mbps {
+            let measured = self.download_url.as_ref().map_or(measurements.throughput_mbps, |url| {
+                measurements.downloads.get(url).copied().flatten()
+            });
+            checks.push(RecipeCheck::limit("throughput_mbps", limit, measured, |measured| measured >= limit));
+        }
+        let udp = match &self.stun_server {
+            Some(server) => measurements.server_udp.get(server).map_or(&[][..], Vec::as_slice),
+            None => &measurements.udp,
+        };
+        for port in self.udp_ports().unwrap_or_default() {
+            let check = udp.iter().find(|check| check.port == port);
+            let served = self.stun_server.is_some() || port.protocol != UdpProtocol::Stun;
+            checks.push(RecipeCheck {
+                metric: format!("udp {port}"),
+                limit: None,
+                measured: check.and_then(|check| check.rtt_ms),
+                outcome: match check.map(|check| check.reachability) {
+                    Some(UdpReachability::Answered) => CheckOutcome::Pass,
+                    Some(UdpReachability::Closed | UdpReachability::NoAnswer) if served => CheckOutcome::Fail,
+                    Some(UdpReachability::Closed | UdpReachability::NoAnswer | UdpReachability::Unresolved)
+                    | None => CheckOutcome::Unmeasured,
+                },
+            });
+        }
+
+        RecipeResult {
+            recipe: name.to_string(),
+            region: region.to_string(),
+            verdict: RecipeVerdict::of(&checks),
+            checks,
+        }
+    }
+}
+
+/// What was measured of a region for the recipes checked against it
+#[derive(Debug, Clone)]
+pub struct RecipeMeasurements {
+    /// Pings of the region
+    pub stats: PingStats,
+    /// UDP ports of the region checked, for recipes that need any
+    pub udp: Vec<UdpCheck>,
+    /// UDP ports checked on each recipe's `stun_server`, by server
+    pub server_udp: BTreeMap<String, Vec<UdpCheck>>,
+    /// Time of a TLS handshake in milliseconds, when timed and successful
+    pub tls_handshake_ms: Option<f64>,
+    /// Download throughput of the region URL in megabits per second, when
+    /// timed and measurable
+    pub throughput_mbps: Option<f64>,
+    /// Download throughput of each recipe's `download_url`, by URL
+    pub downloads: BTreeMap<String, Option<f64>>,
+}
+
+/// Outcome of one check of a recipe
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckOutcome {
+    /// The measurement is within the limit
+    Pass,
+    /// The measurement is beyond the limit, or the port did not answer
+    Fail,
+    /// Nothing could be measured, so the check neither passes nor fails
+    Unmeasured,
+}
+
+impl fmt::Display for CheckOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pass => "pass",
+            Self::Fail => "fail",
+            Self::Unmeasured => "unmeasured",
+        })
+    }
+}
+
+/// One measurement of a recipe compared with its limit
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecipeCheck {
+    /// What was measured, such as `jitter_ms` or `udp 3478/stun`
+    pub metric: String,
+    /// Limit the measurement is compared with; `None` for UDP ports, which must answer
+    pub limit: Option<f64>,
+    /// Measured value; the round trip in milliseconds for UDP ports
+    pub measured: Option<f64>,
+    /// Whether the measurement is within the limit
+    pub outcome: CheckOutcome,
+}
+
+impl RecipeCheck {
+    fn limit(metric: &str, limit: f64, measured: Option<f64>, within: impl Fn(f64) -> bool) -> Self {
+        Self {
+            metric: metric.to_string(),
+            limit: Some(limit),
+            measured,
+            outcome: match measured {
+                Some(measured) if within(measured) => CheckOutcome::Pass,
+                Some(_) => CheckOutcome::Fail,
+                None => CheckOutcome::Unmeasured,
+            },
+        }
+    }
+}
+
+/// Overall outcome of a recipe for a region
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecipeVerdict {
+    /// Every check passed
+    Pass,
+    /// At least one check failed
+    Fail,
+    /// No check failed, but some could not be measured
+    Inconclusive,
+}
+
+impl RecipeVerdict {
+    fn of(checks: &[RecipeCheck]) -> Self {
+        if checks.iter().any(|check| check.outcome == CheckOutcome::Fail) {
+            Self::Fail
+        } else if checks.iter().any(|check| check.outcome == CheckOutcome::Unmeasured) {
+            Self::Inconclusive
+        } else {
+            Self::Pass
+        }
+    }
+}
+
+impl fmt::Display for RecipeVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pass => "pass",
+            Self::Fail => "fail",
+            Self::Inconclusive => "inconclusive",
+        })
+    }
+}
+
+/// One recipe checked against one region
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecipeResult {
+    /// Recipe name
+    pub recipe: String,
+    /// Region name
+    pub region: String,
+    /// Overall outcome
+    pub verdict: RecipeVerdict,
+    /// Every check, latency limits first and UDP ports last
+    pub checks: Vec<RecipeCheck>,
+}
+
+impl RecipeResult {
+    /// One CSV line per check of every result, with a header row
+    #[must_use]
+    pub fn to_csv(results: &[Self]) -> String {
+        let number = |value: Option<f64>| value.map_or_else(String::new, |value| format!("{value:.1}"));
+        let mut csv = String::from("region,recipe,verdict,metric,limit,measured,outcome\n");
+        for result in results {
+            for check in &result.checks {
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{},{},{},{}",
+                    csv_field(&result.region),
+                    csv_field(&result.recipe),
+                    result.verdict,
+                    csv_field(&check.metric),
+                    number(check.limit),
+                    number(check.measured),
+                    check.outcome
+                );
+            }
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UdpProtocol;
+
+    fn stats(latencies: &[f64], failed: usize) -> PingStats {
+        let mut stats = PingStats::new(latencies.len() + failed);
+        stats.latencies = latencies.to_vec();
+        stats.successful_pings = latencies.len();
+        stats.avg = latencies.iter().sum::<f64>() / latencies.len() as f64;
+        stats.jitter = 2.0;
+        stats.packet_loss = 25.0 * failed as f64;
+        stats
+    }
+
+    #[test]
+    fn test_recipes_pass_fail_and_stay_inconclusive() {
+        let call = Recipe {
+            udp_ports: vec!["3478/stun".to_string()],
+            stun_server: Some("stun.example.com".to_string()),
+            max_jitter_ms: Some(30.0),
+            max_loss_percent: Some(1.0),
+            ..Recipe::default()
+        };
+        let download = Recipe {
+            max_tls_handshake_ms: Some(200.0),
+            min_throughput_mbps: Some(10.0),
+            ..Recipe::default()
+        };
+        let stun = UdpPort {
+            port: 3478,
+            protocol: UdpProtocol::Stun,
+        };
+        let check = |reachability| UdpCheck {
+            port: stun,
+            reachability,
+            rtt_ms: (reachability == UdpReachability::Answered).then_some(41.0),
+        };
+        let mut measurements = RecipeMeasurements {
+            stats: stats(&[40.0, 42.0, 41.0, 43.0], 0),
+            udp: vec![check(UdpReachability::NoAnswer)],
+            server_udp: BTreeMap::from([("stun.example.com".to_string(), vec![check(UdpReachability::Answered)])]),
+            tls_handshake_ms: Some(80.0),
+            throughput_mbps: None,
+            downloads: BTreeMap::new(),
+        };
+
+        // The STUN server answers although the region's host does not
+        assert_eq!(call.evaluate("call", "Frankfurt", &measurements).verdict, RecipeVerdict::Pass);
+        // A download too small to time leaves the download undecided
+        let result = download.evaluate("download", "Frankfurt", &measurements);
+        assert_eq!(result.verdict, RecipeVerdict::Inconclusive);
+        assert_eq!(result.checks.last().unwrap().metric, "throughput_mbps");
+        // A configured download URL is timed instead
+        let download = Recipe {
+            download_url: Some("https://files.example.com/100mb".to_string()),
+            ..download
+        };
+        measurements.downloads.insert("https://files.example.com/100mb".to_string(), Some(95.0));
+        assert_eq!(download.evaluate("download", "Frankfurt", &measurements).verdict, RecipeVerdict::Pass);
+
+        // A region's own host not running STUN leaves the port unmeasured
+        let own_host = Recipe {
+            stun_server: None,
+            ..call.clone()
+        };
+        let result = own_host.evaluate("call", "Frankfurt", &measurements);
+        assert_eq!(result.verdict, RecipeVerdict::Inconclusive);
+        assert_eq!(result.checks.last().unwrap().outcome, CheckOutcome::Unmeasured);
+
+        // A silent STUN server and a lost ping each fail the call
+        measurements.server_udp.insert("stun.example.com".to_string(), vec![check(UdpReachability::NoAnswer)]);
+        measurements.stats = stats(&[40.0, 41.0, 43.0], 1);
+        let result = call.evaluate("call", "Frankfurt", &measurements);
+        assert_eq!(result.verdict, RecipeVerdict::Fail);
+        let failed: Vec<&str> = result
+            .checks
+            .iter()
+            .filter(|check| check.outcome == CheckOutcome::Fail)
+            .map(|check| check.metric.as_str())
+            .collect();
+        assert_eq!(failed, ["loss_percent", "udp 3478/stun"]);
+
+        assert!(Recipe::resolve(&["fax".to_string()], &BTreeMap::new()).is_err());
+        assert!(Recipe::default().validate("empty").is_err());
+        call.validate("call").unwrap();
+        download.validate("download").unwrap();
+        let no_ports = Recipe {
+            udp_ports: Vec::new(),
+            ..call
+        };
+        assert!(no_ports.validate("call").is_err());
+        for (name, recipe) in Recipe::built_in() {
+            recipe.validate(&name).unwrap();
+        }
+    }
+}
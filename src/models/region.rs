@@ -9,10 +9,13 @@ use std::collections::HashMap;
 use crate::error::{CloudPingError, Result};
 use super::utils::generate_uuid;
 
-fn default_priority() -> f64 {
+pub(super) fn default_priority() -> f64 {
     1.0
 }
 
+/// Smallest and largest multiple of the base ping and retry counts a region's priority can give it
+const PRIORITY_SCALE_RANGE: (f64, f64) = (0.25, 4.0);
+
 fn default_enabled() -> bool {
     true
 }
@@ -192,6 +195,25 @@ impl Region {
         self.country.eq_ignore_ascii_case(country_code)
     }
 
+    /// Scale a ping or retry count by the region's priority
+    ///
+    /// The default priority of 1 keeps `base`; a priority of 2 doubles it.
+    /// The multiple is capped at 4 and floored at a quarter, and a non-zero
+    /// `base` never drops below one.
+    #[must_use]
+    pub fn scaled_by_priority(&self, base: usize) -> usize {
+        if base == 0 {
+            return 0;
+        }
+        let (min, max) = PRIORITY_SCALE_RANGE;
+        let factor = self.priority.clamp(min, max);
+        let scaled = (f64::from(u32::try_from(base).unwrap_or(u32::MAX)) * factor).round();
+        // `scaled` is positive and at most four times a `u32`
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let scaled = scaled as usize;
+        scaled.max(1)
+    }
+
     /// Get display name with provider
     pub fn display_name(&self) -> String {
         if self.provider.is_empty() {
@@ -424,6 +446,23 @@ mod tests {
         assert!(region.coordinates.is_some());
     }
 
+    #[test]
+    fn test_scaled_by_priority() {
+        let mut region = Region::new("Test".to_string(), "https://example.com".to_string()).unwrap();
+        assert_eq!(region.scaled_by_priority(10), 10);
+
+        region.priority = 2.5;
+        assert_eq!(region.scaled_by_priority(10), 25);
+        assert_eq!(region.scaled_by_priority(0), 0);
+
+        region.priority = 100.0;
+        assert_eq!(region.scaled_by_priority(10), 40);
+
+        region.priority = 0.0;
+        assert_eq!(region.scaled_by_priority(10), 3);
+        assert_eq!(region.scaled_by_priority(1), 1);
+    }
+
     #[test]
     fn test_cloud_provider() {
         let mut provider = CloudProvider::new("Test Provider".to_string()).unwrap();
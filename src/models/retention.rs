@@ -0,0 +1,386 @@
+//! Retention of saved test history
+//!
+//! A deployment benchmarking every few minutes saves hundreds of runs per
+//! region a day. Retention keeps recent runs as they are, folds older ones
+//! into one aggregate run per region and hour, older ones still into one per
+//! day, and optionally drops the oldest. Aggregates keep the min, max,
+//! weighted averages and an evenly spaced sample of the latencies, so trends
+//! and percentiles over old history still work. Their `metadata` says what
+//! they aggregate and how many runs they stand for.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::{DateTime, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::stats::PingStats;
+use super::utils::{generate_uuid, percentile_of_sorted, sort_values};
+use crate::error::{CloudPingError, Result};
+
+/// Latencies kept in an aggregate run, as evenly spaced quantiles of all it aggregates
+pub const AGGREGATE_LATENCIES: usize = 100;
+
+/// Metadata key naming the granularity of an aggregate run
+const AGGREGATE_KEY: &str = "aggregate";
+
+/// Metadata key counting the runs an aggregate stands for
+const AGGREGATED_RUNS_KEY: &str = "aggregated_runs";
+
+/// How long saved runs are kept at each granularity, read from `[history_retention]`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Age up to which runs are kept as they are
+    #[serde(with = "humantime_serde", default = "default_raw")]
+    pub raw: Duration,
+    /// Age up to which older runs are kept as hourly aggregates, then as daily ones
+    #[serde(with = "humantime_serde", default = "default_hourly")]
+    pub hourly: Duration,
+    /// Age beyond which daily aggregates are dropped; kept forever when unset
+    #[serde(with = "humantime_serde", default)]
+    pub daily: Option<Duration>,
+    /// Time between compactions while monitoring
+    #[serde(with = "humantime_serde", default = "default_interval")]
+    pub interval: Duration,
+}
+
+const fn default_raw() -> Duration {
+    Duration::from_secs(7 * 24 * 3600)
+}
+
+const fn default_hourly() -> Duration {
+    Duration::from_secs(90 * 24 * 3600)
+}
+
+const fn default_interval() -> Duration {
+    Duration::from_secs(3600)
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            raw: default_raw(),
+            hourly: default_hourly(),
+            daily: None,
+            interval: default_interval(),
+        }
+    }
+}
+
+/// How much of a region's history one saved run covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Granularity {
+    /// A single benchmark run
+    Raw,
+    /// Every run of one hour
+    Hourly,
+    /// Every run of one day
+    Daily,
+}
+
+impl Granularity {
+    /// Granularity of a saved run, read from its metadata
+    #[must_use]
+    pub fn of(stats: &PingStats) -> Self {
+        match stats.metadata.get(AGGREGATE_KEY).map(String::as_str) {
+            Some("hourly") => Self::Hourly,
+            Some("daily") => Self::Daily,
+            _ => Self::Raw,
+        }
+    }
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Raw => "raw",
+            Self::Hourly => "hourly",
+            Self::Daily => "daily",
+        }
+    }
+
+    /// Start of the hour or day holding `time`, or `time` itself for raw runs
+    #[must_use]
+    pub fn bucket_start(self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let bucket = match self {
+            Self::Raw => return time,
+            Self::Hourly => chrono::Duration::hours(1),
+            Self::Daily => chrono::Duration::days(1),
+        };
+        time.duration_trunc(bucket).unwrap_or(time)
+    }
+}
+
+/// Runs a saved run stands for: one, or the count recorded in an aggregate
+fn aggregated_runs(stats: &PingStats) -> usize {
+    stats
+        .metadata
+        .get(AGGREGATED_RUNS_KEY)
+        .and_then(|runs| runs.parse().ok())
+        .unwrap_or(1)
+}
+
+impl RetentionPolicy {
+    /// # Errors
+    /// Returns a validation error for zero durations or ages that are not increasing
+    pub fn validate(&self) -> Result<()> {
+        if self.raw.is_zero() {
+            return Err(CloudPingError::validation("history_retention.raw", "must be greater than 0"));
+        }
+        if self.hourly < self.raw {
+            return Err(CloudPingError::validation(
+                "history_retention.hourly",
+                "must be at least history_retention.raw",
+            ));
+        }
+        if self.daily.is_some_and(|daily| daily < self.hourly) {
+            return Err(CloudPingError::validation(
+                "history_retention.daily",
+                "must be at least history_retention.hourly",
+            ));
+        }
+        if self.interval.is_zero() {
+            return Err(CloudPingError::validation(
+                "history_retention.interval",
+                "must be greater than 0",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Time before which runs are compacted, aligned to an hour so no hourly
+    /// aggregate covers runs still kept as they are
+    #[must_use]
+    pub fn raw_cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        Granularity::Hourly.bucket_start(ago(now, self.raw))
+    }
+
+    /// Granularity a run tested at `time` is kept at, or `None` once it expired
+    fn granularity_at(&self, time: DateTime<Utc>, now: DateTime<Utc>) -> Option<Granularity> {
+        if time >= self.raw_cutoff(now) {
+            Some(Granularity::Raw)
+        } else if time >= Granularity::Daily.bucket_start(ago(now, self.hourly)) {
+            Some(Granularity::Hourly)
+        } else if self
+            .daily
+            .is_some_and(|daily| time < Granularity::Daily.bucket_start(ago(now, daily)))
+        {
+            None
+        } else {
+            Some(Granularity::Daily)
+        }
+    }
+
+    /// Runs of one region tested before [`Self::raw_cutoff`] as this policy
+    /// keeps them, or `None` when they already are
+    #[must_use]
+    pub fn compact(&self, runs: &[PingStats], now: DateTime<Utc>) -> Option<Vec<PingStats>> {
+        let mut changed = false;
+        let mut buckets: BTreeMap<DateTime<Utc>, (Granularity, Vec<&PingStats>)> = BTreeMap::new();
+        for stats in runs {
+            let Some(granularity) = self.granularity_at(stats.test_time, now) else {
+                changed = true;
+                continue;
+            };
+            buckets
+                .entry(granularity.bucket_start(stats.test_time))
+                .or_insert_with(|| (granularity, Vec::new()))
+                .1
+                .push(stats);
+        }
+
+        let mut compacted = Vec::with_capacity(buckets.len());
+        for (start, (granularity, bucket)) in buckets {
+            match bucket.as_slice() {
+                // Already kept at this granularity or coarser
+                [stats] if Granularity::of(stats) >= granularity => compacted.push((*stats).clone()),
+                _ => {
+                    changed = true;
+                    compacted.push(PingStats::aggregate(&bucket, granularity, start));
+                }
+            }
+        }
+        changed.then_some(compacted)
+    }
+}
+
+/// Time `age` before `now`, saturating at the earliest time representable
+fn ago(now: DateTime<Utc>, age: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(age)
+        .ok()
+        .and_then(|age| now.checked_sub_signed(age))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+impl PingStats {
+    /// One run standing for all of `runs`, tested at `start`
+    ///
+    /// Counts, bytes and durations are summed, averages are weighted by
+    /// successful pings, and region details come from the latest run.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)] // ping counts are far below 2^52
+    pub fn aggregate(runs: &[&Self], granularity: Granularity, start: DateTime<Utc>) -> Self {
+        let Some(latest) = runs.iter().max_by_key(|stats| stats.test_time) else {
+            return Self::new(0);
+        };
+        let mut aggregate = (*latest).clone();
+        aggregate.id = generate_uuid();
+        aggregate.test_time = start;
+        aggregate.total_pings = runs.iter().map(|stats| stats.total_pings).sum();
+        aggregate.successful_pings = runs.iter().map(|stats| stats.successful_pings).sum();
+        aggregate.packet_loss = if aggregate.total_pings == 0 {
+            0.0
+        } else {
+            (aggregate.total_pings - aggregate.successful_pings.min(aggregate.total_pings)) as f64
+                / aggregate.total_pings as f64
+                * 100.0
+        };
+        aggregate.test_duration_ms = runs.iter().map(|stats| stats.test_duration_ms).sum();
+        aggregate.bytes_sent = runs.iter().map(|stats| stats.bytes_sent).sum();
+        aggregate.bytes_received = runs.iter().map(|stats| stats.bytes_received).sum();
+        aggregate.incomplete = runs.iter().any(|stats| stats.incomplete);
+
+        let answered: Vec<&Self> = runs.iter().copied().filter(|stats| stats.successful_pings > 0).collect();
+        if !answered.is_empty() {
+            aggregate.min = answered.iter().map(|stats| stats.min).fold(f64::MAX, f64::min);
+            aggregate.max = answered.iter().map(|stats| stats.max).fold(0.0, f64::max);
+            aggregate.avg = weighted_mean(&answered, |stats| stats.avg);
+            aggregate.jitter = weighted_mean(&answered, |stats| stats.jitter);
+            // Pooled deviation of all pings: within-run variance plus the spread of run averages
+            let avg = aggregate.avg;
+            aggregate.standard_deviation = weighted_mean(&answered, |stats| {
+                stats
+                    .standard_deviation
+                    .mul_add(stats.standard_deviation, (stats.avg - avg).powi(2))
+            })
+            .sqrt();
+        }
+
+        let mut latencies: Vec<f64> = runs
+            .iter()
+            .flat_map(|stats| stats.latencies.iter().copied())
+            .filter(|&latency| latency > 0.0)
+            .collect();
+        sort_values(&mut latencies);
+        if latencies.len() > AGGREGATE_LATENCIES {
+            let step = 100.0 / (AGGREGATE_LATENCIES - 1) as f64;
+            latencies = (0..AGGREGATE_LATENCIES)
+                .map(|i| percentile_of_sorted(&latencies, i as f64 * step))
+                .collect();
+        }
+        aggregate.latencies = latencies;
+
+        aggregate.dns_resolution_time = mean_of_known(runs, |stats| stats.dns_resolution_time);
+        aggregate.connection_time = mean_of_known(runs, |stats| stats.connection_time);
+        aggregate.tls_handshake_time = mean_of_known(runs, |stats| stats.tls_handshake_time);
+        aggregate.control_latency_ms = mean_of_known(runs, |stats| stats.control_latency_ms);
+        aggregate.normalized_avg = mean_of_known(runs, |stats| stats.normalized_avg);
+
+        aggregate
+            .metadata
+            .insert(AGGREGATE_KEY.to_string(), granularity.name().to_string());
+        let runs: usize = runs.iter().map(|stats| aggregated_runs(stats)).sum();
+        aggregate
+            .metadata
+            .insert(AGGREGATED_RUNS_KEY.to_string(), runs.to_string());
+        aggregate
+    }
+}
+
+/// Mean of `value` over `runs` weighted by their successful pings
+#[allow(clippy::cast_precision_loss)] // ping counts are far below 2^52
+fn weighted_mean(runs: &[&PingStats], value: impl Fn(&PingStats) -> f64) -> f64 {
+    let weight: usize = runs.iter().map(|stats| stats.successful_pings).sum();
+    if weight == 0 {
+        return 0.0;
+    }
+    runs.iter()
+        .map(|stats| value(stats) * stats.successful_pings as f64)
+        .sum::<f64>()
+        / weight as f64
+}
+
+/// Mean of the runs' values that are known
+#[allow(clippy::cast_precision_loss)] // run counts are far below 2^52
+fn mean_of_known(runs: &[&PingStats], value: impl Fn(&PingStats) -> Option<f64>) -> Option<f64> {
+    let known: Vec<f64> = runs.iter().filter_map(|stats| value(stats)).collect();
+    (!known.is_empty()).then(|| known.iter().sum::<f64>() / known.len() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(time: DateTime<Utc>, avg: f64) -> PingStats {
+        let mut stats = PingStats::new(10);
+        stats.test_time = time;
+        stats.successful_pings = 10;
+        stats.min = avg - 1.0;
+        stats.max = avg + 1.0;
+        stats.avg = avg;
+        stats.latencies = vec![avg; 10];
+        stats
+    }
+
+    #[test]
+    fn test_compaction() {
+        let now = DateTime::parse_from_rfc3339("2026-06-30T12:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let policy = RetentionPolicy {
+            raw: Duration::from_secs(24 * 3600),
+            hourly: Duration::from_secs(7 * 24 * 3600),
+            daily: Some(Duration::from_secs(30 * 24 * 3600)),
+            ..RetentionPolicy::default()
+        };
+        let days = |days: i64, minutes: i64| now - chrono::Duration::days(days) + chrono::Duration::minutes(minutes);
+        let runs = [
+            // Two runs in one hour three days ago
+            run(days(3, 0), 10.0),
+            run(days(3, 20), 20.0),
+            // Three runs in one day ten days ago
+            run(days(10, 0), 30.0),
+            run(days(10, 120), 30.0),
+            run(days(10, 240), 60.0),
+            // Expired
+            run(days(40, 0), 99.0),
+        ];
+
+        let compacted = policy.compact(&runs, now).unwrap();
+        assert_eq!(compacted.len(), 2);
+        let daily = &compacted[0];
+        assert_eq!(Granularity::of(daily), Granularity::Daily);
+        assert_eq!(daily.test_time, Granularity::Daily.bucket_start(days(10, 0)));
+        assert_eq!(daily.total_pings, 30);
+        assert!((daily.avg - 40.0).abs() < 1e-9);
+        assert_eq!((daily.min, daily.max), (29.0, 61.0));
+        assert_eq!(daily.metadata[AGGREGATED_RUNS_KEY], "3");
+        let hourly = &compacted[1];
+        assert_eq!(Granularity::of(hourly), Granularity::Hourly);
+        assert!((hourly.avg - 15.0).abs() < 1e-9);
+        assert!((hourly.standard_deviation - 5.0).abs() < 1e-9);
+
+        // Compacting again changes nothing
+        assert!(policy.compact(&compacted, now).is_none());
+
+        // Once the hourly aggregate ages past a week, it folds into a daily one
+        let later = now + chrono::Duration::days(5);
+        let again = policy.compact(&compacted, later).unwrap();
+        assert_eq!(Granularity::of(&again[1]), Granularity::Daily);
+        assert_eq!(again[1].metadata[AGGREGATED_RUNS_KEY], "2");
+    }
+
+    #[test]
+    fn test_aggregate_latency_sample() {
+        let start = Utc::now();
+        let runs: Vec<PingStats> = (0..30).map(|i| run(start, f64::from(i) + 1.0)).collect();
+        let refs: Vec<&PingStats> = runs.iter().collect();
+        let aggregate = PingStats::aggregate(&refs, Granularity::Hourly, start);
+        assert_eq!(aggregate.latencies.len(), AGGREGATE_LATENCIES);
+        assert!((aggregate.latencies[0] - 1.0).abs() < 1e-9);
+        assert!((aggregate.latencies[AGGREGATE_LATENCIES - 1] - 30.0).abs() < 1e-9);
+        assert!((aggregate.median_latency() - 15.5).abs() < 0.5);
+
+        let policy = RetentionPolicy { hourly: Duration::from_secs(60), ..RetentionPolicy::default() };
+        assert!(policy.validate().is_err());
+        assert!(RetentionPolicy::default().validate().is_ok());
+    }
+}
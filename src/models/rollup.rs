@@ -0,0 +1,185 @@
+//! Time-bucketed rollups of saved test history
+//!
+//! A chart of a region's latency over a month needs one point per hour or
+//! day, not every saved run with its raw latencies. A rollup groups the runs
+//! of a time range into buckets of a fixed interval and keeps, per bucket,
+//! the average latency weighted by answered pings, the 95th percentile of
+//! their latencies and the packet loss. Buckets without runs are left out.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use chrono::{DateTime, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::stats::{PingStats, TestHistory};
+use super::utils::percentile;
+use crate::collector::csv_field;
+use crate::error::{CloudPingError, Result};
+
+/// Rollup interval used when none is given
+pub const DEFAULT_ROLLUP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Aggregates of the runs of one bucket
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RollupBucket {
+    /// Start of the bucket
+    pub start: DateTime<Utc>,
+    /// Saved runs in the bucket
+    pub runs: usize,
+    /// Pings sent by those runs
+    pub pings: usize,
+    /// Average latency of answered pings in milliseconds, absent when none answered
+    pub avg_ms: Option<f64>,
+    /// 95th percentile latency of answered pings in milliseconds
+    pub p95_ms: Option<f64>,
+    /// Share of pings without an answer, in percent
+    pub loss_percent: f64,
+}
+
+/// Saved runs of one region in buckets of a fixed interval
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Rollup {
+    /// Region name
+    pub region: String,
+    /// Region URL, which identifies the region across runs
+    pub region_url: String,
+    /// Length of each bucket
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    /// Buckets holding at least one run, oldest first
+    pub buckets: Vec<RollupBucket>,
+}
+
+impl Rollup {
+    /// Runs of `history` tested from `from` up to but excluding `to`, in
+    /// buckets of `interval` aligned to the epoch
+    ///
+    /// # Errors
+    /// Returns a validation error for an interval under a second or beyond a year
+    pub fn new(history: &TestHistory, interval: Duration, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Self> {
+        let step = Self::step(interval)?;
+
+        let mut buckets: BTreeMap<DateTime<Utc>, Vec<&PingStats>> = BTreeMap::new();
+        for stats in history
+            .historical_data
+            .iter()
+            .filter(|stats| stats.test_time >= from && stats.test_time < to)
+        {
+            let start = stats.test_time.duration_trunc(step).unwrap_or(stats.test_time);
+            buckets.entry(start).or_default().push(stats);
+        }
+
+        Ok(Self {
+            region: history.region_name.clone(),
+            region_url: history.region_url.clone(),
+            interval,
+            buckets: buckets
+                .into_iter()
+                .map(|(start, runs)| RollupBucket::new(start, &runs))
+                .collect(),
+        })
+    }
+
+    /// `interval` as a bucket length
+    ///
+    /// # Errors
+    /// Returns a validation error for an interval under a second or beyond a year
+    pub fn step(interval: Duration) -> Result<chrono::Duration> {
+        chrono::Duration::from_std(interval)
+            .ok()
+            .filter(|step| *step >= chrono::Duration::seconds(1) && *step <= chrono::Duration::days(366))
+            .ok_or_else(|| CloudPingError::validation("interval", "must be between 1s and 1 year"))
+    }
+
+    /// One CSV line per bucket of every rollup, with a header row
+    #[must_use]
+    pub fn to_csv(rollups: &[Self]) -> String {
+        let mut csv = String::from("region,start,runs,pings,avg_ms,p95_ms,loss_percent\n");
+        let ms = |value: Option<f64>| value.map_or_else(String::new, |value| format!("{value:.1}"));
+        for rollup in rollups {
+            for bucket in &rollup.buckets {
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{},{},{},{:.2}",
+                    csv_field(&rollup.region),
+                    bucket.start.to_rfc3339(),
+                    bucket.runs,
+                    bucket.pings,
+                    ms(bucket.avg_ms),
+                    ms(bucket.p95_ms),
+                    bucket.loss_percent
+                );
+            }
+        }
+        csv
+    }
+}
+
+impl RollupBucket {
+    #[allow(clippy::cast_precision_loss)] // ping counts are far below 2^52
+    fn new(start: DateTime<Utc>, runs: &[&PingStats]) -> Self {
+        let pings: usize = runs.iter().map(|stats| stats.total_pings).sum();
+        let answered: usize = runs.iter().map(|stats| stats.successful_pings).sum();
+        let avg_ms = (answered > 0).then(|| {
+            runs.iter()
+                .map(|stats| stats.avg * stats.successful_pings as f64)
+                .sum::<f64>()
+                / answered as f64
+        });
+        let latencies: Vec<f64> = runs
+            .iter()
+            .flat_map(|stats| stats.latencies.iter().copied())
+            .filter(|&latency| latency > 0.0)
+            .collect();
+        let loss_percent = if pings == 0 {
+            0.0
+        } else {
+            pings.saturating_sub(answered) as f64 / pings as f64 * 100.0
+        };
+        Self {
+            start,
+            runs: runs.len(),
+            pings,
+            avg_ms,
+            p95_ms: (!latencies.is_empty()).then(|| percentile(&latencies, 95.0)),
+            loss_percent,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollup_buckets() {
+        let start = DateTime::parse_from_rfc3339("2026-03-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut history = TestHistory::new("id".to_string(), "Frankfurt".to_string(), "https://fra.example.com".to_string());
+        for (minutes, avg, successful) in [(5, 10.0, 10), (25, 30.0, 10), (70, 50.0, 5), (200, 99.0, 10)] {
+            let mut stats = PingStats::new(10);
+            stats.test_time = start + chrono::Duration::minutes(minutes);
+            stats.avg = avg;
+            stats.successful_pings = successful;
+            stats.latencies = vec![avg; successful];
+            history.add_test_result(stats);
+        }
+
+        let to = start + chrono::Duration::hours(3);
+        let rollup = Rollup::new(&history, DEFAULT_ROLLUP_INTERVAL, start, to).unwrap();
+        assert_eq!(rollup.buckets.len(), 2);
+        let first = &rollup.buckets[0];
+        assert_eq!((first.start, first.runs, first.pings), (start, 2, 20));
+        assert_eq!(first.avg_ms, Some(20.0));
+        assert_eq!(first.p95_ms, Some(30.0));
+        assert!(first.loss_percent.abs() < f64::EPSILON);
+        assert!((rollup.buckets[1].loss_percent - 50.0).abs() < 1e-9);
+
+        let csv = Rollup::to_csv(&[rollup]);
+        assert!(csv.contains("Frankfurt,2026-03-01T13:00:00+00:00,1,10,50.0,50.0,50.00\n"));
+        assert!(Rollup::new(&history, Duration::ZERO, start, to).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+//! Network paths to regions and when they change between runs
+//!
+//! A latency shift between runs has two usual causes: the provider got
+//! slower, or traffic took another route to it. Each run can record a
+//! [`RoutePath`] per region: the autonomous system announcing the region's
+//! address (from ASN enrichment) and, with `route_tracing` on, a hash of the
+//! last routers a traceroute passed. Comparing the paths of consecutive runs
+//! finds the runs where the route changed, so latency changes that coincide
+//! with one can be put down to routing.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::stats::TestHistory;
+
+/// Responding hops nearest the region that [`RoutePath::from_hops`] hashes;
+/// earlier hops cross transit networks whose load balancing (ECMP) picks
+/// another of several equal routers from one trace to the next
+const ROUTE_HASH_HOPS: usize = 3;
+
+/// Path taken to a region in one run
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RoutePath {
+    /// Autonomous system announcing the region's address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_asn: Option<u32>,
+    /// Hash of the addresses of the last three responding hops, in order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hops_hash: Option<String>,
+    /// Number of responding hops
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hop_count: Option<usize>,
+}
+
+impl RoutePath {
+    /// Path through the responding hop addresses `hops`, in order, ending in
+    /// `final_asn`; only the last three are hashed
+    #[must_use]
+    pub fn from_hops<S: AsRef<str>>(hops: &[S], final_asn: Option<u32>) -> Self {
+        let last = &hops[hops.len().saturating_sub(ROUTE_HASH_HOPS)..];
+        let joined = last.iter().map(AsRef::as_ref).collect::<Vec<_>>().join(">");
+        Self {
+            final_asn,
+            hops_hash: Some(format!("{:08x}", crc32fast::hash(joined.as_bytes()))),
+            hop_count: Some(hops.len()),
+        }
+    }
+
+    /// Whether nothing about the path was recorded
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.final_asn.is_none() && self.hops_hash.is_none()
+    }
+
+    /// What differs from `previous`, comparing only what both runs recorded;
+    /// `None` when nothing does
+    #[must_use]
+    pub fn change_from(&self, previous: &Self) -> Option<RouteChangeKind> {
+        let differs = |a: Option<&str>, b: Option<&str>| matches!((a, b), (Some(a), Some(b)) if a != b);
+        if matches!((previous.final_asn, self.final_asn), (Some(a), Some(b)) if a != b) {
+            Some(RouteChangeKind::Asn)
+        } else if differs(previous.hops_hash.as_deref(), self.hops_hash.as_deref()) {
+            Some(RouteChangeKind::Hops)
+        } else {
+            None
+        }
+    }
+}
+
+/// What changed about a path
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteChangeKind {
+    /// The region's address moved to another autonomous system
+    Asn,
+    /// The traced hops differ while the autonomous system stayed
+    Hops,
+}
+
+/// Path to a region that differs from the run before
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RouteChange {
+    /// Time of the first run on the new path
+    pub at: DateTime<Utc>,
+    /// What changed
+    pub kind: RouteChangeKind,
+    /// Path of the run before
+    pub before: RoutePath,
+    /// Path of the run at `at`
+    pub after: RoutePath,
+    /// Average latency of the run before, in milliseconds
+    pub before_ms: f64,
+    /// Average latency of the run at `at`, in milliseconds
+    pub after_ms: f64,
+}
+
+impl RouteChange {
+    /// Sentence describing the change for a region
+    #[must_use]
+    pub fn describe(&self, region: &str) -> String {
+        let detail = match (self.kind, self.before.final_asn, self.after.final_asn) {
+            (RouteChangeKind::Asn, Some(before), Some(after)) => format!("AS{before} -> AS{after}"),
+            _ => format!(
+                "{} -> {} hops",
+                self.before.hop_count.map_or_else(|| "?".to_string(), |hops| hops.to_string()),
+                self.after.hop_count.map_or_else(|| "?".to_string(), |hops| hops.to_string())
+            ),
+        };
+        format!(
+            "Path to {region} changed on {} ({detail}), latency {:.1} ms -> {:.1} ms",
+            self.at.format("%Y-%m-%d %H:%M"),
+            self.before_ms,
+            self.after_ms
+        )
+    }
+}
+
+impl fmt::Display for RouteChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Asn => "asn",
+            Self::Hops => "hops",
+        })
+    }
+}
+
+impl TestHistory {
+    /// Runs whose path differs from the last earlier run that recorded one, oldest first
+    #[must_use]
+    pub fn detect_route_changes(&self) -> Vec<RouteChange> {
+        let mut changes = Vec::new();
+        let mut previous: Option<&super::stats::PingStats> = None;
+        for run in &self.historical_data {
+            let Some(path) = run.route.as_ref().filter(|path| !path.is_empty()) else {
+                continue;
+            };
+            if let Some(before) = previous {
+                let before_path = before.route.clone().unwrap_or_default();
+                if let Some(kind) = path.change_from(&before_path) {
+                    changes.push(RouteChange {
+                        at: run.test_time,
+                        kind,
+                        before: before_path,
+                        after: path.clone(),
+                        before_ms: before.avg,
+                        after_ms: run.avg,
+                    });
+                }
+            }
+            previous = Some(run);
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::models::PingStats;
+
+    #[test]
+    fn test_route_changes_between_runs() {
+        let start = Utc::now();
+        let mut history = TestHistory::new("id".to_string(), "Frankfurt".to_string(), String::new());
+        let paths = [
+            Some(RoutePath::from_hops(&["10.0.0.1", "80.81.192.1"], Some(3320))),
+            None,
+            Some(RoutePath::from_hops(&["10.0.0.1", "80.81.192.1"], Some(3320))),
+            Some(RoutePath::from_hops(&["10.0.0.1", "62.115.1.1", "62.115.2.1", "80.81.192.1"], Some(3320))),
+            // Another router far from the region is load balancing, not a new path
+            Some(RoutePath::from_hops(&["10.0.0.9", "62.115.1.1", "62.115.2.1", "80.81.192.1"], Some(3320))),
+            Some(RoutePath {
+                final_asn: Some(1299),
+                ..RoutePath::default()
+            }),
+        ];
+        for (hour, path) in (0u32..).zip(paths) {
+            let mut stats = PingStats::new(1);
+            stats.successful_pings = 1;
+            stats.avg = 20.0 + 10.0 * f64::from(hour);
+            stats.test_time = start + Duration::hours(i64::from(hour));
+            stats.route = path;
+            history.historical_data.push(stats);
+        }
+
+        let changes = history.detect_route_changes();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].kind, RouteChangeKind::Hops);
+        assert_eq!(changes[0].at, start + Duration::hours(3));
+        assert!(changes[0].describe("Frankfurt").contains("(2 -> 4 hops), latency 40.0 ms -> 50.0 ms"));
+        assert_eq!(changes[1].kind, RouteChangeKind::Asn);
+        assert!(changes[1].describe("Frankfurt").contains("AS3320 -> AS1299"));
+    }
+}
@@ -0,0 +1,228 @@
+//! Breakdown of how a score was reached
+//!
+//! [`ComprehensiveScoreResult::explain`] lists every component with its raw
+//! value, the normalization band the value fell into, its normalized score,
+//! its weight and the points it added. The contributions add up to the final
+//! score, so the breakdown shows exactly why a region got its grade. It
+//! serializes to JSON and prints as plain text.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::normalization::{Band, JITTER_BANDS_MS, LATENCY_BANDS_MS, LOSS_BANDS_PERCENT};
+use super::ComprehensiveScoreResult;
+
+/// Lowest score of each grade, best grade first
+const GRADE_FLOORS: [(char, f64); 5] = [('A', 90.0), ('B', 80.0), ('C', 70.0), ('D', 60.0), ('F', 0.0)];
+
+/// How one component contributed to a score
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ComponentExplanation {
+    /// Component name, e.g. `latency`
+    pub component: String,
+    /// Raw metric, absent when there was nothing to measure
+    pub raw: Option<f64>,
+    /// Unit of the raw metric
+    pub unit: String,
+    /// Band the raw value fell into, for components scored in bands
+    pub band: Option<Band>,
+    /// How the raw value maps to the normalized score
+    pub rule: String,
+    /// Normalized score from 0 to 100
+    pub normalized: f64,
+    /// Weight of the component
+    pub weight: f64,
+    /// Points added to the score: weight times normalized score
+    pub contribution: f64,
+}
+
+/// Every component's part in a score, with the grade it earned
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScoreExplanation {
+    /// Final score, the sum of the contributions
+    pub score: f64,
+    /// Letter grade of the score
+    pub grade: char,
+    /// Score range of the grade, e.g. `70 to 80`
+    pub grade_range: String,
+    /// Points needed for the next better grade, absent for an A
+    pub points_to_next_grade: Option<f64>,
+    /// Components in scoring order
+    pub components: Vec<ComponentExplanation>,
+}
+
+impl ComprehensiveScoreResult {
+    /// Break the score down into the parts each component and weight contributed
+    #[must_use]
+    pub fn explain(&self) -> ScoreExplanation {
+        let inputs = &self.inputs;
+        let weights = &self.weights;
+        let components = &self.components;
+        let banded = |name: &str, raw: Option<f64>, unit: &str, bounds: &[f64; 4], normalized: f64, weight: f64| {
+            let band = raw.map(|value| Band::of(value, bounds));
+            let rule = band.map_or_else(
+                || "no answer scores 0".to_string(),
+                |band| format!("{} band: {} {unit}", band.name(), band.range(bounds)),
+            );
+            ComponentExplanation::new(name, raw, unit, band, rule, normalized, weight)
+        };
+
+        let mut explained = vec![
+            banded(
+                "latency",
+                inputs.latency_ms,
+                "ms",
+                &LATENCY_BANDS_MS,
+                components.latency_score,
+                weights.latency,
+            ),
+            banded(
+                "jitter",
+                Some(inputs.jitter_ms),
+                "ms",
+                &JITTER_BANDS_MS,
+                components.jitter_score,
+                weights.jitter,
+            ),
+            banded(
+                "packet_loss",
+                Some(inputs.loss_percent),
+                "%",
+                &LOSS_BANDS_PERCENT,
+                components.packet_loss_score,
+                weights.packet_loss,
+            ),
+            ComponentExplanation::new(
+                "consistency",
+                Some(inputs.spread_ms),
+                "ms",
+                None,
+                "100 minus the latency spread, at least 0".to_string(),
+                components.consistency_score,
+                weights.consistency,
+            ),
+            ComponentExplanation::new(
+                "availability",
+                Some(inputs.availability_percent),
+                "%",
+                None,
+                "percent answered".to_string(),
+                components.availability_score,
+                weights.availability,
+            ),
+        ];
+        if let Some(loaded) = inputs.loaded_latency_ms {
+            explained[0].rule = format!(
+                "mean of {} and {} band under load",
+                explained[0].rule,
+                Band::of(loaded, &LATENCY_BANDS_MS).name()
+            );
+        }
+
+        let position = GRADE_FLOORS
+            .iter()
+            .position(|(grade, _)| *grade == self.grade)
+            .unwrap_or(GRADE_FLOORS.len() - 1);
+        let floor = GRADE_FLOORS[position].1;
+        let next = position.checked_sub(1).map(|better| GRADE_FLOORS[better]);
+        ScoreExplanation {
+            score: self.score,
+            grade: self.grade,
+            grade_range: next.map_or_else(
+                || format!("{floor} or more"),
+                |(_, ceiling)| format!("{floor} to {ceiling}"),
+            ),
+            points_to_next_grade: next.map(|(_, ceiling)| (ceiling - self.score).max(0.0)),
+            components: explained,
+        }
+    }
+}
+
+impl ComponentExplanation {
+    fn new(
+        component: &str,
+        raw: Option<f64>,
+        unit: &str,
+        band: Option<Band>,
+        rule: String,
+        normalized: f64,
+        weight: f64,
+    ) -> Self {
+        Self {
+            component: component.to_string(),
+            raw,
+            unit: unit.to_string(),
+            band,
+            rule,
+            normalized,
+            weight,
+            contribution: weight * normalized,
+        }
+    }
+
+    /// Raw value with its unit, or `-` without one
+    #[must_use]
+    pub fn raw_text(&self) -> String {
+        self.raw
+            .map_or_else(|| "-".to_string(), |raw| format!("{raw:.2} {}", self.unit))
+    }
+}
+
+impl fmt::Display for ScoreExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Score {:.1}, grade {} ({})",
+            self.score, self.grade, self.grade_range
+        )?;
+        if let Some(points) = self.points_to_next_grade {
+            write!(f, ", {points:.1} points below the next grade")?;
+        }
+        for component in &self.components {
+            write!(
+                f,
+                "\n  {:<13} {:>12}  {:<32} {:>5.1} x {:.2} = {:>4.1}",
+                component.component,
+                component.raw_text(),
+                component.rule,
+                component.normalized,
+                component.weight,
+                component.contribution
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{AlgorithmWeights, PingStats, ScoringAdapter};
+
+    #[test]
+    fn test_explain_score() {
+        let mut stats = PingStats::new(100);
+        stats.successful_pings = 99;
+        stats.avg = 42.0;
+        stats.jitter = 3.0;
+        stats.standard_deviation = 8.0;
+        let result = ScoringAdapter::score_ping_stats(&stats, &AlgorithmWeights::default(), "r");
+
+        let explanation = result.explain();
+        let total: f64 = explanation.components.iter().map(|c| c.contribution).sum();
+        assert!((total - result.score).abs() < 1e-9);
+        assert_eq!(explanation.grade, result.grade);
+
+        let latency = &explanation.components[0];
+        assert_eq!(latency.rule, "good band: 20 to 50 ms");
+        assert_eq!(latency.raw, Some(42.0));
+        let loss = &explanation.components[2];
+        assert_eq!(loss.rule, "fair band: 0.5 to 2 %");
+
+        let text = explanation.to_string();
+        assert!(text.starts_with(&format!("Score {:.1}, grade {}", result.score, result.grade)));
+        assert!(text.contains("points below the next grade"));
+        let json = serde_json::to_value(&explanation).unwrap();
+        assert_eq!(json["components"][0]["band"], "good");
+    }
+}
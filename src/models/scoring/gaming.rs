@@ -0,0 +1,133 @@
+//! Tick-budget scoring for online games
+//!
+//! Game servers simulate the world in fixed ticks (64 Hz gives a 15.6 ms
+//! budget). A round trip that fits within one or two ticks reaches the server
+//! before the next state update, so the share of pings that fit the budget is
+//! a more direct measure of playability than a generic latency score.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CloudPingError, Result};
+use crate::models::PingStats;
+
+/// Weight of the one-tick probability in the tick-budget score; two ticks get the rest
+const ONE_TICK_WEIGHT: f64 = 0.6;
+
+/// Server tick rate a region is scored against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TickBudget {
+    tick_rate_hz: f64,
+}
+
+/// How well a region fits a tick budget
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TickBudgetResult {
+    /// Server tick rate in Hz
+    pub tick_rate_hz: f64,
+    /// Duration of one tick in milliseconds
+    pub budget_ms: f64,
+    /// Share of pings with a round trip within one tick, 0-1
+    pub within_one_tick: f64,
+    /// Share of pings with a round trip within two ticks, 0-1
+    pub within_two_ticks: f64,
+    /// Tick-budget score from 0 to 100
+    pub score: f64,
+}
+
+impl TickBudget {
+    /// Budget for a server ticking at `tick_rate_hz`
+    pub fn new(tick_rate_hz: f64) -> Result<Self> {
+        if !tick_rate_hz.is_finite() || tick_rate_hz <= 0.0 {
+            return Err(CloudPingError::validation(
+                "gaming_tick_rate_hz",
+                "must be a positive number",
+            ));
+        }
+        Ok(Self { tick_rate_hz })
+    }
+
+    /// Server tick rate in Hz
+    #[must_use]
+    pub const fn tick_rate_hz(&self) -> f64 {
+        self.tick_rate_hz
+    }
+
+    /// Duration of one tick in milliseconds
+    #[must_use]
+    pub fn budget_ms(&self) -> f64 {
+        1000.0 / self.tick_rate_hz
+    }
+
+    /// Score a measurement against the budget
+    ///
+    /// Probabilities come from the empirical latency distribution; failed
+    /// pings count as misses.
+    #[must_use]
+    pub fn assess(&self, stats: &PingStats) -> TickBudgetResult {
+        let budget_ms = self.budget_ms();
+        let within_one_tick = Self::share_within(stats, budget_ms);
+        let within_two_ticks = Self::share_within(stats, 2.0 * budget_ms);
+
+        TickBudgetResult {
+            tick_rate_hz: self.tick_rate_hz,
+            budget_ms,
+            within_one_tick,
+            within_two_ticks,
+            score: ONE_TICK_WEIGHT.mul_add(
+                within_one_tick,
+                (1.0 - ONE_TICK_WEIGHT) * within_two_ticks,
+            ) * 100.0,
+        }
+    }
+
+    /// Share of all pings that succeeded with a round trip of at most `limit_ms`
+    fn share_within(stats: &PingStats, limit_ms: f64) -> f64 {
+        let total = u32::try_from(stats.total_pings.max(stats.latencies.len())).unwrap_or(u32::MAX);
+        if total == 0 {
+            return 0.0;
+        }
+
+        // Failed pings are recorded as 0 or as the full timeout
+        let hits = stats
+            .latencies
+            .iter()
+            .filter(|&&rtt| rtt > 0.0 && rtt <= limit_ms)
+            .count();
+        f64::from(u32::try_from(hits).unwrap_or(u32::MAX)) / f64::from(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(latencies: &[f64]) -> PingStats {
+        let mut stats = PingStats::new(latencies.len());
+        stats.latencies = latencies.to_vec();
+        stats.successful_pings = latencies.iter().filter(|&&rtt| rtt > 0.0).count();
+        stats
+    }
+
+    #[test]
+    fn test_budget_from_tick_rate() {
+        let budget = TickBudget::new(64.0).unwrap();
+        assert!((budget.budget_ms() - 15.625).abs() < 1e-9);
+
+        assert!(TickBudget::new(0.0).is_err());
+        assert!(TickBudget::new(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_probabilities_and_score() {
+        let budget = TickBudget::new(64.0).unwrap();
+        // Two within one tick, one more within two ticks, one slow, one failure
+        let result = budget.assess(&stats(&[10.0, 15.0, 25.0, 40.0, 0.0]));
+
+        assert!((result.within_one_tick - 0.4).abs() < 1e-9);
+        assert!((result.within_two_ticks - 0.6).abs() < 1e-9);
+        assert!((result.score - 48.0).abs() < 1e-9);
+
+        let perfect = budget.assess(&stats(&[5.0, 6.0, 7.0]));
+        assert!((perfect.score - 100.0).abs() < 1e-9);
+    }
+}
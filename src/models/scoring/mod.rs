@@ -1,14 +1,21 @@
 //! Scoring algorithms and utilities for network performance evaluation
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
 
-use super::AggregatorState;
+use super::{AggregatorState, LossPattern};
 
+pub mod explain;
+pub mod gaming;
 pub mod normalization;
 pub mod utils;
+pub mod whatif;
 
+pub use explain::{ComponentExplanation, ScoreExplanation};
+pub use gaming::{TickBudget, TickBudgetResult};
 pub use utils::ScoringAdapter;
+pub use whatif::{ProfileRank, WeightProfile, WhatIfComparison, WhatIfRow};
 
 /// Weights for different scoring algorithm components
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -81,6 +88,30 @@ impl Default for ScoreComponents {
     }
 }
 
+/// Raw metrics a score was computed from, before normalization
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScoreInputs {
+    /// Average latency for benchmarks, median for live monitoring
+    pub latency_ms: Option<f64>,
+    /// Jitter
+    pub jitter_ms: f64,
+    /// Failed pings or probes in percent
+    pub loss_percent: f64,
+    /// Latency spread: standard deviation for benchmarks, p90 minus p50 for live monitoring
+    pub spread_ms: f64,
+    /// Successful pings or probes in percent
+    pub availability_percent: f64,
+    /// Median latency while a speed test saturated the link
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loaded_latency_ms: Option<f64>,
+    /// Speed-test download throughput in megabits per second
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_mbps: Option<f64>,
+    /// Speed-test upload throughput in megabits per second
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_mbps: Option<f64>,
+}
+
 /// Comprehensive scoring result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ComprehensiveScoreResult {
@@ -88,6 +119,15 @@ pub struct ComprehensiveScoreResult {
     pub grade: char,
     pub components: ScoreComponents,
     pub suitability: SuitabilityScores,
+    /// Raw metrics behind the components
+    #[serde(default)]
+    pub inputs: ScoreInputs,
+    /// Weights the components were combined with
+    #[serde(default)]
+    pub weights: AlgorithmWeights,
+    /// Metrics derived by `monitoring.scripts`, keyed by script name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub derived: BTreeMap<String, f64>,
 }
 
 /// Suitability scores for different use cases
@@ -134,6 +174,14 @@ pub fn compute_score(state: &AggregatorState, weights: &AlgorithmWeights) -> Com
         consistency_score: calculate_consistency_score_from_state(state),
         availability_score: availability_percent,
     };
+    let inputs = ScoreInputs {
+        latency_ms: Some(avg_latency),
+        jitter_ms: jitter,
+        loss_percent: packet_loss_percent,
+        spread_ms: state.cached_p90_short - state.cached_p50_short,
+        availability_percent,
+        ..ScoreInputs::default()
+    };
 
     // Calculate weighted overall score
     let score = weights.latency * components.latency_score
@@ -146,13 +194,16 @@ pub fn compute_score(state: &AggregatorState, weights: &AlgorithmWeights) -> Com
     let grade = score_to_grade(score);
 
     // Calculate suitability scores
-    let suitability = calculate_suitability_scores(&components);
+    let suitability = calculate_suitability_scores(&components, &state.cached_loss_pattern_short);
 
     ComprehensiveScoreResult {
         score,
         grade,
         components,
         suitability,
+        inputs,
+        weights: weights.clone(),
+        derived: BTreeMap::new(),
     }
 }
 
@@ -163,7 +214,8 @@ fn calculate_consistency_score_from_state(state: &AggregatorState) -> f64 {
     (100.0 - consistency_metric.min(100.0)).max(0.0)
 }
 
-fn score_to_grade(score: f64) -> char {
+/// Letter grade of a 0-100 score
+pub(crate) fn score_to_grade(score: f64) -> char {
     match score {
         s if s >= 90.0 => 'A',
         s if s >= 80.0 => 'B',
@@ -173,13 +225,16 @@ fn score_to_grade(score: f64) -> char {
     }
 }
 
-fn calculate_suitability_scores(components: &ScoreComponents) -> SuitabilityScores {
+fn calculate_suitability_scores(components: &ScoreComponents, loss_pattern: &LossPattern) -> SuitabilityScores {
+    // Real-time media conceals isolated drops but not bursts
+    let burst_factor = loss_pattern.burst_factor();
+
     SuitabilityScores {
         // Gaming prioritizes low latency and jitter
         gaming: (components.latency_score * 0.5 + components.jitter_score * 0.3 + components.packet_loss_score * 0.2),
         
         // Streaming prioritizes consistency and availability
-        streaming: (components.consistency_score * 0.4 + components.availability_score * 0.3 + components.packet_loss_score * 0.3),
+        streaming: (components.consistency_score * 0.4 + components.availability_score * 0.3 + components.packet_loss_score * 0.3) * burst_factor,
         
         // Web browsing is balanced
         web_browsing: (components.latency_score * 0.3 + components.availability_score * 0.3 + components.consistency_score * 0.4),
@@ -188,6 +243,129 @@ fn calculate_suitability_scores(components: &ScoreComponents) -> SuitabilityScor
         file_transfer: (components.availability_score * 0.5 + components.packet_loss_score * 0.3 + components.consistency_score * 0.2),
         
         // VoIP prioritizes low latency, jitter, and packet loss
-        voip: (components.latency_score * 0.4 + components.jitter_score * 0.3 + components.packet_loss_score * 0.3),
+        voip: (components.latency_score * 0.4 + components.jitter_score * 0.3 + components.packet_loss_score * 0.3) * burst_factor,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{PingStats, ProbeRecord};
+    use proptest::prelude::*;
+
+    fn weights() -> impl Strategy<Value = AlgorithmWeights> {
+        (0.0..10.0, 0.0..10.0, 0.0..10.0, 0.0..10.0, 0.01..10.0).prop_map(
+            |(latency, jitter, packet_loss, consistency, availability)| {
+                let mut weights = AlgorithmWeights {
+                    latency,
+                    jitter,
+                    packet_loss,
+                    consistency,
+                    availability,
+                };
+                weights.normalize();
+                weights
+            },
+        )
+    }
+
+    /// State fed with a run of successful (`Some` RTT) and failed probes
+    fn state() -> impl Strategy<Value = AggregatorState> {
+        prop::collection::vec((prop::option::of(0.0..2000.0), 0.01..=1.0), 1..200).prop_map(|probes| {
+            let mut state = AggregatorState::new("endpoint".to_string(), 60, 720);
+            for (rtt_ms, ewma_alpha) in probes {
+                let record = match rtt_ms {
+                    Some(rtt_ms) => ProbeRecord::success("endpoint".to_string(), rtt_ms),
+                    None => ProbeRecord::failure("endpoint".to_string(), None),
+                };
+                state.add_record(record, ewma_alpha);
+            }
+            state
+        })
+    }
+
+    fn stats() -> impl Strategy<Value = PingStats> {
+        (0usize..50)
+            .prop_flat_map(|total| (Just(total), 0..=total, 0.0..2000.0, 0.0..500.0, 0.0..500.0))
+            .prop_map(|(total, successful, avg, jitter, standard_deviation)| {
+                let mut stats = PingStats::new(total);
+                stats.successful_pings = successful;
+                stats.avg = avg;
+                stats.jitter = jitter;
+                stats.standard_deviation = standard_deviation;
+                stats
+            })
+    }
+
+    fn band(score: f64) -> char {
+        ['A', 'B', 'C', 'D']
+            .into_iter()
+            .zip([90.0, 80.0, 70.0, 60.0])
+            .find_map(|(grade, floor)| (score >= floor).then_some(grade))
+            .unwrap_or('F')
     }
-}
\ No newline at end of file
+
+    proptest! {
+        #[test]
+        fn test_scores_stay_in_range_and_match_grades(
+            weights in weights(),
+            state in state(),
+            stats in stats(),
+        ) {
+            let from_state = compute_score(&state, &weights);
+            let from_stats = ScoringAdapter::score_ping_stats(&stats, &weights, "r");
+
+            for result in [from_state, from_stats] {
+                prop_assert!((0.0..=100.0 + 1e-9).contains(&result.score), "{result:?}");
+                prop_assert_eq!(result.grade, band(result.score), "{:?}", result);
+                let components = &result.components;
+                for component in [
+                    components.latency_score,
+                    components.jitter_score,
+                    components.packet_loss_score,
+                    components.consistency_score,
+                    components.availability_score,
+                ] {
+                    prop_assert!((0.0..=100.0).contains(&component), "{result:?}");
+                }
+            }
+        }
+
+        #[test]
+        fn test_worse_metrics_never_score_higher(low in -10.0..1000.0, delta in 0.0..1000.0) {
+            use normalization::{normalize_jitter_ms, normalize_latency_ms, normalize_loss_percent};
+
+            let high = low + delta;
+            prop_assert!(normalize_latency_ms(Some(high)) <= normalize_latency_ms(Some(low)));
+            prop_assert!(normalize_jitter_ms(high) <= normalize_jitter_ms(low));
+            let (low, high) = (low / 10.0, high / 10.0);
+            prop_assert!(normalize_loss_percent(high) <= normalize_loss_percent(low));
+        }
+
+        #[test]
+        fn test_weight_normalization_is_stable(
+            weights in weights(),
+            factor in 0.1..100.0,
+            state in state(),
+        ) {
+            prop_assert!(weights.is_valid(), "{weights:?}");
+
+            // Scaling all weights does not change the normalized weights or the score
+            let mut scaled = AlgorithmWeights {
+                latency: weights.latency * factor,
+                jitter: weights.jitter * factor,
+                packet_loss: weights.packet_loss * factor,
+                consistency: weights.consistency * factor,
+                availability: weights.availability * factor,
+            };
+            scaled.normalize();
+            let mut renormalized = scaled.clone();
+            renormalized.normalize();
+
+            let expected = compute_score(&state, &weights).score;
+            for candidate in [&scaled, &renormalized] {
+                prop_assert!((compute_score(&state, candidate).score - expected).abs() < 1e-9);
+            }
+        }
+    }
+}
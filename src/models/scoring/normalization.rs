@@ -1,4 +1,75 @@
 //! Normalization functions for converting raw metrics to normalized scores (0-100)
+//!
+//! Latency, jitter and loss map onto five bands each. Within a band the score
+//! falls linearly; beyond the last bound it decays towards 0.
+
+use serde::{Deserialize, Serialize};
+
+/// Upper bounds of the excellent, good, fair and poor latency bands, in ms
+pub const LATENCY_BANDS_MS: [f64; 4] = [20.0, 50.0, 100.0, 200.0];
+
+/// Upper bounds of the excellent, good, fair and poor jitter bands, in ms
+pub const JITTER_BANDS_MS: [f64; 4] = [5.0, 15.0, 30.0, 50.0];
+
+/// Upper bounds of the excellent, good, fair and poor loss bands, in percent
+pub const LOSS_BANDS_PERCENT: [f64; 4] = [0.1, 0.5, 2.0, 5.0];
+
+/// Throughput in megabits per second that earns the full throughput score
+pub const FULL_THROUGHPUT_MBPS: f64 = 100.0;
+
+/// Normalization band a raw value falls into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Band {
+    /// Scores 90 to 100
+    Excellent,
+    /// Scores 70 to 90
+    Good,
+    /// Scores 50 to 70
+    Fair,
+    /// Scores 20 to 50
+    Poor,
+    /// Scores below 20
+    Bad,
+}
+
+impl Band {
+    /// Every band, best first
+    pub const ALL: [Self; 5] = [Self::Excellent, Self::Good, Self::Fair, Self::Poor, Self::Bad];
+
+    /// Band of `value` given the upper bounds of the first four bands
+    #[must_use]
+    pub fn of(value: f64, bounds: &[f64; 4]) -> Self {
+        bounds
+            .iter()
+            .position(|&bound| value < bound)
+            .map_or(Self::Bad, |i| Self::ALL[i])
+    }
+
+    /// Raw value range of this band given the band bounds, e.g. `20 to 50`
+    #[must_use]
+    pub fn range(self, bounds: &[f64; 4]) -> String {
+        match self {
+            Self::Excellent => format!("below {}", bounds[0]),
+            Self::Good => format!("{} to {}", bounds[0], bounds[1]),
+            Self::Fair => format!("{} to {}", bounds[1], bounds[2]),
+            Self::Poor => format!("{} to {}", bounds[2], bounds[3]),
+            Self::Bad => format!("{} or more", bounds[3]),
+        }
+    }
+
+    /// Lowercase name
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Excellent => "excellent",
+            Self::Good => "good",
+            Self::Fair => "fair",
+            Self::Poor => "poor",
+            Self::Bad => "bad",
+        }
+    }
+}
 
 /// Normalize latency in milliseconds to a score (0-100)
 /// Lower latency = higher score
@@ -7,12 +78,13 @@ pub fn normalize_latency_ms(latency_ms: Option<f64>) -> f64 {
         Some(latency) if latency <= 0.0 => 100.0,
         Some(latency) => {
             // Excellent: < 20ms, Good: < 50ms, Fair: < 100ms, Poor: < 200ms, Bad: >= 200ms
+            let [excellent, good, fair, poor] = LATENCY_BANDS_MS;
             match latency {
-                l if l < 20.0 => 100.0 - (l / 20.0) * 10.0,  // 90-100
-                l if l < 50.0 => 90.0 - ((l - 20.0) / 30.0) * 20.0,  // 70-90
-                l if l < 100.0 => 70.0 - ((l - 50.0) / 50.0) * 20.0,  // 50-70
-                l if l < 200.0 => 50.0 - ((l - 100.0) / 100.0) * 30.0,  // 20-50
-                _ => (200.0 / latency).min(20.0),  // 0-20
+                l if l < excellent => 100.0 - (l / excellent) * 10.0,  // 90-100
+                l if l < good => 90.0 - ((l - excellent) / (good - excellent)) * 20.0,  // 70-90
+                l if l < fair => 70.0 - ((l - good) / (fair - good)) * 20.0,  // 50-70
+                l if l < poor => 50.0 - ((l - fair) / (poor - fair)) * 30.0,  // 20-50
+                _ => (poor / latency).min(20.0),  // 0-20
             }
         }
         None => 0.0,
@@ -27,12 +99,13 @@ pub fn normalize_jitter_ms(jitter_ms: f64) -> f64 {
     }
     
     // Excellent: < 5ms, Good: < 15ms, Fair: < 30ms, Poor: < 50ms, Bad: >= 50ms
+    let [excellent, good, fair, poor] = JITTER_BANDS_MS;
     match jitter_ms {
-        j if j < 5.0 => 100.0 - (j / 5.0) * 10.0,  // 90-100
-        j if j < 15.0 => 90.0 - ((j - 5.0) / 10.0) * 20.0,  // 70-90
-        j if j < 30.0 => 70.0 - ((j - 15.0) / 15.0) * 20.0,  // 50-70
-        j if j < 50.0 => 50.0 - ((j - 30.0) / 20.0) * 30.0,  // 20-50
-        _ => (50.0 / jitter_ms).min(20.0),  // 0-20
+        j if j < excellent => 100.0 - (j / excellent) * 10.0,  // 90-100
+        j if j < good => 90.0 - ((j - excellent) / (good - excellent)) * 20.0,  // 70-90
+        j if j < fair => 70.0 - ((j - good) / (fair - good)) * 20.0,  // 50-70
+        j if j < poor => 50.0 - ((j - fair) / (poor - fair)) * 30.0,  // 20-50
+        _ => (poor / jitter_ms).min(20.0),  // 0-20
     }
 }
 
@@ -43,13 +116,14 @@ pub fn normalize_loss_percent(loss_percent: f64) -> f64 {
         return 100.0;
     }
     
-    // Excellent: 0%, Good: < 0.1%, Fair: < 0.5%, Poor: < 2%, Bad: >= 2%
+    // Excellent: < 0.1%, Good: < 0.5%, Fair: < 2%, Poor: < 5%, Bad: >= 5%
+    let [excellent, good, fair, poor] = LOSS_BANDS_PERCENT;
     match loss_percent {
-        l if l < 0.1 => 100.0 - (l / 0.1) * 10.0,  // 90-100
-        l if l < 0.5 => 90.0 - ((l - 0.1) / 0.4) * 20.0,  // 70-90
-        l if l < 2.0 => 70.0 - ((l - 0.5) / 1.5) * 20.0,  // 50-70
-        l if l < 5.0 => 50.0 - ((l - 2.0) / 3.0) * 30.0,  // 20-50
-        _ => (5.0 / loss_percent).min(20.0),  // 0-20
+        l if l < excellent => 100.0 - (l / excellent) * 10.0,  // 90-100
+        l if l < good => 90.0 - ((l - excellent) / (good - excellent)) * 20.0,  // 70-90
+        l if l < fair => 70.0 - ((l - good) / (fair - good)) * 20.0,  // 50-70
+        l if l < poor => 50.0 - ((l - fair) / (poor - fair)) * 30.0,  // 20-50
+        _ => (poor / loss_percent).min(20.0),  // 0-20
     }
 }
 
@@ -58,6 +132,13 @@ pub fn normalize_consistency_score(consistency: f64) -> f64 {
     consistency.clamp(0.0, 100.0)
 }
 
+/// Normalize throughput in megabits per second to a score (0-100),
+/// rising linearly up to [`FULL_THROUGHPUT_MBPS`]
+#[must_use]
+pub fn normalize_throughput_mbps(mbps: f64) -> f64 {
+    (mbps / FULL_THROUGHPUT_MBPS * 100.0).clamp(0.0, 100.0)
+}
+
 /// Normalize availability percentage (already 0-100, just clamp)
 pub fn normalize_availability_percent(availability: f64) -> f64 {
     availability.clamp(0.0, 100.0)
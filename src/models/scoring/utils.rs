@@ -1,7 +1,87 @@
 //! Utility functions and adapters for scoring operations
 
-use super::{AlgorithmWeights, ComprehensiveScoreResult, ScoreComponents, SuitabilityScores};
-use crate::models::PingStats;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::{AlgorithmWeights, ComprehensiveScoreResult, ScoreComponents, ScoreInputs, SuitabilityScores};
+use crate::error::CloudPingError;
+use crate::models::{LossPattern, PingStats};
+
+/// A scored result as ranked: score, region name, stats and score breakdown
+pub type RankedResult = (f64, String, PingStats, ComprehensiveScoreResult);
+
+/// What a ranking is ordered by
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    /// Overall score, highest first
+    #[default]
+    Score,
+    /// Average latency, lowest first
+    Latency,
+    /// 95th percentile latency, lowest first
+    P95,
+    /// Packet loss, lowest first
+    Loss,
+    /// Gaming suitability, highest first
+    Gaming,
+    /// Provider name, then score within each provider
+    Provider,
+    /// Score per unit of relative cost, highest first; regions without prices last
+    Value,
+}
+
+impl SortKey {
+    /// Every sort key
+    pub const ALL: [Self; 7] = [
+        Self::Score,
+        Self::Latency,
+        Self::P95,
+        Self::Loss,
+        Self::Gaming,
+        Self::Provider,
+        Self::Value,
+    ];
+
+    /// Name used in the config and on the command line
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Score => "score",
+            Self::Latency => "latency",
+            Self::P95 => "p95",
+            Self::Loss => "loss",
+            Self::Gaming => "gaming",
+            Self::Provider => "provider",
+            Self::Value => "value",
+        }
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = CloudPingError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|key| key.name().eq_ignore_ascii_case(value))
+            .ok_or_else(|| {
+                let known: Vec<&str> = Self::ALL.iter().map(|key| key.name()).collect();
+                CloudPingError::validation(
+                    "sort_by",
+                    format!("unknown sort key '{value}', expected one of {}", known.join(", ")),
+                )
+            })
+    }
+}
 
 /// Adapter for scoring operations on different data types
 pub struct ScoringAdapter;
@@ -28,13 +108,34 @@ impl ScoringAdapter {
             + weights.availability * components.availability_score;
 
         let grade = Self::score_to_grade(score);
-        let suitability = Self::calculate_suitability_scores(&components);
+        let speed_test = stats.speed_test.as_ref();
+        let download_mbps = speed_test.and_then(|result| result.download_mbps);
+        let mut suitability = Self::calculate_suitability_scores(&components, &stats.loss_pattern);
+        if let Some(mbps) = download_mbps {
+            // Bulk transfers and streams need bandwidth more than anything the pings show
+            let throughput_score = super::normalization::normalize_throughput_mbps(mbps);
+            suitability.file_transfer = (suitability.file_transfer + throughput_score) / 2.0;
+            suitability.streaming = (suitability.streaming + throughput_score) / 2.0;
+        }
+        let inputs = ScoreInputs {
+            latency_ms: Some(stats.avg),
+            jitter_ms: stats.jitter,
+            loss_percent: Self::loss_percent(stats),
+            spread_ms: stats.standard_deviation,
+            availability_percent: Self::calculate_availability_score_from_stats(stats),
+            loaded_latency_ms: speed_test.and_then(|result| result.loaded_latency_ms),
+            download_mbps,
+            upload_mbps: speed_test.and_then(|result| result.upload_mbps),
+        };
 
         ComprehensiveScoreResult {
             score,
             grade,
             components,
             suitability,
+            inputs,
+            weights: weights.clone(),
+            derived: std::collections::BTreeMap::new(),
         }
     }
 
@@ -42,7 +143,7 @@ impl ScoringAdapter {
     pub fn get_sorted_results(
         results: &[(String, PingStats)],
         weights: &AlgorithmWeights,
-    ) -> Vec<(f64, String, PingStats, ComprehensiveScoreResult)> {
+    ) -> Vec<RankedResult> {
         let mut scored_results: Vec<_> = results
             .iter()
             .map(|(name, stats)| {
@@ -52,31 +153,79 @@ impl ScoringAdapter {
             })
             .collect();
 
-        // Sort by score descending
-        scored_results.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        // Sort by score descending, higher-priority regions first on equal scores
+        scored_results.sort_by(|a, b| {
+            b.0.partial_cmp(&a.0)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| {
+                    b.2.priority
+                        .partial_cmp(&a.2.priority)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+        });
         scored_results
     }
 
+    /// Get results sorted by `key`, with equal keys in score order
+    ///
+    /// Regions without a successful ping sort last by latency and p95.
+    #[must_use]
+    pub fn get_sorted_results_by(
+        results: &[(String, PingStats)],
+        weights: &AlgorithmWeights,
+        key: SortKey,
+    ) -> Vec<RankedResult> {
+        let mut ranked = Self::get_sorted_results(results, weights);
+        let reached = |stats: &PingStats, latency: f64| {
+            if stats.is_successful() {
+                latency
+            } else {
+                f64::INFINITY
+            }
+        };
+        match key {
+            SortKey::Score => {}
+            SortKey::Latency => sort_ascending_by(&mut ranked, |r| reached(&r.2, r.2.avg)),
+            SortKey::P95 => sort_ascending_by(&mut ranked, |r| reached(&r.2, r.2.percentile_95())),
+            SortKey::Loss => sort_ascending_by(&mut ranked, |r| r.2.packet_loss),
+            SortKey::Gaming => sort_ascending_by(&mut ranked, |r| -r.3.suitability.gaming),
+            // Regions without a provider go last
+            SortKey::Provider => ranked.sort_by(|a, b| {
+                (a.2.provider.is_empty(), &a.2.provider).cmp(&(b.2.provider.is_empty(), &b.2.provider))
+            }),
+            SortKey::Value => sort_ascending_by(&mut ranked, |r| {
+                -r.2.cost.price_performance(r.0).unwrap_or(f64::NEG_INFINITY)
+            }),
+        }
+        ranked
+    }
+
+    /// Scores the average latency, averaged with the latency under load
+    /// when a speed test measured it
     fn calculate_latency_score_from_stats(stats: &PingStats) -> f64 {
-        super::normalization::normalize_latency_ms(Some(stats.avg))
+        let idle = super::normalization::normalize_latency_ms(Some(stats.avg));
+        stats
+            .speed_test
+            .as_ref()
+            .and_then(|result| result.loaded_latency_ms)
+            .map_or(idle, |loaded| (idle + super::normalization::normalize_latency_ms(Some(loaded))) / 2.0)
     }
 
+    /// Scores the jitter recorded on the stats, computed with the configured algorithm
     fn calculate_jitter_score_from_stats(stats: &PingStats) -> f64 {
-        let jitter = if stats.max > stats.min {
-            stats.max - stats.min
-        } else {
-            0.0
-        };
-        super::normalization::normalize_jitter_ms(jitter)
+        super::normalization::normalize_jitter_ms(stats.jitter)
     }
 
     fn calculate_packet_loss_score_from_stats(stats: &PingStats) -> f64 {
-        let loss_percent = if stats.total_pings > 0 {
+        super::normalization::normalize_loss_percent(Self::loss_percent(stats))
+    }
+
+    fn loss_percent(stats: &PingStats) -> f64 {
+        if stats.total_pings > 0 {
             ((stats.total_pings - stats.successful_pings) as f64 / stats.total_pings as f64) * 100.0
         } else {
             0.0
-        };
-        super::normalization::normalize_loss_percent(loss_percent)
+        }
     }
 
     fn calculate_consistency_score_from_stats(stats: &PingStats) -> f64 {
@@ -109,13 +258,16 @@ impl ScoringAdapter {
         }
     }
 
-    fn calculate_suitability_scores(components: &ScoreComponents) -> SuitabilityScores {
+    fn calculate_suitability_scores(components: &ScoreComponents, loss_pattern: &LossPattern) -> SuitabilityScores {
+        // Real-time media conceals isolated drops but not bursts
+        let burst_factor = loss_pattern.burst_factor();
+
         SuitabilityScores {
             // Gaming prioritizes low latency and jitter
             gaming: (components.latency_score * 0.5 + components.jitter_score * 0.3 + components.packet_loss_score * 0.2),
             
             // Streaming prioritizes consistency and availability
-            streaming: (components.consistency_score * 0.4 + components.availability_score * 0.3 + components.packet_loss_score * 0.3),
+            streaming: (components.consistency_score * 0.4 + components.availability_score * 0.3 + components.packet_loss_score * 0.3) * burst_factor,
             
             // Web browsing is balanced
             web_browsing: (components.latency_score * 0.3 + components.availability_score * 0.3 + components.consistency_score * 0.4),
@@ -124,11 +276,21 @@ impl ScoringAdapter {
             file_transfer: (components.availability_score * 0.5 + components.packet_loss_score * 0.3 + components.consistency_score * 0.2),
             
             // VoIP prioritizes low latency, jitter, and packet loss
-            voip: (components.latency_score * 0.4 + components.jitter_score * 0.3 + components.packet_loss_score * 0.3),
+            voip: (components.latency_score * 0.4 + components.jitter_score * 0.3 + components.packet_loss_score * 0.3) * burst_factor,
         }
     }
 }
 
+/// Stable sort by a metric computed once per result, lowest first
+fn sort_ascending_by(ranked: &mut Vec<RankedResult>, metric: impl Fn(&RankedResult) -> f64) {
+    let mut keyed: Vec<(f64, RankedResult)> = ranked
+        .drain(..)
+        .map(|result| (metric(&result), result))
+        .collect();
+    keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+    ranked.extend(keyed.into_iter().map(|(_, result)| result));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -179,4 +341,111 @@ mod tests {
         assert_eq!(sorted[1].1, "bad");
         assert!(sorted[0].0 > sorted[1].0); // First should have higher score
     }
+
+    #[test]
+    fn test_priority_breaks_score_ties() {
+        let mut stats = PingStats::new(10);
+        stats.avg = 20.0;
+        stats.successful_pings = 10;
+        let mut important = stats.clone();
+        important.priority = 2.0;
+
+        let results = vec![
+            ("ordinary".to_string(), stats),
+            ("important".to_string(), important),
+        ];
+        let sorted = ScoringAdapter::get_sorted_results(&results, &AlgorithmWeights::default());
+        assert!((sorted[0].0 - sorted[1].0).abs() < f64::EPSILON);
+        assert_eq!(sorted[0].1, "important");
+    }
+
+    #[test]
+    fn test_sorted_results_by_key() {
+        let region = |avg: f64, p95: f64, loss: f64, provider: &str| {
+            let mut stats = PingStats::new(20);
+            stats.successful_pings = 20;
+            stats.avg = avg;
+            stats.packet_loss = loss;
+            stats.latencies = vec![avg; 19];
+            stats.latencies.push(p95);
+            stats.provider = provider.to_string();
+            stats
+        };
+        let mut unreachable = region(0.0, 0.0, 100.0, "");
+        unreachable.successful_pings = 0;
+        unreachable.latencies.clear();
+        let mut results = vec![
+            ("steady".to_string(), region(40.0, 45.0, 0.0, "GCP")),
+            ("spiky".to_string(), region(30.0, 400.0, 0.0, "AWS")),
+            ("lossy".to_string(), region(20.0, 25.0, 10.0, "GCP")),
+            ("down".to_string(), unreachable),
+        ];
+        results[0].1.cost.price_index = Some(0.5);
+        results[2].1.cost.price_index = Some(2.0);
+        let weights = AlgorithmWeights::default();
+        let order = |key| -> Vec<String> {
+            ScoringAdapter::get_sorted_results_by(&results, &weights, key)
+                .into_iter()
+                .map(|(_, name, _, _)| name)
+                .collect()
+        };
+
+        assert_eq!(order(SortKey::Latency), ["lossy", "spiky", "steady", "down"]);
+        assert_eq!(order(SortKey::P95), ["lossy", "steady", "spiky", "down"]);
+        assert_eq!(order(SortKey::Loss)[2..], ["lossy", "down"]);
+        assert_eq!(order(SortKey::Provider)[0], "spiky");
+        assert_eq!(order(SortKey::Provider)[3], "down");
+        assert_eq!(order(SortKey::Value)[..2], ["steady", "lossy"]);
+        assert_eq!(
+            order(SortKey::Score),
+            ScoringAdapter::get_sorted_results(&results, &weights)
+                .into_iter()
+                .map(|(_, name, _, _)| name)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!("P95".parse::<SortKey>().unwrap(), SortKey::P95);
+        assert!("speed".parse::<SortKey>().is_err());
+    }
+
+    #[test]
+    fn test_speed_tests_count_towards_the_score() {
+        let mut stats = PingStats::new(10);
+        stats.successful_pings = 10;
+        stats.avg = 15.0;
+        let weights = AlgorithmWeights::default();
+        let idle = ScoringAdapter::score_ping_stats(&stats, &weights, "test");
+
+        stats.speed_test = Some(crate::models::SpeedTestResult {
+            download_mbps: Some(20.0),
+            idle_latency_ms: Some(15.0),
+            loaded_latency_ms: Some(150.0),
+            ..Default::default()
+        });
+        let loaded = ScoringAdapter::score_ping_stats(&stats, &weights, "test");
+        assert!(loaded.components.latency_score < idle.components.latency_score);
+        assert!(loaded.score < idle.score);
+        assert!((loaded.suitability.file_transfer - (idle.suitability.file_transfer + 20.0) / 2.0).abs() < 1e-9);
+        assert_eq!(loaded.inputs.loaded_latency_ms, Some(150.0));
+        assert!(loaded.explain().components[0].rule.ends_with("poor band under load"));
+    }
+
+    #[test]
+    fn test_burst_loss_lowers_realtime_suitability() {
+        let mut stats = PingStats::new(10);
+        stats.successful_pings = 8;
+        stats.avg = 30.0;
+        stats.min = 25.0;
+        stats.max = 35.0;
+
+        let weights = AlgorithmWeights::default();
+        stats.loss_pattern = LossPattern::from_outcomes([true, true, true, true, false, true, true, true, true, false]);
+        let isolated = ScoringAdapter::score_ping_stats(&stats, &weights, "test");
+        stats.loss_pattern = LossPattern::from_outcomes([true, true, true, true, true, true, true, true, false, false]);
+        let burst = ScoringAdapter::score_ping_stats(&stats, &weights, "test");
+
+        assert!(burst.suitability.voip < isolated.suitability.voip);
+        assert!(burst.suitability.streaming < isolated.suitability.streaming);
+        assert!((burst.suitability.gaming - isolated.suitability.gaming).abs() < f64::EPSILON);
+        assert!((burst.score - isolated.score).abs() < f64::EPSILON);
+    }
 }
\ No newline at end of file
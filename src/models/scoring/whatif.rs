@@ -0,0 +1,264 @@
+//! What-if comparison of scoring weights
+//!
+//! Re-scores one result set under several weight profiles at once, so the
+//! effect of new [`AlgorithmWeights`] on the ranking can be seen without
+//! running the network tests again. The first profile is the baseline that
+//! rank changes are measured against.
+
+use std::cmp::Ordering;
+use std::fmt::{self, Write as _};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::{AlgorithmWeights, ScoringAdapter};
+use crate::collector::csv_field;
+use crate::error::CloudPingError;
+use crate::models::PingStats;
+
+/// Named candidate weights
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeightProfile {
+    /// Name shown in the comparison
+    pub name: String,
+    /// Weights, normalized to sum to 1
+    pub weights: AlgorithmWeights,
+}
+
+impl WeightProfile {
+    /// Profile with `weights` normalized to sum to 1
+    #[must_use]
+    pub fn new(name: impl Into<String>, mut weights: AlgorithmWeights) -> Self {
+        if !weights.is_valid() {
+            weights.normalize();
+        }
+        Self {
+            name: name.into(),
+            weights,
+        }
+    }
+}
+
+impl fmt::Display for WeightProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let w = &self.weights;
+        write!(
+            f,
+            "{}={:.2},{:.2},{:.2},{:.2},{:.2}",
+            self.name, w.latency, w.jitter, w.packet_loss, w.consistency, w.availability
+        )
+    }
+}
+
+impl FromStr for WeightProfile {
+    type Err = CloudPingError;
+
+    /// Parse `NAME=LATENCY,JITTER,LOSS,CONSISTENCY,AVAILABILITY`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = |message: &str| {
+            CloudPingError::validation(
+                "weights",
+                format!("'{value}': {message}, expected NAME=LATENCY,JITTER,LOSS,CONSISTENCY,AVAILABILITY"),
+            )
+        };
+        let (name, weights) = value
+            .split_once('=')
+            .filter(|(name, _)| !name.trim().is_empty())
+            .ok_or_else(|| invalid("missing name"))?;
+        let parsed = weights
+            .split(',')
+            .map(|weight| weight.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| invalid("weights must be numbers"))?;
+        let [latency, jitter, packet_loss, consistency, availability] = parsed[..] else {
+            return Err(invalid("five weights are needed"));
+        };
+        if parsed.iter().any(|weight| *weight < 0.0) || parsed.iter().sum::<f64>() <= 0.0 {
+            return Err(invalid("weights must not be negative and not all zero"));
+        }
+        Ok(Self::new(
+            name.trim(),
+            AlgorithmWeights {
+                latency,
+                jitter,
+                packet_loss,
+                consistency,
+                availability,
+            },
+        ))
+    }
+}
+
+/// Place of a region under one weight profile
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ProfileRank {
+    /// Position in the ranking, starting at 1
+    pub rank: usize,
+    /// Score under the profile
+    pub score: f64,
+    /// Letter grade of the score
+    pub grade: char,
+}
+
+/// One region's place under every profile
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WhatIfRow {
+    /// Region name
+    pub region: String,
+    /// Rank and score per profile, in profile order
+    pub ranks: Vec<ProfileRank>,
+}
+
+impl WhatIfRow {
+    /// Places moved up under `profile` compared with the baseline; negative when moved down
+    #[must_use]
+    pub fn rank_shift(&self, profile: usize) -> isize {
+        let (Some(baseline), Some(candidate)) = (self.ranks.first(), self.ranks.get(profile)) else {
+            return 0;
+        };
+        // Ranks are bounded by the number of regions, far below isize::MAX
+        #[allow(clippy::cast_possible_wrap)]
+        let shift = baseline.rank as isize - candidate.rank as isize;
+        shift
+    }
+}
+
+/// Rankings of one result set under several weight profiles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhatIfComparison {
+    /// Profiles compared; the first is the baseline
+    pub profiles: Vec<WeightProfile>,
+    /// Regions in baseline rank order
+    pub rows: Vec<WhatIfRow>,
+}
+
+impl WhatIfComparison {
+    /// Best region under each profile, in profile order
+    #[must_use]
+    pub fn leaders(&self) -> Vec<&str> {
+        (0..self.profiles.len())
+            .filter_map(|profile| {
+                self.rows
+                    .iter()
+                    .find(|row| row.ranks[profile].rank == 1)
+                    .map(|row| row.region.as_str())
+            })
+            .collect()
+    }
+
+    /// Regions whose rank differs from the baseline under `profile`
+    #[must_use]
+    pub fn moved(&self, profile: usize) -> usize {
+        self.rows.iter().filter(|row| row.rank_shift(profile) != 0).count()
+    }
+
+    /// One CSV line per region and profile, with a header row
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("region,profile,rank,score,grade,rank_shift\n");
+        for row in &self.rows {
+            for (i, (profile, rank)) in self.profiles.iter().zip(&row.ranks).enumerate() {
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{:.1},{},{}",
+                    csv_field(&row.region),
+                    csv_field(&profile.name),
+                    rank.rank,
+                    rank.score,
+                    rank.grade,
+                    row.rank_shift(i)
+                );
+            }
+        }
+        csv
+    }
+}
+
+impl ScoringAdapter {
+    /// Rank `results` under every profile in `profiles`
+    ///
+    /// Rows follow the ranking under the first profile.
+    #[must_use]
+    pub fn compare_weights(
+        results: &[(String, PingStats)],
+        profiles: &[WeightProfile],
+    ) -> WhatIfComparison {
+        let mut rows: Vec<WhatIfRow> = results
+            .iter()
+            .map(|(name, _)| WhatIfRow {
+                region: name.clone(),
+                ranks: Vec::with_capacity(profiles.len()),
+            })
+            .collect();
+
+        for profile in profiles {
+            let scores: Vec<_> = results
+                .iter()
+                .map(|(name, stats)| Self::score_ping_stats(stats, &profile.weights, name))
+                .collect();
+            // Same order as `get_sorted_results`: score, then priority
+            let mut order: Vec<usize> = (0..results.len()).collect();
+            order.sort_by(|&a, &b| {
+                scores[b]
+                    .score
+                    .partial_cmp(&scores[a].score)
+                    .unwrap_or(Ordering::Equal)
+                    .then_with(|| {
+                        results[b]
+                            .1
+                            .priority
+                            .partial_cmp(&results[a].1.priority)
+                            .unwrap_or(Ordering::Equal)
+                    })
+            });
+            for (rank, index) in order.into_iter().enumerate() {
+                rows[index].ranks.push(ProfileRank {
+                    rank: rank + 1,
+                    score: scores[index].score,
+                    grade: scores[index].grade,
+                });
+            }
+        }
+
+        rows.sort_by_key(|row| row.ranks.first().map_or(usize::MAX, |rank| rank.rank));
+        WhatIfComparison {
+            profiles: profiles.to_vec(),
+            rows,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_weights() {
+        // Fast but lossy against slower and clean
+        let region = |name: &str, avg: f64, successful: usize| {
+            let mut stats = PingStats::new(20);
+            stats.successful_pings = successful;
+            stats.avg = avg;
+            (name.to_string(), stats)
+        };
+        let results = [region("Fast", 10.0, 17), region("Clean", 60.0, 20)];
+        let profiles = [
+            "latency=1,0,0,0,0".parse::<WeightProfile>().unwrap(),
+            "reliable=0,0,1,0,1".parse::<WeightProfile>().unwrap(),
+        ];
+
+        let comparison = ScoringAdapter::compare_weights(&results, &profiles);
+        assert_eq!(comparison.leaders(), ["Fast", "Clean"]);
+        assert_eq!(comparison.rows[0].region, "Fast");
+        assert_eq!(comparison.rows[0].rank_shift(1), -1);
+        assert_eq!(comparison.rows[1].rank_shift(1), 1);
+        assert_eq!(comparison.moved(0), 0);
+        assert_eq!(comparison.moved(1), 2);
+        assert!(comparison.to_csv().contains("\nFast,reliable,2,"));
+
+        assert!((profiles[1].weights.packet_loss - 0.5).abs() < 1e-9);
+        assert!("=1,0,0,0,0".parse::<WeightProfile>().is_err());
+        assert!("x=1,0,0".parse::<WeightProfile>().is_err());
+        assert!("x=0,0,0,0,0".parse::<WeightProfile>().is_err());
+    }
+}
@@ -0,0 +1,157 @@
+//! Time-of-day analysis of test history
+//!
+//! Buckets historical runs by the hour they ran in, so scheduled benchmarks
+//! reveal peak-hour congestion that a single linear trend averages away.
+
+use std::path::Path;
+
+use chrono::{FixedOffset, Timelike};
+use serde::{Deserialize, Serialize};
+
+use super::stats::TestHistory;
+use crate::error::{CloudPingError, Result};
+
+/// Average performance of the runs that started in one hour of the day
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HourlyLatency {
+    /// Hour of the day (0-23) in the offset the profile was built for
+    pub hour: u32,
+    /// Number of runs in this hour
+    pub runs: usize,
+    /// Mean of the runs' average latency, over runs that reached the region
+    pub avg_latency_ms: f64,
+    /// Mean packet loss percentage over all runs
+    pub avg_packet_loss: f64,
+}
+
+impl TestHistory {
+    /// Per-hour averages for hours with at least one successful run, ordered by hour
+    #[must_use]
+    pub fn hourly_profile(&self, offset: FixedOffset) -> Vec<HourlyLatency> {
+        let mut latencies: [Vec<f64>; 24] = std::array::from_fn(|_| Vec::new());
+        let mut losses: [Vec<f64>; 24] = std::array::from_fn(|_| Vec::new());
+
+        for stats in &self.historical_data {
+            let hour = stats.test_time.with_timezone(&offset).hour() as usize;
+            losses[hour].push(stats.packet_loss);
+            if stats.is_successful() {
+                latencies[hour].push(stats.avg);
+            }
+        }
+
+        latencies
+            .iter()
+            .zip(&losses)
+            .zip(0u32..)
+            .filter(|((latency, _), _)| !latency.is_empty())
+            .map(|((latency, loss), hour)| HourlyLatency {
+                hour,
+                runs: loss.len(),
+                avg_latency_ms: statistical::mean(latency),
+                avg_packet_loss: statistical::mean(loss),
+            })
+            .collect()
+    }
+
+    /// Hour with the highest average latency, once runs cover at least two hours
+    #[must_use]
+    pub fn worst_hour(&self, offset: FixedOffset) -> Option<HourlyLatency> {
+        let profile = self.hourly_profile(offset);
+        if profile.len() < 2 {
+            return None;
+        }
+        profile
+            .into_iter()
+            .max_by(|a, b| a.avg_latency_ms.total_cmp(&b.avg_latency_ms))
+    }
+
+    /// Hour with the lowest average latency, once runs cover at least two hours
+    #[must_use]
+    pub fn best_hour(&self, offset: FixedOffset) -> Option<HourlyLatency> {
+        let profile = self.hourly_profile(offset);
+        if profile.len() < 2 {
+            return None;
+        }
+        profile
+            .into_iter()
+            .min_by(|a, b| a.avg_latency_ms.total_cmp(&b.avg_latency_ms))
+    }
+
+    /// Load histories saved with [`TestHistory::save_all`], starting empty if the file does not exist
+    pub fn load_all(path: &Path) -> Result<Vec<Self>> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(|e| {
+            CloudPingError::data_loading(format!("Invalid test history {}: {e}", path.display()))
+        })
+    }
+
+    /// Persist histories as JSON
+    pub fn save_all(histories: &[Self], path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(histories)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PingStats;
+    use chrono::{DateTime, Utc};
+
+    fn run(at: &str, avg: f64) -> PingStats {
+        let mut stats = PingStats::new(10);
+        stats.successful_pings = 10;
+        stats.avg = avg;
+        stats.test_time = DateTime::parse_from_rfc3339(at)
+            .unwrap()
+            .with_timezone(&Utc);
+        stats
+    }
+
+    #[test]
+    fn test_hourly_profile_and_worst_hour() {
+        let mut history = TestHistory::new(
+            "eu".to_string(),
+            "Frankfurt".to_string(),
+            "https://eu.example.com".to_string(),
+        );
+        history.add_test_result(run("2026-10-01T08:10:00Z", 20.0));
+        history.add_test_result(run("2026-10-02T08:40:00Z", 22.0));
+        history.add_test_result(run("2026-10-01T19:05:00Z", 60.0));
+
+        let utc = FixedOffset::east_opt(0).unwrap();
+        let profile = history.hourly_profile(utc);
+        assert_eq!(profile.len(), 2);
+        assert_eq!(profile[0].hour, 8);
+        assert_eq!(profile[0].runs, 2);
+        assert!((profile[0].avg_latency_ms - 21.0).abs() < 1e-9);
+
+        assert_eq!(history.worst_hour(utc).unwrap().hour, 19);
+        assert_eq!(history.best_hour(utc).unwrap().hour, 8);
+
+        // The same runs seen from UTC+2
+        let cest = FixedOffset::east_opt(2 * 3600).unwrap();
+        assert_eq!(history.worst_hour(cest).unwrap().hour, 21);
+    }
+
+    #[test]
+    fn test_single_hour_has_no_worst_hour() {
+        let mut history = TestHistory::new(
+            "eu".to_string(),
+            "Frankfurt".to_string(),
+            "https://eu.example.com".to_string(),
+        );
+        history.add_test_result(run("2026-10-01T08:10:00Z", 20.0));
+
+        assert!(history
+            .worst_hour(FixedOffset::east_opt(0).unwrap())
+            .is_none());
+    }
+}
@@ -0,0 +1,231 @@
+//! Soak tests over a fixed stretch of wall-clock time
+//!
+//! A benchmark sends a fixed number of pings as fast as it can, which shows
+//! how a region performs for a few seconds. A soak test instead pings each
+//! region at a steady rate for a set duration, say every 5 seconds for 30
+//! minutes, and groups the pings into time buckets. Per-bucket percentiles
+//! and availability show when a region degraded during the test rather than
+//! averaging a bad minute away.
+
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::failure::FailureKind;
+use super::utils::percentiles;
+use crate::collector::csv_field;
+
+/// One ping of a soak test
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SoakSample {
+    /// Milliseconds after the start of the test the ping was sent
+    pub offset_ms: u64,
+    /// Round-trip time in milliseconds, `None` if the ping failed
+    pub latency_ms: Option<f64>,
+    /// Why the ping failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<FailureKind>,
+}
+
+/// Pings of a soak test sent within one bucket of time
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SoakBucket {
+    /// Seconds after the start of the test the bucket begins
+    pub start_s: u64,
+    /// Pings sent in the bucket
+    pub pings: usize,
+    /// Pings that succeeded
+    pub successful: usize,
+    /// Median latency of the successful pings in milliseconds
+    pub p50_ms: Option<f64>,
+    /// 95th percentile latency of the successful pings in milliseconds
+    pub p95_ms: Option<f64>,
+}
+
+impl SoakBucket {
+    /// Share of the bucket's pings that succeeded in percent, absent without pings
+    #[must_use]
+    pub fn availability(&self) -> Option<f64> {
+        #[allow(clippy::cast_precision_loss)] // ping counts are far below 2^52
+        let availability =
+            (self.pings > 0).then(|| self.successful as f64 / self.pings as f64 * 100.0);
+        availability
+    }
+}
+
+/// Soak test of one region
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SoakResult {
+    /// Region name
+    pub region: String,
+    /// Region URL
+    pub url: String,
+    /// When the test started
+    pub started: DateTime<Utc>,
+    /// Time between pings
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    /// Width of the time buckets
+    #[serde(with = "humantime_serde")]
+    pub bucket_width: Duration,
+    /// Pings in the order they were sent
+    pub samples: Vec<SoakSample>,
+    /// The test was cancelled before its duration was up
+    #[serde(default)]
+    pub incomplete: bool,
+}
+
+impl SoakResult {
+    /// Samples grouped into consecutive buckets of `bucket_width` from the
+    /// start of the test, including buckets no ping fell in
+    #[must_use]
+    pub fn buckets(&self) -> Vec<SoakBucket> {
+        let width_ms = u64::try_from(self.bucket_width.as_millis())
+            .unwrap_or(u64::MAX)
+            .max(1);
+        let Some(last) = self.samples.iter().map(|sample| sample.offset_ms).max() else {
+            return Vec::new();
+        };
+        let count = usize::try_from(last / width_ms).unwrap_or(usize::MAX) + 1;
+        let mut grouped: Vec<(usize, Vec<f64>)> = vec![(0, Vec::new()); count];
+        for sample in &self.samples {
+            let index = usize::try_from(sample.offset_ms / width_ms).unwrap_or(usize::MAX);
+            let (pings, latencies) = &mut grouped[index];
+            *pings += 1;
+            latencies.extend(sample.latency_ms);
+        }
+
+        grouped
+            .into_iter()
+            .zip(0u64..)
+            .map(|((pings, latencies), index)| {
+                let [p50_ms, p95_ms] = Self::p50_p95(&latencies);
+                SoakBucket {
+                    start_s: index * width_ms / 1000,
+                    pings,
+                    successful: latencies.len(),
+                    p50_ms,
+                    p95_ms,
+                }
+            })
+            .collect()
+    }
+
+    /// Share of all pings that succeeded in percent, absent without pings
+    #[must_use]
+    pub fn availability(&self) -> Option<f64> {
+        let successful = self.latencies().len();
+        #[allow(clippy::cast_precision_loss)] // ping counts are far below 2^52
+        let availability = (!self.samples.is_empty())
+            .then(|| successful as f64 / self.samples.len() as f64 * 100.0);
+        availability
+    }
+
+    /// Median and 95th percentile latency over the whole test
+    #[must_use]
+    pub fn p50_p95_ms(&self) -> [Option<f64>; 2] {
+        Self::p50_p95(&self.latencies())
+    }
+
+    /// Highest per-bucket 95th percentile, the worst stretch of the test
+    #[must_use]
+    pub fn worst_p95_ms(&self) -> Option<f64> {
+        self.buckets()
+            .iter()
+            .filter_map(|bucket| bucket.p95_ms)
+            .max_by(f64::total_cmp)
+    }
+
+    /// One CSV line per bucket of every result, with a header row
+    #[must_use]
+    pub fn to_csv(results: &[Self]) -> String {
+        let optional = |value: Option<f64>| value.map_or_else(String::new, |value| format!("{value:.1}"));
+        let mut csv = String::from(
+            "region,bucket_start_s,pings,successful,availability_percent,p50_ms,p95_ms\n",
+        );
+        for result in results {
+            for bucket in result.buckets() {
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{},{},{},{}",
+                    csv_field(&result.region),
+                    bucket.start_s,
+                    bucket.pings,
+                    bucket.successful,
+                    optional(bucket.availability()),
+                    optional(bucket.p50_ms),
+                    optional(bucket.p95_ms)
+                );
+            }
+        }
+        csv
+    }
+
+    fn latencies(&self) -> Vec<f64> {
+        self.samples.iter().filter_map(|sample| sample.latency_ms).collect()
+    }
+
+    fn p50_p95(latencies: &[f64]) -> [Option<f64>; 2] {
+        if latencies.is_empty() {
+            return [None, None];
+        }
+        match percentiles(latencies, &[50.0, 95.0])[..] {
+            [p50, p95] => [Some(p50), Some(p95)],
+            _ => [None, None],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(offset_ms: u64, latency_ms: Option<f64>) -> SoakSample {
+        SoakSample {
+            offset_ms,
+            latency_ms,
+            failure: latency_ms.is_none().then_some(FailureKind::Timeout),
+        }
+    }
+
+    #[test]
+    fn test_soak_buckets() {
+        let result = SoakResult {
+            region: "frankfurt".to_string(),
+            url: "https://example.com".to_string(),
+            started: Utc::now(),
+            interval: Duration::from_secs(20),
+            bucket_width: Duration::from_secs(60),
+            samples: vec![
+                sample(0, Some(20.0)),
+                sample(20_000, Some(30.0)),
+                sample(40_000, Some(40.0)),
+                sample(60_000, None),
+                sample(80_000, Some(100.0)),
+                // Nothing answered in the third minute's first ping, nor was anything sent after
+                sample(185_000, None),
+            ],
+            incomplete: false,
+        };
+
+        let buckets = result.buckets();
+        assert_eq!(buckets.len(), 4);
+        assert_eq!((buckets[0].start_s, buckets[0].pings, buckets[0].successful), (0, 3, 3));
+        assert_eq!(buckets[0].p50_ms, Some(30.0));
+        assert_eq!(buckets[1].availability(), Some(50.0));
+        assert_eq!(buckets[1].p95_ms, Some(100.0));
+        assert_eq!((buckets[2].pings, buckets[2].availability()), (0, None));
+        assert_eq!((buckets[3].start_s, buckets[3].availability()), (180, Some(0.0)));
+        assert_eq!(buckets[3].p95_ms, None);
+
+        assert!((result.availability().unwrap() - 400.0 / 6.0).abs() < 1e-9);
+        assert_eq!(result.worst_p95_ms(), Some(100.0));
+        assert_eq!(result.p50_p95_ms()[0], Some(35.0));
+
+        let csv = SoakResult::to_csv(&[result]);
+        assert!(csv.contains("\nfrankfurt,60,2,1,50.0,100.0,100.0\n"));
+        assert!(csv.contains("\nfrankfurt,120,0,0,,,\n"));
+    }
+}
@@ -0,0 +1,188 @@
+//! Speed-test endpoints of regions and what they measured
+//!
+//! A region in the data file may name a speed-test service next to its URL
+//! in its `speed_test` metadata: `cloudflare` for the Cloudflare speed API,
+//! at `https://speed.cloudflare.com` unless `speed_test_url` says otherwise,
+//! or `librespeed` for a `LibreSpeed` server whose backend is at
+//! `speed_test_url`. Such regions are measured for download and upload
+//! throughput and for latency while the download saturates the link.
+
+use serde::{Deserialize, Serialize};
+
+use super::bufferbloat::BufferbloatGrade;
+use super::region::Region;
+use crate::error::{CloudPingError, Result};
+
+/// Region metadata key naming the speed-test service
+pub const SPEED_TEST_METADATA_KEY: &str = "speed_test";
+
+/// Region metadata key holding the base URL of the speed-test service
+pub const SPEED_TEST_URL_METADATA_KEY: &str = "speed_test_url";
+
+/// Base URL of the Cloudflare speed API
+pub const CLOUDFLARE_SPEED_URL: &str = "https://speed.cloudflare.com";
+
+/// Speed-test service a region names
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedTestKind {
+    /// Cloudflare speed API: `__down?bytes=`, `__up`
+    Cloudflare,
+    /// `LibreSpeed` backend: `garbage.php`, `empty.php`
+    LibreSpeed,
+}
+
+impl SpeedTestKind {
+    /// Name used in region metadata
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Cloudflare => "cloudflare",
+            Self::LibreSpeed => "librespeed",
+        }
+    }
+}
+
+/// Speed-test service of one region
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SpeedTestEndpoint {
+    /// Service behind `url`
+    pub kind: SpeedTestKind,
+    /// Base URL of the service, without a trailing slash
+    pub url: String,
+}
+
+impl SpeedTestEndpoint {
+    /// Speed-test endpoint named in the metadata of `region`; `None` when it names none
+    ///
+    /// # Errors
+    /// Returns a validation error for an unknown service, or a `LibreSpeed`
+    /// service without a URL
+    pub fn for_region(region: &Region) -> Result<Option<Self>> {
+        let Some(kind) = region.metadata.get(SPEED_TEST_METADATA_KEY) else {
+            return Ok(None);
+        };
+        let url = region
+            .metadata
+            .get(SPEED_TEST_URL_METADATA_KEY)
+            .map(|url| url.trim().trim_end_matches('/').to_string());
+        let key = || format!("{}.metadata.{SPEED_TEST_METADATA_KEY}", region.name);
+        let (kind, url) = match kind.trim().to_ascii_lowercase().as_str() {
+            "cloudflare" => (
+                SpeedTestKind::Cloudflare,
+                url.unwrap_or_else(|| CLOUDFLARE_SPEED_URL.to_string()),
+            ),
+            "librespeed" => (
+                SpeedTestKind::LibreSpeed,
+                url.ok_or_else(|| {
+                    CloudPingError::validation(key(), format!("librespeed needs `{SPEED_TEST_URL_METADATA_KEY}`"))
+                })?,
+            ),
+            other => {
+                return Err(CloudPingError::validation(
+                    key(),
+                    format!("unknown speed test '{other}', expected cloudflare or librespeed"),
+                ))
+            }
+        };
+        Ok(Some(Self { kind, url }))
+    }
+
+    /// URL serving a download of about `bytes` bytes
+    ///
+    /// `LibreSpeed` serves whole MiB chunks, so its downloads are rounded up to one.
+    #[must_use]
+    pub fn download_url(&self, bytes: u64) -> String {
+        match self.kind {
+            SpeedTestKind::Cloudflare => format!("{}/__down?bytes={bytes}", self.url),
+            SpeedTestKind::LibreSpeed => {
+                format!("{}/garbage.php?ckSize={}", self.url, bytes.div_ceil(1024 * 1024).max(1))
+            }
+        }
+    }
+
+    /// URL accepting an upload
+    #[must_use]
+    pub fn upload_url(&self) -> String {
+        match self.kind {
+            SpeedTestKind::Cloudflare => format!("{}/__up", self.url),
+            SpeedTestKind::LibreSpeed => format!("{}/empty.php", self.url),
+        }
+    }
+
+    /// URL answering with an empty body, timed for latency
+    #[must_use]
+    pub fn ping_url(&self) -> String {
+        match self.kind {
+            SpeedTestKind::Cloudflare => format!("{}/__down?bytes=0", self.url),
+            SpeedTestKind::LibreSpeed => format!("{}/empty.php", self.url),
+        }
+    }
+}
+
+/// What a speed test measured; a measurement that failed is absent
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SpeedTestResult {
+    /// Service that was measured
+    pub kind: Option<SpeedTestKind>,
+    /// Download throughput in megabits per second
+    pub download_mbps: Option<f64>,
+    /// Upload throughput in megabits per second
+    pub upload_mbps: Option<f64>,
+    /// Median latency to the service with the link idle, in milliseconds
+    pub idle_latency_ms: Option<f64>,
+    /// Median latency to the service during the download, in milliseconds
+    pub loaded_latency_ms: Option<f64>,
+}
+
+impl SpeedTestResult {
+    /// Latency the download added, in milliseconds; a large value means
+    /// buffers along the path fill up under load (bufferbloat)
+    #[must_use]
+    pub fn bufferbloat_ms(&self) -> Option<f64> {
+        Some((self.loaded_latency_ms? - self.idle_latency_ms?).max(0.0))
+    }
+
+    /// Bufferbloat grade of the latency the download added
+    #[must_use]
+    pub fn bufferbloat_grade(&self) -> Option<BufferbloatGrade> {
+        self.bufferbloat_ms().map(BufferbloatGrade::of)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speed_test_endpoints_from_region_metadata() {
+        let mut region = Region::new("Frankfurt".to_string(), "https://fra.example.com".to_string()).unwrap();
+        assert_eq!(SpeedTestEndpoint::for_region(&region).unwrap(), None);
+
+        region.metadata.insert(SPEED_TEST_METADATA_KEY.to_string(), "Cloudflare".to_string());
+        let cloudflare = SpeedTestEndpoint::for_region(&region).unwrap().unwrap();
+        assert_eq!(cloudflare.download_url(1000), "https://speed.cloudflare.com/__down?bytes=1000");
+        assert_eq!(cloudflare.ping_url(), "https://speed.cloudflare.com/__down?bytes=0");
+
+        region.metadata.insert(SPEED_TEST_METADATA_KEY.to_string(), "librespeed".to_string());
+        assert!(SpeedTestEndpoint::for_region(&region).is_err());
+        region
+            .metadata
+            .insert(SPEED_TEST_URL_METADATA_KEY.to_string(), "https://ls.example.com/backend/".to_string());
+        let librespeed = SpeedTestEndpoint::for_region(&region).unwrap().unwrap();
+        assert_eq!(librespeed.download_url(10_000_000), "https://ls.example.com/backend/garbage.php?ckSize=10");
+        assert_eq!(librespeed.upload_url(), "https://ls.example.com/backend/empty.php");
+
+        region.metadata.insert(SPEED_TEST_METADATA_KEY.to_string(), "ookla".to_string());
+        assert!(SpeedTestEndpoint::for_region(&region).is_err());
+
+        let result = SpeedTestResult {
+            idle_latency_ms: Some(20.0),
+            loaded_latency_ms: Some(85.0),
+            ..SpeedTestResult::default()
+        };
+        assert_eq!(result.bufferbloat_ms(), Some(65.0));
+        assert_eq!(result.bufferbloat_grade(), Some(BufferbloatGrade::C));
+        assert_eq!(SpeedTestResult::default().bufferbloat_ms(), None);
+    }
+}
@@ -9,7 +9,19 @@ use crate::collection_utils::CollectionUtils;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use super::asn::AsnInfo;
+use super::edge::{EdgePop, EdgePopCount};
+use super::failure::FailureKind;
+use super::jitter::JitterAlgorithm;
+use super::loss::LossPattern;
+use super::metrics::HealthStatus;
+use super::quality::QualityFlag;
+use super::labels::RunLabels;
+use super::pricing::RegionCost;
+use super::region::default_priority;
+use super::route::RoutePath;
 use super::scoring::AlgorithmWeights;
+use super::speed_test::SpeedTestResult;
 use super::utils::generate_uuid;
 
 /// Comprehensive network performance statistics
@@ -34,9 +46,66 @@ pub struct PingStats {
     pub metadata: HashMap<String, String>,
     #[serde(default)]
     pub status_codes: Vec<u16>,
+    /// CDN edge POPs that served the successful pings, most used first
+    #[serde(default)]
+    pub edge_pops: Vec<EdgePopCount>,
+    /// Autonomous system of the tested endpoint, absent without ASN enrichment
+    #[serde(default)]
+    pub asn: Option<AsnInfo>,
     pub dns_resolution_time: Option<f64>,
     pub connection_time: Option<f64>,
     pub tls_handshake_time: Option<f64>,
+    /// Reasons this measurement is considered unreliable
+    #[serde(default)]
+    pub quality_flags: Vec<QualityFlag>,
+    /// Run-length analysis of failed pings
+    #[serde(default)]
+    pub loss_pattern: LossPattern,
+    /// Bytes sent in request lines and headers, retries included
+    #[serde(default)]
+    pub bytes_sent: u64,
+    /// Bytes received in status lines, headers and bodies, retries included
+    #[serde(default)]
+    pub bytes_received: u64,
+    /// The run was cancelled before all pings were sent; `total_pings` counts only those sent
+    #[serde(default)]
+    pub incomplete: bool,
+    /// Most common cause among the failed pings
+    #[serde(default)]
+    pub failure_kind: Option<FailureKind>,
+    /// Priority of the tested region, used to break ties in rankings
+    #[serde(default = "default_priority")]
+    pub priority: f64,
+    /// Provider of the tested region, empty when unknown
+    #[serde(default)]
+    pub provider: String,
+    /// Country of the tested region, empty when unknown
+    #[serde(default)]
+    pub country: String,
+    /// Operational notes on the tested region, e.g. "behind Cloudflare"
+    #[serde(default)]
+    pub notes: Vec<String>,
+    /// Egress price and price index of the tested region, empty when unknown
+    #[serde(default)]
+    pub cost: RegionCost,
+    /// Carbon intensity of the tested region's grid in gCO2/kWh, absent when unknown
+    #[serde(default)]
+    pub carbon_g_per_kwh: Option<f64>,
+    /// Control endpoint latency while this region was tested, in milliseconds
+    #[serde(default)]
+    pub control_latency_ms: Option<f64>,
+    /// Average latency minus `control_latency_ms`, comparable across local conditions
+    #[serde(default)]
+    pub normalized_avg: Option<f64>,
+    /// Throughput and latency under load from the region's speed-test endpoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed_test: Option<SpeedTestResult>,
+    /// Path taken to the region, compared across runs to spot route changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route: Option<RoutePath>,
+    /// Labels of the run this result belongs to (e.g. `isp=comcast`)
+    #[serde(default)]
+    pub labels: RunLabels,
 }
 
 impl PingStats {
@@ -58,9 +127,28 @@ impl PingStats {
             test_duration_ms: 0,
             metadata: CollectionUtils::new_hashmap(),
             status_codes: Vec::new(),
+            edge_pops: Vec::new(),
+            asn: None,
             dns_resolution_time: None,
             connection_time: None,
             tls_handshake_time: None,
+            quality_flags: Vec::new(),
+            loss_pattern: LossPattern::default(),
+            bytes_sent: 0,
+            bytes_received: 0,
+            incomplete: false,
+            failure_kind: None,
+            priority: default_priority(),
+            provider: String::new(),
+            country: String::new(),
+            notes: Vec::new(),
+            cost: RegionCost::default(),
+            carbon_g_per_kwh: None,
+            control_latency_ms: None,
+            normalized_avg: None,
+            speed_test: None,
+            route: None,
+            labels: RunLabels::new(),
         }
     }
 
@@ -70,10 +158,78 @@ impl PingStats {
         stats
     }
 
+    /// Fill min, max, average, packet loss, jitter and standard deviation
+    /// from the round trips of the successful pings, in the order taken
+    ///
+    /// `total_pings` must already count every ping sent. Without successful
+    /// pings the latency figures are zero and `error_message`, if still
+    /// empty, says all pings failed.
+    #[allow(clippy::cast_precision_loss)] // ping counts are far below 2^52
+    pub fn summarize(&mut self, successful_latencies: &[f64], jitter_algorithm: JitterAlgorithm) {
+        self.successful_pings = successful_latencies.len();
+        self.packet_loss = if self.total_pings > 0 {
+            (self.total_pings.saturating_sub(self.successful_pings) as f64 / self.total_pings as f64) * 100.0
+        } else {
+            0.0
+        };
+
+        if successful_latencies.is_empty() {
+            self.min = 0.0;
+            self.max = 0.0;
+            self.avg = 0.0;
+            self.jitter = 0.0;
+            self.standard_deviation = 0.0;
+            if self.error_message.is_empty() {
+                self.error_message = "All ping attempts failed".to_string();
+            }
+            return;
+        }
+
+        self.min = successful_latencies.iter().copied().fold(f64::MAX, f64::min);
+        self.max = successful_latencies.iter().copied().fold(0.0, f64::max);
+        self.avg = successful_latencies.iter().sum::<f64>() / successful_latencies.len() as f64;
+        // Jitter: variation between measurements, per the configured algorithm
+        self.jitter = jitter_algorithm.compute(successful_latencies);
+        // Standard deviation: measure of variability
+        let variance_sum: f64 = successful_latencies
+            .iter()
+            .map(|&latency| (latency - self.avg).powi(2))
+            .sum();
+        self.standard_deviation = (variance_sum / successful_latencies.len() as f64).sqrt();
+    }
+
     pub fn is_successful(&self) -> bool {
         self.successful_pings > 0
     }
 
+    /// Health of the region by the same thresholds as monitored endpoints
+    #[must_use]
+    pub fn health_status(&self) -> HealthStatus {
+        if self.total_pings == 0 {
+            return HealthStatus::Unknown;
+        }
+        let avg_rtt = if self.is_successful() { self.avg } else { f64::INFINITY };
+        HealthStatus::from_metrics(self.packet_loss, avg_rtt, self.jitter)
+    }
+
+    /// CDN edge POP that served most of the successful pings
+    #[must_use]
+    pub fn edge_pop(&self) -> Option<&EdgePop> {
+        self.edge_pops.first().map(|count| &count.edge)
+    }
+
+    /// Total bytes sent and received by the test
+    #[must_use]
+    pub const fn data_usage_bytes(&self) -> u64 {
+        self.bytes_sent + self.bytes_received
+    }
+
+    /// Whether a quality check flagged this measurement as unreliable
+    #[must_use]
+    pub fn is_low_quality(&self) -> bool {
+        !self.quality_flags.is_empty()
+    }
+
     pub fn success_rate(&self) -> f64 {
         if self.total_pings == 0 {
             0.0
@@ -240,10 +396,9 @@ impl TestHistory {
     pub fn add_test_result(&mut self, stats: PingStats) {
         self.historical_data.push(stats);
         
-        // Keep only last 100 results to prevent unbounded growth
-        const MAX_HISTORY_SIZE: usize = 100;
-        if self.historical_data.len() > MAX_HISTORY_SIZE {
-            self.historical_data.drain(0..self.historical_data.len() - MAX_HISTORY_SIZE);
+        // Keep only the last runs to prevent unbounded growth
+        if self.historical_data.len() > Self::MAX_RUNS {
+            self.historical_data.drain(0..self.historical_data.len() - Self::MAX_RUNS);
         }
         
         // Sort by test time to ensure chronological order
@@ -354,6 +509,36 @@ impl TestHistory {
 
         Some(scores.iter().sum::<f64>() / scores.len() as f64)
     }
+
+    /// Runs shown in latency sparklines
+    pub const SPARKLINE_RUNS: usize = 20;
+
+    /// Runs kept per region in memory and in the JSON history file
+    pub const MAX_RUNS: usize = 100;
+
+    /// Average latencies of the last `count` runs that reached the region, oldest first
+    #[must_use]
+    pub fn recent_latencies(&self, count: usize) -> Vec<f64> {
+        let mut latencies: Vec<f64> = self
+            .historical_data
+            .iter()
+            .rev()
+            .filter(|stats| stats.is_successful())
+            .take(count)
+            .map(|stats| stats.avg)
+            .collect();
+        latencies.reverse();
+        latencies
+    }
+
+    /// History of the ranked region `name`, matched by region ID, else by name
+    #[must_use]
+    pub fn find<'a>(histories: &'a [Self], name: &str, stats: &PingStats) -> Option<&'a Self> {
+        stats.region_id.as_ref().map_or_else(
+            || histories.iter().find(|history| history.region_name == name),
+            |id| histories.iter().find(|history| history.region_id == *id),
+        )
+    }
 }
 
 /// Aggregated performance metrics over time
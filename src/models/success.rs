@@ -0,0 +1,177 @@
+//! What counts as a successful probe of an endpoint
+//!
+//! A probe succeeds when its transport does: the connection opened, the
+//! handshake finished, the server answered. For some endpoints that is not
+//! enough. An API answering in two seconds is as good as down for its users,
+//! and a health check redirecting to a login page is not healthy. Endpoint
+//! metadata can tighten success with [`SuccessCriteria`]; records that meet
+//! the transport but not the criteria count as failures toward loss and
+//! availability in the aggregator, keeping their round trip for the latency
+//! metrics. Statuses the criteria list also count as success when the probe
+//! itself would fail them, such as a `401` from an API behind a login.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::probe::ProbeRecord;
+use crate::error::{CloudPingError, Result};
+
+/// Endpoint metadata key holding the slowest successful round trip, in milliseconds
+pub const SUCCESS_MAX_RTT_METADATA_KEY: &str = "success_max_rtt_ms";
+
+/// Endpoint metadata key holding the HTTP statuses that count as success, comma-separated
+pub const SUCCESS_STATUS_METADATA_KEY: &str = "success_status";
+
+/// Conditions a probe must meet, beyond transport success, to count as successful
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SuccessCriteria {
+    /// Round trips slower than this, in milliseconds, are failures
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rtt_ms: Option<f64>,
+    /// Statuses that count as success, whether or not the probe accepts them;
+    /// empty allows any the probe accepts. Records without a status, from
+    /// probes that are not HTTP, are not checked.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_status: Vec<u16>,
+}
+
+impl SuccessCriteria {
+    /// Criteria set in the metadata of endpoint `endpoint_id`; `None` when it sets none
+    ///
+    /// # Errors
+    /// Returns a validation error for a round-trip limit that is not a
+    /// positive number, or a status that is not a number from 100 to 599
+    pub fn from_metadata(endpoint_id: &str, metadata: &HashMap<String, String>) -> Result<Option<Self>> {
+        let max_rtt_ms = metadata
+            .get(SUCCESS_MAX_RTT_METADATA_KEY)
+            .map(|value| match value.trim().parse::<f64>() {
+                Ok(max_rtt_ms) if max_rtt_ms.is_finite() && max_rtt_ms > 0.0 => Ok(max_rtt_ms),
+                _ => Err(CloudPingError::validation(
+                    format!("{endpoint_id}.metadata.{SUCCESS_MAX_RTT_METADATA_KEY}"),
+                    format!("`{value}` is not a positive number of milliseconds"),
+                )),
+            })
+            .transpose()?;
+        let allowed_status = metadata
+            .get(SUCCESS_STATUS_METADATA_KEY)
+            .map_or_else(
+                || Ok(Vec::new()),
+                |value| {
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|status| !status.is_empty())
+                        .map(|status| {
+                            status.parse::<u16>().ok().filter(|code| (100..600).contains(code)).ok_or_else(|| {
+                                CloudPingError::validation(
+                                    format!("{endpoint_id}.metadata.{SUCCESS_STATUS_METADATA_KEY}"),
+                                    format!("`{status}` is not an HTTP status"),
+                                )
+                            })
+                        })
+                        .collect::<Result<Vec<u16>>>()
+                },
+            )?;
+        let criteria = Self {
+            max_rtt_ms,
+            allowed_status,
+        };
+        Ok((criteria != Self::default()).then_some(criteria))
+    }
+
+    /// Why the transport-successful `record` fails the criteria; `None` when
+    /// it meets them or already failed
+    #[must_use]
+    pub fn violation(&self, record: &ProbeRecord) -> Option<String> {
+        if !record.is_success() {
+            return None;
+        }
+        if let (Some(max_rtt_ms), Some(rtt_ms)) = (self.max_rtt_ms, record.rtt_ms) {
+            if rtt_ms > max_rtt_ms {
+                return Some(format!("slow: {rtt_ms:.1} ms over {max_rtt_ms} ms"));
+            }
+        }
+        record
+            .status
+            .filter(|status| !self.allowed_status.is_empty() && !self.allowed_status.contains(status))
+            .map(|status| format!("status {status} not allowed"))
+    }
+
+    /// Turn `record` into a failure when it violates the criteria, keeping its
+    /// round trip, or into a success when it only failed on a listed status
+    pub fn apply(&self, record: &mut ProbeRecord) {
+        if !record.success
+            && record.rtt_ms.is_some()
+            && record.status.is_some_and(|status| self.allowed_status.contains(&status))
+        {
+            record.success = true;
+            record.error_code = None;
+        }
+        if let Some(violation) = self.violation(record) {
+            record.success = false;
+            record.error_code = Some(violation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_success_criteria_from_metadata() {
+        let metadata = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs.iter().map(|(key, value)| ((*key).to_string(), (*value).to_string())).collect()
+        };
+        assert_eq!(SuccessCriteria::from_metadata("api", &metadata(&[("name", "API")])).unwrap(), None);
+        assert!(SuccessCriteria::from_metadata("api", &metadata(&[(SUCCESS_MAX_RTT_METADATA_KEY, "-5")])).is_err());
+        assert!(SuccessCriteria::from_metadata("api", &metadata(&[(SUCCESS_STATUS_METADATA_KEY, "200,2xx")])).is_err());
+
+        let criteria = SuccessCriteria::from_metadata(
+            "api",
+            &metadata(&[(SUCCESS_MAX_RTT_METADATA_KEY, "250"), (SUCCESS_STATUS_METADATA_KEY, "200, 204")]),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(criteria.allowed_status, vec![200, 204]);
+
+        let mut fast = ProbeRecord::success("api".to_string(), 40.0);
+        fast.status = Some(204);
+        criteria.apply(&mut fast);
+        assert!(fast.is_success());
+
+        let mut slow = ProbeRecord::success("api".to_string(), 312.0);
+        criteria.apply(&mut slow);
+        assert!(!slow.success);
+        assert_eq!(slow.rtt_ms, Some(312.0));
+        assert_eq!(slow.error_code.as_deref(), Some("slow: 312.0 ms over 250 ms"));
+
+        let mut redirected = ProbeRecord::success("api".to_string(), 40.0);
+        redirected.status = Some(302);
+        assert_eq!(criteria.violation(&redirected).as_deref(), Some("status 302 not allowed"));
+
+        let timeout = ProbeRecord::timeout("api".to_string());
+        assert_eq!(criteria.violation(&timeout), None);
+
+        // A listed status the probe fails on still counts as success
+        let criteria = SuccessCriteria::from_metadata("api", &metadata(&[(SUCCESS_STATUS_METADATA_KEY, "200,401")]))
+            .unwrap()
+            .unwrap();
+        let mut unauthorized = ProbeRecord {
+            rtt_ms: Some(40.0),
+            status: Some(401),
+            ..ProbeRecord::with_error("api".to_string(), "HTTP 401".to_string())
+        };
+        criteria.apply(&mut unauthorized);
+        assert!(unauthorized.is_success());
+        assert_eq!(unauthorized.error_code, None);
+        let mut unavailable = ProbeRecord {
+            rtt_ms: Some(40.0),
+            status: Some(503),
+            ..ProbeRecord::with_error("api".to_string(), "HTTP 503".to_string())
+        };
+        criteria.apply(&mut unavailable);
+        assert!(!unavailable.success);
+    }
+}
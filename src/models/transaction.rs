@@ -0,0 +1,333 @@
+//! Scripted multi-step checks
+//!
+//! A single GET shows how fast an endpoint answers, not how long a user
+//! waits for a real flow such as signing in and then calling an API. A
+//! [`Transaction`] is an ordered list of HTTP steps read from a TOML script.
+//! Each step can extract values from its response for later steps to use
+//! and make assertions about it. Running it against a region gives the
+//! timing of every step and of the whole flow.
+//!
+//! ```toml
+//! name = "sign in and list"
+//!
+//! [[steps]]
+//! name = "login"
+//! method = "POST"
+//! url = "{{region_url}}/auth"
+//! headers = { content-type = "application/json" }
+//! body = '{"user": "probe"}'
+//! extract = { token = { json = "/access_token" } }
+//!
+//! [[steps]]
+//! name = "list"
+//! url = "{{region_url}}/items"
+//! headers = { authorization = "Bearer {{token}}" }
+//! assert = [{ status = 200 }, { body_contains = "items" }, { max_ms = 500 }]
+//! ```
+//!
+//! `{{region_url}}` is the URL of the region being checked without a
+//! trailing slash and `{{region}}` its name.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::collector::csv_field;
+use crate::error::{CloudPingError, Result};
+
+/// Variables every step can use without extracting them
+pub const BUILTIN_VARIABLES: [&str; 2] = ["region_url", "region"];
+
+/// Where a step takes a value for later steps from
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Extract {
+    /// JSON pointer into the response body, such as `/data/0/id`
+    Json(String),
+    /// Response header, by name
+    Header(String),
+}
+
+/// Condition a step's response must meet
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Assertion {
+    /// The response has this status code
+    Status(u16),
+    /// The response body contains this text
+    BodyContains(String),
+    /// The step takes at most this many milliseconds
+    MaxMs(u64),
+}
+
+/// One HTTP request of a transaction
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TransactionStep {
+    /// Name shown in results
+    pub name: String,
+    /// HTTP method
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// URL, with `{{variable}}` placeholders
+    pub url: String,
+    /// Request headers, values with placeholders
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Request body, with placeholders
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Values to take from the response, by variable name
+    #[serde(default)]
+    pub extract: BTreeMap<String, Extract>,
+    /// Conditions on the response; without any, the status must be a success
+    #[serde(default)]
+    pub assert: Vec<Assertion>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+/// Ordered HTTP steps checked together against each region
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Transaction {
+    /// Name shown in results
+    pub name: String,
+    /// Steps in the order they run
+    pub steps: Vec<TransactionStep>,
+}
+
+impl Transaction {
+    /// Read and validate a transaction script
+    ///
+    /// # Errors
+    /// Returns an error when the file cannot be read or parsed, or does not validate
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let transaction: Self = toml::from_str(&content).map_err(|e| {
+            CloudPingError::data_loading(format!(
+                "Invalid transaction script {}: {e}",
+                path.display()
+            ))
+        })?;
+        transaction.validate()?;
+        Ok(transaction)
+    }
+
+    /// # Errors
+    /// Returns a validation error for a script without steps, an unknown
+    /// method, or a placeholder no earlier step extracts
+    pub fn validate(&self) -> Result<()> {
+        if self.steps.is_empty() {
+            return Err(CloudPingError::validation("steps", "must contain at least one step"));
+        }
+        let mut defined: HashSet<&str> = BUILTIN_VARIABLES.into_iter().collect();
+        for (index, step) in self.steps.iter().enumerate() {
+            let key = |field: &str| format!("steps[{index}].{field}");
+            if reqwest::Method::from_bytes(step.method.to_uppercase().as_bytes()).is_err() {
+                return Err(CloudPingError::validation(
+                    key("method"),
+                    format!("`{}` is not an HTTP method", step.method),
+                ));
+            }
+            let templates = std::iter::once(("url", &step.url))
+                .chain(step.headers.values().map(|value| ("headers", value)))
+                .chain(step.body.iter().map(|body| ("body", body)));
+            for (field, template) in templates {
+                let placeholders = placeholders(template)
+                    .map_err(|message| CloudPingError::validation(key(field), message))?;
+                if let Some(unknown) = placeholders.iter().find(|name| !defined.contains(*name)) {
+                    return Err(CloudPingError::validation(
+                        key(field),
+                        format!("uses `{unknown}` before any step extracts it"),
+                    ));
+                }
+            }
+            defined.extend(step.extract.keys().map(String::as_str));
+        }
+        Ok(())
+    }
+}
+
+/// Names of the `{{variable}}` placeholders in `template`
+fn placeholders(template: &str) -> std::result::Result<Vec<&str>, String> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find("{{") {
+        let after = &rest[open + 2..];
+        let close = after
+            .find("}}")
+            .ok_or_else(|| "has a `{{` without a closing `}}`".to_string())?;
+        names.push(after[..close].trim());
+        rest = &after[close + 2..];
+    }
+    Ok(names)
+}
+
+/// Replace the `{{variable}}` placeholders in `template` with their values
+///
+/// # Errors
+/// Returns the name of the first placeholder without a value
+pub(crate) fn render(
+    template: &str,
+    variables: &HashMap<String, String>,
+) -> std::result::Result<String, String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find("{{") {
+        rendered.push_str(&rest[..open]);
+        let after = &rest[open + 2..];
+        let Some(close) = after.find("}}") else {
+            return Err("unclosed `{{`".to_string());
+        };
+        let name = after[..close].trim();
+        let value = variables
+            .get(name)
+            .ok_or_else(|| format!("`{name}` has no value"))?;
+        rendered.push_str(value);
+        rest = &after[close + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Outcome of one step of a transaction
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StepResult {
+    /// Step name
+    pub name: String,
+    /// Status code of the response, absent when none arrived
+    pub status: Option<u16>,
+    /// Time from sending the request to reading the whole response, in milliseconds
+    pub duration_ms: f64,
+    /// Why the step failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// One run of a transaction against a region
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TransactionResult {
+    /// Transaction name
+    pub transaction: String,
+    /// Region the transaction ran against
+    pub region: String,
+    /// Steps that ran, in order; the run stops at the first failed step
+    pub steps: Vec<StepResult>,
+    /// Steps in the transaction
+    pub step_count: usize,
+    /// Time for the whole run in milliseconds
+    pub total_ms: f64,
+}
+
+impl TransactionResult {
+    /// Whether every step ran and passed
+    #[must_use]
+    pub fn is_successful(&self) -> bool {
+        self.steps.len() == self.step_count && self.steps.iter().all(|step| step.error.is_none())
+    }
+
+    /// Step the run stopped at
+    #[must_use]
+    pub fn failed_step(&self) -> Option<&StepResult> {
+        self.steps.iter().find(|step| step.error.is_some())
+    }
+
+    /// One CSV line per step that ran of every result, with a header row
+    #[must_use]
+    pub fn to_csv(results: &[Self]) -> String {
+        let mut csv = String::from("transaction,region,step,status,duration_ms,error\n");
+        for result in results {
+            for step in &result.steps {
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{},{:.1},{}",
+                    csv_field(&result.transaction),
+                    csv_field(&result.region),
+                    csv_field(&step.name),
+                    step.status.map_or_else(String::new, |status| status.to_string()),
+                    step.duration_ms,
+                    csv_field(step.error.as_deref().unwrap_or_default())
+                );
+            }
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = r#"
+        name = "sign in and list"
+
+        [[steps]]
+        name = "login"
+        method = "post"
+        url = "{{region_url}}/auth"
+        body = '{"user": "probe"}'
+        extract = { token = { json = "/access_token" } }
+
+        [[steps]]
+        name = "list"
+        url = "{{ region_url }}/items"
+        headers = { authorization = "Bearer {{token}}" }
+        assert = [{ status = 200 }, { max_ms = 500 }]
+    "#;
+
+    #[test]
+    fn test_transaction_script() {
+        let mut transaction: Transaction = toml::from_str(SCRIPT).unwrap();
+        transaction.validate().unwrap();
+        assert_eq!(transaction.steps[1].method, "GET");
+        assert_eq!(
+            transaction.steps[0].extract["token"],
+            Extract::Json("/access_token".to_string())
+        );
+        assert_eq!(transaction.steps[1].assert[1], Assertion::MaxMs(500));
+
+        let variables = HashMap::from([
+            ("region_url".to_string(), "https://eu.example.com".to_string()),
+            ("token".to_string(), "abc".to_string()),
+        ]);
+        assert_eq!(
+            render(&transaction.steps[1].url, &variables).unwrap(),
+            "https://eu.example.com/items"
+        );
+        assert_eq!(render("{{missing}}", &variables).unwrap_err(), "`missing` has no value");
+
+        // A step cannot use a value before the step extracting it has run
+        transaction.steps.swap(0, 1);
+        let error = transaction.validate().unwrap_err().to_string();
+        assert!(error.contains("steps[0].headers"), "{error}");
+        transaction.steps[0].method = "FETCH ME".to_string();
+        assert!(transaction.validate().is_err());
+    }
+
+    #[test]
+    fn test_transaction_result() {
+        let step = |name: &str, error: Option<&str>| StepResult {
+            name: name.to_string(),
+            status: Some(200),
+            duration_ms: 12.0,
+            error: error.map(str::to_string),
+        };
+        let mut result = TransactionResult {
+            transaction: "flow".to_string(),
+            region: "frankfurt".to_string(),
+            steps: vec![step("login", None), step("list", None)],
+            step_count: 2,
+            total_ms: 24.5,
+        };
+        assert!(result.is_successful());
+
+        result.steps[1] = step("list", Some("expected status 201, got 200"));
+        assert!(!result.is_successful());
+        assert_eq!(result.failed_step().unwrap().name, "list");
+        assert!(TransactionResult::to_csv(&[result])
+            .ends_with("flow,frankfurt,list,200,12.0,\"expected status 201, got 200\"\n"));
+    }
+}
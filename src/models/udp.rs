@@ -0,0 +1,236 @@
+//! UDP reachability of regions from the current network
+//!
+//! HTTP/3 runs over QUIC on UDP/443 and calls use STUN and TURN on UDP/3478,
+//! and many corporate, hotel and mobile networks filter UDP while TCP works.
+//! A reachability check sends each region a datagram the service on the
+//! port answers (a QUIC version negotiation trigger, a STUN binding request)
+//! and records whether an answer came back. Region hosts rarely run STUN, so
+//! STUN ports are checked on the configured STUN servers instead. Silence
+//! alone does not say whether the host serves nothing on the port or the
+//! network filters it.
+
+use std::fmt::{self, Write as _};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::collector::csv_field;
+use crate::error::CloudPingError;
+
+/// Ports checked on regions when none are given
+pub const DEFAULT_UDP_PORTS: &str = "443/quic";
+
+/// What a reachability check sends to a port
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum UdpProtocol {
+    /// A QUIC packet with a reserved version, answered with version negotiation
+    Quic,
+    /// A STUN binding request, answered by STUN and TURN servers
+    Stun,
+    /// A short datagram, answered only by services that echo or reply to anything
+    Raw,
+}
+
+impl fmt::Display for UdpProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Quic => "quic",
+            Self::Stun => "stun",
+            Self::Raw => "udp",
+        })
+    }
+}
+
+/// UDP port and the protocol spoken to it, written as `443/quic`, `3478/stun` or `500`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct UdpPort {
+    /// Port number
+    pub port: u16,
+    /// Protocol of the probe datagram
+    pub protocol: UdpProtocol,
+}
+
+impl UdpPort {
+    /// Parse a comma-separated list such as [`DEFAULT_UDP_PORTS`]
+    ///
+    /// # Errors
+    /// Returns a validation error naming the first invalid entry
+    pub fn parse_list(list: &str) -> crate::error::Result<Vec<Self>> {
+        let ports = list
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(str::parse)
+            .collect::<crate::error::Result<Vec<Self>>>()?;
+        if ports.is_empty() {
+            return Err(CloudPingError::validation("ports", "must list at least one port"));
+        }
+        Ok(ports)
+    }
+}
+
+impl FromStr for UdpPort {
+    type Err = CloudPingError;
+
+    fn from_str(entry: &str) -> crate::error::Result<Self> {
+        let (port, protocol) = entry.split_once('/').unwrap_or((entry, "udp"));
+        let invalid = || {
+            CloudPingError::validation(
+                "ports",
+                format!("`{entry}` is not a port optionally followed by /quic, /stun or /udp"),
+            )
+        };
+        let protocol = match protocol.to_lowercase().as_str() {
+            "quic" => UdpProtocol::Quic,
+            "stun" | "turn" => UdpProtocol::Stun,
+            "udp" | "raw" => UdpProtocol::Raw,
+            _ => return Err(invalid()),
+        };
+        let port = port.parse().ok().filter(|&port| port > 0).ok_or_else(invalid)?;
+        Ok(Self { port, protocol })
+    }
+}
+
+impl fmt::Display for UdpPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.port, self.protocol)
+    }
+}
+
+/// Outcome of a reachability check
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UdpReachability {
+    /// The service answered, so UDP gets through both ways
+    Answered,
+    /// The host reported the port closed, so UDP gets through but nothing listens
+    Closed,
+    /// Nothing came back: filtered on the way, or no service answering
+    NoAnswer,
+    /// The region's host name did not resolve
+    Unresolved,
+}
+
+impl fmt::Display for UdpReachability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Answered => "answered",
+            Self::Closed => "closed",
+            Self::NoAnswer => "no answer",
+            Self::Unresolved => "unresolved",
+        })
+    }
+}
+
+/// Reachability of one port of one region
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UdpCheck {
+    /// Port checked
+    #[serde(flatten)]
+    pub port: UdpPort,
+    /// Whether it answered
+    pub reachability: UdpReachability,
+    /// Time to the answer in milliseconds
+    pub rtt_ms: Option<f64>,
+}
+
+/// Reachability of every checked port of one region
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UdpMatrixRow {
+    /// Region name
+    pub region: String,
+    /// Host the datagrams were sent to
+    pub host: String,
+    /// One check per port, in the order the ports were given
+    pub checks: Vec<UdpCheck>,
+}
+
+impl UdpMatrixRow {
+    /// Ports that no host answered although at least one resolved; the
+    /// hosts may not serve them or something on the way may filter them
+    #[must_use]
+    pub fn unanswered_ports(rows: &[Self]) -> Vec<UdpPort> {
+        let mut ports: Vec<UdpPort> = Vec::new();
+        for check in rows.iter().flat_map(|row| &row.checks) {
+            if !ports.contains(&check.port) {
+                ports.push(check.port);
+            }
+        }
+        ports.retain(|port| {
+            let mut checks = rows
+                .iter()
+                .flat_map(|row| &row.checks)
+                .filter(|check| check.port == *port && check.reachability != UdpReachability::Unresolved)
+                .peekable();
+            checks.peek().is_some()
+                && checks.all(|check| check.reachability == UdpReachability::NoAnswer)
+        });
+        ports
+    }
+
+    /// One CSV line per check of every row, with a header row
+    #[must_use]
+    pub fn to_csv(rows: &[Self]) -> String {
+        let mut csv = String::from("region,host,port,protocol,reachability,rtt_ms\n");
+        for row in rows {
+            for check in &row.checks {
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{},{},{}",
+                    csv_field(&row.region),
+                    csv_field(&row.host),
+                    check.port.port,
+                    check.port.protocol,
+                    check.reachability,
+                    check.rtt_ms.map_or_else(String::new, |rtt| format!("{rtt:.1}"))
+                );
+            }
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ports() {
+        let ports = UdpPort::parse_list("443/quic, 3478/stun").unwrap();
+        assert_eq!(
+            ports,
+            [
+                UdpPort { port: 443, protocol: UdpProtocol::Quic },
+                UdpPort { port: 3478, protocol: UdpProtocol::Stun },
+            ]
+        );
+        assert_eq!(UdpPort::parse_list(DEFAULT_UDP_PORTS).unwrap(), ports[..1]);
+        assert_eq!("500".parse::<UdpPort>().unwrap().to_string(), "500/udp");
+        assert!("443/tcp".parse::<UdpPort>().is_err());
+        assert!("0/quic".parse::<UdpPort>().is_err());
+        assert!(UdpPort::parse_list(" , ").is_err());
+    }
+
+    #[test]
+    fn test_unanswered_ports() {
+        let ports = UdpPort::parse_list("443/quic,3478/stun").unwrap();
+        let row = |region: &str, quic: UdpReachability, stun: UdpReachability| UdpMatrixRow {
+            region: region.to_string(),
+            host: format!("{region}.example.com"),
+            checks: vec![
+                UdpCheck { port: ports[0], reachability: quic, rtt_ms: None },
+                UdpCheck { port: ports[1], reachability: stun, rtt_ms: Some(20.0) },
+            ],
+        };
+        let rows = [
+            row("frankfurt", UdpReachability::NoAnswer, UdpReachability::Answered),
+            row("virginia", UdpReachability::NoAnswer, UdpReachability::NoAnswer),
+            row("gone", UdpReachability::Unresolved, UdpReachability::Unresolved),
+        ];
+        assert_eq!(UdpMatrixRow::unanswered_ports(&rows), [ports[0]]);
+
+        let csv = UdpMatrixRow::to_csv(&rows[..1]);
+        assert!(csv.ends_with("frankfurt,frankfurt.example.com,3478,stun,answered,20.0\n"));
+    }
+}
@@ -20,18 +20,8 @@ pub fn percentile(values: &[f64], p: f64) -> f64 {
 
     // Use unstable sort for better performance
     let mut sorted = values.to_vec();
-    sorted.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-
-    let index = (p / 100.0) * (sorted.len() - 1) as f64;
-    let lower = index.floor() as usize;
-    let upper = index.ceil() as usize;
-
-    if lower == upper || upper >= sorted.len() {
-        sorted[lower.min(sorted.len() - 1)]
-    } else {
-        let weight = index - lower as f64;
-        sorted[lower] * (1.0 - weight) + sorted[upper] * weight
-    }
+    sort_values(&mut sorted);
+    percentile_of_sorted(&sorted, p)
 }
 
 /// Calculate multiple percentiles efficiently
@@ -42,20 +32,32 @@ pub fn percentiles(values: &[f64], percentiles: &[f64]) -> Vec<f64> {
 
     // Sort once and reuse for all percentiles
     let mut sorted = values.to_vec();
-    sorted.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    sort_values(&mut sorted);
+    percentiles.iter().map(|&p| percentile_of_sorted(&sorted, p)).collect()
+}
 
-    percentiles.iter().map(|&p| {
-        let index = (p / 100.0) * (sorted.len() - 1) as f64;
-        let lower = index.floor() as usize;
-        let upper = index.ceil() as usize;
+/// Sort values in place for [`percentile_of_sorted`]
+pub fn sort_values(values: &mut [f64]) {
+    values.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+}
 
-        if lower == upper || upper >= sorted.len() {
-            sorted[lower.min(sorted.len() - 1)]
-        } else {
-            let weight = index - lower as f64;
-            sorted[lower] * (1.0 - weight) + sorted[upper] * weight
-        }
-    }).collect()
+/// Percentile of values already in ascending order, without copying them
+#[must_use]
+pub fn percentile_of_sorted(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::INFINITY;
+    }
+
+    let index = (p / 100.0) * (sorted.len() - 1) as f64;
+    let lower = index.floor() as usize;
+    let upper = index.ceil() as usize;
+
+    if lower == upper || upper >= sorted.len() {
+        sorted[lower.min(sorted.len() - 1)]
+    } else {
+        let weight = index - lower as f64;
+        sorted[lower] * (1.0 - weight) + sorted[upper] * weight
+    }
 }
 
 /// Calculate basic statistics from values
@@ -1,17 +1,39 @@
 //! Main monitoring system that orchestrates probing, aggregation, and alerting
 
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::{broadcast, RwLock};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, watch, Mutex, RwLock};
 use tokio::time::interval;
 use crate::time_utils::TimeUtils;
 use crate::collection_utils::CollectionUtils;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
-use crate::aggregator::{AggregatorConfig, StreamingAggregator};
-use crate::error::Result;
-use crate::models::{Alert, ComprehensiveScoreResult, Endpoint, ProbeType};
-use crate::probe::{ProbeConfig, ProbeRunner};
+use crate::aggregator::{
+    AggregatorConfig, AggregatorOverrides, AggregatorSettings, EndpointTopology, RttHistogramSnapshot,
+    StreamingAggregator,
+};
+use crate::alerting::{AlertRouter, CorrelationSettings, Incident, Notification};
+use crate::endpoint_store::{EndpointChanges, EndpointSpec, EndpointStore, ManagedEndpoints};
+use crate::error::{CloudPingError, Result};
+use crate::events::{EventBus, EventFilter, EventKind, EventSubscription, MonitoringEvent};
+use crate::plugins::PluginRegistry;
+use crate::provider_status::{OutageCorrelator, ProviderStatusClient};
+use crate::models::{
+    namespaced_id, Alert, AlertEnvelope, AvailabilityLedger, AvailabilityReport, ComprehensiveScoreResult, Endpoint,
+    ProbeRecord, ProbeType, Region, ScorePoint, SuccessCriteria, SUCCESS_MAX_RTT_METADATA_KEY,
+    SUCCESS_STATUS_METADATA_KEY,
+};
+use crate::probe::{ProbeConfig, ProbeRunner, ProbeSettings};
+use crate::otlp::OtlpSettings;
+use crate::probe_sink::{ProbeForwarder, ProbeSink, ProbeSinkSettings};
+use crate::score_snapshot::{publish_snapshots, ScoreSnapshot, ScoreSources};
+use crate::scripting::ScriptSettings;
+use crate::sharded_aggregator::ShardedAggregator;
+use crate::self_metrics::{spawn_watched, Queue, SelfMetrics, SelfMetricsSnapshot};
 
 /// Main monitoring system configuration
 #[derive(Debug, Clone)]
@@ -22,6 +44,14 @@ pub struct MonitoringConfig {
     pub aggregator_config: AggregatorConfig,
     /// Interval for exporting metrics in milliseconds
     pub metrics_export_interval_ms: u64,
+    /// Interval for publishing score snapshots to readers in milliseconds
+    pub snapshot_interval_ms: u64,
+    /// File the availability ledger is persisted to, if any
+    pub availability_ledger_path: Option<PathBuf>,
+    /// Grouping of simultaneous alerts into incidents
+    pub correlation: CorrelationSettings,
+    /// Alert grouping replacing `correlation` for a namespace, by namespace name
+    pub namespace_correlation: HashMap<String, CorrelationSettings>,
 }
 
 impl Default for MonitoringConfig {
@@ -30,169 +60,1086 @@ impl Default for MonitoringConfig {
             probe_config: ProbeConfig::default(),
             aggregator_config: AggregatorConfig::default(),
             metrics_export_interval_ms: 60000, // 1 minute
+            snapshot_interval_ms: 1000,
+            availability_ledger_path: None,
+            correlation: CorrelationSettings::default(),
+            namespace_correlation: HashMap::new(),
+        }
+    }
+}
+
+/// Monitoring settings read from `[monitoring]` in the application config
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MonitoringSettings {
+    /// Time between metrics exports
+    #[serde(with = "humantime_serde", default = "default_metrics_export_interval")]
+    pub metrics_export_interval: Duration,
+    /// Time between the score snapshots the APIs and the metrics exporter read
+    #[serde(with = "humantime_serde", default = "default_snapshot_interval")]
+    pub snapshot_interval: Duration,
+    /// Probe timing and concurrency
+    #[serde(default)]
+    pub probe: ProbeSettings,
+    /// Windows, smoothing and alert thresholds
+    #[serde(default)]
+    pub aggregator: AggregatorSettings,
+    /// Grouping of simultaneous alerts into incidents
+    #[serde(default)]
+    pub correlation: CorrelationSettings,
+    /// `ws://` or `wss://` URLs probed with a WebSocket handshake next to the regions
+    #[serde(default)]
+    pub websocket_endpoints: Vec<String>,
+    /// `smtp://`, `imap://`, `redis://` or `postgres://` URLs probed for their
+    /// service greeting next to the regions
+    #[serde(default)]
+    pub banner_endpoints: Vec<String>,
+    /// NTP servers, as `host` or `host:port`, queried to watch the local clock
+    #[serde(default)]
+    pub ntp_servers: Vec<String>,
+    /// How often `status_feeds` are polled so alerts name a provider incident
+    /// matching their region; unset leaves alerts without one
+    #[serde(default, with = "humantime_serde")]
+    pub status_poll_interval: Option<Duration>,
+    /// Sink every probe record is also forwarded to
+    #[serde(default)]
+    pub probe_sink: Option<ProbeSinkSettings>,
+    /// OTLP receiver round-trip histograms and scores are also exported to
+    #[serde(default)]
+    pub otlp: Option<OtlpSettings>,
+    /// Tenants or environments monitored apart from each other
+    #[serde(default)]
+    pub namespaces: Vec<NamespaceSettings>,
+    /// File endpoints created, changed or deleted through the API are kept
+    /// in across restarts; without it such changes last until the process exits
+    #[serde(default)]
+    pub endpoints_file: Option<PathBuf>,
+    /// Alert conditions and derived metrics written as Rhai scripts
+    #[serde(default)]
+    pub scripts: Vec<ScriptSettings>,
+}
+
+/// One namespace read from `[[monitoring.namespaces]]`
+///
+/// The namespace's endpoints keep their own metrics and alert state, are
+/// measured against its own thresholds when `aggregator` is set, and their
+/// alerts only group into incidents with each other.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NamespaceSettings {
+    /// Name prefixed to the namespace's endpoint IDs, as in `acme/eu-central-1`
+    pub name: String,
+    /// Names or IDs of the loaded regions monitored for the namespace
+    #[serde(default)]
+    pub regions: Vec<String>,
+    /// WebSocket, service or NTP URLs monitored for the namespace
+    #[serde(default)]
+    pub urls: Vec<String>,
+    /// Windows, smoothing and thresholds overriding those of `[monitoring.aggregator]`
+    #[serde(default)]
+    pub aggregator: Option<AggregatorOverrides>,
+    /// Alert grouping replacing `[monitoring.correlation]`
+    #[serde(default)]
+    pub correlation: Option<CorrelationSettings>,
+}
+
+impl NamespaceSettings {
+    /// # Errors
+    /// Returns a validation error naming the first invalid setting
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() || self.name.contains('/') {
+            return Err(CloudPingError::validation(
+                "monitoring.namespaces.name",
+                format!("`{}` must be non-empty and free of `/`", self.name),
+            ));
+        }
+        if let Some(url) = self.urls.iter().find(|url| url_target(url).is_none()) {
+            return Err(CloudPingError::validation(
+                "monitoring.namespaces.urls",
+                format!("`{url}` of namespace `{}` is not a URL with a host", self.name),
+            ));
+        }
+        self.correlation.as_ref().map_or(Ok(()), CorrelationSettings::validate)
+    }
+}
+
+const fn default_metrics_export_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+const fn default_snapshot_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
+impl Default for MonitoringSettings {
+    fn default() -> Self {
+        Self {
+            metrics_export_interval: default_metrics_export_interval(),
+            snapshot_interval: default_snapshot_interval(),
+            probe: ProbeSettings::default(),
+            aggregator: AggregatorSettings::default(),
+            correlation: CorrelationSettings::default(),
+            websocket_endpoints: Vec::new(),
+            banner_endpoints: Vec::new(),
+            ntp_servers: Vec::new(),
+            status_poll_interval: None,
+            probe_sink: None,
+            otlp: None,
+            namespaces: Vec::new(),
+            endpoints_file: None,
+            scripts: Vec::new(),
         }
     }
 }
 
+impl MonitoringSettings {
+    /// Copy these settings into a monitoring system configuration
+    pub fn apply(&self, config: &mut MonitoringConfig) {
+        config.metrics_export_interval_ms =
+            u64::try_from(self.metrics_export_interval.as_millis()).unwrap_or(u64::MAX);
+        config.snapshot_interval_ms =
+            u64::try_from(self.snapshot_interval.as_millis()).unwrap_or(u64::MAX);
+        self.probe.apply(&mut config.probe_config);
+        self.aggregator.apply(&mut config.aggregator_config);
+        config.aggregator_config.scripts.clone_from(&self.scripts);
+        config.correlation = self.correlation.clone();
+        config.aggregator_config.namespaces = self
+            .namespaces
+            .iter()
+            .filter_map(|namespace| Some((namespace.name.clone(), namespace.aggregator.clone()?)))
+            .collect();
+        config.namespace_correlation = self
+            .namespaces
+            .iter()
+            .filter_map(|namespace| Some((namespace.name.clone(), namespace.correlation.clone()?)))
+            .collect();
+    }
+
+    /// # Errors
+    /// Returns a validation error naming the first invalid setting
+    pub fn validate(&self) -> Result<()> {
+        if self.metrics_export_interval.is_zero() {
+            return Err(CloudPingError::validation(
+                "monitoring.metrics_export_interval",
+                "must be greater than 0",
+            ));
+        }
+        if self.snapshot_interval.is_zero() {
+            return Err(CloudPingError::validation(
+                "monitoring.snapshot_interval",
+                "must be greater than 0",
+            ));
+        }
+        self.probe.validate()?;
+        self.aggregator.validate()?;
+        self.correlation.validate()?;
+        for url in &self.websocket_endpoints {
+            if !matches!(url_target(url), Some((_, _, ProbeType::WebSocket))) {
+                return Err(CloudPingError::validation(
+                    "monitoring.websocket_endpoints",
+                    format!("`{url}` is not a ws:// or wss:// URL"),
+                ));
+            }
+        }
+        for url in &self.banner_endpoints {
+            if !url_target(url).is_some_and(|(_, _, probe_type)| probe_type.is_banner()) {
+                return Err(CloudPingError::validation(
+                    "monitoring.banner_endpoints",
+                    format!("`{url}` is not an smtp://, imap://, redis:// or postgres:// URL"),
+                ));
+            }
+        }
+        for server in &self.ntp_servers {
+            if url_target(&ntp_url(server)).is_none() {
+                return Err(CloudPingError::validation(
+                    "monitoring.ntp_servers",
+                    format!("`{server}` is not a host or host:port"),
+                ));
+            }
+        }
+        for (i, namespace) in self.namespaces.iter().enumerate() {
+            namespace.validate()?;
+            if let Some(overrides) = &namespace.aggregator {
+                overrides.over(&self.aggregator).validate()?;
+            }
+            if self.namespaces[..i].iter().any(|other| other.name == namespace.name) {
+                return Err(CloudPingError::validation(
+                    "monitoring.namespaces.name",
+                    format!("`{}` is used by more than one namespace", namespace.name),
+                ));
+            }
+        }
+        self.validate_scripts()?;
+        self.otlp.as_ref().map_or(Ok(()), OtlpSettings::validate)?;
+        self.probe_sink.as_ref().map_or(Ok(()), ProbeSinkSettings::validate)
+    }
+
+    /// Check every script has a unique name and, with the `scripting`
+    /// feature, compiles
+    fn validate_scripts(&self) -> Result<()> {
+        for (i, script) in self.scripts.iter().enumerate() {
+            script.validate()?;
+            if self.scripts[..i].iter().any(|other| other.name == script.name) {
+                return Err(CloudPingError::validation(
+                    "monitoring.scripts.name",
+                    format!("`{}` is used by more than one script", script.name),
+                ));
+            }
+        }
+        #[cfg(feature = "scripting")]
+        crate::scripting::ScriptSet::compile(&self.scripts)?;
+        Ok(())
+    }
+}
+
 /// Main monitoring system that coordinates all components
 pub struct NetworkMonitoringSystem {
     config: MonitoringConfig,
     endpoints: Arc<RwLock<HashMap<String, Endpoint>>>,
-    alert_broadcast: broadcast::Sender<Alert>,
-    metrics_broadcast: broadcast::Sender<HashMap<String, ComprehensiveScoreResult>>,
+    events: Arc<EventBus>,
+    availability: Arc<RwLock<AvailabilityLedger>>,
+    scores: Arc<RwLock<HashMap<String, ComprehensiveScoreResult>>>,
+    score_history: Arc<RwLock<HashMap<String, Vec<ScorePoint>>>>,
+    rtt_histograms: RttHistogramSnapshot,
+    /// Latest copy of `scores` and `score_history`, which readers use so
+    /// they never wait on the aggregator
+    snapshots: watch::Sender<Arc<ScoreSnapshot>>,
+    recent_alerts: Arc<RwLock<VecDeque<Alert>>>,
+    incident_broadcast: broadcast::Sender<Incident>,
+    recent_incidents: Arc<RwLock<VecDeque<Incident>>>,
+    probe_updates: watch::Sender<ProbeConfig>,
+    aggregator_updates: watch::Sender<AggregatorConfig>,
+    /// Providers, namespaces and success criteria of the endpoints, published
+    /// to the aggregator as endpoints change once the system has started
+    topology_updates: watch::Sender<EndpointTopology>,
+    export_interval_updates: watch::Sender<u64>,
+    snapshot_interval_updates: watch::Sender<u64>,
+    probe_sink: Option<(Arc<dyn ProbeSink>, ProbeSinkSettings)>,
+    /// Probe runner of the started system, which endpoints added or removed
+    /// afterwards are started on or stopped in
+    probe_runner: OnceLock<ProbeRunner>,
+    endpoint_store: Option<EndpointStore>,
+    /// Endpoints changed through the API; the lock also keeps one change
+    /// from interleaving with another
+    managed: Mutex<ManagedEndpoints>,
+    self_metrics: Arc<SelfMetrics>,
+    self_metrics_broadcast: broadcast::Sender<SelfMetricsSnapshot>,
+    plugins: PluginRegistry,
+    /// Client the probe runner opens WebSocket probes through
+    websocket_client: Option<reqwest::Client>,
+    /// Status feeds polled for provider incidents, and how often
+    status_polling: Option<(Arc<ProviderStatusClient>, Duration)>,
+    /// Provider incidents alerts are matched against
+    outages: Arc<RwLock<OutageCorrelator>>,
 }
 
+/// Events queued for each event bus subscriber before it misses some
+const EVENT_QUEUE_CAPACITY: usize = 1000;
+
+/// Number of alerts kept for the status page
+const RECENT_ALERT_LIMIT: usize = 50;
+
+/// ID of the endpoint added by [`NetworkMonitoringSystem::add_control_endpoint`]
+pub const CONTROL_ENDPOINT_ID: &str = "control";
+
+/// Number of incidents kept for the API
+const RECENT_INCIDENT_LIMIT: usize = 20;
+
+/// How often held-back alerts are checked for release
+const ALERT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
 impl NetworkMonitoringSystem {
     /// Create a new monitoring system
     pub fn new(config: MonitoringConfig) -> Self {
-        let (alert_broadcast, _) = broadcast::channel(1000);
-        let (metrics_broadcast, _) = broadcast::channel(100);
+        let (incident_broadcast, _) = broadcast::channel(100);
+        let (self_metrics_broadcast, _) = broadcast::channel(100);
+
+        let ledger = config.availability_ledger_path.as_deref().map_or_else(
+            AvailabilityLedger::new,
+            |path| {
+                AvailabilityLedger::load(path).unwrap_or_else(|e| {
+                    warn!("Starting with an empty availability ledger: {}", e);
+                    AvailabilityLedger::new()
+                })
+            },
+        );
+
+        let (probe_updates, _) = watch::channel(config.probe_config.clone());
+        let (aggregator_updates, _) = watch::channel(config.aggregator_config.clone());
+        let (topology_updates, _) = watch::channel(EndpointTopology::default());
+        let (export_interval_updates, _) = watch::channel(config.metrics_export_interval_ms);
+        let (snapshot_interval_updates, _) = watch::channel(config.snapshot_interval_ms);
+        let (snapshots, _) = watch::channel(Arc::default());
+        let events = Arc::new(EventBus::new(EVENT_QUEUE_CAPACITY));
+        let self_metrics: Arc<SelfMetrics> = Arc::default();
+        let plugins = PluginRegistry::new(
+            Arc::clone(&events),
+            incident_broadcast.clone(),
+            self_metrics_broadcast.clone(),
+            Arc::clone(&self_metrics),
+        );
 
         Self {
             config,
             endpoints: Arc::new(RwLock::new(CollectionUtils::new_hashmap())),
-            alert_broadcast,
-            metrics_broadcast,
+            events,
+            availability: Arc::new(RwLock::new(ledger)),
+            scores: Arc::new(RwLock::new(CollectionUtils::new_hashmap())),
+            score_history: Arc::new(RwLock::new(CollectionUtils::new_hashmap())),
+            rtt_histograms: Arc::new(RwLock::new(CollectionUtils::new_hashmap())),
+            snapshots,
+            recent_alerts: Arc::new(RwLock::new(VecDeque::with_capacity(RECENT_ALERT_LIMIT))),
+            incident_broadcast,
+            recent_incidents: Arc::new(RwLock::new(VecDeque::with_capacity(RECENT_INCIDENT_LIMIT))),
+            probe_updates,
+            aggregator_updates,
+            topology_updates,
+            export_interval_updates,
+            snapshot_interval_updates,
+            probe_sink: None,
+            probe_runner: OnceLock::new(),
+            endpoint_store: None,
+            managed: Mutex::new(ManagedEndpoints::default()),
+            self_metrics,
+            self_metrics_broadcast,
+            plugins,
+            websocket_client: None,
+            status_polling: None,
+            outages: Arc::default(),
         }
     }
 
-    /// Add an endpoint to monitor
+    /// Keep endpoints created, changed or deleted through the API in `store`
+    #[must_use]
+    pub fn with_endpoint_store(mut self, store: EndpointStore) -> Self {
+        self.endpoint_store = Some(store);
+        self
+    }
+
+    /// Also forward every probe record to `sink`, buffering records it does
+    /// not take in the write-ahead log of `settings`
+    #[must_use]
+    pub fn with_probe_sink(mut self, sink: Arc<dyn ProbeSink>, settings: ProbeSinkSettings) -> Self {
+        self.probe_sink = Some((sink, settings));
+        self
+    }
+
+    /// Open WebSocket probes through `client`, built with [`crate::websocket::client`]
+    #[must_use]
+    pub fn with_websocket_client(mut self, client: reqwest::Client) -> Self {
+        self.websocket_client = Some(client);
+        self
+    }
+
+    /// Poll `client` for provider incidents every `interval` once started,
+    /// and name the incident matching an alert's region in the alert
+    #[must_use]
+    pub fn with_status_polling(mut self, client: ProviderStatusClient, interval: Duration) -> Self {
+        self.status_polling = Some((Arc::new(client), interval));
+        self
+    }
+
+    /// Apply a new configuration to a running system without losing window state
+    ///
+    /// Alert thresholds, weights, smoothing and the probe, recompute, metrics
+    /// export and snapshot intervals take effect right away. Window sizes apply to
+    /// endpoints first seen afterwards; concurrency, rate limits, budgets and
+    /// the availability ledger path keep the values the system started with.
+    pub fn reload(&self, config: &MonitoringConfig) {
+        self.probe_updates.send_replace(config.probe_config.clone());
+        self.aggregator_updates.send_replace(config.aggregator_config.clone());
+        self.export_interval_updates.send_replace(config.metrics_export_interval_ms);
+        self.snapshot_interval_updates.send_replace(config.snapshot_interval_ms);
+        info!("Monitoring configuration reloaded");
+    }
+
+    /// Add an endpoint to monitor, probing it right away once the system
+    /// has started unless it is disabled
+    ///
+    /// An endpoint of the same ID is replaced.
     pub async fn add_endpoint(&self, endpoint: Endpoint) {
         let mut endpoints = self.endpoints.write().await;
-        endpoints.insert(endpoint.id.clone(), endpoint);
-        info!("Added endpoint for monitoring: {}", endpoints.len());
+        if let Some(runner) = self.probe_runner.get() {
+            if endpoint.enabled {
+                runner.probe_endpoint(endpoint.clone());
+            } else {
+                runner.stop_endpoint(&endpoint.id);
+            }
+        }
+        endpoints.insert(endpoint.id.clone(), endpoint.clone());
+        self.publish_topology(&endpoints);
+        let count = endpoints.len();
+        drop(endpoints);
+        self.events.publish(&MonitoringEvent::EndpointAdded(endpoint));
+        info!("Added endpoint for monitoring: {}", count);
     }
 
-    /// Remove an endpoint from monitoring
+    /// Remove an endpoint from monitoring, stopping its probes and dropping its score
     pub async fn remove_endpoint(&self, endpoint_id: &str) -> bool {
         let mut endpoints = self.endpoints.write().await;
-        endpoints.remove(endpoint_id).is_some()
+        if let Some(runner) = self.probe_runner.get() {
+            runner.stop_endpoint(endpoint_id);
+        }
+        self.drop_score(endpoint_id).await;
+        let removed = endpoints.remove(endpoint_id).is_some();
+        if removed {
+            self.publish_topology(&endpoints);
+        }
+        drop(endpoints);
+        if removed {
+            self.events.publish(&MonitoringEvent::EndpointRemoved {
+                endpoint_id: endpoint_id.to_string(),
+            });
+        }
+        removed
+    }
+
+    /// Publish the topology of `endpoints` to the running aggregator, so
+    /// changed providers and success criteria apply from the next record on
+    fn publish_topology(&self, endpoints: &HashMap<String, Endpoint>) {
+        if self.probe_runner.get().is_some() {
+            let endpoints: Vec<Endpoint> = endpoints.values().cloned().collect();
+            self.topology_updates.send_replace(endpoint_topology(&endpoints));
+        }
+    }
+
+    /// Drop the score of an endpoint, from the published snapshot as well
+    async fn drop_score(&self, endpoint_id: &str) {
+        self.scores.write().await.remove(endpoint_id);
+        self.rtt_histograms.write().await.remove(endpoint_id);
+        self.snapshots.send_if_modified(|snapshot| {
+            let Some(without) = snapshot.without_score(endpoint_id) else {
+                return false;
+            };
+            *snapshot = Arc::new(without);
+            true
+        });
+    }
+
+    /// Apply the endpoints saved in the endpoint store over the configured ones
+    ///
+    /// # Errors
+    /// Returns an error if the store cannot be read
+    pub async fn restore_endpoints(&self) -> Result<()> {
+        let Some(store) = &self.endpoint_store else {
+            return Ok(());
+        };
+        let mut managed = self.managed.lock().await;
+        *managed = store.load().await?;
+        for id in &managed.deleted {
+            self.remove_endpoint(id).await;
+        }
+        for endpoint in managed.endpoints.values() {
+            self.add_endpoint(endpoint.clone()).await;
+        }
+        info!(
+            "Restored {} changed and {} deleted endpoints from {}",
+            managed.endpoints.len(),
+            managed.deleted.len(),
+            store.path().display()
+        );
+        drop(managed);
+        Ok(())
+    }
+
+    /// Start monitoring the endpoint of `spec` and save it to the endpoint store
+    ///
+    /// Returns `None` when an endpoint of the same ID is already monitored.
+    ///
+    /// # Errors
+    /// Returns an error if the URL has no host or the store cannot be written
+    pub async fn create_endpoint(&self, spec: &EndpointSpec) -> Result<Option<Endpoint>> {
+        let mut endpoint = url_endpoint(&spec.url)?;
+        if let Some(id) = spec.id.as_deref().filter(|id| !id.is_empty()) {
+            endpoint.id = id.to_string();
+        }
+        if let Some(name) = &spec.name {
+            endpoint.set_metadata("name".to_string(), name.clone());
+        }
+        endpoint.enabled = spec.enabled;
+
+        let mut managed = self.managed.lock().await;
+        if self.endpoints.read().await.contains_key(&endpoint.id) {
+            return Ok(None);
+        }
+        let mut next = managed.clone();
+        next.deleted.remove(&endpoint.id);
+        next.endpoints.insert(endpoint.id.clone(), endpoint.clone());
+        self.save_endpoints(&next).await?;
+        *managed = next;
+
+        self.add_endpoint(endpoint.clone()).await;
+        drop(managed);
+        info!("Endpoint {} created through the API", endpoint.id);
+        Ok(Some(endpoint))
+    }
+
+    /// Apply `changes` to a monitored endpoint and save it to the endpoint store
+    ///
+    /// Returns `None` when no endpoint has the ID. A new URL keeps the
+    /// endpoint's ID; disabling an endpoint stops its probes and drops its score.
+    ///
+    /// # Errors
+    /// Returns an error if the new URL has no host or the store cannot be written
+    pub async fn update_endpoint(&self, endpoint_id: &str, changes: &EndpointChanges) -> Result<Option<Endpoint>> {
+        let mut managed = self.managed.lock().await;
+        let Some(mut endpoint) = self.endpoints.read().await.get(endpoint_id).cloned() else {
+            return Ok(None);
+        };
+        if let Some(url) = &changes.url {
+            let target = url_endpoint(url)?;
+            endpoint.host = target.host;
+            endpoint.port = target.port;
+            endpoint.probe_type = target.probe_type;
+            endpoint.set_metadata("url".to_string(), url.clone());
+        }
+        if let Some(name) = &changes.name {
+            endpoint.set_metadata("name".to_string(), name.clone());
+        }
+        if let Some(enabled) = changes.enabled {
+            endpoint.enabled = enabled;
+        }
+        let mut next = managed.clone();
+        next.endpoints.insert(endpoint.id.clone(), endpoint.clone());
+        self.save_endpoints(&next).await?;
+        *managed = next;
+
+        if !endpoint.enabled {
+            self.drop_score(endpoint_id).await;
+        }
+        self.add_endpoint(endpoint.clone()).await;
+        drop(managed);
+        info!("Endpoint {} updated through the API", endpoint_id);
+        Ok(Some(endpoint))
+    }
+
+    /// Stop monitoring an endpoint and record its deletion in the endpoint store
+    ///
+    /// Returns whether an endpoint had the ID.
+    ///
+    /// # Errors
+    /// Returns an error if the store cannot be written
+    pub async fn delete_endpoint(&self, endpoint_id: &str) -> Result<bool> {
+        let mut managed = self.managed.lock().await;
+        if !self.endpoints.read().await.contains_key(endpoint_id) {
+            return Ok(false);
+        }
+        let mut next = managed.clone();
+        next.endpoints.remove(endpoint_id);
+        next.deleted.insert(endpoint_id.to_string());
+        self.save_endpoints(&next).await?;
+        *managed = next;
+
+        self.remove_endpoint(endpoint_id).await;
+        drop(managed);
+        info!("Endpoint {} deleted through the API", endpoint_id);
+        Ok(true)
+    }
+
+    async fn save_endpoints(&self, managed: &ManagedEndpoints) -> Result<()> {
+        match &self.endpoint_store {
+            Some(store) => store.save(managed).await,
+            None => Ok(()),
+        }
     }
 
     /// Add multiple endpoints from regions
     pub async fn add_endpoints_from_regions(&self, regions: &[crate::models::Region]) {
-        for region in regions {
-            if !region.enabled {
-                continue;
+        for region in regions.iter().filter(|region| region.enabled) {
+            if let Some(endpoint) = region_endpoint(region) {
+                self.add_endpoint(endpoint).await;
             }
+        }
+    }
 
-            // Parse URL to extract host and port
-            if let Ok(url) = url::Url::parse(&region.url) {
-                let host = url.host_str().unwrap_or(&region.url).to_string();
-                let port = url.port().unwrap_or(if url.scheme() == "https" { 443 } else { 80 });
-                let probe_type = if url.scheme() == "http" || url.scheme() == "https" {
-                    ProbeType::HTTP
-                } else {
-                    ProbeType::TCP
-                };
-
-                let endpoint = Endpoint {
-                    id: region.id.clone(),
-                    host,
-                    port,
-                    probe_type,
-                    metadata: CollectionUtils::create_metadata(&[
-                        ("name", &region.name),
-                        ("url", &region.url),
-                        ("provider", &region.provider),
-                        ("country", &region.country),
-                    ]),
-                };
-
-                self.add_endpoint(endpoint).await;
-            } else {
-                warn!("Failed to parse URL for region {}: {}", region.name, region.url);
+    /// Monitor the regions and URLs of a namespace, apart from every other namespace
+    ///
+    /// Regions are picked from `regions` by name or ID. The endpoints' IDs
+    /// are prefixed with the namespace, so one region can be monitored for
+    /// several namespaces with separate metrics.
+    ///
+    /// # Errors
+    /// Returns an error if a region is not found or a URL has no host
+    pub async fn add_namespace(
+        &self,
+        settings: &NamespaceSettings,
+        regions: &[crate::models::Region],
+    ) -> Result<()> {
+        for wanted in &settings.regions {
+            let region = regions
+                .iter()
+                .find(|region| {
+                    region.name.eq_ignore_ascii_case(wanted) || region.id.eq_ignore_ascii_case(wanted)
+                })
+                .ok_or_else(|| {
+                    CloudPingError::config(format!(
+                        "Region `{wanted}` of namespace `{}` not found",
+                        settings.name
+                    ))
+                })?;
+            if let Some(endpoint) = region_endpoint(region) {
+                self.add_endpoint(endpoint.in_namespace(&settings.name)).await;
             }
         }
+        for url in &settings.urls {
+            self.add_endpoint(url_endpoint(url)?.in_namespace(&settings.name)).await;
+        }
+        Ok(())
+    }
+
+    /// Monitor `url` as the control endpoint
+    ///
+    /// Alerts raised while the control endpoint is degraded are hinted as
+    /// local network problems.
+    ///
+    /// # Errors
+    /// Returns an error if `url` has no host
+    pub async fn add_control_endpoint(&self, url: &str) -> Result<()> {
+        let (host, port, probe_type) =
+            url_target(url).ok_or_else(|| CloudPingError::invalid_url(url))?;
+        let endpoint = Endpoint {
+            id: CONTROL_ENDPOINT_ID.to_string(),
+            host,
+            port,
+            probe_type,
+            metadata: CollectionUtils::create_metadata(&[
+                ("name", "Control endpoint"),
+                ("url", url),
+                ("role", "control"),
+            ]),
+            namespace: None,
+            enabled: true,
+        };
+        self.add_endpoint(endpoint).await;
+        Ok(())
+    }
+
+    /// Probe each of `urls` by its scheme: `ws://` and `wss://` with a
+    /// WebSocket handshake, `smtp://`, `imap://`, `redis://` and
+    /// `postgres://` for the service greeting, `ntp://` with an NTP query
+    ///
+    /// # Errors
+    /// Returns an error if a URL has no host
+    pub async fn add_url_endpoints(&self, urls: &[String]) -> Result<()> {
+        for url in urls {
+            self.add_endpoint(url_endpoint(url)?).await;
+        }
+        Ok(())
+    }
+
+    /// Query each of the NTP `servers`, given as `host` or `host:port`
+    ///
+    /// # Errors
+    /// Returns an error if a server is not a valid host
+    pub async fn add_ntp_servers(&self, servers: &[String]) -> Result<()> {
+        let urls: Vec<String> = servers.iter().map(|server| ntp_url(server)).collect();
+        self.add_url_endpoints(&urls).await
+    }
+
+    /// Probe runner starting from the latest probe configuration and
+    /// following its updates, raising its alerts on `alerts`
+    fn probe_runner(
+        &self,
+        alerts: tokio::sync::mpsc::UnboundedSender<Alert>,
+    ) -> (ProbeRunner, tokio::sync::mpsc::UnboundedReceiver<ProbeRecord>) {
+        let (probe_runner, probe_receiver) = ProbeRunner::new(self.probe_updates.borrow().clone());
+        let mut probe_runner = probe_runner
+            .with_config_updates(self.probe_updates.subscribe())
+            .with_self_metrics(Arc::clone(&self.self_metrics))
+            .with_alerts(alerts)
+            .with_probers(self.plugins.probers());
+        if let Some(client) = &self.websocket_client {
+            probe_runner = probe_runner.with_websocket_client(client.clone());
+        }
+        (probe_runner, probe_receiver)
     }
 
     /// Start the monitoring system
     pub async fn start(&self) -> Result<()> {
         info!("Starting network monitoring system");
 
-        // Get current endpoints
-        let endpoints: Vec<Endpoint> = {
+        if self.endpoints.read().await.is_empty() {
+            warn!("No endpoints configured for monitoring yet");
+        }
+
+        // Create probe runner and aggregator
+        // Start from the latest reloaded configuration
+        let (aggregator, alert_receiver) =
+            StreamingAggregator::new(self.aggregator_updates.borrow().clone());
+        let (probe_runner, probe_receiver) = self.probe_runner(aggregator.alert_sender());
+
+        // Endpoints added or removed from now on start or stop their probes
+        // and publish the topology themselves, so the runner is published and
+        // the topology subscribed to under the same lock
+        let (endpoints, topology, topology_updates) = {
             let endpoints_guard = self.endpoints.read().await;
-            endpoints_guard.values().cloned().collect()
+            if self.probe_runner.set(probe_runner.clone()).is_err() {
+                return Err(CloudPingError::system("Monitoring has already been started"));
+            }
+            let endpoints: Vec<Endpoint> = endpoints_guard.values().cloned().collect();
+            let topology = endpoint_topology(&endpoints);
+            self.topology_updates.send_replace(topology.clone());
+            let topology_updates = self.topology_updates.subscribe();
+            drop(endpoints_guard);
+            (endpoints, topology, topology_updates)
         };
 
-        if endpoints.is_empty() {
-            warn!("No endpoints configured for monitoring");
-            return Ok(());
-        }
-
         info!("Starting monitoring for {} endpoints", endpoints.len());
+        let aggregator = aggregator
+            .with_topology(topology)
+            .with_topology_updates(topology_updates)
+            .with_score_snapshot(Arc::clone(&self.scores))
+            .with_history_snapshot(Arc::clone(&self.score_history))
+            .with_histogram_snapshot(Arc::clone(&self.rtt_histograms))
+            .with_config_updates(self.aggregator_updates.subscribe())
+            .with_self_metrics(Arc::clone(&self.self_metrics))
+            .with_events(Arc::clone(&self.events));
 
-        // Create probe runner and aggregator
-        let (probe_runner, probe_receiver) = ProbeRunner::new(self.config.probe_config.clone());
-        let (aggregator, alert_receiver) = StreamingAggregator::new(self.config.aggregator_config.clone());
+        // Availability is keyed by endpoint name so the ledger survives restarts
+        let ledger_keys: HashMap<String, String> = endpoints
+            .iter()
+            .map(|e| {
+                let name = e.metadata.get("name").unwrap_or(&e.id);
+                let key = e
+                    .namespace
+                    .as_deref()
+                    .map_or_else(|| name.clone(), |namespace| namespaced_id(namespace, name));
+                (e.id.clone(), key)
+            })
+            .collect();
 
         // Start probe runner
-        probe_runner.start_probing(endpoints).await?;
+        let enabled: Vec<Endpoint> = endpoints.into_iter().filter(|e| e.enabled).collect();
+        probe_runner.start_probing(enabled).await?;
+
+        // Forward records to the probe sink, if any
+        let sink_sender = match &self.probe_sink {
+            Some((sink, settings)) => {
+                let forwarder = ProbeForwarder::new(Arc::clone(sink), settings)
+                    .await?
+                    .with_self_metrics(Arc::clone(&self.self_metrics));
+                let (sink_sender, sink_receiver) = tokio::sync::mpsc::unbounded_channel();
+                spawn_watched(&self.self_metrics, "probe forwarder".to_string(), forwarder.run(sink_receiver));
+                Some(sink_sender)
+            }
+            None => None,
+        };
+
+        // Track availability on the way to the aggregator
+        let (forward_sender, forward_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let availability = Arc::clone(&self.availability);
+        let ledger_path = self.config.availability_ledger_path.clone();
+        let self_metrics = Arc::clone(&self.self_metrics);
+        let events = Arc::clone(&self.events);
+        spawn_watched(&self.self_metrics, "availability tracker".to_string(), async move {
+            let outlets = RecordOutlets {
+                aggregator: forward_sender,
+                sink: sink_sender,
+                events,
+            };
+            Self::track_availability(
+                probe_receiver,
+                outlets,
+                availability,
+                ledger_keys,
+                ledger_path,
+                self_metrics,
+            )
+            .await;
+        });
 
         // Start alert handler
-        let alert_broadcast = self.alert_broadcast.clone();
-        tokio::spawn(async move {
-            Self::handle_alerts(alert_receiver, alert_broadcast).await;
+        let alert_router = AlertRouter::new(
+            self.config.correlation.clone(),
+            self.config.namespace_correlation.clone(),
+        );
+        let outlets = AlertOutlets {
+            events: Arc::clone(&self.events),
+            recent_alerts: Arc::clone(&self.recent_alerts),
+            incidents: self.incident_broadcast.clone(),
+            recent_incidents: Arc::clone(&self.recent_incidents),
+        };
+        let outages = self.outage_lookup();
+        let self_metrics = Arc::clone(&self.self_metrics);
+        spawn_watched(&self.self_metrics, "alert handler".to_string(), async move {
+            Self::handle_alerts(alert_receiver, alert_router, outlets, outages, self_metrics).await;
         });
 
+        self.spawn_publishers();
+
+        // Start aggregator (this will run indefinitely)
+        Self::aggregate(aggregator, forward_receiver).await?;
+
+        Ok(())
+    }
+
+    /// Start the metrics exporter and the score snapshot publisher
+    fn spawn_publishers(&self) {
         // Start metrics exporter
-        let metrics_broadcast = self.metrics_broadcast.clone();
-        let export_interval = self.config.metrics_export_interval_ms;
-        tokio::spawn(async move {
-            Self::export_metrics_periodically(metrics_broadcast, export_interval).await;
+        let exports = MetricsExports {
+            events: Arc::clone(&self.events),
+            self_metrics: self.self_metrics_broadcast.clone(),
+        };
+        let export_interval = self.export_interval_updates.subscribe();
+        let snapshots = self.snapshots.subscribe();
+        let self_metrics = Arc::clone(&self.self_metrics);
+        spawn_watched(&self.self_metrics, "metrics exporter".to_string(), async move {
+            Self::export_metrics_periodically(exports, snapshots, self_metrics, export_interval).await;
         });
 
-        // Start aggregator (this will run indefinitely)
-        aggregator.start(probe_receiver).await.map_err(|e| {
+        // Publish the scores for readers
+        let sources = ScoreSources {
+            scores: Arc::clone(&self.scores),
+            score_history: Arc::clone(&self.score_history),
+            rtt_histograms: Arc::clone(&self.rtt_histograms),
+        };
+        spawn_watched(
+            &self.self_metrics,
+            "snapshot publisher".to_string(),
+            publish_snapshots(sources, self.snapshots.clone(), self.snapshot_interval_updates.subscribe()),
+        );
+    }
+
+    /// Run the aggregator, spread over its configured number of shards
+    async fn aggregate(
+        aggregator: StreamingAggregator,
+        probe_receiver: tokio::sync::mpsc::UnboundedReceiver<ProbeRecord>,
+    ) -> Result<()> {
+        let shards = aggregator.config().shards;
+        let aggregated = if shards > 1 {
+            ShardedAggregator::new(aggregator, shards).start(probe_receiver).await
+        } else {
+            aggregator.start(probe_receiver).await
+        };
+        aggregated.map_err(|e| {
             crate::error::CloudPingError::system(format!("Aggregator failed: {}", e))
-        })?;
+        })
+    }
 
-        Ok(())
+    /// Registry of the alert sinks, metrics sinks and probers supplied by the application
+    pub const fn plugins(&self) -> &PluginRegistry {
+        &self.plugins
     }
 
-    /// Subscribe to alerts
-    pub fn subscribe_to_alerts(&self) -> broadcast::Receiver<Alert> {
-        self.alert_broadcast.subscribe()
+    /// Subscribe to the monitoring events passing `filter`
+    ///
+    /// Alerts are raised after the correlation window; alerts that are part
+    /// of an incident are delivered through [`Self::subscribe_to_incidents`]
+    /// instead. Scores are published at the metrics export interval.
+    pub fn subscribe(&self, filter: EventFilter) -> EventSubscription {
+        self.events.subscribe(filter)
     }
 
-    /// Subscribe to metrics updates
-    pub fn subscribe_to_metrics(&self) -> broadcast::Receiver<HashMap<String, ComprehensiveScoreResult>> {
-        self.metrics_broadcast.subscribe()
+    /// Subscribe to the monitor's own health, published with every metrics export
+    #[must_use]
+    pub fn subscribe_to_self_metrics(&self) -> broadcast::Receiver<SelfMetricsSnapshot> {
+        self.self_metrics_broadcast.subscribe()
     }
 
-    /// Handle incoming alerts
+    /// Current health of the monitor itself: scheduling lag, queue depths,
+    /// lost records, task panics, aggregator latency and buffer memory
+    #[must_use]
+    pub fn self_metrics(&self) -> SelfMetricsSnapshot {
+        self.self_metrics.snapshot()
+    }
+
+    /// Subscribe to incidents grouping alerts from many endpoints
+    #[must_use]
+    pub fn subscribe_to_incidents(&self) -> broadcast::Receiver<Incident> {
+        self.incident_broadcast.subscribe()
+    }
+
+    /// Lookup of the provider incidents of alerting endpoints, started
+    /// polling the status feeds when configured
+    fn outage_lookup(&self) -> OutageLookup {
+        if let Some((client, every)) = &self.status_polling {
+            let (client, every, outages) = (Arc::clone(client), *every, Arc::clone(&self.outages));
+            spawn_watched(&self.self_metrics, "status poller".to_string(), async move {
+                let mut timer = interval(every);
+                loop {
+                    timer.tick().await;
+                    let incidents = client.fetch_incidents().await;
+                    debug!("{} active provider incidents", incidents.len());
+                    *outages.write().await = OutageCorrelator::new(incidents);
+                }
+            });
+        }
+        OutageLookup {
+            endpoints: Arc::clone(&self.endpoints),
+            outages: Arc::clone(&self.outages),
+        }
+    }
+
+    /// Record incoming alerts and pass them on after correlation
     async fn handle_alerts(
         mut alert_receiver: tokio::sync::mpsc::UnboundedReceiver<Alert>,
-        alert_broadcast: broadcast::Sender<Alert>,
+        mut alert_router: AlertRouter,
+        outlets: AlertOutlets,
+        outages: OutageLookup,
+        self_metrics: Arc<SelfMetrics>,
     ) {
-        while let Some(alert) = alert_receiver.recv().await {
-            info!("Alert received: {:?}", alert);
+        let mut flush_timer = interval(ALERT_FLUSH_INTERVAL);
 
-            // Broadcast alert to subscribers
-            if let Err(e) = alert_broadcast.send(alert) {
-                error!("Failed to broadcast alert: {}", e);
+        loop {
+            let notifications = tokio::select! {
+                alert = alert_receiver.recv() => {
+                    let Some(mut alert) = alert else { break };
+                    self_metrics.dequeued(Queue::Alert);
+                    outages.annotate(&mut alert).await;
+                    info!("Alert received: {:?}", alert);
+                    push_bounded(&outlets.recent_alerts, alert.clone(), RECENT_ALERT_LIMIT).await;
+                    alert_router.ingest(alert)
+                }
+                _ = flush_timer.tick() => alert_router.flush(TimeUtils::now()),
+            };
+            for notification in notifications {
+                outlets.deliver(notification).await;
             }
         }
+
+        // The channel is closed and empty; deliver what is still held back for grouping
+        for notification in alert_router.drain() {
+            outlets.deliver(notification).await;
+        }
+    }
+
+    /// Record up/down transitions from probe results and forward them to
+    /// the aggregator and the probe sink
+    async fn track_availability(
+        mut probe_receiver: tokio::sync::mpsc::UnboundedReceiver<ProbeRecord>,
+        outlets: RecordOutlets,
+        availability: Arc<RwLock<AvailabilityLedger>>,
+        ledger_keys: HashMap<String, String>,
+        ledger_path: Option<PathBuf>,
+        self_metrics: Arc<SelfMetrics>,
+    ) {
+        while let Some(record) = probe_receiver.recv().await {
+            self_metrics.dequeued(Queue::Probe);
+            let key = ledger_keys.get(&record.endpoint_id).unwrap_or(&record.endpoint_id);
+            let changed = availability
+                .write()
+                .await
+                .record(key, record.is_success(), record.timestamp);
+
+            if changed {
+                info!("Endpoint {} is now {}", key, if record.is_success() { "up" } else { "down" });
+                if let Some(path) = &ledger_path {
+                    // Write a snapshot off the runtime so probes keep flowing on a slow disk
+                    let ledger = availability.read().await.clone();
+                    let path = path.clone();
+                    let saved = tokio::task::spawn_blocking(move || ledger.save(&path))
+                        .await
+                        .map_err(|e| CloudPingError::system(format!("Availability ledger task failed: {e}")))
+                        .and_then(|saved| saved);
+                    if let Err(e) = saved {
+                        error!("Failed to save availability ledger: {}", e);
+                    }
+                }
+            }
+
+            if outlets.events.wants(EventKind::ProbeCompleted) {
+                outlets.events.publish(&MonitoringEvent::ProbeCompleted(record.clone()));
+            }
+            if let Some(sink_sender) = &outlets.sink {
+                // The forwarder logs its own failures; the aggregator must keep going
+                self_metrics.enqueued(Queue::Sink);
+                if sink_sender.send(record.clone()).is_err() {
+                    self_metrics.dequeued(Queue::Sink);
+                    self_metrics.dropped(1);
+                }
+            }
+            self_metrics.enqueued(Queue::Aggregator);
+            if outlets.aggregator.send(record).is_err() {
+                self_metrics.dequeued(Queue::Aggregator);
+                break;
+            }
+        }
+    }
+
+    /// Uptime over 24h/7d/30d windows for every endpoint seen so far
+    pub async fn get_availability_report(&self) -> AvailabilityReport {
+        self.availability.read().await.report(TimeUtils::now())
+    }
+
+    /// Latest published scores, at most one snapshot interval old
+    ///
+    /// Cheap to call and never waits on the aggregator; prefer it to
+    /// [`Self::get_endpoint_scores`] when the scores are only looked at.
+    pub fn snapshot(&self) -> Arc<ScoreSnapshot> {
+        Arc::clone(&self.snapshots.borrow())
+    }
+
+    /// Subscribe to score snapshots as they are published
+    pub fn subscribe_to_snapshots(&self) -> watch::Receiver<Arc<ScoreSnapshot>> {
+        self.snapshots.subscribe()
+    }
+
+    /// Latest score of every endpoint that has reported, keyed by endpoint ID
+    pub fn get_endpoint_scores(&self) -> HashMap<String, ComprehensiveScoreResult> {
+        self.snapshot().scores.clone()
+    }
+
+    /// Recent scores of every endpoint that has reported, oldest first
+    pub fn get_score_history(&self) -> HashMap<String, Vec<ScorePoint>> {
+        self.snapshot().score_history.clone()
     }
 
-    /// Export metrics periodically
+    /// Most recent alerts, newest first
+    pub async fn get_recent_alerts(&self) -> Vec<Alert> {
+        self.recent_alerts.read().await.iter().rev().cloned().collect()
+    }
+
+    /// Mark the recent alert `alert_id` as acknowledged; returns whether it was found
+    pub async fn acknowledge_alert(&self, alert_id: &str) -> bool {
+        let mut alerts = self.recent_alerts.write().await;
+        let found = alerts.iter_mut().find(|alert| alert.id == alert_id);
+        found.is_some_and(|alert| {
+            alert.acknowledge();
+            true
+        })
+    }
+
+    /// Most recent incidents, newest first
+    pub async fn get_recent_incidents(&self) -> Vec<Incident> {
+        self.recent_incidents.read().await.iter().rev().cloned().collect()
+    }
+
+    /// Most recent alerts as versioned envelopes with endpoint metadata, newest first
+    pub async fn get_recent_alert_envelopes(&self) -> Vec<AlertEnvelope> {
+        let endpoints = self.endpoints.read().await;
+        self.recent_alerts
+            .read()
+            .await
+            .iter()
+            .rev()
+            .map(|alert| AlertEnvelope::new(alert, endpoints.get(&alert.endpoint_id)))
+            .collect()
+    }
+
+    /// Export scores and the monitor's own health periodically
     async fn export_metrics_periodically(
-        metrics_broadcast: broadcast::Sender<HashMap<String, ComprehensiveScoreResult>>,
-        interval_ms: u64,
+        exports: MetricsExports,
+        snapshots: watch::Receiver<Arc<ScoreSnapshot>>,
+        self_metrics: Arc<SelfMetrics>,
+        mut interval_ms: watch::Receiver<u64>,
     ) {
-        let mut timer = interval(TimeUtils::duration_from_millis(interval_ms));
+        let mut timer = interval(TimeUtils::duration_from_millis(*interval_ms.borrow_and_update()));
 
         loop {
-            timer.tick().await;
+            tokio::select! {
+                _ = timer.tick() => {}
+                Ok(()) = interval_ms.changed() => {
+                    timer = interval(TimeUtils::duration_from_millis(*interval_ms.borrow_and_update()));
+                    continue;
+                }
+            }
 
-            // In a real implementation, you would collect metrics from the aggregator
-            // For now, we'll send an empty metrics update
-            let metrics = CollectionUtils::new_hashmap();
+            let snapshot = Arc::clone(&snapshots.borrow());
+            exports.events.publish(&MonitoringEvent::ScoreUpdated(snapshot));
 
-            if let Err(e) = metrics_broadcast.send(metrics) {
-                error!("Failed to broadcast metrics: {}", e);
-            }
+            let health = self_metrics.snapshot();
+            self_metrics.roll_peaks();
+            debug!("Monitor health: {:?}", health);
+            // Nobody may be listening for the monitor's own health
+            let _ = exports.self_metrics.send(health);
         }
     }
 
@@ -205,6 +1152,181 @@ impl NetworkMonitoringSystem {
     pub async fn get_endpoint_ids(&self) -> Vec<String> {
         self.endpoints.read().await.keys().cloned().collect()
     }
+
+    /// Get a snapshot of all configured endpoints
+    pub async fn get_endpoints(&self) -> Vec<Endpoint> {
+        self.endpoints.read().await.values().cloned().collect()
+    }
+}
+
+/// Endpoint probing `region`, or `None` with a warning when its URL has no host
+///
+/// The region's success criteria, if its metadata sets any, are carried over.
+fn region_endpoint(region: &crate::models::Region) -> Option<Endpoint> {
+    let Some((host, port, probe_type)) = url_target(&region.url) else {
+        warn!("Failed to parse URL for region {}: {}", region.name, region.url);
+        return None;
+    };
+    let mut metadata = CollectionUtils::create_metadata(&[
+        ("name", &region.name),
+        ("url", &region.url),
+        ("provider", &region.provider),
+        ("country", &region.country),
+    ]);
+    for key in [SUCCESS_MAX_RTT_METADATA_KEY, SUCCESS_STATUS_METADATA_KEY] {
+        if let Some(value) = region.metadata.get(key) {
+            metadata.insert(key.to_string(), value.clone());
+        }
+    }
+    Some(Endpoint::with_metadata(region.id.clone(), host, port, probe_type, metadata))
+}
+
+/// Endpoint probing `url` by its scheme, identified by the URL itself
+fn url_endpoint(url: &str) -> Result<Endpoint> {
+    let (host, port, probe_type) =
+        url_target(url).ok_or_else(|| CloudPingError::invalid_url(url))?;
+    Ok(Endpoint::with_metadata(
+        url.to_string(),
+        host,
+        port,
+        probe_type,
+        CollectionUtils::create_metadata(&[("name", url), ("url", url)]),
+    ))
+}
+
+/// Host, port and probe type for a region, control, WebSocket or service URL
+fn url_target(url: &str) -> Option<(String, u16, ProbeType)> {
+    let parsed = url::Url::parse(url).ok()?;
+    let host = parsed.host_str().unwrap_or(url).to_string();
+    let probe_type = match parsed.scheme() {
+        "http" | "https" => ProbeType::HTTP,
+        "ws" | "wss" => ProbeType::WebSocket,
+        "smtp" => ProbeType::SMTP,
+        "imap" => ProbeType::IMAP,
+        "redis" => ProbeType::Redis,
+        "postgres" | "postgresql" => ProbeType::Postgres,
+        "ntp" => ProbeType::NTP,
+        _ => ProbeType::TCP,
+    };
+    let port = parsed.port().unwrap_or_else(|| match parsed.scheme() {
+        "https" | "wss" => 443,
+        "http" | "ws" => 80,
+        _ => probe_type.default_port(),
+    });
+    Some((host, port, probe_type))
+}
+
+/// `ntp://` URL of an NTP server given as `host`, `host:port` or already as a URL
+fn ntp_url(server: &str) -> String {
+    if server.starts_with("ntp://") {
+        server.to_string()
+    } else {
+        format!("ntp://{server}")
+    }
+}
+
+/// Providers, control endpoint and success criteria of `endpoints`, from their metadata
+fn endpoint_topology(endpoints: &[Endpoint]) -> EndpointTopology {
+    EndpointTopology {
+        providers: endpoints
+            .iter()
+            .filter_map(|endpoint| {
+                let provider = endpoint.metadata.get("provider")?;
+                (!provider.is_empty()).then(|| (endpoint.id.clone(), provider.clone()))
+            })
+            .collect(),
+        control: endpoints
+            .iter()
+            .find(|endpoint| endpoint.metadata.get("role").is_some_and(|role| role == "control"))
+            .map(|endpoint| endpoint.id.clone()),
+        namespaces: endpoints
+            .iter()
+            .filter_map(|endpoint| Some((endpoint.id.clone(), endpoint.namespace.clone()?)))
+            .collect(),
+        success_criteria: endpoints
+            .iter()
+            .filter_map(|endpoint| {
+                SuccessCriteria::from_metadata(&endpoint.id, &endpoint.metadata)
+                    .unwrap_or_else(|e| {
+                        warn!("Ignoring the success criteria of {}: {}", endpoint.id, e);
+                        None
+                    })
+                    .map(|criteria| (endpoint.id.clone(), criteria))
+            })
+            .collect(),
+    }
+}
+
+/// Where periodic metrics exports are published
+struct MetricsExports {
+    events: Arc<EventBus>,
+    self_metrics: broadcast::Sender<SelfMetricsSnapshot>,
+}
+
+/// Where probe records go once their availability is tracked
+struct RecordOutlets {
+    aggregator: tokio::sync::mpsc::UnboundedSender<ProbeRecord>,
+    sink: Option<tokio::sync::mpsc::UnboundedSender<ProbeRecord>>,
+    events: Arc<EventBus>,
+}
+
+/// Where correlated alerts and incidents are delivered
+struct AlertOutlets {
+    events: Arc<EventBus>,
+    recent_alerts: Arc<RwLock<VecDeque<Alert>>>,
+    incidents: broadcast::Sender<Incident>,
+    recent_incidents: Arc<RwLock<VecDeque<Incident>>>,
+}
+
+impl AlertOutlets {
+    async fn deliver(&self, notification: Notification) {
+        match notification {
+            Notification::Alert(alert) => {
+                self.events.publish(&MonitoringEvent::AlertRaised(alert));
+            }
+            Notification::Incident(incident) => {
+                warn!("{} ({})", incident.summary(), incident.members.join(", "));
+                push_bounded(&self.recent_incidents, incident.clone(), RECENT_INCIDENT_LIMIT).await;
+                let _ = self.incidents.send(incident);
+            }
+        }
+    }
+}
+
+/// Provider incidents of the monitored endpoints' regions
+struct OutageLookup {
+    endpoints: Arc<RwLock<HashMap<String, Endpoint>>>,
+    outages: Arc<RwLock<OutageCorrelator>>,
+}
+
+impl OutageLookup {
+    /// Name the incident of the alerting endpoint's provider that matches
+    /// its region, for endpoints monitoring a region
+    async fn annotate(&self, alert: &mut Alert) {
+        let outages = self.outages.read().await;
+        if outages.incidents().is_empty() {
+            return;
+        }
+        let Some(endpoint) = self.endpoints.read().await.get(&alert.endpoint_id).cloned() else {
+            return;
+        };
+        let metadata = |key| endpoint.metadata.get(key).cloned().unwrap_or_default();
+        let Ok(region) = Region::new(metadata("name"), metadata("url")) else {
+            return;
+        };
+        if outages.annotate_alert(alert, &metadata("provider"), &region) {
+            debug!("Alert of {} coincides with {:?}", alert.endpoint_id, alert.provider_incident);
+        }
+    }
+}
+
+/// Append to a bounded history, dropping the oldest entry when full
+async fn push_bounded<T: Send + Sync>(history: &RwLock<VecDeque<T>>, item: T, limit: usize) {
+    let mut history = history.write().await;
+    if history.len() == limit {
+        history.pop_front();
+    }
+    history.push_back(item);
 }
 
 /// Convenience function to create a monitoring system with default config
@@ -226,6 +1348,87 @@ pub fn create_common_endpoints() -> Vec<Endpoint> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_settings_apply_to_monitoring_config() {
+        let mut settings = MonitoringSettings::default();
+        let mut config = MonitoringConfig::default();
+        settings.apply(&mut config);
+        // The defaults leave the built-in configuration unchanged
+        assert_eq!(config.metrics_export_interval_ms, 60_000);
+        assert_eq!(config.snapshot_interval_ms, 1_000);
+        assert_eq!(config.probe_config.probe_interval_ms, ProbeConfig::default().probe_interval_ms);
+        assert_eq!(config.aggregator_config.w_long, AggregatorConfig::default().w_long);
+
+        settings.probe.interval = Duration::from_secs(30);
+        settings.aggregator.availability_threshold = 99.0;
+        settings.correlation.min_endpoints = 5;
+        settings.apply(&mut config);
+        assert_eq!(config.probe_config.probe_interval_ms, 30_000);
+        assert_eq!(config.correlation.min_endpoints, 5);
+        assert!((config.aggregator_config.alert_availability_threshold - 99.0).abs() < f64::EPSILON);
+        assert!(settings.validate().is_ok());
+
+        settings.websocket_endpoints = vec!["wss://stream.example.com/socket".to_string()];
+        assert!(settings.validate().is_ok());
+        assert_eq!(
+            url_target("wss://stream.example.com/socket"),
+            Some(("stream.example.com".to_string(), 443, ProbeType::WebSocket))
+        );
+        settings.websocket_endpoints.push("https://example.com".to_string());
+        assert!(settings.validate().is_err());
+        settings.websocket_endpoints.clear();
+        settings.banner_endpoints = vec!["postgres://db.example.com".to_string()];
+        assert!(settings.validate().is_ok());
+        assert_eq!(
+            url_target("postgres://db.example.com"),
+            Some(("db.example.com".to_string(), 5432, ProbeType::Postgres))
+        );
+        settings.banner_endpoints.push("wss://stream.example.com".to_string());
+        assert!(settings.validate().is_err());
+        settings.banner_endpoints.clear();
+        settings.ntp_servers = vec!["pool.ntp.org".to_string()];
+        assert!(settings.validate().is_ok());
+        assert_eq!(
+            url_target(&ntp_url("time.example.com:1123")),
+            Some(("time.example.com".to_string(), 1123, ProbeType::NTP))
+        );
+        settings.ntp_servers.push("not a host".to_string());
+        assert!(settings.validate().is_err());
+        settings.ntp_servers.clear();
+
+        settings.probe.capture = Some(toml::from_str("endpoint = \"redis://cache.example.com\"").unwrap());
+        settings.apply(&mut config);
+        let capture = config.probe_config.capture.as_ref().unwrap();
+        assert_eq!(capture.failure_threshold, 3);
+        assert_eq!(capture.directory, std::path::PathBuf::from("captures"));
+        assert!(settings.validate().is_ok());
+        settings.probe.capture.as_mut().unwrap().max_bytes = 0;
+        assert!(settings.validate().is_err());
+        settings.probe.capture = None;
+
+        settings.aggregator.short_window = 1_000;
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_reload_publishes_new_settings() {
+        let system = NetworkMonitoringSystem::new(MonitoringConfig::default());
+        let mut probe = system.probe_updates.subscribe();
+        let mut aggregator = system.aggregator_updates.subscribe();
+
+        let mut config = MonitoringConfig::default();
+        config.probe_config.probe_interval_ms = 30_000;
+        config.aggregator_config.alert_score_drop_threshold = 35.0;
+        system.reload(&config);
+
+        assert!(probe.has_changed().unwrap());
+        assert_eq!(probe.borrow_and_update().probe_interval_ms, 30_000);
+        assert!(aggregator.has_changed().unwrap());
+        assert!(
+            (aggregator.borrow_and_update().alert_score_drop_threshold - 35.0).abs() < f64::EPSILON
+        );
+    }
+
     #[tokio::test]
     async fn test_monitoring_system_creation() {
         let config = MonitoringConfig::default();
@@ -245,12 +1448,134 @@ mod tests {
             ProbeType::HTTP,
         );
 
+        let mut events = system.subscribe(
+            EventFilter::all().kinds([EventKind::EndpointAdded, EventKind::EndpointRemoved]),
+        );
         system.add_endpoint(endpoint).await;
         assert_eq!(system.endpoint_count().await, 1);
 
         let removed = system.remove_endpoint("test").await;
         assert!(removed);
+        assert!(!system.remove_endpoint("test").await);
         assert_eq!(system.endpoint_count().await, 0);
+        assert!(matches!(events.try_recv(), Some(MonitoringEvent::EndpointAdded(endpoint)) if endpoint.id == "test"));
+        assert!(matches!(events.try_recv(), Some(MonitoringEvent::EndpointRemoved { .. })));
+        assert!(events.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_changes_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EndpointStore::new(dir.path().join("endpoints.json"));
+        let configured = || async {
+            let system = NetworkMonitoringSystem::new(MonitoringConfig::default())
+                .with_endpoint_store(store.clone());
+            system
+                .add_url_endpoints(&["redis://cache.example.com".to_string()])
+                .await
+                .unwrap();
+            system
+        };
+
+        let system = configured().await;
+        let spec = EndpointSpec {
+            url: "https://api.example.com".to_string(),
+            id: Some("api".to_string()),
+            name: Some("API".to_string()),
+            enabled: true,
+        };
+        assert!(system.create_endpoint(&spec).await.unwrap().is_some());
+        assert!(system.create_endpoint(&spec).await.unwrap().is_none());
+        let changes = EndpointChanges {
+            url: Some("https://api2.example.com".to_string()),
+            enabled: Some(false),
+            ..EndpointChanges::default()
+        };
+        let updated = system.update_endpoint("api", &changes).await.unwrap().unwrap();
+        assert_eq!((updated.host.as_str(), updated.enabled), ("api2.example.com", false));
+        assert!(system.delete_endpoint("redis://cache.example.com").await.unwrap());
+        assert!(!system.delete_endpoint("missing").await.unwrap());
+        assert!(system
+            .update_endpoint("api", &EndpointChanges { url: Some("not a url".to_string()), ..EndpointChanges::default() })
+            .await
+            .is_err());
+
+        // A restarted system applies the stored changes over its configuration
+        let system = configured().await;
+        system.restore_endpoints().await.unwrap();
+        let endpoints = system.get_endpoints().await;
+        assert_eq!(endpoints.len(), 1);
+        assert_eq!(endpoints[0].id, "api");
+        assert_eq!(endpoints[0].host, "api2.example.com");
+        assert_eq!(endpoints[0].get_metadata("name").map(String::as_str), Some("API"));
+        assert!(!endpoints[0].enabled);
+    }
+
+    #[tokio::test]
+    async fn test_namespaces() {
+        let settings: MonitoringSettings = toml::from_str(
+            r#"
+            aggregator = { short_window = 30, availability_threshold = 90.0 }
+
+            [[namespaces]]
+            name = "acme"
+            regions = ["frankfurt"]
+            urls = ["redis://cache.acme.example.com"]
+            aggregator = { sustained_loss_threshold = 1.0 }
+
+            [[namespaces]]
+            name = "globex"
+            regions = ["Frankfurt"]
+            correlation = { min_endpoints = 2 }
+            "#,
+        )
+        .unwrap();
+        assert!(settings.validate().is_ok());
+
+        let mut config = MonitoringConfig::default();
+        settings.apply(&mut config);
+        let aggregator = &config.aggregator_config;
+        assert!((aggregator.for_namespace("acme").alert_sustained_loss_threshold - 1.0).abs() < f64::EPSILON);
+        assert!((aggregator.for_namespace("globex").alert_sustained_loss_threshold - 3.0).abs() < f64::EPSILON);
+        // Settings a namespace leaves out keep their global values
+        assert_eq!(aggregator.for_namespace("acme").w_short, 30);
+        assert!((aggregator.for_namespace("acme").alert_availability_threshold - 90.0).abs() < f64::EPSILON);
+        assert_eq!(config.namespace_correlation["globex"].min_endpoints, 2);
+
+        // One region monitored for two namespaces gives two endpoints
+        let region = crate::models::Region::new("Frankfurt".to_string(), "https://fra.example.com".to_string()).unwrap();
+        let system = NetworkMonitoringSystem::new(config);
+        for namespace in &settings.namespaces {
+            system.add_namespace(namespace, std::slice::from_ref(&region)).await.unwrap();
+        }
+        let mut ids = system.get_endpoint_ids().await;
+        ids.sort();
+        assert_eq!(
+            ids,
+            [
+                format!("acme/{}", region.id),
+                "acme/redis://cache.acme.example.com".to_string(),
+                format!("globex/{}", region.id),
+            ]
+        );
+
+        let mut invalid = settings.clone();
+        invalid.namespaces[1].name = "acme".to_string();
+        assert!(invalid.validate().is_err());
+        invalid.namespaces[1].name = "a/b".to_string();
+        assert!(invalid.validate().is_err());
+        // Overrides are checked against the global settings they merge with
+        let mut invalid = settings.clone();
+        invalid.namespaces[0].aggregator = Some(AggregatorOverrides {
+            long_window: Some(10),
+            ..AggregatorOverrides::default()
+        });
+        assert!(invalid.validate().is_err());
+        let missing = NamespaceSettings {
+            regions: vec!["Tokyo".to_string()],
+            ..settings.namespaces[1].clone()
+        };
+        assert!(system.add_namespace(&missing, &[region]).await.is_err());
     }
 
     #[tokio::test]
@@ -265,4 +1590,56 @@ mod tests {
         
         assert!(system.endpoint_count().await > 0);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_alerts_name_provider_incidents() {
+        let mut frankfurt = Region::new(
+            "eu-central-1 (Frankfurt)".to_string(),
+            "https://dynamodb.eu-central-1.amazonaws.com/ping".to_string(),
+        )
+        .unwrap();
+        frankfurt.provider = "Amazon Web Services".to_string();
+        let mut tokyo = frankfurt.clone();
+        tokyo.id = "tokyo".to_string();
+        tokyo.name = "ap-northeast-1 (Tokyo)".to_string();
+        tokyo.url = "https://dynamodb.ap-northeast-1.amazonaws.com/ping".to_string();
+        let endpoints: HashMap<String, Endpoint> = [&frankfurt, &tokyo]
+            .into_iter()
+            .filter_map(region_endpoint)
+            .map(|endpoint| (endpoint.id.clone(), endpoint))
+            .collect();
+        let outages = OutageLookup {
+            endpoints: Arc::new(RwLock::new(endpoints)),
+            outages: Arc::new(RwLock::new(OutageCorrelator::new(vec![
+                crate::provider_status::ProviderIncident {
+                    provider: "Amazon Web Services".to_string(),
+                    title: "Increased latency in Frankfurt".to_string(),
+                    summary: String::new(),
+                    published: None,
+                },
+            ]))),
+        };
+
+        let (alerts, alert_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let (incidents, _) = broadcast::channel(1);
+        let outlets = AlertOutlets {
+            events: Arc::new(EventBus::new(1)),
+            recent_alerts: Arc::default(),
+            incidents,
+            recent_incidents: Arc::default(),
+        };
+        let recent_alerts = Arc::clone(&outlets.recent_alerts);
+        let alert_type = crate::models::AlertType::HighLatency { latency_ms: 500.0 };
+        for region in [&frankfurt, &tokyo] {
+            alerts.send(Alert::new(region.id.clone(), alert_type.clone())).unwrap();
+        }
+        drop(alerts);
+        let router = AlertRouter::new(CorrelationSettings::default(), HashMap::new());
+        NetworkMonitoringSystem::handle_alerts(alert_receiver, router, outlets, outages, Arc::default()).await;
+
+        let recent_alerts = recent_alerts.read().await;
+        let incidents: Vec<Option<&str>> =
+            recent_alerts.iter().map(|alert| alert.provider_incident.as_deref()).collect();
+        assert_eq!(incidents, [Some("Amazon Web Services: Increased latency in Frankfurt"), None]);
+    }
+}
@@ -4,24 +4,82 @@
 //! collection for network performance analysis.
 
 use ipnet::IpNet;
-use reqwest::{Client, ClientBuilder};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use crate::time_utils::TimeUtils;
-use crate::format_utils::FormatUtils;
-use tokio::time::timeout;
-use tracing::{debug, error, info, warn};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::field::Empty;
+use tracing::{debug, error, info, info_span, warn, Instrument};
 use url::Url;
 
 use crate::config::AppConfig;
+use crate::budget::ProbeBudget;
+use crate::connection_budget::ConnectionBudget;
 use crate::error::{CloudPingError, Result};
-use crate::models::PingStats;
+use crate::models::{CertValidation, CertValidationSummary, ConnectionReuse, ConnectionUse, EdgePop, EdgePopCount, FailureKind, LossPattern, PingStats};
+use crate::rate_limit::HostRateLimiter;
+use crate::transport::{HttpTransport, ReqwestTransport};
+
+/// Nested time limits of a ping test
+///
+/// An attempt is one request; a ping is an attempt and its retries with the
+/// delays between them; a region is every ping sent to it. Each limit is cut
+/// short by the one above it, and a request stopped by a limit fails with
+/// that limit's [`FailureKind`].
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Deadlines {
+    /// Time one request may take; `timeout_ms` when unset
+    #[serde(with = "humantime_serde", default)]
+    pub attempt: Option<Duration>,
+    /// Time one ping may take, retries included; unlimited when unset
+    #[serde(with = "humantime_serde", default)]
+    pub ping: Option<Duration>,
+    /// Time the pings to one region may take together; unlimited when unset
+    #[serde(with = "humantime_serde", default)]
+    pub region: Option<Duration>,
+}
+
+impl Deadlines {
+    /// # Errors
+    /// Returns a validation error when a limit is zero
+    pub fn validate(&self) -> Result<()> {
+        for (key, limit) in [
+            ("deadlines.attempt", self.attempt),
+            ("deadlines.ping", self.ping),
+            ("deadlines.region", self.region),
+        ] {
+            if limit.is_some_and(|limit| limit.is_zero()) {
+                return Err(CloudPingError::validation(key, "must be greater than 0"));
+            }
+        }
+        Ok(())
+    }
+
+    /// The earlier of a ping's and its region's deadline, with the failure
+    /// a request cut short by it is reported as
+    fn earliest(ping: Option<Instant>, region: Option<Instant>) -> Option<(Instant, FailureKind)> {
+        match (ping, region) {
+            (Some(ping), Some(region)) if region <= ping => Some((region, FailureKind::RegionBudget)),
+            (Some(ping), _) => Some((ping, FailureKind::PingBudget)),
+            (None, region) => region.map(|region| (region, FailureKind::RegionBudget)),
+        }
+    }
+}
 
 /// HTTP client wrapper for network performance testing
 #[derive(Debug, Clone)]
 pub struct NetworkTester {
     client: Arc<Client>,
+    transport: Arc<dyn HttpTransport>,
     config: AppConfig,
+    rate_limiter: Option<Arc<HostRateLimiter>>,
+    connection_budget: Option<ConnectionBudget>,
+    /// Probe budget each retry is charged to, with the provider it counts against
+    retry_budget: Option<(Arc<ProbeBudget>, String)>,
 }
 
 /// Timing breakdown for individual HTTP requests
@@ -36,41 +94,121 @@ pub struct RequestTiming {
     pub status_code: Option<u16>,
     pub success: bool,
     pub error_message: Option<String>,
+    /// Bytes of the request line and headers, including failed attempts before a retry
+    pub bytes_sent: u64,
+    /// Bytes of the status line, headers and body received, including failed attempts
+    pub bytes_received: u64,
+    /// Why the request failed, `None` on success
+    pub failure: Option<FailureKind>,
+    /// CDN edge POP that answered, when the response identified one
+    pub edge_pop: Option<EdgePop>,
+    /// Whether the request opened a connection or reused a pooled one, when known
+    pub connection: Option<ConnectionUse>,
+    /// Validation of the server's certificate, when the request opened a TLS connection
+    /// with `validate_certificates` on
+    pub cert_validation: Option<CertValidation>,
+}
+
+impl RequestTiming {
+    /// Timing of a request answered with `status_code` after `total_time`
+    #[must_use]
+    pub const fn succeeded(total_time: Duration, status_code: u16) -> Self {
+        Self {
+            total_time,
+            dns_lookup: None, // TODO: Extract from reqwest if available
+            tcp_connect: None,
+            tls_handshake: None,
+            request_send: None,
+            response_receive: None,
+            status_code: Some(status_code),
+            success: true,
+            error_message: None,
+            bytes_sent: 0,
+            bytes_received: 0,
+            failure: None,
+            edge_pop: None,
+            connection: None,
+            cert_validation: None,
+        }
+    }
+
+    /// Timing of a request that failed after `total_time`
+    #[must_use]
+    pub fn failed(total_time: Duration, error_message: impl Into<String>) -> Self {
+        let error_message = error_message.into();
+        Self {
+            total_time,
+            dns_lookup: None,
+            tcp_connect: None,
+            tls_handshake: None,
+            request_send: None,
+            response_receive: None,
+            status_code: None,
+            success: false,
+            failure: Some(FailureKind::classify(&error_message, None)),
+            error_message: Some(error_message),
+            bytes_sent: 0,
+            bytes_received: 0,
+            edge_pop: None,
+            connection: None,
+            cert_validation: None,
+        }
+    }
 }
 
 impl NetworkTester {
     pub fn new(config: AppConfig) -> Result<Self> {
-        let client = Self::build_http_client(&config)?;
-        Ok(Self {
-            client: Arc::new(client),
+        let transport = ReqwestTransport::new(&config)?;
+        let client = Arc::clone(transport.client());
+        Ok(Self::from_parts(client, Arc::new(transport), config))
+    }
+
+    /// Create a tester that sends requests through `transport`
+    pub fn with_transport(config: AppConfig, transport: Arc<dyn HttpTransport>) -> Result<Self> {
+        let client = Arc::clone(ReqwestTransport::new(&config)?.client());
+        Ok(Self::from_parts(client, transport, config))
+    }
+
+    fn from_parts(client: Arc<Client>, transport: Arc<dyn HttpTransport>, config: AppConfig) -> Self {
+        let rate_limiter =
+            HostRateLimiter::new(config.max_requests_per_host_per_second).map(Arc::new);
+        Self {
+            client,
+            transport,
             config,
-        })
+            rate_limiter,
+            connection_budget: None,
+            retry_budget: None,
+        }
     }
 
+    /// Hold a slot of `budget` for every request, sharing the connection cap with other components
     #[must_use]
-    pub const fn builder() -> NetworkTesterBuilder {
-        NetworkTesterBuilder::new()
+    pub fn with_connection_budget(mut self, budget: ConnectionBudget) -> Self {
+        self.connection_budget = Some(budget);
+        self
     }
 
-    /// # PERF: Configures connection pooling and TLS for optimal performance
-    fn build_http_client(config: &AppConfig) -> Result<Client> {
-        let mut builder = ClientBuilder::new()
-            .timeout(TimeUtils::duration_from_millis(config.timeout_ms))
-            .user_agent(&config.user_agent)
-            .pool_max_idle_per_host(10)
-            .pool_idle_timeout(TimeUtils::duration_from_secs(30))
-            .tcp_keepalive(TimeUtils::duration_from_secs(60));
-
-        if !config.validate_certificates {
-            builder = builder.danger_accept_invalid_certs(true);
-        }
+    /// Charge every retry to `provider` in `budget`, giving up on a ping
+    /// rather than retrying once the budget is used up
+    ///
+    /// First attempts are not charged here, as benchmarks reserve them with
+    /// their plan before sending anything.
+    #[must_use]
+    pub fn with_retry_budget(mut self, budget: Arc<ProbeBudget>, provider: String) -> Self {
+        self.retry_budget = Some((budget, provider));
+        self
+    }
 
-        // Use rustls for better performance and security
-        builder = builder.use_rustls_tls();
+    /// Connection budget shared with other components, if any
+    #[must_use]
+    pub const fn connection_budget(&self) -> Option<&ConnectionBudget> {
+        self.connection_budget.as_ref()
+    }
 
-        builder
-            .build()
-            .map_err(|e| CloudPingError::config(format!("Failed to build HTTP client: {}", e)))
+    #[must_use]
+    pub const fn builder() -> NetworkTesterBuilder {
+        NetworkTesterBuilder::new()
     }
 
     /// Add cache buster parameter to URL to prevent caching
@@ -89,6 +227,45 @@ impl NetworkTester {
         Ok(format!("{}{}{}", url, separator, cache_buster))
     }
 
+    /// First address the host of `url` resolves to; an IP host is returned as it is
+    ///
+    /// # Errors
+    /// Returns an error when `url` has no host or the host does not resolve
+    pub async fn resolve_host(url: &str) -> Result<IpAddr> {
+        let (host, addresses) = Self::resolve_addresses(url).await?;
+        addresses
+            .first()
+            .map(SocketAddr::ip)
+            .ok_or_else(|| CloudPingError::network(format!("{host} has no address")))
+    }
+
+    /// Host of `url` with every address it resolves to, in resolver order
+    /// and paired with the URL's port
+    ///
+    /// # Errors
+    /// Returns an error when `url` has no host or the host does not resolve
+    pub async fn resolve_addresses(url: &str) -> Result<(String, Vec<SocketAddr>)> {
+        let parsed = Url::parse(url)
+            .map_err(|e| CloudPingError::invalid_url(format!("Invalid URL '{url}': {e}")))?;
+        let host = parsed
+            .host_str()
+            .ok_or_else(|| CloudPingError::invalid_url(format!("URL '{url}' has no host")))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let port = parsed.port_or_known_default().unwrap_or(443);
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok((host, vec![SocketAddr::new(ip, port)]));
+        }
+        let mut addresses: Vec<SocketAddr> = Vec::new();
+        for address in tokio::net::lookup_host((host.as_str(), port)).await? {
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+        Ok((host, addresses))
+    }
+
     /// Add protocol prefix if missing, validate URL format
     pub fn validate_and_normalize_url(url: &str) -> Result<String> {
         let url = url.trim();
@@ -121,40 +298,111 @@ impl NetworkTester {
 
     /// Execute HTTP request with exponential backoff retry logic
     pub async fn ping_url_with_retry(&self, url: &str, max_retries: usize) -> RequestTiming {
+        self.ping_url_within(url, max_retries, None).await
+    }
+
+    /// Ping like [`Self::ping_url_with_retry`] within the configured ping
+    /// budget and the region's deadline, when there are any
+    async fn ping_url_within(
+        &self,
+        url: &str,
+        max_retries: usize,
+        region_deadline: Option<Instant>,
+    ) -> RequestTiming {
+        let ping_deadline = self.config.deadlines.ping.map(|budget| Instant::now() + budget);
+        let limit = Deadlines::earliest(ping_deadline, region_deadline);
+        let (mut bytes_sent, mut bytes_received) = (0, 0);
+        let mut last_failure = None;
         for attempt in 0..=max_retries {
+            if let Some((budget, provider)) = self.retry_budget.as_ref().filter(|_| attempt > 0) {
+                if let Err(e) = budget.consume(provider) {
+                    warn!("Not retrying request to {}: {}", url, e);
+                    break;
+                }
+            }
             debug!("Attempting request to {} (attempt {}/{})", url, attempt + 1, max_retries + 1);
             
-            let timing = self.perform_single_request(url).await;
-            
+            let span = info_span!(
+                "request",
+                endpoint_id = %url,
+                attempt = attempt + 1,
+                rtt_ms = Empty,
+                outcome = Empty
+            );
+            let request = self.perform_single_request(url).instrument(span.clone());
+            let mut timing = match limit {
+                Some((deadline, kind)) => {
+                    let start = Instant::now();
+                    tokio::time::timeout_at(deadline, request)
+                        .await
+                        .unwrap_or_else(|_| {
+                            warn!("Request to {} stopped: {} exceeded", url, kind);
+                            let mut timing = RequestTiming::failed(
+                                start.elapsed(),
+                                format!("Timed out: {kind} exceeded"),
+                            );
+                            timing.failure = Some(kind);
+                            timing
+                        })
+                }
+                None => request.await,
+            };
+            let outcome = match &timing.failure {
+                _ if timing.success => "ok".to_string(),
+                Some(kind) => kind.to_string(),
+                None => timing.error_message.clone().unwrap_or_default(),
+            };
+            self.config.trace_sampling.finish(
+                &span,
+                timing.total_time,
+                timing.success.then_some(timing.total_time.as_secs_f64() * 1000.0),
+                &outcome,
+            );
+            bytes_sent += timing.bytes_sent;
+            bytes_received += timing.bytes_received;
+
             if timing.success {
+                timing.bytes_sent = bytes_sent;
+                timing.bytes_received = bytes_received;
                 debug!("Request to {} succeeded in {:?}", url, timing.total_time);
                 return timing;
             }
+            last_failure = timing.failure;
 
             if attempt < max_retries {
                 let delay = TimeUtils::duration_from_millis(self.config.retry_delay_ms);
+                if let Some((deadline, kind)) = limit.filter(|(deadline, _)| Instant::now() + delay >= *deadline) {
+                    debug!("No time left for a retry to {} before {:?}", url, deadline);
+                    last_failure = Some(kind);
+                    break;
+                }
                 debug!("Request failed, retrying in {:?}", delay);
                 tokio::time::sleep(delay).await;
             }
         }
 
-        warn!("All {} attempts to {} failed", max_retries + 1, url);
-        RequestTiming {
-            total_time: TimeUtils::duration_from_millis(0),
-            dns_lookup: None,
-            tcp_connect: None,
-            tls_handshake: None,
-            request_send: None,
-            response_receive: None,
-            status_code: None,
-            success: false,
-            error_message: Some("All retry attempts failed".to_string()),
-        }
+        warn!("All attempts to {} failed", url);
+        let message = match last_failure {
+            Some(kind @ (FailureKind::PingBudget | FailureKind::RegionBudget)) => {
+                format!("Timed out: {kind} exceeded")
+            }
+            _ => "All retry attempts failed".to_string(),
+        };
+        let mut timing = RequestTiming::failed(TimeUtils::duration_from_millis(0), message);
+        timing.bytes_sent = bytes_sent;
+        timing.bytes_received = bytes_received;
+        // Keep the cause of the last attempt so reports can say why the region failed
+        timing.failure = last_failure;
+        timing
     }
 
     async fn perform_single_request(&self, url: &str) -> RequestTiming {
-        let start = Instant::now();
-        
+        if let Some(limiter) = &self.rate_limiter {
+            if let Some(host) = Url::parse(url).ok().as_ref().and_then(Url::host_str) {
+                limiter.acquire(host).await;
+            }
+        }
+
         // Add cache buster to prevent cached responses
         let url_with_cache_buster = match Self::add_cache_buster(url) {
             Ok(url) => url,
@@ -163,82 +411,96 @@ impl NetworkTester {
                 url.to_string() // Fall back to original URL
             }
         };
-        
-        let request_future = self.client.get(&url_with_cache_buster).send();
-        let timeout_duration = TimeUtils::duration_from_millis(self.config.timeout_ms);
-        
-        match timeout(timeout_duration, request_future).await {
-            Ok(Ok(response)) => {
-                let total_time = start.elapsed();
-                let status_code = response.status().as_u16();
-                let success = response.status().is_success() || 
-                             response.status().is_redirection() ||
-                             status_code == 0; // Some endpoints return 0 for successful pings
-
-                debug!("Request completed with status {} in {:?}", status_code, total_time);
-
-                RequestTiming {
-                    total_time,
-                    dns_lookup: None, // TODO: Extract from reqwest if available
-                    tcp_connect: None,
-                    tls_handshake: None,
-                    request_send: None,
-                    response_receive: None,
-                    status_code: Some(status_code),
-                    success,
-                    error_message: if success { None } else { Some(format!("HTTP {}", status_code)) },
-                }
-            }
-            Ok(Err(e)) => {
-                let total_time = start.elapsed();
-                error!("Request to {} failed: {}", url, e);
-                
-                RequestTiming {
-                    total_time,
-                    dns_lookup: None,
-                    tcp_connect: None,
-                    tls_handshake: None,
-                    request_send: None,
-                    response_receive: None,
-                    status_code: None,
-                    success: false,
-                    error_message: Some(e.to_string()),
-                }
-            }
-            Err(_) => {
-                let total_time = TimeUtils::duration_from_millis(self.config.timeout_ms);
-                warn!("Request to {} timed out after {:?}", url, total_time);
-                
-                RequestTiming {
-                    total_time,
-                    dns_lookup: None,
-                    tcp_connect: None,
-                    tls_handshake: None,
-                    request_send: None,
-                    response_receive: None,
-                    status_code: Some(408), // Request Timeout status code
-                    success: false,
-                    error_message: Some(FormatUtils::format_timeout_message(self.config.timeout_ms)),
+
+        // Take a connection slot last so waiting for the rate limit does not hold one
+        let _permit = match &self.connection_budget {
+            Some(budget) => match budget.acquire().await {
+                Ok(permit) => Some(permit),
+                Err(e) => {
+                    let mut timing = RequestTiming::failed(Duration::ZERO, e.to_string());
+                    timing.failure = Some(FailureKind::Internal);
+                    return timing;
                 }
-            }
-        }
+            },
+            None => None,
+        };
+
+        self.transport.send(&url_with_cache_buster).await
     }
 
     /// Execute multiple requests and aggregate performance statistics
     pub async fn perform_ping_test(&self, url: &str, count: usize) -> PingStats {
+        self.perform_ping_test_until(url, count, &CancellationToken::new()).await
+    }
+
+    /// Execute multiple requests like [`Self::perform_ping_test`], stopping once `cancel` fires
+    ///
+    /// A ping in flight when the token fires is abandoned. The returned stats
+    /// cover the pings completed before that, with `total_pings` reduced to
+    /// match and `incomplete` set.
+    pub async fn perform_ping_test_until(
+        &self,
+        url: &str,
+        count: usize,
+        cancel: &CancellationToken,
+    ) -> PingStats {
+        self.perform_ping_test_with_retries(url, count, self.config.retry_attempts, cancel)
+            .await
+    }
+
+    /// Execute multiple requests like [`Self::perform_ping_test_until`], retrying
+    /// each failed ping up to `retry_attempts` times instead of the configured count
+    pub async fn perform_ping_test_with_retries(
+        &self,
+        url: &str,
+        count: usize,
+        retry_attempts: usize,
+        cancel: &CancellationToken,
+    ) -> PingStats {
         info!("Starting ping test to {} with {} requests", url, count);
         let test_start = Instant::now();
+        let region_deadline = self.config.deadlines.region.map(|budget| test_start + budget);
         
         let mut stats = PingStats::new(count);
         let mut successful_latencies = Vec::new();
         let mut status_codes = Vec::new();
+        let mut edge_pops = Vec::new();
+        let mut connections = Vec::new();
+        let mut cert_validations = Vec::new();
+        let mut outcomes = Vec::with_capacity(count);
+        // Kinds in the order first seen, so ties go to the earliest
+        let mut failure_counts: Vec<(FailureKind, usize)> = Vec::new();
 
         for i in 0..count {
             debug!("Ping {}/{} to {}", i + 1, count, url);
+            if region_deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                warn!("Region budget for {} exceeded after {} of {} pings", url, i, count);
+                stats.total_pings = i;
+                stats.incomplete = true;
+                break;
+            }
             
-            let timing = self.ping_url_with_retry(url, self.config.retry_attempts).await;
+            let timing = tokio::select! {
+                timing = self.ping_url_within(url, retry_attempts, region_deadline) => timing,
+                () = cancel.cancelled() => {
+                    warn!("Ping test to {} cancelled after {} of {} pings", url, i, count);
+                    stats.total_pings = i;
+                    stats.incomplete = true;
+                    break;
+                }
+            };
+            stats.bytes_sent += timing.bytes_sent;
+            stats.bytes_received += timing.bytes_received;
+            if timing.failure == Some(FailureKind::RegionBudget) {
+                // The budget ran out with the ping in flight, which says nothing about the region
+                warn!("Region budget for {} exceeded after {} of {} pings", url, i, count);
+                stats.total_pings = i;
+                stats.incomplete = true;
+                break;
+            }
             let latency_ms = timing.total_time.as_millis() as f64;
 
+            outcomes.push(timing.success && latency_ms > 0.0);
             if timing.success && latency_ms > 0.0 {
                 stats.successful_pings += 1;
                 successful_latencies.push(latency_ms);
@@ -250,21 +512,15 @@ impl NetworkTester {
                 if let Some(code) = timing.status_code {
                     status_codes.push(code);
                 }
-            } else {
-                // For timeouts and failures, record the actual timeout duration for scoring penalty
-                let penalty_latency = if timing.error_message.as_ref()
-                    .map_or(false, |msg| msg.contains("timeout") || msg.contains("timed out")) {
-                    self.config.timeout_ms as f64 // Record full timeout duration for penalty
-                } else {
-                    0.0 // Other failures get 0
-                };
-                
-                stats.latencies.push(penalty_latency);
-                if let Some(error) = timing.error_message {
-                    if stats.error_message.is_empty() {
-                        stats.error_message = error;
-                    }
+                if let Some(edge) = timing.edge_pop {
+                    edge_pops.push((edge, latency_ms));
                 }
+                if let Some(connection) = timing.connection {
+                    connections.push((connection, latency_ms));
+                }
+                cert_validations.extend(timing.cert_validation);
+            } else {
+                self.record_failure(&mut stats, &mut failure_counts, timing);
             }
 
             // Small delay between requests to avoid overwhelming the server
@@ -275,6 +531,26 @@ impl NetworkTester {
 
         stats.test_duration_ms = test_start.elapsed().as_millis() as u64;
         stats.status_codes = status_codes;
+        stats.edge_pops = EdgePopCount::tally(edge_pops);
+        if !connections.is_empty() {
+            ConnectionReuse::tally(connections).write_metadata(&mut stats.metadata);
+        }
+        if let Some(summary) = CertValidationSummary::from_validations(&cert_validations) {
+            summary.write_metadata(&mut stats.metadata);
+        }
+        stats.loss_pattern = LossPattern::from_outcomes(outcomes);
+        // `max_by_key` keeps the last of equal counts, so search from the end
+        stats.failure_kind = failure_counts
+            .into_iter()
+            .rev()
+            .max_by_key(|&(_, count)| count)
+            .map(|(kind, _)| kind);
+
+        if stats.incomplete && stats.total_pings == 0 {
+            stats.min = 0.0;
+            stats.error_message = "Cancelled before any ping completed".to_string();
+            return stats;
+        }
         
         self.calculate_statistics(&mut stats, &successful_latencies);
         
@@ -286,45 +562,31 @@ impl NetworkTester {
         stats
     }
 
-    fn calculate_statistics(&self, stats: &mut PingStats, successful_latencies: &[f64]) {
-        let count = stats.total_pings;
-        
-        // Calculate packet loss percentage
-        stats.packet_loss = ((count - stats.successful_pings) as f64 / count as f64) * 100.0;
-
-        if stats.successful_pings > 0 {
-            stats.avg /= stats.successful_pings as f64;
-
-            // Calculate jitter and standard deviation
-            if successful_latencies.len() > 1 {
-                // Jitter: average absolute difference between consecutive measurements
-                let mut jitter_sum = 0.0;
-                for i in 1..successful_latencies.len() {
-                    jitter_sum += (successful_latencies[i] - successful_latencies[i - 1]).abs();
-                }
-                stats.jitter = jitter_sum / (successful_latencies.len() - 1) as f64;
-
-                // Standard deviation: measure of variability
-                let variance_sum: f64 = successful_latencies
-                    .iter()
-                    .map(|&latency| (latency - stats.avg).powi(2))
-                    .sum();
-                stats.standard_deviation = (variance_sum / successful_latencies.len() as f64).sqrt();
-            } else {
-                // Single successful ping
-                stats.jitter = 0.0;
-                stats.standard_deviation = 0.0;
-            }
+    /// Count a failed ping in `stats` and `failure_counts`, by kind in the order first seen
+    fn record_failure(&self, stats: &mut PingStats, failure_counts: &mut Vec<(FailureKind, usize)>, timing: RequestTiming) {
+        // For timeouts and failures, record the actual timeout duration for scoring penalty
+        let penalty_latency = if timing.error_message.as_ref()
+            .map_or(false, |msg| msg.contains("timeout") || msg.contains("timed out")) {
+            self.config.attempt_timeout().as_secs_f64() * 1000.0 // Record full timeout duration for penalty
         } else {
-            // No successful pings
-            stats.min = 0.0;
-            stats.avg = 0.0;
-            stats.jitter = 0.0;
-            stats.standard_deviation = 0.0;
+            0.0 // Other failures get 0
+        };
+
+        stats.latencies.push(penalty_latency);
+        let kind = timing.failure.unwrap_or(FailureKind::Other);
+        match failure_counts.iter_mut().find(|(seen, _)| *seen == kind) {
+            Some((_, count)) => *count += 1,
+            None => failure_counts.push((kind, 1)),
+        }
+        if let Some(error) = timing.error_message {
             if stats.error_message.is_empty() {
-                stats.error_message = "All ping attempts failed".to_string();
+                stats.error_message = error;
             }
         }
+    }
+
+    fn calculate_statistics(&self, stats: &mut PingStats, successful_latencies: &[f64]) {
+        stats.summarize(successful_latencies, self.config.jitter_algorithm);
 
         debug!(
             "Statistics calculated - avg: {:.2}ms, jitter: {:.2}ms, loss: {:.1}%, stddev: {:.2}ms",
@@ -353,12 +615,18 @@ impl NetworkTester {
 #[derive(Debug, Clone)]
 pub struct NetworkTesterBuilder {
     config: Option<AppConfig>,
+    transport: Option<Arc<dyn HttpTransport>>,
+    connection_budget: Option<ConnectionBudget>,
 }
 
 impl NetworkTesterBuilder {
     #[must_use]
     pub const fn new() -> Self {
-        Self { config: None }
+        Self {
+            config: None,
+            transport: None,
+            connection_budget: None,
+        }
     }
 
     #[must_use]
@@ -367,11 +635,30 @@ impl NetworkTesterBuilder {
         self
     }
 
+    /// Send requests through `transport` instead of the default reqwest client
+    #[must_use]
+    pub fn transport(mut self, transport: Arc<dyn HttpTransport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Share `budget` with other components that open connections
+    #[must_use]
+    pub fn connection_budget(mut self, budget: ConnectionBudget) -> Self {
+        self.connection_budget = Some(budget);
+        self
+    }
+
     /// # Errors
     /// Returns error if HTTP client configuration fails
     pub fn build(self) -> Result<NetworkTester> {
         let config = self.config.unwrap_or_default();
-        NetworkTester::new(config)
+        let mut tester = match self.transport {
+            Some(transport) => NetworkTester::with_transport(config, transport)?,
+            None => NetworkTester::new(config)?,
+        };
+        tester.connection_budget = self.connection_budget;
+        Ok(tester)
     }
 }
 
@@ -380,10 +667,50 @@ impl Default for NetworkTesterBuilder {
         Self::new()
     }
 }
+/// Bytes after the first chunk a download must bring for its throughput to count
+const MIN_THROUGHPUT_BYTES: u64 = 256 * 1024;
+
+/// Download throughput of the body of `url` in megabits per second
+///
+/// Timed from the first to the last byte read, reading up to `limit` bytes
+/// within `timeout`. `None` when the request fails or the body is too small
+/// to time.
+pub(crate) async fn throughput_mbps(client: &Client, url: &str, limit: u64, timeout: Duration) -> Option<f64> {
+    let download = async {
+        let mut response = client
+            .get(NetworkTester::add_cache_buster(url).ok()?)
+            .send()
+            .await
+            .ok()?
+            .error_for_status()
+            .ok()?;
+        response.chunk().await.ok()??;
+        let start = Instant::now();
+        let mut bytes = 0u64;
+        while bytes < limit {
+            match response.chunk().await {
+                Ok(Some(chunk)) => bytes += chunk.len() as u64,
+                Ok(None) => break,
+                Err(e) => {
+                    debug!("Download of {} failed: {}", url, e);
+                    return None;
+                }
+            }
+        }
+        Some((bytes, start.elapsed()))
+    };
+    let (bytes, elapsed) = tokio::time::timeout(timeout, download).await.ok()??;
+    #[allow(clippy::cast_precision_loss)] // at most `limit` plus a chunk
+    let mbps = (bytes >= MIN_THROUGHPUT_BYTES && !elapsed.is_zero())
+        .then(|| bytes as f64 * 8.0 / elapsed.as_secs_f64() / 1_000_000.0);
+    mbps
+}
+
 #[
 cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::{FaultConfig, FaultServer};
 
     #[test]
     fn test_add_cache_buster_no_query() {
@@ -430,4 +757,161 @@ mod tests {
         let result = NetworkTester::validate_and_normalize_url("");
         assert!(result.is_err());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_data_usage_is_counted() {
+        let server = FaultServer::start(FaultConfig::default()).await.unwrap();
+
+        let tester = NetworkTester::new(AppConfig::default()).unwrap();
+        let stats = tester.perform_ping_test(&server.url(), 2).await;
+
+        assert_eq!(stats.successful_pings, 2);
+        // Request line with the cache buster, host and user agent
+        assert!(stats.bytes_sent > 2 * 60);
+        // Status line, headers and the four-byte body
+        assert!(stats.bytes_received > 2 * (17 + 4));
+        assert_eq!(stats.data_usage_bytes(), stats.bytes_sent + stats.bytes_received);
+    }
+
+    #[tokio::test]
+    async fn test_retries_are_charged_to_the_probe_budget() {
+        let server = FaultServer::start(FaultConfig {
+            status: 503,
+            ..FaultConfig::default()
+        })
+        .await
+        .unwrap();
+        let budget = Arc::new(ProbeBudget::new(crate::budget::ProbeBudgetConfig {
+            max_requests_per_run: Some(2),
+            max_requests_per_provider_per_hour: None,
+        }));
+        let config = AppConfig {
+            retry_delay_ms: 1,
+            ..AppConfig::default()
+        };
+        let tester = NetworkTester::new(config)
+            .unwrap()
+            .with_retry_budget(Arc::clone(&budget), "AWS".to_string());
+
+        // The first attempt was reserved with the plan; two retries fit the budget
+        let timing = tester.ping_url_with_retry(&server.url(), 5).await;
+        assert!(!timing.success);
+        assert_eq!(server.request_count(), 3);
+        assert_eq!(budget.used(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_testers_share_connection_budget() {
+        let server = FaultServer::start(FaultConfig::default().latency(Duration::from_millis(30)))
+            .await
+            .unwrap();
+        let budget = ConnectionBudget::new(1);
+        let first = NetworkTester::new(AppConfig::default())
+            .unwrap()
+            .with_connection_budget(budget.clone());
+        let second = NetworkTester::builder()
+            .connection_budget(budget.clone())
+            .build()
+            .unwrap();
+
+        let url = server.url();
+        let (a, b) = tokio::join!(
+            first.perform_ping_test(&url, 3),
+            second.perform_ping_test(&url, 3)
+        );
+        assert_eq!(a.successful_pings + b.successful_pings, 6);
+
+        let stats = budget.stats();
+        assert_eq!(stats.peak_in_use, 1);
+        assert_eq!(stats.acquired, 6);
+        assert!(stats.waited > 0);
+    }
+
+    #[tokio::test]
+    async fn test_connection_reuse_metadata() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = axum::Router::new().route(
+            "/",
+            axum::routing::get(|| async {
+                // Latencies under a millisecond do not count as successful pings
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                "pong"
+            }),
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let tester = NetworkTester::new(AppConfig::default()).unwrap();
+        let stats = tester.perform_ping_test(&format!("http://{addr}/"), 3).await;
+        let reuse = ConnectionReuse::from_metadata(&stats.metadata).unwrap();
+        assert_eq!((reuse.new, reuse.reused, reuse.reconnects), (1, 2, 0));
+
+        // The fault server closes every connection after one response
+        let server = FaultServer::start(FaultConfig::default()).await.unwrap();
+        let stats = tester.perform_ping_test(&server.url(), 3).await;
+        let reuse = ConnectionReuse::from_metadata(&stats.metadata).unwrap();
+        assert_eq!((reuse.new, reuse.reused, reuse.reconnects), (3, 0, 2));
+    }
+
+    /// Transport answering every request after a latency of tokio time, which tests can pause
+    #[derive(Debug)]
+    struct SlowTransport(Duration);
+
+    impl HttpTransport for SlowTransport {
+        fn send<'a>(&'a self, _url: &'a str) -> futures::future::BoxFuture<'a, RequestTiming> {
+            Box::pin(async move {
+                tokio::time::sleep(self.0).await;
+                RequestTiming::succeeded(self.0, 200)
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ping_and_region_budgets() {
+        let slow = |latency| -> Arc<dyn HttpTransport> { Arc::new(SlowTransport(latency)) };
+        let url = "https://example.com/ping";
+
+        let mut config = AppConfig::default();
+        config.deadlines.ping = Some(Duration::from_millis(100));
+        let stats = NetworkTester::with_transport(config, slow(Duration::from_millis(300)))
+            .unwrap()
+            .perform_ping_test(url, 1)
+            .await;
+        // The budget cuts the first attempt short and leaves no time for a retry
+        assert_eq!(stats.successful_pings, 0);
+        assert_eq!(stats.failure_kind, Some(FailureKind::PingBudget));
+        assert!(stats.test_duration_ms < 300);
+
+        // Pings end at 200 and 410 ms; the third is cut at 500 ms and not counted
+        let mut config = AppConfig::default();
+        config.deadlines.region = Some(Duration::from_millis(500));
+        let stats = NetworkTester::with_transport(config, slow(Duration::from_millis(200)))
+            .unwrap()
+            .perform_ping_test(url, 10)
+            .await;
+        assert!(stats.incomplete);
+        assert_eq!((stats.total_pings, stats.successful_pings), (2, 2));
+        assert!(stats.packet_loss.abs() < f64::EPSILON);
+        assert_eq!(stats.failure_kind, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancelled_ping_test_keeps_completed_pings() {
+        let transport = Arc::new(SlowTransport(Duration::from_millis(100)));
+        let tester = NetworkTester::with_transport(AppConfig::default(), transport).unwrap();
+        let cancel = CancellationToken::new();
+        let timer = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            timer.cancel();
+        });
+
+        let stats = tester
+            .perform_ping_test_until("https://example.com/ping", 10, &cancel)
+            .await;
+        assert!(stats.incomplete);
+        assert_eq!(stats.total_pings, 2);
+        assert_eq!(stats.successful_pings, 2);
+        assert!(stats.packet_loss.abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,198 @@
+//! SNTP queries measuring the local clock's offset
+//!
+//! Every measurement is stamped with the local clock, and SLO windows and
+//! alert timelines assume that clock is sane. A query sends one client
+//! packet to an NTP server over UDP (RFC 4330) and compares the server's
+//! receive and transmit times with the local send and receive times, which
+//! gives both the offset of the local clock and the round-trip delay. The
+//! local receive time is taken from a monotonic clock so that a clock step
+//! during the query does not distort it.
+
+use std::net::SocketAddr;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use tokio::net::UdpSocket;
+use tracing::debug;
+
+use crate::models::FailureKind;
+
+/// Seconds from the NTP epoch (1900) to the Unix epoch (1970)
+const NTP_EPOCH_OFFSET: u64 = 2_208_988_800;
+
+/// Length of an NTP packet without extension fields
+const PACKET_LEN: usize = 48;
+
+/// Leap indicator 0, version 4, mode 3 (client)
+const CLIENT_HEADER: u8 = 0b00_100_011;
+
+/// Mode of a server's answer
+const MODE_SERVER: u8 = 4;
+
+/// Leap indicator of a server whose clock is not synchronized
+const LEAP_UNSYNCHRONIZED: u8 = 3;
+
+/// Offset and delay measured by one query
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NtpSample {
+    /// Offset of the server's clock from the local one in milliseconds;
+    /// positive when the local clock is behind
+    pub offset_ms: f64,
+    /// Round-trip delay without the server's processing time in milliseconds
+    pub delay_ms: f64,
+}
+
+/// Query the NTP server at `addr` once
+///
+/// # Errors
+/// Returns [`FailureKind::Connection`] when the packet cannot be sent or
+/// the server is unreachable, and [`FailureKind::Protocol`] for an answer
+/// that is not a usable server reply
+pub async fn query(addr: SocketAddr) -> Result<NtpSample, FailureKind> {
+    let bind: SocketAddr = if addr.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = UdpSocket::bind(bind).await.map_err(|e| connection_failure(&e))?;
+    socket.connect(addr).await.map_err(|e| connection_failure(&e))?;
+
+    let sent_at = SystemTime::now();
+    let start = Instant::now();
+    let transmit = to_ntp(sent_at);
+    socket
+        .send(&request_packet(transmit))
+        .await
+        .map_err(|e| connection_failure(&e))?;
+    let mut answer = [0u8; 128];
+    let len = socket.recv(&mut answer).await.map_err(|e| connection_failure(&e))?;
+    let received_at = sent_at + start.elapsed();
+
+    parse_response(&answer[..len], transmit, received_at).map_err(|kind| {
+        debug!("{} sent an unusable NTP answer", addr);
+        kind
+    })
+}
+
+/// Client packet carrying `transmit` as its transmit timestamp
+fn request_packet(transmit: u64) -> [u8; PACKET_LEN] {
+    let mut packet = [0u8; PACKET_LEN];
+    packet[0] = CLIENT_HEADER;
+    packet[40..48].copy_from_slice(&transmit.to_be_bytes());
+    packet
+}
+
+/// Offset and delay from the server's answer to the request sent with `transmit`
+fn parse_response(
+    packet: &[u8],
+    transmit: u64,
+    received_at: SystemTime,
+) -> Result<NtpSample, FailureKind> {
+    if packet.len() < PACKET_LEN {
+        return Err(FailureKind::Protocol);
+    }
+    let leap = packet[0] >> 6;
+    let mode = packet[0] & 0b111;
+    let stratum = packet[1];
+    let timestamp = |at: usize| {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&packet[at..at + 8]);
+        u64::from_be_bytes(bytes)
+    };
+    // Stratum 0 is a "kiss-o'-death" asking the client to back off; the
+    // origin timestamp must echo the request so stale or spoofed answers are dropped
+    if mode != MODE_SERVER
+        || stratum == 0
+        || leap == LEAP_UNSYNCHRONIZED
+        || timestamp(24) != transmit
+    {
+        return Err(FailureKind::Protocol);
+    }
+
+    let t1 = ntp_seconds(transmit);
+    let t2 = ntp_seconds(timestamp(32));
+    let t3 = ntp_seconds(timestamp(40));
+    let t4 = ntp_seconds(to_ntp(received_at));
+    Ok(NtpSample {
+        offset_ms: ((t2 - t1) + (t3 - t4)) / 2.0 * 1000.0,
+        delay_ms: ((t4 - t1) - (t3 - t2)) * 1000.0,
+    })
+}
+
+/// `time` as a 32.32 fixed-point NTP timestamp
+fn to_ntp(time: SystemTime) -> u64 {
+    let since_unix = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_unix.as_secs() + NTP_EPOCH_OFFSET;
+    let fraction = (u64::from(since_unix.subsec_nanos()) << 32) / 1_000_000_000;
+    (seconds << 32) | fraction
+}
+
+/// Seconds since the Unix epoch of an NTP timestamp
+fn ntp_seconds(timestamp: u64) -> f64 {
+    // Seconds since 1970 and 32-bit fractions are exact in an f64 to well below a microsecond
+    #[allow(clippy::cast_precision_loss)]
+    let seconds = (timestamp >> 32).wrapping_sub(NTP_EPOCH_OFFSET) as f64;
+    #[allow(clippy::cast_precision_loss)]
+    let fraction = (timestamp & 0xFFFF_FFFF) as f64 / 4_294_967_296.0;
+    seconds + fraction
+}
+
+fn connection_failure(error: &std::io::Error) -> FailureKind {
+    debug!("NTP query failed: {}", error);
+    FailureKind::Connection
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Answer one query as a stratum 2 server whose clock runs `ahead` of the local one
+    async fn server(ahead: Duration, stratum: u8) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut request = [0u8; PACKET_LEN];
+            let (_, client) = socket.recv_from(&mut request).await.unwrap();
+            let now = to_ntp(SystemTime::now() + ahead);
+            let mut answer = [0u8; PACKET_LEN];
+            answer[0] = 0b00_100_100;
+            answer[1] = stratum;
+            answer[24..32].copy_from_slice(&request[40..48]);
+            answer[32..40].copy_from_slice(&now.to_be_bytes());
+            answer[40..48].copy_from_slice(&now.to_be_bytes());
+            socket.send_to(&answer, client).await.unwrap();
+        });
+        addr
+    }
+
+    #[test]
+    fn test_ntp_timestamps() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_250);
+        assert_eq!(to_ntp(time) >> 32, 1_700_000_000 + NTP_EPOCH_OFFSET);
+        assert!((ntp_seconds(to_ntp(time)) - 1_700_000_000.25).abs() < 1e-6);
+
+        // An answer that does not echo the request is dropped
+        let mut answer = [0u8; PACKET_LEN];
+        answer[0] = 0b00_100_100;
+        answer[1] = 2;
+        answer[24..32].copy_from_slice(&42u64.to_be_bytes());
+        assert!(parse_response(&answer, 42, SystemTime::now()).is_ok());
+        assert_eq!(
+            parse_response(&answer, 43, SystemTime::now()),
+            Err(FailureKind::Protocol)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_measures_offset() {
+        let sample = query(server(Duration::from_secs(2), 2).await).await.unwrap();
+        assert!((sample.offset_ms - 2000.0).abs() < 50.0, "{sample:?}");
+        assert!(sample.delay_ms >= 0.0 && sample.delay_ms < 50.0, "{sample:?}");
+
+        // Kiss-o'-death
+        assert_eq!(
+            query(server(Duration::ZERO, 0).await).await,
+            Err(FailureKind::Protocol)
+        );
+    }
+}
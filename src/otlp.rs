@@ -0,0 +1,265 @@
+//! Export of round-trip histograms and scores over OTLP
+//!
+//! With `[monitoring.otlp]` set, every metrics export is also posted to an
+//! OpenTelemetry collector, or any backend taking OTLP/HTTP JSON, at
+//! `{endpoint}/v1/metrics`. Each endpoint's round trips go out as a
+//! cumulative exponential histogram, so the backend can answer for any
+//! quantile and merge agents, and its score as a gauge. Both carry the
+//! endpoint ID as the `endpoint.id` attribute; the agent is named by the
+//! `service.instance.id` resource attribute.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use reqwest::{Client, ClientBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::config::AppConfig;
+use crate::error::{CloudPingError, Result};
+use crate::models::ExponentialHistogram;
+use crate::plugins::MetricsSink;
+use crate::score_snapshot::ScoreSnapshot;
+use crate::time_utils::TimeUtils;
+
+/// Name the OTLP sink is registered under
+pub const OTLP_SINK_NAME: &str = "otlp";
+
+/// Name of the round-trip histogram metric
+pub const RTT_METRIC: &str = "cloud_ping.rtt";
+
+/// Name of the score gauge metric
+pub const SCORE_METRIC: &str = "cloud_ping.score";
+
+/// `AGGREGATION_TEMPORALITY_CUMULATIVE`: every export holds all values since `startTimeUnixNano`
+const CUMULATIVE: u8 = 2;
+
+/// OTLP export settings read from `[monitoring.otlp]`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OtlpSettings {
+    /// Base URL of the OTLP/HTTP receiver; metrics go to `{endpoint}/v1/metrics`
+    pub endpoint: String,
+    /// Headers sent with every request, such as an API key
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl OtlpSettings {
+    /// # Errors
+    /// Returns a validation error naming the first invalid setting
+    pub fn validate(&self) -> Result<()> {
+        if !url::Url::parse(&self.endpoint).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+            return Err(CloudPingError::validation(
+                "monitoring.otlp.endpoint",
+                format!("`{}` is not an http:// or https:// URL", self.endpoint),
+            ));
+        }
+        for (name, value) in &self.headers {
+            if reqwest::header::HeaderName::try_from(name.as_str()).is_err()
+                || reqwest::header::HeaderValue::try_from(value.as_str()).is_err()
+            {
+                return Err(CloudPingError::validation(
+                    "monitoring.otlp.headers",
+                    format!("`{name}` is not a valid header"),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Metrics sink posting OTLP/HTTP JSON
+#[derive(Debug, Clone)]
+pub struct OtlpMetricsSink {
+    client: Client,
+    url: String,
+    headers: BTreeMap<String, String>,
+    agent_id: String,
+}
+
+impl OtlpMetricsSink {
+    /// Sink for `settings` using the app's timeout, user agent and agent ID
+    ///
+    /// # Errors
+    /// Returns an error if the HTTP client cannot be built
+    pub fn new(config: &AppConfig, settings: &OtlpSettings) -> Result<Self> {
+        let client = ClientBuilder::new()
+            .timeout(config.get_timeout())
+            .user_agent(&config.user_agent)
+            .build()?;
+
+        Ok(Self {
+            client,
+            url: format!("{}/v1/metrics", settings.endpoint.trim_end_matches('/')),
+            headers: settings.headers.clone(),
+            agent_id: config.agent_id.clone(),
+        })
+    }
+
+    /// `ExportMetricsServiceRequest` carrying the histograms and scores of `snapshot`
+    #[must_use]
+    pub fn export_request(&self, snapshot: &ScoreSnapshot) -> Value {
+        let now = snapshot.taken_at.unwrap_or_else(TimeUtils::now);
+        let mut endpoint_ids: Vec<&String> = snapshot.rtt_histograms.keys().collect();
+        endpoint_ids.sort();
+        let histograms: Vec<Value> = endpoint_ids
+            .into_iter()
+            .map(|endpoint_id| histogram_point(endpoint_id, &snapshot.rtt_histograms[endpoint_id], now))
+            .collect();
+        let mut scores: Vec<(&String, f64)> =
+            snapshot.scores.iter().map(|(endpoint_id, score)| (endpoint_id, score.score)).collect();
+        scores.sort_by(|a, b| a.0.cmp(b.0));
+        let gauges: Vec<Value> = scores
+            .into_iter()
+            .map(|(endpoint_id, score)| {
+                json!({
+                    "attributes": [attribute("endpoint.id", endpoint_id)],
+                    "timeUnixNano": nanos(now),
+                    "asDouble": score,
+                })
+            })
+            .collect();
+
+        let mut resource = vec![attribute("service.name", env!("CARGO_PKG_NAME"))];
+        if !self.agent_id.is_empty() {
+            resource.push(attribute("service.instance.id", &self.agent_id));
+        }
+        json!({
+            "resourceMetrics": [{
+                "resource": { "attributes": resource },
+                "scopeMetrics": [{
+                    "scope": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                    "metrics": [
+                        {
+                            "name": RTT_METRIC,
+                            "description": "Round-trip time of successful probes",
+                            "unit": "ms",
+                            "exponentialHistogram": {
+                                "aggregationTemporality": CUMULATIVE,
+                                "dataPoints": histograms,
+                            },
+                        },
+                        {
+                            "name": SCORE_METRIC,
+                            "description": "Composite network score, 0 to 100",
+                            "unit": "1",
+                            "gauge": { "dataPoints": gauges },
+                        },
+                    ],
+                }],
+            }],
+        })
+    }
+
+    async fn post(&self, snapshot: &ScoreSnapshot) -> Result<()> {
+        if snapshot.rtt_histograms.is_empty() && snapshot.scores.is_empty() {
+            return Ok(());
+        }
+        let mut request = self.client.post(&self.url).json(&self.export_request(snapshot));
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+impl MetricsSink for OtlpMetricsSink {
+    fn export_scores<'a>(&'a self, snapshot: &'a ScoreSnapshot) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.post(snapshot))
+    }
+}
+
+/// `ExponentialHistogramDataPoint` of one endpoint; 64-bit integers are
+/// strings, as the OTLP JSON encoding wants
+fn histogram_point(endpoint_id: &str, histogram: &ExponentialHistogram, now: DateTime<Utc>) -> Value {
+    let mut point = json!({
+        "attributes": [attribute("endpoint.id", endpoint_id)],
+        "startTimeUnixNano": nanos(histogram.start.unwrap_or(now)),
+        "timeUnixNano": nanos(now),
+        "count": histogram.count.to_string(),
+        "sum": histogram.sum,
+        "scale": histogram.scale,
+        "zeroCount": histogram.zero_count.to_string(),
+        "positive": {
+            "offset": histogram.offset,
+            "bucketCounts": histogram.counts.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        },
+    });
+    if let (Some(min), Some(max)) = (histogram.min, histogram.max) {
+        point["min"] = json!(min);
+        point["max"] = json!(max);
+    }
+    point
+}
+
+fn attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+fn nanos(at: DateTime<Utc>) -> String {
+    at.timestamp_nanos_opt().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::scoring::compute_score;
+    use crate::models::{AggregatorState, AlgorithmWeights, ProbeRecord};
+
+    #[test]
+    fn test_export_request_carries_histograms_and_scores() {
+        let config = AppConfig {
+            agent_id: "agent-1".to_string(),
+            ..AppConfig::default()
+        };
+        let settings = OtlpSettings {
+            endpoint: "https://otlp.example.com/".to_string(),
+            headers: BTreeMap::from([("x-api-key".to_string(), "secret".to_string())]),
+        };
+        settings.validate().unwrap();
+        let sink = OtlpMetricsSink::new(&config, &settings).unwrap();
+        assert_eq!(sink.url, "https://otlp.example.com/v1/metrics");
+
+        let mut state = AggregatorState::new("api".to_string(), 8, 8);
+        let start = TimeUtils::now();
+        for rtt in [20.0, 22.0, 250.0] {
+            let mut record = ProbeRecord::success("api".to_string(), rtt);
+            record.timestamp = start;
+            state.add_record(record, 0.1);
+        }
+        let mut snapshot = ScoreSnapshot {
+            taken_at: Some(start),
+            ..ScoreSnapshot::default()
+        };
+        snapshot.scores.insert("api".to_string(), compute_score(&state, &AlgorithmWeights::default()));
+        snapshot.rtt_histograms.insert("api".to_string(), state.rtt_histogram.clone());
+
+        let request = sink.export_request(&snapshot);
+        let resource = &request["resourceMetrics"][0];
+        assert_eq!(resource["resource"]["attributes"][1]["value"]["stringValue"], "agent-1");
+        let metrics = &resource["scopeMetrics"][0]["metrics"];
+        let histogram = &metrics[0]["exponentialHistogram"];
+        assert_eq!(histogram["aggregationTemporality"], 2);
+        let point = &histogram["dataPoints"][0];
+        assert_eq!(point["attributes"][0]["value"]["stringValue"], "api");
+        assert_eq!(point["count"], "3");
+        assert_eq!(point["max"], 250.0);
+        let buckets: u64 = point["positive"]["bucketCounts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|count| count.as_str().unwrap().parse::<u64>().unwrap())
+            .sum();
+        assert_eq!(buckets, 3);
+        assert_eq!(point["startTimeUnixNano"], nanos(start));
+        assert!(metrics[1]["gauge"]["dataPoints"][0]["asDouble"].is_number());
+
+        let invalid = OtlpSettings {
+            endpoint: "otlp.example.com".to_string(),
+            headers: BTreeMap::new(),
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,340 @@
+//! Sinks and probers supplied by the embedding application
+//!
+//! Integrations such as a paging service, a metrics backend or a protocol
+//! the crate does not probe itself need not live in this crate. A library
+//! user implements [`AlertSink`], [`MetricsSink`] or [`Prober`] and
+//! registers it with the monitoring system's [`PluginRegistry`], before or
+//! while it runs:
+//!
+//! - every alert sink is handed each alert after correlation, and each incident;
+//! - every metrics sink is handed the scores at each metrics export, and the
+//!   monitor's own health;
+//! - a prober registered for a URL scheme probes the endpoints whose URL has
+//!   that scheme, in place of the TCP connect unknown schemes get otherwise.
+//!
+//! Each sink is fed by its own task, so a slow or failing sink delays
+//! neither the monitor nor the other sinks; failures are logged.
+
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use tokio::sync::broadcast;
+use tokio::task::AbortHandle;
+use tracing::{info, warn};
+
+use crate::alerting::Incident;
+use crate::error::Result;
+use crate::events::{EventBus, EventFilter, EventKind, EventSubscription, MonitoringEvent};
+use crate::models::{Alert, Endpoint};
+use crate::probe::ProbeOutcome;
+use crate::score_snapshot::ScoreSnapshot;
+use crate::self_metrics::{spawn_watched, SelfMetrics, SelfMetricsSnapshot};
+
+/// Destination of alerts and incidents
+pub trait AlertSink: std::fmt::Debug + Send + Sync {
+    /// Deliver an alert raised after the correlation window
+    ///
+    /// # Errors
+    /// An error is logged; the alert is not sent again.
+    fn send_alert<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<()>>;
+
+    /// Deliver an incident grouping alerts of many endpoints; ignored unless implemented
+    ///
+    /// # Errors
+    /// An error is logged; the incident is not sent again.
+    fn send_incident<'a>(&'a self, _incident: &'a Incident) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Destination of periodic metrics exports
+pub trait MetricsSink: std::fmt::Debug + Send + Sync {
+    /// Export the scores published at a metrics export
+    ///
+    /// # Errors
+    /// An error is logged; the next export carries newer scores.
+    fn export_scores<'a>(&'a self, snapshot: &'a ScoreSnapshot) -> BoxFuture<'a, Result<()>>;
+
+    /// Export the monitor's own health; ignored unless implemented
+    ///
+    /// # Errors
+    /// An error is logged; the next export carries newer values.
+    fn export_health<'a>(&'a self, _health: &'a SelfMetricsSnapshot) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Probe for the endpoints of one URL scheme
+pub trait Prober: std::fmt::Debug + Send + Sync {
+    /// Probe `endpoint` once, giving up after `timeout`
+    ///
+    /// Return the failure kind for a failed probe; an error is recorded as
+    /// a failure with its message as the error code. A probe still running
+    /// after `timeout` is dropped and recorded as a timeout.
+    fn probe<'a>(&'a self, endpoint: &'a Endpoint, timeout: Duration) -> BoxFuture<'a, Result<ProbeOutcome>>;
+}
+
+/// Probers by URL scheme, shared with the probe runner
+#[derive(Debug, Clone, Default)]
+pub struct Probers {
+    by_scheme: Arc<RwLock<HashMap<String, Arc<dyn Prober>>>>,
+}
+
+impl Probers {
+    /// Probe endpoints of `scheme` with `prober`, returning the one it replaces
+    pub fn register(&self, scheme: &str, prober: Arc<dyn Prober>) -> Option<Arc<dyn Prober>> {
+        self.by_scheme
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(scheme.to_ascii_lowercase(), prober)
+    }
+
+    /// Prober for `endpoint`, by the scheme of its URL
+    #[must_use]
+    pub fn for_endpoint(&self, endpoint: &Endpoint) -> Option<Arc<dyn Prober>> {
+        let by_scheme = self.by_scheme.read().unwrap_or_else(PoisonError::into_inner);
+        if by_scheme.is_empty() {
+            return None;
+        }
+        let (scheme, _) = endpoint.metadata.get("url")?.split_once("://")?;
+        by_scheme.get(&scheme.to_ascii_lowercase()).cloned()
+    }
+
+    /// Schemes with a prober, sorted
+    #[must_use]
+    pub fn schemes(&self) -> Vec<String> {
+        let mut schemes: Vec<String> = self
+            .by_scheme
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .keys()
+            .cloned()
+            .collect();
+        schemes.sort();
+        schemes
+    }
+}
+
+/// Sinks and probers registered with a monitoring system
+pub struct PluginRegistry {
+    events: Arc<EventBus>,
+    incidents: broadcast::Sender<Incident>,
+    health: broadcast::Sender<SelfMetricsSnapshot>,
+    self_metrics: Arc<SelfMetrics>,
+    probers: Probers,
+    /// Delivery task of every sink, by sink name
+    sinks: std::sync::Mutex<HashMap<String, AbortHandle>>,
+}
+
+impl PluginRegistry {
+    /// Registry feeding sinks from `events`, `incidents` and `health`
+    #[must_use]
+    pub fn new(
+        events: Arc<EventBus>,
+        incidents: broadcast::Sender<Incident>,
+        health: broadcast::Sender<SelfMetricsSnapshot>,
+        self_metrics: Arc<SelfMetrics>,
+    ) -> Self {
+        Self {
+            events,
+            incidents,
+            health,
+            self_metrics,
+            probers: Probers::default(),
+            sinks: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Hand every alert and incident from now on to `sink`
+    ///
+    /// A sink of the same name is replaced. Must be called within a Tokio runtime.
+    pub fn register_alert_sink(&self, name: &str, sink: Arc<dyn AlertSink>) {
+        let alerts = self.events.subscribe(EventFilter::all().kinds([EventKind::AlertRaised]));
+        let task = deliver_alerts(name.to_string(), sink, alerts, self.incidents.subscribe());
+        self.add_sink(name, spawn_watched(&self.self_metrics, format!("alert sink {name}"), task));
+    }
+
+    /// Hand the scores and the monitor's health of every metrics export from now on to `sink`
+    ///
+    /// A sink of the same name is replaced. Must be called within a Tokio runtime.
+    pub fn register_metrics_sink(&self, name: &str, sink: Arc<dyn MetricsSink>) {
+        let scores = self.events.subscribe(EventFilter::all().kinds([EventKind::ScoreUpdated]));
+        let task = deliver_metrics(name.to_string(), sink, scores, self.health.subscribe());
+        self.add_sink(name, spawn_watched(&self.self_metrics, format!("metrics sink {name}"), task));
+    }
+
+    /// Probe endpoints whose URL has `scheme` with `prober` from their next probe on
+    ///
+    /// A prober registered for the same scheme is replaced.
+    pub fn register_prober(&self, scheme: &str, prober: Arc<dyn Prober>) {
+        if self.probers.register(scheme, prober).is_some() {
+            info!("Replaced the prober of {}:// endpoints", scheme);
+        }
+    }
+
+    /// Stop feeding the sink `name`; returns whether one had the name
+    pub fn unregister_sink(&self, name: &str) -> bool {
+        let removed = self.sinks.lock().unwrap_or_else(PoisonError::into_inner).remove(name);
+        removed.map(|task| task.abort()).is_some()
+    }
+
+    /// Names of the registered sinks, sorted
+    #[must_use]
+    pub fn sink_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .sinks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .keys()
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Probers by scheme, shared with the probe runner
+    #[must_use]
+    pub fn probers(&self) -> Probers {
+        self.probers.clone()
+    }
+
+    fn add_sink(&self, name: &str, task: AbortHandle) {
+        let replaced = self
+            .sinks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(name.to_string(), task);
+        if let Some(replaced) = replaced {
+            replaced.abort();
+            info!("Replaced sink {}", name);
+        }
+    }
+}
+
+impl Drop for PluginRegistry {
+    fn drop(&mut self) {
+        for task in self.sinks.get_mut().unwrap_or_else(PoisonError::into_inner).values() {
+            task.abort();
+        }
+    }
+}
+
+async fn deliver_alerts(
+    name: String,
+    sink: Arc<dyn AlertSink>,
+    mut alerts: EventSubscription,
+    mut incidents: broadcast::Receiver<Incident>,
+) {
+    loop {
+        tokio::select! {
+            event = alerts.recv() => {
+                let Some(event) = event else { return };
+                if let MonitoringEvent::AlertRaised(alert) = event {
+                    if let Err(e) = sink.send_alert(&alert).await {
+                        warn!(sink = %name, "Alert sink {} failed to take alert {}: {}", name, alert.id, e);
+                    }
+                }
+            }
+            incident = incidents.recv() => match incident {
+                Ok(incident) => {
+                    if let Err(e) = sink.send_incident(&incident).await {
+                        warn!(sink = %name, "Alert sink {} failed to take an incident: {}", name, e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(sink = %name, "Alert sink {} fell behind, skipped {} incidents", name, skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    }
+}
+
+async fn deliver_metrics(
+    name: String,
+    sink: Arc<dyn MetricsSink>,
+    mut scores: EventSubscription,
+    mut health: broadcast::Receiver<SelfMetricsSnapshot>,
+) {
+    loop {
+        tokio::select! {
+            event = scores.recv() => {
+                let Some(event) = event else { return };
+                if let MonitoringEvent::ScoreUpdated(snapshot) = event {
+                    if let Err(e) = sink.export_scores(&snapshot).await {
+                        warn!(sink = %name, "Metrics sink {} failed to export scores: {}", name, e);
+                    }
+                }
+            }
+            snapshot = health.recv() => match snapshot {
+                Ok(snapshot) => {
+                    if let Err(e) = sink.export_health(&snapshot).await {
+                        warn!(sink = %name, "Metrics sink {} failed to export health: {}", name, e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(sink = %name, "Metrics sink {} fell behind, skipped {} health exports", name, skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CloudPingError;
+    use crate::models::{AlertType, ProbeType};
+    use tokio::sync::mpsc;
+
+    #[derive(Debug)]
+    struct ChannelSink(mpsc::UnboundedSender<String>);
+
+    impl AlertSink for ChannelSink {
+        fn send_alert<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<()>> {
+            let _ = self.0.send(alert.endpoint_id.clone());
+            Box::pin(async { Err(CloudPingError::system("failing after delivery")) })
+        }
+    }
+
+    #[derive(Debug)]
+    struct RefusingProber;
+
+    impl Prober for RefusingProber {
+        fn probe<'a>(&'a self, _endpoint: &'a Endpoint, _timeout: Duration) -> BoxFuture<'a, Result<ProbeOutcome>> {
+            Box::pin(async { Ok(Err(crate::models::FailureKind::Connection)) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registered_sinks_and_probers() {
+        let events = Arc::new(EventBus::new(10));
+        let (incidents, _) = broadcast::channel(10);
+        let (health, _) = broadcast::channel(10);
+        let registry = PluginRegistry::new(Arc::clone(&events), incidents, health, Arc::default());
+
+        let (sender, mut delivered) = mpsc::unbounded_channel();
+        registry.register_alert_sink("pager", Arc::new(ChannelSink(sender)));
+        assert_eq!(registry.sink_names(), ["pager"]);
+        let alert = Alert::new("api".to_string(), AlertType::HighLatency { latency_ms: 900.0 });
+        events.publish(&MonitoringEvent::AlertRaised(alert.clone()));
+        events.publish(&MonitoringEvent::AlertRaised(alert));
+        // A failing delivery does not stop the sink
+        assert_eq!(delivered.recv().await.as_deref(), Some("api"));
+        assert_eq!(delivered.recv().await.as_deref(), Some("api"));
+
+        assert!(registry.unregister_sink("pager"));
+        assert!(!registry.unregister_sink("pager"));
+        assert_eq!(delivered.recv().await, None);
+
+        registry.register_prober("MQTT", Arc::new(RefusingProber));
+        assert_eq!(registry.probers().schemes(), ["mqtt"]);
+        let mut endpoint = Endpoint::new("broker".to_string(), "broker".to_string(), 1883, ProbeType::TCP);
+        assert!(registry.probers().for_endpoint(&endpoint).is_none());
+        endpoint.set_metadata("url".to_string(), "mqtt://broker:1883".to_string());
+        assert!(registry.probers().for_endpoint(&endpoint).is_some());
+    }
+}
@@ -0,0 +1,32 @@
+//! Pricing fetcher
+//!
+//! Downloads a pricing document from `pricing_url` so region costs can be
+//! kept current without editing the data file. The document is a JSON object
+//! of prices keyed by region name, `provider/name` or URL, in the same shape
+//! as a `pricing_file`.
+
+use crate::config::AppConfig;
+use crate::error::Result;
+use crate::feed::FeedClient;
+use crate::models::RegionPricing;
+
+/// Fetches region prices from a pricing endpoint
+pub struct PricingClient {
+    feed: FeedClient,
+}
+
+impl PricingClient {
+    /// Create a client for `url` using the app's timeout, user agent and TLS settings
+    pub fn new(config: &AppConfig, url: &str) -> Result<Self> {
+        Ok(Self {
+            feed: FeedClient::new(config, url)?,
+        })
+    }
+
+    /// Fetch the pricing document with `GET {url}`
+    pub async fn fetch(&self) -> Result<RegionPricing> {
+        let pricing: RegionPricing = self.feed.fetch("pricing document").await?;
+        pricing.validate()?;
+        Ok(pricing)
+    }
+}
@@ -1,20 +1,56 @@
 //! Asynchronous endpoint probing with concurrent testing
 //!
-//! Provides TCP, HTTP, and ICMP probing capabilities with configurable
-//! concurrency limits and jitter for distributed testing.
+//! Provides TCP, HTTP, ICMP, WebSocket, NTP and service banner (SMTP, IMAP,
+//! Redis, Postgres) probing capabilities with configurable concurrency
+//! limits and jitter for distributed testing.
 
 use std::net::{SocketAddr, ToSocketAddrs};
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 use std::time::{Duration, Instant};
 use crate::time_utils::TimeUtils;
 use tokio::net::TcpStream;
-use tokio::sync::{mpsc, Semaphore};
+use tokio::sync::{mpsc, watch, Semaphore};
+use tokio::task::AbortHandle;
 use tokio::time::{sleep, timeout};
-use tracing::{debug, error, info, warn};
+use tracing::field::Empty;
+use tracing::{debug, error, info, info_span, warn, Instrument};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
+use crate::config::AppConfig;
 use crate::error::{CloudPingError, Result};
-use crate::models::{Endpoint, ProbeRecord, ProbeType};
+use crate::models::{Alert, AlertType, Endpoint, FailureKind, ProbeRecord, ProbeType};
+use crate::budget::{ProbeBudget, ProbeBudgetConfig};
+use crate::connection_budget::ConnectionBudget;
+use crate::rate_limit::HostRateLimiter;
+use crate::plugins::Probers;
+use crate::self_metrics::{Queue, SelfMetrics};
+use crate::supervisor::{spawn_supervised, RestartPolicy};
+use crate::trace_sampling::TraceSampling;
+use crate::transport::classify_request_error;
+use crate::{banner, ntp, websocket};
+
+/// Outcome of a single probe: success, or why it failed
+///
+/// A successful probe that timed its own round trip returns the timing;
+/// otherwise the time the whole probe took is recorded.  A probe can also
+/// return a timing without a round trip to report only its other fields.
+pub type ProbeOutcome = std::result::Result<Option<ProbeTiming>, FailureKind>;
+
+/// Round trip a probe timed itself, apart from setting up its connection
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProbeTiming {
+    /// Round-trip time in milliseconds, or `None` to record the time the whole probe took
+    pub rtt_ms: Option<f64>,
+    /// Time to set up the connection in milliseconds
+    pub setup_ms: Option<f64>,
+    /// Offset of the server's clock from the local one in milliseconds
+    pub clock_offset_ms: Option<f64>,
+    /// Status the server answered with, for HTTP probes
+    pub status: Option<u16>,
+}
 
 /// Configuration for probe timing and concurrency
 #[derive(Debug, Clone)]
@@ -23,6 +59,18 @@ pub struct ProbeConfig {
     pub concurrency_limit: usize,
     pub rtt_timeout_ms: u64,
     pub jitter_percent: u8,
+    /// Requests per second allowed to a single host across all probe loops (0 disables)
+    pub max_requests_per_host_per_second: f64,
+    /// Caps on requests per monitoring session and per provider per hour
+    pub budget: ProbeBudgetConfig,
+    /// Connection cap shared with the benchmark, on top of `concurrency_limit`
+    pub connection_budget: Option<ConnectionBudget>,
+    /// Time a ping/pong round trip after each WebSocket handshake
+    pub websocket_ping: bool,
+    /// Socket-level capture of one endpoint's failing probes
+    pub capture: Option<CaptureSettings>,
+    /// Which finished probes log their trace span
+    pub trace_sampling: TraceSampling,
 }
 
 impl Default for ProbeConfig {
@@ -32,7 +80,164 @@ impl Default for ProbeConfig {
             concurrency_limit: 500,
             rtt_timeout_ms: 2000,
             jitter_percent: 10,
+            max_requests_per_host_per_second: 10.0,
+            budget: ProbeBudgetConfig::default(),
+            connection_budget: None,
+            websocket_ping: true,
+            capture: None,
+            trace_sampling: TraceSampling::default(),
+        }
+    }
+}
+
+/// Probe settings read from `[monitoring.probe]` in the application config
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProbeSettings {
+    /// Time between probes of one endpoint
+    #[serde(with = "humantime_serde", default = "default_probe_interval")]
+    pub interval: Duration,
+    /// Give up on a probe after this long
+    #[serde(with = "humantime_serde", default = "default_probe_timeout")]
+    pub timeout: Duration,
+    /// Probes in flight at once
+    #[serde(default = "default_concurrency_limit")]
+    pub concurrency_limit: usize,
+    /// Random spread added to each interval, in percent
+    #[serde(default = "default_jitter_percent")]
+    pub jitter_percent: u8,
+    /// Time a ping/pong round trip after each WebSocket handshake; the
+    /// handshake alone is timed when off
+    #[serde(default = "default_websocket_ping")]
+    pub websocket_ping: bool,
+    /// Socket-level capture of one endpoint's failing probes; needs the `capture` feature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture: Option<CaptureSettings>,
+}
+
+/// Capture of one endpoint's failing probes, read from `[monitoring.probe.capture]`
+///
+/// Once the endpoint's probes have failed `failure_threshold` times in a
+/// row, every further failing probe is followed by a traced connection
+/// attempt whose socket-level events are appended to a capture file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CaptureSettings {
+    /// ID of the endpoint whose probes are captured
+    pub endpoint: String,
+    /// Consecutive failures after which failing probes are captured
+    #[serde(default = "default_capture_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Directory capture files are written to
+    #[serde(default = "default_capture_directory")]
+    pub directory: PathBuf,
+    /// Size in bytes a capture file stops growing at
+    #[serde(default = "default_capture_max_bytes")]
+    pub max_bytes: u64,
+}
+
+const fn default_capture_failure_threshold() -> u32 {
+    3
+}
+
+fn default_capture_directory() -> PathBuf {
+    PathBuf::from("captures")
+}
+
+const fn default_capture_max_bytes() -> u64 {
+    1024 * 1024
+}
+
+const fn default_probe_interval() -> Duration {
+    Duration::from_secs(5)
+}
+
+const fn default_probe_timeout() -> Duration {
+    Duration::from_secs(2)
+}
+
+const fn default_concurrency_limit() -> usize {
+    500
+}
+
+const fn default_jitter_percent() -> u8 {
+    10
+}
+
+const fn default_websocket_ping() -> bool {
+    true
+}
+
+impl Default for ProbeSettings {
+    fn default() -> Self {
+        Self {
+            interval: default_probe_interval(),
+            timeout: default_probe_timeout(),
+            concurrency_limit: default_concurrency_limit(),
+            jitter_percent: default_jitter_percent(),
+            websocket_ping: default_websocket_ping(),
+            capture: None,
+        }
+    }
+}
+
+impl ProbeSettings {
+    /// Copy these settings into a probe runner configuration
+    pub fn apply(&self, config: &mut ProbeConfig) {
+        config.probe_interval_ms = u64::try_from(self.interval.as_millis()).unwrap_or(u64::MAX);
+        config.rtt_timeout_ms = u64::try_from(self.timeout.as_millis()).unwrap_or(u64::MAX);
+        config.concurrency_limit = self.concurrency_limit;
+        config.jitter_percent = self.jitter_percent;
+        config.websocket_ping = self.websocket_ping;
+        config.capture.clone_from(&self.capture);
+    }
+
+    /// # Errors
+    /// Returns a validation error naming the first invalid setting
+    pub fn validate(&self) -> Result<()> {
+        if self.interval.is_zero() {
+            return Err(CloudPingError::validation(
+                "monitoring.probe.interval",
+                "must be greater than 0",
+            ));
+        }
+        if self.timeout.is_zero() {
+            return Err(CloudPingError::validation(
+                "monitoring.probe.timeout",
+                "must be greater than 0",
+            ));
         }
+        if self.concurrency_limit == 0 {
+            return Err(CloudPingError::validation(
+                "monitoring.probe.concurrency_limit",
+                "must be greater than 0",
+            ));
+        }
+        if self.jitter_percent > 100 {
+            return Err(CloudPingError::validation(
+                "monitoring.probe.jitter_percent",
+                "must be at most 100",
+            ));
+        }
+        if let Some(capture) = &self.capture {
+            if capture.endpoint.trim().is_empty() {
+                return Err(CloudPingError::validation(
+                    "monitoring.probe.capture.endpoint",
+                    "must name an endpoint",
+                ));
+            }
+            if capture.failure_threshold == 0 {
+                return Err(CloudPingError::validation(
+                    "monitoring.probe.capture.failure_threshold",
+                    "must be greater than 0",
+                ));
+            }
+            if capture.max_bytes == 0 {
+                return Err(CloudPingError::validation(
+                    "monitoring.probe.capture.max_bytes",
+                    "must be greater than 0",
+                ));
+            }
+        }
+        Ok(())
     }
 }
 
@@ -41,40 +246,214 @@ pub struct ProbeRunner {
     config: ProbeConfig,
     semaphore: Arc<Semaphore>,
     probe_sender: mpsc::UnboundedSender<ProbeRecord>,
+    rate_limiter: Option<Arc<HostRateLimiter>>,
+    budget: Arc<ProbeBudget>,
+    config_updates: Option<watch::Receiver<ProbeConfig>>,
+    /// Probe loop of every endpoint being probed, by endpoint ID
+    loops: Arc<Mutex<HashMap<String, AbortHandle>>>,
+    self_metrics: Arc<SelfMetrics>,
+    restart_policy: RestartPolicy,
+    /// Where to raise alerts about probe loops that keep crashing
+    alerts: Option<mpsc::UnboundedSender<Alert>>,
+    /// Probers registered for URL schemes, used before the built-in probes
+    probers: Probers,
+    /// Client WebSocket probes upgrade through, built from the default
+    /// settings on first use unless one is given
+    websocket_client: Arc<OnceLock<reqwest::Client>>,
+    #[cfg(feature = "capture")]
+    capture: Option<Arc<crate::capture::FailureCapture>>,
 }
 
 impl ProbeRunner {
     pub fn new(config: ProbeConfig) -> (Self, mpsc::UnboundedReceiver<ProbeRecord>) {
         let (probe_sender, probe_receiver) = mpsc::unbounded_channel();
         let semaphore = Arc::new(Semaphore::new(config.concurrency_limit));
+        let rate_limiter =
+            HostRateLimiter::new(config.max_requests_per_host_per_second).map(Arc::new);
+        let budget = Arc::new(ProbeBudget::new(config.budget.clone()));
+        #[cfg(feature = "capture")]
+        let capture = config
+            .capture
+            .clone()
+            .map(|settings| Arc::new(crate::capture::FailureCapture::new(settings)));
+        #[cfg(not(feature = "capture"))]
+        if let Some(capture) = &config.capture {
+            warn!(
+                "Ignoring capture of {}: built without the capture feature",
+                capture.endpoint
+            );
+        }
 
         let runner = Self {
             config,
             semaphore,
             probe_sender,
+            rate_limiter,
+            budget,
+            config_updates: None,
+            loops: Arc::default(),
+            self_metrics: Arc::default(),
+            restart_policy: RestartPolicy::default(),
+            alerts: None,
+            probers: Probers::default(),
+            websocket_client: Arc::default(),
+            #[cfg(feature = "capture")]
+            capture,
         };
 
         (runner, probe_receiver)
     }
 
+    /// Report scheduling lag, queued records and probe loop panics into `metrics`
+    #[must_use]
+    pub fn with_self_metrics(mut self, metrics: Arc<SelfMetrics>) -> Self {
+        self.self_metrics = metrics;
+        self
+    }
+
+    /// Probe endpoints whose URL has a scheme registered in `probers` with its prober
+    #[must_use]
+    pub fn with_probers(mut self, probers: Probers) -> Self {
+        self.probers = probers;
+        self
+    }
+
+    /// Open WebSocket probes through `client`, which carries the TLS,
+    /// certificate and user agent settings; see [`websocket::client`]
+    #[must_use]
+    pub fn with_websocket_client(mut self, client: reqwest::Client) -> Self {
+        self.websocket_client = Arc::new(OnceLock::from(client));
+        self
+    }
+
+    /// Restart panicked probe loops according to `policy`
+    #[must_use]
+    pub const fn with_restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.restart_policy = policy;
+        self
+    }
+
+    /// Raise a [`AlertType::ProbeLoopFailing`] alert on `alerts` when an
+    /// endpoint's probe loop keeps crashing
+    #[must_use]
+    pub fn with_alerts(mut self, alerts: mpsc::UnboundedSender<Alert>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// Follow configurations published on `updates` while probing
+    ///
+    /// Probe interval, timeout and jitter apply from each endpoint's next
+    /// probe. Concurrency, rate limits and budgets keep their initial values.
+    #[must_use]
+    pub fn with_config_updates(mut self, updates: watch::Receiver<ProbeConfig>) -> Self {
+        self.config_updates = Some(updates);
+        self
+    }
+
+    /// Read the latest configuration, including published updates
+    fn read_config<T>(&self, read: impl FnOnce(&ProbeConfig) -> T) -> T {
+        match &self.config_updates {
+            Some(updates) => read(&updates.borrow()),
+            None => read(&self.config),
+        }
+    }
+
     /// Launch probe loops for all provided endpoints
     pub async fn start_probing(&self, endpoints: Vec<Endpoint>) -> Result<()> {
         info!("Starting probe runner with {} endpoints", endpoints.len());
 
         for endpoint in endpoints {
-            let runner_clone = self.clone();
-            tokio::spawn(async move {
-                runner_clone.probe_loop(endpoint).await;
-            });
+            self.probe_endpoint(endpoint);
         }
 
         Ok(())
     }
 
+    /// Start probing one more endpoint, replacing any probe loop of the same ID
+    ///
+    /// A probe loop that panics is restarted with backoff; when it keeps
+    /// crashing, an alert is raised for the endpoint.
+    pub fn probe_endpoint(&self, endpoint: Endpoint) {
+        let id = endpoint.id.clone();
+        let runner_clone = self.clone();
+        let looped = endpoint.clone();
+        let alerts = self.alerts.clone();
+        let self_metrics = Arc::clone(&self.self_metrics);
+        let on_failing = move |restarts| {
+            let Some(alerts) = &alerts else { return };
+            let mut alert = Alert::new(endpoint.id.clone(), AlertType::ProbeLoopFailing { restarts });
+            alert.namespace.clone_from(&endpoint.namespace);
+            self_metrics.enqueued(Queue::Alert);
+            if alerts.send(alert).is_err() {
+                self_metrics.dequeued(Queue::Alert);
+            }
+        };
+        let task = spawn_supervised(
+            &self.self_metrics,
+            format!("probe loop of {id}"),
+            self.restart_policy,
+            move || {
+                let runner = runner_clone.clone();
+                let endpoint = looped.clone();
+                async move { runner.probe_loop(endpoint).await }
+            },
+            on_failing,
+        );
+        let replaced = self
+            .loops
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(id, task);
+        if let Some(replaced) = replaced {
+            replaced.abort();
+        }
+    }
+
+    /// Stop probing the endpoint `endpoint_id`; returns whether it was probed
+    pub fn stop_endpoint(&self, endpoint_id: &str) -> bool {
+        let stopped = self
+            .loops
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(endpoint_id);
+        stopped.is_some_and(|task| {
+            task.abort();
+            true
+        })
+    }
+
     async fn probe_loop(&self, endpoint: Endpoint) {
         info!("Starting probe loop for endpoint: {}", endpoint.id);
 
+        let provider = endpoint
+            .metadata
+            .get("provider")
+            .filter(|provider| !provider.is_empty())
+            .unwrap_or(&endpoint.host)
+            .clone();
+
+        let mut attempt: u64 = 0;
+        // When the next probe should start, to tell how late it actually does
+        let mut due = Instant::now();
         loop {
+            if let Err(e) = self.budget.consume(&provider) {
+                if self.budget.run_exhausted() {
+                    error!("Stopping probes of {}: {}", endpoint.id, e);
+                    break;
+                }
+                warn!("Skipping probe of {}: {}", endpoint.id, e);
+                let sleep_duration = self.calculate_sleep_duration();
+                due = Instant::now() + sleep_duration;
+                sleep(sleep_duration).await;
+                continue;
+            }
+
+            // Wait for the host's rate limit before taking a concurrency slot
+            if let Some(limiter) = &self.rate_limiter {
+                limiter.acquire(&endpoint.host).await;
+            }
+
             // Acquire semaphore permit
             let _permit = match self.semaphore.acquire().await {
                 Ok(permit) => permit,
@@ -84,44 +463,105 @@ impl ProbeRunner {
                 }
             };
 
+            let connection = match &self.config.connection_budget {
+                Some(budget) => match budget.acquire().await {
+                    Ok(permit) => Some(permit),
+                    Err(e) => {
+                        error!("Failed to acquire connection slot for {}: {}", endpoint.id, e);
+                        break;
+                    }
+                },
+                None => None,
+            };
+
+            self.self_metrics.probe_started(due.elapsed());
+            attempt += 1;
+            let span = info_span!(
+                "probe",
+                endpoint_id = %endpoint.id,
+                attempt,
+                rtt_ms = Empty,
+                outcome = Empty
+            );
             let start = Instant::now();
-            let result = self.probe_once(&endpoint).await;
+            let result = self.probe_once(&endpoint).instrument(span.clone()).await;
             let elapsed = start.elapsed();
+            // Return the shared slot now rather than holding it through the sleep below
+            drop(connection);
 
             let record = match result {
-                Ok(success) if success => {
-                    let rtt_ms = elapsed.as_millis() as f64;
-                    ProbeRecord::new(endpoint.id.clone(), Some(rtt_ms), true)
+                Ok(Ok(timing)) => {
+                    let timing = timing.unwrap_or_default();
+                    let rtt_ms = timing.rtt_ms.unwrap_or(elapsed.as_millis() as f64);
+                    ProbeRecord {
+                        setup_ms: timing.setup_ms,
+                        clock_offset_ms: timing.clock_offset_ms,
+                        status: timing.status,
+                        ..ProbeRecord::new(endpoint.id.clone(), Some(rtt_ms), true)
+                    }
                 }
-                Ok(_) => ProbeRecord::new(endpoint.id.clone(), None, false),
+                Ok(Err(kind)) => failure_record(endpoint.id.clone(), kind, elapsed),
                 Err(e) => ProbeRecord::with_error(endpoint.id.clone(), e.to_string()),
             };
 
+            let sampling = self.read_config(|config| config.trace_sampling);
+            sampling.finish(
+                &span,
+                elapsed,
+                record.rtt_ms,
+                record.error_code.as_deref().unwrap_or("ok"),
+            );
+
+            #[cfg(feature = "capture")]
+            if let Some(capture) = &self.capture {
+                let timeout_duration =
+                    TimeUtils::duration_from_millis(self.read_config(|config| config.rtt_timeout_ms));
+                capture.observe(&endpoint, &record, timeout_duration);
+            }
+
             // Send record to aggregator
+            self.self_metrics.enqueued(Queue::Probe);
             if let Err(e) = self.probe_sender.send(record) {
+                self.self_metrics.dequeued(Queue::Probe);
                 error!("Failed to send probe record for {}: {}", endpoint.id, e);
                 break;
             }
 
             // Sleep with jitter before next probe
             let sleep_duration = self.calculate_sleep_duration();
+            due = Instant::now() + sleep_duration;
             sleep(sleep_duration).await;
         }
 
         warn!("Probe loop ended for endpoint: {}", endpoint.id);
     }
 
-    async fn probe_once(&self, endpoint: &Endpoint) -> Result<bool> {
-        let timeout_duration = TimeUtils::duration_from_millis(self.config.rtt_timeout_ms);
-
+    async fn probe_once(&self, endpoint: &Endpoint) -> Result<ProbeOutcome> {
+        let timeout_duration =
+            TimeUtils::duration_from_millis(self.read_config(|config| config.rtt_timeout_ms));
+
+        if let Some(prober) = self.probers.for_endpoint(endpoint) {
+            // A prober that overruns its timeout must not stall the probe loop
+            return timeout(timeout_duration, prober.probe(endpoint, timeout_duration))
+                .await
+                .unwrap_or_else(|_| {
+                    debug!("Prober for {} timed out", endpoint.id);
+                    Ok(Err(FailureKind::Timeout))
+                });
+        }
         match endpoint.probe_type {
             ProbeType::TCP => self.probe_tcp(endpoint, timeout_duration).await,
             ProbeType::HTTP => self.probe_http(endpoint, timeout_duration).await,
             ProbeType::ICMP => self.probe_icmp(endpoint, timeout_duration).await,
+            ProbeType::WebSocket => self.probe_websocket(endpoint, timeout_duration).await,
+            ProbeType::SMTP | ProbeType::IMAP | ProbeType::Redis | ProbeType::Postgres => {
+                self.probe_banner(endpoint, timeout_duration).await
+            }
+            ProbeType::NTP => self.probe_ntp(endpoint, timeout_duration).await,
         }
     }
 
-    async fn probe_tcp(&self, endpoint: &Endpoint, timeout_duration: Duration) -> Result<bool> {
+    async fn probe_tcp(&self, endpoint: &Endpoint, timeout_duration: Duration) -> Result<ProbeOutcome> {
         let addr = format!("{}:{}", endpoint.host, endpoint.port);
         
         // Resolve address
@@ -129,7 +569,7 @@ impl ProbeRunner {
             Ok(addr) => addr,
             Err(e) => {
                 debug!("DNS resolution failed for {}: {}", addr, e);
-                return Ok(false);
+                return Ok(Err(FailureKind::Dns));
             }
         };
 
@@ -140,20 +580,20 @@ impl ProbeRunner {
             Ok(Ok(stream)) => {
                 debug!("TCP connection successful to {}", addr);
                 drop(stream); // Close connection immediately
-                Ok(true)
+                Ok(Ok(None))
             }
             Ok(Err(e)) => {
                 debug!("TCP connection failed to {}: {}", addr, e);
-                Ok(false)
+                Ok(Err(FailureKind::Connection))
             }
             Err(_) => {
                 debug!("TCP connection timed out to {}", addr);
-                Ok(false)
+                Ok(Err(FailureKind::Timeout))
             }
         }
     }
 
-    async fn probe_http(&self, endpoint: &Endpoint, timeout_duration: Duration) -> Result<bool> {
+    async fn probe_http(&self, endpoint: &Endpoint, timeout_duration: Duration) -> Result<ProbeOutcome> {
         let url = if endpoint.port == 443 || endpoint.port == 8443 {
             format!("https://{}:{}", endpoint.host, endpoint.port)
         } else {
@@ -176,19 +616,138 @@ impl ProbeRunner {
 
         match client.head(&url_with_cache_buster).send().await {
             Ok(response) => {
-                let success = response.status().is_success() || response.status().is_redirection();
-                debug!("HTTP probe to {} returned status: {}", url, response.status());
-                Ok(success)
+                let status = response.status();
+                debug!("HTTP probe to {} returned status: {}", url, status);
+                if status.is_success() || status.is_redirection() {
+                    // The status is kept for the endpoint's success criteria
+                    Ok(Ok(Some(ProbeTiming {
+                        status: Some(status.as_u16()),
+                        ..ProbeTiming::default()
+                    })))
+                } else {
+                    Ok(Err(FailureKind::HttpStatus { status: status.as_u16() }))
+                }
             }
             Err(e) => {
                 debug!("HTTP probe failed to {}: {}", url, e);
-                Ok(false)
+                Ok(Err(classify_request_error(&e)))
+            }
+        }
+    }
+
+    /// Open a WebSocket to the endpoint's `url` metadata, or to `/` on its
+    /// host and port, and time its handshake and ping/pong round trip
+    ///
+    /// Without the ping the handshake time is recorded as the round trip.
+    async fn probe_websocket(&self, endpoint: &Endpoint, timeout_duration: Duration) -> Result<ProbeOutcome> {
+        let url = endpoint
+            .metadata
+            .get("url")
+            .filter(|url| url.starts_with("ws://") || url.starts_with("wss://"))
+            .cloned()
+            .unwrap_or_else(|| {
+                let scheme = if endpoint.port == 443 || endpoint.port == 8443 { "wss" } else { "ws" };
+                format!("{scheme}://{}:{}/", endpoint.host, endpoint.port)
+            });
+        let ping = self.read_config(|config| config.websocket_ping);
+        let client = self.websocket_client.get().map_or_else(
+            || websocket::client(&AppConfig::default()).map(|client| self.websocket_client.get_or_init(|| client)),
+            Ok,
+        )?;
+
+        match timeout(timeout_duration, websocket::probe(client, &url, ping)).await {
+            Ok(Ok(timing)) => {
+                debug!("WebSocket probe to {} succeeded: {:?}", url, timing);
+                // With a ping the handshake is the setup and the pong the round trip
+                Ok(Ok(Some(ProbeTiming {
+                    rtt_ms: Some(timing.ping_rtt_ms.unwrap_or(timing.handshake_ms)),
+                    setup_ms: timing.ping_rtt_ms.map(|_| timing.handshake_ms),
+                    clock_offset_ms: None,
+                    status: None,
+                })))
+            }
+            Ok(Err(kind)) => Ok(Err(kind)),
+            Err(_) => {
+                debug!("WebSocket probe timed out to {}", url);
+                Ok(Err(FailureKind::Timeout))
+            }
+        }
+    }
+
+    /// Connect to the endpoint and wait for its service's greeting
+    ///
+    /// The connect is recorded as the setup time and the wait for the
+    /// greeting after it as the round trip.
+    async fn probe_banner(&self, endpoint: &Endpoint, timeout_duration: Duration) -> Result<ProbeOutcome> {
+        let addr = endpoint.address();
+        let socket_addr = match self.resolve_address(&addr).await {
+            Ok(addr) => addr,
+            Err(e) => {
+                debug!("DNS resolution failed for {}: {}", addr, e);
+                return Ok(Err(FailureKind::Dns));
+            }
+        };
+
+        let start = Instant::now();
+        let probe = async {
+            let mut stream = TcpStream::connect(socket_addr).await.map_err(|e| {
+                debug!("TCP connection failed to {}: {}", addr, e);
+                FailureKind::Connection
+            })?;
+            let connected = Instant::now();
+            banner::greet(&mut stream, endpoint.probe_type).await?;
+            Ok::<_, FailureKind>(ProbeTiming {
+                rtt_ms: Some(connected.elapsed().as_secs_f64() * 1000.0),
+                setup_ms: Some((connected - start).as_secs_f64() * 1000.0),
+                clock_offset_ms: None,
+                status: None,
+            })
+        };
+        match timeout(timeout_duration, probe).await {
+            Ok(Ok(timing)) => {
+                debug!("{:?} probe to {} succeeded: {:?}", endpoint.probe_type, addr, timing);
+                Ok(Ok(Some(timing)))
+            }
+            Ok(Err(kind)) => Ok(Err(kind)),
+            Err(_) => {
+                debug!("{:?} probe timed out to {}", endpoint.probe_type, addr);
+                Ok(Err(FailureKind::Timeout))
+            }
+        }
+    }
+
+    /// Query the endpoint as an NTP server, recording the delay as the round
+    /// trip and the offset of the local clock
+    async fn probe_ntp(&self, endpoint: &Endpoint, timeout_duration: Duration) -> Result<ProbeOutcome> {
+        let addr = endpoint.address();
+        let socket_addr = match self.resolve_address(&addr).await {
+            Ok(addr) => addr,
+            Err(e) => {
+                debug!("DNS resolution failed for {}: {}", addr, e);
+                return Ok(Err(FailureKind::Dns));
+            }
+        };
+
+        match timeout(timeout_duration, ntp::query(socket_addr)).await {
+            Ok(Ok(sample)) => {
+                debug!("NTP probe to {} succeeded: {:?}", addr, sample);
+                Ok(Ok(Some(ProbeTiming {
+                    rtt_ms: Some(sample.delay_ms),
+                    setup_ms: None,
+                    clock_offset_ms: Some(sample.offset_ms),
+                    status: None,
+                })))
+            }
+            Ok(Err(kind)) => Ok(Err(kind)),
+            Err(_) => {
+                debug!("NTP probe timed out to {}", addr);
+                Ok(Err(FailureKind::Timeout))
             }
         }
     }
 
     /// # OPS: ICMP requires raw socket privileges - falls back to TCP
-    async fn probe_icmp(&self, endpoint: &Endpoint, _timeout_duration: Duration) -> Result<bool> {
+    async fn probe_icmp(&self, endpoint: &Endpoint, _timeout_duration: Duration) -> Result<ProbeOutcome> {
         warn!("ICMP probing not implemented, falling back to TCP for {}", endpoint.id);
         self.probe_tcp(endpoint, _timeout_duration).await
     }
@@ -209,8 +768,9 @@ impl ProbeRunner {
 
     /// # WHY: Jitter prevents thundering herd effects in distributed probing
     fn calculate_sleep_duration(&self) -> Duration {
-        let base_ms = self.config.probe_interval_ms;
-        let jitter_range = (base_ms * self.config.jitter_percent as u64) / 100;
+        let (base_ms, jitter_percent) =
+            self.read_config(|config| (config.probe_interval_ms, config.jitter_percent));
+        let jitter_range = (base_ms * jitter_percent as u64) / 100;
         
         let mut rng = rand::thread_rng();
         let jitter = rng.gen_range(0..=jitter_range * 2) as i64 - jitter_range as i64;
@@ -226,10 +786,38 @@ impl Clone for ProbeRunner {
             config: self.config.clone(),
             semaphore: Arc::clone(&self.semaphore),
             probe_sender: self.probe_sender.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            budget: Arc::clone(&self.budget),
+            config_updates: self.config_updates.clone(),
+            loops: Arc::clone(&self.loops),
+            self_metrics: Arc::clone(&self.self_metrics),
+            restart_policy: self.restart_policy,
+            alerts: self.alerts.clone(),
+            probers: self.probers.clone(),
+            websocket_client: Arc::clone(&self.websocket_client),
+            #[cfg(feature = "capture")]
+            capture: self.capture.clone(),
         }
     }
 }
 
+/// Record of a probe of `endpoint_id` that failed with `kind` after `elapsed`
+///
+/// The failure kind is kept as the error code so the aggregator can classify
+/// it. A server answering with a status did make the round trip, which keeps
+/// its time for success criteria that list the status.
+fn failure_record(endpoint_id: String, kind: FailureKind, elapsed: Duration) -> ProbeRecord {
+    let status = match kind {
+        FailureKind::HttpStatus { status } => Some(status),
+        _ => None,
+    };
+    ProbeRecord {
+        rtt_ms: status.map(|_| elapsed.as_secs_f64() * 1000.0),
+        status,
+        ..ProbeRecord::with_error(endpoint_id, kind.to_string())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -281,7 +869,91 @@ mod tests {
 
         let result = runner.probe_tcp(&endpoint, TimeUtils::duration_from_millis(100)).await;
         assert!(result.is_ok());
-        assert!(!result.unwrap()); // Should fail
+        assert!(result.unwrap().is_err()); // Should fail
+    }
+
+    #[tokio::test]
+    async fn test_banner_probe_times_connect_and_greeting() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut ping = [0u8; 6];
+            stream.read_exact(&mut ping).await.unwrap();
+            sleep(Duration::from_millis(5)).await;
+            stream.write_all(b"+PONG\r\n").await.unwrap();
+        });
+        let (runner, _receiver) = ProbeRunner::new(ProbeConfig::default());
+        let endpoint = Endpoint::new("redis".to_string(), "127.0.0.1".to_string(), port, ProbeType::Redis);
+
+        let timing = runner
+            .probe_once(&endpoint)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(timing.rtt_ms.unwrap() >= 5.0);
+        assert!(timing.setup_ms.is_some());
+    }
+
+    #[derive(Debug)]
+    struct FixedProber;
+
+    impl crate::plugins::Prober for FixedProber {
+        fn probe<'a>(
+            &'a self,
+            _endpoint: &'a Endpoint,
+            _timeout: Duration,
+        ) -> futures::future::BoxFuture<'a, Result<ProbeOutcome>> {
+            Box::pin(async {
+                Ok(Ok(Some(ProbeTiming {
+                    rtt_ms: Some(42.0),
+                    setup_ms: None,
+                    clock_offset_ms: None,
+                    status: None,
+                })))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registered_prober_probes_its_scheme() {
+        let probers = Probers::default();
+        probers.register("mqtt", Arc::new(FixedProber));
+        let (runner, _receiver) = ProbeRunner::new(ProbeConfig::default());
+        let runner = runner.with_probers(probers);
+        let mut endpoint = Endpoint::new("broker".to_string(), "192.0.2.1".to_string(), 1883, ProbeType::TCP);
+        endpoint.set_metadata("url".to_string(), "mqtt://192.0.2.1:1883".to_string());
+
+        let timing = runner.probe_once(&endpoint).await.unwrap().unwrap().unwrap();
+        assert!((timing.rtt_ms.unwrap() - 42.0).abs() < f64::EPSILON);
+    }
+
+    #[derive(Debug)]
+    struct HangingProber;
+
+    impl crate::plugins::Prober for HangingProber {
+        fn probe<'a>(
+            &'a self,
+            _endpoint: &'a Endpoint,
+            _timeout: Duration,
+        ) -> futures::future::BoxFuture<'a, Result<ProbeOutcome>> {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_prober_ignoring_its_timeout_times_out() {
+        let probers = Probers::default();
+        probers.register("mqtt", Arc::new(HangingProber));
+        let (runner, _receiver) = ProbeRunner::new(ProbeConfig::default());
+        let runner = runner.with_probers(probers);
+        let mut endpoint = Endpoint::new("broker".to_string(), "192.0.2.1".to_string(), 1883, ProbeType::TCP);
+        endpoint.set_metadata("url".to_string(), "mqtt://192.0.2.1:1883".to_string());
+
+        let outcome = runner.probe_once(&endpoint).await.unwrap();
+        assert_eq!(outcome, Err(FailureKind::Timeout));
     }
 
     #[test]
@@ -299,4 +971,24 @@ mod tests {
             assert!(ms >= 900 && ms <= 1100, "Duration {} outside expected range", ms);
         }
     }
+
+    #[test]
+    fn test_published_config_changes_probe_interval() {
+        let config = ProbeConfig {
+            probe_interval_ms: 1000,
+            jitter_percent: 0,
+            ..Default::default()
+        };
+        let (updates, receiver) = watch::channel(config.clone());
+        let (runner, _records) = ProbeRunner::new(config.clone());
+        let runner = runner.with_config_updates(receiver);
+        assert_eq!(runner.calculate_sleep_duration(), Duration::from_secs(1));
+
+        updates.send_replace(ProbeConfig {
+            probe_interval_ms: 3000,
+            ..config
+        });
+        // Clones handed to running probe loops see the change too
+        assert_eq!(runner.clone().calculate_sleep_duration(), Duration::from_secs(3));
+    }
 }
\ No newline at end of file
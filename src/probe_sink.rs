@@ -0,0 +1,358 @@
+//! Forwarding of probe records to a remote sink
+//!
+//! With `[monitoring.probe_sink]` set, every probe record is also posted to
+//! a sink in batches, as an [`AgentProbeBatch`] naming this agent. A batch
+//! the sink does not take is appended to a [`ProbeWal`] instead, and the
+//! log is replayed, oldest records first, before the next batch is sent.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::interval;
+use tracing::{debug, error, info, warn};
+
+use crate::config::AppConfig;
+use crate::error::{CloudPingError, Result};
+use crate::models::{AgentInfo, AgentProbeBatch, ProbeRecord};
+use crate::self_metrics::{Queue, SelfMetrics};
+use crate::tls::CertValidations;
+use crate::transport::ReqwestTransport;
+use crate::wal::ProbeWal;
+
+/// Destination of forwarded probe records
+pub trait ProbeSink: std::fmt::Debug + Send + Sync {
+    /// Deliver `records`, oldest first
+    ///
+    /// An error leaves the records to be sent again later.
+    fn send<'a>(&'a self, records: &'a [ProbeRecord]) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Probe sink settings read from `[monitoring.probe_sink]`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProbeSinkSettings {
+    /// URL probe record batches are posted to as JSON
+    pub url: String,
+    /// Records sent in one batch
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Longest time a record waits before its batch is sent
+    #[serde(with = "humantime_serde", default = "default_flush_interval")]
+    pub flush_interval: Duration,
+    /// Write-ahead log holding records the sink has not taken yet
+    #[serde(default = "default_wal_path")]
+    pub wal_path: PathBuf,
+    /// Size in bytes the write-ahead log is kept under by dropping its oldest records
+    #[serde(default = "default_wal_max_bytes")]
+    pub wal_max_bytes: u64,
+}
+
+const fn default_batch_size() -> usize {
+    500
+}
+
+const fn default_flush_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_wal_path() -> PathBuf {
+    PathBuf::from("probe-wal.log")
+}
+
+const fn default_wal_max_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+impl ProbeSinkSettings {
+    /// # Errors
+    /// Returns a validation error naming the first invalid setting
+    pub fn validate(&self) -> Result<()> {
+        if !url::Url::parse(&self.url).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+            return Err(CloudPingError::validation(
+                "monitoring.probe_sink.url",
+                format!("`{}` is not an http:// or https:// URL", self.url),
+            ));
+        }
+        if self.batch_size == 0 {
+            return Err(CloudPingError::validation(
+                "monitoring.probe_sink.batch_size",
+                "must be greater than 0",
+            ));
+        }
+        if self.flush_interval.is_zero() {
+            return Err(CloudPingError::validation(
+                "monitoring.probe_sink.flush_interval",
+                "must be greater than 0",
+            ));
+        }
+        if self.wal_max_bytes == 0 {
+            return Err(CloudPingError::validation(
+                "monitoring.probe_sink.wal_max_bytes",
+                "must be greater than 0",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Sink posting [`AgentProbeBatch`] JSON to a URL
+#[derive(Debug, Clone)]
+pub struct HttpProbeSink {
+    client: Client,
+    url: String,
+    agent: AgentInfo,
+}
+
+impl HttpProbeSink {
+    /// Sink for `url` using the app's timeout, user agent, TLS settings and
+    /// agent identity
+    ///
+    /// # Errors
+    /// Returns an error if the client certificate cannot be read or the HTTP
+    /// client cannot be built
+    pub fn new(config: &AppConfig, url: &str) -> Result<Self> {
+        let client = ReqwestTransport::client_builder(config, &CertValidations::default())?
+            .timeout(config.get_timeout())
+            .build()?;
+
+        Ok(Self {
+            client,
+            url: url.to_string(),
+            agent: AgentInfo::local(&config.agent_id, &config.agent_location),
+        })
+    }
+
+    async fn post(&self, records: &[ProbeRecord]) -> Result<()> {
+        let batch = AgentProbeBatch {
+            agent: self.agent.clone(),
+            records: records.to_vec(),
+        };
+        self.client
+            .post(&self.url)
+            .json(&batch)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+impl ProbeSink for HttpProbeSink {
+    fn send<'a>(&'a self, records: &'a [ProbeRecord]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.post(records))
+    }
+}
+
+/// Batches probe records to a sink, logging what it does not take
+#[derive(Debug)]
+pub struct ProbeForwarder {
+    sink: Arc<dyn ProbeSink>,
+    wal: ProbeWal,
+    batch_size: usize,
+    flush_interval: Duration,
+    self_metrics: Arc<SelfMetrics>,
+}
+
+impl ProbeForwarder {
+    /// Forwarder to `sink`, opening the write-ahead log of `settings`
+    ///
+    /// Records left in the log by an earlier run are replayed first.
+    ///
+    /// # Errors
+    /// Returns an error if the write-ahead log cannot be opened
+    pub async fn new(sink: Arc<dyn ProbeSink>, settings: &ProbeSinkSettings) -> Result<Self> {
+        let wal = ProbeWal::open(&settings.wal_path, settings.wal_max_bytes).await?;
+        if !wal.is_empty() {
+            info!(
+                "Replaying {} bytes of buffered probe records from {}",
+                wal.len_bytes(),
+                wal.path().display()
+            );
+        }
+        Ok(Self {
+            sink,
+            wal,
+            batch_size: settings.batch_size,
+            flush_interval: settings.flush_interval,
+            self_metrics: Arc::default(),
+        })
+    }
+
+    /// Report queued and lost records into `metrics`
+    #[must_use]
+    pub fn with_self_metrics(mut self, metrics: Arc<SelfMetrics>) -> Self {
+        self.self_metrics = metrics;
+        self
+    }
+
+    /// Forward records from `receiver` until it closes
+    pub async fn run(mut self, mut receiver: UnboundedReceiver<ProbeRecord>) {
+        let mut pending = Vec::with_capacity(self.batch_size);
+        let mut flush_timer = interval(self.flush_interval);
+
+        loop {
+            tokio::select! {
+                record = receiver.recv() => {
+                    let Some(record) = record else { break };
+                    self.self_metrics.dequeued(Queue::Sink);
+                    pending.push(record);
+                    if pending.len() >= self.batch_size {
+                        self.flush(&mut pending).await;
+                    }
+                }
+                _ = flush_timer.tick() => self.flush(&mut pending).await,
+            }
+        }
+        self.flush(&mut pending).await;
+    }
+
+    /// Send `pending` after anything still in the log, logging it when the
+    /// sink is unavailable
+    pub async fn flush(&mut self, pending: &mut Vec<ProbeRecord>) {
+        let batch = std::mem::take(pending);
+        let delivered = match self.replay().await {
+            Ok(true) if !batch.is_empty() => match self.sink.send(&batch).await {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Probe sink unavailable, buffering {} records: {}", batch.len(), e);
+                    false
+                }
+            },
+            Ok(true) => true,
+            Ok(false) => false,
+            Err(e) => {
+                error!("Failed to replay buffered probe records: {}", e);
+                false
+            }
+        };
+        if delivered || batch.is_empty() {
+            return;
+        }
+
+        match self.wal.append(&batch).await {
+            Ok(0) => {}
+            Ok(dropped) => {
+                self.self_metrics.dropped(dropped);
+                warn!("Probe write-ahead log is full, dropped its {} oldest records", dropped);
+            }
+            Err(e) => {
+                self.self_metrics.dropped(batch.len());
+                error!("Lost {} probe records, the write-ahead log failed: {}", batch.len(), e);
+            }
+        }
+    }
+
+    /// Send the records in the log in batches, keeping those the sink did
+    /// not take
+    ///
+    /// Returns whether the log is now empty.
+    ///
+    /// # Errors
+    /// Returns an error if the log cannot be read or rewritten
+    pub async fn replay(&mut self) -> Result<bool> {
+        if self.wal.is_empty() {
+            return Ok(true);
+        }
+        let contents = self.wal.read().await?;
+        if contents.skipped > 0 {
+            warn!("Skipped {} damaged lines of the probe write-ahead log", contents.skipped);
+        }
+
+        let mut sent = 0;
+        for batch in contents.records.chunks(self.batch_size) {
+            if let Err(e) = self.sink.send(batch).await {
+                debug!("Probe sink still unavailable: {}", e);
+                if sent > 0 {
+                    self.wal.rewrite(&contents.records[sent..]).await?;
+                }
+                return Ok(false);
+            }
+            sent += batch.len();
+        }
+        if sent > 0 {
+            info!("Replayed {} buffered probe records", sent);
+        }
+        self.wal.clear().await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Sink that keeps what it is sent while up
+    #[derive(Debug, Default)]
+    struct MemorySink {
+        up: AtomicBool,
+        received: Mutex<Vec<String>>,
+    }
+
+    impl ProbeSink for MemorySink {
+        fn send<'a>(&'a self, records: &'a [ProbeRecord]) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async move {
+                if !self.up.load(Ordering::SeqCst) {
+                    return Err(CloudPingError::network("sink down"));
+                }
+                let mut received = self.received.lock().unwrap();
+                received.extend(records.iter().map(|record| record.endpoint_id.clone()));
+                Ok(())
+            })
+        }
+    }
+
+    fn settings(dir: &std::path::Path) -> ProbeSinkSettings {
+        ProbeSinkSettings {
+            url: "https://collector.example.com/probes".to_string(),
+            batch_size: 2,
+            flush_interval: default_flush_interval(),
+            wal_path: dir.join("probes.log"),
+            wal_max_bytes: default_wal_max_bytes(),
+        }
+    }
+
+    fn batch(ids: &[&str]) -> Vec<ProbeRecord> {
+        ids.iter().map(|id| ProbeRecord::success((*id).to_string(), 5.0)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_forwarder_replays_after_outage() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = Arc::new(MemorySink::default());
+        let mut forwarder = ProbeForwarder::new(sink.clone(), &settings(dir.path())).await.unwrap();
+
+        forwarder.flush(&mut batch(&["a", "b"])).await;
+        forwarder.flush(&mut batch(&["c"])).await;
+        assert!(sink.received.lock().unwrap().is_empty());
+        drop(forwarder);
+
+        // A restarted forwarder replays the log before newer records
+        sink.up.store(true, Ordering::SeqCst);
+        let mut forwarder = ProbeForwarder::new(sink.clone(), &settings(dir.path())).await.unwrap();
+        forwarder.flush(&mut batch(&["d"])).await;
+        assert_eq!(*sink.received.lock().unwrap(), ["a", "b", "c", "d"]);
+        assert!(forwarder.wal.is_empty());
+        assert!(!dir.path().join("probes.log").exists());
+    }
+
+    #[test]
+    fn test_probe_sink_settings_validate() {
+        let settings: ProbeSinkSettings =
+            toml::from_str("url = \"https://collector.example.com/probes\"").unwrap();
+        assert_eq!(settings.batch_size, 500);
+        assert_eq!(settings.wal_path, PathBuf::from("probe-wal.log"));
+        assert!(settings.validate().is_ok());
+        assert!(ProbeSinkSettings { url: "ftp://example.com".to_string(), ..settings.clone() }
+            .validate()
+            .is_err());
+        assert!(ProbeSinkSettings { batch_size: 0, ..settings.clone() }.validate().is_err());
+        assert!(ProbeSinkSettings { wal_max_bytes: 0, ..settings }.validate().is_err());
+    }
+}
@@ -0,0 +1,513 @@
+//! Provider status feeds and outage correlation
+//!
+//! Polls the public status pages of cloud providers (RSS/Atom feeds and
+//! Statuspage.io JSON APIs) and matches declared incidents against degraded
+//! measurements, so a slow region can be blamed on the provider rather than
+//! the local network.
+
+use chrono::{DateTime, Duration, Utc};
+use reqwest::{Client, ClientBuilder};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::config::AppConfig;
+use crate::error::{CloudPingError, Result};
+use crate::models::{Alert, PingStats, Region};
+use crate::time_utils::TimeUtils;
+
+/// Packet loss above which a measurement counts as degraded
+const DEGRADED_LOSS_PERCENT: f64 = 5.0;
+
+/// How far back feed entries are considered relevant
+const INCIDENT_LOOKBACK_HOURS: i64 = 24;
+
+/// Wire format of a provider status feed
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusFeedFormat {
+    /// RSS 2.0 or Atom feed
+    Rss,
+    /// Statuspage.io `incidents/unresolved.json` API
+    Statuspage,
+}
+
+/// A status feed published by a provider
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StatusFeed {
+    /// Provider name as it appears in the data file
+    pub provider: String,
+    /// Feed URL
+    pub url: String,
+    /// Feed format
+    pub format: StatusFeedFormat,
+}
+
+impl StatusFeed {
+    /// Create a feed description
+    #[must_use]
+    pub fn new(provider: &str, url: &str, format: StatusFeedFormat) -> Self {
+        Self {
+            provider: provider.to_string(),
+            url: url.to_string(),
+            format,
+        }
+    }
+}
+
+/// Status feeds for the providers shipped in the default data file
+#[must_use]
+pub fn default_status_feeds() -> Vec<StatusFeed> {
+    vec![
+        StatusFeed::new(
+            "Amazon Web Services",
+            "https://status.aws.amazon.com/rss/all.rss",
+            StatusFeedFormat::Rss,
+        ),
+        StatusFeed::new(
+            "Azure",
+            "https://azure.status.microsoft/en-us/status/feed/",
+            StatusFeedFormat::Rss,
+        ),
+        StatusFeed::new(
+            "Google Cloud Platform",
+            "https://status.cloud.google.com/en/feed.atom",
+            StatusFeedFormat::Rss,
+        ),
+        StatusFeed::new(
+            "DigitalOcean",
+            "https://status.digitalocean.com/api/v2/incidents/unresolved.json",
+            StatusFeedFormat::Statuspage,
+        ),
+        StatusFeed::new(
+            "Linode",
+            "https://status.linode.com/api/v2/incidents/unresolved.json",
+            StatusFeedFormat::Statuspage,
+        ),
+    ]
+}
+
+/// An incident declared on a provider status page
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProviderIncident {
+    /// Provider that declared the incident
+    pub provider: String,
+    /// Incident title
+    pub title: String,
+    /// Latest description or update text
+    pub summary: String,
+    /// When the incident was published, if the feed says
+    pub published: Option<DateTime<Utc>>,
+}
+
+impl ProviderIncident {
+    /// Whether the incident text mentions the given keyword (case-insensitive)
+    #[must_use]
+    pub fn mentions(&self, keyword: &str) -> bool {
+        let keyword = keyword.to_lowercase();
+        self.title.to_lowercase().contains(&keyword)
+            || self.summary.to_lowercase().contains(&keyword)
+    }
+
+    /// Whether the incident is described as affecting many or all regions
+    #[must_use]
+    pub fn is_widespread(&self) -> bool {
+        ["multiple regions", "all regions", "global"]
+            .iter()
+            .any(|keyword| self.mentions(keyword))
+    }
+
+    /// One-line description used in annotations
+    #[must_use]
+    pub fn headline(&self) -> String {
+        format!("{}: {}", self.provider, self.title)
+    }
+}
+
+/// Fetches incidents from provider status feeds
+pub struct ProviderStatusClient {
+    client: Client,
+    feeds: Vec<StatusFeed>,
+}
+
+impl ProviderStatusClient {
+    /// Create a client for the given feeds using the app's timeout and user agent
+    pub fn new(config: &AppConfig, feeds: Vec<StatusFeed>) -> Result<Self> {
+        let client = ClientBuilder::new()
+            .timeout(config.get_timeout())
+            .user_agent(&config.user_agent)
+            .build()?;
+
+        Ok(Self { client, feeds })
+    }
+
+    /// Fetch incidents from every feed, skipping feeds that fail
+    pub async fn fetch_incidents(&self) -> Vec<ProviderIncident> {
+        let fetches = self.feeds.iter().map(|feed| async move {
+            match self.fetch_feed(feed).await {
+                Ok(incidents) => incidents,
+                Err(e) => {
+                    warn!("Failed to fetch status feed for {}: {}", feed.provider, e);
+                    Vec::new()
+                }
+            }
+        });
+
+        futures::future::join_all(fetches)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Fetch and parse a single feed
+    pub async fn fetch_feed(&self, feed: &StatusFeed) -> Result<Vec<ProviderIncident>> {
+        debug!("Fetching status feed {}", feed.url);
+        let body = self
+            .client
+            .get(&feed.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        match feed.format {
+            StatusFeedFormat::Rss => {
+                let since = TimeUtils::now() - Duration::hours(INCIDENT_LOOKBACK_HOURS);
+                Ok(parse_rss(&feed.provider, &body, since))
+            }
+            StatusFeedFormat::Statuspage => parse_statuspage(&feed.provider, &body),
+        }
+    }
+}
+
+/// Parse RSS `<item>` or Atom `<entry>` elements published after `since`
+///
+/// Entries without a parseable date are kept, since some feeds omit them.
+#[must_use]
+pub fn parse_rss(provider: &str, body: &str, since: DateTime<Utc>) -> Vec<ProviderIncident> {
+    let (open, close) = if body.contains("<entry") {
+        ("<entry", "</entry>")
+    } else {
+        ("<item", "</item>")
+    };
+
+    body.split(open)
+        .skip(1)
+        .filter_map(|chunk| chunk.split(close).next())
+        .filter_map(|item| {
+            let title = xml_text(item, "title")?;
+            let summary = xml_text(item, "description")
+                .or_else(|| xml_text(item, "summary"))
+                .or_else(|| xml_text(item, "content"))
+                .unwrap_or_default();
+            let published = xml_text(item, "pubDate")
+                .and_then(|date| DateTime::parse_from_rfc2822(&date).ok())
+                .or_else(|| {
+                    xml_text(item, "updated")
+                        .and_then(|date| DateTime::parse_from_rfc3339(&date).ok())
+                })
+                .map(|date| date.with_timezone(&Utc));
+
+            if published.is_some_and(|date| date < since) {
+                return None;
+            }
+
+            Some(ProviderIncident {
+                provider: provider.to_string(),
+                title,
+                summary,
+                published,
+            })
+        })
+        .collect()
+}
+
+/// Text content of the first `<tag>` element, with CDATA and entities decoded
+fn xml_text(fragment: &str, tag: &str) -> Option<String> {
+    let start = fragment.find(&format!("<{tag}"))?;
+    let after_open = start + fragment[start..].find('>')? + 1;
+    let end = after_open + fragment[after_open..].find(&format!("</{tag}>"))?;
+    let raw = fragment[after_open..end].trim();
+
+    let text = raw
+        .strip_prefix("<![CDATA[")
+        .and_then(|inner| inner.strip_suffix("]]>"))
+        .unwrap_or(raw);
+
+    Some(
+        text.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&amp;", "&")
+            .trim()
+            .to_string(),
+    )
+}
+
+#[derive(Deserialize)]
+struct StatuspageResponse {
+    incidents: Vec<StatuspageIncident>,
+}
+
+#[derive(Deserialize)]
+struct StatuspageIncident {
+    name: String,
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    incident_updates: Vec<StatuspageUpdate>,
+    #[serde(default)]
+    components: Vec<StatuspageComponent>,
+}
+
+#[derive(Deserialize)]
+struct StatuspageUpdate {
+    body: String,
+}
+
+#[derive(Deserialize)]
+struct StatuspageComponent {
+    name: String,
+}
+
+/// Parse a Statuspage.io unresolved incidents response
+pub fn parse_statuspage(provider: &str, body: &str) -> Result<Vec<ProviderIncident>> {
+    let response: StatuspageResponse = serde_json::from_str(body).map_err(|e| {
+        CloudPingError::data_loading(format!("Invalid status page response from {provider}: {e}"))
+    })?;
+
+    Ok(response
+        .incidents
+        .into_iter()
+        .map(|incident| {
+            let mut summary = incident
+                .incident_updates
+                .into_iter()
+                .next()
+                .map(|update| update.body)
+                .unwrap_or_default();
+            if !incident.components.is_empty() {
+                let components: Vec<String> =
+                    incident.components.into_iter().map(|c| c.name).collect();
+                summary = format!("{summary} (affected: {})", components.join(", "));
+            }
+
+            ProviderIncident {
+                provider: provider.to_string(),
+                title: incident.name,
+                summary,
+                published: incident.created_at,
+            }
+        })
+        .collect())
+}
+
+/// A degraded measurement that coincides with a provider incident
+#[derive(Debug, Clone, Serialize)]
+pub struct IncidentAnnotation {
+    /// Region whose measurement was degraded
+    pub region: String,
+    /// Provider of the region
+    pub provider: String,
+    /// Matching incident
+    pub incident: ProviderIncident,
+}
+
+/// Matches degraded measurements against declared provider incidents
+#[derive(Debug, Clone, Default)]
+pub struct OutageCorrelator {
+    incidents: Vec<ProviderIncident>,
+}
+
+impl OutageCorrelator {
+    /// Create a correlator over a set of active incidents
+    #[must_use]
+    pub const fn new(incidents: Vec<ProviderIncident>) -> Self {
+        Self { incidents }
+    }
+
+    /// Known incidents
+    #[must_use]
+    pub fn incidents(&self) -> &[ProviderIncident] {
+        &self.incidents
+    }
+
+    /// Whether a measurement looks degraded enough to look for an incident
+    #[must_use]
+    pub fn is_degraded(stats: &PingStats) -> bool {
+        !stats.is_successful() || 100.0 - stats.success_rate() > DEGRADED_LOSS_PERCENT
+    }
+
+    /// Find an incident from `provider` that plausibly affects `region`
+    ///
+    /// An incident matches when it names the region (code or city) or is
+    /// described as widespread.
+    #[must_use]
+    pub fn find_incident(&self, provider: &str, region: &Region) -> Option<&ProviderIncident> {
+        let keywords = region_keywords(region);
+
+        self.incidents
+            .iter()
+            .filter(|incident| same_provider(&incident.provider, provider))
+            .find(|incident| {
+                incident.is_widespread() || keywords.iter().any(|k| incident.mentions(k))
+            })
+    }
+
+    /// Annotate degraded benchmark results with matching incidents
+    ///
+    /// `regions` pairs each tested region with its provider name.
+    #[must_use]
+    pub fn annotate_results(
+        &self,
+        results: &[(String, PingStats)],
+        regions: &[(String, Region)],
+    ) -> Vec<IncidentAnnotation> {
+        results
+            .iter()
+            .filter(|(_, stats)| Self::is_degraded(stats))
+            .filter_map(|(name, stats)| {
+                let (provider, region) = regions
+                    .iter()
+                    .find(|(_, region)| stats.region_id.as_deref() == Some(region.id.as_str()))?;
+                let incident = self.find_incident(provider, region)?;
+
+                Some(IncidentAnnotation {
+                    region: name.clone(),
+                    provider: provider.clone(),
+                    incident: incident.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Attach a matching incident to an alert raised for `region`
+    ///
+    /// Returns whether the alert was annotated.
+    pub fn annotate_alert(&self, alert: &mut Alert, provider: &str, region: &Region) -> bool {
+        match self.find_incident(provider, region) {
+            Some(incident) => {
+                alert.provider_incident = Some(incident.headline());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Case-insensitive provider name match where one name contains the other
+fn same_provider(a: &str, b: &str) -> bool {
+    let (shorter, longer) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    !shorter.is_empty() && longer.to_lowercase().contains(&shorter.to_lowercase())
+}
+
+/// Words that identify a region in incident text: its code, city and host labels
+fn region_keywords(region: &Region) -> Vec<String> {
+    let mut keywords: Vec<String> = region
+        .name
+        .split(['(', ')', ','])
+        .map(str::trim)
+        .filter(|part| part.len() >= 4)
+        .map(str::to_lowercase)
+        .collect();
+
+    if let Ok(url) = url::Url::parse(&region.url) {
+        if let Some(host) = url.host_str() {
+            keywords.extend(
+                host.split('.')
+                    .filter(|label| {
+                        label.contains('-') && label.chars().any(|c| c.is_ascii_digit())
+                    })
+                    .map(str::to_lowercase),
+            );
+        }
+    }
+
+    keywords
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(name: &str, url: &str) -> Region {
+        Region::new(name.to_string(), url.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_rss_items() {
+        let body = r#"<rss><channel><title>Feed</title>
+            <item><title><![CDATA[Increased API error rates]]></title>
+            <description>We are investigating errors in the eu-central-1 Region &amp; others.</description>
+            <pubDate>Mon, 12 Oct 2026 10:00:00 GMT</pubDate></item>
+            <item><title>Old issue</title><pubDate>Mon, 01 Jan 2024 10:00:00 GMT</pubDate></item>
+            </channel></rss>"#;
+        let since = DateTime::parse_from_rfc3339("2026-10-11T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let incidents = parse_rss("Amazon Web Services", body, since);
+        assert_eq!(incidents.len(), 1);
+        assert_eq!(incidents[0].title, "Increased API error rates");
+        assert!(incidents[0]
+            .summary
+            .contains("eu-central-1 Region & others"));
+    }
+
+    #[test]
+    fn test_parse_statuspage() {
+        let body = r#"{"incidents":[{"name":"Network degradation","created_at":"2026-10-12T10:00:00Z",
+            "incident_updates":[{"body":"Investigating packet loss"}],
+            "components":[{"name":"FRA1"}]}]}"#;
+
+        let incidents = parse_statuspage("DigitalOcean", body).unwrap();
+        assert_eq!(incidents.len(), 1);
+        assert!(incidents[0].mentions("fra1"));
+        assert!(parse_statuspage("DigitalOcean", "not json").is_err());
+    }
+
+    #[test]
+    fn test_correlate_degraded_results() {
+        let frankfurt = region(
+            "eu-central-1 (Frankfurt)",
+            "https://dynamodb.eu-central-1.amazonaws.com/ping",
+        );
+        let tokyo = region(
+            "ap-northeast-1 (Tokyo)",
+            "https://dynamodb.ap-northeast-1.amazonaws.com/ping",
+        );
+        let correlator = OutageCorrelator::new(vec![ProviderIncident {
+            provider: "Amazon Web Services".to_string(),
+            title: "Increased latency in Frankfurt".to_string(),
+            summary: String::new(),
+            published: None,
+        }]);
+
+        let mut degraded = PingStats::new_with_region(10, frankfurt.id.clone());
+        degraded.successful_pings = 5;
+        let mut healthy = PingStats::new_with_region(10, tokyo.id.clone());
+        healthy.successful_pings = 10;
+        let mut degraded_tokyo = PingStats::new_with_region(10, tokyo.id.clone());
+        degraded_tokyo.successful_pings = 0;
+
+        let provider = "Amazon Web Services".to_string();
+        let regions = vec![(provider.clone(), frankfurt.clone()), (provider, tokyo)];
+        let results = vec![
+            (frankfurt.name.clone(), degraded),
+            ("Tokyo".to_string(), healthy),
+            ("Tokyo again".to_string(), degraded_tokyo),
+        ];
+
+        let annotations = correlator.annotate_results(&results, &regions);
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].region, frankfurt.name);
+
+        let mut alert = Alert::new(
+            frankfurt.id.clone(),
+            crate::models::AlertType::HighLatency { latency_ms: 500.0 },
+        );
+        assert!(correlator.annotate_alert(&mut alert, "AWS Amazon Web Services", &frankfurt));
+        assert!(alert.provider_incident.is_some());
+    }
+}
@@ -0,0 +1,189 @@
+//! Happy Eyeballs connection racing
+//!
+//! Races TCP connects to every address an endpoint's host resolves to the
+//! way RFC 8305 clients do: addresses are ordered IPv6 first, alternating
+//! families, and each attempt starts once the previous one has had
+//! `attempt_delay` to connect or has failed. Unlike a client, the racer lets
+//! every attempt run to completion after a winner connects, so each address
+//! gets a timing of its own. Only the TCP handshake is timed; connections
+//! are closed as soon as they open.
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::net::TcpStream;
+use tracing::debug;
+
+use crate::error::{CloudPingError, Result};
+use crate::models::{IpFamily, RaceAttempt, RaceResult, RaceRound, Region};
+use crate::network::NetworkTester;
+
+/// Head start of each attempt before the next begins, as recommended by RFC 8305
+pub const DEFAULT_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Races connections to the addresses of endpoints
+#[derive(Debug, Clone)]
+pub struct ConnectionRacer {
+    attempt_delay: Duration,
+    timeout: Duration,
+}
+
+impl ConnectionRacer {
+    /// Race with `attempt_delay` between attempts, giving up on an attempt after `timeout`
+    #[must_use]
+    pub const fn new(attempt_delay: Duration, timeout: Duration) -> Self {
+        Self {
+            attempt_delay,
+            timeout,
+        }
+    }
+
+    /// Order `addresses` for a race: IPv6 first, then alternating families,
+    /// keeping the resolver's order within each family
+    #[must_use]
+    pub fn interleave(addresses: &[SocketAddr]) -> Vec<SocketAddr> {
+        let (ipv6, ipv4): (Vec<SocketAddr>, Vec<SocketAddr>) = addresses
+            .iter()
+            .partition(|address| IpFamily::of(&address.ip()) == IpFamily::Ipv6);
+        let mut ordered = Vec::with_capacity(addresses.len());
+        for index in 0..ipv6.len().max(ipv4.len()) {
+            ordered.extend(ipv6.get(index));
+            ordered.extend(ipv4.get(index));
+        }
+        ordered
+    }
+
+    /// Run `rounds` races against the endpoint of `region`
+    ///
+    /// # Errors
+    /// Returns an error when the endpoint's host does not resolve
+    pub async fn race_region(&self, region: &Region, rounds: usize) -> Result<RaceResult> {
+        let (host, addresses) = NetworkTester::resolve_addresses(&region.url).await?;
+        if addresses.is_empty() {
+            return Err(CloudPingError::network(format!("{host} has no address")));
+        }
+        let addresses = Self::interleave(&addresses);
+        debug!("Racing {} addresses of {} for {}", addresses.len(), host, region.name);
+
+        let mut result = RaceResult {
+            region: region.name.clone(),
+            host,
+            rounds: Vec::with_capacity(rounds),
+        };
+        for _ in 0..rounds {
+            result.rounds.push(self.race(&addresses).await);
+        }
+        Ok(result)
+    }
+
+    /// Race connections to `addresses`, attempted in the given order
+    pub async fn race(&self, addresses: &[SocketAddr]) -> RaceRound {
+        let start = Instant::now();
+        let mut attempts: Vec<RaceAttempt> = addresses
+            .iter()
+            .map(|&address| RaceAttempt {
+                address,
+                started_ms: 0.0,
+                connect_ms: None,
+                error: None,
+            })
+            .collect();
+        let mut pending = FuturesUnordered::new();
+        let mut next = 0;
+        let mut next_at = start;
+
+        while next < addresses.len() || !pending.is_empty() {
+            if next < addresses.len() && Instant::now() >= next_at {
+                attempts[next].started_ms = elapsed_ms(start);
+                pending.push(self.connect(next, addresses[next]));
+                next += 1;
+                next_at = Instant::now() + self.attempt_delay;
+                continue;
+            }
+            tokio::select! {
+                Some((index, outcome)) = pending.next() => match outcome {
+                    Ok(connect_ms) => attempts[index].connect_ms = Some(connect_ms),
+                    Err(error) => {
+                        attempts[index].error = Some(error);
+                        // A failed attempt hands over to the next one at once
+                        if index + 1 == next {
+                            next_at = Instant::now();
+                        }
+                    }
+                },
+                () = tokio::time::sleep_until(next_at.into()), if next < addresses.len() => {}
+            }
+        }
+        RaceRound { attempts }
+    }
+
+    async fn connect(&self, index: usize, address: SocketAddr) -> (usize, std::result::Result<f64, String>) {
+        let start = Instant::now();
+        let outcome = match tokio::time::timeout(self.timeout, TcpStream::connect(address)).await {
+            Ok(Ok(stream)) => {
+                drop(stream);
+                Ok(elapsed_ms(start))
+            }
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(format!("timed out after {}ms", self.timeout.as_millis())),
+        };
+        debug!("Connect to {} finished: {:?}", address, outcome);
+        (index, outcome)
+    }
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interleave() {
+        let addresses: Vec<SocketAddr> = [
+            "192.0.2.1:443",
+            "192.0.2.2:443",
+            "192.0.2.3:443",
+            "[2001:db8::1]:443",
+            "[2001:db8::2]:443",
+        ]
+        .iter()
+        .map(|address| address.parse().unwrap())
+        .collect();
+        let ordered: Vec<String> = ConnectionRacer::interleave(&addresses)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            ordered,
+            [
+                "[2001:db8::1]:443",
+                "192.0.2.1:443",
+                "[2001:db8::2]:443",
+                "192.0.2.2:443",
+                "192.0.2.3:443"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_race_hands_over_after_failure() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap();
+        let closed = {
+            let unused = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            unused.local_addr().unwrap()
+        };
+
+        // The refused first attempt must not hold the second back for the delay
+        let racer = ConnectionRacer::new(Duration::from_secs(30), Duration::from_secs(5));
+        let round = racer.race(&[closed, open]).await;
+        assert!(round.attempts[0].error.is_some());
+        assert!(round.attempts[1].started_ms < 5_000.0);
+        assert_eq!(round.winner().map(|attempt| attempt.address), Some(open));
+        assert_eq!(round.margin_ms(), None);
+    }
+}
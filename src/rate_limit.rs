@@ -0,0 +1,149 @@
+//! Per-host rate limiting of outbound requests
+//!
+//! Several regions of one provider often share a domain, so a concurrent
+//! benchmark can send far more requests to a single host than the per-region
+//! settings suggest. A token bucket per host caps the request rate across all
+//! tasks sharing the limiter.
+
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use tracing::{debug, info};
+
+/// Token bucket state for one host
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+    throttled: u64,
+}
+
+/// Token-bucket rate limiter keyed by host name
+#[derive(Debug)]
+pub struct HostRateLimiter {
+    requests_per_second: f64,
+    burst: f64,
+    buckets: DashMap<String, TokenBucket>,
+}
+
+impl HostRateLimiter {
+    /// Limit each host to `requests_per_second`, allowing bursts of up to one second's worth
+    ///
+    /// Returns `None` when the rate is zero or negative, which disables limiting.
+    #[must_use]
+    pub fn new(requests_per_second: f64) -> Option<Self> {
+        if !requests_per_second.is_finite() || requests_per_second <= 0.0 {
+            return None;
+        }
+
+        Some(Self {
+            requests_per_second,
+            burst: requests_per_second.max(1.0),
+            buckets: DashMap::new(),
+        })
+    }
+
+    /// Configured request rate per host
+    #[must_use]
+    pub const fn requests_per_second(&self) -> f64 {
+        self.requests_per_second
+    }
+
+    /// Wait until a request to `host` is allowed
+    pub async fn acquire(&self, host: &str) {
+        if let Some(wait) = self.reserve(host, Instant::now()) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Number of requests to `host` that had to wait for a token
+    #[must_use]
+    pub fn throttled_count(&self, host: &str) -> u64 {
+        self.buckets.get(host).map_or(0, |bucket| bucket.throttled)
+    }
+
+    /// Take a token for `host` at `now`, returning how long the caller must wait for it
+    ///
+    /// Tokens are reserved even when not yet available, so concurrent callers
+    /// queue up one interval apart instead of all waking at once.
+    fn reserve(&self, host: &str, now: Instant) -> Option<Duration> {
+        let mut bucket = self
+            .buckets
+            .entry(host.to_string())
+            .or_insert_with(|| TokenBucket {
+                tokens: self.burst,
+                last_refill: now,
+                throttled: 0,
+            });
+
+        let elapsed = now
+            .saturating_duration_since(bucket.last_refill)
+            .as_secs_f64();
+        bucket.tokens = elapsed
+            .mul_add(self.requests_per_second, bucket.tokens)
+            .min(self.burst);
+        bucket.last_refill = now;
+        bucket.tokens -= 1.0;
+
+        if bucket.tokens >= 0.0 {
+            return None;
+        }
+
+        bucket.throttled += 1;
+        let first_throttle = bucket.throttled == 1;
+        let wait = Duration::from_secs_f64(-bucket.tokens / self.requests_per_second);
+        drop(bucket);
+
+        if first_throttle {
+            info!(
+                "Throttling requests to {} to {} per second",
+                host, self.requests_per_second
+            );
+        } else {
+            debug!("Delaying request to {} by {:?}", host, wait);
+        }
+        Some(wait)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_then_throttle() {
+        let limiter = HostRateLimiter::new(2.0).unwrap();
+        let now = Instant::now();
+
+        // Two requests fit the burst, the next two queue half a second apart
+        assert!(limiter.reserve("example.com", now).is_none());
+        assert!(limiter.reserve("example.com", now).is_none());
+        assert_eq!(
+            limiter.reserve("example.com", now),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            limiter.reserve("example.com", now),
+            Some(Duration::from_millis(1000))
+        );
+        assert_eq!(limiter.throttled_count("example.com"), 2);
+
+        // Other hosts have their own bucket
+        assert!(limiter.reserve("other.example.com", now).is_none());
+    }
+
+    #[test]
+    fn test_refill_and_disabled() {
+        let limiter = HostRateLimiter::new(1.0).unwrap();
+        let now = Instant::now();
+
+        assert!(limiter.reserve("example.com", now).is_none());
+        assert!(limiter.reserve("example.com", now).is_some());
+        // After the queued request, two seconds refill one token
+        assert!(limiter
+            .reserve("example.com", now + Duration::from_secs(2))
+            .is_none());
+
+        assert!(HostRateLimiter::new(0.0).is_none());
+    }
+}
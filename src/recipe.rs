@@ -0,0 +1,183 @@
+//! Checking regions against application recipes
+//!
+//! Each region is measured once for all recipes being checked: pings for
+//! latency, jitter and loss, and only when some recipe needs them the UDP
+//! ports, a TLS handshake and a download. The handshake is timed on a fresh
+//! TCP connection with the client's TLS settings, from the `ClientHello` to
+//! the end of the handshake. Throughput is the response body of the region
+//! URL, or of the recipe's `download_url`, divided by the time from its first
+//! to its last byte, so the round trip to the first byte does not count
+//! against it; bodies too small for that to mean anything leave throughput
+//! unmeasured. A recipe's `stun_server` and `download_url` do not depend on
+//! the region, so they are measured once per run.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::stream::{self, StreamExt};
+use rustls::pki_types::ServerName;
+use rustls::ClientConfig;
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tracing::debug;
+
+use crate::config::AppConfig;
+use crate::error::{CloudPingError, Result};
+use crate::models::{Recipe, RecipeMeasurements, RecipeResult, Region, UdpCheck, UdpPort, UdpProtocol};
+use crate::network::{throughput_mbps, NetworkTester};
+use crate::tls::{timed_client_config, CertValidations};
+use crate::udp::UdpReachabilityTest;
+
+/// Bytes a download reads at most before its throughput is taken
+const MAX_THROUGHPUT_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Checks regions against recipes
+#[derive(Debug, Clone)]
+pub struct RecipeRunner {
+    tester: NetworkTester,
+    tls: Arc<ClientConfig>,
+    pings: usize,
+    udp_timeout: Duration,
+}
+
+impl RecipeRunner {
+    /// Runner sending `pings` pings per region and waiting up to
+    /// `udp_timeout` for each UDP port to answer
+    ///
+    /// # Errors
+    /// Returns a validation error when `pings` or the timeout is zero, or an
+    /// error when the HTTP or TLS client cannot be built
+    pub fn new(config: &AppConfig, pings: usize, udp_timeout: Duration) -> Result<Self> {
+        if pings == 0 {
+            return Err(CloudPingError::validation("count", "must be greater than 0"));
+        }
+        if udp_timeout.is_zero() {
+            return Err(CloudPingError::validation("udp_timeout", "must be greater than 0"));
+        }
+        let tls = timed_client_config(&config.tls, &CertValidations::default())?;
+        Ok(Self {
+            tester: NetworkTester::new(config.clone())?,
+            tls: Arc::new(tls),
+            pings,
+            udp_timeout,
+        })
+    }
+
+    /// Check every region against every recipe, one result per region and
+    /// recipe, ordered by region
+    ///
+    /// # Errors
+    /// Returns a validation error when a recipe lists an invalid UDP port
+    pub async fn run(&self, recipes: &[(String, Recipe)], regions: &[Region]) -> Result<Vec<RecipeResult>> {
+        let mut ports: Vec<UdpPort> = Vec::new();
+        let mut servers: Vec<(String, Vec<UdpPort>)> = Vec::new();
+        for (_, recipe) in recipes {
+            let server = recipe.stun_server.as_ref().map(|server| {
+                servers.iter().position(|(known, _)| known == server).unwrap_or_else(|| {
+                    servers.push((server.clone(), Vec::new()));
+                    servers.len() - 1
+                })
+            });
+            let wanted = server.map_or(&mut ports, |index| &mut servers[index].1);
+            for port in recipe.udp_ports()? {
+                // Region hosts rarely run STUN, so without a server it stays unmeasured
+                if !wanted.contains(&port) && (server.is_some() || port.protocol != UdpProtocol::Stun) {
+                    wanted.push(port);
+                }
+            }
+        }
+        let udp = if ports.is_empty() {
+            vec![Vec::new(); regions.len()]
+        } else {
+            let rows = UdpReachabilityTest::new(ports, self.udp_timeout)?.run(regions).await;
+            rows.into_iter().map(|row| row.checks).collect()
+        };
+        let mut server_udp = BTreeMap::new();
+        for (server, ports) in servers {
+            let row = UdpReachabilityTest::new(ports, self.udp_timeout)?.check_host(&server).await;
+            server_udp.insert(server, row.checks);
+        }
+        let mut downloads = BTreeMap::new();
+        for url in recipes.iter().filter_map(|(_, recipe)| recipe.download_url.as_ref()) {
+            if !downloads.contains_key(url) {
+                let mbps = self.throughput_mbps(url).await;
+                downloads.insert(url.clone(), mbps);
+            }
+        }
+        let tls = recipes.iter().any(|(_, recipe)| recipe.needs_tls_handshake());
+        let throughput = recipes
+            .iter()
+            .any(|(_, recipe)| recipe.needs_throughput() && recipe.download_url.is_none());
+
+        let measured: Vec<RecipeMeasurements> = stream::iter(regions.iter().zip(udp))
+            .map(|(region, udp)| self.measure(region, udp, tls, throughput))
+            .buffered(self.tester.config().max_threads.max(1))
+            .collect()
+            .await;
+        Ok(regions
+            .iter()
+            .zip(measured)
+            .flat_map(|(region, mut measurements)| {
+                measurements.server_udp.clone_from(&server_udp);
+                measurements.downloads.clone_from(&downloads);
+                recipes
+                    .iter()
+                    .map(move |(name, recipe)| recipe.evaluate(name, &region.name, &measurements))
+                    .collect::<Vec<_>>()
+            })
+            .collect())
+    }
+
+    async fn measure(&self, region: &Region, udp: Vec<UdpCheck>, tls: bool, throughput: bool) -> RecipeMeasurements {
+        let stats = self.tester.perform_ping_test(&region.url, self.pings).await;
+        let tls_handshake_ms = if tls { self.tls_handshake_ms(&region.url).await } else { None };
+        let throughput_mbps = if throughput { self.throughput_mbps(&region.url).await } else { None };
+        RecipeMeasurements {
+            stats,
+            udp,
+            server_udp: BTreeMap::new(),
+            tls_handshake_ms,
+            throughput_mbps,
+            downloads: BTreeMap::new(),
+        }
+    }
+
+    /// Download throughput of `url` in megabits per second, when measurable
+    async fn throughput_mbps(&self, url: &str) -> Option<f64> {
+        let timeout = self.tester.config().get_timeout();
+        throughput_mbps(self.tester.client(), url, MAX_THROUGHPUT_BYTES, timeout).await
+    }
+
+    /// Time of a TLS handshake with the host of `url` in milliseconds;
+    /// `None` for plain HTTP URLs and failed handshakes
+    async fn tls_handshake_ms(&self, url: &str) -> Option<f64> {
+        if !url.starts_with("https://") {
+            return None;
+        }
+        let timeout = self.tester.config().attempt_timeout();
+        let handshake = async {
+            let (host, addresses) = NetworkTester::resolve_addresses(url).await?;
+            let address = addresses
+                .first()
+                .ok_or_else(|| CloudPingError::network(format!("{host} has no address")))?;
+            let server_name = ServerName::try_from(host.clone())
+                .map_err(|e| CloudPingError::network(format!("{host} is not a TLS server name: {e}")))?;
+            let tcp = TcpStream::connect(address).await?;
+            let start = Instant::now();
+            TlsConnector::from(Arc::clone(&self.tls)).connect(server_name, tcp).await?;
+            Ok::<_, CloudPingError>(start.elapsed().as_secs_f64() * 1000.0)
+        };
+        match tokio::time::timeout(timeout, handshake).await {
+            Ok(Ok(handshake_ms)) => Some(handshake_ms),
+            Ok(Err(e)) => {
+                debug!("TLS handshake with {} failed: {}", url, e);
+                None
+            }
+            Err(_) => {
+                debug!("TLS handshake with {} timed out", url);
+                None
+            }
+        }
+    }
+}
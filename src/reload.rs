@@ -0,0 +1,137 @@
+//! Configuration reload triggers for long-running modes
+//!
+//! A [`ConfigWatcher`] calls back whenever the configuration should be read
+//! again: when the process receives SIGHUP (Unix only) or when the config
+//! file's modification time changes. The callback decides what to reload.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use tokio::task::JoinHandle;
+use tracing::info;
+
+/// Watches for configuration reload requests
+#[derive(Debug, Clone)]
+pub struct ConfigWatcher {
+    path: Option<PathBuf>,
+    poll_interval: Duration,
+}
+
+impl ConfigWatcher {
+    /// Watch `path` for changes, if given, and listen for SIGHUP
+    #[must_use]
+    pub const fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            poll_interval: Duration::from_secs(2),
+        }
+    }
+
+    /// How often the config file is checked for changes
+    #[must_use]
+    pub const fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Call `on_reload` on every reload request until the returned task is aborted
+    pub fn spawn<F>(self, mut on_reload: F) -> JoinHandle<()>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        tokio::spawn(async move {
+            let mut hangup = listen_for_hangup();
+            let mut modified = self.path.as_deref().and_then(modified_at);
+            let mut ticker = tokio::time::interval(self.poll_interval);
+
+            loop {
+                let reason = tokio::select! {
+                    () = next_hangup(&mut hangup) => "SIGHUP",
+                    _ = ticker.tick() => {
+                        let now = self.path.as_deref().and_then(modified_at);
+                        if now == modified {
+                            continue;
+                        }
+                        modified = now;
+                        "config file changed"
+                    }
+                };
+                info!("Reloading configuration ({})", reason);
+                on_reload();
+            }
+        })
+    }
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(unix)]
+type Hangup = Option<tokio::signal::unix::Signal>;
+
+#[cfg(not(unix))]
+type Hangup = ();
+
+#[cfg(unix)]
+fn listen_for_hangup() -> Hangup {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    signal(SignalKind::hangup())
+        .map_err(|e| tracing::warn!("Cannot listen for SIGHUP: {}", e))
+        .ok()
+}
+
+#[cfg(not(unix))]
+const fn listen_for_hangup() -> Hangup {}
+
+/// Resolve on the next SIGHUP; never resolves where SIGHUP is unavailable
+#[cfg(unix)]
+async fn next_hangup(hangup: &mut Hangup) {
+    if let Some(signal) = hangup {
+        if signal.recv().await.is_some() {
+            return;
+        }
+    }
+    *hangup = None;
+    std::future::pending::<()>().await;
+}
+
+#[cfg(not(unix))]
+async fn next_hangup(_hangup: &mut Hangup) {
+    std::future::pending::<()>().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_file_change_triggers_reload() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let reloads = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&reloads);
+        let watcher = ConfigWatcher::new(Some(file.path().to_path_buf()))
+            .poll_interval(Duration::from_millis(10))
+            .spawn(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(reloads.load(Ordering::SeqCst), 0);
+
+        // Push the modification time forward so coarse timestamps still differ
+        writeln!(file, "max_threads = 4").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(5);
+        file.as_file().set_modified(later).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(reloads.load(Ordering::SeqCst), 1);
+        watcher.abort();
+    }
+}
@@ -0,0 +1,1041 @@
+//! Self-contained HTML report generation
+//!
+//! Renders benchmark rankings, the test environment and availability figures
+//! into a single HTML file with inline styles, suitable for sharing or
+//! archiving without any external assets. The same styles back the live
+//! status page served by the monitoring API.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use chrono::FixedOffset;
+
+use crate::error::Result;
+use crate::i18n::{Locale, Message};
+use crate::models::{
+    Alert, AlertSeverity, AlgorithmWeights, AsnBreakdown, AvailabilityReport, AvailabilityState,
+    labels_summary, ComprehensiveScoreResult, ConcurrencyAdjustment, ControlSeries, Endpoint,
+    EstimateSource, GreenThreshold, LatencyMatrix, PingStats, RegionFailure, ReportFilter, RunLabels, ScoringAdapter, SortKey, TestEnvironment, TestHistory,
+};
+use crate::time_utils::TimeUtils;
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2rem;color:#222}\
+table{border-collapse:collapse;margin:1rem 0}\
+th,td{border:1px solid #ddd;padding:.4rem .8rem;text-align:left}\
+th{background:#f4f4f4}td.num{text-align:right}\
+.up{color:#1a7f37;font-weight:600}.down{color:#cf222e;font-weight:600}\
+.muted{color:#666}\
+.bar{width:12rem;height:.8rem;background:#cf222e;border-radius:2px;overflow:hidden}\
+.bar span{display:block;height:100%;background:#1a7f37}\
+.critical{color:#cf222e}.warning{color:#9a6700}.info{color:#0969da}\
+table.heatmap td{padding:.3rem;min-width:2rem;font-size:.8rem;text-align:center}\
+svg.spark{width:6rem;height:1.2rem;vertical-align:middle}\
+svg.spark polyline{fill:none;stroke:#0969da;stroke-width:1.5}";
+
+/// Size of the sparkline drawing area, in SVG user units
+const SPARKLINE_SIZE: (f64, f64) = (100.0, 20.0);
+
+/// Seconds between automatic refreshes of the status page
+const STATUS_REFRESH_SECS: u32 = 30;
+
+/// Builder for a standalone HTML report
+pub struct HtmlReport {
+    title: String,
+    results: Vec<(String, PingStats)>,
+    weights: AlgorithmWeights,
+    environment: Option<TestEnvironment>,
+    labels: RunLabels,
+    failures: Vec<RegionFailure>,
+    concurrency_adjustments: Vec<ConcurrencyAdjustment>,
+    control: Option<ControlSeries>,
+    availability: Option<AvailabilityReport>,
+    history: Vec<TestHistory>,
+    history_offset: FixedOffset,
+    locale: Locale,
+    sort_by: SortKey,
+    filter: ReportFilter,
+    green: Option<GreenThreshold>,
+    latency_matrix: Option<LatencyMatrix>,
+}
+
+impl HtmlReport {
+    /// Start a report with the given title
+    #[must_use]
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            results: Vec::new(),
+            weights: AlgorithmWeights::default(),
+            environment: None,
+            labels: RunLabels::new(),
+            failures: Vec::new(),
+            concurrency_adjustments: Vec::new(),
+            control: None,
+            availability: None,
+            history: Vec::new(),
+            history_offset: FixedOffset::east_opt(0).expect("zero offset is valid"),
+            locale: Locale::default(),
+            sort_by: SortKey::default(),
+            filter: ReportFilter::default(),
+            green: None,
+            latency_matrix: None,
+        }
+    }
+
+    /// Include a heatmap of estimated region-to-region latency
+    #[must_use]
+    pub fn latency_matrix(mut self, matrix: LatencyMatrix) -> Self {
+        self.latency_matrix = Some(matrix);
+        self
+    }
+
+    /// Recommend the lowest-carbon ranked region that meets `threshold`
+    #[must_use]
+    pub const fn green(mut self, threshold: GreenThreshold) -> Self {
+        self.green = Some(threshold);
+        self
+    }
+
+    /// Show only the ranked regions `filter` keeps
+    #[must_use]
+    pub fn filter(mut self, filter: ReportFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Order of the ranking
+    #[must_use]
+    pub const fn sort_by(mut self, sort_by: SortKey) -> Self {
+        self.sort_by = sort_by;
+        self
+    }
+
+    /// Language and number format of the report
+    #[must_use]
+    pub const fn locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Include a ranking of benchmark results scored with `weights`
+    #[must_use]
+    pub fn results(mut self, results: &[(String, PingStats)], weights: &AlgorithmWeights) -> Self {
+        self.results = results.to_vec();
+        self.weights = weights.clone();
+        self
+    }
+
+    /// Include the environment the run was captured in
+    #[must_use]
+    pub fn environment(mut self, environment: TestEnvironment) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    /// Show the labels the run was taken with
+    #[must_use]
+    pub fn labels(mut self, labels: RunLabels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Include the regions that failed during the run
+    #[must_use]
+    pub fn failures(mut self, failures: Vec<RegionFailure>) -> Self {
+        self.failures = failures;
+        self
+    }
+
+    /// Note concurrency reductions made during the run
+    #[must_use]
+    pub fn concurrency_adjustments(mut self, adjustments: Vec<ConcurrencyAdjustment>) -> Self {
+        self.concurrency_adjustments = adjustments;
+        self
+    }
+
+    /// Include raw and control-normalized latency per region
+    #[must_use]
+    pub fn control(mut self, control: ControlSeries) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    /// Include uptime figures from the availability ledger
+    #[must_use]
+    pub fn availability(mut self, availability: AvailabilityReport) -> Self {
+        self.availability = Some(availability);
+        self
+    }
+
+    /// Include a time-of-day latency heatmap, with hours in `offset`
+    #[must_use]
+    pub fn history(mut self, history: Vec<TestHistory>, offset: FixedOffset) -> Self {
+        self.history = history;
+        self.history_offset = offset;
+        self
+    }
+
+    /// Render the report as an HTML document
+    #[must_use]
+    pub fn render(&self) -> String {
+        let locale = self.locale;
+        let mut html = String::new();
+        let title = escape_html(&self.title);
+
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"{locale}\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n\
+             <p class=\"muted\">{}</p>\n",
+            locale.format(
+                Message::Generated,
+                &[&locale.format_datetime(&TimeUtils::now())]
+            )
+        );
+
+        if let Some(environment) = &self.environment {
+            let _ = writeln!(
+                html,
+                "<p>{}: {}</p>",
+                locale.text(Message::Environment),
+                escape_html(&environment.summary())
+            );
+        }
+
+        if !self.labels.is_empty() {
+            let _ = writeln!(
+                html,
+                "<p>{}: {}</p>",
+                locale.text(Message::Labels),
+                escape_html(&labels_summary(&self.labels))
+            );
+        }
+
+        if !self.results.is_empty() {
+            self.render_ranking(&mut html);
+        }
+
+        if let Some(matrix) = &self.latency_matrix {
+            self.render_latency_matrix(&mut html, matrix);
+        }
+
+        if let Some(control) = &self.control {
+            self.render_normalized(&mut html, control);
+        }
+
+        if !self.failures.is_empty() {
+            self.render_failures(&mut html);
+        }
+
+        if !self.concurrency_adjustments.is_empty() {
+            let _ = write!(
+                html,
+                "<h2>{}</h2>\n<p class=\"muted\">{}</p>\n<ul>\n",
+                locale.text(Message::ConcurrencyAdjustments),
+                locale.text(Message::ConcurrencyAdjustmentsNote)
+            );
+            for adjustment in &self.concurrency_adjustments {
+                let _ = writeln!(
+                    html,
+                    "<li>{}: {}</li>",
+                    locale.format_datetime(&adjustment.at),
+                    escape_html(&adjustment.summary())
+                );
+            }
+            html.push_str("</ul>\n");
+        }
+
+        if let Some(availability) = &self.availability {
+            self.render_availability(&mut html, availability);
+        }
+
+        if !self.history.is_empty() {
+            self.render_heatmap(&mut html);
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    /// Regions by hour of day, each cell shaded from the region's best (green) to worst (red) hour
+    fn render_heatmap(&self, html: &mut String) {
+        let locale = self.locale;
+        let _ = write!(
+            html,
+            "<h2>{}</h2>\n<p class=\"muted\">{}</p>\n<table class=\"heatmap\">\n<tr><th>{}</th>",
+            locale.text(Message::LatencyByTimeOfDay),
+            locale.format(
+                Message::AverageLatencyPerHour,
+                &[&self.history_offset.to_string()]
+            ),
+            locale.text(Message::Region)
+        );
+        for hour in 0..24 {
+            let _ = write!(html, "<th>{hour:02}</th>");
+        }
+        let _ = writeln!(html, "<th>{}</th></tr>", locale.text(Message::WorstHour));
+
+        let mut histories: Vec<&TestHistory> = self.history.iter().collect();
+        histories.sort_by(|a, b| a.region_name.cmp(&b.region_name));
+
+        for history in histories {
+            let profile = history.hourly_profile(self.history_offset);
+            if profile.is_empty() {
+                continue;
+            }
+            let best = profile.iter().map(|h| h.avg_latency_ms).fold(f64::INFINITY, f64::min);
+            let worst = profile.iter().map(|h| h.avg_latency_ms).fold(0.0, f64::max);
+
+            let _ = write!(html, "<tr><td>{}</td>", escape_html(&history.region_name));
+            for hour in 0..24 {
+                match profile.iter().find(|h| h.hour == hour) {
+                    Some(bucket) => {
+                        // Hue 120 (green) for the best hour down to 0 (red) for the worst
+                        let spread = worst - best;
+                        let hue = if spread > 0.0 {
+                            120.0 * (worst - bucket.avg_latency_ms) / spread
+                        } else {
+                            120.0
+                        };
+                        let _ = write!(
+                            html,
+                            "<td style=\"background:hsl({hue:.0},70%,80%)\" title=\"{}\">{}</td>",
+                            locale.format(Message::Runs, &[&locale.format_count(bucket.runs)]),
+                            locale.format_number(bucket.avg_latency_ms, 0)
+                        );
+                    }
+                    None => html.push_str("<td></td>"),
+                }
+            }
+            let worst_hour = history
+                .worst_hour(self.history_offset)
+                .map_or_else(|| "-".to_string(), |h| format!("{:02}:00", h.hour));
+            let _ = writeln!(html, "<td>{worst_hour}</td></tr>");
+        }
+
+        html.push_str("</table>\n");
+
+        let mut changes: Vec<String> = self
+            .history
+            .iter()
+            .flat_map(|history| {
+                history
+                    .detect_change_points()
+                    .into_iter()
+                    .map(|change| change.describe(&history.region_name))
+            })
+            .collect();
+        if !changes.is_empty() {
+            changes.sort();
+            let _ = writeln!(html, "<h2>{}</h2>\n<ul>", locale.text(Message::LatencyChanges));
+            for change in changes {
+                let _ = writeln!(html, "<li>{}</li>", escape_html(&change));
+            }
+            html.push_str("</ul>\n");
+        }
+    }
+
+    fn render_ranking(&self, html: &mut String) {
+        let locale = self.locale;
+        let mut columns = vec![
+            Message::Rank,
+            Message::Region,
+            Message::Score,
+            Message::Grade,
+            Message::Health,
+            Message::Latency,
+            Message::Loss,
+        ];
+        let has_trend = !self.history.is_empty();
+        if has_trend {
+            columns.push(Message::Trend);
+        }
+        let ranked = self.filter.apply(ScoringAdapter::get_sorted_results_by(
+            &self.results,
+            &self.weights,
+            self.sort_by,
+        ));
+        let has_value = ranked.iter().any(|(_, _, stats, _)| !stats.cost.is_empty());
+        if has_value {
+            columns.push(Message::Value);
+        }
+        let _ = writeln!(
+            html,
+            "<h2>{}</h2>\n<table>\n{}",
+            locale.text(Message::Ranking),
+            header_row(locale, &columns)
+        );
+
+        for (i, (score, name, stats, result)) in ranked.iter().enumerate() {
+            let health = stats.health_status();
+            let notes = if stats.notes.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    "<br><small class=\"muted\">{}</small>",
+                    escape_html(&stats.notes.join("; "))
+                )
+            };
+            let _ = write!(
+                html,
+                "<tr><td class=\"num\">{}</td><td>{}{notes}</td><td class=\"num\">{}</td><td>{}</td>\
+                 <td>{} {}</td><td class=\"num\">{}</td><td class=\"num\">{}</td>",
+                i + 1,
+                escape_html(name),
+                locale.format_score(*score),
+                result.grade,
+                health.emoji(),
+                locale.health(health),
+                locale.format_latency_ms(stats.avg),
+                locale.format_percentage(stats.packet_loss)
+            );
+            if has_trend {
+                let latencies = TestHistory::find(&self.history, name, stats)
+                    .map(|history| history.recent_latencies(TestHistory::SPARKLINE_RUNS))
+                    .unwrap_or_default();
+                let _ = write!(html, "<td>{}</td>", sparkline_svg(locale, &latencies));
+            }
+            if has_value {
+                let value = stats
+                    .cost
+                    .price_performance(*score)
+                    .map_or_else(|| "-".to_string(), |value| locale.format_score(value));
+                let _ = write!(html, "<td class=\"num\">{value}</td>");
+            }
+            html.push_str("</tr>\n");
+        }
+
+        html.push_str("</table>\n");
+
+        if let Some(green) = self.green.as_ref().and_then(|threshold| threshold.recommend(&ranked)) {
+            let _ = writeln!(
+                html,
+                "<p>{}</p>",
+                escape_html(&locale.format(
+                    Message::GreenestRegion,
+                    &[
+                        &green.region,
+                        &locale.format_number(green.carbon_g_per_kwh, 0),
+                        &locale.format_score(green.score),
+                    ]
+                ))
+            );
+        }
+
+        if ranked.iter().any(|(_, _, stats, _)| stats.asn.is_some()) {
+            Self::render_networks(html, locale, &AsnBreakdown::from_ranking(&ranked));
+        }
+    }
+
+    /// Ranked regions grouped by the autonomous system behind them
+    fn render_networks(html: &mut String, locale: Locale, breakdown: &AsnBreakdown) {
+        let _ = writeln!(
+            html,
+            "<h2>{}</h2>\n<table>\n{}",
+            locale.text(Message::Networks),
+            header_row(
+                locale,
+                &[
+                    Message::AutonomousSystem,
+                    Message::Organization,
+                    Message::Latency,
+                    Message::Slow,
+                    Message::Regions,
+                ]
+            )
+        );
+        for group in &breakdown.groups {
+            let latency = group
+                .avg_latency_ms
+                .map_or_else(|| "-".to_string(), |latency| locale.format_latency_ms(latency));
+            let _ = writeln!(
+                html,
+                "<tr><td>AS{}</td><td>{}</td><td class=\"num\">{latency}</td>\
+                 <td class=\"num\">{}/{}</td><td>{}</td></tr>",
+                group.asn,
+                escape_html(&group.organization),
+                group.slow.len(),
+                group.regions.len(),
+                escape_html(&group.regions.join(", "))
+            );
+        }
+        html.push_str("</table>\n");
+
+        if let Some(group) = breakdown.slow_behind_one() {
+            let network = format!("AS{} {}", group.asn, group.organization);
+            let _ = writeln!(
+                html,
+                "<p>{}</p>",
+                escape_html(&locale.format(
+                    Message::SlowBehindNetwork,
+                    &[&group.slow.len().to_string(), network.trim_end()]
+                ))
+            );
+        }
+    }
+
+    /// Region pairs shaded from the fastest (green) to the slowest (red) estimate
+    fn render_latency_matrix(&self, html: &mut String, matrix: &LatencyMatrix) {
+        let locale = self.locale;
+        let _ = write!(
+            html,
+            "<h2>{}</h2>\n<p class=\"muted\">{}</p>\n<table class=\"heatmap\">\n<tr><th></th>",
+            locale.text(Message::InterRegionLatency),
+            locale.format(
+                Message::InterRegionNote,
+                &[&locale.format_number(matrix.route_factor, 2)]
+            )
+        );
+        for region in &matrix.regions {
+            let _ = write!(html, "<th>{}</th>", escape_html(region));
+        }
+        html.push_str("</tr>\n");
+
+        let (fastest, slowest) = matrix.range().unwrap_or((0.0, 0.0));
+        for (region, row) in matrix.regions.iter().zip(&matrix.rows) {
+            let _ = write!(html, "<tr><th>{}</th>", escape_html(region));
+            for estimate in row {
+                match estimate {
+                    Some(estimate) => {
+                        // Hue 120 (green) for the fastest pair down to 0 (red) for the slowest
+                        let spread = slowest - fastest;
+                        let hue = if spread > 0.0 {
+                            120.0 * (slowest - estimate.rtt_ms) / spread
+                        } else {
+                            120.0
+                        };
+                        let value = locale.format_number(estimate.rtt_ms, 0);
+                        let value = match estimate.source {
+                            EstimateSource::Dataset => format!("<b>{value}</b>"),
+                            EstimateSource::Distance => value,
+                        };
+                        let _ = write!(
+                            html,
+                            "<td style=\"background:hsl({hue:.0},70%,80%)\">{value}</td>"
+                        );
+                    }
+                    None => html.push_str("<td></td>"),
+                }
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+    }
+
+    fn render_normalized(&self, html: &mut String, control: &ControlSeries) {
+        let locale = self.locale;
+        let baseline = control.baseline_ms().map_or_else(
+            || locale.text(Message::NoAnswer).to_string(),
+            |ms| locale.format_latency_ms(ms),
+        );
+        let _ = writeln!(
+            html,
+            "<h2>{}</h2>\n<p class=\"muted\">{}</p>\n<table>\n{}",
+            locale.text(Message::ControlNormalizedLatency),
+            locale.format(
+                Message::ControlNormalizedNote,
+                &[&escape_html(&control.url), &baseline]
+            ),
+            header_row(
+                locale,
+                &[
+                    Message::Region,
+                    Message::Raw,
+                    Message::Control,
+                    Message::Normalized,
+                ]
+            )
+        );
+
+        let format =
+            |ms: Option<f64>| ms.map_or_else(|| "-".to_string(), |ms| locale.format_latency_ms(ms));
+        for (name, stats) in &self.results {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td>\
+                 <td class=\"num\">{}</td></tr>",
+                escape_html(name),
+                format(stats.is_successful().then_some(stats.avg)),
+                format(stats.control_latency_ms),
+                format(stats.normalized_avg)
+            );
+        }
+
+        html.push_str("</table>\n");
+    }
+
+    fn render_failures(&self, html: &mut String) {
+        let _ = writeln!(
+            html,
+            "<h2>{}</h2>\n<table>\n{}",
+            self.locale.text(Message::FailedRegions),
+            header_row(
+                self.locale,
+                &[Message::Region, Message::Cause, Message::Error]
+            )
+        );
+
+        for failure in &self.failures {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"down\">{}</td><td>{}</td></tr>",
+                escape_html(&failure.region),
+                failure.kind,
+                escape_html(&failure.message)
+            );
+        }
+
+        html.push_str("</table>\n");
+    }
+
+    fn render_availability(&self, html: &mut String, availability: &AvailabilityReport) {
+        let locale = self.locale;
+        let _ = writeln!(
+            html,
+            "<h2>{}</h2>\n<table>\n<tr><th>{}</th><th>{}</th><th>{}</th>\
+             <th>24h</th><th>7d</th><th>30d</th></tr>",
+            locale.text(Message::Availability),
+            locale.text(Message::Endpoint),
+            locale.text(Message::Status),
+            locale.text(Message::Since)
+        );
+
+        for endpoint in &availability.endpoints {
+            let (class, label) = match endpoint.current_state {
+                AvailabilityState::Up => ("up", locale.text(Message::Up)),
+                AvailabilityState::Down => ("down", locale.text(Message::Down)),
+            };
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"{class}\">{label}</td><td>{}</td>\
+                 <td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>",
+                escape_html(&endpoint.endpoint_id),
+                locale.format_datetime(&endpoint.since),
+                format_uptime(locale, endpoint.uptime_24h),
+                format_uptime(locale, endpoint.uptime_7d),
+                format_uptime(locale, endpoint.uptime_30d)
+            );
+        }
+
+        html.push_str("</table>\n");
+    }
+
+    /// Render and write the report to `path`
+    pub async fn write(&self, path: &Path) -> Result<()> {
+        tokio::fs::write(path, self.render()).await?;
+        Ok(())
+    }
+}
+
+/// Live status page for endpoints under continuous monitoring
+///
+/// Shows current health and score per endpoint, a 24 hour uptime bar and the
+/// most recent alerts. The page refreshes itself, so it can be left open as an
+/// internal dashboard.
+pub struct StatusPage {
+    title: String,
+    endpoints: Vec<Endpoint>,
+    scores: HashMap<String, ComprehensiveScoreResult>,
+    availability: Option<AvailabilityReport>,
+    alerts: Vec<Alert>,
+    locale: Locale,
+}
+
+impl StatusPage {
+    /// Start a status page with the given title
+    #[must_use]
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            endpoints: Vec::new(),
+            scores: HashMap::new(),
+            availability: None,
+            alerts: Vec::new(),
+            locale: Locale::default(),
+        }
+    }
+
+    /// Language and number format of the page
+    #[must_use]
+    pub const fn locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Monitored endpoints with their latest scores, keyed by endpoint ID
+    #[must_use]
+    pub fn endpoints(
+        mut self,
+        endpoints: Vec<Endpoint>,
+        scores: HashMap<String, ComprehensiveScoreResult>,
+    ) -> Self {
+        self.endpoints = endpoints;
+        self.scores = scores;
+        self
+    }
+
+    /// Uptime figures, keyed by endpoint name as the monitoring ledger records them
+    #[must_use]
+    pub fn availability(mut self, availability: AvailabilityReport) -> Self {
+        self.availability = Some(availability);
+        self
+    }
+
+    /// Recent alerts, newest first
+    #[must_use]
+    pub fn alerts(mut self, alerts: Vec<Alert>) -> Self {
+        self.alerts = alerts;
+        self
+    }
+
+    /// Render the page as an HTML document
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut html = String::new();
+        let title = escape_html(&self.title);
+
+        let locale = self.locale;
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"{locale}\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <meta http-equiv=\"refresh\" content=\"{STATUS_REFRESH_SECS}\">\n\
+             <title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n\
+             <p class=\"muted\">{}</p>\n",
+            locale.format(
+                Message::Updated,
+                &[&locale.format_datetime(&TimeUtils::now())]
+            )
+        );
+
+        self.render_endpoints(&mut html);
+        self.render_alerts(&mut html);
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    fn render_endpoints(&self, html: &mut String) {
+        let locale = self.locale;
+        let _ = writeln!(
+            html,
+            "<h2>{}</h2>\n<table>\n<tr><th>{}</th><th>{}</th><th>{}</th><th>{}</th><th>{}</th>\
+             <th></th><th>30d</th></tr>",
+            locale.text(Message::Endpoints),
+            locale.text(Message::Endpoint),
+            locale.text(Message::Status),
+            locale.text(Message::Score),
+            locale.text(Message::Grade),
+            locale.text(Message::UptimeDay)
+        );
+
+        let mut endpoints: Vec<(&str, &Endpoint)> =
+            self.endpoints.iter().map(|e| (endpoint_name(e), e)).collect();
+        endpoints.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (name, endpoint) in endpoints {
+            let uptime = self
+                .availability
+                .as_ref()
+                .and_then(|report| report.endpoints.iter().find(|a| a.endpoint_id == name));
+            let (class, label) = match uptime.map(|a| a.current_state) {
+                Some(AvailabilityState::Up) => ("up", locale.text(Message::Up)),
+                Some(AvailabilityState::Down) => ("down", locale.text(Message::Down)),
+                None => ("muted", locale.text(Message::Pending)),
+            };
+            let score = self.scores.get(&endpoint.id);
+            let uptime_24h = uptime.and_then(|a| a.uptime_24h);
+
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"{class}\">{label}</td><td class=\"num\">{}</td>\
+                 <td>{}</td><td class=\"num\">{}</td><td>{}</td><td class=\"num\">{}</td></tr>",
+                escape_html(name),
+                score.map_or_else(|| "-".to_string(), |s| locale.format_score(s.score)),
+                score.map_or_else(|| "-".to_string(), |s| s.grade.to_string()),
+                format_uptime(locale, uptime_24h),
+                uptime_24h.map_or_else(String::new, |value| format!(
+                    "<div class=\"bar\"><span style=\"width:{value:.1}%\"></span></div>"
+                )),
+                format_uptime(locale, uptime.and_then(|a| a.uptime_30d))
+            );
+        }
+
+        html.push_str("</table>\n");
+    }
+
+    fn render_alerts(&self, html: &mut String) {
+        let locale = self.locale;
+        let _ = writeln!(html, "<h2>{}</h2>", locale.text(Message::RecentAlerts));
+        if self.alerts.is_empty() {
+            let _ = writeln!(html, "<p class=\"muted\">{}</p>", locale.text(Message::NoAlerts));
+            return;
+        }
+
+        let _ = writeln!(
+            html,
+            "<table>\n{}",
+            header_row(
+                locale,
+                &[
+                    Message::Time,
+                    Message::Severity,
+                    Message::Endpoint,
+                    Message::Alert,
+                ]
+            )
+        );
+        for alert in &self.alerts {
+            let severity = alert.severity();
+            let class = match severity {
+                AlertSeverity::Critical => "critical",
+                AlertSeverity::Warning => "warning",
+                AlertSeverity::Info => "info",
+            };
+            let label = locale.severity(severity);
+            let mut description = escape_html(&alert.description());
+            if let Some(incident) = &alert.provider_incident {
+                let _ = write!(description, " <span class=\"muted\">({})</span>", escape_html(incident));
+            }
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"{class}\">{label}</td><td>{}</td><td>{description}</td></tr>",
+                locale.format_datetime(&alert.timestamp),
+                escape_html(
+                    self.endpoints
+                        .iter()
+                        .find(|e| e.id == alert.endpoint_id)
+                        .map_or(alert.endpoint_id.as_str(), endpoint_name)
+                )
+            );
+        }
+        html.push_str("</table>\n");
+    }
+}
+
+/// Display name of a monitored endpoint, which is also its availability ledger key
+fn endpoint_name(endpoint: &Endpoint) -> &str {
+    endpoint
+        .metadata
+        .get("name")
+        .map_or(endpoint.id.as_str(), String::as_str)
+}
+
+/// Uptime with three decimals, as status pages usually show it
+fn format_uptime(locale: Locale, uptime: Option<f64>) -> String {
+    uptime.map_or_else(
+        || "-".to_string(),
+        |value| locale.format_percentage_with(value, 3),
+    )
+}
+
+/// Table row of translated column headers
+fn header_row(locale: Locale, columns: &[Message]) -> String {
+    let mut row = String::from("<tr>");
+    for column in columns {
+        let _ = write!(row, "<th>{}</th>", locale.text(*column));
+    }
+    row.push_str("</tr>");
+    row
+}
+
+/// Inline SVG line of `latencies`, higher meaning slower; empty below two points
+fn sparkline_svg(locale: Locale, latencies: &[f64]) -> String {
+    if latencies.len() < 2 {
+        return String::new();
+    }
+    let (width, height) = SPARKLINE_SIZE;
+    let min = latencies.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = latencies.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let step = width / f64::from(u32::try_from(latencies.len() - 1).unwrap_or(u32::MAX));
+
+    let mut points = String::new();
+    for (latency, i) in latencies.iter().zip(0u32..) {
+        let level = if max > min { (latency - min) / (max - min) } else { 0.5 };
+        let y = (height - 2.0).mul_add(-level, height - 1.0);
+        let separator = if i == 0 { "" } else { " " };
+        let _ = write!(points, "{separator}{:.1},{y:.1}", f64::from(i) * step);
+    }
+    format!(
+        "<svg class=\"spark\" viewBox=\"0 0 {width} {height}\" preserveAspectRatio=\"none\">\
+         <title>{} - {}</title><polyline points=\"{points}\"/></svg>",
+        locale.format_latency_ms(min),
+        locale.format_latency_ms(max)
+    )
+}
+
+/// Escape text for inclusion in HTML element content or attributes
+#[must_use]
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AvailabilityLedger, LatencyEstimate};
+
+    #[test]
+    fn test_render_report_sections() {
+        let mut stats = PingStats::new(5);
+        stats.successful_pings = 5;
+        stats.avg = 25.0;
+        stats.notes = vec!["behind <CDN>".to_string()];
+        stats.cost.price_index = Some(2.0);
+        stats.carbon_g_per_kwh = Some(120.0);
+        stats.asn = Some(crate::models::AsnInfo {
+            ip: "192.0.2.1".parse().unwrap(),
+            asn: 64_500,
+            organization: "Example & Co".to_string(),
+            prefix: None,
+            country: None,
+        });
+
+        let mut ledger = AvailabilityLedger::new();
+        ledger.record("<eu>", true, TimeUtils::now());
+
+        let mut history = TestHistory::new(
+            "eu".to_string(),
+            "Frankfurt".to_string(),
+            "https://eu.example.com".to_string(),
+        );
+        let mut evening = stats.clone();
+        evening.avg = 80.0;
+        evening.test_time = stats.test_time + chrono::Duration::hours(12);
+        history.add_test_result(stats.clone());
+        history.add_test_result(evening);
+
+        let html = HtmlReport::new("Weekly report")
+            .results(
+                &[("Frankfurt".to_string(), stats)],
+                &AlgorithmWeights::default(),
+            )
+            .failures(vec![RegionFailure {
+                region: "Sydney".to_string(),
+                url: "https://au.example.com".to_string(),
+                kind: crate::models::FailureKind::Dns,
+                message: "failed to lookup address".to_string(),
+            }])
+            .labels(RunLabels::from([(
+                "location".to_string(),
+                "<office>".to_string(),
+            )]))
+            .availability(ledger.report(TimeUtils::now()))
+            .history(vec![history], FixedOffset::east_opt(0).unwrap())
+            .green(GreenThreshold::default())
+            .render();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h1>Weekly report</h1>"));
+        assert!(html.contains("Frankfurt"));
+        assert!(html.contains("&lt;eu&gt;"));
+        assert!(html.contains("100.000%"));
+        assert!(html.contains("Latency by time of day"));
+        assert!(html.contains("<p>Labels: location=&lt;office&gt;</p>"));
+        assert!(html.contains("<h2>Failed regions</h2>"));
+        assert!(html.contains("<td>Sydney</td><td class=\"down\">DNS resolution</td>"));
+        assert!(html.contains("hsl(0,70%,80%)\" title=\"1 runs\">80</td>"));
+        assert!(html.contains("<th>Trend</th><th>Value</th>"));
+        assert!(html.contains("<p>Greenest acceptable region: Frankfurt (120 gCO2/kWh, score "));
+        assert!(html.contains("<h2>Networks (ASN)</h2>"));
+        assert!(html.contains("<tr><td>AS64500</td><td>Example &amp; Co</td><td class=\"num\">25.00ms</td>"));
+        assert!(html.contains("Frankfurt<br><small class=\"muted\">behind &lt;CDN&gt;</small>"));
+        assert!(html.contains("<title>25.00ms - 80.00ms</title><polyline points=\"0.0,19.0 100.0,1.0\"/>"));
+    }
+
+    #[test]
+    fn test_render_latency_matrix() {
+        let estimate = |rtt_ms, source| Some(LatencyEstimate { rtt_ms, source });
+        let matrix = LatencyMatrix {
+            regions: vec!["Frankfurt".to_string(), "<Tokyo>".to_string()],
+            route_factor: 1.5,
+            calibration_samples: 0,
+            rows: vec![
+                vec![None, estimate(230.0, EstimateSource::Dataset)],
+                vec![estimate(240.0, EstimateSource::Distance), None],
+            ],
+        };
+
+        let html = HtmlReport::new("Matrix").latency_matrix(matrix).render();
+        assert!(html.contains("<h2>Region-to-region latency</h2>"));
+        assert!(html.contains("route factor of 1.50"));
+        assert!(html.contains("<tr><th></th><th>Frankfurt</th><th>&lt;Tokyo&gt;</th></tr>"));
+        assert!(html.contains("<td style=\"background:hsl(120,70%,80%)\"><b>230</b></td>"));
+        assert!(html.contains("<td style=\"background:hsl(0,70%,80%)\">240</td><td></td></tr>"));
+    }
+
+    #[test]
+    fn test_render_status_page() {
+        let mut endpoint = Endpoint::new(
+            "id-1".to_string(),
+            "example.com".to_string(),
+            443,
+            crate::models::ProbeType::HTTP,
+        );
+        endpoint
+            .metadata
+            .insert("name".to_string(), "Frankfurt".to_string());
+
+        let mut ledger = AvailabilityLedger::new();
+        ledger.record("Frankfurt", false, TimeUtils::now());
+
+        let alert = Alert::new(
+            "id-1".to_string(),
+            crate::models::AlertType::SustainedLoss { loss_percent: 12.0 },
+        );
+
+        let html = StatusPage::new("Status")
+            .endpoints(vec![endpoint], HashMap::new())
+            .availability(ledger.report(TimeUtils::now()))
+            .alerts(vec![alert])
+            .render();
+
+        assert!(html.contains("http-equiv=\"refresh\""));
+        assert!(html.contains("<td class=\"down\">Down</td>"));
+        assert!(html.contains("width:0.0%"));
+        assert!(html.contains("<td>Frankfurt</td><td>"));
+        assert!(!html.contains("No alerts."));
+    }
+
+    #[test]
+    fn test_render_report_in_locale() {
+        let mut stats = PingStats::new(4);
+        stats.successful_pings = 4;
+        stats.avg = 1234.5;
+        stats.packet_loss = 2.5;
+
+        let html = HtmlReport::new("Bericht")
+            .locale(Locale::De)
+            .results(
+                &[("Frankfurt".to_string(), stats)],
+                &AlgorithmWeights::default(),
+            )
+            .render();
+
+        assert!(html.contains("<html lang=\"de\">"));
+        assert!(html.contains("<h2>Rangliste</h2>"));
+        assert!(html.contains("<th>Latenz</th><th>Verlust</th>"));
+        assert!(html.contains("1.234,50ms"));
+        assert!(html.contains("2,5%"));
+        assert!(!html.contains("Generated"));
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html(r#"<a href="x">&'"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&#39;"
+        );
+    }
+}
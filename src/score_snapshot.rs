@@ -0,0 +1,132 @@
+//! Read-only copies of the scores for exporters and the APIs
+//!
+//! The aggregator writes every endpoint's score and score history under a
+//! lock as records come in. Reading those maps from the HTTP and gRPC APIs
+//! or the metrics exporter would hold the aggregator up on every request,
+//! so [`publish_snapshots`] copies them into a [`ScoreSnapshot`] once per
+//! `monitoring.snapshot_interval` instead. Readers share the latest snapshot
+//! through a watch channel and never touch the aggregator's locks; what they
+//! see is at most one interval old.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use tokio::sync::{watch, RwLock};
+use tokio::time::interval;
+
+use crate::models::{ComprehensiveScoreResult, ExponentialHistogram, ScorePoint};
+use crate::time_utils::TimeUtils;
+
+/// Scores of every endpoint that has reported, as of one moment
+#[derive(Debug, Clone, Default)]
+pub struct ScoreSnapshot {
+    /// When the scores were copied; `None` until the first copy
+    pub taken_at: Option<DateTime<Utc>>,
+    /// Latest score, keyed by endpoint ID
+    pub scores: HashMap<String, ComprehensiveScoreResult>,
+    /// Recent scores, oldest first, keyed by endpoint ID
+    pub score_history: HashMap<String, Vec<ScorePoint>>,
+    /// Every round trip since the endpoint was added, keyed by endpoint ID
+    pub rtt_histograms: HashMap<String, ExponentialHistogram>,
+}
+
+impl ScoreSnapshot {
+    /// This snapshot without the score of `endpoint_id`; `None` when it has none
+    #[must_use]
+    pub fn without_score(&self, endpoint_id: &str) -> Option<Self> {
+        self.scores.contains_key(endpoint_id).then(|| {
+            let mut snapshot = self.clone();
+            snapshot.scores.remove(endpoint_id);
+            snapshot.rtt_histograms.remove(endpoint_id);
+            snapshot
+        })
+    }
+}
+
+/// Maps the aggregator writes the scores to
+#[derive(Debug, Clone)]
+pub struct ScoreSources {
+    /// Latest score of every endpoint
+    pub scores: Arc<RwLock<HashMap<String, ComprehensiveScoreResult>>>,
+    /// Recent scores of every endpoint
+    pub score_history: Arc<RwLock<HashMap<String, Vec<ScorePoint>>>>,
+    /// Round-trip histogram of every endpoint
+    pub rtt_histograms: Arc<RwLock<HashMap<String, ExponentialHistogram>>>,
+}
+
+impl ScoreSources {
+    /// Copy the current scores, holding each lock only while cloning its map
+    pub async fn snapshot(&self) -> ScoreSnapshot {
+        let scores = self.scores.read().await.clone();
+        let score_history = self.score_history.read().await.clone();
+        let rtt_histograms = self.rtt_histograms.read().await.clone();
+        ScoreSnapshot {
+            taken_at: Some(TimeUtils::now()),
+            scores,
+            score_history,
+            rtt_histograms,
+        }
+    }
+}
+
+/// Publish a snapshot of `sources` to `snapshots` every `interval_ms` milliseconds
+///
+/// A changed interval takes effect right away.
+pub async fn publish_snapshots(
+    sources: ScoreSources,
+    snapshots: watch::Sender<Arc<ScoreSnapshot>>,
+    mut interval_ms: watch::Receiver<u64>,
+) {
+    let mut timer = interval(TimeUtils::duration_from_millis(*interval_ms.borrow_and_update()));
+
+    loop {
+        tokio::select! {
+            _ = timer.tick() => {}
+            Ok(()) = interval_ms.changed() => {
+                timer = interval(TimeUtils::duration_from_millis(*interval_ms.borrow_and_update()));
+                continue;
+            }
+        }
+        snapshots.send_replace(Arc::new(sources.snapshot().await));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::scoring::compute_score;
+    use crate::models::{AggregatorState, AlgorithmWeights};
+
+    #[tokio::test]
+    async fn test_snapshots_are_published_at_the_interval() {
+        let sources = ScoreSources {
+            scores: Arc::default(),
+            score_history: Arc::default(),
+            rtt_histograms: Arc::default(),
+        };
+        let (snapshots, mut reader) = watch::channel(Arc::new(ScoreSnapshot::default()));
+        let (_interval, interval_ms) = watch::channel(10);
+        tokio::spawn(publish_snapshots(sources.clone(), snapshots, interval_ms));
+
+        let state = AggregatorState::new("api".to_string(), 8, 8);
+        let score = compute_score(&state, &AlgorithmWeights::default());
+        sources.scores.write().await.insert("api".to_string(), score);
+        // Readers see the write once the next snapshot is published
+        let snapshot = loop {
+            reader.changed().await.unwrap();
+            let snapshot = Arc::clone(&reader.borrow_and_update());
+            if !snapshot.scores.is_empty() {
+                break snapshot;
+            }
+        };
+        assert!(snapshot.taken_at.is_some());
+        assert!(snapshot.scores.contains_key("api"));
+
+        // Writes after a snapshot leave it as it was
+        sources.scores.write().await.clear();
+        assert!(snapshot.scores.contains_key("api"));
+        assert!(snapshot.without_score("api").unwrap().scores.is_empty());
+        assert!(snapshot.without_score("db").is_none());
+    }
+}
@@ -0,0 +1,368 @@
+//! Alert conditions and derived metrics written as scripts
+//!
+//! Conditions too bespoke for the built-in thresholds, such as "latency
+//! above 200 ms while some probes fail", are written as Rhai expressions in
+//! `[[monitoring.scripts]]`. After every rescore the aggregator evaluates
+//! them against the endpoint's state:
+//!
+//! - a `metric` script yields a number, kept with the endpoint's score under
+//!   the script's name and visible to the scripts after it;
+//! - a `condition` script yields a boolean and raises a `scripted` alert
+//!   when it first holds, re-arming once it no longer does.
+//!
+//! Scripts see `endpoint`, `namespace`, `score`, `p50_ms`, `p90_ms`,
+//! `p99_ms`, `jitter_ms`, `loss_percent`, `loss_long_percent`,
+//! `availability_percent`, `availability_long_percent` and `samples`. They
+//! run with limits on operations, string, array and map sizes and nesting,
+//! and cannot import modules, so a runaway script fails rather than stalling
+//! the aggregator or exhausting its memory. Needs the `scripting` feature; without it
+//! configured scripts are ignored with a warning.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CloudPingError, Result};
+use crate::models::AlertSeverity;
+
+/// One script read from `[[monitoring.scripts]]`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScriptSettings {
+    /// Name of the derived metric, or of the alert condition in its alerts
+    pub name: String,
+    /// Expression that raises an alert while it is `true`
+    #[serde(default)]
+    pub condition: Option<String>,
+    /// Expression whose number is kept as a derived metric
+    #[serde(default)]
+    pub metric: Option<String>,
+    /// Severity of the alerts a condition raises
+    #[serde(default = "default_severity")]
+    pub severity: AlertSeverity,
+}
+
+const fn default_severity() -> AlertSeverity {
+    AlertSeverity::Warning
+}
+
+impl ScriptSettings {
+    /// # Errors
+    /// Returns a validation error naming the first invalid setting
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            return Err(CloudPingError::validation(
+                "monitoring.scripts.name",
+                "must not be empty",
+            ));
+        }
+        if self.condition.is_some() == self.metric.is_some() {
+            return Err(CloudPingError::validation(
+                "monitoring.scripts",
+                format!("`{}` needs exactly one of `condition` and `metric`", self.name),
+            ));
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "scripting")]
+    fn source(&self) -> &str {
+        self.condition.as_deref().or(self.metric.as_deref()).unwrap_or_default()
+    }
+}
+
+/// Result of one condition script for one endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionResult {
+    /// Name of the condition
+    pub name: String,
+    /// Severity of the alerts it raises
+    pub severity: AlertSeverity,
+    /// Whether the condition holds; `false` when the script failed
+    pub holds: bool,
+}
+
+/// Derived metrics and conditions of one endpoint
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScriptOutcome {
+    /// Derived metrics by name; scripts that failed are left out
+    pub metrics: std::collections::BTreeMap<String, f64>,
+    /// Every condition, in the configured order
+    pub conditions: Vec<ConditionResult>,
+}
+
+/// Values of an endpoint scripts are evaluated against
+#[derive(Debug, Clone, Copy)]
+pub struct ScriptInputs<'a> {
+    /// Endpoint ID
+    pub endpoint: &'a str,
+    /// Namespace of the endpoint; empty outside one
+    pub namespace: &'a str,
+    /// Short-window state of the endpoint
+    pub state: &'a crate::models::AggregatorState,
+    /// Score just computed for the endpoint
+    pub score: f64,
+}
+
+#[cfg(feature = "scripting")]
+pub use self::engine::ScriptSet;
+
+#[cfg(feature = "scripting")]
+mod engine {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use rhai::{Dynamic, Engine, Scope, AST};
+    use tracing::{debug, warn};
+
+    use super::{ConditionResult, ScriptInputs, ScriptOutcome, ScriptSettings};
+    use crate::error::{CloudPingError, Result};
+
+    /// Operations a script may run per evaluation
+    const MAX_OPERATIONS: u64 = 100_000;
+
+    /// Longest string a script may build, in bytes
+    const MAX_STRING_SIZE: usize = 4096;
+
+    /// Most elements of an array or map a script may build
+    const MAX_COLLECTION_SIZE: usize = 1024;
+
+    /// Deepest nesting of function calls
+    const MAX_CALL_LEVELS: usize = 16;
+
+    /// Deepest nesting of expressions, at the top level and in functions
+    const MAX_EXPR_DEPTHS: (usize, usize) = (64, 32);
+
+    struct Script {
+        settings: ScriptSettings,
+        ast: AST,
+        /// Whether a failure of the script was logged already
+        failed: AtomicBool,
+    }
+
+    /// Compiled scripts, evaluated in the configured order
+    pub struct ScriptSet {
+        engine: Engine,
+        scripts: Vec<Script>,
+    }
+
+    impl std::fmt::Debug for ScriptSet {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("ScriptSet")
+                .field(
+                    "scripts",
+                    &self.scripts.iter().map(|script| &script.settings.name).collect::<Vec<_>>(),
+                )
+                .finish_non_exhaustive()
+        }
+    }
+
+    impl ScriptSet {
+        /// Compile `scripts`; `None` when there are none
+        ///
+        /// # Errors
+        /// Returns a validation error for the first script that does not compile
+        pub fn compile(scripts: &[ScriptSettings]) -> Result<Option<Self>> {
+            if scripts.is_empty() {
+                return Ok(None);
+            }
+            let mut engine = Engine::new();
+            engine.set_max_operations(MAX_OPERATIONS);
+            engine.set_max_string_size(MAX_STRING_SIZE);
+            engine.set_max_array_size(MAX_COLLECTION_SIZE);
+            engine.set_max_map_size(MAX_COLLECTION_SIZE);
+            engine.set_max_call_levels(MAX_CALL_LEVELS);
+            engine.set_max_expr_depths(MAX_EXPR_DEPTHS.0, MAX_EXPR_DEPTHS.1);
+            // Scripts are self-contained; `import` must not reach the file system
+            engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+            engine.on_print(|text| debug!("Script printed: {}", text));
+            let scripts = scripts
+                .iter()
+                .map(|settings| {
+                    settings.validate()?;
+                    let ast = engine.compile(settings.source()).map_err(|e| {
+                        CloudPingError::validation(
+                            "monitoring.scripts",
+                            format!("`{}` does not compile: {e}", settings.name),
+                        )
+                    })?;
+                    Ok(Script {
+                        settings: settings.clone(),
+                        ast,
+                        failed: AtomicBool::new(false),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Some(Self { engine, scripts }))
+        }
+
+        /// Evaluate every script against `inputs`
+        ///
+        /// The first failure of each script is logged; a failed metric is
+        /// left out and a failed condition does not hold.
+        #[must_use]
+        pub fn evaluate(&self, inputs: &ScriptInputs<'_>) -> ScriptOutcome {
+            let mut scope = scope(inputs);
+            let mut outcome = ScriptOutcome::default();
+            for script in &self.scripts {
+                let result = self.engine.eval_ast_with_scope::<Dynamic>(&mut scope, &script.ast);
+                let value = result.map_err(|e| e.to_string()).and_then(|value| {
+                    if script.settings.condition.is_some() {
+                        value
+                            .as_bool()
+                            .map(Dynamic::from)
+                            .map_err(|kind| format!("yields {kind}, not a bool"))
+                    } else {
+                        number(&value)
+                            .map(Dynamic::from)
+                            .ok_or_else(|| format!("yields {}, not a number", value.type_name()))
+                    }
+                });
+                let value = match value {
+                    Ok(value) => Some(value),
+                    Err(e) => {
+                        if !script.failed.swap(true, Ordering::Relaxed) {
+                            warn!(
+                                "Script {} failed for endpoint {}: {}",
+                                script.settings.name, inputs.endpoint, e
+                            );
+                        }
+                        None
+                    }
+                };
+                let name = script.settings.name.clone();
+                if script.settings.condition.is_some() {
+                    outcome.conditions.push(ConditionResult {
+                        name,
+                        severity: script.settings.severity,
+                        holds: value.is_some_and(|value| value.as_bool().unwrap_or(false)),
+                    });
+                } else if let Some(metric) = value.as_ref().and_then(number) {
+                    scope.push_constant(name.as_str(), metric);
+                    outcome.metrics.insert(name, metric);
+                }
+            }
+            outcome
+        }
+    }
+
+    /// A script's number as a float
+    #[allow(clippy::cast_precision_loss)] // integers from scripts are small
+    fn number(value: &Dynamic) -> Option<f64> {
+        value.as_float().ok().or_else(|| value.as_int().ok().map(|int| int as f64))
+    }
+
+    fn scope(inputs: &ScriptInputs<'_>) -> Scope<'static> {
+        let state = inputs.state;
+        let mut scope = Scope::new();
+        scope.push_constant("endpoint", inputs.endpoint.to_string());
+        scope.push_constant("namespace", inputs.namespace.to_string());
+        scope.push_constant("score", inputs.score);
+        scope.push_constant("p50_ms", state.cached_p50_short);
+        scope.push_constant("p90_ms", state.cached_p90_short);
+        scope.push_constant("p99_ms", state.cached_p99_short);
+        scope.push_constant("jitter_ms", state.ewma_jitter_ms);
+        scope.push_constant("loss_percent", state.cached_loss_short);
+        scope.push_constant("loss_long_percent", state.cached_loss_long);
+        scope.push_constant("availability_percent", state.cached_avail_short);
+        scope.push_constant("availability_long_percent", state.cached_avail_long);
+        scope.push_constant(
+            "samples",
+            i64::try_from(state.circular_buffer_short.len()).unwrap_or(i64::MAX),
+        );
+        scope
+    }
+}
+
+/// Stand-in for the compiled scripts in builds without the `scripting` feature
+#[cfg(not(feature = "scripting"))]
+#[derive(Debug)]
+pub struct ScriptSet;
+
+#[cfg(not(feature = "scripting"))]
+impl ScriptSet {
+    /// Warn that `scripts` are ignored; always `None`
+    ///
+    /// # Errors
+    /// Never fails; the signature matches the scripting build
+    pub fn compile(scripts: &[ScriptSettings]) -> Result<Option<Self>> {
+        if !scripts.is_empty() {
+            tracing::warn!(
+                "Ignoring {} scripts: built without the scripting feature",
+                scripts.len()
+            );
+        }
+        Ok(None)
+    }
+
+    /// Nothing to evaluate without the `scripting` feature
+    #[must_use]
+    pub fn evaluate(&self, _inputs: &ScriptInputs<'_>) -> ScriptOutcome {
+        ScriptOutcome::default()
+    }
+}
+
+#[cfg(all(test, feature = "scripting"))]
+mod tests {
+    use super::*;
+    use crate::models::{AggregatorState, ProbeRecord};
+
+    fn script(name: &str, condition: Option<&str>, metric: Option<&str>) -> ScriptSettings {
+        ScriptSettings {
+            name: name.to_string(),
+            condition: condition.map(str::to_string),
+            metric: metric.map(str::to_string),
+            severity: AlertSeverity::Critical,
+        }
+    }
+
+    #[test]
+    fn test_scripts_derive_metrics_and_check_conditions() {
+        let scripts = ScriptSet::compile(&[
+            script("headroom", None, Some("200.0 - p50_ms")),
+            script("slow", Some("headroom < 150.0 && samples >= 3"), None),
+            script("broken", Some("p50_ms"), None),
+        ])
+        .unwrap()
+        .unwrap();
+        let mut state = AggregatorState::new("api".to_string(), 8, 8);
+        for rtt_ms in [80.0, 90.0, 100.0] {
+            state.add_record(ProbeRecord::success("api".to_string(), rtt_ms), 0.1);
+        }
+        state.recompute_short_aggregates();
+        let inputs = ScriptInputs {
+            endpoint: "api",
+            namespace: "",
+            state: &state,
+            score: 90.0,
+        };
+
+        let outcome = scripts.evaluate(&inputs);
+        assert!((outcome.metrics["headroom"] - (200.0 - state.cached_p50_short)).abs() < 1e-9);
+        assert_eq!(
+            outcome.conditions,
+            [
+                ConditionResult {
+                    name: "slow".to_string(),
+                    severity: AlertSeverity::Critical,
+                    holds: true,
+                },
+                // A number is not a condition
+                ConditionResult {
+                    name: "broken".to_string(),
+                    severity: AlertSeverity::Critical,
+                    holds: false,
+                },
+            ]
+        );
+
+        // Scripts cannot grow without bound or load modules
+        let limited = ScriptSet::compile(&[
+            script("huge", None, Some(r#"let s = "x"; for i in 0..20 { s += s; } s.len().to_float()"#)),
+            script("import", None, Some(r#"import "metrics" as m; 1.0"#)),
+        ])
+        .unwrap()
+        .unwrap();
+        assert!(limited.evaluate(&inputs).metrics.is_empty());
+
+        assert!(ScriptSet::compile(&[script("bad", Some("p50_ms >"), None)]).is_err());
+        assert!(ScriptSet::compile(&[script("both", Some("true"), Some("1"))]).is_err());
+        assert!(ScriptSet::compile(&[]).unwrap().is_none());
+    }
+}
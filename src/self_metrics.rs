@@ -0,0 +1,288 @@
+//! Internal health of the monitor itself
+//!
+//! When probes start late, records pile up between tasks or the aggregator
+//! falls behind, the monitor rather than the network is the bottleneck and
+//! its scores stop meaning much. [`SelfMetrics`] is shared by the probe
+//! runner, the forwarding tasks and the aggregator, which update it as they
+//! go. A [`SelfMetricsSnapshot`] of it is published with every metrics
+//! export, served at `/api/self` and sent on the gRPC metrics stream.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::task::AbortHandle;
+use tracing::error;
+
+/// Channel between two monitoring tasks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Queue {
+    /// Probe records on their way from the probe loops
+    Probe,
+    /// Probe records on their way to the aggregator
+    Aggregator,
+    /// Probe records on their way to the probe sink
+    Sink,
+    /// Alerts on their way to correlation
+    Alert,
+}
+
+/// Highest value seen in the current export interval and in the one before
+#[derive(Debug, Default)]
+struct Peak {
+    current: AtomicU64,
+    previous: AtomicU64,
+}
+
+impl Peak {
+    fn observe(&self, value: u64) {
+        self.current.fetch_max(value, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.current
+            .load(Ordering::Relaxed)
+            .max(self.previous.load(Ordering::Relaxed))
+    }
+
+    fn roll(&self) {
+        self.previous
+            .store(self.current.swap(0, Ordering::Relaxed), Ordering::Relaxed);
+    }
+}
+
+/// Counters and gauges of the monitoring tasks
+#[derive(Debug, Default)]
+pub struct SelfMetrics {
+    probes_started: AtomicU64,
+    scheduling_lag_total_us: AtomicU64,
+    scheduling_lag_peak_us: Peak,
+    probe_queue: AtomicI64,
+    aggregator_queue: AtomicI64,
+    sink_queue: AtomicI64,
+    alert_queue: AtomicI64,
+    dropped_records: AtomicU64,
+    task_panics: AtomicU64,
+    records_aggregated: AtomicU64,
+    aggregator_latency_total_us: AtomicU64,
+    aggregator_latency_peak_us: Peak,
+    ring_buffer_bytes: AtomicU64,
+}
+
+/// Point-in-time copy of [`SelfMetrics`]
+///
+/// Counters count from the start of monitoring; peaks cover roughly the last
+/// metrics export interval.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SelfMetricsSnapshot {
+    /// Probes started
+    pub probes_started: u64,
+    /// Average time probes started after they were due, in milliseconds
+    pub scheduling_lag_avg_ms: f64,
+    /// Longest time a probe started after it was due, in milliseconds
+    pub scheduling_lag_max_ms: f64,
+    /// Probe records waiting to leave the probe loops
+    pub probe_queue: u64,
+    /// Probe records waiting for the aggregator
+    pub aggregator_queue: u64,
+    /// Probe records waiting for the probe sink
+    pub sink_queue: u64,
+    /// Alerts waiting for correlation
+    pub alert_queue: u64,
+    /// Probe records lost on the way to the probe sink or its write-ahead log
+    pub dropped_records: u64,
+    /// Monitoring tasks that panicked
+    pub task_panics: u64,
+    /// Probe records the aggregator processed
+    pub records_aggregated: u64,
+    /// Average time the aggregator took per record, in milliseconds
+    pub aggregator_latency_avg_ms: f64,
+    /// Longest time the aggregator took per record of one batch, in milliseconds
+    pub aggregator_latency_max_ms: f64,
+    /// Memory held by the aggregator's per-endpoint ring buffers, in bytes
+    pub ring_buffer_bytes: u64,
+}
+
+/// Duration in whole microseconds, saturating
+fn micros(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros()).unwrap_or(u64::MAX)
+}
+
+/// Average in milliseconds of `count` values summing to `total_us` microseconds
+#[allow(clippy::cast_precision_loss)] // microsecond totals stay far below 2^52
+fn average_ms(total_us: u64, count: u64) -> f64 {
+    if count == 0 {
+        0.0
+    } else {
+        total_us as f64 / count as f64 / 1000.0
+    }
+}
+
+#[allow(clippy::cast_precision_loss)] // microsecond peaks stay far below 2^52
+fn ms(us: u64) -> f64 {
+    us as f64 / 1000.0
+}
+
+impl SelfMetrics {
+    const fn queue(&self, queue: Queue) -> &AtomicI64 {
+        match queue {
+            Queue::Probe => &self.probe_queue,
+            Queue::Aggregator => &self.aggregator_queue,
+            Queue::Sink => &self.sink_queue,
+            Queue::Alert => &self.alert_queue,
+        }
+    }
+
+    /// A probe started `lag` after it was due
+    pub fn probe_started(&self, lag: Duration) {
+        self.probes_started.fetch_add(1, Ordering::Relaxed);
+        let lag = micros(lag);
+        self.scheduling_lag_total_us.fetch_add(lag, Ordering::Relaxed);
+        self.scheduling_lag_peak_us.observe(lag);
+    }
+
+    /// An item is about to be sent on `queue`
+    pub fn enqueued(&self, queue: Queue) {
+        self.queue(queue).fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// An item was received from `queue`, or its send failed
+    pub fn dequeued(&self, queue: Queue) {
+        self.queue(queue).fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// `count` probe records were lost
+    pub fn dropped(&self, count: usize) {
+        self.dropped_records
+            .fetch_add(u64::try_from(count).unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+
+    /// A monitoring task panicked
+    pub fn task_panicked(&self) {
+        self.task_panics.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The aggregator took `latency` to process one record
+    pub fn record_aggregated(&self, latency: Duration) {
+        self.batch_aggregated(1, latency);
+    }
+
+    /// The aggregator took `latency` to process a batch of `records` records
+    pub fn batch_aggregated(&self, records: usize, latency: Duration) {
+        let records = u64::try_from(records).unwrap_or(u64::MAX);
+        if records == 0 {
+            return;
+        }
+        self.records_aggregated.fetch_add(records, Ordering::Relaxed);
+        let latency = micros(latency);
+        self.aggregator_latency_total_us.fetch_add(latency, Ordering::Relaxed);
+        self.aggregator_latency_peak_us.observe(latency / records);
+    }
+
+    /// The ring buffers of one aggregator shard went from `previous` to `current` bytes
+    pub fn ring_buffer_bytes_changed(&self, previous: usize, current: usize) {
+        let bytes = |bytes: usize| u64::try_from(bytes).unwrap_or(u64::MAX);
+        self.ring_buffer_bytes.fetch_add(bytes(current), Ordering::Relaxed);
+        self.ring_buffer_bytes.fetch_sub(bytes(previous), Ordering::Relaxed);
+    }
+
+    /// Start a new peak interval; called on every metrics export
+    pub fn roll_peaks(&self) {
+        self.scheduling_lag_peak_us.roll();
+        self.aggregator_latency_peak_us.roll();
+    }
+
+    /// Current values
+    #[must_use]
+    pub fn snapshot(&self) -> SelfMetricsSnapshot {
+        let depth = |queue: Queue| u64::try_from(self.queue(queue).load(Ordering::Relaxed)).unwrap_or(0);
+        let probes_started = self.probes_started.load(Ordering::Relaxed);
+        let records_aggregated = self.records_aggregated.load(Ordering::Relaxed);
+        SelfMetricsSnapshot {
+            probes_started,
+            scheduling_lag_avg_ms: average_ms(
+                self.scheduling_lag_total_us.load(Ordering::Relaxed),
+                probes_started,
+            ),
+            scheduling_lag_max_ms: ms(self.scheduling_lag_peak_us.get()),
+            probe_queue: depth(Queue::Probe),
+            aggregator_queue: depth(Queue::Aggregator),
+            sink_queue: depth(Queue::Sink),
+            alert_queue: depth(Queue::Alert),
+            dropped_records: self.dropped_records.load(Ordering::Relaxed),
+            task_panics: self.task_panics.load(Ordering::Relaxed),
+            records_aggregated,
+            aggregator_latency_avg_ms: average_ms(
+                self.aggregator_latency_total_us.load(Ordering::Relaxed),
+                records_aggregated,
+            ),
+            aggregator_latency_max_ms: ms(self.aggregator_latency_peak_us.get()),
+            ring_buffer_bytes: self.ring_buffer_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Spawn `task`, counting it in `metrics` if it panics
+///
+/// Returns a handle to abort the task; an aborted task is not counted.
+pub fn spawn_watched<F>(metrics: &Arc<SelfMetrics>, name: String, task: F) -> AbortHandle
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let handle = tokio::spawn(task);
+    let abort = handle.abort_handle();
+    let metrics = Arc::clone(metrics);
+    tokio::spawn(async move {
+        if let Err(e) = handle.await {
+            if e.is_panic() {
+                metrics.task_panicked();
+                error!("Task {} panicked: {}", name, e);
+            }
+        }
+    });
+    abort
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_self_metrics() {
+        let metrics = Arc::new(SelfMetrics::default());
+        metrics.probe_started(Duration::from_millis(2));
+        metrics.probe_started(Duration::from_millis(6));
+        metrics.enqueued(Queue::Aggregator);
+        metrics.enqueued(Queue::Aggregator);
+        metrics.dequeued(Queue::Aggregator);
+        metrics.dequeued(Queue::Sink);
+        metrics.dropped(3);
+        metrics.record_aggregated(Duration::from_micros(500));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.probes_started, 2);
+        assert!((snapshot.scheduling_lag_avg_ms - 4.0).abs() < 1e-9);
+        assert!((snapshot.scheduling_lag_max_ms - 6.0).abs() < 1e-9);
+        assert_eq!((snapshot.aggregator_queue, snapshot.sink_queue), (1, 0));
+        assert_eq!(snapshot.dropped_records, 3);
+        assert!((snapshot.aggregator_latency_avg_ms - 0.5).abs() < 1e-9);
+
+        // Peaks last one more export interval, then give way to newer ones
+        metrics.roll_peaks();
+        assert!((metrics.snapshot().scheduling_lag_max_ms - 6.0).abs() < 1e-9);
+        metrics.probe_started(Duration::from_millis(1));
+        metrics.roll_peaks();
+        assert!((metrics.snapshot().scheduling_lag_max_ms - 1.0).abs() < 1e-9);
+
+        spawn_watched(&metrics, "doomed".to_string(), async { panic!("boom") });
+        for _ in 0..100 {
+            if metrics.snapshot().task_panics > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(metrics.snapshot().task_panics, 1);
+    }
+}
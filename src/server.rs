@@ -0,0 +1,1037 @@
+//! Monitoring HTTP API and embedded status page
+//!
+//! Serves a view of a running [`NetworkMonitoringSystem`]: an HTML
+//! status page at `/` for use as an internal dashboard, and the same data as
+//! JSON under `/api` for scripts and other tools. SVG badges of endpoint and
+//! provider scores under `/api/badges` can be embedded in wikis and READMEs.
+//! With a history store, `/api/history/rollup` returns saved runs in time
+//! buckets for charts. Endpoint, score, availability, alert and incident
+//! listings take a `namespace` parameter to return one namespace's data
+//! only, or with an empty value the data outside every namespace. `/api/self`
+//! reports the health of the monitor itself.
+//!
+//! `POST /api/endpoints` creates an endpoint, and `PATCH` and `DELETE` on
+//! `/api/endpoints/<id>` change, disable or delete one while monitoring
+//! runs; see [`crate::endpoint_store`] for how the changes are kept.
+//!
+//! With `[[api.tokens]]` configured, every request needs a bearer token.
+//! Read-only tokens may only make `GET` requests; admin tokens may also
+//! change endpoints and acknowledge alerts. `[api.tls]` serves the API
+//! over HTTPS. The gRPC control plane of [`crate::grpc`] takes the same tokens.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, patch, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::service::TowerToHyperService;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::alerting::Incident;
+use crate::badge::{badge_slug, region_badge_slug, Badge};
+use crate::endpoint_store::{EndpointChanges, EndpointSpec};
+use crate::error::{CloudPingError, Result};
+use crate::self_metrics::SelfMetricsSnapshot;
+use crate::history_store::{self, HistoryStore};
+use crate::i18n::Locale;
+use crate::models::{
+    alert_json_schema, Alert, AlertEnvelope, AvailabilityReport, ComprehensiveScoreResult, Endpoint,
+    Rollup, ScoreExplanation, ScorePoint, DEFAULT_ROLLUP_INTERVAL,
+};
+use crate::models::scoring::score_to_grade;
+use crate::monitoring::NetworkMonitoringSystem;
+use crate::report::StatusPage;
+use crate::time_utils::TimeUtils;
+
+/// Title shown on the status page when none is configured
+const DEFAULT_STATUS_TITLE: &str = "cloud-ping status";
+
+/// Shortest API token accepted, so tokens cannot be guessed
+const MIN_TOKEN_LENGTH: usize = 16;
+
+/// What an API token may do
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiScope {
+    /// `GET` requests: the status page, listings, badges and rollups
+    #[default]
+    Read,
+    /// Also adding and removing endpoints and acknowledging alerts
+    Admin,
+}
+
+/// Bearer token read from `[[api.tokens]]`
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ApiToken {
+    /// Name logged for requests made with the token
+    pub name: String,
+    /// Secret sent as `Authorization: Bearer <token>`
+    pub token: String,
+    /// What the token may do
+    #[serde(default)]
+    pub scope: ApiScope,
+}
+
+impl std::fmt::Debug for ApiToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiToken")
+            .field("name", &self.name)
+            .field("token", &"<redacted>")
+            .field("scope", &self.scope)
+            .finish()
+    }
+}
+
+/// Certificate the API is served with, read from `[api.tls]`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ApiTlsSettings {
+    /// PEM certificate chain, leaf first
+    pub cert: PathBuf,
+    /// PEM private key of the certificate
+    pub key: PathBuf,
+}
+
+/// Access to the HTTP API read from `[api]` in the application config
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ApiSettings {
+    /// Tokens the API accepts; without any, the API is open to everyone
+    /// who can reach it
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
+    /// Serve over HTTPS with this certificate
+    #[serde(default)]
+    pub tls: Option<ApiTlsSettings>,
+}
+
+impl ApiSettings {
+    /// # Errors
+    /// Returns a validation error for a short or repeated token
+    pub fn validate(&self) -> Result<()> {
+        for (i, token) in self.tokens.iter().enumerate() {
+            if token.token.len() < MIN_TOKEN_LENGTH {
+                return Err(CloudPingError::validation(
+                    "api.tokens.token",
+                    format!("token `{}` must be at least {MIN_TOKEN_LENGTH} characters", token.name),
+                ));
+            }
+            if self.tokens[..i].iter().any(|other| other.token == token.token) {
+                return Err(CloudPingError::validation(
+                    "api.tokens.token",
+                    format!("token `{}` is the same as an earlier one", token.name),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Health of a monitored endpoint as returned by `/api/endpoints`
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointHealth {
+    /// Endpoint configuration
+    #[serde(flatten)]
+    pub endpoint: Endpoint,
+    /// Latest score, once the endpoint has been probed
+    pub score: Option<ComprehensiveScoreResult>,
+}
+
+/// Namespace filter of endpoint, score, availability, alert and incident listings
+#[derive(Debug, Deserialize)]
+struct NamespaceQuery {
+    /// Namespace to list; everything when absent, and only what is in no
+    /// namespace when empty
+    namespace: Option<String>,
+}
+
+impl NamespaceQuery {
+    /// Whether an item of `namespace` is listed
+    fn matches(&self, namespace: Option<&String>) -> bool {
+        match self.namespace.as_deref() {
+            None => true,
+            Some("") => namespace.is_none(),
+            Some(wanted) => namespace.is_some_and(|namespace| namespace == wanted),
+        }
+    }
+
+    /// IDs of the monitored endpoints listed, or `None` when all of them are
+    async fn endpoint_ids(&self, monitoring: &NetworkMonitoringSystem) -> Option<Vec<String>> {
+        self.namespace.as_ref()?;
+        Some(
+            monitoring
+                .get_endpoints()
+                .await
+                .into_iter()
+                .filter(|endpoint| self.matches(endpoint.namespace.as_ref()))
+                .map(|endpoint| endpoint.id)
+                .collect(),
+        )
+    }
+}
+
+/// Query of `/api/history/rollup`
+#[derive(Debug, Deserialize)]
+struct RollupQuery {
+    /// Region name, URL or ID; every region when absent
+    region: Option<String>,
+    /// Start of the range, a day before `to` when absent
+    from: Option<DateTime<Utc>>,
+    /// End of the range, now when absent
+    to: Option<DateTime<Utc>>,
+    /// Bucket length such as `1m` or `1h`
+    #[serde(default, with = "humantime_serde")]
+    interval: Option<std::time::Duration>,
+}
+
+#[derive(Clone)]
+struct ServerState {
+    monitoring: Arc<NetworkMonitoringSystem>,
+    history: Option<Arc<dyn HistoryStore>>,
+    title: Arc<str>,
+    locale: Locale,
+    tokens: Arc<[ApiToken]>,
+}
+
+/// HTTP server exposing the monitoring system
+pub struct ApiServer {
+    state: ServerState,
+    tls: Option<Arc<rustls::ServerConfig>>,
+}
+
+impl ApiServer {
+    /// Create a server backed by a monitoring system
+    #[must_use]
+    pub fn new(monitoring: Arc<NetworkMonitoringSystem>) -> Self {
+        Self {
+            state: ServerState {
+                monitoring,
+                history: None,
+                title: Arc::from(DEFAULT_STATUS_TITLE),
+                locale: Locale::default(),
+                tokens: Arc::from([]),
+            },
+            tls: None,
+        }
+    }
+
+    /// Require the tokens of `settings` and serve with its certificate
+    ///
+    /// # Errors
+    /// Returns an error if the certificate or key cannot be loaded
+    pub fn access(mut self, settings: &ApiSettings) -> Result<Self> {
+        self.state.tokens = Arc::from(settings.tokens.as_slice());
+        self.tls = settings
+            .tls
+            .as_ref()
+            .map(|tls| crate::tls::server_config(&tls.cert, &tls.key).map(Arc::new))
+            .transpose()?;
+        Ok(self)
+    }
+
+    /// Set the status page title
+    #[must_use]
+    pub fn title(mut self, title: &str) -> Self {
+        self.state.title = Arc::from(title);
+        self
+    }
+
+    /// Serve rollups of the test history saved in `store`
+    #[must_use]
+    pub fn history(mut self, store: Arc<dyn HistoryStore>) -> Self {
+        self.state.history = Some(store);
+        self
+    }
+
+    /// Set the language and number format of the status page
+    #[must_use]
+    pub const fn locale(mut self, locale: Locale) -> Self {
+        self.state.locale = locale;
+        self
+    }
+
+    /// Routes served by the API
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/", get(status_page))
+            .route("/api/endpoints", get(endpoints).post(add_endpoint))
+            .route("/api/endpoints/*id", patch(update_endpoint).delete(delete_endpoint))
+            .route("/api/scores/history", get(score_history))
+            .route("/api/scores/:id/explain", get(explain_score))
+            .route("/api/availability", get(availability))
+            .route("/api/alerts", get(alerts))
+            .route("/api/alerts/envelopes", get(alert_envelopes))
+            .route("/api/alerts/schema", get(alert_schema))
+            .route("/api/alerts/:id/ack", post(acknowledge_alert))
+            .route("/api/incidents", get(incidents))
+            .route("/api/self", get(self_metrics))
+            .route("/api/history/rollup", get(history_rollup))
+            .route("/api/badges/endpoints/:id", get(endpoint_badge))
+            .route("/api/badges/providers/:provider", get(provider_badge))
+            .layer(middleware::from_fn_with_state(self.state.clone(), authorize))
+            .with_state(self.state.clone())
+    }
+
+    /// Listen on `addr` and serve until the process exits
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| CloudPingError::network(format!("Cannot listen on {addr}: {e}")))?;
+        let Some(tls) = self.tls.clone() else {
+            info!("Serving status page on http://{}", addr);
+            return axum::serve(listener, self.router())
+                .await
+                .map_err(|e| CloudPingError::system(format!("HTTP server failed: {e}")));
+        };
+
+        info!("Serving status page on https://{}", addr);
+        serve_tls(listener, self.router(), tls).await
+    }
+}
+
+/// Longest a client may take to finish the TLS handshake before it is dropped
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Serve `router` over TLS on every connection `listener` accepts
+async fn serve_tls(listener: tokio::net::TcpListener, router: Router, tls: Arc<rustls::ServerConfig>) -> Result<()> {
+    let acceptor = tokio_rustls::TlsAcceptor::from(tls);
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                // Out of file descriptors and the like; give it a moment
+                warn!("Failed to accept connection: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let service = TowerToHyperService::new(router.clone());
+        tokio::spawn(async move {
+            // A client that never finishes the handshake would hold the connection open
+            let stream = match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    debug!("TLS handshake with {} failed: {}", peer, e);
+                    return;
+                }
+                Err(_) => {
+                    debug!("TLS handshake with {} timed out", peer);
+                    return;
+                }
+            };
+            let served = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+            if let Err(e) = served {
+                debug!("Connection from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+/// Reject requests without a token allowed to make them, once tokens are configured
+async fn authorize(State(state): State<ServerState>, request: Request, next: Next) -> Response {
+    if state.tokens.is_empty() {
+        return next.run(request).await;
+    }
+    let required = if matches!(*request.method(), Method::GET | Method::HEAD) {
+        ApiScope::Read
+    } else {
+        ApiScope::Admin
+    };
+    let presented = bearer_token(&request);
+    let Some(token) = presented.and_then(|presented| find_token(&state.tokens, &presented)) else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            "missing or unknown API token",
+        )
+            .into_response();
+    };
+    if token.scope < required {
+        return (
+            StatusCode::FORBIDDEN,
+            format!("token `{}` is read-only", token.name),
+        )
+            .into_response();
+    }
+    debug!("{} {} with token {}", request.method(), request.uri().path(), token.name);
+    next.run(request).await
+}
+
+/// Token of the `Authorization: Bearer` header
+///
+/// Tokens are never taken from the URL, where they would end up in logs,
+/// browser history and `Referer` headers.
+fn bearer_token(request: &Request) -> Option<String> {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+}
+
+/// Configured token matching the `presented` secret
+pub(crate) fn find_token<'a>(tokens: &'a [ApiToken], presented: &str) -> Option<&'a ApiToken> {
+    tokens.iter().find(|token| tokens_match(&token.token, presented))
+}
+
+/// Compare tokens in a time that does not depend on where they differ
+fn tokens_match(expected: &str, presented: &str) -> bool {
+    expected.len() == presented.len()
+        && expected
+            .bytes()
+            .zip(presented.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn status_page(State(state): State<ServerState>) -> impl IntoResponse {
+    let monitoring = &state.monitoring;
+    let page = StatusPage::new(state.title.as_ref())
+        .locale(state.locale)
+        .endpoints(
+            monitoring
+                .get_endpoints()
+                .await
+                .into_iter()
+                .filter(|endpoint| endpoint.enabled)
+                .collect(),
+            monitoring.get_endpoint_scores(),
+        )
+        .availability(monitoring.get_availability_report().await)
+        .alerts(monitoring.get_recent_alerts().await);
+
+    Html(page.render())
+}
+
+async fn endpoints(
+    State(state): State<ServerState>,
+    Query(query): Query<NamespaceQuery>,
+) -> Json<Vec<EndpointHealth>> {
+    let mut scores = state.monitoring.get_endpoint_scores();
+    let mut health: Vec<EndpointHealth> = state
+        .monitoring
+        .get_endpoints()
+        .await
+        .into_iter()
+        .filter(|endpoint| query.matches(endpoint.namespace.as_ref()))
+        .map(|endpoint| EndpointHealth {
+            score: scores.remove(&endpoint.id),
+            endpoint,
+        })
+        .collect();
+    health.sort_by(|a, b| a.endpoint.id.cmp(&b.endpoint.id));
+
+    Json(health)
+}
+
+async fn score_history(
+    State(state): State<ServerState>,
+    Query(query): Query<NamespaceQuery>,
+) -> Json<HashMap<String, Vec<ScorePoint>>> {
+    let mut history = state.monitoring.get_score_history();
+    if let Some(listed) = query.endpoint_ids(&state.monitoring).await {
+        history.retain(|id, _| listed.contains(id));
+    }
+    Json(history)
+}
+
+/// How the latest score of an endpoint was reached
+async fn explain_score(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<ScoreExplanation>, StatusCode> {
+    state
+        .monitoring
+        .snapshot()
+        .scores
+        .get(&id)
+        .map(|score| Json(score.explain()))
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn availability(
+    State(state): State<ServerState>,
+    Query(query): Query<NamespaceQuery>,
+) -> Json<AvailabilityReport> {
+    let mut report = state.monitoring.get_availability_report().await;
+    if let Some(listed) = query.endpoint_ids(&state.monitoring).await {
+        report.endpoints.retain(|endpoint| listed.contains(&endpoint.endpoint_id));
+    }
+    Json(report)
+}
+
+async fn alerts(State(state): State<ServerState>, Query(query): Query<NamespaceQuery>) -> Json<Vec<Alert>> {
+    let mut alerts = state.monitoring.get_recent_alerts().await;
+    alerts.retain(|alert| query.matches(alert.namespace.as_ref()));
+    Json(alerts)
+}
+
+async fn alert_envelopes(
+    State(state): State<ServerState>,
+    Query(query): Query<NamespaceQuery>,
+) -> Json<Vec<AlertEnvelope>> {
+    let mut envelopes = state.monitoring.get_recent_alert_envelopes().await;
+    envelopes.retain(|envelope| query.matches(envelope.namespace.as_ref()));
+    Json(envelopes)
+}
+
+async fn incidents(State(state): State<ServerState>, Query(query): Query<NamespaceQuery>) -> Json<Vec<Incident>> {
+    let mut incidents = state.monitoring.get_recent_incidents().await;
+    incidents.retain(|incident| query.matches(incident.namespace.as_ref()));
+    Json(incidents)
+}
+
+/// Health of the monitor itself
+async fn self_metrics(State(state): State<ServerState>) -> Json<SelfMetricsSnapshot> {
+    Json(state.monitoring.self_metrics())
+}
+
+async fn alert_schema() -> Json<serde_json::Value> {
+    Json(alert_json_schema())
+}
+
+/// Saved runs of the requested regions and range in time buckets
+async fn history_rollup(
+    State(state): State<ServerState>,
+    Query(query): Query<RollupQuery>,
+) -> std::result::Result<Json<Vec<Rollup>>, (StatusCode, String)> {
+    let Some(store) = &state.history else {
+        return Err((StatusCode::NOT_FOUND, "no history_file is configured".to_string()));
+    };
+    let to = query.to.unwrap_or_else(TimeUtils::now);
+    let from = query.from.unwrap_or(to - chrono::Duration::days(1));
+    let interval = query.interval.unwrap_or(DEFAULT_ROLLUP_INTERVAL);
+    history_store::rollup(store.as_ref(), query.region.as_deref(), interval, from, to)
+        .await
+        .map(Json)
+        .map_err(|e| match e {
+            CloudPingError::Validation { .. } => (StatusCode::BAD_REQUEST, e.to_string()),
+            e => {
+                warn!("History rollup failed: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            }
+        })
+}
+
+/// Start monitoring the endpoint of the request body
+async fn add_endpoint(
+    State(state): State<ServerState>,
+    Json(spec): Json<EndpointSpec>,
+) -> std::result::Result<(StatusCode, Json<Endpoint>), (StatusCode, String)> {
+    match state.monitoring.create_endpoint(&spec).await {
+        Ok(Some(endpoint)) => Ok((StatusCode::CREATED, Json(endpoint))),
+        Ok(None) => Err((StatusCode::CONFLICT, "an endpoint of this ID is already monitored".to_string())),
+        Err(e) => Err(endpoint_error(&e)),
+    }
+}
+
+/// Change an endpoint's URL or name, or disable or enable it; its ID may contain slashes
+async fn update_endpoint(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+    Json(changes): Json<EndpointChanges>,
+) -> std::result::Result<Json<Endpoint>, (StatusCode, String)> {
+    match state.monitoring.update_endpoint(&id, &changes).await {
+        Ok(Some(endpoint)) => Ok(Json(endpoint)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, format!("no endpoint `{id}`"))),
+        Err(e) => Err(endpoint_error(&e)),
+    }
+}
+
+/// Stop monitoring an endpoint for good; its ID may contain slashes
+async fn delete_endpoint(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+) -> std::result::Result<StatusCode, (StatusCode, String)> {
+    match state.monitoring.delete_endpoint(&id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err((StatusCode::NOT_FOUND, format!("no endpoint `{id}`"))),
+        Err(e) => Err(endpoint_error(&e)),
+    }
+}
+
+/// Status of a failed endpoint change: the request's fault for a bad URL,
+/// the server's when the endpoint store cannot be written
+fn endpoint_error(error: &CloudPingError) -> (StatusCode, String) {
+    let status = match error {
+        CloudPingError::InvalidUrl { .. } | CloudPingError::Validation { .. } => StatusCode::BAD_REQUEST,
+        _ => {
+            warn!("Failed to change endpoints: {}", error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+    (status, error.to_string())
+}
+
+async fn acknowledge_alert(State(state): State<ServerState>, Path(id): Path<String>) -> StatusCode {
+    if state.monitoring.acknowledge_alert(&id).await {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+/// Badge of one endpoint by ID or by the slug of its name, with or without
+/// its provider; `.svg` is optional
+async fn endpoint_badge(State(state): State<ServerState>, Path(id): Path<String>) -> Response {
+    let id = id.strip_suffix(".svg").unwrap_or(&id);
+    let endpoints = state.monitoring.get_endpoints().await;
+    let Some(endpoint) = endpoints.iter().find(|endpoint| endpoint.id == id).or_else(|| {
+        endpoints.iter().find(|endpoint| {
+            endpoint.metadata.get("name").is_some_and(|name| {
+                let provider = endpoint.metadata.get("provider").map_or("", String::as_str);
+                badge_slug(name) == id || region_badge_slug(provider, name) == id
+            })
+        })
+    }) else {
+        return svg_response(StatusCode::NOT_FOUND, &Badge::unknown(id));
+    };
+    let label = endpoint.metadata.get("name").unwrap_or(&endpoint.id);
+    let badge = state
+        .monitoring
+        .snapshot()
+        .scores
+        .get(&endpoint.id)
+        .map_or_else(
+            || Badge::pending(label),
+            |score| Badge::score(label, score.score, score.grade),
+        );
+    svg_response(StatusCode::OK, &badge)
+}
+
+/// Badge of the average score of a provider's endpoints, matched by `provider` metadata
+async fn provider_badge(
+    State(state): State<ServerState>,
+    Path(provider): Path<String>,
+) -> Response {
+    let provider = provider.strip_suffix(".svg").unwrap_or(&provider);
+    let endpoints: Vec<String> = state
+        .monitoring
+        .get_endpoints()
+        .await
+        .into_iter()
+        .filter(|endpoint| {
+            endpoint
+                .metadata
+                .get("provider")
+                .is_some_and(|p| p.eq_ignore_ascii_case(provider))
+        })
+        .map(|endpoint| endpoint.id)
+        .collect();
+    if endpoints.is_empty() {
+        return svg_response(StatusCode::NOT_FOUND, &Badge::unknown(provider));
+    }
+
+    let snapshot = state.monitoring.snapshot();
+    let provider_scores: Vec<f64> = endpoints
+        .iter()
+        .filter_map(|id| snapshot.scores.get(id).map(|score| score.score))
+        .collect();
+    let badge = if provider_scores.is_empty() {
+        Badge::pending(provider)
+    } else {
+        let average = statistical::mean(&provider_scores);
+        Badge::score(provider, average, score_to_grade(average))
+    };
+    svg_response(StatusCode::OK, &badge)
+}
+
+/// Badge as an uncached SVG response, so embedded badges stay current
+fn svg_response(status: StatusCode, badge: &Badge) -> Response {
+    (
+        status,
+        [
+            (header::CONTENT_TYPE, "image/svg+xml"),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        badge.render(),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProbeType;
+    use crate::monitoring::create_default_monitoring_system;
+
+    async fn spawn_server(monitoring: Arc<NetworkMonitoringSystem>) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = ApiServer::new(monitoring)
+            .title("Internal <status>")
+            .router();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_status_page_served() {
+        let monitoring = Arc::new(create_default_monitoring_system());
+        let mut endpoint = Endpoint::new(
+            "eu-1".to_string(),
+            "example.com".to_string(),
+            443,
+            ProbeType::HTTP,
+        );
+        endpoint
+            .metadata
+            .insert("name".to_string(), "Frankfurt".to_string());
+        monitoring.add_endpoint(endpoint).await;
+
+        let addr = spawn_server(monitoring).await;
+        let response = reqwest::get(format!("http://{addr}/")).await.unwrap();
+        assert!(response.status().is_success());
+
+        let html = response.text().await.unwrap();
+        assert!(html.contains("<h1>Internal &lt;status&gt;</h1>"));
+        assert!(html.contains("Frankfurt"));
+        assert!(html.contains("Pending"));
+        assert!(html.contains("No alerts."));
+    }
+
+    #[tokio::test]
+    async fn test_json_api() {
+        let monitoring = Arc::new(create_default_monitoring_system());
+        monitoring
+            .add_endpoint(Endpoint::new(
+                "dns".to_string(),
+                "1.1.1.1".to_string(),
+                53,
+                ProbeType::TCP,
+            ))
+            .await;
+
+        let addr = spawn_server(monitoring).await;
+        let endpoints: serde_json::Value = reqwest::get(format!("http://{addr}/api/endpoints"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(endpoints[0]["id"], "dns");
+        assert!(endpoints[0]["score"].is_null());
+
+        let availability: AvailabilityReport =
+            reqwest::get(format!("http://{addr}/api/availability"))
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+        assert!(availability.endpoints.is_empty());
+
+        let schema: serde_json::Value = reqwest::get(format!("http://{addr}/api/alerts/schema"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(schema, alert_json_schema());
+
+        let unscored = reqwest::get(format!("http://{addr}/api/scores/dns/explain"))
+            .await
+            .unwrap();
+        assert_eq!(unscored.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let incidents: Vec<Incident> = reqwest::get(format!("http://{addr}/api/incidents"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(incidents.is_empty());
+
+        let health: SelfMetricsSnapshot = reqwest::get(format!("http://{addr}/api/self"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(health.task_panics, 0);
+    }
+
+    #[tokio::test]
+    async fn test_namespace_filter() {
+        let monitoring = Arc::new(create_default_monitoring_system());
+        for namespace in [None, Some("acme"), Some("globex")] {
+            let endpoint = Endpoint::new("dns".to_string(), "1.1.1.1".to_string(), 53, ProbeType::TCP);
+            monitoring
+                .add_endpoint(namespace.map_or(endpoint.clone(), |namespace| endpoint.in_namespace(namespace)))
+                .await;
+        }
+
+        let addr = spawn_server(monitoring).await;
+        let endpoints: serde_json::Value = reqwest::get(format!("http://{addr}/api/endpoints?namespace=acme"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(endpoints.as_array().unwrap().len(), 1);
+        assert_eq!(endpoints[0]["id"], "acme/dns");
+        assert_eq!(endpoints[0]["namespace"], "acme");
+
+        let alerts: Vec<Alert> = reqwest::get(format!("http://{addr}/api/alerts?namespace=acme"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(alerts.is_empty());
+
+        let endpoints: serde_json::Value = reqwest::get(format!("http://{addr}/api/endpoints?namespace="))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(endpoints.as_array().unwrap().len(), 1);
+        assert_eq!(endpoints[0]["id"], "dns");
+
+        let availability: AvailabilityReport = reqwest::get(format!("http://{addr}/api/availability?namespace=acme"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(availability
+            .endpoints
+            .iter()
+            .all(|endpoint| endpoint.endpoint_id == "acme/dns"));
+    }
+
+    #[tokio::test]
+    async fn test_badges_served() {
+        let monitoring = Arc::new(create_default_monitoring_system());
+        let mut endpoint = Endpoint::new(
+            "eu-1".to_string(),
+            "example.com".to_string(),
+            443,
+            ProbeType::HTTP,
+        );
+        endpoint
+            .metadata
+            .insert("provider".to_string(), "AWS".to_string());
+        endpoint
+            .metadata
+            .insert("name".to_string(), "São Paulo".to_string());
+        monitoring.add_endpoint(endpoint).await;
+
+        let addr = spawn_server(monitoring).await;
+        for path in ["eu-1.svg", "são-paulo", "aws-são-paulo.svg"] {
+            let response = reqwest::get(format!("http://{addr}/api/badges/endpoints/{path}"))
+                .await
+                .unwrap();
+            assert_eq!(response.headers()["content-type"], "image/svg+xml");
+            assert!(response
+                .text()
+                .await
+                .unwrap()
+                .contains("<title>São Paulo: pending</title>"));
+        }
+
+        let provider = reqwest::get(format!("http://{addr}/api/badges/providers/aws"))
+            .await
+            .unwrap();
+        assert!(provider.status().is_success());
+
+        let missing = reqwest::get(format!("http://{addr}/api/badges/endpoints/gone"))
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+        assert!(missing.text().await.unwrap().contains("gone: unknown"));
+    }
+
+    #[tokio::test]
+    async fn test_history_rollup() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(history_store::JsonHistoryStore::new(dir.path().join("history.json")));
+        let mut history = crate::models::TestHistory::new(
+            "fra".to_string(),
+            "Frankfurt".to_string(),
+            "https://fra.example.com".to_string(),
+        );
+        let mut stats = crate::models::PingStats::new(10);
+        stats.test_time = DateTime::parse_from_rfc3339("2026-03-01T12:34:00Z").unwrap().with_timezone(&Utc);
+        stats.successful_pings = 10;
+        stats.avg = 20.0;
+        history.add_test_result(stats);
+        store.save_stats(&[history]).await.unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = ApiServer::new(Arc::new(create_default_monitoring_system()))
+            .history(store)
+            .router();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let url = format!(
+            "http://{addr}/api/history/rollup?region=frankfurt&from=2026-03-01T00:00:00Z&to=2026-03-02T00:00:00Z&interval=1h"
+        );
+        let rollups: Vec<Rollup> = reqwest::get(url).await.unwrap().json().await.unwrap();
+        assert_eq!(rollups.len(), 1);
+        assert_eq!(rollups[0].buckets[0].start.to_rfc3339(), "2026-03-01T12:00:00+00:00");
+        assert_eq!(rollups[0].buckets[0].avg_ms, Some(20.0));
+
+        let invalid = reqwest::get(format!("http://{addr}/api/history/rollup?interval=0s"))
+            .await
+            .unwrap();
+        assert_eq!(invalid.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        // Without a history store there is nothing to roll up
+        let addr = spawn_server(Arc::new(create_default_monitoring_system())).await;
+        let missing = reqwest::get(format!("http://{addr}/api/history/rollup")).await.unwrap();
+        assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_api_tokens() {
+        let settings: ApiSettings = toml::from_str(
+            r#"
+            [[tokens]]
+            name = "dashboard"
+            token = "read-only-secret-0123"
+
+            [[tokens]]
+            name = "ops"
+            token = "admin-secret-0123456"
+            scope = "admin"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(settings.tokens[0].scope, ApiScope::Read);
+        assert!(settings.validate().is_ok());
+        assert!(!format!("{settings:?}").contains("admin-secret"));
+
+        let monitoring = Arc::new(create_default_monitoring_system());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = ApiServer::new(monitoring.clone()).access(&settings).unwrap().router();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = reqwest::Client::new();
+        let endpoints = format!("http://{addr}/api/endpoints");
+        let anonymous = client.get(&endpoints).send().await.unwrap();
+        assert_eq!(anonymous.status(), reqwest::StatusCode::UNAUTHORIZED);
+        let wrong = client.get(&endpoints).bearer_auth("read-only-secret-0124").send().await.unwrap();
+        assert_eq!(wrong.status(), reqwest::StatusCode::UNAUTHORIZED);
+        let read = client.get(&endpoints).bearer_auth("read-only-secret-0123").send().await.unwrap();
+        assert!(read.status().is_success());
+        let query = client
+            .get(format!("{endpoints}?access_token=read-only-secret-0123"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(query.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let body = serde_json::json!({ "url": "https://api.example.com" });
+        let forbidden = client
+            .post(&endpoints)
+            .bearer_auth("read-only-secret-0123")
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(forbidden.status(), reqwest::StatusCode::FORBIDDEN);
+        let created = client
+            .post(&endpoints)
+            .bearer_auth("admin-secret-0123456")
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(created.status(), reqwest::StatusCode::CREATED);
+        assert_eq!(monitoring.get_endpoints().await.len(), 1);
+        let disabled: Endpoint = client
+            .patch(format!("{endpoints}/https://api.example.com"))
+            .bearer_auth("admin-secret-0123456")
+            .json(&serde_json::json!({ "enabled": false }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(!disabled.enabled);
+
+        let removed = client
+            .delete(format!("{endpoints}/https://api.example.com"))
+            .bearer_auth("admin-secret-0123456")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(removed.status(), reqwest::StatusCode::NO_CONTENT);
+        assert!(monitoring.get_endpoints().await.is_empty());
+        let ack = client
+            .post(format!("http://{addr}/api/alerts/unknown/ack"))
+            .bearer_auth("admin-secret-0123456")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(ack.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let short = ApiSettings {
+            tokens: vec![ApiToken { token: "short".to_string(), ..settings.tokens[0].clone() }],
+            tls: None,
+        };
+        assert!(short.validate().is_err());
+        let missing_cert = ApiSettings {
+            tls: Some(ApiTlsSettings { cert: "missing.pem".into(), key: "missing.pem".into() }),
+            ..settings
+        };
+        assert!(ApiServer::new(monitoring).access(&missing_cert).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_serves_https() {
+        let rcgen::CertifiedKey { cert, key_pair } =
+            rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let settings = ApiSettings {
+            tokens: Vec::new(),
+            tls: Some(ApiTlsSettings {
+                cert: dir.path().join("cert.pem"),
+                key: dir.path().join("key.pem"),
+            }),
+        };
+        std::fs::write(dir.path().join("cert.pem"), cert.pem()).unwrap();
+        std::fs::write(dir.path().join("key.pem"), key_pair.serialize_pem()).unwrap();
+
+        let server = ApiServer::new(Arc::new(create_default_monitoring_system()))
+            .access(&settings)
+            .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_tls(listener, server.router(), server.tls.clone().unwrap()));
+
+        let client = reqwest::Client::builder()
+            .add_root_certificate(reqwest::Certificate::from_pem(cert.pem().as_bytes()).unwrap())
+            .resolve("localhost", addr)
+            .build()
+            .unwrap();
+        let response = client
+            .get(format!("https://localhost:{}/api/endpoints", addr.port()))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        let endpoints: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(endpoints, serde_json::json!([]));
+
+        // Plain HTTP gets no answer from the TLS listener
+        assert!(client.get(format!("http://{addr}/api/endpoints")).send().await.is_err());
+    }
+}
@@ -0,0 +1,625 @@
+//! First-run setup
+//!
+//! Writes a `config.toml` in which every setting carries a comment, and
+//! offers an interactive wizard for the settings most users change first.
+//! Optional settings without a value are written as commented-out examples.
+
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::Duration;
+
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
+
+use crate::config::{AppConfig, OutputFormat};
+use crate::error::{CloudPingError, Result};
+
+/// Comment and, for optional settings, an example value of one config key
+struct KeyDoc {
+    /// Dotted path of the key (`monitoring.probe.interval`)
+    path: &'static str,
+    comment: &'static str,
+    /// Written commented out when the key has no value
+    example: Option<&'static str>,
+}
+
+const fn doc(path: &'static str, comment: &'static str) -> KeyDoc {
+    KeyDoc {
+        path,
+        comment,
+        example: None,
+    }
+}
+
+const fn example(path: &'static str, comment: &'static str, example: &'static str) -> KeyDoc {
+    KeyDoc {
+        path,
+        comment,
+        example: Some(example),
+    }
+}
+
+const KEY_DOCS: &[KeyDoc] = &[
+    doc("default_ping_count", "Pings per region for `benchmark`"),
+    doc("quick_ping_count", "Pings per region for `quick`"),
+    doc("timeout_ms", "Request timeout in milliseconds"),
+    doc("timeout", "Request timeout as a duration (e.g. \"5s\", \"500ms\")"),
+    doc("max_threads", "Regions tested at once"),
+    doc("enable_color_output", "Colored terminal output"),
+    doc("save_results_to_file", "Save results to `results_filename` after each run"),
+    doc("results_filename", "File results are saved to"),
+    doc("data_file", "JSON file listing providers and regions"),
+    doc("show_progress", "Progress bars while testing"),
+    doc("retry_attempts", "Retries of a failed request"),
+    doc("retry_delay_ms", "Delay between retries in milliseconds"),
+    doc("retry_delay", "Delay between retries as a duration"),
+    doc("verbose", "Detailed logging"),
+    doc("output_format", "Output format: table, json or csv"),
+    doc("user_agent", "HTTP User-Agent header"),
+    doc(
+        "validate_certificates",
+        "Validate TLS certificates, timing each validation into result metadata",
+    ),
+    doc("tls", "TLS settings of the HTTP client"),
+    example("tls.min_version", "Lowest TLS version: \"1.2\" or \"1.3\"", "\"1.3\""),
+    doc("tls.alpn", "Protocols offered through ALPN: \"http/1.1\" and \"h2\""),
+    example("tls.client_cert", "PEM client certificate for mutual TLS", "\"client.pem\""),
+    example("tls.client_key", "PEM key of the client certificate", "\"client.key\""),
+    example(
+        "tls.sni_override",
+        "Server name sent instead of each URL's host",
+        "\"api.example.com\"",
+    ),
+    doc("agent_id", "Agent identifier in pushed results (host name when empty)"),
+    doc("agent_location", "Agent location in pushed results"),
+    example(
+        "client_coordinates",
+        "Your location, used to flag physically impossible measurements",
+        "{ latitude = 52.52, longitude = 13.40 }",
+    ),
+    doc("score_low_quality", "Score measurements flagged as low quality"),
+    example(
+        "isp_first_hop",
+        "ISP first hop to time; discovered with trace_isp_first_hop when unset",
+        "\"192.0.2.1\"",
+    ),
+    doc("trace_isp_first_hop", "Trace the route before each run to find the ISP first hop"),
+    example(
+        "availability_ledger",
+        "File recording region up/down transitions across runs",
+        "\"availability.json\"",
+    ),
+    doc("environment_lookup", "Look up the public IP and ASN of each run"),
+    example(
+        "stun_servers",
+        "STUN servers asked for the NAT type of each run; two tell cone from symmetric NAT",
+        "[\"stun.l.google.com:19302\", \"stun.cloudflare.com:3478\"]",
+    ),
+    doc("jitter_algorithm", "Jitter algorithm: consecutive_diff, rfc3550 or std_dev"),
+    example(
+        "history_file",
+        "History kept across runs: SQLite or .json file, sled directory or postgres:// URL",
+        "\"history.db\"",
+    ),
+    example(
+        "history_retention",
+        "Fold runs older than `raw` into hourly aggregates, older than `hourly` into daily ones",
+        "{ raw = \"7d\", hourly = \"90d\", daily = \"2y\" }",
+    ),
+    example(
+        "history_backend",
+        "History store: json, sqlite, postgres or sled; inferred from history_file when unset",
+        "\"sqlite\"",
+    ),
+    example(
+        "history_wal_file",
+        "Write-ahead log buffering runs while the history store is unavailable",
+        "\"history-wal.log\"",
+    ),
+    example(
+        "notes_file",
+        "TOML notes per region name or URL, shown in reports",
+        "\"notes.toml\"",
+    ),
+    example(
+        "geoip_file",
+        "GeoIP city database locating regions without coordinates (geoip feature)",
+        "\"GeoLite2-City.mmdb\"",
+    ),
+    example(
+        "pricing_file",
+        "TOML egress price and price index per region name or URL",
+        "\"pricing.toml\"",
+    ),
+    example(
+        "pricing_url",
+        "JSON prices per region, fetched when regions are loaded",
+        "\"https://prices.example.com/regions.json\"",
+    ),
+    example(
+        "carbon_file",
+        "TOML carbon intensity in gCO2/kWh per region name or URL",
+        "\"carbon.toml\"",
+    ),
+    example(
+        "carbon_url",
+        "JSON carbon intensity per region, fetched when regions are loaded",
+        "\"https://carbon.example.com/regions.json\"",
+    ),
+    doc(
+        "speed_test_bytes",
+        "Bytes downloaded per region speed test, half as many uploaded; 0 skips them",
+    ),
+    doc(
+        "max_requests_per_host_per_second",
+        "Requests per second to a single host, 0 disables the limit",
+    ),
+    doc("community_sharing", "Upload anonymised summaries after each benchmark"),
+    example(
+        "community_endpoint",
+        "Community dataset service for sharing and comparisons",
+        "\"https://community.example.com/api\"",
+    ),
+    doc("gaming_tick_rate_hz", "Server tick rate for `benchmark --gaming`"),
+    example(
+        "run_deadline",
+        "Stop a benchmark after this long and keep partial results",
+        "\"10m\"",
+    ),
+    doc("deadlines", "Time limits per request attempt, per ping and per region"),
+    example("deadlines.attempt", "Time one request may take; `timeout_ms` when unset", "\"2s\""),
+    example("deadlines.ping", "Time one ping may take with its retries", "\"5s\""),
+    example("deadlines.region", "Time the pings to one region may take together", "\"1m\""),
+    doc("priority_scaling", "Scale pings and retries by region priority"),
+    doc("max_connections", "Connections open at once across benchmark and monitoring"),
+    example(
+        "weights",
+        "Scoring weights; built-in weights when unset",
+        "{ latency = 0.3, jitter = 0.2, packet_loss = 0.25, consistency = 0.15, availability = 0.1 }",
+    ),
+    doc(
+        "sort_by",
+        "Ranking order: score, latency, p95, loss, gaming, provider or value",
+    ),
+    doc("report_filter", "Regions shown in rankings, in every output format"),
+    example("report_filter.min_grade", "Only regions graded this or better", "\"B\""),
+    example(
+        "report_filter.providers",
+        "Only these providers; all when empty",
+        "[\"AWS\", \"GCP\"]",
+    ),
+    example(
+        "report_filter.countries",
+        "Only these countries; all when empty",
+        "[\"Germany\", \"France\"]",
+    ),
+    example("report_filter.per_provider", "At most this many regions per provider", "3"),
+    example("report_filter.limit", "At most this many regions in total", "10"),
+    doc("footprint", "Redundancy of the regions picked with benchmark --footprint"),
+    example("footprint.count", "Regions in the footprint", "3"),
+    example("footprint.min_providers", "Distinct providers the regions must span", "2"),
+    example(
+        "footprint.min_continents",
+        "Distinct continents the regions must span, by country code",
+        "2",
+    ),
+    example("footprint.min_grade", "Grade every region must reach", "\"B\""),
+    doc("green", "Threshold of the greenest acceptable region recommendation"),
+    example("green.min_score", "Lowest score of a recommended region", "70.0"),
+    example("green.max_latency_ms", "Highest latency of a recommended region", "80.0"),
+    doc("asn", "Resolve endpoints to their autonomous system (ASN)"),
+    example(
+        "asn.mmdb_file",
+        "MaxMind DB mapping addresses to ASNs, e.g. GeoLite2-ASN",
+        "\"GeoLite2-ASN.mmdb\"",
+    ),
+    doc("asn.cymru", "Look up addresses missing from the file through Team Cymru DNS"),
+    example("asn.dns_server", "DNS server for Team Cymru lookups", "\"1.1.1.1:53\""),
+    doc("route_tracing", "Traceroute regions to detect route changes between runs"),
+    doc("status_feeds", "Provider status feeds polled for outage correlation"),
+    doc("probe_budget", "Caps on requests per run and per provider per hour"),
+    example(
+        "probe_budget.max_requests_per_run",
+        "Refuse runs that would send more requests",
+        "2000",
+    ),
+    example(
+        "probe_budget.max_requests_per_provider_per_hour",
+        "Rolling hourly cap per provider",
+        "5000",
+    ),
+    doc(
+        "adaptive_concurrency",
+        "Lower concurrency when a reference endpoint shows local saturation",
+    ),
+    example(
+        "adaptive_concurrency.reference_url",
+        "Known-fast endpoint watched during runs; off when unset",
+        "\"https://www.cloudflare.com/cdn-cgi/trace\"",
+    ),
+    doc("adaptive_concurrency.interval", "Time between reference pings"),
+    doc(
+        "adaptive_concurrency.degradation_ratio",
+        "Dial down when the reference is this many times slower than idle",
+    ),
+    doc(
+        "adaptive_concurrency.min_increase_ms",
+        "and at least this many milliseconds slower",
+    ),
+    doc("adaptive_concurrency.min_threads", "Never test fewer regions at once"),
+    doc("control", "Control endpoint used to normalize region latencies"),
+    example(
+        "control.url",
+        "Endpoint pinged throughout each run; off when unset",
+        "\"https://www.cloudflare.com/cdn-cgi/trace\"",
+    ),
+    doc("control.interval", "Time between control pings"),
+    doc("labels", "Labels saved with every result, e.g. location = \"office\""),
+    doc("locale", "Language of HTML reports and the status page: en, de, fr or es"),
+    doc("ascii_only", "Plain ASCII terminal output, without emoji or box drawing"),
+    example(
+        "table_drop_order",
+        "Columns dropped first when a table is wider than the terminal",
+        "[\"Streaming\", \"Gaming\"]",
+    ),
+    doc("trace_sampling", "Which finished probes and requests log their trace span"),
+    doc("trace_sampling.sample_rate", "Fraction that log when they finish, from 0 to 1"),
+    doc("trace_sampling.slow_threshold", "Probes and requests this slow always log"),
+    doc(
+        "recipes",
+        "Application recipes checked with `recipe`, e.g. [recipes.voip]; replace built-in ones of the same name",
+    ),
+    doc("monitoring", "Settings of `monitor` and `agent`"),
+    doc("monitoring.metrics_export_interval", "Time between metrics exports"),
+    doc(
+        "monitoring.snapshot_interval",
+        "Time between the score snapshots the APIs and the metrics exporter read",
+    ),
+    example(
+        "monitoring.websocket_endpoints",
+        "WebSocket URLs probed next to the regions",
+        "[\"wss://stream.example.com/socket\"]",
+    ),
+    example(
+        "monitoring.banner_endpoints",
+        "SMTP, IMAP, Redis or PostgreSQL servers probed for their greeting",
+        "[\"redis://cache.example.com\", \"postgres://db.example.com:5432\"]",
+    ),
+    example(
+        "monitoring.ntp_servers",
+        "NTP servers queried for the local clock's offset",
+        "[\"pool.ntp.org\", \"time.cloudflare.com\"]",
+    ),
+    example(
+        "monitoring.status_poll_interval",
+        "How often `status_feeds` are polled to name matching provider incidents in alerts",
+        "\"5m\"",
+    ),
+    example(
+        "monitoring.namespaces",
+        "Regions and URLs monitored per tenant, with their own thresholds and alert grouping",
+        "[{ name = \"acme\", regions = [\"Frankfurt\"], aggregator = { availability_threshold = 99.0 } }]",
+    ),
+    example(
+        "monitoring.endpoints_file",
+        "Keep endpoints created, changed or deleted through the HTTP or gRPC API across restarts",
+        "\"endpoints.json\"",
+    ),
+    example(
+        "monitoring.scripts",
+        "Alert conditions and derived metrics written as Rhai scripts; needs the scripting feature",
+        "[{ name = \"slow_and_lossy\", condition = \"p90_ms > 200.0 && loss_percent > 1.0\", severity = \"Critical\" }]",
+    ),
+    example(
+        "monitoring.probe_sink",
+        "Also post probe records to a URL, buffering them in a write-ahead log while it is unreachable",
+        "{ url = \"https://collector.example.com/probes\", batch_size = 500, flush_interval = \"10s\", wal_path = \"probe-wal.log\", wal_max_bytes = 67108864 }",
+    ),
+    example(
+        "monitoring.otlp",
+        "Also export round-trip histograms and scores to an OTLP/HTTP receiver at each metrics export",
+        "{ endpoint = \"http://localhost:4318\", headers = { \"x-api-key\" = \"...\" } }",
+    ),
+    doc("monitoring.probe.interval", "Time between probes of one endpoint"),
+    doc("monitoring.probe.timeout", "Give up on a probe after this long"),
+    doc("monitoring.probe.concurrency_limit", "Probes in flight at once"),
+    doc("monitoring.probe.jitter_percent", "Random spread added to each interval"),
+    doc(
+        "monitoring.probe.websocket_ping",
+        "Time a ping/pong round trip after each WebSocket handshake",
+    ),
+    example(
+        "monitoring.probe.capture",
+        "Trace the failing probes of one endpoint to a capture file; needs the `capture` feature",
+        "{ endpoint = \"redis://cache.example.com\", failure_threshold = 3, directory = \"captures\", max_bytes = 1048576 }",
+    ),
+    doc("monitoring.aggregator.short_window", "Probes in the short window"),
+    doc("monitoring.aggregator.long_window", "Probes in the long window"),
+    doc("monitoring.aggregator.ewma_alpha", "Latency smoothing factor, in (0, 1]"),
+    doc(
+        "monitoring.aggregator.long_recompute_interval",
+        "Time between recomputations of long-window metrics",
+    ),
+    doc(
+        "monitoring.aggregator.score_drop_threshold",
+        "Alert when the score drops this far below its recent median",
+    ),
+    doc(
+        "monitoring.aggregator.score_history",
+        "Scores kept per endpoint as the baseline for score drops",
+    ),
+    doc(
+        "monitoring.aggregator.sustained_loss_threshold",
+        "Alert when packet loss exceeds this percentage",
+    ),
+    doc(
+        "monitoring.aggregator.availability_threshold",
+        "Alert when availability falls below this percentage",
+    ),
+    doc(
+        "monitoring.aggregator.clock_offset_threshold_ms",
+        "Alert when an NTP server finds the local clock off by more than this",
+    ),
+    doc(
+        "monitoring.aggregator.batch_size",
+        "Waiting probe records processed together, rescoring each endpoint once",
+    ),
+    doc(
+        "monitoring.aggregator.shards",
+        "Aggregator tasks the endpoints are spread over; up to one per core",
+    ),
+    example(
+        "monitoring.aggregator.memory_budget_bytes",
+        "Bytes the probe windows of all endpoints may take together; windows are cut short to stay within it",
+        "268435456",
+    ),
+    doc(
+        "monitoring.correlation.window",
+        "Alerts are held back this long to group simultaneous ones",
+    ),
+    doc(
+        "monitoring.correlation.min_endpoints",
+        "Endpoints alerting together that form one incident",
+    ),
+    doc("api", "Access to the HTTP API of `serve`"),
+    example(
+        "api.tokens",
+        "Bearer tokens the API requires; `admin` tokens may also change endpoints and acknowledge alerts",
+        "[{ name = \"dashboard\", token = \"change-me-to-a-long-secret\", scope = \"read\" }]",
+    ),
+    example(
+        "api.tls",
+        "Serve the API over HTTPS with a PEM certificate chain and key",
+        "{ cert = \"/etc/cloud-ping/cert.pem\", key = \"/etc/cloud-ping/key.pem\" }",
+    ),
+    doc(
+        "profiles",
+        "Named presets applied with --profile, e.g. [profiles.quick]",
+    ),
+];
+
+fn key_doc(path: &str) -> Option<&'static KeyDoc> {
+    KEY_DOCS.iter().find(|doc| doc.path == path)
+}
+
+/// Render `config` as TOML with a comment above every documented setting
+///
+/// # Errors
+/// Returns an error if the configuration cannot be serialized
+pub fn commented_toml(config: &AppConfig) -> Result<String> {
+    let plain = toml::to_string_pretty(config)
+        .map_err(|e| CloudPingError::config(format!("Failed to serialize config: {e}")))?;
+
+    let mut out = String::from(
+        "# cloud-ping configuration\n#\n\
+         # Every setting can also be set with a CLOUD_PING_ environment variable,\n\
+         # using __ between nested keys (CLOUD_PING_MONITORING__PROBE__INTERVAL).\n\n",
+    );
+    let mut table = String::new();
+    let mut present: Vec<String> = Vec::new();
+
+    for line in plain.lines() {
+        if let Some(header) = table_header(line) {
+            push_missing_examples(&mut out, &table, &present);
+            table = header.to_string();
+            present.clear();
+            // Only the first of repeated [[array]] headers gets the comment
+            if !line.starts_with("[[") || !out.contains(line) {
+                push_comment(&mut out, &table);
+            }
+        } else if let Some((key, _)) = line.split_once(" = ") {
+            let path = join_path(&table, key.trim());
+            push_comment(&mut out, &path);
+            present.push(path);
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    push_missing_examples(&mut out, &table, &present);
+    Ok(out)
+}
+
+/// Write `config` with comments to `path`, creating parent directories
+///
+/// # Errors
+/// Returns an error if `path` exists and `overwrite` is false, or writing fails
+pub fn write_commented_config(config: &AppConfig, path: &Path, overwrite: bool) -> Result<()> {
+    if path.exists() && !overwrite {
+        return Err(CloudPingError::config(format!(
+            "{} already exists (use --force to overwrite)",
+            path.display()
+        )));
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, commented_toml(config)?)?;
+    Ok(())
+}
+
+fn table_header(line: &str) -> Option<&str> {
+    line.strip_prefix("[[")
+        .and_then(|rest| rest.strip_suffix("]]"))
+        .or_else(|| {
+            line.strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+        })
+}
+
+fn join_path(table: &str, key: &str) -> String {
+    if table.is_empty() {
+        key.to_string()
+    } else {
+        format!("{table}.{key}")
+    }
+}
+
+fn push_comment(out: &mut String, path: &str) {
+    if let Some(doc) = key_doc(path) {
+        let _ = writeln!(out, "# {}", doc.comment);
+    }
+}
+
+/// Commented-out examples for optional keys of `table` that have no value
+fn push_missing_examples(out: &mut String, table: &str, present: &[String]) {
+    // Keep the examples inside the table rather than after its trailing blank line
+    let separated = out.ends_with("\n\n");
+    if separated {
+        out.pop();
+    }
+    for doc in KEY_DOCS {
+        let Some(example) = doc.example else {
+            continue;
+        };
+        let (doc_table, key) = doc.path.rsplit_once('.').unwrap_or(("", doc.path));
+        if doc_table == table && !present.iter().any(|path| path == doc.path) {
+            let _ = writeln!(out, "# {}\n# {key} = {example}", doc.comment);
+        }
+    }
+    if separated {
+        out.push('\n');
+    }
+}
+
+/// Ask for the settings most users change first, starting from `config`
+///
+/// # Errors
+/// Returns an error if the terminal is not interactive or the answers are invalid
+pub fn run_wizard(mut config: AppConfig) -> Result<AppConfig> {
+    let theme = ColorfulTheme::default();
+    let prompt_error = |e: dialoguer::Error| CloudPingError::config(format!("Setup aborted: {e}"));
+
+    config.data_file = Input::with_theme(&theme)
+        .with_prompt("Data file with providers and regions")
+        .default(config.data_file.clone())
+        .interact_text()
+        .map_err(prompt_error)?;
+    config.default_ping_count = Input::with_theme(&theme)
+        .with_prompt("Pings per region")
+        .default(config.default_ping_count)
+        .interact_text()
+        .map_err(prompt_error)?;
+    config.max_threads = Input::with_theme(&theme)
+        .with_prompt("Regions tested at once")
+        .default(config.max_threads)
+        .interact_text()
+        .map_err(prompt_error)?;
+    let timeout_ms: u64 = Input::with_theme(&theme)
+        .with_prompt("Request timeout in milliseconds")
+        .default(config.timeout_ms)
+        .interact_text()
+        .map_err(prompt_error)?;
+    config.timeout_ms = timeout_ms;
+    config.timeout = Duration::from_millis(timeout_ms);
+
+    let aggregator = &mut config.monitoring.aggregator;
+    aggregator.availability_threshold = Input::with_theme(&theme)
+        .with_prompt("Alert when availability falls below (%)")
+        .default(aggregator.availability_threshold)
+        .interact_text()
+        .map_err(prompt_error)?;
+    aggregator.sustained_loss_threshold = Input::with_theme(&theme)
+        .with_prompt("Alert when packet loss exceeds (%)")
+        .default(aggregator.sustained_loss_threshold)
+        .interact_text()
+        .map_err(prompt_error)?;
+
+    let formats = ["table", "json", "csv"];
+    let current = match config.output_format {
+        OutputFormat::Table => 0,
+        OutputFormat::Json => 1,
+        OutputFormat::Csv => 2,
+    };
+    config.output_format = match Select::with_theme(&theme)
+        .with_prompt("Output format")
+        .items(&formats)
+        .default(current)
+        .interact()
+        .map_err(prompt_error)?
+    {
+        1 => OutputFormat::Json,
+        2 => OutputFormat::Csv,
+        _ => OutputFormat::Table,
+    };
+    config.enable_color_output = Confirm::with_theme(&theme)
+        .with_prompt("Colored output?")
+        .default(config.enable_color_output)
+        .interact()
+        .map_err(prompt_error)?;
+    config.show_progress = Confirm::with_theme(&theme)
+        .with_prompt("Show progress bars?")
+        .default(config.show_progress)
+        .interact()
+        .map_err(prompt_error)?;
+
+    let history: String = Input::with_theme(&theme)
+        .with_prompt("History file (empty to keep no history)")
+        .default(config.history_file.clone().unwrap_or_default())
+        .allow_empty(true)
+        .interact_text()
+        .map_err(prompt_error)?;
+    config.history_file = (!history.trim().is_empty()).then(|| history.trim().to_string());
+
+    config.validate()?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commented_toml_round_trips() {
+        let config = AppConfig {
+            history_file: Some("history.json".to_string()),
+            ..AppConfig::default()
+        };
+        let text = commented_toml(&config).unwrap();
+
+        assert!(text.contains("# Pings per region for `benchmark`\ndefault_ping_count = 10\n"));
+        assert!(text.contains("# Time between probes of one endpoint\ninterval = \"5s\"\n"));
+        // Unset optional settings appear as commented-out examples
+        assert!(text.contains("# run_deadline = \"10m\"\n"));
+        assert!(text.contains("# reference_url = "));
+        assert!(!text.contains("# history_file = "));
+
+        let parsed: AppConfig = toml::from_str(&text).unwrap();
+        assert_eq!(parsed.history_file.as_deref(), Some("history.json"));
+        assert_eq!(parsed.status_feeds.len(), config.status_feeds.len());
+        assert_eq!(parsed.monitoring, config.monitoring);
+        assert!(parsed.run_deadline.is_none());
+    }
+
+    #[test]
+    fn test_write_refuses_to_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("config.toml");
+        let config = AppConfig::default();
+
+        write_commented_config(&config, &path, false).unwrap();
+        assert!(write_commented_config(&config, &path, false).is_err());
+        write_commented_config(&config, &path, true).unwrap();
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("# cloud-ping configuration"));
+    }
+}
@@ -0,0 +1,114 @@
+//! Aggregation spread over several tasks
+//!
+//! One [`StreamingAggregator`] processes every probe record on a single task
+//! and so on a single core. With `monitoring.aggregator.shards` above 1 the
+//! endpoints are spread over that many aggregators by a hash of their ID,
+//! each running as its own task. A router task hands every record to its
+//! endpoint's shard.
+//!
+//! Scores, score history, alerts and self-metrics are shared by the shards,
+//! so readers see all endpoints as before.
+
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+use crate::aggregator::{shard_index, StreamingAggregator};
+use crate::models::ProbeRecord;
+
+/// Aggregators each owning the endpoints [`shard_index`] assigns them
+pub struct ShardedAggregator {
+    shards: Vec<StreamingAggregator>,
+}
+
+impl ShardedAggregator {
+    /// Spread the endpoints of `aggregator` over `shards` aggregators
+    #[must_use]
+    pub fn new(aggregator: StreamingAggregator, shards: usize) -> Self {
+        Self {
+            shards: aggregator.split(shards),
+        }
+    }
+
+    /// Number of shards
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Whether there are no shards; never the case, as at least one is made
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.shards.is_empty()
+    }
+
+    /// Start every shard on its own task and route the records of `probe_receiver` to them
+    ///
+    /// # Errors
+    /// Returns an error when a shard stops, failing or panicking
+    pub async fn start(
+        self,
+        mut probe_receiver: mpsc::UnboundedReceiver<ProbeRecord>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        info!("Starting {} aggregator shards", self.shards.len());
+        let (senders, tasks): (Vec<_>, Vec<_>) = self
+            .shards
+            .into_iter()
+            .map(|shard| {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                (sender, tokio::spawn(shard.start(receiver)))
+            })
+            .unzip();
+
+        while let Some(record) = probe_receiver.recv().await {
+            let shard = shard_index(&record.endpoint_id, senders.len());
+            if senders[shard].send(record).is_err() {
+                error!("Aggregator shard {} stopped, no longer routing records", shard);
+                break;
+            }
+        }
+        drop(senders);
+
+        for (shard, task) in tasks.into_iter().enumerate() {
+            task.await
+                .map_err(|e| format!("Aggregator shard {shard} panicked: {e}"))??;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregator::AggregatorConfig;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::RwLock;
+
+    #[tokio::test]
+    async fn test_shards_share_scores() {
+        let scores = Arc::new(RwLock::new(HashMap::new()));
+        let (aggregator, _alerts) = StreamingAggregator::new(AggregatorConfig::default());
+        let sharded = ShardedAggregator::new(aggregator.with_score_snapshot(Arc::clone(&scores)), 4);
+        assert_eq!(sharded.len(), 4);
+
+        let (records, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(sharded.start(receiver));
+        let endpoints: Vec<String> = (0..40).map(|i| format!("endpoint-{i}")).collect();
+        for endpoint in &endpoints {
+            records.send(ProbeRecord::success(endpoint.clone(), 20.0)).unwrap();
+        }
+        // Endpoints land on more than one shard
+        let shards: std::collections::HashSet<usize> =
+            endpoints.iter().map(|endpoint| shard_index(endpoint, 4)).collect();
+        assert!(shards.len() > 1);
+
+        for _ in 0..100 {
+            if scores.read().await.len() == 40 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(scores.read().await.len(), 40);
+    }
+}
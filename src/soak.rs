@@ -0,0 +1,176 @@
+//! Soak tests: pinging regions at a steady rate for a set duration
+//!
+//! Every region is pinged on its own schedule, all regions at once, each
+//! ping a single request without retries so that every failure shows in the
+//! availability timeline. A ping that takes longer than the interval delays
+//! the next one instead of being followed by a burst.
+
+use std::time::{Duration, Instant};
+
+use futures::future::join_all;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+use crate::error::{CloudPingError, Result};
+use crate::models::{FailureKind, Region, SoakResult, SoakSample};
+use crate::network::NetworkTester;
+use crate::time_utils::TimeUtils;
+
+/// Time between pings to a region when none is given
+pub const DEFAULT_SOAK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Width of the time buckets when none is given
+pub const DEFAULT_BUCKET_WIDTH: Duration = Duration::from_secs(60);
+
+/// Pings regions at a steady rate for a set duration
+#[derive(Debug, Clone)]
+pub struct SoakTest {
+    tester: NetworkTester,
+    duration: Duration,
+    interval: Duration,
+    bucket_width: Duration,
+}
+
+impl SoakTest {
+    /// Soak test pinging every `interval` for `duration`, grouped into buckets of `bucket_width`
+    ///
+    /// # Errors
+    /// Returns a validation error when a duration is zero or the interval is
+    /// longer than the test
+    pub fn new(
+        tester: NetworkTester,
+        duration: Duration,
+        interval: Duration,
+        bucket_width: Duration,
+    ) -> Result<Self> {
+        for (key, value) in [
+            ("duration", duration),
+            ("interval", interval),
+            ("bucket", bucket_width),
+        ] {
+            if value.is_zero() {
+                return Err(CloudPingError::validation(key, "must be greater than 0"));
+            }
+        }
+        if interval > duration {
+            return Err(CloudPingError::validation(
+                "interval",
+                "must not be longer than the test duration",
+            ));
+        }
+        Ok(Self {
+            tester,
+            duration,
+            interval,
+            bucket_width,
+        })
+    }
+
+    /// Soak test every region at once until the duration is up or `cancel` fires
+    pub async fn run(&self, regions: &[Region], cancel: &CancellationToken) -> Vec<SoakResult> {
+        info!(
+            "Soak testing {} regions every {:?} for {:?}",
+            regions.len(),
+            self.interval,
+            self.duration
+        );
+        join_all(regions.iter().map(|region| self.soak_region(region, cancel))).await
+    }
+
+    async fn soak_region(&self, region: &Region, cancel: &CancellationToken) -> SoakResult {
+        let mut result = SoakResult {
+            region: region.name.clone(),
+            url: region.url.clone(),
+            started: TimeUtils::now(),
+            interval: self.interval,
+            bucket_width: self.bucket_width,
+            samples: Vec::new(),
+            incomplete: false,
+        };
+        let start = Instant::now();
+        let end = start + self.duration;
+        let mut ticks = tokio::time::interval(self.interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                () = cancel.cancelled() => {
+                    result.incomplete = true;
+                    break;
+                }
+                _ = ticks.tick() => {}
+            }
+            let sent = Instant::now();
+            if sent >= end {
+                break;
+            }
+            let timing = tokio::select! {
+                () = cancel.cancelled() => {
+                    result.incomplete = true;
+                    break;
+                }
+                timing = self.tester.ping_url_with_retry(&region.url, 0) => timing,
+            };
+            let latency_ms = timing.total_time.as_secs_f64() * 1000.0;
+            result.samples.push(SoakSample {
+                offset_ms: u64::try_from((sent - start).as_millis()).unwrap_or(u64::MAX),
+                latency_ms: timing.success.then_some(latency_ms),
+                failure: (!timing.success)
+                    .then(|| timing.failure.unwrap_or(FailureKind::Other)),
+            });
+        }
+        debug!("Soak test of {} sent {} pings", region.name, result.samples.len());
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::test_util::MockTransport;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_soak_pings_at_interval_until_duration() {
+        let transport = Arc::new(
+            MockTransport::new().latency("https://up.example.com", Duration::from_millis(12)),
+        );
+        let config = AppConfig {
+            max_requests_per_host_per_second: 0.0,
+            ..AppConfig::default()
+        };
+        let tester = NetworkTester::with_transport(config, transport).unwrap();
+        let region = |name: &str, url: &str| Region::new(name.to_string(), url.to_string()).unwrap();
+        let regions = [
+            region("up", "https://up.example.com"),
+            region("down", "https://down.example.com"),
+        ];
+
+        let soak = SoakTest::new(
+            tester.clone(),
+            Duration::from_millis(100),
+            Duration::from_millis(20),
+            Duration::from_millis(50),
+        )
+        .unwrap();
+        let results = soak.run(&regions, &CancellationToken::new()).await;
+
+        let up = &results[0];
+        assert!((4..=6).contains(&up.samples.len()), "{} pings", up.samples.len());
+        assert!(!up.incomplete);
+        assert_eq!(up.availability(), Some(100.0));
+        assert_eq!(up.p50_p95_ms()[1], Some(12.0));
+        assert_eq!(results[1].availability(), Some(0.0));
+        assert!(results[1].samples.iter().all(|sample| sample.failure.is_some()));
+
+        let too_slow = SoakTest::new(
+            tester,
+            Duration::from_secs(1),
+            Duration::from_secs(2),
+            DEFAULT_BUCKET_WIDTH,
+        );
+        assert!(too_slow.is_err());
+    }
+}
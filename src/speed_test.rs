@@ -0,0 +1,189 @@
+//! Speed tests against the speed-test endpoints of regions
+//!
+//! A test first times a few empty requests with the link idle, then
+//! downloads `speed_test_bytes` while timing empty requests alongside, and
+//! finally uploads half as many bytes. The difference between the latency
+//! during the download and with the link idle shows how much the path
+//! buffers under load. Tests run one at a time, and a benchmark runs them
+//! only once every region's pings are done, so no test loads the link while
+//! another test or a ping is measuring it. [`under_load`] takes the idle and
+//! loaded round trips for both speed tests and `bufferbloat`.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use futures::future::join_all;
+use reqwest::Client;
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::config::AppConfig;
+use crate::models::utils::percentile;
+use crate::models::{SpeedTestEndpoint, SpeedTestResult};
+use crate::network::{throughput_mbps, NetworkTester};
+
+/// Empty requests timed with the link idle
+const IDLE_PINGS: usize = 5;
+
+/// Pause between round trips
+const PING_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time the downloads run before round trips under load are taken, so TCP
+/// slow start has filled the pipe
+const LOAD_WARMUP: Duration = Duration::from_millis(500);
+
+/// Time a download or an upload may take
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How [`under_load`] loads the link
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Load<'a> {
+    /// Download URL
+    pub url: &'a str,
+    /// Bytes each download fetches
+    pub bytes: u64,
+    /// Parallel downloads
+    pub streams: usize,
+    /// Round trips taken idle
+    pub idle_pings: usize,
+    /// Most round trips taken under load
+    pub loaded_pings: usize,
+}
+
+/// Round trips in milliseconds with the link idle and under load, and the
+/// throughput of all downloads together
+#[derive(Debug, Clone, Default)]
+pub(crate) struct UnderLoad {
+    pub idle: Vec<f64>,
+    pub loaded: Vec<f64>,
+    pub download_mbps: Option<f64>,
+}
+
+/// Take `load.idle_pings` round trips with `rtt_ms`, then load the link with
+/// the parallel downloads of `load` and take round trips again after a warm-up,
+/// until `load.loaded_pings` were taken or the downloads finish
+pub(crate) async fn under_load<F, Fut>(client: &Client, load: Load<'_>, rtt_ms: F) -> UnderLoad
+where
+    F: Fn() -> Fut + Sync,
+    Fut: Future<Output = Option<f64>> + Send,
+{
+    let mut idle = Vec::with_capacity(load.idle_pings);
+    for _ in 0..load.idle_pings {
+        idle.extend(rtt_ms().await);
+        tokio::time::sleep(PING_INTERVAL).await;
+    }
+
+    let downloaded = AtomicBool::new(false);
+    let download = async {
+        let streams = (0..load.streams).map(|_| throughput_mbps(client, load.url, load.bytes, TRANSFER_TIMEOUT));
+        let mbps: Vec<f64> = join_all(streams).await.into_iter().flatten().collect();
+        downloaded.store(true, Ordering::Relaxed);
+        (!mbps.is_empty()).then(|| mbps.iter().sum::<f64>())
+    };
+    let loaded = async {
+        tokio::time::sleep(LOAD_WARMUP).await;
+        let mut loaded = Vec::new();
+        while loaded.len() < load.loaded_pings && !downloaded.load(Ordering::Relaxed) {
+            loaded.extend(rtt_ms().await);
+            tokio::time::sleep(PING_INTERVAL).await;
+        }
+        loaded
+    };
+    let (download_mbps, loaded) = tokio::join!(download, loaded);
+    UnderLoad { idle, loaded, download_mbps }
+}
+
+/// Runs speed tests, one at a time
+#[derive(Debug)]
+pub struct SpeedTester {
+    client: Client,
+    bytes: u64,
+    ping_timeout: Duration,
+    /// Held for the whole of a test
+    running: Mutex<()>,
+}
+
+impl SpeedTester {
+    /// Tester downloading `speed_test_bytes` per test with the client of
+    /// `tester`; `None` when speed tests are turned off
+    #[must_use]
+    pub fn from_config(config: &AppConfig, tester: &NetworkTester) -> Option<Self> {
+        (config.speed_test_bytes > 0).then(|| Self {
+            client: tester.client().clone(),
+            bytes: config.speed_test_bytes,
+            ping_timeout: config.attempt_timeout(),
+            running: Mutex::new(()),
+        })
+    }
+
+    /// Measure `endpoint`, waiting for any test already running to finish
+    pub async fn run(&self, endpoint: &SpeedTestEndpoint) -> SpeedTestResult {
+        let _running = self.running.lock().await;
+        let ping_url = endpoint.ping_url();
+        let download_url = endpoint.download_url(self.bytes);
+        let load = Load {
+            url: &download_url,
+            bytes: self.bytes,
+            streams: 1,
+            idle_pings: IDLE_PINGS,
+            loaded_pings: usize::MAX,
+        };
+        let measured = under_load(&self.client, load, || self.latency_ms(&ping_url)).await;
+
+        SpeedTestResult {
+            kind: Some(endpoint.kind),
+            download_mbps: measured.download_mbps,
+            upload_mbps: self.upload_mbps(&endpoint.upload_url()).await,
+            idle_latency_ms: median(&measured.idle),
+            loaded_latency_ms: median(&measured.loaded),
+        }
+    }
+
+    /// Time of an empty request to `url` in milliseconds; `None` when it fails
+    async fn latency_ms(&self, url: &str) -> Option<f64> {
+        let url = NetworkTester::add_cache_buster(url).ok()?;
+        let start = Instant::now();
+        let response = self.client.get(&url).timeout(self.ping_timeout).send().await;
+        match response.and_then(reqwest::Response::error_for_status) {
+            Ok(response) => {
+                let elapsed = start.elapsed();
+                drop(response);
+                Some(elapsed.as_secs_f64() * 1000.0)
+            }
+            Err(e) => {
+                debug!("Speed-test ping to {} failed: {}", url, e);
+                None
+            }
+        }
+    }
+
+    /// Upload throughput to `url` in megabits per second, timed over the
+    /// whole request; `None` when it fails
+    async fn upload_mbps(&self, url: &str) -> Option<f64> {
+        let bytes = self.bytes / 2;
+        let body = vec![0u8; usize::try_from(bytes).ok()?];
+        let start = Instant::now();
+        let response = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .body(body)
+            .timeout(TRANSFER_TIMEOUT)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        if let Err(e) = response {
+            debug!("Upload to {} failed: {}", url, e);
+            return None;
+        }
+        let elapsed = start.elapsed();
+        #[allow(clippy::cast_precision_loss)] // speed-test sizes are far below 2^52 bytes
+        let mbps = (bytes > 0 && !elapsed.is_zero()).then(|| bytes as f64 * 8.0 / elapsed.as_secs_f64() / 1_000_000.0);
+        mbps
+    }
+}
+
+fn median(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| percentile(values, 50.0))
+}
@@ -0,0 +1,289 @@
+//! STUN discovery of the public UDP mapping and NAT type
+//!
+//! A STUN binding request (RFC 5389) asks a server which address and port
+//! the request arrived from, which is the public mapping the NAT gave the
+//! local socket. Asking two servers from the same socket shows whether the
+//! NAT keeps that mapping for every destination (cone) or makes a new one
+//! per destination (symmetric), in the manner of RFC 5780 but without
+//! relying on servers that support its change requests. Only IPv4 is used,
+//! where NAT applies.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use tokio::net::{lookup_host, UdpSocket};
+use tracing::debug;
+
+use crate::models::NatType;
+
+/// Fixed value in every STUN message since RFC 5389
+const MAGIC_COOKIE: u32 = 0x2112_A442;
+
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS: u16 = 0x0101;
+const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+
+/// Length of the STUN message header
+const HEADER_LEN: usize = 20;
+
+/// Requests sent to a server before giving up on it, as UDP may drop any of them
+const ATTEMPTS: usize = 3;
+
+/// Time to wait for each answer
+const ATTEMPT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Public mapping and NAT type found with STUN
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NatDiscovery {
+    /// Mapping the first answering server saw
+    pub public_mapping: Option<SocketAddr>,
+    /// NAT behaviour
+    pub nat_type: NatType,
+}
+
+/// Send binding requests from one socket to each of `servers` (`host:port`)
+/// and classify the NAT from the mappings they report
+///
+/// UDP only counts as blocked when a server was asked and none answered;
+/// without a server to ask, the NAT type is unknown.
+pub async fn discover(servers: &[String]) -> NatDiscovery {
+    let unknown = NatDiscovery {
+        public_mapping: None,
+        nat_type: NatType::Unknown,
+    };
+    let Ok(socket) = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0))).await else {
+        return unknown;
+    };
+    let mut asked = false;
+    let mut local = None;
+    let mut mappings = Vec::new();
+    for server in servers {
+        let Some(addr) = resolve(server).await else {
+            debug!("STUN server {} did not resolve to an IPv4 address", server);
+            continue;
+        };
+        asked = true;
+        if local.is_none() {
+            local = local_address(&socket, addr).await;
+        }
+        if let Some(mapping) = binding(&socket, addr).await {
+            debug!("STUN server {} sees this client as {}", server, mapping);
+            mappings.push(mapping);
+        } else {
+            debug!("No STUN answer from {}", server);
+        }
+    }
+
+    match (local, mappings.first()) {
+        (Some(local), Some(&first)) => NatDiscovery {
+            public_mapping: Some(first),
+            nat_type: classify(local, &mappings),
+        },
+        (None, Some(&first)) => NatDiscovery {
+            public_mapping: Some(first),
+            nat_type: NatType::Unknown,
+        },
+        (_, None) if asked => NatDiscovery {
+            public_mapping: None,
+            nat_type: NatType::UdpBlocked,
+        },
+        (_, None) => unknown,
+    }
+}
+
+/// NAT type from the local address and the mappings servers reported for it
+fn classify(local: SocketAddr, mappings: &[SocketAddr]) -> NatType {
+    match mappings {
+        [] => NatType::UdpBlocked,
+        [first, ..] if *first == local => NatType::Open,
+        [first, rest @ ..] if rest.iter().any(|mapping| mapping != first) => NatType::Symmetric,
+        [_] => NatType::Unknown,
+        _ => NatType::Cone,
+    }
+}
+
+/// First IPv4 address of `server`
+async fn resolve(server: &str) -> Option<SocketAddr> {
+    lookup_host(server).await.ok()?.find(SocketAddr::is_ipv4)
+}
+
+/// Address `socket` sends to `server` from, with the interface address the route uses
+async fn local_address(socket: &UdpSocket, server: SocketAddr) -> Option<SocketAddr> {
+    // Connecting a UDP socket only picks the route, nothing is sent
+    let route = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0))).await.ok()?;
+    route.connect(server).await.ok()?;
+    Some(SocketAddr::new(
+        route.local_addr().ok()?.ip(),
+        socket.local_addr().ok()?.port(),
+    ))
+}
+
+/// Mapping `server` reports for `socket`, retrying lost requests
+async fn binding(socket: &UdpSocket, server: SocketAddr) -> Option<SocketAddr> {
+    let transaction_id: [u8; 12] = rand::random();
+    let request = binding_request(transaction_id);
+    let mut buffer = [0u8; 576];
+    for _ in 0..ATTEMPTS {
+        socket.send_to(&request, server).await.ok()?;
+        let answer = tokio::time::timeout(ATTEMPT_TIMEOUT, async {
+            loop {
+                let (len, from) = socket.recv_from(&mut buffer).await.ok()?;
+                // Answers to earlier attempts or from other hosts are skipped
+                if from == server {
+                    if let Some(mapping) = parse_binding_response(&buffer[..len], transaction_id) {
+                        return Some(mapping);
+                    }
+                }
+            }
+        })
+        .await;
+        if let Ok(mapping) = answer {
+            return mapping;
+        }
+    }
+    None
+}
+
+/// Binding request without attributes
+pub(crate) fn binding_request(transaction_id: [u8; 12]) -> [u8; HEADER_LEN] {
+    let mut request = [0u8; HEADER_LEN];
+    request[0..2].copy_from_slice(&BINDING_REQUEST.to_be_bytes());
+    request[4..8].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    request[8..20].copy_from_slice(&transaction_id);
+    request
+}
+
+/// Whether `packet` is a STUN success or error response to `transaction_id`;
+/// either shows a STUN or TURN server received the request
+pub(crate) fn is_response(packet: &[u8], transaction_id: [u8; 12]) -> bool {
+    // The high class bit, the lowest bit of the first byte, is set in
+    // success (0b10) and error (0b11) responses
+    packet.len() >= HEADER_LEN
+        && packet[0] & 0x01 != 0
+        && packet[4..8] == MAGIC_COOKIE.to_be_bytes()
+        && packet[8..20] == transaction_id
+}
+
+/// Mapped address of a binding success answering `transaction_id`,
+/// preferring `XOR-MAPPED-ADDRESS` over the older `MAPPED-ADDRESS`
+fn parse_binding_response(packet: &[u8], transaction_id: [u8; 12]) -> Option<SocketAddr> {
+    if packet.len() < HEADER_LEN
+        || packet[0..2] != BINDING_SUCCESS.to_be_bytes()
+        || packet[4..8] != MAGIC_COOKIE.to_be_bytes()
+        || packet[8..20] != transaction_id
+    {
+        return None;
+    }
+    let length = usize::from(u16::from_be_bytes([packet[2], packet[3]]));
+    let mut attributes = packet.get(HEADER_LEN..HEADER_LEN + length)?;
+
+    let mut mapped = None;
+    while attributes.len() >= 4 {
+        let kind = u16::from_be_bytes([attributes[0], attributes[1]]);
+        let len = usize::from(u16::from_be_bytes([attributes[2], attributes[3]]));
+        let value = attributes.get(4..4 + len)?;
+        match kind {
+            ATTR_XOR_MAPPED_ADDRESS => return address(value, Some(&packet[4..20])),
+            ATTR_MAPPED_ADDRESS => mapped = address(value, None),
+            _ => {}
+        }
+        // Attribute values are padded to a multiple of 4 bytes
+        let padded = 4 + len.div_ceil(4) * 4;
+        attributes = attributes.get(padded..).unwrap_or_default();
+    }
+    mapped
+}
+
+/// Address of a (XOR-)MAPPED-ADDRESS value; `xor` holds the magic cookie
+/// and transaction ID the XOR variant is masked with
+fn address(value: &[u8], xor: Option<&[u8]>) -> Option<SocketAddr> {
+    let mask = |bytes: &[u8]| -> Vec<u8> {
+        let key = xor.unwrap_or(&[0; 16]);
+        bytes.iter().zip(key).map(|(byte, key)| byte ^ key).collect()
+    };
+    let port = mask(value.get(2..4)?);
+    let port = u16::from_be_bytes([port[0], port[1]]);
+    let ip = match value.get(1)? {
+        0x01 => {
+            let octets: [u8; 4] = mask(value.get(4..8)?).try_into().ok()?;
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+        0x02 => {
+            let octets: [u8; 16] = mask(value.get(4..20)?).try_into().ok()?;
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// STUN server on localhost that reports each request's source, with
+    /// `port_shift` added to the port to play a NAT
+    async fn server(port_shift: u16) -> String {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut request = [0u8; HEADER_LEN];
+            while let Ok((_, from)) = socket.recv_from(&mut request).await {
+                let mut answer = request.to_vec();
+                answer[0..2].copy_from_slice(&BINDING_SUCCESS.to_be_bytes());
+                answer[2..4].copy_from_slice(&12u16.to_be_bytes());
+                let port = (from.port() + port_shift) ^ 0x2112;
+                answer.extend_from_slice(&[0x00, 0x20, 0, 8, 0, 1]);
+                answer.extend_from_slice(&port.to_be_bytes());
+                answer.extend_from_slice(&[127 ^ 0x21, 0x12, 0xA4, 1 ^ 0x42]);
+                socket.send_to(&answer, from).await.unwrap();
+            }
+        });
+        addr.to_string()
+    }
+
+    #[test]
+    fn test_parse_binding_response() {
+        // Sample IPv4 response of RFC 5769 section 2.2, with only the XOR-MAPPED-ADDRESS
+        let transaction_id = [
+            0xb7, 0xe7, 0xa7, 0x01, 0xbc, 0x34, 0xd6, 0x86, 0xfa, 0x87, 0xdf, 0xae,
+        ];
+        let mut packet = vec![0x01, 0x01, 0x00, 0x0c, 0x21, 0x12, 0xa4, 0x42];
+        packet.extend_from_slice(&transaction_id);
+        packet.extend_from_slice(&[0x00, 0x20, 0x00, 0x08, 0x00, 0x01, 0xa1, 0x47, 0xe1, 0x12, 0xa6, 0x43]);
+        assert_eq!(
+            parse_binding_response(&packet, transaction_id),
+            Some("192.0.2.1:32853".parse().unwrap())
+        );
+        assert_eq!(parse_binding_response(&packet, [0; 12]), None);
+    }
+
+    #[test]
+    fn test_classify() {
+        let local: SocketAddr = "192.168.1.20:40000".parse().unwrap();
+        let public: SocketAddr = "203.0.113.7:40000".parse().unwrap();
+        let other: SocketAddr = "203.0.113.7:51234".parse().unwrap();
+        assert_eq!(classify(local, &[]), NatType::UdpBlocked);
+        assert_eq!(classify(local, &[local]), NatType::Open);
+        assert_eq!(classify(local, &[public]), NatType::Unknown);
+        assert_eq!(classify(local, &[public, public]), NatType::Cone);
+        assert_eq!(classify(local, &[public, other]), NatType::Symmetric);
+    }
+
+    #[tokio::test]
+    async fn test_discover() {
+        let open = discover(&[server(0).await, server(0).await]).await;
+        assert_eq!(open.nat_type, NatType::Open);
+        assert_eq!(open.public_mapping.unwrap().ip(), Ipv4Addr::LOCALHOST);
+
+        let cone = discover(&[server(1).await, server(1).await]).await;
+        assert_eq!(cone.nat_type, NatType::Cone);
+        let symmetric = discover(&[server(1).await, server(2).await]).await;
+        assert_eq!(symmetric.nat_type, NatType::Symmetric);
+
+        // No server to ask says nothing about UDP
+        assert_eq!(discover(&[]).await.nat_type, NatType::Unknown);
+        assert_eq!(discover(&["no port".to_string()]).await.nat_type, NatType::Unknown);
+    }
+}
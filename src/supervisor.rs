@@ -0,0 +1,177 @@
+//! Restarting monitoring tasks that panic
+//!
+//! A probe loop that panics would otherwise stop probing its endpoint until
+//! the next start, with nothing but a log line to show for it. Tasks spawned
+//! with [`spawn_supervised`] are run again after a panic, waiting longer
+//! after every crash in a row, and report when they keep crashing so an
+//! alert can be raised. Tasks that return, or are aborted, stay stopped.
+//!
+//! Restarts catch the unwinding panic, so every build profile keeps
+//! `panic = "unwind"`; with `"abort"` the first panic ends the process.
+
+use std::any::Any;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::FutureExt;
+use tokio::task::AbortHandle;
+use tokio::time::sleep;
+use tracing::error;
+
+use crate::self_metrics::SelfMetrics;
+
+/// When and how often a panicked task is restarted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// Wait before the first restart, doubled for every further crash in the window
+    pub initial_backoff: Duration,
+    /// Longest wait before a restart
+    pub max_backoff: Duration,
+    /// Crashes longer ago than this are forgotten
+    pub failure_window: Duration,
+    /// Crashes within the window after which the task counts as failing
+    pub failing_after: u32,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            failure_window: Duration::from_secs(600),
+            failing_after: 3,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Wait before restarting a task that crashed `crashes` times within the window
+    #[must_use]
+    pub fn backoff(&self, crashes: u32) -> Duration {
+        let doublings = crashes.saturating_sub(1).min(16);
+        self.initial_backoff
+            .saturating_mul(1 << doublings)
+            .min(self.max_backoff)
+    }
+}
+
+/// Message a task panicked with
+#[must_use]
+pub fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+/// Spawn the task made by `task`, making it again whenever it panics
+///
+/// Every panic is counted in `metrics` and logged with the task's `name`,
+/// the crashes within the policy's failure window and the wait before the
+/// restart. When the crashes within the window reach
+/// [`RestartPolicy::failing_after`], `on_failing` is called with their count.
+///
+/// Returns a handle to abort the task, which stops restarting it as well.
+pub fn spawn_supervised<T, F, A>(
+    metrics: &Arc<SelfMetrics>,
+    name: String,
+    policy: RestartPolicy,
+    mut task: T,
+    mut on_failing: A,
+) -> AbortHandle
+where
+    T: FnMut() -> F + Send + 'static,
+    F: Future<Output = ()> + Send + 'static,
+    A: FnMut(u32) + Send + 'static,
+{
+    let metrics = Arc::clone(metrics);
+    tokio::spawn(async move {
+        let mut crashes: Vec<Instant> = Vec::new();
+        while let Err(panic) = AssertUnwindSafe(task()).catch_unwind().await {
+            metrics.task_panicked();
+            let now = Instant::now();
+            crashes.retain(|crashed| now.duration_since(*crashed) < policy.failure_window);
+            crashes.push(now);
+            let count = u32::try_from(crashes.len()).unwrap_or(u32::MAX);
+            let backoff = policy.backoff(count);
+            error!(
+                task = %name,
+                crashes = count,
+                backoff_ms = u64::try_from(backoff.as_millis()).unwrap_or(u64::MAX),
+                "Task {} panicked, restarting: {}",
+                name,
+                panic_message(panic.as_ref())
+            );
+            if count == policy.failing_after {
+                on_failing(count);
+            }
+            sleep(backoff).await;
+        }
+    })
+    .abort_handle()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_release_profiles_unwind_panics() {
+        // Tests always unwind, so check the settings release binaries are built with
+        let manifest: toml::Value = toml::from_str(include_str!("../Cargo.toml")).unwrap();
+        for profile in ["release", "bench"] {
+            let settings = &manifest["profile"][profile];
+            let inherited = settings
+                .get("inherits")
+                .and_then(toml::Value::as_str)
+                .map(|parent| &manifest["profile"][parent]);
+            let panic = settings
+                .get("panic")
+                .or_else(|| inherited.and_then(|parent| parent.get("panic")))
+                .and_then(toml::Value::as_str);
+            assert_ne!(panic, Some("abort"), "profile.{profile} aborts on panic");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panicking_task_is_restarted_until_it_returns() {
+        let policy = RestartPolicy {
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+            failure_window: Duration::from_secs(60),
+            failing_after: 2,
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(1));
+        assert_eq!(policy.backoff(2), Duration::from_millis(2));
+        assert_eq!(policy.backoff(10), Duration::from_millis(4));
+
+        let metrics = Arc::new(SelfMetrics::default());
+        let runs = Arc::new(AtomicU32::new(0));
+        let (failing_sender, mut failing) = tokio::sync::mpsc::unbounded_channel();
+        let task_runs = Arc::clone(&runs);
+        spawn_supervised(
+            &metrics,
+            "flaky".to_string(),
+            policy,
+            move || {
+                let run = task_runs.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    assert!(run >= 3, "crash {run}");
+                }
+            },
+            move |crashes| {
+                let _ = failing_sender.send(crashes);
+            },
+        );
+
+        // Reported failing once, at the second crash, then running on after the third
+        assert_eq!(failing.recv().await, Some(2));
+        assert_eq!(failing.recv().await, None);
+        assert_eq!(runs.load(Ordering::SeqCst), 4);
+        assert_eq!(metrics.snapshot().task_panics, 3);
+    }
+}
@@ -0,0 +1,451 @@
+//! Fault-injection HTTP server for integration tests
+//!
+//! [`FaultServer`] listens on a local port and answers every request after a
+//! controllable delay with a chosen status code, or drops the connection
+//! without answering to simulate loss. Tests can point `NetworkTester` or
+//! `ProbeRunner` at it instead of a real endpoint. Loss and jitter are drawn
+//! from a seeded generator, so a given request sequence always sees the same
+//! faults. [`MockTransport`] goes further and replaces the network entirely
+//! with scripted request timings. Enabled by the `test-util` feature.
+
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use futures::future::BoxFuture;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::error::Result;
+use crate::models::{Endpoint, ProbeType};
+use crate::network::RequestTiming;
+use crate::transport::HttpTransport;
+
+/// Body of every response
+const RESPONSE_BODY: &str = "pong";
+
+/// Largest request head read before answering
+const MAX_REQUEST_HEAD: usize = 16 * 1024;
+
+/// Faults a [`FaultServer`] injects
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultConfig {
+    /// Delay before every response
+    pub latency: Duration,
+    /// Maximum random deviation added to or subtracted from `latency`
+    pub jitter: Duration,
+    /// Probability from 0 to 1 that a request is dropped without a response
+    pub loss_rate: f64,
+    /// Status code of every response
+    pub status: u16,
+    /// Seed for the loss and jitter draws
+    pub seed: u64,
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        Self {
+            // Sub-millisecond round trips count as failures
+            latency: Duration::from_millis(5),
+            jitter: Duration::ZERO,
+            loss_rate: 0.0,
+            status: 200,
+            seed: 0,
+        }
+    }
+}
+
+impl FaultConfig {
+    /// Set the delay before every response
+    #[must_use]
+    pub const fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Set the maximum random deviation from the latency
+    #[must_use]
+    pub const fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set the probability that a request is dropped, clamped to 0..=1
+    #[must_use]
+    pub fn loss_rate(mut self, loss_rate: f64) -> Self {
+        self.loss_rate = loss_rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the status code of every response
+    #[must_use]
+    pub const fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Set the seed for loss and jitter
+    #[must_use]
+    pub const fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+/// What the server does with one request
+enum Fault {
+    Drop,
+    Respond { delay: Duration, status: u16 },
+}
+
+/// Configuration and counters shared with the accept loop
+#[derive(Debug)]
+struct FaultState {
+    config: Mutex<FaultConfig>,
+    rng: Mutex<StdRng>,
+    requests: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl FaultState {
+    fn next_fault(&self) -> Fault {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let config = self
+            .config
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let mut rng = self.rng.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if rng.gen_bool(config.loss_rate) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return Fault::Drop;
+        }
+
+        let jitter = config.jitter.as_secs_f64();
+        let offset = if jitter > 0.0 {
+            rng.gen_range(-jitter..=jitter)
+        } else {
+            0.0
+        };
+        drop(rng);
+
+        Fault::Respond {
+            delay: Duration::from_secs_f64((config.latency.as_secs_f64() + offset).max(0.0)),
+            status: config.status,
+        }
+    }
+}
+
+/// Local HTTP server with controllable latency, jitter, loss and status codes
+///
+/// The server stops when dropped.
+#[derive(Debug)]
+pub struct FaultServer {
+    addr: SocketAddr,
+    state: Arc<FaultState>,
+    task: JoinHandle<()>,
+}
+
+impl FaultServer {
+    /// Start a server on a free port of the loopback interface
+    pub async fn start(config: FaultConfig) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(FaultState {
+            rng: Mutex::new(StdRng::seed_from_u64(config.seed)),
+            config: Mutex::new(config),
+            requests: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        });
+
+        let task = tokio::spawn(Self::accept_loop(listener, Arc::clone(&state)));
+        Ok(Self { addr, state, task })
+    }
+
+    /// Address the server listens on
+    #[must_use]
+    pub const fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// URL of the server's root, for `NetworkTester`
+    #[must_use]
+    pub fn url(&self) -> String {
+        format!("http://{}/", self.addr)
+    }
+
+    /// HTTP endpoint pointing at the server, for `ProbeRunner`
+    #[must_use]
+    pub fn endpoint(&self, id: &str) -> Endpoint {
+        Endpoint::new(
+            id.to_string(),
+            self.addr.ip().to_string(),
+            self.addr.port(),
+            ProbeType::HTTP,
+        )
+    }
+
+    /// Replace the injected faults, restarting the random draws from the new seed
+    pub fn set_config(&self, config: FaultConfig) {
+        *self
+            .state
+            .rng
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = StdRng::seed_from_u64(config.seed);
+        *self
+            .state
+            .config
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = config;
+    }
+
+    /// Requests received so far, including dropped ones
+    #[must_use]
+    pub fn request_count(&self) -> u64 {
+        self.state.requests.load(Ordering::Relaxed)
+    }
+
+    /// Requests dropped without a response
+    #[must_use]
+    pub fn dropped_count(&self) -> u64 {
+        self.state.dropped.load(Ordering::Relaxed)
+    }
+
+    async fn accept_loop(listener: TcpListener, state: Arc<FaultState>) {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(Self::handle(stream, Arc::clone(&state)));
+        }
+    }
+
+    /// Answer one request per connection, closing it afterwards
+    async fn handle(mut stream: TcpStream, state: Arc<FaultState>) {
+        let mut head = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+            match stream.read(&mut buffer).await {
+                Ok(0) | Err(_) => return,
+                Ok(read) => head.extend_from_slice(&buffer[..read]),
+            }
+            if head.len() > MAX_REQUEST_HEAD {
+                return;
+            }
+        }
+
+        let (delay, status) = match state.next_fault() {
+            Fault::Drop => return,
+            Fault::Respond { delay, status } => (delay, status),
+        };
+        tokio::time::sleep(delay).await;
+
+        let reason = StatusCode::from_u16(status)
+            .ok()
+            .and_then(|status| status.canonical_reason())
+            .unwrap_or("Unknown");
+        let body = if head.starts_with(b"HEAD ") {
+            ""
+        } else {
+            RESPONSE_BODY
+        };
+        let response = format!(
+            "HTTP/1.1 {status} {reason}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            RESPONSE_BODY.len()
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+        let _ = stream.shutdown().await;
+    }
+}
+
+impl Drop for FaultServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Scripted responses for URLs starting with a prefix
+#[derive(Debug)]
+struct MockRoute {
+    prefix: String,
+    timings: VecDeque<RequestTiming>,
+}
+
+/// Transport that answers from scripted timings without any network access
+///
+/// Requests are matched to the first route whose prefix starts their URL.
+/// Each route plays its timings in order and repeats the last one; requests
+/// matching no route fail.
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    routes: Mutex<Vec<MockRoute>>,
+    requests: Mutex<Vec<String>>,
+    delay: Duration,
+}
+
+impl MockTransport {
+    /// Create a transport with no routes
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer requests to URLs starting with `prefix` with `timings` in order
+    #[must_use]
+    pub fn respond(self, prefix: &str, timings: Vec<RequestTiming>) -> Self {
+        self.routes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(MockRoute {
+                prefix: prefix.to_string(),
+                timings: timings.into(),
+            });
+        self
+    }
+
+    /// Answer every request to URLs starting with `prefix` successfully after `latency`
+    #[must_use]
+    pub fn latency(self, prefix: &str, latency: Duration) -> Self {
+        self.respond(prefix, vec![RequestTiming::succeeded(latency, 200)])
+    }
+
+    /// Answer every request only after `delay` of tokio time, so tests that
+    /// pause the clock can cut requests short without waiting
+    #[must_use]
+    pub const fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// URLs requested so far, in order
+    #[must_use]
+    pub fn requests(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn next_timing(&self, url: &str) -> RequestTiming {
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(url.to_string());
+
+        let mut routes = self.routes.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(route) = routes
+            .iter_mut()
+            .find(|route| url.starts_with(&route.prefix))
+        else {
+            return RequestTiming::failed(Duration::ZERO, format!("No mock response for {url}"));
+        };
+        if route.timings.len() > 1 {
+            route.timings.pop_front().unwrap_or_else(|| {
+                RequestTiming::failed(Duration::ZERO, "Mock route has no timings")
+            })
+        } else {
+            route.timings.front().cloned().unwrap_or_else(|| {
+                RequestTiming::failed(Duration::ZERO, "Mock route has no timings")
+            })
+        }
+    }
+}
+
+impl HttpTransport for MockTransport {
+    fn send<'a>(&'a self, url: &'a str) -> BoxFuture<'a, RequestTiming> {
+        let timing = self.next_timing(url);
+        Box::pin(async move {
+            if !self.delay.is_zero() {
+                tokio::time::sleep(self.delay).await;
+            }
+            timing
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::network::NetworkTester;
+
+    fn tester() -> NetworkTester {
+        NetworkTester::new(AppConfig {
+            retry_attempts: 0,
+            timeout_ms: 1000,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_latency_and_status() {
+        let server = FaultServer::start(FaultConfig::default().latency(Duration::from_millis(30)))
+            .await
+            .unwrap();
+
+        let stats = tester().perform_ping_test(&server.url(), 3).await;
+        assert_eq!(stats.successful_pings, 3);
+        assert!(stats.min >= 30.0);
+
+        server.set_config(FaultConfig::default().status(503));
+        let stats = tester().perform_ping_test(&server.url(), 2).await;
+        assert_eq!(stats.successful_pings, 0);
+        assert_eq!(server.request_count(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_seeded_loss_is_repeatable() {
+        let config = FaultConfig::default().loss_rate(0.5).seed(11);
+        let mut outcomes = Vec::new();
+        for _ in 0..2 {
+            let server = FaultServer::start(config.clone()).await.unwrap();
+            let stats = tester().perform_ping_test(&server.url(), 10).await;
+            assert_eq!(server.request_count(), 10);
+            assert_eq!(
+                u64::try_from(stats.total_pings - stats.successful_pings).unwrap(),
+                server.dropped_count()
+            );
+            outcomes.push(server.dropped_count());
+        }
+
+        assert_eq!(outcomes[0], outcomes[1]);
+        assert!(outcomes[0] > 0 && outcomes[0] < 10);
+    }
+
+    #[tokio::test]
+    async fn test_mock_transport_plays_script() {
+        let transport = Arc::new(MockTransport::new().respond(
+            "https://eu.example.com",
+            vec![
+                RequestTiming::succeeded(Duration::from_millis(20), 200),
+                RequestTiming::failed(Duration::from_millis(3), "connection reset"),
+                RequestTiming::succeeded(Duration::from_millis(30), 200),
+            ],
+        ));
+        let tester = NetworkTester::builder()
+            .config(AppConfig {
+                retry_attempts: 0,
+                ..Default::default()
+            })
+            .transport(transport.clone())
+            .build()
+            .unwrap();
+
+        let stats = tester
+            .perform_ping_test("https://eu.example.com/ping", 4)
+            .await;
+        assert_eq!(stats.successful_pings, 3);
+        assert_eq!(stats.latencies, vec![20.0, 0.0, 30.0, 30.0]);
+        assert_eq!(stats.error_message, "All retry attempts failed");
+
+        // Requests carry the cache buster; unknown hosts fail
+        assert!(transport.requests()[0].contains("cache_buster="));
+        let stats = tester.perform_ping_test("https://us.example.com", 1).await;
+        assert_eq!(stats.successful_pings, 0);
+    }
+}
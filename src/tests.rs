@@ -5,7 +5,7 @@ mod tests {
     use crate::{
         config::AppConfig,
         error::CloudPingError,
-        models::{CloudProvider, PingStats, Region, AlgorithmWeights, AggregatorState, ProbeRecord, scoring},
+        models::{CloudProvider, PingStats, Region, AlgorithmWeights, AggregatorState, ProbeRecord, ScoringAdapter, TestHistory, scoring},
         network::NetworkTester,
     };
     use tempfile::NamedTempFile;
@@ -30,6 +30,7 @@ mod tests {
             output_format: crate::OutputFormat::Json,
             user_agent: "test-agent".to_string(),
             validate_certificates: false,
+            ..AppConfig::default()
         }
     }
 
@@ -69,6 +70,87 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_config_profile_overrides_base_settings() {
+        use config::{Config, File, FileFormat};
+        use std::time::Duration;
+
+        let toml = r#"
+            retry_attempts = 4
+
+            [profiles.thorough]
+            default_ping_count = 50
+            timeout = "10s"
+
+            [profiles.gaming]
+            quick_ping_count = 5
+            output_format = "json"
+            weights = { latency = 0.5, jitter = 0.3, packet_loss = 0.2, consistency = 0.0, availability = 0.0 }
+        "#;
+        let mut config: AppConfig = Config::builder()
+            .add_source(Config::try_from(&AppConfig::default()).unwrap())
+            .add_source(File::from_str(toml, FileFormat::Toml))
+            .build()
+            .and_then(Config::try_deserialize)
+            .unwrap();
+        assert_eq!(config.profiles.len(), 2);
+
+        config.apply_profile("thorough").unwrap();
+        assert_eq!(config.default_ping_count, 50);
+        assert_eq!(config.timeout, Duration::from_secs(10));
+        assert_eq!(config.timeout_ms, 10_000);
+        // Settings the profile leaves out keep their base values
+        assert_eq!(config.retry_attempts, 4);
+        assert!(config.weights.is_none());
+
+        config.apply_profile("gaming").unwrap();
+        assert_eq!(config.quick_ping_count, 5);
+        assert!(matches!(config.output_format, crate::OutputFormat::Json));
+        assert!((config.weights.as_ref().unwrap().latency - 0.5).abs() < f64::EPSILON);
+        assert!(config.validate().is_ok());
+
+        let error = config.apply_profile("missing").unwrap_err().to_string();
+        assert!(error.contains("gaming, thorough"));
+    }
+
+    #[test]
+    fn test_env_vars_map_underscored_and_nested_keys() {
+        use config::Config;
+        use std::collections::HashMap;
+        use std::time::Duration;
+
+        let vars: HashMap<String, String> = [
+            ("CLOUD_PING_TIMEOUT_MS", "3000"),
+            ("CLOUD_PING_SHOW_PROGRESS", "false"),
+            ("CLOUD_PING_MONITORING__PROBE__INTERVAL", "15s"),
+            ("CLOUD_PING_MONITORING__AGGREGATOR__SHORT_WINDOW", "30"),
+            ("CLOUD_PING_LABELS__ISP", "comcast"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+
+        let config: AppConfig = Config::builder()
+            .add_source(Config::try_from(&AppConfig::default()).unwrap())
+            .add_source(AppConfig::environment(Some(vars.clone())))
+            .build()
+            .and_then(Config::try_deserialize)
+            .unwrap();
+        assert_eq!(config.timeout_ms, 3000);
+        assert!(!config.show_progress);
+        assert_eq!(config.monitoring.probe.interval, Duration::from_secs(15));
+        assert_eq!(config.monitoring.aggregator.short_window, 30);
+        assert_eq!(config.labels["isp"], "comcast");
+
+        // Misspelt keys are reported rather than silently ignored
+        let unknown = AppConfig::unknown_env_vars(
+            vars.into_iter()
+                .chain([("CLOUD_PING_TIMEOUT_MSS".to_string(), "1".to_string())])
+                .chain([("HOME".to_string(), "/root".to_string())]),
+        );
+        assert_eq!(unknown, vec!["CLOUD_PING_TIMEOUT_MSS".to_string()]);
+    }
+
     #[test]
     fn test_scoring_weights_validation() {
         let mut weights = AlgorithmWeights::default();
@@ -203,6 +285,237 @@ mod tests {
         assert!(benchmark.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_benchmark_ranks_mocked_regions() {
+        use crate::test_util::MockTransport;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let transport = MockTransport::new()
+            .latency("https://fast.example.com", Duration::from_millis(15))
+            .latency("https://slow.example.com", Duration::from_millis(180));
+        let benchmark = crate::ConnectionBenchmark::builder(create_test_config())
+            .transport(Arc::new(transport))
+            .build()
+            .unwrap();
+        let regions: Vec<Region> = serde_json::from_str(
+            r#"[{"name": "slow", "url": "https://slow.example.com"},
+                {"name": "fast", "url": "https://fast.example.com"}]"#,
+        )
+        .unwrap();
+
+        let results = benchmark.test_regions_concurrently(&regions, 3).await.unwrap();
+        let ranked = ScoringAdapter::get_sorted_results(&results, &AlgorithmWeights::default());
+        assert_eq!(ranked[0].1, "fast");
+        assert!((ranked[0].2.avg - 15.0).abs() < f64::EPSILON);
+        assert!(ranked[0].0 > ranked[1].0);
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_regions_start_first_with_more_pings() {
+        use crate::test_util::MockTransport;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let transport = Arc::new(
+            MockTransport::new()
+                .latency("https://low.example.com", Duration::from_millis(20))
+                .latency("https://high.example.com", Duration::from_millis(20)),
+        );
+        let config = AppConfig {
+            max_threads: 1,
+            priority_scaling: true,
+            ..create_test_config()
+        };
+        let benchmark = crate::ConnectionBenchmark::builder(config)
+            .transport(transport.clone())
+            .build()
+            .unwrap();
+        let regions: Vec<Region> = serde_json::from_str(
+            r#"[{"name": "low", "url": "https://low.example.com", "priority": 0.5},
+                {"name": "high", "url": "https://high.example.com", "priority": 2.0}]"#,
+        )
+        .unwrap();
+
+        let results = benchmark.test_regions_concurrently(&regions, 2).await.unwrap();
+        assert!(transport.requests()[0].starts_with("https://high.example.com"));
+        assert_eq!(results[0].0, "high");
+        assert_eq!(results[0].1.total_pings, 4);
+        assert!((results[0].1.priority - 2.0).abs() < f64::EPSILON);
+        assert_eq!(results[1].1.total_pings, 1);
+    }
+
+    #[tokio::test]
+    async fn test_latencies_are_normalized_against_control() {
+        use crate::control::ControlConfig;
+        use crate::test_util::MockTransport;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let transport = MockTransport::new()
+            .latency("https://control.example.com", Duration::from_millis(10))
+            .latency("https://near.example.com", Duration::from_millis(30))
+            .latency("https://far.example.com", Duration::from_millis(50));
+        let config = AppConfig {
+            control: ControlConfig {
+                url: Some("https://control.example.com".to_string()),
+                interval: Duration::from_millis(5),
+            },
+            ..create_test_config()
+        };
+        let benchmark = crate::ConnectionBenchmark::builder(config)
+            .transport(Arc::new(transport))
+            .build()
+            .unwrap();
+        let regions: Vec<Region> = serde_json::from_str(
+            r#"[{"name": "near", "url": "https://near.example.com"},
+                {"name": "far", "url": "https://far.example.com"}]"#,
+        )
+        .unwrap();
+
+        let report = benchmark
+            .test_regions_until(&regions, 2, &tokio_util::sync::CancellationToken::new())
+            .await
+            .unwrap();
+        let control = report.control.unwrap();
+        assert_eq!(control.baseline_ms(), Some(10.0));
+        for (name, stats) in &report.results {
+            let expected = if name == "near" { 20.0 } else { 40.0 };
+            assert!((stats.avg - stats.normalized_avg.unwrap() - 10.0).abs() < f64::EPSILON);
+            assert_eq!(stats.normalized_avg, Some(expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_region_is_reported_with_its_cause() {
+        use crate::models::FailureKind;
+        use crate::network::RequestTiming;
+        use crate::test_util::MockTransport;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let transport = MockTransport::new()
+            .latency("https://up.example.com", Duration::from_millis(20))
+            .respond(
+                "https://down.example.com",
+                vec![RequestTiming::failed(
+                    Duration::from_millis(2),
+                    "tcp connect error: Connection refused",
+                )],
+            );
+        let benchmark = crate::ConnectionBenchmark::builder(create_test_config())
+            .transport(Arc::new(transport))
+            .build()
+            .unwrap();
+        let regions: Vec<Region> = serde_json::from_str(
+            r#"[{"name": "up", "url": "https://up.example.com"},
+                {"name": "down", "url": "https://down.example.com"}]"#,
+        )
+        .unwrap();
+
+        let report = benchmark
+            .test_regions_until(&regions, 2, &tokio_util::sync::CancellationToken::new())
+            .await
+            .unwrap();
+        assert!(!report.incomplete);
+        assert_eq!(report.results.len(), 2);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].region, "down");
+        assert_eq!(report.failures[0].kind, FailureKind::Connection);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_deadline_returns_partial_results() {
+        use crate::test_util::MockTransport;
+        use std::io::Write;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let mut data_file = NamedTempFile::new().unwrap();
+        write!(
+            data_file,
+            r#"{{"Local": {{"regions": [
+                {{"name": "first", "url": "https://first.example.com"}},
+                {{"name": "second", "url": "https://second.example.com"}}]}}}}"#
+        )
+        .unwrap();
+
+        let config = AppConfig {
+            max_threads: 1,
+            run_deadline: Some(Duration::from_millis(350)),
+            ..create_test_config()
+        };
+        let latency = Duration::from_millis(100);
+        let transport = MockTransport::new().latency("https://", latency).delay(latency);
+        let mut benchmark = crate::ConnectionBenchmark::builder(config)
+            .transport(Arc::new(transport))
+            .build()
+            .unwrap();
+        benchmark
+            .load_cloud_providers(data_file.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let cancel = tokio_util::sync::CancellationToken::new();
+        let report = benchmark
+            .run_filtered_benchmark_until(10, None, None, &cancel)
+            .await
+            .unwrap();
+
+        // The first region is cut short and the second never starts
+        assert!(cancel.is_cancelled());
+        assert!(report.incomplete);
+        assert_eq!(report.results.len(), 1);
+        assert!(report.results[0].1.incomplete);
+        assert!(report.results[0].1.total_pings < 10);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].region, "second");
+        assert_eq!(report.failures[0].kind, crate::models::FailureKind::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_run_labels_are_saved_with_history() {
+        use crate::test_util::MockTransport;
+        use std::io::Write;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let mut data_file = NamedTempFile::new().unwrap();
+        write!(
+            data_file,
+            r#"{{"Local": {{"regions": [{{"name": "near", "url": "https://near.example.com"}}]}}}}"#
+        )
+        .unwrap();
+
+        let mut config = create_test_config();
+        config.labels.insert("isp".to_string(), "comcast".to_string());
+        config.labels.insert("location".to_string(), "office".to_string());
+        let transport =
+            MockTransport::new().latency("https://near.example.com", Duration::from_millis(20));
+        let mut benchmark = crate::ConnectionBenchmark::builder(config)
+            .transport(Arc::new(transport))
+            .build()
+            .unwrap();
+        benchmark
+            .load_cloud_providers(data_file.path().to_str().unwrap())
+            .await
+            .unwrap();
+
+        let run = benchmark
+            .run_benchmark_with_environment(2, None, None, &tokio_util::sync::CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(run.labels["location"], "office");
+        assert_eq!(run.results[0].1.labels, run.labels);
+
+        // The labels survive a round trip through the history file
+        let history_file = NamedTempFile::new().unwrap();
+        crate::models::TestHistory::save_all(&benchmark.get_all_test_histories(), history_file.path()).unwrap();
+        let histories = crate::models::TestHistory::load_all(history_file.path()).unwrap();
+        assert_eq!(histories.len(), 1);
+        assert_eq!(histories[0].results_by_label("isp")["comcast"].len(), 1);
+    }
+
     #[test]
     fn test_output_format_serialization() {
         use crate::OutputFormat;
@@ -273,6 +586,108 @@ mod tests {
         assert_eq!(stats.get_performance_category(), "Unreachable");
     }
 
+    #[test]
+    fn test_region_health_status() {
+        use crate::models::HealthStatus;
+
+        let mut stats = PingStats::new(10);
+        stats.successful_pings = 10;
+        stats.avg = 15.0;
+        stats.jitter = 3.0;
+        assert_eq!(stats.health_status(), HealthStatus::Excellent);
+
+        stats.avg = 150.0;
+        assert_eq!(stats.health_status(), HealthStatus::Fair);
+
+        stats.successful_pings = 0;
+        stats.packet_loss = 100.0;
+        assert_eq!(stats.health_status(), HealthStatus::Critical);
+        assert_eq!(PingStats::new(0).health_status(), HealthStatus::Unknown);
+    }
+
+    #[test]
+    fn test_ascii_only_display() {
+        use crate::models::HealthStatus;
+        use crate::ui_utils::DisplayUtils;
+        use tabled::{builder::Builder, Table};
+
+        let table = |builder: Builder| {
+            let mut table: Table = builder.build();
+            DisplayUtils::style_table(&mut table).to_string()
+        };
+        let mut builder = Builder::default();
+        builder.push_record(["Region", "≤ 1 Tick"]);
+        builder.push_record(["Frankfurt", "98.0%"]);
+
+        DisplayUtils::set_ascii_only(true);
+        let ascii = table(builder.clone());
+        let health = DisplayUtils::format_health(HealthStatus::Poor);
+        let sparkline = DisplayUtils::sparkline(&[10.0, 20.0, 40.0]);
+        DisplayUtils::set_ascii_only(false);
+
+        assert!(ascii.is_ascii(), "{ascii}");
+        assert!(ascii.contains("<= 1 Tick"));
+        assert_eq!(health, "[-] Poor");
+        assert_eq!(sparkline, "_,#");
+        assert!(!table(builder).is_ascii());
+    }
+
+    #[test]
+    fn test_tables_fit_terminal_width() {
+        use crate::ui_utils::DisplayUtils;
+        use tabled::builder::Builder;
+
+        let mut builder = Builder::default();
+        builder.push_record(["Region", "Score", "Gaming", "Streaming"]);
+        builder.push_record(["Frankfurt (eu-central-1)", "91.2", "88.0", "95.5"]);
+        let full = builder.clone().build().to_string();
+        let width = |text: &str| text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+
+        // Columns go in drop order until the table fits
+        let mut table = builder.clone().build();
+        let fitted = DisplayUtils::fit_table_to(&mut table, 40, &["Streaming", "Missing", "Gaming"])
+            .to_string();
+        assert!(width(&fitted) <= 40, "{fitted}");
+        assert!(!fitted.contains("Streaming") && !fitted.contains("Gaming"));
+        assert!(fitted.contains("Frankfurt (eu-central-1)"));
+
+        // Without droppable columns the widest column is truncated
+        let mut table = builder.build();
+        let truncated = DisplayUtils::fit_table_to(&mut table, 30, &[]).to_string();
+        assert!(width(&truncated) <= 30, "{truncated}");
+        assert!(!truncated.contains("Frankfurt (eu-central-1)"));
+        assert!(width(&full) > 40);
+    }
+
+    #[test]
+    fn test_history_sparkline() {
+        use crate::ui_utils::DisplayUtils;
+
+        let mut history = TestHistory::new(
+            "eu".to_string(),
+            "Frankfurt".to_string(),
+            "https://eu.example.com".to_string(),
+        );
+        for (hours, avg) in [(0, 30.0), (1, 0.0), (2, 20.0), (3, 45.0)] {
+            let mut stats = PingStats::new(4);
+            stats.successful_pings = if avg > 0.0 { 4 } else { 0 };
+            stats.avg = avg;
+            stats.test_time += chrono::Duration::hours(hours);
+            history.add_test_result(stats);
+        }
+
+        // Unreached runs are skipped and the newest runs kept, oldest first
+        assert_eq!(history.recent_latencies(2), [20.0, 45.0]);
+        assert_eq!(history.recent_latencies(10), [30.0, 20.0, 45.0]);
+        assert_eq!(DisplayUtils::sparkline(&history.recent_latencies(10)).chars().count(), 3);
+        assert!(DisplayUtils::sparkline(&[30.0]).is_empty());
+
+        let histories = [history];
+        let stats = PingStats::new(4);
+        assert!(TestHistory::find(&histories, "Frankfurt", &stats).is_some());
+        assert!(TestHistory::find(&histories, "Paris", &stats).is_none());
+    }
+
     #[test]
     fn test_qos_grade_calculation() {
         let weights = AlgorithmWeights::default();
@@ -1,7 +1,11 @@
 //! Time and duration utilities - thin wrappers for consistency
 
 use chrono::{DateTime, Utc};
-use std::time::Duration;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// Instant monotonic times are measured from
+static MONOTONIC_ORIGIN: OnceLock<Instant> = OnceLock::new();
 
 /// Time utilities - mostly re-exports for consistency
 pub struct TimeUtils;
@@ -13,6 +17,14 @@ impl TimeUtils {
         Utc::now()
     }
 
+    /// Nanoseconds since the first call in this process, from a clock that
+    /// only moves forward, whatever NTP or an operator does to the wall clock
+    #[inline]
+    pub fn monotonic_ns() -> u64 {
+        let origin = MONOTONIC_ORIGIN.get_or_init(Instant::now);
+        u64::try_from(origin.elapsed().as_nanos()).unwrap_or(u64::MAX)
+    }
+
     /// Create duration from milliseconds
     #[inline]
     pub const fn duration_from_millis(ms: u64) -> Duration {
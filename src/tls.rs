@@ -0,0 +1,205 @@
+//! TLS client configuration with timed certificate validation
+//!
+//! With `validate_certificates` on, the HTTP client is given a rustls
+//! configuration of its own instead of the one reqwest builds, so that
+//! certificate validation can be wrapped in a timer. The configuration
+//! mirrors reqwest's: the webpki root certificates, the ring crypto
+//! provider, and the version, ALPN and client certificate `tls` settings.
+//!
+//! [`server_config`] builds the configuration of the HTTP API listener from
+//! a PEM certificate chain and key, with the same crypto provider.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, ServerConfig, SignatureScheme};
+
+use crate::error::{CloudPingError, Result};
+use crate::models::CertValidation;
+use crate::transport::{TlsConfig, TlsVersion};
+
+/// Latest certificate validation per server name, shared between the
+/// verifier of a client and the transport sending through it
+#[derive(Debug, Clone, Default)]
+pub struct CertValidations {
+    latest: Arc<Mutex<HashMap<String, CertValidation>>>,
+}
+
+impl CertValidations {
+    /// Take the validation recorded for `server_name` since the last call
+    #[must_use]
+    pub fn take(&self, server_name: &str) -> Option<CertValidation> {
+        self.latest
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(server_name)
+    }
+
+    fn record(&self, server_name: String, validation: CertValidation) {
+        self.latest
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(server_name, validation);
+    }
+}
+
+/// Certificate verifier timing the webpki verifier it wraps
+#[derive(Debug)]
+struct TimedVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    validations: CertValidations,
+}
+
+impl ServerCertVerifier for TimedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let start = Instant::now();
+        let verified =
+            self.inner
+                .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now);
+        self.validations.record(
+            server_name.to_str().into_owned(),
+            CertValidation {
+                validation_ms: start.elapsed().as_secs_f64() * 1000.0,
+                ocsp_stapled: !ocsp_response.is_empty(),
+                chain_length: 1 + intermediates.len(),
+            },
+        );
+        verified
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Client configuration for `tls` that records each certificate validation in `validations`
+///
+/// # Errors
+/// Returns an error when the client certificate or key cannot be read or parsed
+pub fn timed_client_config(tls: &TlsConfig, validations: &CertValidations) -> Result<ClientConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let versions: &[&rustls::SupportedProtocolVersion] = match tls.min_version {
+        Some(TlsVersion::Tls13) => &[&rustls::version::TLS13],
+        Some(TlsVersion::Tls12) | None => rustls::DEFAULT_VERSIONS,
+    };
+    let roots: RootCertStore = webpki_roots::TLS_SERVER_ROOTS.iter().cloned().collect();
+    let verifier = TimedVerifier {
+        inner: WebPkiServerVerifier::builder_with_provider(Arc::new(roots), Arc::clone(&provider))
+            .build()
+            .map_err(|e| CloudPingError::config(format!("Invalid TLS verification settings: {e}")))?,
+        validations: validations.clone(),
+    };
+
+    let builder = ClientConfig::builder_with_provider(Arc::clone(&provider))
+        .with_protocol_versions(versions)
+        .map_err(|e| CloudPingError::config(format!("Invalid TLS versions: {e}")))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier));
+    let mut config = match tls.client_identity_pem()? {
+        Some(pem) => {
+            let certs = CertificateDer::pem_slice_iter(&pem)
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|e| CloudPingError::config(format!("Invalid client certificate: {e}")))?;
+            let key = PrivateKeyDer::from_pem_slice(&pem)
+                .map_err(|e| CloudPingError::config(format!("Invalid client key: {e}")))?;
+            builder
+                .with_client_auth_cert(certs, key)
+                .map_err(|e| CloudPingError::config(format!("Invalid client certificate: {e}")))?
+        }
+        None => builder.with_no_client_auth(),
+    };
+    config.alpn_protocols = tls.alpn_protocols();
+    Ok(config)
+}
+
+/// Server configuration presenting the PEM certificate chain in `cert` with
+/// the private key in `key`, offering HTTP/2 and HTTP/1.1
+///
+/// # Errors
+/// Returns an error when either file cannot be read or parsed, or the key
+/// does not match the certificate
+pub fn server_config(cert: &std::path::Path, key: &std::path::Path) -> Result<ServerConfig> {
+    let read = |path: &std::path::Path| {
+        std::fs::read(path)
+            .map_err(|e| CloudPingError::config(format!("Cannot read {}: {e}", path.display())))
+    };
+    let certs = CertificateDer::pem_slice_iter(&read(cert)?)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| CloudPingError::config(format!("Invalid server certificate: {e}")))?;
+    let key = PrivateKeyDer::from_pem_slice(&read(key)?)
+        .map_err(|e| CloudPingError::config(format!("Invalid server key: {e}")))?;
+
+    let mut config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_protocol_versions(rustls::DEFAULT_VERSIONS)
+        .map_err(|e| CloudPingError::config(format!("Invalid TLS versions: {e}")))?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| CloudPingError::config(format!("Invalid server certificate: {e}")))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timed_verifier_records_validation() {
+        let validations = CertValidations::default();
+        let config = timed_client_config(&TlsConfig::default(), &validations).unwrap();
+        assert_eq!(config.alpn_protocols, [b"http/1.1".to_vec()]);
+
+        let roots: RootCertStore = webpki_roots::TLS_SERVER_ROOTS.iter().cloned().collect();
+        let verifier = TimedVerifier {
+            inner: WebPkiServerVerifier::builder_with_provider(
+                Arc::new(roots),
+                Arc::new(rustls::crypto::ring::default_provider()),
+            )
+            .build()
+            .unwrap(),
+            validations: validations.clone(),
+        };
+        let leaf = CertificateDer::from(vec![0x30, 0x00]);
+        let issuer = CertificateDer::from(vec![0x30, 0x00]);
+        let server_name = ServerName::try_from("api.example.com").unwrap();
+        assert!(verifier
+            .verify_server_cert(&leaf, &[issuer], &server_name, b"staple", UnixTime::now())
+            .is_err());
+
+        let validation = validations.take("api.example.com").unwrap();
+        assert!(validation.ocsp_stapled);
+        assert_eq!(validation.chain_length, 2);
+        assert!(validation.validation_ms >= 0.0);
+        assert_eq!(validations.take("api.example.com"), None);
+    }
+}
@@ -0,0 +1,116 @@
+//! Sampling of per-probe and per-request trace logs
+//!
+//! Every monitoring probe and benchmark request attempt runs inside a
+//! tracing span carrying its endpoint, attempt, round trip and outcome, so
+//! the debug logs emitted while it runs can be told apart from those of
+//! other probes. A log line per probe would drown everything else at
+//! monitoring rates, so only a sampled fraction of them log a "finished"
+//! event with the span's fields. Probes at least as slow as the slow
+//! threshold always log, so a single slow probe can still be followed.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::Span;
+
+use crate::error::{CloudPingError, Result};
+
+/// Which finished probes and requests log their span, read from `[trace_sampling]`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct TraceSampling {
+    /// Fraction of probes and requests that log when they finish, from 0 to 1
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+    /// Probes and requests taking at least this long always log
+    #[serde(with = "humantime_serde", default = "default_slow_threshold")]
+    pub slow_threshold: Option<Duration>,
+}
+
+const fn default_sample_rate() -> f64 {
+    0.01
+}
+
+#[allow(clippy::unnecessary_wraps)]
+const fn default_slow_threshold() -> Option<Duration> {
+    Some(Duration::from_secs(1))
+}
+
+impl Default for TraceSampling {
+    fn default() -> Self {
+        Self {
+            sample_rate: default_sample_rate(),
+            slow_threshold: default_slow_threshold(),
+        }
+    }
+}
+
+impl TraceSampling {
+    /// # Errors
+    /// Returns a validation error for a rate outside 0 to 1 or a zero slow threshold
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.sample_rate) {
+            return Err(CloudPingError::validation(
+                "trace_sampling.sample_rate",
+                "must be between 0 and 1",
+            ));
+        }
+        if self.slow_threshold.is_some_and(|threshold| threshold.is_zero()) {
+            return Err(CloudPingError::validation(
+                "trace_sampling.slow_threshold",
+                "must be greater than 0",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether a probe or request that took `elapsed` logs when it finishes
+    #[must_use]
+    pub fn should_log(&self, elapsed: Duration) -> bool {
+        self.slow_threshold.is_some_and(|threshold| elapsed >= threshold)
+            || rand::random::<f64>() < self.sample_rate
+    }
+
+    /// Record the round trip and outcome on `span` and log it finishing
+    /// when it is sampled or slow
+    pub fn finish(&self, span: &Span, elapsed: Duration, rtt_ms: Option<f64>, outcome: &str) {
+        if let Some(rtt_ms) = rtt_ms {
+            span.record("rtt_ms", rtt_ms);
+        }
+        span.record("outcome", outcome);
+        if self.should_log(elapsed) {
+            // The span's name and fields prefix the event
+            span.in_scope(|| tracing::info!("finished"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampling() {
+        let never = TraceSampling {
+            sample_rate: 0.0,
+            slow_threshold: None,
+        };
+        assert!(!never.should_log(Duration::from_secs(60)));
+        let always = TraceSampling {
+            sample_rate: 1.0,
+            ..never
+        };
+        assert!(always.should_log(Duration::ZERO));
+
+        // Slow probes log whatever the rate
+        let sampling = TraceSampling {
+            sample_rate: 0.0,
+            ..TraceSampling::default()
+        };
+        assert!(!sampling.should_log(Duration::from_millis(999)));
+        assert!(sampling.should_log(Duration::from_secs(1)));
+
+        assert!(TraceSampling { sample_rate: 1.5, ..sampling }.validate().is_err());
+        assert!(TraceSampling { slow_threshold: Some(Duration::ZERO), ..sampling }.validate().is_err());
+        assert!(sampling.validate().is_ok());
+    }
+}
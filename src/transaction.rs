@@ -0,0 +1,259 @@
+//! Running scripted transactions against regions
+//!
+//! Steps go through an HTTP client configured like the benchmark's, so the
+//! timeout, TLS and user agent settings apply to each step. Every step is
+//! timed from sending its request to reading the whole response; a run stops
+//! at the first step whose request fails, whose assertions do not hold or
+//! whose values cannot be extracted.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use reqwest::header::HeaderMap;
+use reqwest::{Client, Method, StatusCode};
+use tracing::debug;
+
+use crate::config::AppConfig;
+use crate::error::Result;
+use crate::models::transaction::render;
+use crate::models::{Assertion, Extract, Region, StepResult, Transaction, TransactionResult, TransactionStep};
+use crate::transport::ReqwestTransport;
+
+/// Response of a step: status, headers and body
+type StepResponse = (StatusCode, HeaderMap, String);
+
+/// Runs transactions against regions
+#[derive(Debug, Clone)]
+pub struct TransactionRunner {
+    client: Arc<Client>,
+}
+
+impl TransactionRunner {
+    /// Create a runner with a client configured from `config`
+    ///
+    /// # Errors
+    /// Returns an error when the HTTP client cannot be built
+    pub fn new(config: &AppConfig) -> Result<Self> {
+        Ok(Self::with_client(Arc::clone(ReqwestTransport::new(config)?.client())))
+    }
+
+    /// Create a runner that sends through an existing client
+    #[must_use]
+    pub const fn with_client(client: Arc<Client>) -> Self {
+        Self { client }
+    }
+
+    /// Run `transaction` against `region`, stopping at the first failed step
+    pub async fn run(&self, transaction: &Transaction, region: &Region) -> TransactionResult {
+        let mut variables = HashMap::from([
+            ("region_url".to_string(), region.url.trim_end_matches('/').to_string()),
+            ("region".to_string(), region.name.clone()),
+        ]);
+        let start = Instant::now();
+        let mut steps = Vec::with_capacity(transaction.steps.len());
+        for step in &transaction.steps {
+            let result = self.run_step(step, &mut variables).await;
+            let failed = result.error.is_some();
+            steps.push(result);
+            if failed {
+                break;
+            }
+        }
+        debug!(
+            "Transaction {} against {} ran {} of {} steps",
+            transaction.name,
+            region.name,
+            steps.len(),
+            transaction.steps.len()
+        );
+        TransactionResult {
+            transaction: transaction.name.clone(),
+            region: region.name.clone(),
+            steps,
+            step_count: transaction.steps.len(),
+            total_ms: elapsed_ms(start),
+        }
+    }
+
+    async fn run_step(
+        &self,
+        step: &TransactionStep,
+        variables: &mut HashMap<String, String>,
+    ) -> StepResult {
+        let start = Instant::now();
+        let response = self.send(step, variables).await;
+        let duration_ms = elapsed_ms(start);
+
+        let mut result = StepResult {
+            name: step.name.clone(),
+            status: None,
+            duration_ms,
+            error: None,
+        };
+        match response {
+            Ok((status, headers, body)) => {
+                result.status = Some(status.as_u16());
+                result.error = check(step, status, &body, duration_ms)
+                    .and_then(|()| extract(step, &headers, &body, variables))
+                    .err();
+            }
+            Err(error) => result.error = Some(error),
+        }
+        result
+    }
+
+    async fn send(
+        &self,
+        step: &TransactionStep,
+        variables: &HashMap<String, String>,
+    ) -> std::result::Result<StepResponse, String> {
+        let method = Method::from_bytes(step.method.to_uppercase().as_bytes())
+            .map_err(|_| format!("`{}` is not an HTTP method", step.method))?;
+        let mut request = self.client.request(method, render(&step.url, variables)?);
+        for (name, value) in &step.headers {
+            request = request.header(name, render(value, variables)?);
+        }
+        if let Some(body) = &step.body {
+            request = request.body(render(body, variables)?);
+        }
+
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text().await.map_err(|e| e.to_string())?;
+        Ok((status, headers, body))
+    }
+}
+
+/// Check the step's assertions, or for a step without any that the status is a success
+fn check(
+    step: &TransactionStep,
+    status: StatusCode,
+    body: &str,
+    duration_ms: f64,
+) -> std::result::Result<(), String> {
+    if step.assert.is_empty() && !status.is_success() {
+        return Err(format!("unsuccessful status {}", status.as_u16()));
+    }
+    for assertion in &step.assert {
+        match assertion {
+            Assertion::Status(expected) if status.as_u16() != *expected => {
+                return Err(format!("expected status {expected}, got {}", status.as_u16()));
+            }
+            Assertion::BodyContains(text) if !body.contains(text.as_str()) => {
+                return Err(format!("body does not contain `{text}`"));
+            }
+            #[allow(clippy::cast_precision_loss)] // limits are far below 2^52 ms
+            Assertion::MaxMs(limit) if duration_ms > *limit as f64 => {
+                return Err(format!("took {duration_ms:.1}ms, over the {limit}ms limit"));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Store the values the step extracts from its response in `variables`
+fn extract(
+    step: &TransactionStep,
+    headers: &HeaderMap,
+    body: &str,
+    variables: &mut HashMap<String, String>,
+) -> std::result::Result<(), String> {
+    let mut json = None;
+    for (name, source) in &step.extract {
+        let value = match source {
+            Extract::Json(pointer) => {
+                if json.is_none() {
+                    json = Some(
+                        serde_json::from_str::<serde_json::Value>(body)
+                            .map_err(|e| format!("cannot extract `{name}`: body is not JSON: {e}"))?,
+                    );
+                }
+                match json.as_ref().and_then(|json| json.pointer(pointer)) {
+                    Some(serde_json::Value::String(value)) => value.clone(),
+                    Some(value) => value.to_string(),
+                    None => return Err(format!("cannot extract `{name}`: no value at {pointer}")),
+                }
+            }
+            Extract::Header(header) => headers
+                .get(header)
+                .and_then(|value| value.to_str().ok())
+                .ok_or_else(|| format!("cannot extract `{name}`: no {header} header"))?
+                .to_string(),
+        };
+        variables.insert(name.clone(), value);
+    }
+    Ok(())
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderMap as AxumHeaders;
+    use axum::routing::{get, post};
+
+    #[tokio::test]
+    async fn test_transaction_passes_extracted_values_on() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = axum::Router::new()
+            .route("/auth", post(|| async { r#"{"access_token": "abc", "ttl": 60}"# }))
+            .route(
+                "/items",
+                get(|headers: AxumHeaders| async move {
+                    if headers.get("authorization").is_some_and(|value| value == "Bearer abc") {
+                        (axum::http::StatusCode::OK, "items: 3")
+                    } else {
+                        (axum::http::StatusCode::UNAUTHORIZED, "no token")
+                    }
+                }),
+            );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let mut transaction: Transaction = toml::from_str(
+            r#"
+            name = "flow"
+
+            [[steps]]
+            name = "login"
+            method = "POST"
+            url = "{{region_url}}/auth"
+            extract = { token = { json = "/access_token" }, ttl = { json = "/ttl" } }
+
+            [[steps]]
+            name = "list"
+            url = "{{region_url}}/items?ttl={{ttl}}"
+            headers = { authorization = "Bearer {{token}}" }
+            assert = [{ status = 200 }, { body_contains = "items" }]
+            "#,
+        )
+        .unwrap();
+        transaction.validate().unwrap();
+        let region = Region::new("local".to_string(), format!("http://{addr}/")).unwrap();
+        let runner = TransactionRunner::new(&AppConfig::default()).unwrap();
+
+        let result = runner.run(&transaction, &region).await;
+        assert!(result.is_successful(), "{result:?}");
+        assert_eq!(result.steps[1].status, Some(200));
+        let step_ms: f64 = result.steps.iter().map(|step| step.duration_ms).sum();
+        assert!(result.total_ms >= step_ms);
+
+        // Without the token the second step is refused and the run stops there
+        transaction.steps[0].extract.remove("token");
+        transaction.steps[1].headers.clear();
+        transaction.steps.push(transaction.steps[1].clone());
+        let result = runner.run(&transaction, &region).await;
+        assert!(!result.is_successful());
+        assert_eq!(result.steps.len(), 2);
+        assert_eq!(
+            result.failed_step().unwrap().error.as_deref(),
+            Some("expected status 200, got 401")
+        );
+    }
+}
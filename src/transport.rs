@@ -0,0 +1,535 @@
+//! HTTP transports used by `NetworkTester`
+//!
+//! `NetworkTester` owns retries, rate limiting and statistics; a transport
+//! only sends a single request and times it. [`ReqwestTransport`] is the
+//! default. Tests can substitute a transport with scripted timings, such as
+//! `test_util::MockTransport`, to exercise benchmark and scoring flows
+//! without a network.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+
+use futures::future::BoxFuture;
+use hyper_util::client::legacy::connect::HttpInfo;
+use reqwest::{Client, ClientBuilder, Identity};
+use serde::{Deserialize, Serialize};
+use tokio::time::timeout;
+use tracing::{debug, error, warn};
+use url::Url;
+
+use crate::config::AppConfig;
+use crate::error::{CloudPingError, Result};
+use crate::format_utils::FormatUtils;
+use crate::models::{ConnectionUse, EdgePop, FailureKind};
+use crate::network::{NetworkTester, RequestTiming};
+use crate::tls::{timed_client_config, CertValidations};
+use crate::time_utils::TimeUtils;
+
+/// Sends one timed HTTP request
+pub trait HttpTransport: Debug + Send + Sync {
+    /// Send a GET request to `url` and report how it went
+    ///
+    /// Failures are reported through `RequestTiming::success` rather than
+    /// an error, so retries and statistics treat every outcome alike.
+    fn send<'a>(&'a self, url: &'a str) -> BoxFuture<'a, RequestTiming>;
+}
+
+/// Lowest TLS version the client negotiates
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TlsVersion {
+    /// TLS 1.2
+    #[serde(rename = "1.2")]
+    Tls12,
+    /// TLS 1.3
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+/// Protocols the client may offer through ALPN
+const ALPN_PROTOCOLS: [&str; 2] = ["http/1.1", "h2"];
+
+fn default_alpn() -> Vec<String> {
+    vec!["http/1.1".to_string()]
+}
+
+/// TLS settings of the HTTP client
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// Lowest TLS version to negotiate, `1.2` or `1.3`; the client's default when unset
+    #[serde(default)]
+    pub min_version: Option<TlsVersion>,
+    /// Protocols offered through ALPN, `http/1.1` and `h2`
+    ///
+    /// HTTP/2 is preferred when both are offered. With `h2` alone, plain
+    /// `http://` endpoints are spoken to in HTTP/2 without negotiation.
+    #[serde(default = "default_alpn")]
+    pub alpn: Vec<String>,
+    /// PEM file with the client certificate chain for endpoints requiring mutual TLS,
+    /// optionally followed by its private key
+    #[serde(default)]
+    pub client_cert: Option<String>,
+    /// PEM file with the private key of `client_cert`, when it is not in that file
+    #[serde(default)]
+    pub client_key: Option<String>,
+    /// Server name sent in SNI and the `Host` header instead of each URL's host,
+    /// while still connecting to the URL's own address
+    #[serde(default)]
+    pub sni_override: Option<String>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            min_version: None,
+            alpn: default_alpn(),
+            client_cert: None,
+            client_key: None,
+            sni_override: None,
+        }
+    }
+}
+
+impl TlsConfig {
+    /// # Errors
+    /// Returns a validation error for an unknown or repeated ALPN protocol, a
+    /// key without a certificate or a server name that is not a host name
+    pub fn validate(&self) -> Result<()> {
+        if self.alpn.is_empty() {
+            return Err(CloudPingError::validation("tls.alpn", "must offer at least one protocol"));
+        }
+        for (index, protocol) in self.alpn.iter().enumerate() {
+            if !ALPN_PROTOCOLS.contains(&protocol.as_str()) {
+                return Err(CloudPingError::validation(
+                    "tls.alpn",
+                    format!("unknown protocol '{protocol}', expected one of {}", ALPN_PROTOCOLS.join(", ")),
+                ));
+            }
+            if self.alpn[..index].contains(protocol) {
+                return Err(CloudPingError::validation("tls.alpn", format!("'{protocol}' is listed twice")));
+            }
+        }
+        if self.client_key.is_some() && self.client_cert.is_none() {
+            return Err(CloudPingError::validation("tls.client_key", "requires tls.client_cert"));
+        }
+        if let Some(name) = &self.sni_override {
+            if url::Host::parse(name).map_or(true, |host| !matches!(host, url::Host::Domain(_))) {
+                return Err(CloudPingError::validation("tls.sni_override", "must be a host name"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Protocols to offer through ALPN, HTTP/2 first
+    #[must_use]
+    pub fn alpn_protocols(&self) -> Vec<Vec<u8>> {
+        ["h2", "http/1.1"]
+            .into_iter()
+            .filter(|protocol| self.alpn.iter().any(|offered| offered == protocol))
+            .map(|protocol| protocol.as_bytes().to_vec())
+            .collect()
+    }
+
+    /// PEM of the client certificate followed by its key, absent without `client_cert`
+    ///
+    /// # Errors
+    /// Returns an error when a file cannot be read
+    pub fn client_identity_pem(&self) -> Result<Option<Vec<u8>>> {
+        let Some(cert) = &self.client_cert else {
+            return Ok(None);
+        };
+        let read = |path: &str| {
+            std::fs::read(path).map_err(|e| {
+                CloudPingError::config(format!("Cannot read client certificate {path}: {e}"))
+            })
+        };
+        let mut pem = read(cert)?;
+        if let Some(key) = &self.client_key {
+            pem.push(b'\n');
+            pem.extend(read(key)?);
+        }
+        Ok(Some(pem))
+    }
+
+    /// Apply the settings to `builder`, timing certificate validations into
+    /// `validations` when they are given
+    ///
+    /// # Errors
+    /// Returns an error when the client certificate or key cannot be read or parsed
+    fn apply(&self, mut builder: ClientBuilder, validations: Option<&CertValidations>) -> Result<ClientBuilder> {
+        let offers = |protocol: &str| self.alpn.iter().any(|offered| offered == protocol);
+        builder = match (offers("http/1.1"), offers("h2")) {
+            (true, false) => builder.http1_only(),
+            (false, true) => builder.http2_prior_knowledge(),
+            _ => builder,
+        };
+        if let Some(validations) = validations {
+            return Ok(builder.use_preconfigured_tls(timed_client_config(self, validations)?));
+        }
+
+        if let Some(version) = self.min_version {
+            builder = builder.min_tls_version(match version {
+                TlsVersion::Tls12 => reqwest::tls::Version::TLS_1_2,
+                TlsVersion::Tls13 => reqwest::tls::Version::TLS_1_3,
+            });
+        }
+        if let Some(pem) = self.client_identity_pem()? {
+            let identity = Identity::from_pem(&pem)
+                .map_err(|e| CloudPingError::config(format!("Invalid client certificate: {e}")))?;
+            builder = builder.identity(identity);
+        }
+        Ok(builder)
+    }
+}
+
+/// Clients that present one server name while connecting to each URL's own host
+#[derive(Debug)]
+struct SniOverride {
+    server_name: String,
+    config: AppConfig,
+    validations: CertValidations,
+    clients: Mutex<HashMap<String, Arc<Client>>>,
+}
+
+impl SniOverride {
+    /// `url` with its host replaced by the server name, and a client that
+    /// connects that name to the addresses of the original host
+    async fn route(&self, url: &str) -> Result<(String, Arc<Client>)> {
+        let mut parsed = Url::parse(url)
+            .map_err(|e| CloudPingError::invalid_url(format!("Invalid URL '{url}': {e}")))?;
+        let authority = format!(
+            "{}:{}",
+            parsed.host_str().unwrap_or_default(),
+            parsed.port_or_known_default().unwrap_or(443)
+        );
+        parsed
+            .set_host(Some(&self.server_name))
+            .map_err(|e| CloudPingError::invalid_url(format!("Invalid server name: {e}")))?;
+
+        let cached = self
+            .clients
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&authority)
+            .cloned();
+        let client = if let Some(client) = cached {
+            client
+        } else {
+            let (_, addresses) = NetworkTester::resolve_addresses(url).await?;
+            let client = Arc::new(
+                ReqwestTransport::client_builder(&self.config, &self.validations)?
+                    .resolve_to_addrs(&self.server_name, &addresses)
+                    .build()?,
+            );
+            self.clients
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(authority, Arc::clone(&client));
+            client
+        };
+        Ok((parsed.into(), client))
+    }
+}
+
+/// Idle connections the client keeps per host, and how many connections per
+/// host the transport remembers to recognize reuse
+const POOL_MAX_IDLE_PER_HOST: usize = 10;
+
+/// Transport backed by a pooled `reqwest` client
+///
+/// A connection is recognized by its local and remote address; a response on
+/// a connection the transport has not seen before is reported as a new one.
+/// With `validate_certificates` on, a transport created with [`Self::new`]
+/// also reports how long validating the server's certificate took.
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    client: Arc<Client>,
+    timeout_ms: u64,
+    user_agent: String,
+    connections: Arc<Mutex<HashMap<SocketAddr, VecDeque<SocketAddr>>>>,
+    sni_override: Option<Arc<SniOverride>>,
+    cert_validations: CertValidations,
+}
+
+impl ReqwestTransport {
+    /// Create a transport with its own client configured from `config`
+    pub fn new(config: &AppConfig) -> Result<Self> {
+        let validations = CertValidations::default();
+        let client = Arc::new(Self::build_http_client(config, &validations)?);
+        Ok(Self::assemble(client, config, validations))
+    }
+
+    /// Create a transport that sends through an existing client
+    ///
+    /// With `tls.sni_override` set, requests go through clients of the
+    /// transport's own that connect the server name to each URL's host.
+    #[must_use]
+    pub fn with_client(client: Arc<Client>, config: &AppConfig) -> Self {
+        Self::assemble(client, config, CertValidations::default())
+    }
+
+    fn assemble(client: Arc<Client>, config: &AppConfig, cert_validations: CertValidations) -> Self {
+        Self {
+            client,
+            timeout_ms: u64::try_from(config.attempt_timeout().as_millis()).unwrap_or(u64::MAX),
+            user_agent: config.user_agent.clone(),
+            connections: Arc::default(),
+            sni_override: config.tls.sni_override.as_ref().map(|server_name| {
+                Arc::new(SniOverride {
+                    server_name: server_name.clone(),
+                    config: config.clone(),
+                    validations: cert_validations.clone(),
+                    clients: Mutex::default(),
+                })
+            }),
+            cert_validations,
+        }
+    }
+
+    /// Client the transport sends through
+    #[must_use]
+    pub const fn client(&self) -> &Arc<Client> {
+        &self.client
+    }
+
+    /// # PERF: Configures connection pooling and TLS for optimal performance
+    fn build_http_client(config: &AppConfig, validations: &CertValidations) -> Result<Client> {
+        Self::client_builder(config, validations)?
+            .build()
+            .map_err(|e| CloudPingError::config(format!("Failed to build HTTP client: {e}")))
+    }
+
+    /// Builder with the pooling and TLS settings of `config`, recording
+    /// certificate validations into `validations` when they are on
+    pub(crate) fn client_builder(config: &AppConfig, validations: &CertValidations) -> Result<ClientBuilder> {
+        let mut builder = ClientBuilder::new()
+            .timeout(config.attempt_timeout())
+            .user_agent(&config.user_agent)
+            .pool_max_idle_per_host(POOL_MAX_IDLE_PER_HOST)
+            .pool_idle_timeout(TimeUtils::duration_from_secs(30))
+            .tcp_keepalive(TimeUtils::duration_from_secs(60));
+
+        if !config.validate_certificates {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        // Use rustls for better performance and security
+        builder = builder.use_rustls_tls();
+
+        config
+            .tls
+            .apply(builder, config.validate_certificates.then_some(validations))
+    }
+
+    async fn send_request(&self, url: &str) -> RequestTiming {
+        let (url, client) = match &self.sni_override {
+            Some(sni_override) => match sni_override.route(url).await {
+                Ok(route) => route,
+                Err(e) => {
+                    error!("Request to {} failed: {}", url, e);
+                    return RequestTiming::failed(std::time::Duration::ZERO, e.to_string());
+                }
+            },
+            None => (url.to_string(), Arc::clone(&self.client)),
+        };
+        let url = url.as_str();
+
+        let start = Instant::now();
+        let bytes_sent = Self::request_bytes(url, &self.user_agent);
+        let timeout_duration = TimeUtils::duration_from_millis(self.timeout_ms);
+
+        match timeout(timeout_duration, client.get(url).send()).await {
+            Ok(Ok(response)) => {
+                let total_time = start.elapsed();
+                let status_code = response.status().as_u16();
+                let success = response.status().is_success()
+                    || response.status().is_redirection()
+                    || status_code == 0; // Some endpoints return 0 for successful pings
+
+                debug!("Request completed with status {status_code} in {total_time:?}");
+
+                // Drain the body after timing so its size counts towards data usage
+                let header_bytes = Self::response_header_bytes(&response);
+                let edge_pop = EdgePop::from_headers(|name| {
+                    response.headers().get(name).and_then(|value| value.to_str().ok())
+                });
+                let connection = self.connection_use(&response);
+                let cert_validation = response
+                    .url()
+                    .host_str()
+                    .and_then(|host| self.cert_validations.take(host));
+                let body_bytes = match timeout(timeout_duration, response.bytes()).await {
+                    Ok(Ok(body)) => u64::try_from(body.len()).unwrap_or(u64::MAX),
+                    _ => 0,
+                };
+
+                let mut timing = if success {
+                    RequestTiming::succeeded(total_time, status_code)
+                } else {
+                    let mut timing =
+                        RequestTiming::failed(total_time, format!("HTTP {status_code}"));
+                    timing.status_code = Some(status_code);
+                    timing.failure = Some(FailureKind::HttpStatus {
+                        status: status_code,
+                    });
+                    timing
+                };
+                timing.bytes_sent = bytes_sent;
+                timing.bytes_received = header_bytes + body_bytes;
+                timing.edge_pop = edge_pop;
+                timing.connection = connection;
+                timing.cert_validation = cert_validation;
+                timing
+            }
+            Ok(Err(e)) => {
+                error!("Request to {} failed: {}", url, e);
+                let mut timing = RequestTiming::failed(start.elapsed(), e.to_string());
+                // The message drops the error's sources, which tell DNS and TLS failures apart
+                timing.failure = Some(classify_request_error(&e));
+                timing.bytes_sent = bytes_sent;
+                timing
+            }
+            Err(_) => {
+                warn!("Request to {} timed out after {:?}", url, timeout_duration);
+                let mut timing = RequestTiming::failed(
+                    timeout_duration,
+                    FormatUtils::format_timeout_message(self.timeout_ms),
+                );
+                timing.status_code = Some(408); // Request Timeout status code
+                timing.bytes_sent = bytes_sent;
+                timing
+            }
+        }
+    }
+
+    /// Whether `response` came on a connection seen before, absent when the
+    /// client did not report the connection's addresses
+    fn connection_use(&self, response: &reqwest::Response) -> Option<ConnectionUse> {
+        let info = response.extensions().get::<HttpInfo>()?;
+        let mut connections = self.connections.lock().unwrap_or_else(PoisonError::into_inner);
+        let seen = connections.entry(info.remote_addr()).or_default();
+        let reused = seen.contains(&info.local_addr());
+        if !reused {
+            if seen.len() == POOL_MAX_IDLE_PER_HOST {
+                seen.pop_front();
+            }
+            seen.push_back(info.local_addr());
+        }
+        drop(connections);
+        Some(if reused { ConnectionUse::Reused } else { ConnectionUse::New })
+    }
+
+    /// Size of the request line and the headers sent with every ping
+    fn request_bytes(url: &str, user_agent: &str) -> u64 {
+        let Ok(parsed) = Url::parse(url) else {
+            return 0;
+        };
+        let path = parsed.query().map_or_else(
+            || parsed.path().to_string(),
+            |query| format!("{}?{query}", parsed.path()),
+        );
+        let host = parsed.host_str().unwrap_or_default();
+
+        let head = format!(
+            "GET {path} HTTP/1.1\r\nhost: {host}\r\nuser-agent: {user_agent}\r\naccept: */*\r\n\r\n"
+        );
+        u64::try_from(head.len()).unwrap_or(u64::MAX)
+    }
+
+    /// Size of the status line and headers of a response
+    fn response_header_bytes(response: &reqwest::Response) -> u64 {
+        // "HTTP/1.1 200 OK\r\n" plus the blank line ending the headers
+        let status_line = 17 + 2;
+        let headers: usize = response
+            .headers()
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len() + 4)
+            .sum();
+        u64::try_from(status_line + headers).unwrap_or(u64::MAX)
+    }
+}
+
+impl HttpTransport for ReqwestTransport {
+    fn send<'a>(&'a self, url: &'a str) -> BoxFuture<'a, RequestTiming> {
+        Box::pin(self.send_request(url))
+    }
+}
+
+/// Failure kind of an HTTP request error, looking through its source chain
+pub(crate) fn classify_request_error(error: &reqwest::Error) -> FailureKind {
+    if error.is_timeout() {
+        return FailureKind::Timeout;
+    }
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    match FailureKind::classify(&message, None) {
+        FailureKind::Other if error.is_connect() => FailureKind::Connection,
+        kind => kind,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tls_config_validate() {
+        let tls = |edit: fn(&mut TlsConfig)| {
+            let mut tls = TlsConfig::default();
+            edit(&mut tls);
+            tls.validate()
+        };
+        assert!(tls(|_| {}).is_ok());
+        assert!(tls(|tls| tls.alpn = vec!["h2".into(), "http/1.1".into()]).is_ok());
+        assert!(tls(|tls| tls.alpn = vec!["h3".into()]).is_err());
+        assert!(tls(|tls| tls.alpn = vec!["h2".into(), "h2".into()]).is_err());
+        assert!(tls(|tls| tls.alpn.clear()).is_err());
+        assert!(tls(|tls| tls.client_key = Some("client.key".into())).is_err());
+        assert!(tls(|tls| tls.sni_override = Some("192.0.2.1".into())).is_err());
+        assert!(tls(|tls| tls.sni_override = Some("api.example.com".into())).is_ok());
+
+        let config: TlsConfig = toml::from_str("min_version = \"1.3\"").unwrap();
+        assert_eq!(config.min_version, Some(TlsVersion::Tls13));
+        assert_eq!(config.alpn, ["http/1.1"]);
+
+        let mut config = AppConfig::default();
+        config.validate_certificates = true;
+        config.tls.alpn = vec!["h2".into(), "http/1.1".into()];
+        assert!(ReqwestTransport::new(&config).is_ok());
+        config.tls.client_cert = Some("/nonexistent/client.pem".into());
+        assert!(ReqwestTransport::new(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sni_override() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = axum::Router::new().route(
+            "/",
+            axum::routing::get(|headers: axum::http::HeaderMap| async move {
+                let host = headers.get("host").and_then(|host| host.to_str().ok());
+                if host.is_some_and(|host| host.starts_with("backend.example:")) {
+                    axum::http::StatusCode::OK
+                } else {
+                    axum::http::StatusCode::MISDIRECTED_REQUEST
+                }
+            }),
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        let url = format!("http://{addr}/");
+
+        let mut config = AppConfig::default();
+        assert!(!ReqwestTransport::new(&config).unwrap().send(&url).await.success);
+        config.tls.sni_override = Some("backend.example".to_string());
+        let transport = ReqwestTransport::new(&config).unwrap();
+        assert!(transport.send(&url).await.success);
+        assert!(transport.send(&url).await.success);
+    }
+}
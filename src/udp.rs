@@ -0,0 +1,276 @@
+//! Checking which UDP ports of each region the current network lets through
+//!
+//! Each check sends a region's host a datagram the expected service must
+//! answer from a connected socket, retrying once as UDP may drop either
+//! direction. QUIC servers answer a packet with a reserved version with a
+//! version negotiation packet (RFC 9000 section 6), STUN and TURN servers
+//! answer a binding request, and for other ports any datagram counts. An
+//! ICMP port unreachable surfaces as a refused receive and shows the port is
+//! closed at the host rather than filtered on the way.
+
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use futures::future::join_all;
+use tokio::net::UdpSocket;
+use tracing::debug;
+
+use crate::error::{CloudPingError, Result};
+use crate::models::{Region, UdpCheck, UdpMatrixRow, UdpPort, UdpProtocol, UdpReachability};
+use crate::network::NetworkTester;
+use crate::stun;
+
+/// Datagrams sent per check before giving up
+const ATTEMPTS: u32 = 2;
+
+/// Version reserved by RFC 9000 to force version negotiation
+const QUIC_RESERVED_VERSION: u32 = 0x1A2A_3A4A;
+
+/// Smallest datagram a QUIC server must answer, as it guards against amplification
+const QUIC_MIN_DATAGRAM: usize = 1200;
+
+/// Connection ID length used in the QUIC probe
+const QUIC_CID_LEN: u8 = 8;
+
+/// Connection ID of the QUIC probe
+type ConnectionId = [u8; QUIC_CID_LEN as usize];
+
+/// Checks UDP ports of regions for answers
+#[derive(Debug, Clone)]
+pub struct UdpReachabilityTest {
+    ports: Vec<UdpPort>,
+    timeout: Duration,
+}
+
+impl UdpReachabilityTest {
+    /// Test checking `ports` of each region, waiting up to `timeout` for each port to answer
+    ///
+    /// # Errors
+    /// Returns a validation error when no port is given or the timeout is zero
+    pub fn new(ports: Vec<UdpPort>, timeout: Duration) -> Result<Self> {
+        if ports.is_empty() {
+            return Err(CloudPingError::validation("ports", "must list at least one port"));
+        }
+        if timeout.is_zero() {
+            return Err(CloudPingError::validation("timeout", "must be greater than 0"));
+        }
+        Ok(Self { ports, timeout })
+    }
+
+    /// Check every port of every region at once
+    pub async fn run(&self, regions: &[Region]) -> Vec<UdpMatrixRow> {
+        join_all(regions.iter().map(|region| self.check_region(region))).await
+    }
+
+    /// Check every port of `host`, such as a STUN server, rather than of a
+    /// region; a port written after the host is ignored for the checked ports
+    pub async fn check_host(&self, host: &str) -> UdpMatrixRow {
+        self.check_url(host, &format!("udp://{host}")).await
+    }
+
+    async fn check_region(&self, region: &Region) -> UdpMatrixRow {
+        self.check_url(&region.name, &region.url).await
+    }
+
+    async fn check_url(&self, name: &str, url: &str) -> UdpMatrixRow {
+        let resolved = NetworkTester::resolve_addresses(url).await;
+        let (host, ip) = match resolved {
+            Ok((host, addresses)) => {
+                let ip = addresses.first().map(SocketAddr::ip);
+                (host, ip)
+            }
+            Err(e) => {
+                debug!("{} did not resolve: {}", name, e);
+                (url.to_string(), None)
+            }
+        };
+        let checks = if let Some(ip) = ip {
+            join_all(self.ports.iter().map(|&port| self.check(ip, port))).await
+        } else {
+            self.ports
+                .iter()
+                .map(|&port| UdpCheck {
+                    port,
+                    reachability: UdpReachability::Unresolved,
+                    rtt_ms: None,
+                })
+                .collect()
+        };
+        UdpMatrixRow {
+            region: name.to_string(),
+            host,
+            checks,
+        }
+    }
+
+    /// Send the probe datagram for `port` to `ip` and wait for its answer
+    async fn check(&self, ip: IpAddr, port: UdpPort) -> UdpCheck {
+        let addr = SocketAddr::new(ip, port.port);
+        let (reachability, rtt_ms) = match exchange(addr, port.protocol, self.timeout).await {
+            Ok(Some(rtt_ms)) => (UdpReachability::Answered, Some(rtt_ms)),
+            Ok(None) => (UdpReachability::NoAnswer, None),
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => (UdpReachability::Closed, None),
+            Err(e) => {
+                debug!("UDP check of {}:{} failed: {}", ip, port, e);
+                (UdpReachability::NoAnswer, None)
+            }
+        };
+        UdpCheck {
+            port,
+            reachability,
+            rtt_ms,
+        }
+    }
+}
+
+/// Round trip in milliseconds of the first valid answer from `addr`, or
+/// `None` when nothing valid arrives within `timeout`
+async fn exchange(addr: SocketAddr, protocol: UdpProtocol, timeout: Duration) -> std::io::Result<Option<f64>> {
+    let bind: SocketAddr = if addr.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(addr).await?;
+
+    let (request, expected) = Probe::new(protocol);
+    let mut buffer = vec![0u8; 2048];
+    for _ in 0..ATTEMPTS {
+        let sent = Instant::now();
+        socket.send(&request).await?;
+        let answer = tokio::time::timeout(timeout / ATTEMPTS, async {
+            loop {
+                let len = socket.recv(&mut buffer).await?;
+                // Answers that do not match the probe are skipped
+                if expected.matches(&buffer[..len]) {
+                    return Ok::<_, std::io::Error>(sent.elapsed().as_secs_f64() * 1000.0);
+                }
+            }
+        })
+        .await;
+        if let Ok(rtt) = answer {
+            return rtt.map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// What a valid answer to a probe datagram looks like
+enum Probe {
+    /// Version negotiation echoing the source connection ID sent
+    Quic(ConnectionId),
+    /// STUN response to the transaction ID sent
+    Stun([u8; 12]),
+    /// Any datagram
+    Raw,
+}
+
+impl Probe {
+    /// Probe datagram for `protocol` and what its answer must look like
+    fn new(protocol: UdpProtocol) -> (Vec<u8>, Self) {
+        match protocol {
+            UdpProtocol::Quic => {
+                let destination: ConnectionId = rand::random();
+                let source: ConnectionId = rand::random();
+                (quic_packet(destination, source), Self::Quic(source))
+            }
+            UdpProtocol::Stun => {
+                let transaction_id: [u8; 12] = rand::random();
+                (stun::binding_request(transaction_id).to_vec(), Self::Stun(transaction_id))
+            }
+            UdpProtocol::Raw => (b"cloud-ping\n".to_vec(), Self::Raw),
+        }
+    }
+
+    fn matches(&self, packet: &[u8]) -> bool {
+        match self {
+            Self::Quic(source) => is_version_negotiation(packet, *source),
+            Self::Stun(transaction_id) => stun::is_response(packet, *transaction_id),
+            Self::Raw => true,
+        }
+    }
+}
+
+/// Long header QUIC packet with a reserved version, padded to the size servers must answer
+fn quic_packet(destination: ConnectionId, source: ConnectionId) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(QUIC_MIN_DATAGRAM);
+    // Long header with the fixed bit set
+    packet.push(0xC0);
+    packet.extend_from_slice(&QUIC_RESERVED_VERSION.to_be_bytes());
+    for cid in [destination, source] {
+        packet.push(QUIC_CID_LEN);
+        packet.extend_from_slice(&cid);
+    }
+    packet.resize(QUIC_MIN_DATAGRAM, 0);
+    packet
+}
+
+/// Whether `packet` is a version negotiation sent back to the connection ID `source`
+fn is_version_negotiation(packet: &[u8], source: ConnectionId) -> bool {
+    packet.len() > 6 + source.len()
+        && packet[0] & 0x80 != 0
+        && packet[1..5] == [0; 4]
+        && packet[5] == QUIC_CID_LEN
+        && packet[6..6 + source.len()] == source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answer QUIC-sized datagrams with a version negotiation packet, as a QUIC server would
+    async fn quic_server() -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut packet = [0u8; QUIC_MIN_DATAGRAM];
+            while let Ok((len, client)) = socket.recv_from(&mut packet).await {
+                if len < QUIC_MIN_DATAGRAM {
+                    continue;
+                }
+                let destination = &packet[6..14];
+                let source = &packet[15..23];
+                let mut answer = vec![0x80, 0, 0, 0, 0, 8];
+                answer.extend_from_slice(source);
+                answer.push(8);
+                answer.extend_from_slice(destination);
+                answer.extend_from_slice(&1u32.to_be_bytes());
+                socket.send_to(&answer, client).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_version_negotiation() {
+        let packet = quic_packet([1; 8], [2; 8]);
+        assert_eq!(packet.len(), QUIC_MIN_DATAGRAM);
+        assert_eq!(packet[1..5], QUIC_RESERVED_VERSION.to_be_bytes());
+
+        let mut answer = vec![0x80, 0, 0, 0, 0, 8];
+        answer.extend_from_slice(&[2; 8]);
+        answer.extend_from_slice(&[8, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 1]);
+        assert!(is_version_negotiation(&answer, [2; 8]));
+        assert!(!is_version_negotiation(&answer, [3; 8]));
+        // Our own packet echoed back is not an answer
+        assert!(!is_version_negotiation(&packet, [2; 8]));
+    }
+
+    #[tokio::test]
+    async fn test_exchange() {
+        let timeout = Duration::from_millis(400);
+        let rtt = exchange(quic_server().await, UdpProtocol::Quic, timeout).await.unwrap();
+        assert!(rtt.is_some());
+
+        // A server that stays silent to anything but QUIC gets no answer for STUN
+        let rtt = exchange(quic_server().await, UdpProtocol::Stun, timeout).await.unwrap();
+        assert_eq!(rtt, None);
+
+        // Nothing listens on a port just released, so localhost reports it closed
+        let closed = UdpSocket::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        let error = exchange(closed, UdpProtocol::Raw, timeout).await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ConnectionRefused);
+    }
+}
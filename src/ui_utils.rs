@@ -1,6 +1,49 @@
 //! UI utilities for progress bars and display formatting
+//!
+//! Terminal output uses emoji, rounded box-drawing tables and block progress
+//! bars by default. [`DisplayUtils::set_ascii_only`] switches all of them to
+//! plain ASCII for terminals and log collectors that mangle Unicode.
+//!
+//! Tables printed to a terminal are fitted to its width: columns are dropped
+//! in priority order, then the widest remaining columns are truncated.
+//! [`TableLayout::wide`] keeps every column at full width.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
 
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use tabled::settings::location::ByColumnName;
+use tabled::settings::peaker::PriorityMax;
+use tabled::settings::{format::Format, object::Rows, Disable, Modify, Style, Width};
+use tabled::Table;
+
+use crate::models::{AlertSeverity, HealthStatus};
+
+/// Whether terminal output is restricted to ASCII, set once at startup
+static ASCII_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// How tables adapt to the terminal width, set once at startup
+static TABLE_LAYOUT: RwLock<TableLayout> = RwLock::new(TableLayout::new());
+
+/// How tables adapt to narrow terminals
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableLayout {
+    /// Print every column at full width, whatever the terminal width
+    pub wide: bool,
+    /// Column headers dropped first, before each table's own drop order
+    pub drop_order: Vec<String>,
+}
+
+impl TableLayout {
+    /// Fit tables to the terminal using each table's own drop order
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            wide: false,
+            drop_order: Vec::new(),
+        }
+    }
+}
 
 /// Utility for creating and managing progress bars
 pub struct ProgressBarFactory {
@@ -19,8 +62,12 @@ impl ProgressBarFactory {
         
         let style = ProgressStyle::default_bar()
             .template("{spinner:.green} {msg} [{bar:30.cyan/blue}] {pos}/{len} ({eta}) {per_sec}")
-            .unwrap()
-            .progress_chars("█▉▊▋▌▍▎▏  ");
+            .unwrap();
+        let style = if DisplayUtils::ascii_only() {
+            style.progress_chars("#>-").tick_chars("|/-\\ ")
+        } else {
+            style.progress_chars("█▉▊▋▌▍▎▏  ")
+        };
         
         pb.set_style(style);
         pb.set_message(format!("Testing {}", Self::truncate_text(label, 30)));
@@ -49,6 +96,40 @@ impl ProgressBarFactory {
 pub struct DisplayUtils;
 
 impl DisplayUtils {
+    /// Restrict terminal output to ASCII from now on
+    pub fn set_ascii_only(ascii_only: bool) {
+        ASCII_ONLY.store(ascii_only, Ordering::Relaxed);
+    }
+
+    /// Whether terminal output is restricted to ASCII
+    #[must_use]
+    pub fn ascii_only() -> bool {
+        ASCII_ONLY.load(Ordering::Relaxed)
+    }
+
+    /// `unicode` normally, `ascii` in ASCII-only mode
+    #[must_use]
+    pub fn symbol(unicode: &'static str, ascii: &'static str) -> &'static str {
+        if Self::ascii_only() {
+            ascii
+        } else {
+            unicode
+        }
+    }
+
+    /// Apply the table border style, replacing symbols in the header in ASCII-only mode
+    pub fn style_table(table: &mut Table) -> &mut Table {
+        if Self::ascii_only() {
+            table
+                .with(Style::ascii())
+                .with(Modify::new(Rows::first()).with(Format::content(|header| {
+                    header.replace('≤', "<=").replace('≥', ">=").replace('×', "x")
+                })))
+        } else {
+            table.with(Style::rounded())
+        }
+    }
+
     /// Format a region name for display with consistent truncation
     pub fn format_region_name(name: &str, max_len: usize) -> String {
         ProgressBarFactory::truncate_text(name, max_len)
@@ -78,4 +159,106 @@ impl DisplayUtils {
     pub fn format_score(value: f64) -> String {
         format!("{:.1}", value)
     }
+
+    /// One character per value, scaled from the lowest to the highest
+    ///
+    /// Empty for fewer than two values, since a single point shows no trend.
+    #[must_use]
+    pub fn sparkline(values: &[f64]) -> String {
+        const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+        const ASCII: [char; 8] = ['_', '.', ',', '-', '=', '+', '*', '#'];
+
+        if values.len() < 2 {
+            return String::new();
+        }
+        let levels = if Self::ascii_only() { &ASCII } else { &BLOCKS };
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let top = 7.0;
+        values
+            .iter()
+            .map(|value| {
+                let level = if max > min {
+                    ((value - min) / (max - min) * top).round()
+                } else {
+                    top / 2.0
+                };
+                // `level` is a whole number from 0 to 7
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let level = level as usize;
+                levels[level]
+            })
+            .collect()
+    }
+
+    /// Format a health status with its colored marker
+    #[must_use]
+    pub fn format_health(status: HealthStatus) -> String {
+        format!(
+            "{} {}",
+            Self::symbol(status.emoji(), status.ascii_marker()),
+            status
+        )
+    }
+
+    /// Set how tables adapt to the terminal width from now on
+    pub fn set_table_layout(layout: TableLayout) {
+        *TABLE_LAYOUT.write().unwrap_or_else(std::sync::PoisonError::into_inner) = layout;
+    }
+
+    /// Width of the terminal on stdout, or `None` when output is not a terminal
+    #[must_use]
+    pub fn terminal_width() -> Option<usize> {
+        console::Term::stdout()
+            .size_checked()
+            .map(|(_, columns)| usize::from(columns))
+    }
+
+    /// Fit `table` to the terminal, dropping columns named in `drop_order` first
+    ///
+    /// Columns in the configured [`TableLayout::drop_order`] go before the
+    /// table's own `drop_order`. Nothing changes in wide mode or when stdout
+    /// is not a terminal.
+    pub fn fit_table<'a>(table: &'a mut Table, drop_order: &[&str]) -> &'a mut Table {
+        let layout = TABLE_LAYOUT
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone();
+        match Self::terminal_width() {
+            Some(width) if !layout.wide => {
+                let order: Vec<&str> = layout
+                    .drop_order
+                    .iter()
+                    .map(String::as_str)
+                    .chain(drop_order.iter().copied())
+                    .collect();
+                Self::fit_table_to(table, width, &order)
+            }
+            _ => table,
+        }
+    }
+
+    /// Drop columns in `drop_order`, then truncate the widest, until `table` fits in `width`
+    pub fn fit_table_to<'a>(table: &'a mut Table, width: usize, drop_order: &[&str]) -> &'a mut Table {
+        for column in drop_order {
+            if table.total_width() <= width {
+                return table;
+            }
+            table.with(Disable::column(ByColumnName::new(*column)));
+        }
+        if table.total_width() > width {
+            table.with(
+                Width::truncate(width)
+                    .priority::<PriorityMax>()
+                    .suffix(Self::symbol("…", "~")),
+            );
+        }
+        table
+    }
+
+    /// Marker of an alert severity
+    #[must_use]
+    pub fn severity_marker(severity: AlertSeverity) -> &'static str {
+        Self::symbol(severity.emoji(), severity.ascii_marker())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,288 @@
+//! Write-ahead log of records waiting for a sink or store
+//!
+//! Probe records a sink could not take, and test history the history store
+//! could not save, are appended to a local file and replayed once the
+//! destination is reachable again, so an outage or a restart in the middle
+//! of one loses nothing. Each line holds the CRC-32 of a record's JSON in
+//! hex, a space and the JSON itself. A line torn by a crash is cut off when
+//! the log is opened, so later appends start on a fresh line; a line damaged
+//! in any other way fails its checksum and is skipped on reading instead of
+//! losing the records around it. The file is kept under `max_bytes` by
+//! dropping the oldest records when an append would overflow it.
+
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+
+use crate::error::Result;
+use crate::models::ProbeRecord;
+
+/// Share of `max_bytes` a full log is cut down to, so it is not rewritten
+/// on every append once it is full
+const TRIM_PERCENT: u64 = 75;
+
+/// Records read back from a log
+#[derive(Debug, Clone)]
+pub struct WalContents<T> {
+    /// Intact records, oldest first
+    pub records: Vec<T>,
+    /// Lines skipped for a bad checksum or unreadable record
+    pub skipped: usize,
+}
+
+impl<T> Default for WalContents<T> {
+    fn default() -> Self {
+        Self {
+            records: Vec::new(),
+            skipped: 0,
+        }
+    }
+}
+
+/// Append-only log of records bounded to `max_bytes`
+#[derive(Debug)]
+pub struct Wal<T> {
+    path: PathBuf,
+    max_bytes: u64,
+    len: u64,
+    records: PhantomData<fn() -> T>,
+}
+
+/// Log of probe records a sink has not taken yet
+pub type ProbeWal = Wal<ProbeRecord>;
+
+impl<T: Clone + Send + Sync + Serialize + DeserializeOwned> Wal<T> {
+    /// Open the log at `path`, creating its directory; an existing log is
+    /// kept, less any last line a crash tore off before its newline
+    ///
+    /// # Errors
+    /// Returns an error if the directory cannot be created or the log not read
+    pub async fn open(path: impl Into<PathBuf>, max_bytes: u64) -> Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let bytes = match tokio::fs::read(&path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut len = bytes.len() as u64;
+        if bytes.last().is_some_and(|&byte| byte != b'\n') {
+            // Otherwise the next append would be glued onto the torn line
+            len = bytes.iter().rposition(|&byte| byte == b'\n').map_or(0, |end| end as u64 + 1);
+            let file = tokio::fs::OpenOptions::new().write(true).open(&path).await?;
+            file.set_len(len).await?;
+            file.sync_all().await?;
+        }
+        Ok(Self {
+            path,
+            max_bytes,
+            len,
+            records: PhantomData,
+        })
+    }
+
+    /// File the log is kept in
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Size of the log in bytes
+    #[must_use]
+    pub const fn len_bytes(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the log holds nothing to replay
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Append `records`, dropping the oldest records when the log would
+    /// grow beyond `max_bytes`
+    ///
+    /// Returns the number of records dropped to make room. The records are
+    /// on disk once this returns.
+    ///
+    /// # Errors
+    /// Returns an error if a record cannot be encoded or the log not written
+    pub async fn append(&mut self, records: &[T]) -> Result<usize> {
+        let lines = encode(records)?;
+        let size: u64 = lines.iter().map(|line| line.len() as u64).sum();
+        if self.len + size <= self.max_bytes {
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await?;
+            file.write_all(lines.concat().as_bytes()).await?;
+            file.sync_data().await?;
+            self.len += size;
+            return Ok(0);
+        }
+
+        // Keep the newest records that fit in the trimmed size
+        let mut contents = self.read().await?;
+        let kept_before = contents.records.len() + records.len();
+        contents.records.extend_from_slice(records);
+        let mut all = encode(&contents.records)?;
+        let budget = self.max_bytes / 100 * TRIM_PERCENT;
+        let mut kept = 0;
+        let mut total = 0;
+        for line in all.iter().rev() {
+            if total + line.len() as u64 > budget {
+                break;
+            }
+            total += line.len() as u64;
+            kept += 1;
+        }
+        let lines = all.split_off(all.len() - kept);
+        self.write_lines(&lines).await?;
+        Ok(kept_before - kept)
+    }
+
+    /// Read every intact record back, skipping damaged lines
+    ///
+    /// # Errors
+    /// Returns an error if the log exists but cannot be read
+    pub async fn read(&self) -> Result<WalContents<T>> {
+        let bytes = match tokio::fs::read(&self.path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(WalContents::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut contents = WalContents::default();
+        for line in bytes.split(|&byte| byte == b'\n').filter(|line| !line.is_empty()) {
+            match decode(line) {
+                Some(record) => contents.records.push(record),
+                None => contents.skipped += 1,
+            }
+        }
+        Ok(contents)
+    }
+
+    /// Replace the log with `records`, as after a partial replay
+    ///
+    /// # Errors
+    /// Returns an error if a record cannot be encoded or the log not written
+    pub async fn rewrite(&mut self, records: &[T]) -> Result<()> {
+        let lines = encode(records)?;
+        self.write_lines(&lines).await
+    }
+
+    /// Empty the log once its records have been replayed
+    ///
+    /// # Errors
+    /// Returns an error if the log cannot be removed
+    pub async fn clear(&mut self) -> Result<()> {
+        match tokio::fs::remove_file(&self.path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        self.len = 0;
+        Ok(())
+    }
+
+    /// Write `lines` to a temporary file and move it over the log, so a
+    /// crash leaves either the old log or the new one
+    async fn write_lines(&mut self, lines: &[String]) -> Result<()> {
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        let mut file = tokio::fs::File::create(&temporary).await?;
+        file.write_all(lines.concat().as_bytes()).await?;
+        file.sync_all().await?;
+        drop(file);
+        tokio::fs::rename(&temporary, &self.path).await?;
+        self.len = lines.iter().map(|line| line.len() as u64).sum();
+        Ok(())
+    }
+}
+
+/// One checksummed line per record
+fn encode<T: Serialize>(records: &[T]) -> Result<Vec<String>> {
+    records
+        .iter()
+        .map(|record| {
+            let json = serde_json::to_string(record)?;
+            Ok(format!("{:08x} {json}\n", crc32fast::hash(json.as_bytes())))
+        })
+        .collect()
+}
+
+/// Record of an intact line
+fn decode<T: DeserializeOwned>(line: &[u8]) -> Option<T> {
+    let line = std::str::from_utf8(line).ok()?;
+    let (checksum, json) = line.split_once(' ')?;
+    let checksum = u32::from_str_radix(checksum, 16).ok()?;
+    if crc32fast::hash(json.as_bytes()) != checksum {
+        return None;
+    }
+    serde_json::from_str(json).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(count: usize) -> Vec<ProbeRecord> {
+        (0..count)
+            .map(|i| ProbeRecord::success(format!("endpoint-{i}"), 10.0))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_wal_skips_damaged_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wal").join("probes.log");
+        let mut wal = ProbeWal::open(&path, 1 << 20).await.unwrap();
+        assert!(wal.is_empty());
+        wal.append(&records(3)).await.unwrap();
+
+        // Flip a byte of the second record and tear off the end of a fourth
+        let mut bytes = std::fs::read(&path).unwrap();
+        let second = bytes.iter().position(|&byte| byte == b'\n').unwrap() + 20;
+        bytes[second] ^= 0x20;
+        let torn = encode(&records(1)).unwrap().concat();
+        bytes.extend_from_slice(&torn.as_bytes()[..torn.len() / 2]);
+        std::fs::write(&path, bytes).unwrap();
+
+        // Reopening cuts off the torn tail, so a record appended after it reads back
+        let mut wal = ProbeWal::open(&path, 1 << 20).await.unwrap();
+        assert_eq!(wal.len_bytes(), std::fs::metadata(&path).unwrap().len());
+        wal.append(&[ProbeRecord::success("endpoint-new".to_string(), 10.0)])
+            .await
+            .unwrap();
+        let contents = wal.read().await.unwrap();
+        let ids: Vec<&str> = contents.records.iter().map(|r| r.endpoint_id.as_str()).collect();
+        assert_eq!(ids, ["endpoint-0", "endpoint-2", "endpoint-new"]);
+        assert_eq!(contents.skipped, 1);
+    }
+
+    #[tokio::test]
+    async fn test_wal_drops_oldest_at_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        let line = encode(&records(1)).unwrap()[0].len() as u64;
+        let mut wal = ProbeWal::open(dir.path().join("probes.log"), line * 10).await.unwrap();
+
+        assert_eq!(wal.append(&records(8)).await.unwrap(), 0);
+        let dropped = wal.append(&records(4)).await.unwrap();
+        assert!(dropped > 0);
+        assert!(wal.len_bytes() <= line * 10);
+
+        let contents = wal.read().await.unwrap();
+        assert_eq!(contents.records.len(), 12 - dropped);
+        assert_eq!(contents.records.last().unwrap().endpoint_id, "endpoint-3");
+
+        wal.clear().await.unwrap();
+        assert!(wal.is_empty());
+        assert!(wal.read().await.unwrap().records.is_empty());
+    }
+}
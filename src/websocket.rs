@@ -0,0 +1,272 @@
+//! WebSocket handshakes and ping/pong round trips
+//!
+//! Games, trading and collaboration tools keep a WebSocket open and
+//! exchange small messages over it, so for them the time to open the socket
+//! and the round trip of a message on it matter more than an HTTP request.
+//! A probe opens a WebSocket with the HTTP/1.1 upgrade handshake of RFC 6455,
+//! checks the server's `Sec-WebSocket-Accept`, and optionally times a ping
+//! frame until the matching pong arrives. The socket is then closed.
+
+use std::time::Instant;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use rand::Rng;
+use reqwest::header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE};
+use reqwest::{Client, StatusCode};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::debug;
+
+use crate::config::AppConfig;
+use crate::error::{CloudPingError, Result};
+use crate::models::FailureKind;
+use crate::tls::CertValidations;
+use crate::transport::{classify_request_error, ReqwestTransport};
+
+/// GUID appended to the key to derive `Sec-WebSocket-Accept`, from RFC 6455
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest frame payload read while waiting for the pong
+const MAX_FRAME_LEN: u64 = 1 << 20;
+
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// Timings of one WebSocket probe
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WebSocketTiming {
+    /// Time to connect and complete the upgrade handshake in milliseconds
+    pub handshake_ms: f64,
+    /// Time from sending a ping to receiving its pong in milliseconds, when one was sent
+    pub ping_rtt_ms: Option<f64>,
+}
+
+/// HTTP client for WebSocket probes with the TLS, certificate and user agent
+/// settings of `config`, speaking HTTP/1.1 to upgrade
+///
+/// # Errors
+/// Returns an error when the client certificate cannot be read or the client cannot be built
+pub fn client(config: &AppConfig) -> Result<Client> {
+    ReqwestTransport::client_builder(config, &CertValidations::default())?
+        .http1_only()
+        .build()
+        .map_err(|e| CloudPingError::network(format!("Failed to build WebSocket client: {e}")))
+}
+
+/// `Sec-WebSocket-Accept` a server must answer `key` with
+#[must_use]
+pub fn accept_key(key: &str) -> String {
+    let digest = ring::digest::digest(
+        &ring::digest::SHA1_FOR_LEGACY_USE_ONLY,
+        format!("{key}{ACCEPT_GUID}").as_bytes(),
+    );
+    STANDARD.encode(digest.as_ref())
+}
+
+/// Open a WebSocket to `url` (`ws://` or `wss://`), time a ping when `ping` is set, and close it
+///
+/// # Errors
+/// Returns the kind of failure when the handshake is refused or fails, or
+/// the socket closes before the pong
+pub async fn probe(client: &Client, url: &str, ping: bool) -> std::result::Result<WebSocketTiming, FailureKind> {
+    let http_url = match url.split_once("://") {
+        Some(("ws", rest)) => format!("http://{rest}"),
+        Some(("wss", rest)) => format!("https://{rest}"),
+        _ => url.to_string(),
+    };
+    let key = STANDARD.encode(rand::thread_rng().gen::<[u8; 16]>());
+
+    let start = Instant::now();
+    let response = client
+        .get(&http_url)
+        .header(CONNECTION, "Upgrade")
+        .header(UPGRADE, "websocket")
+        .header(SEC_WEBSOCKET_VERSION, "13")
+        .header(SEC_WEBSOCKET_KEY, &key)
+        .send()
+        .await
+        .map_err(|e| {
+            debug!("WebSocket handshake with {} failed: {}", url, e);
+            classify_request_error(&e)
+        })?;
+    if response.status() != StatusCode::SWITCHING_PROTOCOLS {
+        debug!("{} answered the WebSocket upgrade with {}", url, response.status());
+        return Err(FailureKind::HttpStatus {
+            status: response.status().as_u16(),
+        });
+    }
+    let accepted = response
+        .headers()
+        .get(SEC_WEBSOCKET_ACCEPT)
+        .is_some_and(|accept| accept.as_bytes() == accept_key(&key).as_bytes());
+    if !accepted {
+        debug!("{} answered with a wrong Sec-WebSocket-Accept", url);
+        return Err(FailureKind::Protocol);
+    }
+    let mut socket = response.upgrade().await.map_err(|e| {
+        debug!("WebSocket upgrade with {} failed: {}", url, e);
+        FailureKind::Connection
+    })?;
+    let handshake_ms = elapsed_ms(start);
+
+    let ping_rtt_ms = if ping {
+        let payload: [u8; 8] = rand::thread_rng().gen();
+        let sent = Instant::now();
+        write_frame(&mut socket, OPCODE_PING, &payload)
+            .await
+            .map_err(|_| FailureKind::Connection)?;
+        wait_for_pong(&mut socket, &payload).await.map_err(|e| {
+            debug!("No pong from {}: {}", url, e);
+            FailureKind::Connection
+        })?;
+        Some(elapsed_ms(sent))
+    } else {
+        None
+    };
+
+    // Close politely; the timings are taken either way
+    let _ = write_frame(&mut socket, OPCODE_CLOSE, &1000u16.to_be_bytes()).await;
+    let _ = socket.shutdown().await;
+    Ok(WebSocketTiming {
+        handshake_ms,
+        ping_rtt_ms,
+    })
+}
+
+/// Write a final client frame, masked as RFC 6455 requires; `payload` is at most 125 bytes
+async fn write_frame<S: AsyncWrite + Unpin>(socket: &mut S, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mask: [u8; 4] = rand::thread_rng().gen();
+    let mut frame = Vec::with_capacity(6 + payload.len());
+    frame.push(0x80 | opcode);
+    // Control frame payloads are at most 125 bytes
+    #[allow(clippy::cast_possible_truncation)]
+    frame.push(0x80 | payload.len().min(125) as u8);
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().zip(mask.iter().cycle()).map(|(byte, mask)| byte ^ mask));
+    socket.write_all(&frame).await?;
+    socket.flush().await
+}
+
+/// Read frames until the pong echoing `payload`, skipping any others
+async fn wait_for_pong<S: AsyncRead + Unpin>(socket: &mut S, payload: &[u8]) -> std::io::Result<()> {
+    loop {
+        let mut header = [0u8; 2];
+        socket.read_exact(&mut header).await?;
+        let opcode = header[0] & 0x0F;
+        let masked = header[1] & 0x80 != 0;
+        let len = match header[1] & 0x7F {
+            126 => u64::from(socket.read_u16().await?),
+            127 => socket.read_u64().await?,
+            len => u64::from(len),
+        };
+        if len > MAX_FRAME_LEN {
+            return Err(std::io::Error::other(format!("{len} byte frame is too large")));
+        }
+        let mut mask = [0u8; 4];
+        if masked {
+            socket.read_exact(&mut mask).await?;
+        }
+        let mut body = vec![0u8; usize::try_from(len).unwrap_or(usize::MAX)];
+        socket.read_exact(&mut body).await?;
+        if masked {
+            body.iter_mut().zip(mask.iter().cycle()).for_each(|(byte, mask)| *byte ^= mask);
+        }
+
+        match opcode {
+            OPCODE_PONG if body == payload => return Ok(()),
+            OPCODE_CLOSE => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionAborted,
+                    "server closed the WebSocket",
+                ))
+            }
+            // Skip messages the server pushes, its own pings and stale pongs
+            _ => {}
+        }
+    }
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::AsyncBufReadExt;
+
+    /// Accept one WebSocket, push a text message, then answer pings with pongs
+    async fn echo_server(accept: bool) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = tokio::io::BufReader::new(stream);
+            let mut key = String::new();
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).await.unwrap();
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("sec-websocket-key") {
+                        key = value.trim().to_string();
+                    }
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let accept = if accept { accept_key(&key) } else { "wrong".to_string() };
+            let response = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n"
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.write_all(&[0x81, 2, b'h', b'i']).await.unwrap();
+
+            let mut header = [0u8; 2];
+            while stream.read_exact(&mut header).await.is_ok() {
+                let len = usize::from(header[1] & 0x7F);
+                let mut mask = [0u8; 4];
+                stream.read_exact(&mut mask).await.unwrap();
+                let mut body = vec![0u8; len];
+                stream.read_exact(&mut body).await.unwrap();
+                body.iter_mut().zip(mask.iter().cycle()).for_each(|(byte, mask)| *byte ^= mask);
+                if header[0] & 0x0F == OPCODE_PING {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                    let mut pong = vec![0x80 | OPCODE_PONG, header[1] & 0x7F];
+                    pong.extend(body);
+                    stream.write_all(&pong).await.unwrap();
+                }
+            }
+        });
+        format!("ws://{addr}/socket")
+    }
+
+    #[test]
+    fn test_accept_key() {
+        // Example handshake from RFC 6455 section 1.3
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[tokio::test]
+    async fn test_probe_times_handshake_and_ping() {
+        let client = client(&AppConfig::default()).unwrap();
+        let timing = probe(&client, &echo_server(true).await, true).await.unwrap();
+        assert!(timing.handshake_ms > 0.0);
+        assert!(timing.ping_rtt_ms.unwrap() >= 5.0);
+
+        let timing = probe(&client, &echo_server(true).await, false).await.unwrap();
+        assert_eq!(timing.ping_rtt_ms, None);
+
+        assert_eq!(
+            probe(&client, &echo_server(false).await, true).await,
+            Err(FailureKind::Protocol)
+        );
+
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/socket", closed.local_addr().unwrap());
+        drop(closed);
+        assert_eq!(probe(&client, &url, true).await, Err(FailureKind::Connection));
+    }
+}